@@ -0,0 +1,8 @@
+fn main() {
+    prost_build::Config::new()
+        .embed_file_descriptor_set()
+        .enable_type_names()
+        .btree_map(&["."])
+        .compile_protos(&["proto/gateway/v1/orders.proto"], &["proto"])
+        .unwrap();
+}
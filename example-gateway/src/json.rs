@@ -0,0 +1,296 @@
+//! Transcoding between the Protobuf JSON mapping and dynamic messages.
+//!
+//! The gateway does not know the Rust types of the messages it forwards, so JSON is converted
+//! using only the message descriptors. `google.protobuf.Any` and `google.protobuf.FieldMask` use
+//! their special JSON representations.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost::Message;
+use prost_types::dynamic::{MapKey, Value};
+use prost_types::reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use prost_types::serde::vec_u8;
+use prost_types::DynamicMessage;
+use serde_json::{Map, Number, Value as Json};
+
+const ANY: &str = "google.protobuf.Any";
+const FIELD_MASK: &str = "google.protobuf.FieldMask";
+
+/// Converts a dynamic message to JSON.
+///
+/// Messages packed in an `Any` are looked up in the descriptor pool of `message`.
+pub fn to_json(message: &DynamicMessage) -> Result<Json> {
+    match message.descriptor().full_name() {
+        ANY => any_to_json(message),
+        FIELD_MASK => Ok(Json::String(
+            field_mask_paths(message)
+                .iter()
+                .map(|path| snake_to_camel(path))
+                .collect::<Vec<_>>()
+                .join(","),
+        )),
+        _ => {
+            let mut object = Map::new();
+            for field in message.descriptor().fields() {
+                if message.has_field(&field) {
+                    let value = message.get_field(&field);
+                    object.insert(
+                        field.json_name().to_string(),
+                        field_to_json(&field, &value)?,
+                    );
+                }
+            }
+            Ok(Json::Object(object))
+        }
+    }
+}
+
+/// Converts JSON to a dynamic message of the given type.
+pub fn from_json(desc: &MessageDescriptor, json: &Json) -> Result<DynamicMessage> {
+    let mut message = DynamicMessage::new(desc.clone());
+    match desc.full_name() {
+        ANY => {
+            let object = json.as_object().context("expected an object for Any")?;
+            let type_url = object
+                .get("@type")
+                .and_then(Json::as_str)
+                .context("Any is missing @type")?;
+            let packed_desc = find_type(desc, type_url)?;
+            let mut fields = object.clone();
+            fields.remove("@type");
+            let packed = from_json(&packed_desc, &Json::Object(fields))?;
+            message.set_field_by_name("type_url", Value::String(type_url.to_string()));
+            message.set_field_by_name("value", Value::Bytes(packed.encode_to_vec().into()));
+        }
+        FIELD_MASK => {
+            let paths = json.as_str().context("expected a string for FieldMask")?;
+            let paths = paths
+                .split(',')
+                .filter(|path| !path.is_empty())
+                .map(|path| Value::String(camel_to_snake(path)))
+                .collect();
+            message.set_field_by_name("paths", Value::List(paths));
+        }
+        _ => {
+            let object = json.as_object().context("expected an object")?;
+            for (name, value) in object {
+                let field = desc
+                    .get_field_by_json_name(name)
+                    .or_else(|| desc.get_field_by_name(name))
+                    .ok_or_else(|| anyhow!("unknown field {} in {}", name, desc.full_name()))?;
+                if value.is_null() {
+                    continue;
+                }
+                let value = field_from_json(&field, value)
+                    .with_context(|| format!("invalid value for field {}", name))?;
+                message.set_field(&field, value);
+            }
+        }
+    }
+    Ok(message)
+}
+
+fn field_to_json(field: &FieldDescriptor, value: &Value) -> Result<Json> {
+    let kind = field.kind();
+    match value {
+        Value::List(values) => values
+            .iter()
+            .map(|value| value_to_json(&kind, value))
+            .collect::<Result<_>>()
+            .map(Json::Array),
+        Value::Map(map) => {
+            let entry = kind.as_message().expect("map field is not a message");
+            let value_kind = entry.map_entry_value_field().kind();
+            map.iter()
+                .map(|(key, value)| Ok((key.to_string(), value_to_json(&value_kind, value)?)))
+                .collect::<Result<_>>()
+                .map(Json::Object)
+        }
+        value => value_to_json(&kind, value),
+    }
+}
+
+fn value_to_json(kind: &Kind, value: &Value) -> Result<Json> {
+    Ok(match value {
+        Value::Bool(value) => Json::Bool(*value),
+        Value::I32(value) => Json::from(*value),
+        Value::U32(value) => Json::from(*value),
+        // 64-bit integers are represented as strings, since JSON numbers are usually doubles.
+        Value::I64(value) => Json::String(value.to_string()),
+        Value::U64(value) => Json::String(value.to_string()),
+        Value::F32(value) => float_to_json(f64::from(*value)),
+        Value::F64(value) => float_to_json(*value),
+        Value::String(value) => Json::String(value.clone()),
+        Value::Bytes(value) => Json::String(vec_u8::Base64(value).to_string()),
+        Value::EnumNumber(number) => {
+            let desc = kind.as_enum().expect("enum value for non-enum field");
+            match desc.get_value(*number) {
+                Some(name) => Json::String(name.to_string()),
+                None => Json::from(*number),
+            }
+        }
+        Value::Message(message) => to_json(message)?,
+        Value::List(_) | Value::Map(_) => bail!("nested lists and maps are not supported"),
+    })
+}
+
+fn float_to_json(value: f64) -> Json {
+    match Number::from_f64(value) {
+        Some(number) => Json::Number(number),
+        None if value.is_nan() => Json::String("NaN".to_string()),
+        None if value > 0.0 => Json::String("Infinity".to_string()),
+        None => Json::String("-Infinity".to_string()),
+    }
+}
+
+fn field_from_json(field: &FieldDescriptor, json: &Json) -> Result<Value> {
+    let kind = field.kind();
+    if field.is_map() {
+        let entry = kind.as_message().expect("map field is not a message");
+        let key_kind = entry.map_entry_key_field().kind();
+        let value_kind = entry.map_entry_value_field().kind();
+        let object = json.as_object().context("expected an object")?;
+        let mut map = BTreeMap::new();
+        for (key, value) in object {
+            let key = value_from_json(&key_kind, &Json::String(key.clone()))?;
+            let key = MapKey::from_value(key).context("invalid map key")?;
+            map.insert(key, value_from_json(&value_kind, value)?);
+        }
+        Ok(Value::Map(map))
+    } else if field.is_list() {
+        let array = json.as_array().context("expected an array")?;
+        array
+            .iter()
+            .map(|value| value_from_json(&kind, value))
+            .collect::<Result<_>>()
+            .map(Value::List)
+    } else {
+        value_from_json(&kind, json)
+    }
+}
+
+fn value_from_json(kind: &Kind, json: &Json) -> Result<Value> {
+    // Integers may be provided as either JSON numbers or strings.
+    fn integer<T: std::str::FromStr>(json: &Json) -> Result<T> {
+        let text = match json {
+            Json::Number(number) => number.to_string(),
+            Json::String(text) => text.clone(),
+            _ => bail!("expected an integer"),
+        };
+        text.parse()
+            .map_err(|_| anyhow!("invalid integer: {}", text))
+    }
+
+    fn float(json: &Json) -> Result<f64> {
+        match json {
+            Json::Number(number) => number.as_f64().context("invalid number"),
+            Json::String(text) => match text.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                text => text
+                    .parse()
+                    .map_err(|_| anyhow!("invalid number: {}", text)),
+            },
+            _ => bail!("expected a number"),
+        }
+    }
+
+    Ok(match kind {
+        Kind::Double => Value::F64(float(json)?),
+        Kind::Float => Value::F32(float(json)? as f32),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(integer(json)?),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(integer(json)?),
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(integer(json)?),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(integer(json)?),
+        Kind::Bool => match json {
+            Json::Bool(value) => Value::Bool(*value),
+            Json::String(text) if text == "true" => Value::Bool(true),
+            Json::String(text) if text == "false" => Value::Bool(false),
+            _ => bail!("expected a boolean"),
+        },
+        Kind::String => Value::String(json.as_str().context("expected a string")?.to_string()),
+        Kind::Bytes => Value::Bytes(
+            vec_u8::deserialize::<Vec<u8>, _>(json.clone())
+                .context("invalid base64")?
+                .into(),
+        ),
+        Kind::Enum(desc) => match json {
+            Json::String(name) => Value::EnumNumber(
+                desc.get_value_by_name(name)
+                    .ok_or_else(|| anyhow!("unknown {} value: {}", desc.full_name(), name))?,
+            ),
+            json => Value::EnumNumber(integer(json)?),
+        },
+        Kind::Message(desc) => Value::Message(from_json(desc, json)?),
+    })
+}
+
+fn any_to_json(message: &DynamicMessage) -> Result<Json> {
+    let type_url = message.get_field_by_name("type_url").unwrap();
+    let type_url = type_url.as_str().unwrap_or_default();
+    let value = message.get_field_by_name("value").unwrap();
+    let packed_desc = find_type(message.descriptor(), type_url)?;
+    let packed =
+        DynamicMessage::decode(packed_desc, value.as_bytes().cloned().unwrap_or_default())?;
+
+    let mut object = Map::new();
+    object.insert("@type".to_string(), Json::String(type_url.to_string()));
+    match to_json(&packed)? {
+        Json::Object(fields) => object.extend(fields),
+        // Well-known types with a non-object representation are nested under `value`.
+        json => {
+            object.insert("value".to_string(), json);
+        }
+    }
+    Ok(Json::Object(object))
+}
+
+/// Looks up the type named by an `Any` type URL in the pool which describes the `Any` itself.
+fn find_type(any: &MessageDescriptor, type_url: &str) -> Result<MessageDescriptor> {
+    let name = type_url.rsplit('/').next().unwrap_or(type_url);
+    any.parent_pool()
+        .get_message_by_name(name)
+        .ok_or_else(|| anyhow!("unknown type URL: {}", type_url))
+}
+
+fn field_mask_paths(message: &DynamicMessage) -> Vec<String> {
+    message
+        .get_field_by_name("paths")
+        .unwrap()
+        .as_list()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|path| path.as_str().map(str::to_string))
+        .collect()
+}
+
+fn snake_to_camel(path: &str) -> String {
+    let mut camel = String::with_capacity(path.len());
+    let mut upper = false;
+    for c in path.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            camel.push(c.to_ascii_uppercase());
+            upper = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+fn camel_to_snake(path: &str) -> String {
+    let mut snake = String::with_capacity(path.len() + 4);
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
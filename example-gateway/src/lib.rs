@@ -0,0 +1,292 @@
+//! An example JSON to Protobuf gateway.
+//!
+//! The gateway accepts requests in the Protobuf JSON mapping, transcodes them to Protobuf using
+//! the descriptors embedded by `prost-build`, and dispatches them to a typed, in-memory order
+//! service. Responses travel the same path in reverse. This exercises the pieces of `prost` and
+//! `prost-types` which a real gateway depends on:
+//!
+//!  * `Config::embed_file_descriptor_set` and [`DescriptorPool`], to describe messages at runtime;
+//!  * [`DynamicMessage`], to transcode messages without knowing their Rust types;
+//!  * [`prost::Name`], [`Any`] and [`TypeRegistry`], to pack and unpack attachments;
+//!  * `google.protobuf.FieldMask`, to select the fields which are read and updated.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, Context, Result};
+use prost::Message;
+use prost_types::{
+    Any, DescriptorPool, DynamicMessage, FieldMask, FileDescriptorSet, TypeRegistry,
+};
+
+pub mod json;
+
+pub mod gateway {
+    pub mod v1 {
+        include!(concat!(env!("OUT_DIR"), "/gateway.v1.rs"));
+    }
+}
+
+use gateway::v1::{GetOrderRequest, Note, Order, UpdateOrderRequest};
+
+/// A JSON gateway in front of an in-memory order store.
+#[derive(Debug)]
+pub struct Gateway {
+    pool: DescriptorPool,
+    registry: TypeRegistry,
+    orders: BTreeMap<String, Order>,
+}
+
+impl Gateway {
+    /// Creates a gateway with an empty order store.
+    pub fn new() -> Result<Gateway> {
+        let file_descriptor_set = FileDescriptorSet::decode(gateway::v1::file_descriptor_set())?;
+        let pool = DescriptorPool::from_file_descriptor_set(&file_descriptor_set)?;
+
+        let mut registry = TypeRegistry::new();
+        registry.register::<Note>().descriptor_pool(pool.clone());
+
+        Ok(Gateway {
+            pool,
+            registry,
+            orders: BTreeMap::new(),
+        })
+    }
+
+    /// Handles a JSON request to a method of the `gateway.v1.Orders` service, returning the JSON
+    /// response.
+    pub fn handle(&mut self, method: &str, body: &str) -> Result<String> {
+        let response = match method {
+            "GetOrder" => {
+                let request: GetOrderRequest = self.decode_json(body)?;
+                let order = self
+                    .orders
+                    .get(&request.id)
+                    .ok_or_else(|| anyhow!("order not found: {}", request.id))?;
+                let mut order = self.to_dynamic(order)?;
+                if let Some(read_mask) = &request.read_mask {
+                    project(&mut order, read_mask)?;
+                }
+                order
+            }
+            "UpdateOrder" => {
+                let request: UpdateOrderRequest = self.decode_json(body)?;
+                let update = request.order.context("missing order")?;
+                let order = match (self.orders.get(&update.id), &request.update_mask) {
+                    (Some(existing), Some(update_mask)) if !update_mask.paths.is_empty() => {
+                        let mut order = self.to_dynamic(existing)?;
+                        update_mask.merge(&self.to_dynamic(&update)?, &mut order)?;
+                        Order::decode(&*order.encode_to_vec())?
+                    }
+                    _ => update,
+                };
+                let response = self.to_dynamic(&order)?;
+                self.orders.insert(order.id.clone(), order);
+                response
+            }
+            _ => bail!("unknown method: {}", method),
+        };
+        Ok(json::to_json(&response)?.to_string())
+    }
+
+    /// Returns the notes attached to an order.
+    pub fn notes(&self, id: &str) -> Result<Vec<Note>> {
+        let order = self
+            .orders
+            .get(id)
+            .ok_or_else(|| anyhow!("order not found: {}", id))?;
+        order
+            .attachments
+            .iter()
+            .filter(|attachment| attachment.is::<Note>())
+            .map(|attachment| {
+                let note = self.registry.unpack(attachment)?;
+                Ok(*note.downcast::<Note>().expect("registered type mismatch"))
+            })
+            .collect()
+    }
+
+    /// Attaches a note to an order.
+    pub fn attach_note(&mut self, id: &str, note: &Note) -> Result<()> {
+        let order = self
+            .orders
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("order not found: {}", id))?;
+        order.attachments.push(Any::pack(note));
+        Ok(())
+    }
+
+    fn decode_json<M>(&self, body: &str) -> Result<M>
+    where
+        M: prost::Name + Default,
+    {
+        let desc = self
+            .pool
+            .get_message_by_name(&M::full_name())
+            .ok_or_else(|| anyhow!("no descriptor for {}", M::full_name()))?;
+        let body = serde_json::from_str(body).context("invalid JSON")?;
+        let message = json::from_json(&desc, &body)?;
+        Ok(M::decode(&*message.encode_to_vec())?)
+    }
+
+    fn to_dynamic<M>(&self, message: &M) -> Result<DynamicMessage>
+    where
+        M: prost::Name,
+    {
+        let desc = self
+            .pool
+            .get_message_by_name(&M::full_name())
+            .ok_or_else(|| anyhow!("no descriptor for {}", M::full_name()))?;
+        Ok(DynamicMessage::decode(desc, &*message.encode_to_vec())?)
+    }
+}
+
+/// Clears every field of `message` which is not named by the mask.
+///
+/// Only top-level paths are supported. An empty mask selects every field.
+fn project(message: &mut DynamicMessage, mask: &FieldMask) -> Result<()> {
+    if mask.paths.is_empty() {
+        return Ok(());
+    }
+    let desc = message.descriptor().clone();
+    for path in &mask.paths {
+        if desc.get_field_by_name(path).is_none() {
+            bail!("invalid field mask path: {}", path);
+        }
+    }
+    for field in desc.fields() {
+        if !mask.paths.iter().any(|path| path == field.name()) {
+            message.clear_field(&field);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::{json, Value as Json};
+
+    use gateway::v1::order::Status;
+    use gateway::v1::LineItem;
+
+    fn update(gateway: &mut Gateway, body: Json) -> Json {
+        let response = gateway.handle("UpdateOrder", &body.to_string()).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    fn get(gateway: &mut Gateway, body: Json) -> Json {
+        let response = gateway.handle("GetOrder", &body.to_string()).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut gateway = Gateway::new().unwrap();
+        let order = json!({
+            "id": "o-1",
+            "status": "STATUS_PENDING",
+            "items": [
+                { "sku": "widget", "quantity": 3, "priceMicros": "1500000" },
+                { "sku": "gadget", "quantity": 1, "priceMicros": "-25" },
+            ],
+            "labels": { "region": "eu", "channel": "web" },
+            "signature": "3q2+7w==",
+            "attachments": [
+                { "@type": "type.googleapis.com/gateway.v1.Note", "text": "fragile" },
+            ],
+        });
+        let response = update(&mut gateway, json!({ "order": order }));
+        assert_eq!(response, order);
+        assert_eq!(get(&mut gateway, json!({ "id": "o-1" })), order);
+
+        // The stored order is a typed message.
+        let stored = &gateway.orders["o-1"];
+        assert_eq!(stored.status(), Status::Pending);
+        assert_eq!(
+            stored.items[1],
+            LineItem {
+                sku: "gadget".to_string(),
+                quantity: 1,
+                price_micros: -25,
+            }
+        );
+        assert_eq!(stored.signature, b"\xDE\xAD\xBE\xEF");
+    }
+
+    #[test]
+    fn field_masks() {
+        let mut gateway = Gateway::new().unwrap();
+        update(
+            &mut gateway,
+            json!({
+                "order": {
+                    "id": "o-2",
+                    "status": "STATUS_PENDING",
+                    "labels": { "region": "us" },
+                },
+            }),
+        );
+
+        let response = update(
+            &mut gateway,
+            json!({
+                "order": { "id": "o-2", "status": "STATUS_SHIPPED", "labels": {} },
+                "updateMask": "status",
+            }),
+        );
+        assert_eq!(
+            response,
+            json!({ "id": "o-2", "status": "STATUS_SHIPPED", "labels": { "region": "us" } })
+        );
+
+        let response = get(
+            &mut gateway,
+            json!({ "id": "o-2", "readMask": "id,labels" }),
+        );
+        assert_eq!(
+            response,
+            json!({ "id": "o-2", "labels": { "region": "us" } })
+        );
+
+        let error = gateway
+            .handle("GetOrder", r#"{"id":"o-2","readMask":"missingField"}"#)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "invalid field mask path: missing_field");
+    }
+
+    #[test]
+    fn attachments() {
+        let mut gateway = Gateway::new().unwrap();
+        update(&mut gateway, json!({ "order": { "id": "o-3" } }));
+
+        let note = Note {
+            text: "leave at the door".to_string(),
+        };
+        gateway.attach_note("o-3", &note).unwrap();
+        assert_eq!(gateway.notes("o-3").unwrap(), vec![note]);
+
+        let response = get(&mut gateway, json!({ "id": "o-3" }));
+        assert_eq!(
+            response["attachments"],
+            json!([{ "@type": "type.googleapis.com/gateway.v1.Note", "text": "leave at the door" }])
+        );
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let mut gateway = Gateway::new().unwrap();
+        assert!(gateway.handle("DeleteOrder", "{}").is_err());
+        assert!(gateway.handle("GetOrder", "{").is_err());
+        assert!(gateway.handle("GetOrder", r#"{"id":"o-4"}"#).is_err());
+        assert!(gateway
+            .handle("UpdateOrder", r#"{"order":{"status":"STATUS_LOST"}}"#)
+            .is_err());
+        assert!(gateway
+            .handle(
+                "UpdateOrder",
+                r#"{"order":{"attachments":[{"@type":"example.com/Unknown"}]}}"#
+            )
+            .is_err());
+    }
+}
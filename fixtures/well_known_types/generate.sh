@@ -0,0 +1,22 @@
+#!/usr/bin/env bash
+#
+# Regenerates the binary golden files (`*.binpb`) from the text format fixtures (`*.textproto`)
+# using `protoc`. The message type of each fixture is read from its `# proto-message:` header.
+#
+# `protoc` and the well-known type definitions are taken from the `PROTOC` and `PROTOC_INCLUDE`
+# environment variables if they are set, and from the copies bundled with `prost-build` otherwise.
+
+set -euo pipefail
+
+cd "$(dirname "$0")"
+
+BUNDLE="../../prost-build/third-party/protobuf"
+PROTOC="${PROTOC:-$BUNDLE/protoc-linux-x86_64}"
+PROTOC_INCLUDE="${PROTOC_INCLUDE:-$BUNDLE/include}"
+
+for fixture in *.textproto; do
+    file="$(sed -n 's/^# proto-file: //p' "$fixture")"
+    message="$(sed -n 's/^# proto-message: //p' "$fixture")"
+    "$PROTOC" -I "$PROTOC_INCLUDE" --encode="$message" "$file" \
+        < "$fixture" > "${fixture%.textproto}.binpb"
+done
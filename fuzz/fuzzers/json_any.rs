@@ -0,0 +1,37 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost_types::{Any, Duration, Timestamp, TypeRegistry, Value};
+
+// `Value` holds binary data as the JSON mapping of an `Any` packing a `BytesValue`, which is the
+// only `Any` JSON this crate parses.
+fuzz_target!(|data: &[u8]| {
+    let value = match prost_types::json::from_slice::<Value>(data) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    if let Ok(Some(bytes)) = value.to_bytes() {
+        assert_eq!(
+            Value::from_bytes(&bytes).to_bytes().unwrap(),
+            Some(bytes.clone())
+        );
+
+        // Unpack the bytes as the value of an `Any` of each registered type.
+        let mut registry = TypeRegistry::new();
+        registry
+            .register::<Timestamp>()
+            .register::<Duration>()
+            .register::<Value>();
+        for type_url in &[
+            "type.googleapis.com/google.protobuf.Timestamp",
+            "type.googleapis.com/google.protobuf.Duration",
+            "type.googleapis.com/google.protobuf.Value",
+        ] {
+            let any = Any {
+                type_url: type_url.to_string(),
+                value: bytes.clone(),
+            };
+            let _ = registry.unpack(&any);
+        }
+    }
+});
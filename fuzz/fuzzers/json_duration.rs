@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost_types::Duration;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Wrapper(#[serde(with = "prost_types::serde::duration")] Duration);
+
+// The input is the content of a JSON string, so that the fuzzer doesn't spend its time on quoting.
+fuzz_target!(|data: &str| {
+    let json = serde_json::Value::String(data.to_owned()).to_string();
+    if let Ok(duration) = prost_types::json::from_str::<Wrapper>(&json) {
+        let json = prost_types::json::to_string(&duration).unwrap();
+        assert_eq!(
+            prost_types::json::from_str::<Wrapper>(&json).unwrap(),
+            duration
+        );
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost_types::Timestamp;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Strict(#[serde(with = "prost_types::serde::timestamp")] Timestamp);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Lenient(#[serde(with = "prost_types::serde::timestamp::lenient")] Timestamp);
+
+// The input is the content of a JSON string, so that the fuzzer doesn't spend its time on quoting.
+fuzz_target!(|data: &str| {
+    let json = serde_json::Value::String(data.to_owned()).to_string();
+    if let Ok(timestamp) = prost_types::json::from_str::<Strict>(&json) {
+        let json = prost_types::json::to_string(&timestamp).unwrap();
+        assert_eq!(
+            prost_types::json::from_str::<Strict>(&json).unwrap(),
+            timestamp
+        );
+    }
+    if let Ok(timestamp) = prost_types::json::from_str::<Lenient>(&json) {
+        let json = prost_types::json::to_string(&timestamp).unwrap();
+        assert_eq!(
+            prost_types::json::from_str::<Lenient>(&json).unwrap(),
+            timestamp
+        );
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost_types::Value;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(value) = prost_types::json::from_slice::<Value>(data) {
+        let json = prost_types::json::to_string(&value).unwrap();
+        assert_eq!(prost_types::json::from_str::<Value>(&json).unwrap(), value);
+    }
+});
@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tests::{groups, nesting, recursive_oneof, roundtrip};
+
+// The first byte selects the message type, so that a single target covers nested, recursive and
+// group-encoded messages.
+fuzz_target!(|data: &[u8]| {
+    let (selector, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let _ = match selector % 8 {
+        0 => roundtrip::<nesting::A>(data),
+        1 => roundtrip::<nesting::D>(data),
+        2 => roundtrip::<recursive_oneof::A>(data),
+        3 => roundtrip::<groups::Test1>(data),
+        4 => roundtrip::<groups::Test2>(data),
+        5 => roundtrip::<groups::OneofGroup>(data),
+        6 => roundtrip::<groups::NestedGroup>(data),
+        _ => roundtrip::<groups::NestedGroup2>(data),
+    }
+    .unwrap_error();
+});
@@ -0,0 +1,38 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost::Message;
+use prost_types::{
+    Any, Duration, FieldMask, FileDescriptorSet, Struct, Timestamp, TypeRegistry, Value,
+};
+use tests::roundtrip;
+
+fuzz_target!(|data: &[u8]| {
+    let (selector, data) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let _ = match selector % 7 {
+        0 => roundtrip::<Timestamp>(data),
+        1 => roundtrip::<Duration>(data),
+        2 => roundtrip::<Value>(data),
+        3 => roundtrip::<Struct>(data),
+        4 => roundtrip::<FieldMask>(data),
+        5 => roundtrip::<FileDescriptorSet>(data),
+        _ => {
+            // Unpack the `Any` as whichever well-known type its type URL names.
+            if let Ok(any) = Any::decode(data) {
+                let mut registry = TypeRegistry::new();
+                registry
+                    .register::<Timestamp>()
+                    .register::<Duration>()
+                    .register::<Value>()
+                    .register::<Struct>()
+                    .register::<FieldMask>();
+                let _ = registry.unpack(&any);
+            }
+            roundtrip::<Any>(data)
+        }
+    }
+    .unwrap_error();
+});
@@ -7,7 +7,8 @@
 //!     2. The bundled `protoc`.
 //!     3. The `protoc` on the `PATH`.
 //!
-//! If no `protoc` binary is available in these locations, the build fails.
+//! If no `protoc` binary is available in these locations, the build fails, unless the `no-protoc`
+//! feature is enabled, in which case `protoc` is not used.
 //!
 //! The following locations are checked for the Protobuf include directory in decreasing priority:
 //!
@@ -102,6 +103,10 @@ fn main() {
     let protoc = env_protoc()
         .or_else(bundled_protoc)
         .or_else(path_protoc)
+        .or_else(|| {
+            // With the built-in parser, `protoc` is only returned by `prost_build::protoc`.
+            env::var_os("CARGO_FEATURE_NO_PROTOC").map(|_| PathBuf::from("protoc"))
+        })
         .expect(
             "Failed to find the protoc binary. The PROTOC environment variable is not set, \
              there is no bundled protoc for this platform, and protoc is not in the PATH",
@@ -2,8 +2,10 @@ use lazy_static::lazy_static;
 use prost_types::source_code_info::Location;
 use regex::Regex;
 
+use crate::CustomOptions;
+
 /// Comments on a Protobuf item.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct Comments {
     /// Leading detached blocks of comments.
     pub leading_detached: Vec<Vec<String>>,
@@ -90,6 +92,17 @@ impl Comments {
         }
     }
 
+    /// Marks the code blocks of the leading and trailing comments as `text`, so that rustdoc
+    /// renders them without compiling and running them as Rust doctests.
+    ///
+    /// Fenced code blocks without an info string are given the `text` info string, and indented
+    /// code blocks are converted to fenced `text` code blocks. Fenced code blocks which already
+    /// have an info string are left as they are.
+    pub(crate) fn mark_code_blocks_as_text(&mut self) {
+        self.leading = mark_code_blocks_as_text(&self.leading);
+        self.trailing = mark_code_blocks_as_text(&self.trailing);
+    }
+
     /// Sanitizes the line for rustdoc by performing the following operations:
     ///     - escape urls as <http://foo.com>
     ///     - escape `[` & `]`
@@ -105,6 +118,101 @@ impl Comments {
     }
 }
 
+/// Returns the number of leading spaces of a line.
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// Returns the fence of a line which opens or closes a fenced code block, such as `` ``` ``, and
+/// its info string.
+fn code_fence(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let fence_char = line.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = line.len() - line.trim_start_matches(fence_char).len();
+    if len < 3 {
+        return None;
+    }
+    Some((&line[..len], line[len..].trim()))
+}
+
+/// Marks the code blocks of the lines of a comment as `text`.
+///
+/// Like rustdoc, which strips the common indentation of doc comments, indented code blocks are
+/// identified relative to the least indented line of the comment.
+fn mark_code_blocks_as_text(lines: &[String]) -> Vec<String> {
+    let base = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+    let margin = " ".repeat(base);
+
+    let mut result = Vec::with_capacity(lines.len());
+    // The fence of the fenced code block the current line is in.
+    let mut fence: Option<String> = None;
+    // Whether the current line is in an indented code block, and the blank lines which may
+    // continue it.
+    let mut indented = false;
+    let mut blank_lines = Vec::new();
+    let mut previous_blank = true;
+
+    for line in lines {
+        let blank = line.trim().is_empty();
+
+        if let Some(open) = &fence {
+            // A closing fence may be followed by an indented code block, like a blank line.
+            previous_blank = match code_fence(line) {
+                Some((close, "")) if close.starts_with(open.as_str()) => {
+                    fence = None;
+                    true
+                }
+                _ => blank,
+            };
+            result.push(line.clone());
+            continue;
+        }
+
+        if indented {
+            if blank {
+                blank_lines.push(line.clone());
+                continue;
+            }
+            if indentation(line) >= base + 4 {
+                result.append(&mut blank_lines);
+                result.push(line[4..].to_string());
+                continue;
+            }
+            result.push(format!("{}```", margin));
+            result.append(&mut blank_lines);
+            indented = false;
+            previous_blank = true;
+        }
+
+        if let Some((open, info)) = code_fence(line) {
+            if info.is_empty() {
+                result.push(format!("{}{}text", &line[..indentation(line)], open));
+            } else {
+                result.push(line.clone());
+            }
+            fence = Some(open.to_string());
+        } else if !blank && previous_blank && indentation(line) >= base + 4 {
+            result.push(format!("{}```text", margin));
+            result.push(line[4..].to_string());
+            indented = true;
+        } else {
+            result.push(line.clone());
+        }
+        previous_blank = blank;
+    }
+
+    if indented {
+        result.push(format!("{}```", margin));
+        result.append(&mut blank_lines);
+    }
+    result
+}
+
 /// A service descriptor.
 #[derive(Debug)]
 pub struct Service {
@@ -120,6 +228,8 @@ pub struct Service {
     pub methods: Vec<Method>,
     /// The service options.
     pub options: prost_types::ServiceOptions,
+    /// The custom options of the service, which are not part of `options`.
+    pub custom_options: CustomOptions,
 }
 
 /// A service method descriptor.
@@ -141,6 +251,8 @@ pub struct Method {
     pub output_proto_type: String,
     /// The method options.
     pub options: prost_types::MethodOptions,
+    /// The custom options of the method, which are not part of `options`.
+    pub custom_options: CustomOptions,
     /// Identifies if client streams multiple client messages.
     pub client_streaming: bool,
     /// Identifies if server streams multiple server messages.
@@ -151,6 +263,54 @@ pub struct Method {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_comment_mark_code_blocks_as_text() {
+        let lines = |text: &str| text.lines().map(str::to_owned).collect::<Vec<_>>();
+        let mut comments = Comments {
+            leading_detached: vec![],
+            leading: lines(
+                " Fenced:\n \n ```\n let x = invalid;\n ```\n \n ```rust,ignore\n ignored\n ```\n \n \
+                 Indented:\n \n     if (x) {\n\n       y();\n     }\n \n Done.\n     not code",
+            ),
+            trailing: lines(" ~~~~\n ```\n ~~~~\n     tail"),
+        };
+        comments.mark_code_blocks_as_text();
+
+        let mut actual = String::new();
+        comments.append_with_indent(0, &mut actual);
+        let expected = "\
+/// Fenced:
+/// 
+/// ```text
+/// let x = invalid;
+/// ```
+/// 
+/// ```rust,ignore
+/// ignored
+/// ```
+/// 
+/// Indented:
+/// 
+/// ```text
+/// if (x) {
+///
+///   y();
+/// }
+/// ```
+/// 
+/// Done.
+///     not code
+///
+/// ~~~~text
+/// ```
+/// ~~~~
+/// ```text
+/// tail
+/// ```
+";
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn test_comment_append_with_indent_sanitizes_comment_doc_url() {
         struct TestCases {
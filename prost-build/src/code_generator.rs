@@ -1,5 +1,6 @@
 use std::ascii;
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::iter;
 
@@ -15,10 +16,41 @@ use prost_types::{
 };
 
 use crate::ast::{Comments, Method, Service};
+use crate::custom_options::CustomOptionsIndex;
 use crate::extern_paths::ExternPaths;
 use crate::ident::{to_snake, to_upper_camel};
 use crate::message_graph::MessageGraph;
-use crate::{BytesType, Config, MapType};
+use crate::path::PathMap;
+use crate::validate::{FieldRules, ValidationRules};
+use crate::{BytesType, Config, MapType, StringType};
+
+/// The argument of a builder setter.
+enum SetterParam {
+    /// A value of the type.
+    Value(String),
+    /// A value converted into the type with `Into`.
+    Into(String),
+    /// An iterator over values of the type.
+    Items(String),
+    /// An iterator over values converted into the type with `Into`.
+    IntoItems(String),
+}
+
+/// How the value of a field is held in its message, for validation.
+#[derive(Clone, Copy)]
+enum Holder<'a> {
+    /// A singular field held directly, with its presence bit if it has one.
+    Plain(Option<usize>),
+    /// A singular field held in an `Option`.
+    Option,
+    /// A repeated field.
+    Repeated,
+    /// A map field, with the descriptor of its values.
+    Map(&'a FieldDescriptorProto),
+    /// A field of a oneof, held in a variant of the oneof enum. `member` is the name of the oneof
+    /// in the message, and `variant` the path of the field's variant.
+    Variant { member: &'a str, variant: &'a str },
+}
 
 #[derive(PartialEq)]
 enum Syntax {
@@ -29,13 +61,42 @@ enum Syntax {
 pub struct CodeGenerator<'a> {
     config: &'a mut Config,
     package: String,
+    file_package: String,
     source_info: SourceCodeInfo,
     syntax: Syntax,
     message_graph: &'a MessageGraph,
     extern_paths: &'a ExternPaths,
+    custom_options: &'a CustomOptionsIndex,
+    validation_rules: &'a ValidationRules,
     depth: u8,
     path: Vec<i32>,
     buf: &'a mut String,
+    /// The builder setters of the fields of the message being generated, if it gets a builder.
+    setters: Option<Vec<String>>,
+    /// The accessors of the fields of the message being generated, if it gets accessors.
+    accessors: Option<Vec<String>>,
+    /// The checks of the fields of the message being generated, if it implements `Validate`.
+    validations: Option<Vec<String>>,
+    /// The statements generating the fields of the message being generated, if it implements
+    /// `arbitrary::Arbitrary`.
+    arbitrary: Option<Vec<String>>,
+    /// The next presence bit to assign to a field of the message being generated, if it uses
+    /// presence bits.
+    presence_bits: Option<usize>,
+    /// The estimated sizes of the messages, by fully-qualified name.
+    message_sizes: RefCell<HashMap<String, usize>>,
+    /// The messages which derive each of the `CHECKED_DERIVES` traits requested with
+    /// `Config::derive`, by trait.
+    derived_messages: RefCell<HashMap<&'static str, HashSet<String>>>,
+}
+
+/// The standard traits which `Config::derive` only derives on the messages whose fields
+/// implement them.
+const CHECKED_DERIVES: &[&str] = &["Copy", "Eq", "Hash", "PartialOrd", "Ord"];
+
+/// Returns the name of a trait in a list of derives, without its path.
+fn derive_name(derive: &str) -> &str {
+    derive.rsplit("::").next().unwrap_or(derive)
 }
 
 fn push_indent(buf: &mut String, depth: u8) {
@@ -49,12 +110,13 @@ impl<'a> CodeGenerator<'a> {
         config: &mut Config,
         message_graph: &MessageGraph,
         extern_paths: &ExternPaths,
+        custom_options: &CustomOptionsIndex,
+        validation_rules: &ValidationRules,
         file: FileDescriptorProto,
         buf: &mut String,
     ) {
-        let mut source_info = file
-            .source_code_info
-            .expect("no source code info in request");
+        // Descriptors built without source info have no comments.
+        let mut source_info = file.source_code_info.unwrap_or_default();
         source_info.location.retain(|location| {
             let len = location.path.len();
             len > 0 && len % 2 == 0
@@ -69,16 +131,27 @@ impl<'a> CodeGenerator<'a> {
             Some(s) => panic!("unknown syntax: {}", s),
         };
 
+        let package = file.package.unwrap_or_default();
         let mut code_gen = CodeGenerator {
             config,
-            package: file.package.unwrap_or_default(),
+            file_package: package.clone(),
+            package,
             source_info,
             syntax,
             message_graph,
             extern_paths,
+            custom_options,
+            validation_rules,
             depth: 0,
             path: Vec::new(),
             buf,
+            setters: None,
+            accessors: None,
+            validations: None,
+            arbitrary: None,
+            presence_bits: None,
+            message_sizes: RefCell::new(HashMap::new()),
+            derived_messages: RefCell::new(HashMap::new()),
         };
 
         debug!(
@@ -181,16 +254,74 @@ impl<'a> CodeGenerator<'a> {
                 }
             });
 
+        let presence_bits = fields
+            .iter()
+            .filter(|&&(ref field, _)| self.has_presence_bit(&fq_message_name, field))
+            .count();
+
+        let mut recursive_fields = self.recursive_fields(
+            &fq_message_name,
+            &message_name,
+            &fields,
+            &map_types,
+            &message.oneof_decl,
+            &oneof_fields,
+        );
+        if let Some(recursive_fields) = &mut recursive_fields {
+            if presence_bits > 0 {
+                recursive_fields.push(RecursiveField::Plain("_presence".to_string()));
+            }
+        }
+
         self.append_doc(&fq_message_name, None);
         self.append_type_attributes(&fq_message_name);
         self.push_indent();
-        self.buf
-            .push_str("#[derive(Clone, PartialEq, ::prost::Message)]\n");
+        let derives = if recursive_fields.is_some() {
+            "::prost::Message"
+        } else {
+            "Clone, PartialEq, ::prost::Message"
+        };
+        self.buf.push_str(&format!(
+            "#[derive({}{}{})]\n",
+            derives,
+            self.message_derives(&fq_message_name),
+            self.serde_derives()
+        ));
+        if self.config.enable_serde_json {
+            self.push_indent();
+            self.buf.push_str("#[serde(default)]\n");
+        }
+        if let Some(function) = self
+            .config
+            .check_encode
+            .get_first(&fq_message_name)
+            .cloned()
+        {
+            self.push_indent();
+            self.buf
+                .push_str(&format!("#[prost(check_encode={:?})]\n", function));
+        }
         self.push_indent();
         self.buf.push_str("pub struct ");
         self.buf.push_str(&to_upper_camel(&message_name));
         self.buf.push_str(" {\n");
 
+        if self.config.builders.get_first(&fq_message_name).is_some() {
+            self.setters = Some(Vec::new());
+        }
+        if self.config.accessors.get_first(&fq_message_name).is_some() {
+            self.accessors = Some(Vec::new());
+        }
+        if self.config.validate.get_first(&fq_message_name).is_some() {
+            self.validations = Some(Vec::new());
+        }
+        if self.implements_arbitrary(&fq_message_name) {
+            self.arbitrary = Some(Vec::new());
+        }
+        if presence_bits > 0 {
+            self.presence_bits = Some(0);
+        }
+
         self.depth += 1;
         self.path.push(2);
         for (field, idx) in fields {
@@ -224,10 +355,68 @@ impl<'a> CodeGenerator<'a> {
         }
         self.path.pop();
 
+        if self.presence_bits.take().is_some() {
+            let doc = match (
+                self.uses_presence_bits(&fq_message_name),
+                self.enforces_required(&fq_message_name),
+            ) {
+                (true, true) => "/// The presence bits of the optional and required fields.\n",
+                (false, true) => "/// The presence bits of the required fields.\n",
+                _ => "/// The presence bits of the optional fields.\n",
+            };
+            self.push_indent();
+            self.buf.push_str(doc);
+            self.push_indent();
+            self.buf.push_str("#[prost(presence_bits)]\n");
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "pub _presence: ::prost::PresenceBits<{}>,\n",
+                (presence_bits + 63) / 64
+            ));
+        }
+
         self.depth -= 1;
         self.push_indent();
         self.buf.push_str("}\n");
 
+        if let Some(recursive_fields) = recursive_fields {
+            self.append_recursive_impls(&message_name, &recursive_fields);
+        }
+
+        if self.config.embed_file_descriptor_set {
+            self.append_descriptor_full_name(&message_name, &fq_message_name);
+        }
+
+        if self.config.enable_type_names {
+            self.append_type_name(&message_name, &fq_message_name);
+        }
+
+        if self
+            .config
+            .message_pipeline
+            .get_first(&fq_message_name)
+            .is_some()
+        {
+            self.append_pipelined(&message_name, &fq_message_name);
+        }
+
+        if let Some(accessors) = self.accessors.take() {
+            self.append_accessors(&message_name, &accessors);
+        }
+
+        if let Some(setters) = self.setters.take() {
+            self.append_builder(&message_name, &setters);
+        }
+
+        if let Some(validations) = self.validations.take() {
+            self.append_validate(&message_name, &validations);
+        }
+
+        if let Some(arbitrary) = self.arbitrary.take() {
+            self.append_arbitrary(&message_name, &arbitrary);
+        }
+        self.append_proptest_arbitrary(&message_name, &fq_message_name);
+
         if !message.enum_type.is_empty() || !nested_types.is_empty() || !oneof_fields.is_empty() {
             self.push_mod(&message_name);
             self.path.push(3);
@@ -260,6 +449,1171 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    fn append_descriptor_full_name(&mut self, message_name: &str, fq_message_name: &str) {
+        self.push_indent();
+        self.buf.push_str("impl ");
+        self.buf.push_str(&to_upper_camel(message_name));
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str("/// The fully-qualified Protobuf name of this message.\n");
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "pub const DESCRIPTOR_FULL_NAME: &str = \"{}\";\n",
+            fq_message_name.trim_start_matches('.')
+        ));
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_type_name(&mut self, message_name: &str, fq_message_name: &str) {
+        let full_name = fq_message_name.trim_start_matches('.');
+        let name = if self.file_package.is_empty() {
+            full_name
+        } else {
+            &full_name[self.file_package.len() + 1..]
+        };
+
+        self.push_indent();
+        self.buf.push_str("impl ::prost::Name for ");
+        self.buf.push_str(&to_upper_camel(message_name));
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str(&format!("const NAME: &'static str = \"{}\";\n", name));
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "const PACKAGE: &'static str = \"{}\";\n",
+            self.file_package
+        ));
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_pipelined(&mut self, message_name: &str, fq_message_name: &str) {
+        self.push_indent();
+        self.buf
+            .push_str("impl ::prost_types::pipeline::Pipelined for ");
+        self.buf.push_str(&to_upper_camel(message_name));
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "const FULL_NAME: &'static str = \"{}\";\n",
+            fq_message_name.trim_start_matches('.')
+        ));
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_builder(&mut self, message_name: &str, setters: &[String]) {
+        let message_name = to_upper_camel(message_name);
+        let mut code = format!(
+            "impl {0} {{\n    \
+             /// Returns a builder for `{0}`.\n    \
+             pub fn builder() -> {0}Builder {{\n        \
+             {0}Builder::default()\n    \
+             }}\n\
+             }}\n\
+             /// A builder for `{0}`, created with `{0}::builder()`.\n\
+             #[derive(Clone, Debug, Default)]\n\
+             pub struct {0}Builder {{\n    \
+             inner: {0},\n\
+             }}\n\
+             impl {0}Builder {{\n",
+            message_name
+        );
+        for setter in setters {
+            code += setter;
+        }
+        code += &format!(
+            "    /// Returns the message, with the fields which were not set holding their default \
+             value.\n    \
+             pub fn build(self) -> {} {{\n        \
+             self.inner\n    \
+             }}\n\
+             }}\n",
+            message_name
+        );
+        self.push_code(&code);
+    }
+
+    fn append_validate(&mut self, message_name: &str, validations: &[String]) {
+        let mut code = format!(
+            "impl ::prost::Validate for {} {{\n    \
+             fn validate(&self) -> ::core::result::Result<(), ::prost::ValidationError> {{\n",
+            to_upper_camel(message_name)
+        );
+        if !validations.is_empty() {
+            code += &format!("        const MESSAGE: &str = {:?};\n", message_name);
+        }
+        for validation in validations {
+            code += validation;
+        }
+        code += "        ::core::result::Result::Ok(())\n    }\n}\n";
+        self.push_code(&code);
+    }
+
+    fn append_arbitrary(&mut self, message_name: &str, statements: &[String]) {
+        let mut code = format!(
+            "impl<'arbitrary> ::arbitrary::Arbitrary<'arbitrary> for {} {{\n    \
+             #[allow(deprecated, clippy::field_reassign_with_default)]\n",
+            to_upper_camel(message_name)
+        );
+        if statements.is_empty() {
+            code += "    fn arbitrary(_: &mut ::arbitrary::Unstructured<'arbitrary>) \
+                     -> ::arbitrary::Result<Self> {\n        \
+                     ::core::result::Result::Ok(Self::default())\n";
+        } else {
+            code += "    fn arbitrary(u: &mut ::arbitrary::Unstructured<'arbitrary>) \
+                     -> ::arbitrary::Result<Self> {\n        \
+                     let mut message = Self::default();\n";
+            for statement in statements {
+                code += statement;
+            }
+            code += "        ::core::result::Result::Ok(message)\n";
+        }
+        code += "    }\n}\n";
+        self.push_code(&code);
+    }
+
+    /// Appends the implementation of `proptest::arbitrary::Arbitrary` for a message or enum, if
+    /// it is matched by `Config::proptest_arbitrary`.
+    fn append_proptest_arbitrary(&mut self, name: &str, fq_name: &str) {
+        if self.config.proptest_arbitrary.get_first(fq_name).is_none() {
+            return;
+        }
+        let code = format!(
+            "impl ::proptest::arbitrary::Arbitrary for {} {{\n    \
+             type Parameters = ();\n    \
+             type Strategy = ::proptest::strategy::BoxedStrategy<Self>;\n    \
+             fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {{\n        \
+             ::prost_types::arbitrary::strategy()\n    \
+             }}\n\
+             }}\n",
+            to_upper_camel(name)
+        );
+        self.push_code(&code);
+    }
+
+    fn append_accessors(&mut self, message_name: &str, accessors: &[String]) {
+        let mut code = format!("impl {} {{\n", to_upper_camel(message_name));
+        for accessor in accessors {
+            code += accessor;
+        }
+        code += "}\n";
+        self.push_code(&code);
+    }
+
+    /// Appends the lines of the code at the current indentation.
+    fn push_code(&mut self, code: &str) {
+        for line in code.lines() {
+            if !line.is_empty() {
+                self.push_indent();
+                self.buf.push_str(line);
+            }
+            self.buf.push('\n');
+        }
+    }
+
+    /// Returns the code of an accessor method of a field.
+    fn accessor(
+        &self,
+        field: &FieldDescriptorProto,
+        doc: &str,
+        signature: &str,
+        body: &str,
+    ) -> String {
+        let mut code = format!("    /// {}\n", doc);
+        if self.deprecated(field) {
+            code += "    #[deprecated]\n    #[allow(deprecated)]\n";
+        }
+        code += &format!("    pub fn {} {{\n        {}\n    }}\n", signature, body);
+        code
+    }
+
+    /// Records the accessors of a field which is not part of a oneof, if the message gets
+    /// accessors.
+    ///
+    /// Getters and setters which are already generated by `prost-derive`, for enumeration fields
+    /// and for scalar fields with explicit presence, are skipped.
+    fn push_field_accessors(
+        &mut self,
+        field: &FieldDescriptorProto,
+        ty: &str,
+        repeated: bool,
+        optional: bool,
+        boxed: bool,
+    ) {
+        if self.accessors.is_none() {
+            return;
+        }
+
+        let member = to_snake(field.name());
+        let name = member.trim_start_matches("r#");
+        let mut code = String::new();
+        if repeated {
+            code += &self.accessor(
+                field,
+                &format!("Clears `{}`.", name),
+                &format!("clear_{}(&mut self)", name),
+                &format!("self.{}.clear();", member),
+            );
+            if let Some(accessors) = &mut self.accessors {
+                accessors.push(code);
+            }
+            return;
+        }
+
+        match field.r#type() {
+            Type::Message | Type::Group => {
+                code += &self.accessor(
+                    field,
+                    &format!("Returns the value of `{}`, or `None` if it is unset.", name),
+                    &format!("{}(&self) -> ::core::option::Option<&{}>", member, ty),
+                    &format!(
+                        "self.{}.{}()",
+                        member,
+                        if boxed { "as_deref" } else { "as_ref" }
+                    ),
+                );
+                code += &self.accessor(
+                    field,
+                    &format!(
+                        "Returns a mutable reference to `{}`, setting it to the default value if \
+                         it is unset.",
+                        name
+                    ),
+                    &format!("{}_mut(&mut self) -> &mut {}", name, ty),
+                    &format!(
+                        "self.{}.get_or_insert_with(::core::default::Default::default)",
+                        member
+                    ),
+                );
+                let value = if boxed {
+                    "::prost::alloc::boxed::Box::new(value)"
+                } else {
+                    "value"
+                };
+                code += &self.accessor(
+                    field,
+                    &format!("Sets `{}` to the provided value.", name),
+                    &format!("set_{}(&mut self, value: {})", name, ty),
+                    &format!("self.{} = ::core::option::Option::Some({});", member, value),
+                );
+            }
+            Type::Enum => {}
+            _ if optional => {
+                code += &self.accessor(
+                    field,
+                    &format!("Sets `{}` to the provided value.", name),
+                    &format!(
+                        "set_{}(&mut self, value: impl ::core::convert::Into<{}>)",
+                        name, ty
+                    ),
+                    &format!(
+                        "self.{} = ::core::option::Option::Some(value.into());",
+                        member
+                    ),
+                );
+            }
+            type_ => {
+                let (return_ty, value) = match type_ {
+                    Type::String => ("&str".to_string(), format!("&self.{}", member)),
+                    Type::Bytes => ("&[u8]".to_string(), format!("&self.{}[..]", member)),
+                    _ => (ty.to_string(), format!("self.{}", member)),
+                };
+                code += &self.accessor(
+                    field,
+                    &format!("Returns the value of `{}`.", name),
+                    &format!("{}(&self) -> {}", member, return_ty),
+                    &value,
+                );
+                code += &self.accessor(
+                    field,
+                    &format!("Sets `{}` to the provided value.", name),
+                    &format!(
+                        "set_{}(&mut self, value: impl ::core::convert::Into<{}>)",
+                        name, ty
+                    ),
+                    &format!("self.{} = value.into();", member),
+                );
+            }
+        }
+
+        if optional {
+            code += &self.accessor(
+                field,
+                &format!("Returns `true` if `{}` is set.", name),
+                &format!("has_{}(&self) -> bool", name),
+                &format!("self.{}.is_some()", member),
+            );
+            code += &self.accessor(
+                field,
+                &format!("Unsets `{}`.", name),
+                &format!("clear_{}(&mut self)", name),
+                &format!("self.{} = ::core::option::Option::None;", member),
+            );
+        } else if field.label() != Label::Required {
+            code += &self.accessor(
+                field,
+                &format!("Resets `{}` to the default value.", name),
+                &format!("clear_{}(&mut self)", name),
+                &format!("self.{} = ::core::default::Default::default();", member),
+            );
+        }
+
+        if let Some(accessors) = &mut self.accessors {
+            accessors.push(code);
+        }
+    }
+
+    /// Records the getter and setters of an optional message field with a presence bit, if the
+    /// message gets accessors.
+    fn push_present_message_accessors(
+        &mut self,
+        field: &FieldDescriptorProto,
+        ty: &str,
+        bit: usize,
+        boxed: bool,
+    ) {
+        if self.accessors.is_none() {
+            return;
+        }
+
+        let member = to_snake(field.name());
+        let name = member.trim_start_matches("r#");
+        let deref = if boxed { "*" } else { "" };
+        let mut code = self.accessor(
+            field,
+            &format!("Returns the value of `{}`, or `None` if it is unset.", name),
+            &format!("{}(&self) -> ::core::option::Option<&{}>", member, ty),
+            &format!(
+                "if self._presence.get({}) {{\n            \
+                 ::core::option::Option::Some(&{}self.{})\n        \
+                 }} else {{\n            \
+                 ::core::option::Option::None\n        \
+                 }}",
+                bit, deref, member
+            ),
+        );
+        code += &self.accessor(
+            field,
+            &format!(
+                "Returns a mutable reference to `{}`, marking it as set.",
+                name
+            ),
+            &format!("{}_mut(&mut self) -> &mut {}", name, ty),
+            &format!(
+                "self._presence.set({});\n        &mut {}self.{}",
+                bit, deref, member
+            ),
+        );
+        code += &self.accessor(
+            field,
+            &format!("Sets `{}` to the provided value.", name),
+            &format!("set_{}(&mut self, value: {})", name, ty),
+            &format!("*self.{}_mut() = value;", name),
+        );
+        if let Some(accessors) = &mut self.accessors {
+            accessors.push(code);
+        }
+    }
+
+    /// Records the accessors of a field which is part of a oneof, if the message gets accessors.
+    ///
+    /// `member` is the name of the oneof in the message, and `variant` the path of the field's
+    /// variant in the oneof enum.
+    fn push_oneof_field_accessors(
+        &mut self,
+        field: &FieldDescriptorProto,
+        member: &str,
+        variant: &str,
+        ty: &str,
+        boxed: bool,
+    ) {
+        if self.accessors.is_none() {
+            return;
+        }
+
+        let name = to_snake(field.name());
+        let bare_name = name.trim_start_matches("r#");
+        let (return_ty, value, default, param, set_value) = match field.r#type() {
+            Type::Message | Type::Group => (
+                format!("::core::option::Option<&{}>", ty),
+                format!(
+                    "::core::option::Option::Some({})",
+                    if boxed { "&**value" } else { "value" }
+                ),
+                "::core::option::Option::None".to_string(),
+                ty.to_string(),
+                if boxed {
+                    "::prost::alloc::boxed::Box::new(value)"
+                } else {
+                    "value"
+                },
+            ),
+            Type::Enum => {
+                let enum_ty = self.resolve_ident(field.type_name());
+                (
+                    enum_ty.clone(),
+                    format!("{}::from_i32(*value).unwrap_or_default()", enum_ty),
+                    format!("{}::default()", enum_ty),
+                    enum_ty,
+                    "value as i32",
+                )
+            }
+            Type::String => (
+                "&str".to_string(),
+                "value".to_string(),
+                "\"\"".to_string(),
+                format!("impl ::core::convert::Into<{}>", ty),
+                "value.into()",
+            ),
+            Type::Bytes => (
+                "&[u8]".to_string(),
+                "&value[..]".to_string(),
+                "&[]".to_string(),
+                format!("impl ::core::convert::Into<{}>", ty),
+                "value.into()",
+            ),
+            _ => (
+                ty.to_string(),
+                "*value".to_string(),
+                "::core::default::Default::default()".to_string(),
+                format!("impl ::core::convert::Into<{}>", ty),
+                "value.into()",
+            ),
+        };
+
+        let mut code = self.accessor(
+            field,
+            &format!(
+                "Returns the value of `{}`, or the default value if the oneof is set to another \
+                 field or unset.",
+                bare_name
+            ),
+            &format!("{}(&self) -> {}", name, return_ty),
+            &format!(
+                "match &self.{} {{\n            \
+                 ::core::option::Option::Some({}(value)) => {},\n            \
+                 _ => {},\n        \
+                 }}",
+                member, variant, value, default
+            ),
+        );
+        code += &self.accessor(
+            field,
+            &format!("Sets the oneof to `{}` with the provided value.", bare_name),
+            &format!("set_{}(&mut self, value: {})", bare_name, param),
+            &format!(
+                "self.{} = ::core::option::Option::Some({}({}));",
+                member, variant, set_value
+            ),
+        );
+        code += &self.accessor(
+            field,
+            &format!("Returns `true` if the oneof is set to `{}`.", bare_name),
+            &format!("has_{}(&self) -> bool", bare_name),
+            &format!(
+                "::core::matches!(self.{}, ::core::option::Option::Some({}(_)))",
+                member, variant
+            ),
+        );
+        code += &self.accessor(
+            field,
+            &format!("Unsets the oneof if it is set to `{}`.", bare_name),
+            &format!("clear_{}(&mut self)", bare_name),
+            &format!(
+                "if ::core::matches!(self.{0}, ::core::option::Option::Some({1}(_))) {{\n            \
+                 self.{0} = ::core::option::Option::None;\n        \
+                 }}",
+                member, variant
+            ),
+        );
+
+        if let Some(accessors) = &mut self.accessors {
+            accessors.push(code);
+        }
+    }
+
+    /// Records the builder setter of a field, if the message gets a builder.
+    ///
+    /// `assignment` is the statement setting the field of the message `self.inner`, in terms of the
+    /// setter's argument `value`.
+    fn push_setter(&mut self, field: &FieldDescriptorProto, param: SetterParam, assignment: &str) {
+        if self.setters.is_none() {
+            return;
+        }
+
+        let mut name = to_snake(field.name());
+        if name == "build" {
+            name.push('_');
+        }
+        let mut code = format!("    /// Sets the `{}` field.\n", field.name());
+        if self.deprecated(field) {
+            code += "    #[deprecated]\n    #[allow(deprecated)]\n";
+        }
+        match param {
+            SetterParam::Value(ty) => {
+                code += &format!("    pub fn {}(mut self, value: {}) -> Self {{\n", name, ty)
+            }
+            SetterParam::Into(ty) => {
+                code += &format!(
+                    "    pub fn {}(mut self, value: impl ::core::convert::Into<{}>) -> Self {{\n",
+                    name, ty
+                )
+            }
+            SetterParam::Items(item) => {
+                code += &format!(
+                    "    pub fn {}<I>(mut self, value: I) -> Self\n    \
+                 where\n        \
+                 I: ::core::iter::IntoIterator<Item = {}>,\n    \
+                 {{\n",
+                    name, item
+                )
+            }
+            SetterParam::IntoItems(ty) => {
+                code += &format!(
+                    "    pub fn {}<I>(mut self, value: I) -> Self\n    \
+                 where\n        \
+                 I: ::core::iter::IntoIterator,\n        \
+                 I::Item: ::core::convert::Into<{}>,\n    \
+                 {{\n",
+                    name, ty
+                )
+            }
+        }
+        code += &format!("        {};\n        self\n    }}\n", assignment);
+        if let Some(setters) = &mut self.setters {
+            setters.push(code);
+        }
+    }
+
+    /// Records the validation checks of a field, if the message implements `Validate`.
+    fn push_validation(
+        &mut self,
+        fq_message_name: &str,
+        field: &FieldDescriptorProto,
+        holder: Holder<'_>,
+    ) {
+        if self.validations.is_none() {
+            return;
+        }
+
+        let default_rules = FieldRules::default();
+        let rules = self
+            .validation_rules
+            .field(fq_message_name, field.name())
+            .unwrap_or(&default_rules);
+        if rules.skip {
+            return;
+        }
+        let path = format!("{}.{}", fq_message_name, field.name());
+        let name = field.name();
+        let member = to_snake(name);
+
+        // The wrapper types are mapped to Rust primitives, and validated as such.
+        let (type_, type_name) = match holder {
+            Holder::Map(value) => (value.r#type(), value.type_name()),
+            _ => (field.r#type(), field.type_name()),
+        };
+        let type_ = match type_ {
+            Type::Message | Type::Group => match &*self.resolve_ident(type_name) {
+                "f64" => Type::Double,
+                "f32" => Type::Float,
+                "i64" => Type::Int64,
+                "u64" => Type::Uint64,
+                "i32" => Type::Int32,
+                "u32" => Type::Uint32,
+                "bool" => Type::Bool,
+                "::prost::alloc::string::String" => Type::String,
+                "::prost::alloc::vec::Vec<u8>" => Type::Bytes,
+                _ => type_,
+            },
+            type_ => type_,
+        };
+        let validates_message = (type_ == Type::Message || type_ == Type::Group)
+            && self.extern_paths.resolve_ident(type_name).is_none()
+            && self.config.validate.get_first(type_name).is_some();
+
+        let item_rules = match holder {
+            Holder::Repeated => rules.items(),
+            Holder::Map(_) => None,
+            _ => Some(rules),
+        };
+        let enum_ty = if type_ == Type::Enum {
+            self.resolve_ident(type_name)
+        } else {
+            String::new()
+        };
+        let checks = item_rules
+            .map(|rules| rules.value_checks(type_, &enum_ty, &path))
+            .unwrap_or_default();
+        if !checks.is_empty() && self.integer_type(fq_message_name, field).is_some() {
+            panic!(
+                "the validation rules of field {} are not supported for fields mapped to `char` \
+                 or `NonZero` types",
+                path
+            );
+        }
+
+        // The checks of a single value, bound to `value` as a reference.
+        let value_code = |indent: usize| {
+            let pad = " ".repeat(indent);
+            let mut code = String::new();
+            if !checks.is_empty() {
+                let binding = match type_ {
+                    Type::String => "let value: &str = value;",
+                    Type::Bytes => "let value: &[u8] = value;",
+                    _ => "let value = *value;",
+                };
+                code += &format!("{}{}\n", pad, binding);
+                for check in &checks {
+                    code += &validation_check(&check.violation, &check.description, name, indent);
+                }
+            }
+            if validates_message {
+                code += &format!(
+                    "{0}if let ::core::result::Result::Err(mut error) = \
+                     ::prost::Validate::validate(value) {{\n\
+                     {0}    error.push(MESSAGE, {1:?});\n\
+                     {0}    return ::core::result::Result::Err(error);\n\
+                     {0}}}\n",
+                    pad, name
+                );
+            }
+            code
+        };
+        let required = |violation: &str| {
+            if rules.required {
+                validation_check(violation, "value is required", name, 8)
+            } else {
+                String::new()
+            }
+        };
+
+        let code = match holder {
+            Holder::Plain(Some(bit)) => {
+                let mut code = required(&format!("!self._presence.get({})", bit));
+                let value_code = value_code(12);
+                if !value_code.is_empty() {
+                    code += &format!(
+                        "        if self._presence.get({}) {{\n            \
+                         let value = &self.{};\n{}        }}\n",
+                        bit, member, value_code
+                    );
+                }
+                code
+            }
+            Holder::Plain(None) => {
+                let empty = match type_ {
+                    Type::String | Type::Bytes => format!("self.{}.is_empty()", member),
+                    Type::Bool => format!("!self.{}", member),
+                    Type::Float | Type::Double => format!("self.{} == 0.0", member),
+                    _ => format!("self.{} == 0", member),
+                };
+                let mut code = required(&empty);
+                let value_code = value_code(12);
+                if !value_code.is_empty() {
+                    code += &format!(
+                        "        {{\n            let value = &self.{};\n{}        }}\n",
+                        member, value_code
+                    );
+                }
+                code
+            }
+            Holder::Option => {
+                let mut code = required(&format!("self.{}.is_none()", member));
+                let value_code = value_code(12);
+                if !value_code.is_empty() {
+                    code += &format!(
+                        "        if let ::core::option::Option::Some(value) = &self.{} {{\n{}        }}\n",
+                        member, value_code
+                    );
+                }
+                code
+            }
+            Holder::Repeated | Holder::Map(_) => {
+                let counts = match holder {
+                    Holder::Repeated => rules.repeated_checks(&path),
+                    _ => rules.map_checks(&path),
+                };
+                let mut code = required(&format!("self.{}.is_empty()", member));
+                let value_code = value_code(16);
+                if !counts.is_empty() || !value_code.is_empty() {
+                    code += &format!("        {{\n            let values = &self.{};\n", member);
+                    for check in &counts {
+                        code += &validation_check(&check.violation, &check.description, name, 12);
+                    }
+                    if !value_code.is_empty() {
+                        let iter = match holder {
+                            Holder::Map(_) => "values.values()",
+                            _ => "values",
+                        };
+                        code += &format!(
+                            "            for value in {} {{\n{}            }}\n",
+                            iter, value_code
+                        );
+                    }
+                    code += "        }\n";
+                }
+                code
+            }
+            Holder::Variant { member, variant } => {
+                let value_code = value_code(12);
+                if value_code.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        "        if let ::core::option::Option::Some({}(value)) = &self.{} {{\n{}        }}\n",
+                        variant, member, value_code
+                    )
+                }
+            }
+        };
+
+        if !code.is_empty() {
+            if let Some(validations) = &mut self.validations {
+                validations.push(code);
+            }
+        }
+    }
+
+    /// Records the statement generating a field, if the message implements `Arbitrary`. `value`
+    /// is the expression generating a single value of the field, or an entry of a map field.
+    fn push_arbitrary(&mut self, field: &FieldDescriptorProto, holder: Holder<'_>, value: &str) {
+        let statements = match &mut self.arbitrary {
+            Some(statements) => statements,
+            None => return,
+        };
+        let member = to_snake(field.name());
+        let required = field.label() == Label::Required;
+        let code = match holder {
+            Holder::Plain(Some(bit)) if required => format!(
+                "        message.{} = {};\n        message._presence.set({});\n",
+                member, value, bit
+            ),
+            Holder::Plain(Some(bit)) => format!(
+                "        if u.arbitrary()? {{\n            \
+                 message.{} = {};\n            \
+                 message._presence.set({});\n        \
+                 }}\n",
+                member, value, bit
+            ),
+            Holder::Plain(None) => format!("        message.{} = {};\n", member, value),
+            Holder::Option if required => format!(
+                "        message.{} = ::core::option::Option::Some({});\n",
+                member, value
+            ),
+            Holder::Option => format!(
+                "        if u.arbitrary()? {{\n            \
+                 message.{} = ::core::option::Option::Some({});\n        \
+                 }}\n",
+                member, value
+            ),
+            Holder::Repeated => format!(
+                "        while u.arbitrary()? {{\n            \
+                 message.{}.push({});\n        \
+                 }}\n",
+                member, value
+            ),
+            Holder::Map(_) => format!(
+                "        while u.arbitrary()? {{\n            \
+                 let (key, value) = {};\n            \
+                 ::prost::ProstMap::insert(&mut message.{}, key, value);\n        \
+                 }}\n",
+                value, member
+            ),
+            Holder::Variant { .. } => unreachable!("oneofs are generated by their enum"),
+        };
+        statements.push(code);
+    }
+
+    /// Returns the expression generating an arbitrary value of a field of Rust type `ty`, from
+    /// the `Unstructured` bound to `u`.
+    fn arbitrary_value(&self, field: &FieldDescriptorProto, ty: &str, boxed: bool) -> String {
+        const ARBITRARY: &str = "::arbitrary::Arbitrary::arbitrary(u)?";
+        let value = match field.r#type() {
+            Type::Message | Type::Group => "::prost_types::arbitrary::nested(u)?".to_string(),
+            Type::Enum => format!(
+                "<{} as ::arbitrary::Arbitrary>::arbitrary(u)? as i32",
+                self.resolve_ident(field.type_name())
+            ),
+            Type::String if ty != StringType::String.rust_type() => format!(
+                "::core::convert::From::from(<{} as ::arbitrary::Arbitrary>::arbitrary(u)?)",
+                StringType::String.rust_type()
+            ),
+            Type::Bytes if ty != BytesType::Vec.rust_type() => format!(
+                "::core::convert::From::from(<{} as ::arbitrary::Arbitrary>::arbitrary(u)?)",
+                BytesType::Vec.rust_type()
+            ),
+            _ => ARBITRARY.to_string(),
+        };
+        if boxed {
+            format!("::prost::alloc::boxed::Box::new({})", value)
+        } else {
+            value
+        }
+    }
+
+    /// Returns whether the message or enum implements `arbitrary::Arbitrary`.
+    fn implements_arbitrary(&self, fq_name: &str) -> bool {
+        self.config.arbitrary.get_first(fq_name).is_some()
+            || self.config.proptest_arbitrary.get_first(fq_name).is_some()
+    }
+
+    /// Returns the fields of a message which gets iterative `Clone`, `PartialEq` and `Drop`
+    /// implementations, or `None` if the message is not matched or is not recursive.
+    fn recursive_fields(
+        &self,
+        fq_message_name: &str,
+        message_name: &str,
+        fields: &[(FieldDescriptorProto, usize)],
+        map_types: &HashMap<String, (FieldDescriptorProto, FieldDescriptorProto)>,
+        oneofs: &[OneofDescriptorProto],
+        oneof_fields: &MultiMap<i32, (FieldDescriptorProto, usize)>,
+    ) -> Option<Vec<RecursiveField>> {
+        if !self.iterative_recursion(fq_message_name) {
+            return None;
+        }
+
+        // A field holds recursive children if its message type contains this message, and also
+        // gets iterative implementations.
+        let is_child = |field: &FieldDescriptorProto| {
+            field.r#type() == Type::Message
+                && self.iterative_recursion(field.type_name())
+                && self
+                    .message_graph
+                    .is_contained(field.type_name(), fq_message_name)
+        };
+
+        let mut recursive_fields = Vec::new();
+        for &(ref field, _) in fields {
+            let name = to_snake(field.name());
+            let map_value = field
+                .type_name
+                .as_ref()
+                .and_then(|type_name| map_types.get(type_name))
+                .map(|&(_, ref value)| value);
+            recursive_fields.push(match map_value {
+                Some(value) if is_child(value) => RecursiveField::Map(name),
+                Some(_) => RecursiveField::Plain(name),
+                None if is_child(field) && field.label() == Label::Repeated => {
+                    RecursiveField::Repeated(name)
+                }
+                None if is_child(field) => {
+                    RecursiveField::Optional(name, self.boxed(field, fq_message_name, None))
+                }
+                None => RecursiveField::Plain(name),
+            });
+        }
+
+        for (idx, oneof) in oneofs.iter().enumerate() {
+            let fields = match oneof_fields.get_vec(&(idx as i32)) {
+                Some(fields) => fields,
+                None => continue,
+            };
+            let name = to_snake(oneof.name());
+            let variants = fields
+                .iter()
+                .filter(|&&(ref field, _)| is_child(field))
+                .map(|&(ref field, _)| {
+                    (
+                        to_upper_camel(field.name()),
+                        self.boxed(field, fq_message_name, Some(oneof.name())),
+                    )
+                })
+                .collect::<Vec<_>>();
+            recursive_fields.push(if variants.is_empty() {
+                RecursiveField::Plain(name)
+            } else {
+                let path = format!(
+                    "{}::{}",
+                    to_snake(message_name),
+                    to_upper_camel(oneof.name())
+                );
+                RecursiveField::Oneof(name, path, variants)
+            });
+        }
+
+        if recursive_fields
+            .iter()
+            .all(|field| matches!(field, RecursiveField::Plain(_)))
+        {
+            return None;
+        }
+        Some(recursive_fields)
+    }
+
+    /// Appends implementations of `Recursive`, `Clone`, `PartialEq` and `Drop` which walk the
+    /// recursive children of the message with a work-list, rather than recursing.
+    fn append_recursive_impls(&mut self, message_name: &str, fields: &[RecursiveField]) {
+        const RECURSIVE: &str = "::prost::recursive::Recursive";
+        const BOX: &str = "::prost::alloc::boxed::Box";
+        const SOME: &str = "::core::option::Option::Some";
+        const NONE: &str = "::core::option::Option::None";
+
+        let message_name = to_upper_camel(message_name);
+        let boxed_children = format!("::prost::alloc::vec::Vec<{}<dyn ({})>>", BOX, RECURSIVE);
+        // Borrows a child from a binding, and moves a child from a binding into a box.
+        let deref = |boxed: bool, name: &str| {
+            if boxed {
+                format!("&**{}", name)
+            } else {
+                name.to_string()
+            }
+        };
+        let take = |boxed: bool| {
+            if boxed {
+                "child".to_string()
+            } else {
+                format!("{}::new(child)", BOX)
+            }
+        };
+
+        let mut children = String::new();
+        let mut take_children = String::new();
+        let mut clone = String::new();
+        let mut eq = String::new();
+        for field in fields {
+            match *field {
+                RecursiveField::Plain(ref name) => {
+                    clone += &format!("{0}: ::core::clone::Clone::clone(&self.{0}),\n", name);
+                    eq += &format!("if self.{0} != other.{0} {{\n    return false;\n}}\n", name);
+                }
+                RecursiveField::Optional(ref name, boxed) => {
+                    children += &format!(
+                        "if let {}(child) = &self.{} {{\n    children.push({});\n}}\n",
+                        SOME,
+                        name,
+                        deref(boxed, "child")
+                    );
+                    take_children += &format!(
+                        "if let {}(child) = self.{}.take() {{\n    children.push({});\n}}\n",
+                        SOME,
+                        name,
+                        take(boxed)
+                    );
+                    clone += &format!(
+                        "{}: self.{}.as_ref().map(|_| {}::prost::recursive::pop(children)),\n",
+                        name,
+                        name,
+                        if boxed { "" } else { "*" }
+                    );
+                    eq += &format!(
+                        "match (&self.{1}, &other.{1}) {{\n    \
+                         ({0}(a), {0}(b)) => pairs.push(({2}, {3})),\n    \
+                         ({4}, {4}) => {{}}\n    \
+                         _ => return false,\n\
+                         }}\n",
+                        SOME,
+                        name,
+                        deref(boxed, "a"),
+                        deref(boxed, "b"),
+                        NONE
+                    );
+                }
+                RecursiveField::Repeated(ref name) => {
+                    children += &format!(
+                        "for child in &self.{} {{\n    children.push(child);\n}}\n",
+                        name
+                    );
+                    take_children += &format!(
+                        "for child in self.{}.drain(..) {{\n    children.push({}::new(child));\n}}\n",
+                        name, BOX
+                    );
+                    clone += &format!(
+                        "{0}: self.{0}.iter().map(|_| *::prost::recursive::pop(children)).collect(),\n",
+                        name
+                    );
+                    eq += &format!(
+                        "if self.{0}.len() != other.{0}.len() {{\n    return false;\n}}\n\
+                         for (a, b) in self.{0}.iter().zip(&other.{0}) {{\n    \
+                         pairs.push((a, b));\n\
+                         }}\n",
+                        name
+                    );
+                }
+                RecursiveField::Map(ref name) => {
+                    children += &format!(
+                        "for child in self.{}.values() {{\n    children.push(child);\n}}\n",
+                        name
+                    );
+                    take_children += &format!(
+                        "for (_, child) in ::core::mem::take(&mut self.{}) {{\n    \
+                         children.push({}::new(child));\n\
+                         }}\n",
+                        name, BOX
+                    );
+                    clone += &format!(
+                        "{0}: self.{0}.keys().map(|key| (::core::clone::Clone::clone(key), \
+                         *::prost::recursive::pop(children))).collect(),\n",
+                        name
+                    );
+                    eq += &format!(
+                        "if self.{0}.len() != other.{0}.len() {{\n    return false;\n}}\n\
+                         for (key, a) in &self.{0} {{\n    \
+                         match other.{0}.get(key) {{\n        \
+                         {1}(b) => pairs.push((a, b)),\n        \
+                         {2} => return false,\n    \
+                         }}\n\
+                         }}\n",
+                        name, SOME, NONE
+                    );
+                }
+                RecursiveField::Oneof(ref name, ref path, ref variants) => {
+                    for &(ref variant, boxed) in variants {
+                        children += &format!(
+                            "if let {}({}::{}(child)) = &self.{} {{\n    children.push({});\n}}\n",
+                            SOME,
+                            path,
+                            variant,
+                            name,
+                            deref(boxed, "child")
+                        );
+                    }
+
+                    if let [(ref variant, boxed)] = variants[..] {
+                        take_children += &format!(
+                            "if let {}({}::{}(child)) = self.{}.take() {{\n    children.push({});\n}}\n",
+                            SOME,
+                            path,
+                            variant,
+                            name,
+                            take(boxed)
+                        );
+                    } else {
+                        take_children += &format!("match self.{}.take() {{\n", name);
+                        for &(ref variant, boxed) in variants {
+                            take_children += &format!(
+                                "    {}({}::{}(child)) => children.push({}),\n",
+                                SOME,
+                                path,
+                                variant,
+                                take(boxed)
+                            );
+                        }
+                        take_children += "    _ => {}\n}\n";
+                    }
+
+                    clone += &format!("{}: match &self.{} {{\n", name, name);
+                    for &(ref variant, boxed) in variants {
+                        clone += &format!(
+                            "    {0}({1}::{2}(_)) => {0}({1}::{2}({3}::prost::recursive::pop(children))),\n",
+                            SOME,
+                            path,
+                            variant,
+                            if boxed { "" } else { "*" }
+                        );
+                    }
+                    clone += "    other => ::core::clone::Clone::clone(other),\n},\n";
+
+                    eq += &format!("match (&self.{0}, &other.{0}) {{\n", name);
+                    for &(ref variant, boxed) in variants {
+                        eq += &format!(
+                            "    ({0}({1}::{2}(a)), {0}({1}::{2}(b))) => pairs.push(({3}, {4})),\n",
+                            SOME,
+                            path,
+                            variant,
+                            deref(boxed, "a"),
+                            deref(boxed, "b")
+                        );
+                    }
+                    eq += &format!(
+                        "    {} => return false,\n",
+                        variants
+                            .iter()
+                            .map(|&(ref variant, _)| format!(
+                                "({0}({1}::{2}(_)), _) | (_, {0}({1}::{2}(_)))",
+                                SOME, path, variant
+                            ))
+                            .join(" | ")
+                    );
+                    eq += "    (a, b) => {\n        if a != b {\n            return false;\n        }\n    }\n}\n";
+                }
+            }
+        }
+
+        let indent = |code: &str, depth: usize| {
+            code.lines()
+                .map(|line| format!("{}{}\n", "    ".repeat(depth), line))
+                .collect::<String>()
+        };
+
+        // Trait objects are parenthesized, since edition 2015 parses `dyn ::path` as a path.
+        let mut code = format!(
+            "#[allow(unused_parens)]\nimpl {} for {} {{\n",
+            RECURSIVE, message_name
+        );
+        code += &format!(
+            "    fn children<'a>(&'a self, children: &mut ::prost::alloc::vec::Vec<&'a dyn ({})>) {{\n",
+            RECURSIVE
+        );
+        code += &indent(&children, 2);
+        code += "    }\n";
+        code += &format!(
+            "    fn take_children(&mut self, children: &mut {}) {{\n",
+            boxed_children
+        );
+        code += &indent(&take_children, 2);
+        code += "    }\n";
+        code += &format!(
+            "    fn clone_with_children(&self, children: &mut {}) -> {}<dyn ({})> {{\n",
+            boxed_children, BOX, RECURSIVE
+        );
+        code += &format!("        {}::new({} {{\n", BOX, message_name);
+        code += &indent(&clone, 3);
+        code += "        })\n";
+        code += "    }\n";
+        code += &format!(
+            "    fn eq_shallow<'a>(&'a self, other: &'a dyn ({0}), \
+             pairs: &mut ::prost::alloc::vec::Vec<(&'a dyn ({0}), &'a dyn ({0}))>) -> bool {{\n",
+            RECURSIVE
+        );
+        code += &format!(
+            "        let other = match {}::as_any(other).downcast_ref::<Self>() {{\n",
+            RECURSIVE
+        );
+        code += &format!("            {}(other) => other,\n", SOME);
+        code += &format!("            {} => return false,\n", NONE);
+        code += "        };\n";
+        code += &indent(&eq, 2);
+        code += "        true\n";
+        code += "    }\n";
+        code += "    fn as_any(&self) -> &dyn (::core::any::Any) {\n        self\n    }\n";
+        code += &format!(
+            "    fn into_any(self: {0}<Self>) -> {0}<dyn (::core::any::Any)> {{\n        self\n    }}\n",
+            BOX
+        );
+        code += "}\n";
+        code += &format!(
+            "impl ::core::clone::Clone for {} {{\n    \
+             fn clone(&self) -> Self {{\n        ::prost::recursive::clone(self)\n    }}\n\
+             }}\n",
+            message_name
+        );
+        code += &format!(
+            "impl ::core::cmp::PartialEq for {} {{\n    \
+             fn eq(&self, other: &Self) -> bool {{\n        ::prost::recursive::eq(self, other)\n    }}\n\
+             }}\n",
+            message_name
+        );
+        code += &format!(
+            "impl ::core::ops::Drop for {} {{\n    \
+             fn drop(&mut self) {{\n        ::prost::recursive::drop(self)\n    }}\n\
+             }}\n",
+            message_name
+        );
+
+        self.buf.push_str(&indent(&code, self.depth as usize));
+    }
+
     fn append_type_attributes(&mut self, fq_message_name: &str) {
         assert_eq!(b'.', fq_message_name.as_bytes()[0]);
         for attribute in self.config.type_attributes.get(fq_message_name) {
@@ -282,18 +1636,288 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    /// Returns the traits requested with `Config::derive` for the message or enum, in order.
+    fn requested_derives(&self, fq_name: &str) -> Vec<&str> {
+        self.config
+            .derives
+            .get(fq_name)
+            .flat_map(|derives| derives.split(','))
+            .map(str::trim)
+            .filter(|derive| !derive.is_empty())
+            .unique()
+            .collect()
+    }
+
+    /// Returns the derives requested with `Config::derive` which the message and its oneofs
+    /// implement, to append to their derives.
+    fn message_derives(&self, fq_message_name: &str) -> String {
+        self.requested_derives(fq_message_name)
+            .into_iter()
+            .filter(|&derive| {
+                let name = derive_name(derive);
+                !matches!(name, "Clone" | "PartialEq" | "Debug" | "Default")
+                    && (!CHECKED_DERIVES.contains(&name) || self.derives(fq_message_name, name))
+            })
+            .map(|derive| format!(", {}", derive))
+            .collect()
+    }
+
+    /// Returns `true` if the message derives the trait, one of `CHECKED_DERIVES`.
+    fn derives(&self, fq_message_name: &str, derive: &str) -> bool {
+        let derive = match CHECKED_DERIVES.iter().find(|&&checked| checked == derive) {
+            Some(derive) => *derive,
+            None => return false,
+        };
+        if !self.derived_messages.borrow().contains_key(derive) {
+            let messages = self.derived_messages(derive);
+            self.derived_messages.borrow_mut().insert(derive, messages);
+        }
+        self.derived_messages.borrow()[derive].contains(fq_message_name)
+    }
+
+    /// Returns the messages which derive the trait: the largest set of the messages requesting it
+    /// whose fields all implement it, given that the messages of the set implement it.
+    fn derived_messages(&self, derive: &str) -> HashSet<String> {
+        let mut messages = self
+            .message_graph
+            .messages()
+            .filter(|&(name, _)| {
+                self.extern_paths.resolve_ident(name).is_none()
+                    && self
+                        .requested_derives(name)
+                        .into_iter()
+                        .any(|requested| derive_name(requested) == derive)
+            })
+            .map(|(name, _)| name.to_string())
+            .collect::<HashSet<_>>();
+        loop {
+            let excluded = messages
+                .iter()
+                .filter(|&name| {
+                    let message = self.message_graph.get_message(name).unwrap();
+                    !message
+                        .field
+                        .iter()
+                        .all(|field| self.field_derives(name, field, derive, &messages))
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            if excluded.is_empty() {
+                return messages;
+            }
+            for name in excluded {
+                messages.remove(&name);
+            }
+        }
+    }
+
+    /// Returns `true` if the type of the field implements the trait, given the `messages` which
+    /// derive it.
+    fn field_derives(
+        &self,
+        fq_message_name: &str,
+        field: &FieldDescriptorProto,
+        derive: &str,
+        messages: &HashSet<String>,
+    ) -> bool {
+        let type_ = field.r#type();
+        let repeated = field.label() == Label::Repeated;
+        if derive == "Copy"
+            && (repeated
+                || matches!(
+                    type_,
+                    Type::String | Type::Bytes | Type::Message | Type::Group
+                ))
+        {
+            return false;
+        }
+
+        match type_ {
+            // NaN isn't equal to itself, so floats only implement `PartialEq` and `PartialOrd`.
+            Type::Float | Type::Double => !matches!(derive, "Eq" | "Hash" | "Ord"),
+            Type::Message | Type::Group => {
+                let type_name = field.type_name();
+                let map_entry = self.message_graph.get_message(type_name).filter(|entry| {
+                    repeated
+                        && entry
+                            .options
+                            .as_ref()
+                            .map_or(false, |options| options.map_entry())
+                });
+                if let Some(entry) = map_entry {
+                    // `HashMap` and `IndexMap` are neither ordered nor hashable.
+                    let btree_map = self
+                        .config
+                        .map_type
+                        .get_first_field(fq_message_name, field.name())
+                        == Some(&MapType::BTreeMap);
+                    return (btree_map || derive == "Eq")
+                        && entry
+                            .field
+                            .iter()
+                            .all(|field| self.field_derives(type_name, field, derive, messages));
+                }
+                self.extern_paths.resolve_ident(type_name).is_none() && messages.contains(type_name)
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns the serde derives to append to the derives of generated messages and oneofs.
+    fn serde_derives(&self) -> &'static str {
+        if self.config.enable_serde_json {
+            ", ::serde::Serialize, ::serde::Deserialize"
+        } else {
+            ""
+        }
+    }
+
+    /// Appends the serde attributes which map the field as the Protobuf JSON mapping requires.
+    fn append_serde_attributes(
+        &mut self,
+        fq_message_name: &str,
+        field: &FieldDescriptorProto,
+        integer_type: Option<IntegerType>,
+        shape: SerdeShape<'_>,
+    ) {
+        if !self.config.enable_serde_json {
+            return;
+        }
+
+        let json_name = json_name(field);
+        let mut attributes = vec![format!("rename = {:?}", json_name)];
+        if json_name != field.name() {
+            attributes.push(format!("alias = {:?}", field.name()));
+        }
+        match shape {
+            SerdeShape::Plain
+            | SerdeShape::Repeated
+            | SerdeShape::Map(_)
+            | SerdeShape::EnumMap(..) => attributes
+                .push("skip_serializing_if = \"::prost_types::serde::is_default\"".to_string()),
+            SerdeShape::Optional => attributes
+                .push("skip_serializing_if = \"::prost_types::serde::is_none\"".to_string()),
+            SerdeShape::Required | SerdeShape::Variant => {}
+        }
+        attributes.extend(self.serde_with(fq_message_name, field, integer_type, shape));
+
+        self.push_indent();
+        self.buf
+            .push_str(&format!("#[serde({})]\n", attributes.join(", ")));
+    }
+
+    /// Returns the attribute wiring the field to its helper in `prost_types::serde`, or `None` if
+    /// the field's own `Serialize` and `Deserialize` implementations follow the JSON mapping.
+    fn serde_with(
+        &self,
+        fq_message_name: &str,
+        field: &FieldDescriptorProto,
+        integer_type: Option<IntegerType>,
+        shape: SerdeShape<'_>,
+    ) -> Option<String> {
+        // Enum fields are `i32`s, so their helpers take the enum type as well.
+        let enumeration = |module: &str, ty: &str, inferred: &str| {
+            format!(
+                "serialize_with = \"::prost_types::serde::{0}::serialize::<{1}, {2}>\", \
+                 deserialize_with = \"::prost_types::serde::{0}::deserialize::<{1}, {2}>\"",
+                module, ty, inferred
+            )
+        };
+        match shape {
+            SerdeShape::Map(module) => {
+                return Some(format!("with = \"::prost_types::serde::{}\"", module));
+            }
+            SerdeShape::EnumMap(module, ty) => return Some(enumeration(module, ty, "_, _, _")),
+            _ => {}
+        }
+        let suffix = match shape {
+            SerdeShape::Optional => "::option",
+            SerdeShape::Repeated => "::repeated",
+            _ => "",
+        };
+        let int64 = || {
+            if self
+                .config
+                .int64_numbers
+                .get_first_field(fq_message_name, field.name())
+                .is_some()
+            {
+                "int64::number"
+            } else {
+                "int64"
+            }
+        };
+        let module = match field.r#type() {
+            // `NonZeroU32` and `NonZeroU64` are serialized as numbers by serde.
+            _ if integer_type == Some(IntegerType::NonZero) => return None,
+            Type::Uint32 if integer_type == Some(IntegerType::Char) => {
+                if shape == SerdeShape::Repeated {
+                    return None;
+                }
+                "char"
+            }
+            Type::Int32 | Type::Uint32 | Type::Sint32 | Type::Fixed32 | Type::Sfixed32 => "int32",
+            Type::Int64 | Type::Uint64 | Type::Sint64 | Type::Fixed64 | Type::Sfixed64 => int64(),
+            Type::Float | Type::Double => "float",
+            Type::Bytes => "vec_u8",
+            Type::Enum => {
+                let ty = self.resolve_ident(field.type_name());
+                let module = format!("{}{}", self.enum_module(fq_message_name, field), suffix);
+                return Some(enumeration(&module, &ty, "_"));
+            }
+            // Well-known types, including the wrapper types which are mapped to Rust primitives.
+            Type::Message => match &*self.resolve_ident(field.type_name()) {
+                "::prost_types::Timestamp" => "timestamp",
+                "::prost_types::Duration" => "duration",
+                "i32" | "u32" => "int32",
+                "i64" | "u64" => int64(),
+                "f32" | "f64" => "float",
+                "::prost::alloc::vec::Vec<u8>" => "vec_u8",
+                "()" => "empty",
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(format!(
+            "with = \"::prost_types::serde::{}{}\"",
+            module, suffix
+        ))
+    }
+
+    /// Returns the module of `prost_types::serde` serializing the values of an enum field.
+    fn enum_module(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> &'static str {
+        if self
+            .config
+            .enum_numbers
+            .get_first_field(fq_message_name, field.name())
+            .is_some()
+        {
+            "enumeration::number"
+        } else {
+            "enumeration"
+        }
+    }
+
     fn append_field(&mut self, fq_message_name: &str, field: FieldDescriptorProto) {
         let type_ = field.r#type();
         let repeated = field.label == Some(Label::Repeated as i32);
         let deprecated = self.deprecated(&field);
-        let optional = self.optional(&field);
-        let ty = self.resolve_type(&field, fq_message_name);
+        let integer_type = self.integer_type(fq_message_name, &field);
+        let optional =
+            self.optional(&field) || (!repeated && integer_type == Some(IntegerType::NonZero));
+        let ty = match integer_type {
+            Some(integer_type) => integer_type.rust_type(field.r#type()).to_owned(),
+            None => self.resolve_type(&field, fq_message_name),
+        };
 
-        let boxed = !repeated
-            && (type_ == Type::Message || type_ == Type::Group)
-            && self
-                .message_graph
-                .is_nested(field.type_name(), fq_message_name);
+        let boxed = self.boxed(&field, fq_message_name, None);
+        let presence_bit = match self.presence_bits {
+            Some(bit) if self.has_presence_bit(fq_message_name, &field) => {
+                self.presence_bits = Some(bit + 1);
+                Some(bit)
+            }
+            _ => None,
+        };
 
         debug!(
             "    field: {:?}, type: {:?}, boxed: {}",
@@ -324,14 +1948,31 @@ impl<'a> CodeGenerator<'a> {
             self.buf
                 .push_str(&format!("={:?}", bytes_type.annotation()));
         }
+        if type_ == Type::String {
+            if let Some(annotation) = self.string_type(fq_message_name, &field).annotation() {
+                self.buf.push_str(&format!("={:?}", annotation));
+            }
+        }
+        if let Some(integer_type) = integer_type {
+            self.buf
+                .push_str(&format!("={:?}", integer_type.annotation()));
+        }
 
         match field.label() {
             Label::Optional => {
                 if optional {
                     self.buf.push_str(", optional");
                 }
+                if let Some(bit) = presence_bit {
+                    self.buf.push_str(&format!(", presence_bit=\"{}\"", bit));
+                }
+            }
+            Label::Required => {
+                self.buf.push_str(", required");
+                if let Some(bit) = presence_bit {
+                    self.buf.push_str(&format!(", presence_bit=\"{}\"", bit));
+                }
             }
-            Label::Required => self.buf.push_str(", required"),
             Label::Repeated => {
                 self.buf.push_str(", repeated");
                 if can_pack(&field)
@@ -384,14 +2025,26 @@ impl<'a> CodeGenerator<'a> {
         }
 
         self.buf.push_str("\")]\n");
+        let shape = if repeated {
+            SerdeShape::Repeated
+        } else if optional {
+            SerdeShape::Optional
+        } else if field.label() == Label::Required {
+            SerdeShape::Required
+        } else {
+            SerdeShape::Plain
+        };
+        self.append_serde_attributes(fq_message_name, &field, integer_type, shape);
         self.append_field_attributes(fq_message_name, field.name());
         self.push_indent();
         self.buf.push_str("pub ");
         self.buf.push_str(&to_snake(field.name()));
         self.buf.push_str(": ");
+        // Fields with a presence bit hold their value directly.
+        let option = optional && presence_bit.is_none();
         if repeated {
             self.buf.push_str("::prost::alloc::vec::Vec<");
-        } else if optional {
+        } else if option {
             self.buf.push_str("::core::option::Option<");
         }
         if boxed {
@@ -401,10 +2054,78 @@ impl<'a> CodeGenerator<'a> {
         if boxed {
             self.buf.push('>');
         }
-        if repeated || optional {
+        if repeated || option {
             self.buf.push('>');
         }
         self.buf.push_str(",\n");
+
+        if self.setters.is_some() {
+            let (param, mut value) = match (type_, repeated) {
+                (Type::Enum, true) => (
+                    SetterParam::Items(self.resolve_ident(field.type_name())),
+                    "value.into_iter().map(|value| value as i32).collect()".to_string(),
+                ),
+                (_, true) => (
+                    SetterParam::IntoItems(ty.clone()),
+                    "value.into_iter().map(::core::convert::Into::into).collect()".to_string(),
+                ),
+                (Type::Enum, false) => (
+                    SetterParam::Value(self.resolve_ident(field.type_name())),
+                    "value as i32".to_string(),
+                ),
+                (_, false) => (SetterParam::Into(ty.clone()), "value.into()".to_string()),
+            };
+            if boxed {
+                value = format!("::prost::alloc::boxed::Box::new({})", value);
+            }
+            let member = to_snake(field.name());
+            let assignment = if let (Some(bit), Type::Message) | (Some(bit), Type::Group) =
+                (presence_bit, type_)
+            {
+                format!(
+                    "self.inner.{} = {};\n        self.inner._presence.set({})",
+                    member, value, bit
+                )
+            } else if presence_bit.is_some() {
+                // The setter generated by `prost-derive` sets the presence bit, and takes enums
+                // as their Rust type.
+                if type_ == Type::Enum {
+                    value = "value".to_string();
+                }
+                let setter = format!("set_{}", member.trim_start_matches("r#"));
+                format!("self.inner.{}({})", setter, value)
+            } else if optional {
+                format!(
+                    "self.inner.{} = ::core::option::Option::Some({})",
+                    member, value
+                )
+            } else {
+                format!("self.inner.{} = {}", member, value)
+            };
+            self.push_setter(&field, param, &assignment);
+        }
+        // `prost-derive` generates all of the accessors of scalar fields with a presence bit,
+        // and the `has_` and `clear_` methods of message fields with one.
+        match presence_bit {
+            None => self.push_field_accessors(&field, &ty, repeated, optional, boxed),
+            Some(bit) if type_ == Type::Message && optional => {
+                self.push_present_message_accessors(&field, &ty, bit, boxed)
+            }
+            Some(_) => {}
+        }
+
+        let holder = if repeated {
+            Holder::Repeated
+        } else if option {
+            Holder::Option
+        } else {
+            Holder::Plain(presence_bit)
+        };
+        self.push_validation(fq_message_name, &field, holder);
+        if self.arbitrary.is_some() {
+            let value = self.arbitrary_value(&field, &ty, boxed);
+            self.push_arbitrary(&field, holder, &value);
+        }
     }
 
     fn append_map_field(
@@ -414,8 +2135,13 @@ impl<'a> CodeGenerator<'a> {
         key: &FieldDescriptorProto,
         value: &FieldDescriptorProto,
     ) {
-        let key_ty = self.resolve_type(key, fq_message_name);
-        let value_ty = self.resolve_type(value, fq_message_name);
+        // Map keys and values are always `String`s, whatever the configured string type.
+        let resolve_type = |field: &FieldDescriptorProto| match field.r#type() {
+            Type::String => StringType::String.rust_type().to_owned(),
+            _ => self.resolve_type(field, fq_message_name),
+        };
+        let key_ty = resolve_type(key);
+        let value_ty = resolve_type(value);
 
         debug!(
             "    map field: {:?}, key type: {:?}, value type: {:?}",
@@ -431,18 +2157,50 @@ impl<'a> CodeGenerator<'a> {
             .config
             .map_type
             .get_first_field(fq_message_name, field.name())
-            .copied()
+            .cloned()
             .unwrap_or_default();
         let key_tag = self.field_type_tag(key);
         let value_tag = self.map_value_type_tag(value);
+        let map_type_attr = match &map_type {
+            MapType::Custom(path) => format!(", map_type=\"{}\"", path),
+            _ => String::new(),
+        };
 
         self.buf.push_str(&format!(
-            "#[prost({}=\"{}, {}\", tag=\"{}\")]\n",
+            "#[prost({}=\"{}, {}\"{}, tag=\"{}\")]\n",
             map_type.annotation(),
             key_tag,
             value_tag,
+            map_type_attr,
             field.number()
         ));
+        let map_keys = |paths: &PathMap<()>| {
+            key.r#type() == Type::String
+                && paths
+                    .get_first_field(fq_message_name, field.name())
+                    .is_some()
+        };
+        let map_module = if map_keys(&self.config.timestamp_map_keys) {
+            "timestamp_key"
+        } else if map_keys(&self.config.duration_map_keys) {
+            "duration_key"
+        } else if value_ty == "()" {
+            "empty::map"
+        } else {
+            "map"
+        };
+        let enum_values = match value.r#type() {
+            Type::Enum if map_module == "map" => Some((
+                format!("{}::map", self.enum_module(fq_message_name, &field)),
+                self.resolve_ident(value.type_name()),
+            )),
+            _ => None,
+        };
+        let shape = match &enum_values {
+            Some((module, ty)) => SerdeShape::EnumMap(module, ty),
+            None => SerdeShape::Map(map_module),
+        };
+        self.append_serde_attributes(fq_message_name, &field, None, shape);
         self.append_field_attributes(fq_message_name, field.name());
         self.push_indent();
         self.buf.push_str(&format!(
@@ -452,6 +2210,25 @@ impl<'a> CodeGenerator<'a> {
             key_ty,
             value_ty
         ));
+
+        self.push_setter(
+            &field,
+            SetterParam::Items(format!("({}, {})", key_ty, value_ty)),
+            &format!(
+                "self.inner.{} = value.into_iter().collect()",
+                to_snake(field.name())
+            ),
+        );
+        self.push_field_accessors(&field, "", true, false, false);
+        self.push_validation(fq_message_name, &field, Holder::Map(value));
+        if self.arbitrary.is_some() {
+            let entry = format!(
+                "({}, {})",
+                self.arbitrary_value(key, &key_ty, false),
+                self.arbitrary_value(value, &value_ty, false)
+            );
+            self.push_arbitrary(&field, Holder::Map(value), &entry);
+        }
     }
 
     fn append_oneof_field(
@@ -476,6 +2253,12 @@ impl<'a> CodeGenerator<'a> {
                 .map(|&(ref field, _)| field.number())
                 .join(", ")
         ));
+        if self.config.enable_serde_json {
+            self.push_indent();
+            self.buf.push_str(
+                "#[serde(flatten, deserialize_with = \"::prost_types::serde::oneof::deserialize\")]\n",
+            );
+        }
         self.append_field_attributes(fq_message_name, oneof.name());
         self.push_indent();
         self.buf.push_str(&format!(
@@ -483,6 +2266,68 @@ impl<'a> CodeGenerator<'a> {
             to_snake(oneof.name()),
             name
         ));
+
+        let member = to_snake(oneof.name());
+        if self.validations.is_some() {
+            if self
+                .validation_rules
+                .is_required_oneof(fq_message_name, oneof.name())
+            {
+                let check = validation_check(
+                    &format!("self.{}.is_none()", member),
+                    "value is required",
+                    oneof.name(),
+                    8,
+                );
+                if let Some(validations) = &mut self.validations {
+                    validations.push(check);
+                }
+            }
+            for (field, _) in fields {
+                let variant = format!("{}::{}", name, to_upper_camel(field.name()));
+                self.push_validation(
+                    fq_message_name,
+                    field,
+                    Holder::Variant {
+                        member: &member,
+                        variant: &variant,
+                    },
+                );
+            }
+        }
+
+        // The oneof enum implements `Arbitrary` along with the message.
+        if let Some(arbitrary) = &mut self.arbitrary {
+            arbitrary.push(format!("        message.{} = u.arbitrary()?;\n", member));
+        }
+
+        if self.setters.is_none() && self.accessors.is_none() {
+            return;
+        }
+        for (field, _) in fields {
+            let type_ = field.r#type();
+            let ty = self.resolve_type(field, fq_message_name);
+            let boxed = self.boxed(field, fq_message_name, Some(oneof.name()));
+            let variant = format!("{}::{}", name, to_upper_camel(field.name()));
+
+            let (param, mut value) = if type_ == Type::Enum {
+                (
+                    SetterParam::Value(self.resolve_ident(field.type_name())),
+                    "value as i32".to_string(),
+                )
+            } else {
+                (SetterParam::Into(ty.clone()), "value.into()".to_string())
+            };
+            if boxed {
+                value = format!("::prost::alloc::boxed::Box::new({})", value);
+            }
+            let assignment = format!(
+                "self.inner.{} = ::core::option::Option::Some({}({}))",
+                member, variant, value
+            );
+            self.push_setter(field, param, &assignment);
+            self.push_oneof_field_accessors(field, &member, &variant, &ty, boxed);
+        }
     }
 
     fn append_oneof(
@@ -501,38 +2346,54 @@ impl<'a> CodeGenerator<'a> {
         let oneof_name = format!("{}.{}", fq_message_name, oneof.name());
         self.append_type_attributes(&oneof_name);
         self.push_indent();
-        self.buf
-            .push_str("#[derive(Clone, PartialEq, ::prost::Oneof)]\n");
+        self.buf.push_str(&format!(
+            "#[derive(Clone, PartialEq, ::prost::Oneof{}{})]\n",
+            self.message_derives(fq_message_name),
+            self.serde_derives()
+        ));
         self.push_indent();
         self.buf.push_str("pub enum ");
         self.buf.push_str(&to_upper_camel(oneof.name()));
         self.buf.push_str(" {\n");
 
+        // The JSON names and Protobuf names of the fields, which are all accepted when
+        // deserializing the oneof.
+        let field_names = fields
+            .iter()
+            .flat_map(|(field, _)| vec![json_name(field), field.name().to_string()])
+            .unique()
+            .collect::<Vec<_>>();
+
+        // The variants, with the expression generating their value if the oneof implements
+        // `Arbitrary`.
+        let mut variants = Vec::new();
+
         self.path.push(2);
         self.depth += 1;
         for (field, idx) in fields {
-            let type_ = field.r#type();
-
             self.path.push(idx as i32);
             self.append_doc(fq_message_name, Some(field.name()));
             self.path.pop();
 
             self.push_indent();
-            let ty_tag = self.field_type_tag(&field);
+            let mut ty_tag = self.field_type_tag(&field);
+            if field.r#type() == Type::String {
+                if let Some(annotation) = self.string_type(fq_message_name, &field).annotation() {
+                    ty_tag.to_mut().push_str(&format!("={:?}", annotation));
+                }
+            }
             self.buf.push_str(&format!(
                 "#[prost({}, tag=\"{}\")]\n",
                 ty_tag,
                 field.number()
             ));
+            self.append_serde_attributes(fq_message_name, &field, None, SerdeShape::Variant);
             self.append_field_attributes(&oneof_name, field.name());
 
             self.push_indent();
             let ty = self.resolve_type(&field, fq_message_name);
 
-            let boxed = (type_ == Type::Message || type_ == Type::Group)
-                && self
-                    .message_graph
-                    .is_nested(field.type_name(), fq_message_name);
+            let boxed = self.boxed(&field, fq_message_name, Some(oneof.name()));
 
             debug!(
                 "    oneof: {:?}, type: {:?}, boxed: {}",
@@ -551,22 +2412,101 @@ impl<'a> CodeGenerator<'a> {
                 self.buf
                     .push_str(&format!("{}({}),\n", to_upper_camel(field.name()), ty));
             }
+            variants.push((
+                to_upper_camel(field.name()),
+                self.arbitrary_value(&field, &ty, boxed),
+            ));
         }
         self.depth -= 1;
         self.path.pop();
 
         self.push_indent();
         self.buf.push_str("}\n");
+
+        if self.config.enable_serde_json {
+            self.append_oneof_fields(&oneof_name, &to_upper_camel(oneof.name()), &field_names);
+        }
+
+        if self.implements_arbitrary(fq_message_name) {
+            self.append_oneof_arbitrary(&to_upper_camel(oneof.name()), &variants);
+        }
+    }
+
+    /// Appends the implementation of `arbitrary::Arbitrary` for a oneof, choosing one of its
+    /// variants, given with the expression generating their value.
+    fn append_oneof_arbitrary(&mut self, oneof_name: &str, variants: &[(String, String)]) {
+        let mut code = format!(
+            "impl<'arbitrary> ::arbitrary::Arbitrary<'arbitrary> for {} {{\n    \
+             #[allow(deprecated)]\n    \
+             fn arbitrary(u: &mut ::arbitrary::Unstructured<'arbitrary>) \
+             -> ::arbitrary::Result<Self> {{\n",
+            oneof_name
+        );
+        match variants {
+            [(variant, value)] => {
+                code += &format!(
+                    "        ::core::result::Result::Ok(Self::{}({}))\n",
+                    variant, value
+                );
+            }
+            _ => {
+                code += &format!(
+                    "        ::core::result::Result::Ok(match u.int_in_range(0..={})? {{\n",
+                    variants.len() - 1
+                );
+                for (idx, (variant, value)) in variants.iter().enumerate() {
+                    let pattern = if idx + 1 == variants.len() {
+                        "_".to_string()
+                    } else {
+                        idx.to_string()
+                    };
+                    code += &format!("            {} => Self::{}({}),\n", pattern, variant, value);
+                }
+                code += "        })\n";
+            }
+        }
+        code += "    }\n}\n";
+        self.push_code(&code);
     }
 
-    fn location(&self) -> &Location {
+    /// Appends the implementation of `prost_types::serde::OneofFields` used to deserialize the
+    /// oneof from the fields of its message.
+    fn append_oneof_fields(
+        &mut self,
+        fq_oneof_name: &str,
+        oneof_name: &str,
+        field_names: &[String],
+    ) {
+        let fields = field_names
+            .iter()
+            .map(|name| format!("{:?}", name))
+            .join(", ");
+        let code = format!(
+            "impl ::prost_types::serde::OneofFields for {} {{\n    \
+             const NAME: &'static str = {:?};\n    \
+             const FIELDS: &'static [&'static str] = &[{}];\n\
+             }}\n",
+            oneof_name,
+            fq_oneof_name.trim_start_matches('.'),
+            fields
+        );
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Returns the location of the current path, which is missing if the descriptors were built
+    /// without source info.
+    fn location(&self) -> Option<&Location> {
         let idx = self
             .source_info
             .location
             .binary_search_by_key(&&self.path[..], |location| &location.path[..])
-            .unwrap();
+            .ok()?;
 
-        &self.source_info.location[idx]
+        Some(&self.source_info.location[idx])
     }
 
     fn append_doc(&mut self, fq_name: &str, field_name: Option<&str>) {
@@ -579,8 +2519,30 @@ impl<'a> CodeGenerator<'a> {
             self.config.disable_comments.get(fq_name).next().is_none()
         };
         if append_doc {
-            Comments::from_location(self.location()).append_with_indent(self.depth, self.buf)
+            self.comments(fq_name, field_name)
+                .append_with_indent(self.depth, self.buf)
+        }
+    }
+
+    /// Returns the comments of the current location, with their code blocks marked as text if
+    /// configured.
+    fn comments(&self, fq_name: &str, field_name: Option<&str>) -> Comments {
+        let mut comments = self
+            .location()
+            .map(Comments::from_location)
+            .unwrap_or_default();
+        let text_code_blocks = if let Some(field_name) = field_name {
+            self.config
+                .text_code_blocks
+                .get_first_field(fq_name, field_name)
+                .is_some()
+        } else {
+            self.config.text_code_blocks.get(fq_name).next().is_some()
+        };
+        if text_code_blocks {
+            comments.mark_code_blocks_as_text();
         }
+        comments
     }
 
     fn append_enum(&mut self, desc: EnumDescriptorProto) {
@@ -602,9 +2564,29 @@ impl<'a> CodeGenerator<'a> {
         self.append_doc(&fq_enum_name, None);
         self.append_type_attributes(&fq_enum_name);
         self.push_indent();
-        self.buf.push_str(
-            "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]\n",
-        );
+        let derives = self
+            .requested_derives(&fq_enum_name)
+            .into_iter()
+            .filter(|&derive| {
+                !matches!(
+                    derive_name(derive),
+                    "Clone"
+                        | "Copy"
+                        | "Debug"
+                        | "Default"
+                        | "PartialEq"
+                        | "Eq"
+                        | "Hash"
+                        | "PartialOrd"
+                        | "Ord"
+                )
+            })
+            .map(|derive| format!(", {}", derive))
+            .collect::<String>();
+        self.buf.push_str(&format!(
+            "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration{})]\n",
+            derives
+        ));
         self.push_indent();
         self.buf.push_str("#[repr(i32)]\n");
         self.push_indent();
@@ -612,24 +2594,24 @@ impl<'a> CodeGenerator<'a> {
         self.buf.push_str(&to_upper_camel(desc.name()));
         self.buf.push_str(" {\n");
 
-        let mut numbers = HashSet::new();
+        // The variants, by number, and the aliases, which share the number of a variant.
+        let mut variants = HashMap::new();
+        let mut aliases = Vec::new();
 
         self.depth += 1;
         self.path.push(2);
         for (idx, value) in enum_values.iter().enumerate() {
             // Skip duplicate enum values. Protobuf allows this when the
             // 'allow_alias' option is set.
-            if !numbers.insert(value.number()) {
+            if variants.contains_key(&value.number()) {
+                aliases.push(idx);
                 continue;
             }
 
             self.path.push(idx as i32);
-            let stripped_prefix = if self.config.strip_enum_prefix {
-                Some(to_upper_camel(enum_name))
-            } else {
-                None
-            };
-            self.append_enum_value(&fq_enum_name, value, stripped_prefix);
+            let name = self.enum_value_name(enum_name, value);
+            self.append_enum_value(&fq_enum_name, value, &name);
+            variants.insert(value.number(), name);
             self.path.pop();
         }
         self.path.pop();
@@ -637,33 +2619,244 @@ impl<'a> CodeGenerator<'a> {
 
         self.push_indent();
         self.buf.push_str("}\n");
+
+        self.append_enum_names(&desc, &variants);
+        self.append_enum_aliases(&fq_enum_name, &desc, &variants, &aliases);
+
+        if self.config.enable_serde_json {
+            self.append_enum_name(&fq_enum_name, &to_upper_camel(enum_name), enum_values);
+        }
+
+        if self.implements_arbitrary(&fq_enum_name) {
+            self.append_enum_arbitrary(&desc, &variants);
+        }
+        self.append_proptest_arbitrary(enum_name, &fq_enum_name);
+    }
+
+    /// Appends the implementation of `arbitrary::Arbitrary` for an enum, choosing one of its
+    /// values.
+    fn append_enum_arbitrary(
+        &mut self,
+        desc: &EnumDescriptorProto,
+        variants: &HashMap<i32, String>,
+    ) {
+        let mut numbers = HashSet::new();
+        let values = desc
+            .value
+            .iter()
+            .filter(|value| numbers.insert(value.number()))
+            .map(|value| format!("Self::{}", variants[&value.number()]))
+            .join(", ");
+        let code = format!(
+            "impl<'arbitrary> ::arbitrary::Arbitrary<'arbitrary> for {} {{\n    \
+             #[allow(deprecated)]\n    \
+             fn arbitrary(u: &mut ::arbitrary::Unstructured<'arbitrary>) \
+             -> ::arbitrary::Result<Self> {{\n        \
+             ::core::result::Result::Ok(*u.choose(&[{}])?)\n    \
+             }}\n\
+             }}\n",
+            to_upper_camel(desc.name()),
+            values
+        );
+        self.push_code(&code);
+    }
+
+    /// Appends the implementation of `prost_types::serde::EnumName` used to serialize enum fields
+    /// by name.
+    fn append_enum_name(
+        &mut self,
+        fq_enum_name: &str,
+        enum_name: &str,
+        values: &[EnumValueDescriptorProto],
+    ) {
+        const SOME: &str = "::core::option::Option::Some";
+        const NONE: &str = "::core::option::Option::None";
+
+        // Aliases share a number, so only the first name of each number is used for serializing,
+        // while every name is accepted when deserializing.
+        let mut numbers = HashSet::new();
+        let mut name = String::new();
+        let mut from_name = String::new();
+        for value in values {
+            if numbers.insert(value.number()) {
+                name += &format!(
+                    "            {} => {}({:?}),\n",
+                    value.number(),
+                    SOME,
+                    value.name()
+                );
+            }
+            from_name += &format!(
+                "            {:?} => {}({}),\n",
+                value.name(),
+                SOME,
+                value.number()
+            );
+        }
+
+        let mut code = format!("impl ::prost_types::serde::EnumName for {} {{\n", enum_name);
+        code += &format!(
+            "    const FULL_NAME: &'static str = {:?};\n",
+            fq_enum_name.trim_start_matches('.')
+        );
+        code += "    fn name(value: i32) -> ::core::option::Option<&'static str> {\n";
+        code += "        match value {\n";
+        code += &name;
+        code += &format!("            _ => {},\n", NONE);
+        code += "        }\n    }\n";
+        code += "    fn from_name(name: &str) -> ::core::option::Option<i32> {\n";
+        code += "        match name {\n";
+        code += &from_name;
+        code += &format!("            _ => {},\n", NONE);
+        code += "        }\n    }\n";
+        code += "}\n";
+
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Returns the Rust name of an enum value.
+    fn enum_value_name(&self, enum_name: &str, value: &EnumValueDescriptorProto) -> String {
+        let name = to_upper_camel(value.name());
+        if self.config.strip_enum_prefix {
+            strip_enum_prefix(&to_upper_camel(enum_name), &name).to_string()
+        } else {
+            name
+        }
     }
 
     fn append_enum_value(
         &mut self,
         fq_enum_name: &str,
         value: &EnumValueDescriptorProto,
-        prefix_to_strip: Option<String>,
+        name: &str,
     ) {
         self.append_doc(fq_enum_name, Some(value.name()));
         self.append_field_attributes(fq_enum_name, value.name());
         self.push_indent();
-        let name = to_upper_camel(value.name());
-        let name_unprefixed = match prefix_to_strip {
-            Some(prefix) => strip_enum_prefix(&prefix, &name),
-            None => &name,
-        };
-        self.buf.push_str(name_unprefixed);
+        self.buf.push_str(name);
         self.buf.push_str(" = ");
         self.buf.push_str(&value.number().to_string());
         self.buf.push_str(",\n");
     }
 
+    /// Appends the table of the Protobuf names and numbers of an enum's values, and the methods
+    /// converting the enum from and to the Protobuf names.
+    fn append_enum_names(&mut self, desc: &EnumDescriptorProto, variants: &HashMap<i32, String>) {
+        const SOME: &str = "::core::option::Option::Some";
+        const NONE: &str = "::core::option::Option::None";
+
+        let mut numbers = HashSet::new();
+        let mut names = String::new();
+        let mut as_str_name = String::new();
+        let mut from_str_name = String::new();
+        for value in &desc.value {
+            let variant = &variants[&value.number()];
+            names += &format!("        ({:?}, {}),\n", value.name(), value.number());
+            if numbers.insert(value.number()) {
+                as_str_name += &format!("            Self::{} => {:?},\n", variant, value.name());
+            }
+            from_str_name += &format!(
+                "            {:?} => {}(Self::{}),\n",
+                value.name(),
+                SOME,
+                variant
+            );
+        }
+
+        let mut code = format!("impl {} {{\n", to_upper_camel(desc.name()));
+        code += "    /// The Protobuf names and numbers of the values of the enum, in declaration order,\n";
+        code += "    /// including aliases.\n";
+        code += "    pub const NAMES: &'static [(&'static str, i32)] = &[\n";
+        code += &names;
+        code += "    ];\n";
+        code += "    /// Returns the name of the value in the Protobuf definition, which is stable as long as\n";
+        code += "    /// the definition doesn't change.\n";
+        code += "    pub fn as_str_name(&self) -> &'static str {\n";
+        code += "        match self {\n";
+        code += &as_str_name;
+        code += "        }\n    }\n";
+        code += "    /// Returns the value with the name in the Protobuf definition, including aliases.\n";
+        code += "    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {\n";
+        code += "        match value {\n";
+        code += &from_str_name;
+        code += &format!("            _ => {},\n", NONE);
+        code += "        }\n    }\n";
+        code += "}\n";
+
+        for line in code.lines() {
+            self.push_indent();
+            self.buf.push_str(line);
+            self.buf.push('\n');
+        }
+    }
+
+    /// Appends the aliases of an enum, which are the values sharing the number of an earlier value,
+    /// as associated constants equal to the variant with the number.
+    ///
+    /// Aliases whose Rust name is already taken, e.g. because they only differ in case from
+    /// another value, are skipped.
+    fn append_enum_aliases(
+        &mut self,
+        fq_enum_name: &str,
+        desc: &EnumDescriptorProto,
+        variants: &HashMap<i32, String>,
+        aliases: &[usize],
+    ) {
+        let enum_name = to_upper_camel(desc.name());
+        let mut names: HashSet<String> = variants.values().cloned().collect();
+        let aliases: Vec<(usize, String)> = aliases
+            .iter()
+            .map(|&idx| (idx, self.enum_value_name(desc.name(), &desc.value[idx])))
+            .filter(|(_, name)| names.insert(name.clone()))
+            .collect();
+        if aliases.is_empty() {
+            return;
+        }
+
+        self.push_indent();
+        self.buf.push_str("impl ");
+        self.buf.push_str(&enum_name);
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+        self.path.push(2);
+        for (idx, name) in aliases {
+            let value = &desc.value[idx];
+            self.path.push(idx as i32);
+            self.append_doc(fq_enum_name, Some(value.name()));
+            self.path.pop();
+            self.push_indent();
+            self.buf.push_str("#[allow(non_upper_case_globals)]\n");
+            self.push_indent();
+            self.buf.push_str(&format!(
+                "pub const {}: {} = {}::{};\n",
+                name,
+                enum_name,
+                enum_name,
+                variants[&value.number()]
+            ));
+        }
+        self.path.pop();
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
     fn push_service(&mut self, service: ServiceDescriptorProto) {
         let name = service.name().to_owned();
         debug!("  service: {:?}", name);
 
-        let comments = Comments::from_location(self.location());
+        let fq_service_name = format!(
+            "{}{}.{}",
+            if self.package.is_empty() { "" } else { "." },
+            self.package,
+            name
+        );
+        let comments = self.comments(&fq_service_name, None);
+        let custom_options = self.custom_options;
 
         self.path.push(2);
         let methods = service
@@ -672,16 +2865,20 @@ impl<'a> CodeGenerator<'a> {
             .enumerate()
             .map(|(idx, mut method)| {
                 debug!("  method: {:?}", method.name());
+                let name = method.name.take().unwrap();
                 self.path.push(idx as i32);
-                let comments = Comments::from_location(self.location());
+                let comments = self.comments(&fq_service_name, Some(&name));
                 self.path.pop();
 
-                let name = method.name.take().unwrap();
                 let input_proto_type = method.input_type.take().unwrap();
                 let output_proto_type = method.output_type.take().unwrap();
                 let input_type = self.resolve_ident(&input_proto_type);
                 let output_type = self.resolve_ident(&output_proto_type);
                 let client_streaming = method.client_streaming();
+                let method_options = custom_options
+                    .method(&fq_service_name, &name)
+                    .cloned()
+                    .unwrap_or_default();
                 let server_streaming = method.server_streaming();
 
                 Method {
@@ -693,6 +2890,7 @@ impl<'a> CodeGenerator<'a> {
                     input_proto_type,
                     output_proto_type,
                     options: method.options.unwrap_or_default(),
+                    custom_options: method_options,
                     client_streaming,
                     server_streaming,
                 }
@@ -707,6 +2905,10 @@ impl<'a> CodeGenerator<'a> {
             comments,
             methods,
             options: service.options.unwrap_or_default(),
+            custom_options: custom_options
+                .service(&fq_service_name)
+                .cloned()
+                .unwrap_or_default(),
         };
 
         if let Some(service_generator) = self.config.service_generator.as_mut() {
@@ -754,7 +2956,10 @@ impl<'a> CodeGenerator<'a> {
             Type::Int32 | Type::Sfixed32 | Type::Sint32 | Type::Enum => String::from("i32"),
             Type::Int64 | Type::Sfixed64 | Type::Sint64 => String::from("i64"),
             Type::Bool => String::from("bool"),
-            Type::String => String::from("::prost::alloc::string::String"),
+            Type::String => self
+                .string_type(fq_message_name, field)
+                .rust_type()
+                .to_owned(),
             Type::Bytes => self
                 .config
                 .bytes_type
@@ -767,6 +2972,14 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    fn string_type(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> StringType {
+        self.config
+            .string_type
+            .get_first_field(fq_message_name, field.name())
+            .cloned()
+            .unwrap_or_default()
+    }
+
     fn resolve_ident(&self, pb_ident: &str) -> String {
         // protoc should always give fully qualified identifiers.
         assert_eq!(".", &pb_ident[..1]);
@@ -837,6 +3050,212 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    /// Returns `true` if the singular message field, or oneof field, is held in a `Box`.
+    ///
+    /// Fields are boxed when the message type contains the message holding the field, which
+    /// would otherwise have an infinite size, when they are matched by `Config::boxed`, or when
+    /// the message type is larger than the threshold set by `Config::box_large_messages`.
+    fn boxed(
+        &self,
+        field: &FieldDescriptorProto,
+        fq_message_name: &str,
+        oneof: Option<&str>,
+    ) -> bool {
+        let type_ = field.r#type();
+        if field.label() == Label::Repeated || !(type_ == Type::Message || type_ == Type::Group) {
+            return false;
+        }
+        if self
+            .message_graph
+            .is_nested(field.type_name(), fq_message_name)
+        {
+            return true;
+        }
+
+        // The serde helpers of the well-known types don't support boxed values.
+        if self.config.enable_serde_json
+            && self
+                .serde_with(fq_message_name, field, None, SerdeShape::Optional)
+                .is_some()
+        {
+            return false;
+        }
+
+        let matched = self
+            .config
+            .boxed
+            .get_first_field(fq_message_name, field.name())
+            .is_some()
+            || oneof.map_or(false, |oneof| {
+                self.config
+                    .boxed
+                    .get_first_field(&format!("{}.{}", fq_message_name, oneof), field.name())
+                    .is_some()
+            });
+        matched
+            || self.config.box_large_messages.map_or(false, |threshold| {
+                self.message_size(field.type_name()) > threshold
+            })
+    }
+
+    /// Returns an estimate of the size in bytes of the struct generated for the message, on
+    /// 64-bit targets.
+    fn message_size(&self, fq_message_name: &str) -> usize {
+        if let Some(&size) = self.message_sizes.borrow().get(fq_message_name) {
+            return size;
+        }
+        let message = match self.message_graph.get_message(fq_message_name) {
+            Some(message) => message,
+            // An external type, which is usually small.
+            None => return 8,
+        };
+        // Guard against cycles through fields which are not part of the message graph, such as
+        // groups. The estimate is replaced once it is computed.
+        self.message_sizes
+            .borrow_mut()
+            .insert(fq_message_name.to_string(), 8);
+
+        let mut size = 0;
+        let mut oneof_sizes = vec![0; message.oneof_decl.len()];
+        for field in &message.field {
+            match field.oneof_index {
+                Some(idx) if !field.proto3_optional() => {
+                    let oneof = message.oneof_decl[idx as usize].name();
+                    let field_size = self.field_size(field, fq_message_name, Some(oneof));
+                    let oneof_size = &mut oneof_sizes[idx as usize];
+                    *oneof_size = (*oneof_size).max(field_size);
+                }
+                _ => size += self.field_size(field, fq_message_name, None),
+            }
+        }
+        // The discriminant of the oneof enum and of its `Option`, padded.
+        size += oneof_sizes
+            .into_iter()
+            .filter(|&oneof_size| oneof_size > 0)
+            .map(|oneof_size| oneof_size + 8)
+            .sum::<usize>();
+        let size = (size + 7) / 8 * 8;
+
+        self.message_sizes
+            .borrow_mut()
+            .insert(fq_message_name.to_string(), size);
+        size
+    }
+
+    /// Returns an estimate of the size in bytes of a field of a message, on 64-bit targets.
+    fn field_size(
+        &self,
+        field: &FieldDescriptorProto,
+        fq_message_name: &str,
+        oneof: Option<&str>,
+    ) -> usize {
+        let type_ = field.r#type();
+        if field.label() == Label::Repeated {
+            let map_entry = type_ == Type::Message
+                && self
+                    .message_graph
+                    .get_message(field.type_name())
+                    .map_or(false, |entry| {
+                        entry
+                            .options
+                            .as_ref()
+                            .map_or(false, |options| options.map_entry())
+                    });
+            if !map_entry {
+                return 24;
+            }
+            return match self
+                .config
+                .map_type
+                .get_first_field(fq_message_name, field.name())
+                .cloned()
+                .unwrap_or_default()
+            {
+                MapType::HashMap => 48,
+                MapType::BTreeMap => 24,
+                // The size of an `IndexMap`, a `HashMap` with a `Vec` of entries.
+                MapType::Custom(_) => 72,
+            };
+        }
+
+        // `Option` adds a discriminant to numeric types, padded to their alignment.
+        let presence = if field.proto3_optional() && !self.uses_presence_bits(fq_message_name) {
+            2
+        } else {
+            1
+        };
+        match type_ {
+            Type::Message | Type::Group => {
+                if self.boxed(field, fq_message_name, oneof) {
+                    8
+                } else {
+                    self.message_size(field.type_name()) + 8
+                }
+            }
+            Type::String => match self.string_type(fq_message_name, field) {
+                StringType::Bytes => 32,
+                _ => 24,
+            },
+            Type::Bytes => match self
+                .config
+                .bytes_type
+                .get_first_field(fq_message_name, field.name())
+                .copied()
+                .unwrap_or_default()
+            {
+                BytesType::Vec => 24,
+                BytesType::Bytes => 32,
+            },
+            Type::Bool => presence,
+            Type::Double
+            | Type::Int64
+            | Type::Uint64
+            | Type::Sint64
+            | Type::Fixed64
+            | Type::Sfixed64 => 8 * presence,
+            _ => 4 * presence,
+        }
+    }
+
+    /// Returns `true` if the optional scalar fields of the message are tracked with presence bits.
+    fn uses_presence_bits(&self, fq_message_name: &str) -> bool {
+        self.config
+            .presence_bits
+            .get_first(fq_message_name)
+            .is_some()
+            && !self.config.enable_serde_json
+    }
+
+    /// Returns `true` if the required fields of the message are tracked with presence bits.
+    fn enforces_required(&self, fq_message_name: &str) -> bool {
+        self.config
+            .enforce_required
+            .get_first(fq_message_name)
+            .is_some()
+            && !self.config.enable_serde_json
+    }
+
+    /// Returns `true` if the field is assigned a presence bit, either as an optional scalar or
+    /// message field of a message using presence bits, or as a required field of a message
+    /// enforcing them.
+    ///
+    /// Optional message fields which are part of a recursion cycle keep their `Option`, since the
+    /// messages they hold can't be allocated up front.
+    fn has_presence_bit(&self, fq_message_name: &str, field: &FieldDescriptorProto) -> bool {
+        if field.label() == Label::Required {
+            return self.enforces_required(fq_message_name);
+        }
+        self.uses_presence_bits(fq_message_name)
+            && self.optional(field)
+            && match field.r#type() {
+                Type::Message => !self
+                    .message_graph
+                    .is_nested(field.type_name(), fq_message_name),
+                Type::Group => false,
+                _ => self.integer_type(fq_message_name, field) != Some(IntegerType::NonZero),
+            }
+    }
+
     fn optional(&self, field: &FieldDescriptorProto) -> bool {
         if field.proto3_optional.unwrap_or(false) {
             return true;
@@ -852,6 +3271,46 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    /// Returns the Rust integer type override configured for a `uint32` or `uint64` field.
+    fn integer_type(
+        &self,
+        fq_message_name: &str,
+        field: &FieldDescriptorProto,
+    ) -> Option<IntegerType> {
+        if field.label() == Label::Required {
+            return None;
+        }
+        let char_type = || {
+            self.config
+                .char_fields
+                .get_first_field(fq_message_name, field.name())
+                .is_some()
+        };
+        let nonzero_type = || {
+            field.default_value.is_none()
+                && self
+                    .config
+                    .nonzero_fields
+                    .get_first_field(fq_message_name, field.name())
+                    .is_some()
+        };
+        match field.r#type() {
+            Type::Uint32 if char_type() => Some(IntegerType::Char),
+            Type::Uint32 | Type::Uint64 if nonzero_type() => Some(IntegerType::NonZero),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if the message gets iterative `Clone`, `PartialEq` and `Drop`
+    /// implementations when it is recursive.
+    fn iterative_recursion(&self, fq_message_name: &str) -> bool {
+        self.config
+            .iterative_recursion
+            .get_first(fq_message_name)
+            .is_some()
+            && self.extern_paths.resolve_ident(fq_message_name).is_none()
+    }
+
     /// Returns `true` if the field options includes the `deprecated` option.
     fn deprecated(&self, field: &FieldDescriptorProto) -> bool {
         field
@@ -861,6 +3320,27 @@ impl<'a> CodeGenerator<'a> {
     }
 }
 
+/// Returns the JSON name of the field, which `protoc` derives from the field name by removing
+/// underscores and capitalizing the letter following each, unless set with the `json_name` option.
+fn json_name(field: &FieldDescriptorProto) -> String {
+    if let Some(ref json_name) = field.json_name {
+        return json_name.clone();
+    }
+    let mut json_name = String::with_capacity(field.name().len());
+    let mut capitalize = false;
+    for c in field.name().chars() {
+        if c == '_' {
+            capitalize = true;
+        } else if capitalize {
+            json_name.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
 /// Returns `true` if the repeated field type can be packed.
 fn can_pack(field: &FieldDescriptorProto) -> bool {
     matches!(
@@ -988,6 +3468,22 @@ fn unescape_c_escape_string(s: &str) -> Vec<u8> {
     dst
 }
 
+/// Returns the code of a validation check, returning a `ValidationError` for the field when
+/// `violation` holds, indented by `indent` spaces.
+fn validation_check(violation: &str, description: &str, field: &str, indent: usize) -> String {
+    format!(
+        "{0}if {1} {{\n\
+         {0}    let mut error = ::prost::ValidationError::new({2:?});\n\
+         {0}    error.push(MESSAGE, {3:?});\n\
+         {0}    return ::core::result::Result::Err(error);\n\
+         {0}}}\n",
+        " ".repeat(indent),
+        violation,
+        description,
+        field
+    )
+}
+
 /// Strip an enum's type name from the prefix of an enum value.
 ///
 /// This function assumes that both have been formatted to Rust's
@@ -1017,16 +3513,78 @@ impl MapType {
     /// The `prost-derive` annotation type corresponding to the map type.
     fn annotation(&self) -> &'static str {
         match self {
-            MapType::HashMap => "map",
+            MapType::HashMap | MapType::Custom(_) => "map",
             MapType::BTreeMap => "btree_map",
         }
     }
 
     /// The fully-qualified Rust type corresponding to the map type.
-    fn rust_type(&self) -> &'static str {
+    fn rust_type(&self) -> &str {
         match self {
             MapType::HashMap => "::std::collections::HashMap",
             MapType::BTreeMap => "::prost::alloc::collections::BTreeMap",
+            MapType::Custom(path) => path,
+        }
+    }
+}
+
+/// A field of a message with iterative `Clone`, `PartialEq` and `Drop` implementations.
+enum RecursiveField {
+    /// A field without recursive children.
+    Plain(String),
+    /// A singular message field holding a recursive child, and whether it is boxed.
+    Optional(String, bool),
+    /// A repeated message field holding recursive children.
+    Repeated(String),
+    /// A map field with message values holding recursive children.
+    Map(String),
+    /// A oneof field, its enum path, and the variants holding recursive children, with whether
+    /// they are boxed.
+    Oneof(String, String, Vec<(String, bool)>),
+}
+
+/// How a field is held by the generated code, which determines its serde attributes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SerdeShape<'a> {
+    /// A singular field without presence.
+    Plain,
+    /// A `required` field.
+    Required,
+    /// A singular field with presence, held in an `Option`.
+    Optional,
+    /// A `repeated` field, held in a `Vec`.
+    Repeated,
+    /// A `map` field, serialized by the named module of `prost_types::serde`.
+    Map(&'static str),
+    /// A `map` field with enum values, serialized by the named module of `prost_types::serde` for
+    /// the enum type.
+    EnumMap(&'a str, &'a str),
+    /// A field of a oneof, held in a variant of the oneof enum.
+    Variant,
+}
+
+/// A Rust type used in place of the default integer type for a `uint32` or `uint64` field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum IntegerType {
+    Char,
+    NonZero,
+}
+
+impl IntegerType {
+    /// The `prost-derive` annotation corresponding to the integer type.
+    fn annotation(self) -> &'static str {
+        match self {
+            IntegerType::Char => "char",
+            IntegerType::NonZero => "nonzero",
+        }
+    }
+
+    /// The fully-qualified Rust type corresponding to the integer type.
+    fn rust_type(self, field_type: Type) -> &'static str {
+        match (self, field_type) {
+            (IntegerType::Char, _) => "char",
+            (IntegerType::NonZero, Type::Uint64) => "::core::num::NonZeroU64",
+            (IntegerType::NonZero, _) => "::core::num::NonZeroU32",
         }
     }
 }
@@ -1049,6 +3607,30 @@ impl BytesType {
     }
 }
 
+impl StringType {
+    /// The `prost-derive` annotation corresponding to the string type, or `None` for `String`.
+    fn annotation(&self) -> Option<&str> {
+        match self {
+            StringType::String => None,
+            StringType::Cow => Some("cow"),
+            StringType::Bytes => Some("bytes"),
+            StringType::SmolStr => Some("smol_str"),
+            StringType::Custom(path) => Some(path),
+        }
+    }
+
+    /// The fully-qualified Rust type corresponding to the string type.
+    fn rust_type(&self) -> &str {
+        match self {
+            StringType::String => "::prost::alloc::string::String",
+            StringType::Cow => "::prost::alloc::borrow::Cow<'static, str>",
+            StringType::Bytes => "::prost::ByteStr",
+            StringType::SmolStr => "::prost::smol_str::SmolStr",
+            StringType::Custom(path) => path,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1080,6 +3662,20 @@ mod tests {
         unescape_c_escape_string(r#"\x1"#);
     }
 
+    #[test]
+    fn test_json_name() {
+        let field = |name: &str, json_name: Option<&str>| FieldDescriptorProto {
+            name: Some(name.to_string()),
+            json_name: json_name.map(str::to_string),
+            ..FieldDescriptorProto::default()
+        };
+        assert_eq!(json_name(&field("foo_bar_baz", None)), "fooBarBaz");
+        assert_eq!(json_name(&field("foo__bar_", None)), "fooBar");
+        assert_eq!(json_name(&field("_foo_1st", None)), "Foo1st");
+        assert_eq!(json_name(&field("FooBar", None)), "FooBar");
+        assert_eq!(json_name(&field("foo_bar", Some("custom"))), "custom");
+    }
+
     #[test]
     fn test_strip_enum_prefix() {
         assert_eq!(strip_enum_prefix("Foo", "FooBar"), "Bar");
@@ -0,0 +1,417 @@
+//! Custom options of descriptors.
+//!
+//! Custom options are extensions of the descriptor options messages, such as
+//! `google.protobuf.FieldOptions`. `prost-types` has no support for extensions, so they are dropped
+//! when decoding a `FileDescriptorSet`. They are instead read from the encoded `FileDescriptorSet`,
+//! and exposed as raw bytes which code generators can decode according to the definitions of the
+//! extensions.
+//!
+//! `protoc` interprets the custom options whose extensions are defined in the imported files, so
+//! `uninterpreted_option` is empty in its output, and is left to the standard options.
+
+use std::collections::HashMap;
+
+use prost::bytes::{Buf, Bytes};
+use prost::encoding::{decode_key, skip_and_capture, DecodeContext, WireType};
+use prost::DecodeError;
+
+/// The smallest field number of extensions of the descriptor options messages, which all declare
+/// `extensions 1000 to max`.
+const MIN_EXTENSION: u32 = 1000;
+
+/// The encoded fields of a message, with the raw bytes of their values as returned by
+/// `skip_and_capture`, in the order they appear.
+pub(crate) type Fields = Vec<(u32, WireType, Bytes)>;
+
+pub(crate) fn decode_fields(mut buf: Bytes) -> Result<Fields, DecodeError> {
+    let mut fields = Vec::new();
+    while buf.has_remaining() {
+        let (tag, wire_type) = decode_key(&mut buf)?;
+        let value = skip_and_capture(wire_type, tag, &mut buf, DecodeContext::default())?;
+        fields.push((tag, wire_type, value));
+    }
+    Ok(fields)
+}
+
+pub(crate) fn decode_string(value: &Bytes) -> Result<String, DecodeError> {
+    String::from_utf8(value.to_vec())
+        .map_err(|_| DecodeError::new("invalid string value: data is not UTF-8 encoded"))
+}
+
+/// A custom option, set on a descriptor.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomOption {
+    /// The field number of the extension.
+    pub number: u32,
+    /// The wire type of the value.
+    pub wire_type: WireType,
+    /// The encoded value, without its key, as returned by `prost::encoding::skip_and_capture`.
+    ///
+    /// Messages, strings and bytes are given without their length prefix, so that a message can be
+    /// decoded with `Message::decode`.
+    pub value: Bytes,
+}
+
+/// The custom options set on a descriptor, in the order they appear in its options.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CustomOptions {
+    options: Vec<CustomOption>,
+}
+
+impl CustomOptions {
+    /// Reads the custom options from the encoded options message of a descriptor.
+    fn decode(options: Bytes) -> Result<CustomOptions, DecodeError> {
+        let options = decode_fields(options)?
+            .into_iter()
+            .filter(|&(number, _, _)| number >= MIN_EXTENSION)
+            .map(|(number, wire_type, value)| CustomOption {
+                number,
+                wire_type,
+                value,
+            })
+            .collect();
+        Ok(CustomOptions { options })
+    }
+
+    /// Returns `true` if no custom option is set.
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    /// Returns the last value of the custom option with the field number, which is the value of
+    /// a singular scalar option.
+    pub fn get(&self, number: u32) -> Option<&CustomOption> {
+        self.options
+            .iter()
+            .rev()
+            .find(|option| option.number == number)
+    }
+
+    /// Returns all the values of the custom option with the field number, such as the items of a
+    /// repeated option, or the parts of a message option to merge.
+    pub fn get_all(&self, number: u32) -> impl Iterator<Item = &CustomOption> {
+        self.options
+            .iter()
+            .filter(move |option| option.number == number)
+    }
+
+    /// Returns an iterator over all the custom options.
+    pub fn iter(&self) -> impl Iterator<Item = &CustomOption> {
+        self.options.iter()
+    }
+}
+
+/// The kinds of descriptors which have options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum Kind {
+    File,
+    Message,
+    Field,
+    Oneof,
+    Enum,
+    EnumValue,
+    Service,
+    Method,
+}
+
+/// The custom options of all the descriptors of a `FileDescriptorSet`.
+///
+/// Descriptors are identified by fully-qualified name, with a leading `.`, such as
+/// `.my.package.Message`, and their members by the name of their parent and their own name. Files
+/// are identified by their path, as in `FileDescriptorProto.name`.
+#[derive(Clone, Debug, Default)]
+pub struct CustomOptionsIndex {
+    /// The custom options of the descriptors which have any.
+    options: HashMap<(Kind, String), CustomOptions>,
+}
+
+impl CustomOptionsIndex {
+    /// Reads the custom options from an encoded `FileDescriptorSet`.
+    pub fn decode(file_descriptor_set: &[u8]) -> Result<CustomOptionsIndex, DecodeError> {
+        let mut index = CustomOptionsIndex::default();
+        for (tag, _, file) in decode_fields(Bytes::copy_from_slice(file_descriptor_set))? {
+            // `FileDescriptorSet.file`
+            if tag == 1 {
+                index.add_file(file)?;
+            }
+        }
+        Ok(index)
+    }
+
+    /// Returns the custom options of a file, by path.
+    pub fn file(&self, name: &str) -> Option<&CustomOptions> {
+        self.get(Kind::File, name)
+    }
+
+    /// Returns the custom options of a message.
+    pub fn message(&self, fq_message_name: &str) -> Option<&CustomOptions> {
+        self.get(Kind::Message, fq_message_name)
+    }
+
+    /// Returns the custom options of a field of a message.
+    pub fn field(&self, fq_message_name: &str, field_name: &str) -> Option<&CustomOptions> {
+        self.get_member(Kind::Field, fq_message_name, field_name)
+    }
+
+    /// Returns the custom options of a oneof of a message.
+    pub fn oneof(&self, fq_message_name: &str, oneof_name: &str) -> Option<&CustomOptions> {
+        self.get_member(Kind::Oneof, fq_message_name, oneof_name)
+    }
+
+    /// Returns the custom options of an enumeration.
+    pub fn enumeration(&self, fq_enum_name: &str) -> Option<&CustomOptions> {
+        self.get(Kind::Enum, fq_enum_name)
+    }
+
+    /// Returns the custom options of a value of an enumeration.
+    pub fn enum_value(&self, fq_enum_name: &str, value_name: &str) -> Option<&CustomOptions> {
+        self.get_member(Kind::EnumValue, fq_enum_name, value_name)
+    }
+
+    /// Returns the custom options of a service.
+    pub fn service(&self, fq_service_name: &str) -> Option<&CustomOptions> {
+        self.get(Kind::Service, fq_service_name)
+    }
+
+    /// Returns the custom options of a method of a service.
+    pub fn method(&self, fq_service_name: &str, method_name: &str) -> Option<&CustomOptions> {
+        self.get_member(Kind::Method, fq_service_name, method_name)
+    }
+
+    /// Returns the custom options of all the descriptors of a kind, by name.
+    pub(crate) fn iter(&self, kind: Kind) -> impl Iterator<Item = (&str, &CustomOptions)> {
+        self.options
+            .iter()
+            .filter(move |((other, _), _)| *other == kind)
+            .map(|((_, name), options)| (name.as_str(), options))
+    }
+
+    fn get(&self, kind: Kind, name: &str) -> Option<&CustomOptions> {
+        self.options.get(&(kind, name.to_string()))
+    }
+
+    fn get_member(&self, kind: Kind, parent: &str, name: &str) -> Option<&CustomOptions> {
+        self.options.get(&(kind, format!("{}.{}", parent, name)))
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        kind: Kind,
+        name: String,
+        options: Bytes,
+    ) -> Result<(), DecodeError> {
+        let options = CustomOptions::decode(options)?;
+        if !options.is_empty() {
+            self.options.insert((kind, name), options);
+        }
+        Ok(())
+    }
+
+    /// Adds the options of the descriptors of a `FileDescriptorProto`.
+    fn add_file(&mut self, file: Bytes) -> Result<(), DecodeError> {
+        let fields = decode_fields(file)?;
+        let mut name = String::new();
+        let mut prefix = String::new();
+        for (tag, _, value) in &fields {
+            match tag {
+                1 => name = decode_string(value)?,
+                2 => prefix = format!(".{}", decode_string(value)?),
+                _ => {}
+            }
+        }
+        for (tag, _, value) in fields {
+            match tag {
+                // `message_type`
+                4 => self.add_message(&prefix, value)?,
+                // `enum_type`
+                5 => self.add_enum(&prefix, value)?,
+                // `service`
+                6 => self.add_service(&prefix, value)?,
+                // `options`
+                8 => self.insert(Kind::File, name.clone(), value)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the options of a `DescriptorProto`, and of its members and nested types.
+    fn add_message(&mut self, prefix: &str, message: Bytes) -> Result<(), DecodeError> {
+        let fields = decode_fields(message)?;
+        let fq_message_name = format!("{}.{}", prefix, name(&fields)?);
+        for (tag, _, value) in fields {
+            match tag {
+                // `field`
+                2 => self.add_member(Kind::Field, &fq_message_name, value, 8)?,
+                // `nested_type`
+                3 => self.add_message(&fq_message_name, value)?,
+                // `enum_type`
+                4 => self.add_enum(&fq_message_name, value)?,
+                // `options`
+                7 => self.insert(Kind::Message, fq_message_name.clone(), value)?,
+                // `oneof_decl`
+                8 => self.add_member(Kind::Oneof, &fq_message_name, value, 2)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the options of an `EnumDescriptorProto`, and of its values.
+    fn add_enum(&mut self, prefix: &str, enumeration: Bytes) -> Result<(), DecodeError> {
+        let fields = decode_fields(enumeration)?;
+        let fq_enum_name = format!("{}.{}", prefix, name(&fields)?);
+        for (tag, _, value) in fields {
+            match tag {
+                // `value`
+                2 => self.add_member(Kind::EnumValue, &fq_enum_name, value, 3)?,
+                // `options`
+                3 => self.insert(Kind::Enum, fq_enum_name.clone(), value)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the options of a `ServiceDescriptorProto`, and of its methods.
+    fn add_service(&mut self, prefix: &str, service: Bytes) -> Result<(), DecodeError> {
+        let fields = decode_fields(service)?;
+        let fq_service_name = format!("{}.{}", prefix, name(&fields)?);
+        for (tag, _, value) in fields {
+            match tag {
+                // `method`
+                2 => self.add_member(Kind::Method, &fq_service_name, value, 4)?,
+                // `options`
+                3 => self.insert(Kind::Service, fq_service_name.clone(), value)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the options of a member of a descriptor, such as a field, whose options are the field
+    /// `options_tag` of its descriptor.
+    fn add_member(
+        &mut self,
+        kind: Kind,
+        parent: &str,
+        member: Bytes,
+        options_tag: u32,
+    ) -> Result<(), DecodeError> {
+        let fields = decode_fields(member)?;
+        let name = format!("{}.{}", parent, name(&fields)?);
+        for (tag, _, value) in fields {
+            if tag == options_tag {
+                self.insert(kind, name.clone(), value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the `name` field of a descriptor, which is field 1 of all descriptors.
+fn name(fields: &Fields) -> Result<String, DecodeError> {
+    match fields.iter().find(|&&(tag, _, _)| tag == 1) {
+        Some((_, _, value)) => decode_string(value),
+        None => Ok(String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::Message;
+    use prost_types::{FieldDescriptorProto, FieldOptions};
+
+    /// Appends a field to an encoded message.
+    fn append(buf: &mut Vec<u8>, number: u32, wire_type: WireType, value: &[u8]) {
+        prost::encoding::encode_key(number, wire_type, buf);
+        if wire_type == WireType::LengthDelimited {
+            prost::encoding::encode_varint(value.len() as u64, buf);
+        }
+        buf.extend_from_slice(value);
+    }
+
+    #[test]
+    fn custom_options() {
+        // `prost-types` can not represent custom options, which are added to the encoded
+        // descriptors.
+        let mut field_options = FieldOptions {
+            deprecated: Some(true),
+            ..FieldOptions::default()
+        }
+        .encode_to_vec();
+        append(
+            &mut field_options,
+            50000,
+            WireType::LengthDelimited,
+            b"\x08\x01",
+        );
+        append(&mut field_options, 50001, WireType::Varint, &[7]);
+        append(&mut field_options, 50001, WireType::Varint, &[9]);
+        let mut annotated = FieldDescriptorProto {
+            name: Some("annotated".to_string()),
+            number: Some(2),
+            ..FieldDescriptorProto::default()
+        }
+        .encode_to_vec();
+        append(&mut annotated, 8, WireType::LengthDelimited, &field_options);
+        let plain = FieldDescriptorProto {
+            name: Some("plain".to_string()),
+            number: Some(1),
+            options: Some(FieldOptions {
+                deprecated: Some(true),
+                ..FieldOptions::default()
+            }),
+            ..FieldDescriptorProto::default()
+        }
+        .encode_to_vec();
+
+        let mut message_options = Vec::new();
+        append(
+            &mut message_options,
+            50002,
+            WireType::ThirtyTwoBit,
+            &[1, 0, 0, 0],
+        );
+        let mut inner = Vec::new();
+        append(&mut inner, 1, WireType::LengthDelimited, b"Inner");
+        append(&mut inner, 7, WireType::LengthDelimited, &message_options);
+
+        let mut outer = Vec::new();
+        append(&mut outer, 1, WireType::LengthDelimited, b"Outer");
+        append(&mut outer, 2, WireType::LengthDelimited, &plain);
+        append(&mut outer, 2, WireType::LengthDelimited, &annotated);
+        append(&mut outer, 3, WireType::LengthDelimited, &inner);
+        let mut file = Vec::new();
+        append(&mut file, 1, WireType::LengthDelimited, b"sample.proto");
+        append(&mut file, 2, WireType::LengthDelimited, b"sample");
+        append(&mut file, 4, WireType::LengthDelimited, &outer);
+        let mut file_descriptor_set = Vec::new();
+        append(
+            &mut file_descriptor_set,
+            1,
+            WireType::LengthDelimited,
+            &file,
+        );
+
+        let index = CustomOptionsIndex::decode(&file_descriptor_set).unwrap();
+        assert!(index.field(".sample.Outer", "plain").is_none());
+        assert!(index.message(".sample.Outer").is_none());
+        assert!(index.file("sample.proto").is_none());
+
+        let options = index.field(".sample.Outer", "annotated").unwrap();
+        let option = options.get(50000).unwrap();
+        assert_eq!(option.wire_type, WireType::LengthDelimited);
+        assert_eq!(&option.value[..], b"\x08\x01");
+        // The last value of a singular option wins.
+        assert_eq!(&options.get(50001).unwrap().value[..], &[9]);
+        assert_eq!(options.get_all(50001).count(), 2);
+        // Standard options, such as `deprecated`, are not custom options.
+        assert_eq!(options.iter().count(), 3);
+
+        let options = index.message(".sample.Outer.Inner").unwrap();
+        assert_eq!(&options.get(50002).unwrap().value[..], &[1, 0, 0, 0]);
+    }
+}
@@ -17,6 +17,26 @@ fn validate_proto_path(path: &str) -> Result<(), String> {
     Ok(())
 }
 
+const WELL_KNOWN_TYPES: &[(&str, &str)] = &[
+    (".google.protobuf", "::prost_types"),
+    (".google.protobuf.BoolValue", "bool"),
+    (
+        ".google.protobuf.BytesValue",
+        "::prost::alloc::vec::Vec<u8>",
+    ),
+    (".google.protobuf.DoubleValue", "f64"),
+    (".google.protobuf.Empty", "()"),
+    (".google.protobuf.FloatValue", "f32"),
+    (".google.protobuf.Int32Value", "i32"),
+    (".google.protobuf.Int64Value", "i64"),
+    (
+        ".google.protobuf.StringValue",
+        "::prost::alloc::string::String",
+    ),
+    (".google.protobuf.UInt32Value", "u32"),
+    (".google.protobuf.UInt64Value", "u64"),
+];
+
 #[derive(Debug)]
 pub struct ExternPaths {
     extern_paths: HashMap<String, String>,
@@ -32,33 +52,15 @@ impl ExternPaths {
             extern_paths.insert(proto_path.clone(), rust_path.clone())?;
         }
 
+        // The well-known types are mapped to `prost_types` and Rust primitives, unless the paths
+        // were declared explicitly.
         if prost_types {
-            extern_paths.insert(".google.protobuf".to_string(), "::prost_types".to_string())?;
-            extern_paths.insert(".google.protobuf.BoolValue".to_string(), "bool".to_string())?;
-            extern_paths.insert(
-                ".google.protobuf.BytesValue".to_string(),
-                "::prost::alloc::vec::Vec<u8>".to_string(),
-            )?;
-            extern_paths.insert(
-                ".google.protobuf.DoubleValue".to_string(),
-                "f64".to_string(),
-            )?;
-            extern_paths.insert(".google.protobuf.Empty".to_string(), "()".to_string())?;
-            extern_paths.insert(".google.protobuf.FloatValue".to_string(), "f32".to_string())?;
-            extern_paths.insert(".google.protobuf.Int32Value".to_string(), "i32".to_string())?;
-            extern_paths.insert(".google.protobuf.Int64Value".to_string(), "i64".to_string())?;
-            extern_paths.insert(
-                ".google.protobuf.StringValue".to_string(),
-                "::prost::alloc::string::String".to_string(),
-            )?;
-            extern_paths.insert(
-                ".google.protobuf.UInt32Value".to_string(),
-                "u32".to_string(),
-            )?;
-            extern_paths.insert(
-                ".google.protobuf.UInt64Value".to_string(),
-                "u64".to_string(),
-            )?;
+            for &(proto_path, rust_path) in WELL_KNOWN_TYPES {
+                extern_paths
+                    .extern_paths
+                    .entry(proto_path.to_string())
+                    .or_insert_with(|| rust_path.to_string());
+            }
         }
 
         Ok(extern_paths)
@@ -167,4 +169,41 @@ mod tests {
         case(".google.protobuf.Duration", "::prost_types::Duration");
         case(".google.protobuf.Empty", "()");
     }
+
+    #[test]
+    fn test_well_known_types_override() {
+        let paths = ExternPaths::new(
+            &[
+                (".google.protobuf".to_string(), "::prost_types".to_string()),
+                (
+                    ".google.protobuf.Empty".to_string(),
+                    "::my_protos::Empty".to_string(),
+                ),
+                (
+                    ".google.protobuf.Any".to_string(),
+                    "::my_any::Any".to_string(),
+                ),
+            ],
+            true,
+        )
+        .unwrap();
+
+        let case = |proto_ident: &str, resolved_ident: &str| {
+            assert_eq!(paths.resolve_ident(proto_ident).unwrap(), resolved_ident);
+        };
+
+        case(".google.protobuf.Value", "::prost_types::Value");
+        case(".google.protobuf.Empty", "::my_protos::Empty");
+        case(".google.protobuf.Any", "::my_any::Any");
+        case(".google.protobuf.Int64Value", "i64");
+
+        assert!(ExternPaths::new(
+            &[
+                (".foo".to_string(), "::foo1".to_string()),
+                (".foo".to_string(), "::foo2".to_string()),
+            ],
+            false,
+        )
+        .is_err());
+    }
 }
@@ -74,6 +74,9 @@
 //! }
 //! ```
 //!
+//! The `include!` can also be written `prost::include_proto!("snazzy.items")`. To generate a module
+//! tree or a single file instead of a file per package, see [`Config::output_layout`].
+//!
 //! That's it! Run `cargo doc` to see documentation for the generated code. The full
 //! example project can be found on [GitHub](https://github.com/danburkert/snazzy).
 //!
@@ -108,15 +111,39 @@
 //!
 //! To force `prost-build` to use the `protoc` on the `PATH`, add `PROTOC=protoc` to the
 //! environment.
+//!
+//! ## Building without `protoc`
+//!
+//! With the `no-protoc` feature, `prost-build` parses `.proto` files itself, and `protoc` is not
+//! needed. The Protobuf include directory is still searched for imports such as
+//! `google/protobuf/timestamp.proto`. The generated code is the same, with a few limitations:
+//!
+//! * Less validation is done than by `protoc`, so some invalid files are accepted.
+//! * Arguments added with [`Config::protoc_arg`] are ignored.
+//! * Custom options are available to code generators, but are not included in the file
+//!   descriptor set written to [`Config::file_descriptor_set_path`].
+//! * Editions are not supported.
+//!
+//! ## Formatting
+//!
+//! The generated files are written in a stable order, so that they are the same across builds
+//! and platforms. With the `format` feature, the generated code is also formatted with
+//! `prettyplease`, unless disabled with `Config::format`, which helps when the generated files are
+//! checked in.
 
 mod ast;
 mod code_generator;
+mod custom_options;
 mod extern_paths;
 mod ident;
 mod message_graph;
+#[cfg(feature = "no-protoc")]
+mod parse;
 mod path;
+mod service_trait;
+mod validate;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::default;
 use std::env;
 use std::ffi::{OsStr, OsString};
@@ -124,18 +151,22 @@ use std::fmt;
 use std::fs;
 use std::io::{Error, ErrorKind, Result, Write};
 use std::path::{Path, PathBuf};
+#[cfg(not(feature = "no-protoc"))]
 use std::process::Command;
 
 use log::trace;
-use prost::Message;
+use prost::{DecodeError, Message};
 use prost_types::{FileDescriptorProto, FileDescriptorSet};
 
 pub use crate::ast::{Comments, Method, Service};
 use crate::code_generator::CodeGenerator;
+pub use crate::custom_options::{CustomOption, CustomOptions, CustomOptionsIndex};
 use crate::extern_paths::ExternPaths;
 use crate::ident::to_snake;
 use crate::message_graph::MessageGraph;
 use crate::path::PathMap;
+pub use crate::service_trait::ServiceTraitGenerator;
+use crate::validate::ValidationRules;
 
 type Module = Vec<String>;
 
@@ -152,7 +183,8 @@ type Module = Vec<String>;
 /// details like how errors are handled or if it is asynchronous. Then the user provides an
 /// implementation of the generated trait in the application code and plugs it into the framework.
 ///
-/// Such framework isn't part of Prost at present.
+/// Such framework isn't part of Prost at present. [`ServiceTraitGenerator`] is a reference
+/// implementation, which generates a plain Rust trait for each service.
 pub trait ServiceGenerator {
     /// Generates a Rust interface or implementation for a service, writing the
     /// result to `buf`.
@@ -186,12 +218,14 @@ pub trait ServiceGenerator {
 
 /// The map collection type to output for Protobuf `map` fields.
 #[non_exhaustive]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum MapType {
     /// The [`std::collections::HashMap`] type.
     HashMap,
     /// The [`std::collections::BTreeMap`] type.
     BTreeMap,
+    /// A map type implementing `prost::ProstMap`, given by its path.
+    Custom(String),
 }
 
 impl Default for MapType {
@@ -216,6 +250,60 @@ impl Default for BytesType {
     }
 }
 
+/// The Rust type to output for Protobuf `string` fields, see [`Config::string_type`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum StringType {
+    /// The [`String`] type, which is the default.
+    String,
+    /// The `Cow<'static, str>` type, which can hold static strings without allocating.
+    Cow,
+    /// The `prost::ByteStr` type, which shares the buffer it was decoded from when decoding from
+    /// `Bytes`.
+    Bytes,
+    /// The [`SmolStr`][1] type, which stores short strings inline. This requires the `smol_str`
+    /// feature of `prost`.
+    ///
+    /// [1]: https://docs.rs/smol_str/latest/smol_str/struct.SmolStr.html
+    SmolStr,
+    /// A custom type implementing `prost::StringAdapter`, given by its fully-qualified Rust path,
+    /// e.g. `crate::intern::Symbol`.
+    Custom(String),
+}
+
+impl Default for StringType {
+    fn default() -> StringType {
+        StringType::String
+    }
+}
+
+/// The layout of the generated files, set with [`Config::output_layout`].
+#[non_exhaustive]
+#[derive(Clone, Debug, PartialEq)]
+pub enum OutputLayout {
+    /// A file for each package, named after the package, such as `foo.bar.rs`.
+    ///
+    /// The files can be included with `prost::include_proto!`, or with the file configured with
+    /// [`Config::include_file`].
+    Files,
+    /// A module tree, with a `mod.rs` file for each package in a directory following the
+    /// package, such as `foo/bar/mod.rs`, declaring the modules of the nested packages.
+    ///
+    /// The `mod.rs` file of the output directory declares the top-level modules, and contains the
+    /// code of files without a package. This layout is meant for generated code which is checked
+    /// in, since the `mod` items cannot refer to `OUT_DIR`.
+    ModuleTree,
+    /// A single file, with the packages as nested modules, written to the path relative to the
+    /// output directory.
+    SingleFile(PathBuf),
+}
+
+impl Default for OutputLayout {
+    fn default() -> OutputLayout {
+        OutputLayout::Files
+    }
+}
+
 /// Configuration options for Protobuf code generation.
 ///
 /// This configuration builder can be used to set non-default code generation options.
@@ -224,8 +312,28 @@ pub struct Config {
     service_generator: Option<Box<dyn ServiceGenerator>>,
     map_type: PathMap<MapType>,
     bytes_type: PathMap<BytesType>,
+    string_type: PathMap<StringType>,
+    char_fields: PathMap<()>,
+    nonzero_fields: PathMap<()>,
+    iterative_recursion: PathMap<()>,
+    message_pipeline: PathMap<()>,
+    builders: PathMap<()>,
+    accessors: PathMap<()>,
+    presence_bits: PathMap<()>,
+    enforce_required: PathMap<()>,
+    validate: PathMap<()>,
+    arbitrary: PathMap<()>,
+    proptest_arbitrary: PathMap<()>,
+    timestamp_map_keys: PathMap<()>,
+    duration_map_keys: PathMap<()>,
+    int64_numbers: PathMap<()>,
+    enum_numbers: PathMap<()>,
+    boxed: PathMap<()>,
+    box_large_messages: Option<usize>,
     type_attributes: PathMap<String>,
     field_attributes: PathMap<String>,
+    derives: PathMap<String>,
+    check_encode: PathMap<String>,
     prost_types: bool,
     strip_enum_prefix: bool,
     out_dir: Option<PathBuf>,
@@ -233,8 +341,16 @@ pub struct Config {
     default_package_filename: String,
     protoc_args: Vec<OsString>,
     disable_comments: PathMap<()>,
+    text_code_blocks: PathMap<()>,
     skip_protoc_run: bool,
     include_file: Option<PathBuf>,
+    embed_file_descriptor_set: bool,
+    enable_type_names: bool,
+    enable_serde_json: bool,
+    output_layout: OutputLayout,
+    package_features: Option<PathBuf>,
+    #[cfg(feature = "format")]
+    fmt: bool,
 }
 
 impl Config {
@@ -258,6 +374,13 @@ impl Config {
     /// The matching is done on the Protobuf names, before converting to Rust-friendly casing
     /// standards.
     ///
+    /// Paths containing a `*` are glob patterns. A `*` matches any part of a single path
+    /// component, and a `**` component matches any number of components. Unlike other paths, a
+    /// glob pattern only matches whole names, so `.my_messages.*` matches the map fields of the
+    /// messages in the `my_messages` package, but not those of nested messages, which
+    /// `.my_messages.**` matches. A full or suffix path match takes precedence over a glob
+    /// pattern, which takes precedence over a prefix match.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -286,6 +409,13 @@ impl Config {
     ///
     /// // Match all fields named 'my_map_field', and all fields in the 'foo.bar' package.
     /// config.btree_map(&["my_map_field", ".foo.bar"]);
+    ///
+    /// // Match all fields whose name ends with '_index' in the messages of the 'my_messages'
+    /// // package, but not in nested messages.
+    /// config.btree_map(&[".my_messages.*.*_index"]);
+    ///
+    /// // Match all fields named 'labels' in the 'my_messages' package or its sub-packages.
+    /// config.btree_map(&[".my_messages.**.labels"]);
     /// ```
     ///
     /// [1]: https://doc.rust-lang.org/std/collections/struct.BTreeMap.html
@@ -304,6 +434,54 @@ impl Config {
         self
     }
 
+    /// Configure the code generator to generate Rust fields of the given map type for Protobuf
+    /// [`map`][1] type fields, instead of [`HashMap`][2].
+    ///
+    /// The map type is the path of a generic type taking the key and value types as parameters,
+    /// such as `indexmap::IndexMap`, which preserves the order in which entries were inserted or
+    /// decoded. The generated `Message` implementation encodes and decodes the field through the
+    /// `prost::ProstMap` trait, which `prost` implements for `HashMap`, `BTreeMap` and, with its
+    /// `indexmap` feature, `IndexMap`, and which can be implemented for other map types. Entries
+    /// are encoded in the iteration order of the map.
+    ///
+    /// Unlike most options, calls add to the paths configured by previous calls, so that different
+    /// fields can use different map types. If a field matches paths configured with different
+    /// types, the type of the most specific path applies. Calling
+    /// [`btree_map`](#method.btree_map) replaces the paths configured by this method.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages which should use the
+    /// `map_type` for Protobuf `map` fields. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// **`map_type`** - the path of the Rust map type of the matched fields.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Keep the entries of configuration maps in the order they were written.
+    /// config.map_type(&[".my_config"], "indexmap::IndexMap");
+    /// ```
+    ///
+    /// [1]: https://developers.google.com/protocol-buffers/docs/proto3#maps
+    /// [2]: https://doc.rust-lang.org/std/collections/struct.HashMap.html
+    pub fn map_type<I, S, T>(&mut self, paths: I, map_type: T) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+        T: AsRef<str>,
+    {
+        for matcher in paths {
+            self.map_type.insert(
+                matcher.as_ref().to_string(),
+                MapType::Custom(map_type.as_ref().to_string()),
+            );
+        }
+        self
+    }
+
     /// Configure the code generator to generate Rust [`bytes::Bytes`][1] fields for Protobuf
     /// [`bytes`][2] type fields.
     ///
@@ -365,198 +543,1044 @@ impl Config {
         self
     }
 
-    /// Add additional attribute to matched fields.
+    /// Configure the code generator to generate Rust fields of the given type for Protobuf
+    /// `string` fields, instead of [`String`].
     ///
-    /// # Arguments
+    /// Unlike most options, calls add to the paths configured by previous calls, so that different
+    /// fields can use different string types. If a field matches paths configured with different
+    /// types, the type of the most specific path applies.
     ///
-    /// **`path`** - a path matching any number of fields. These fields get the attribute.
-    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    /// The generated `Message` implementation encodes and decodes the field through the
+    /// `prost::StringAdapter` trait, which can be implemented for custom string types, such as
+    /// interned strings. Types which restrict their contents can reject values in
+    /// `StringAdapter::check_encode`, which `Message::encode_checked` calls before encoding.
+    /// Protobuf `map` fields always use `String` keys and values.
     ///
-    /// **`attribute`** - an arbitrary string that'll be placed before each matched field. The
-    /// expected usage are additional attributes, usually in concert with whole-type
-    /// attributes set with [`type_attribute`](method.type_attribute), but it is not
-    /// checked and anything can be put there.
+    /// # Arguments
     ///
-    /// Note that the calls to this method are cumulative ‒ if multiple paths from multiple calls
-    /// match the same field, the field gets all the corresponding attributes.
+    /// **`paths`** - paths to specific fields, messages, or packages which should use the
+    /// `string_type` for Protobuf `string` fields. Paths are specified in terms of the Protobuf
+    /// type name (not the generated Rust type name). Paths with a leading `.` are treated as fully
+    /// qualified names. Paths without a leading `.` are treated as relative, and are suffix
+    /// matched on the fully qualified field name.
+    ///
+    /// **`string_type`** - the Rust type of the matched fields.
     ///
     /// # Examples
     ///
     /// ```rust
+    /// # use prost_build::StringType;
     /// # let mut config = prost_build::Config::new();
-    /// // Prost renames fields named `in` to `in_`. But if serialized through serde,
-    /// // they should as `in`.
-    /// config.field_attribute("in", "#[serde(rename = \"in\")]");
+    /// // Decode the strings of a hot-path message without copying them.
+    /// config.string_type(&[".my_messages.Event"], StringType::Bytes);
+    ///
+    /// // Use interned strings for a field repeated across many messages.
+    /// config.string_type(
+    ///     &["LogLine.host"],
+    ///     StringType::Custom("crate::intern::Symbol".to_string()),
+    /// );
     /// ```
-    pub fn field_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    pub fn string_type<I, S>(&mut self, paths: I, string_type: StringType) -> &mut Self
     where
-        P: AsRef<str>,
-        A: AsRef<str>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
     {
-        self.field_attributes
-            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        for matcher in paths {
+            self.string_type
+                .insert(matcher.as_ref().to_string(), string_type.clone());
+        }
         self
     }
 
-    /// Add additional attribute to matched messages, enums and one-ofs.
+    /// Configure the code generator to generate Rust `char` fields for Protobuf `uint32` fields.
     ///
     /// # Arguments
     ///
-    /// **`paths`** - a path matching any number of types. It works the same way as in
-    /// [`btree_map`](#method.btree_map), just with the field name omitted.
-    ///
-    /// **`attribute`** - an arbitrary string to be placed before each matched type. The
-    /// expected usage are additional attributes, but anything is allowed.
-    ///
-    /// The calls to this method are cumulative. They don't overwrite previous calls and if a
-    /// type is matched by multiple calls of the method, all relevant attributes are added to
-    /// it.
+    /// **`paths`** - paths to specific fields, messages, or packages which should use a Rust
+    /// `char` for Protobuf `uint32` fields. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
     ///
-    /// For things like serde it might be needed to combine with [field
-    /// attributes](#method.field_attribute).
+    /// Decoding a value which is not a Unicode scalar value fails, so the generated field always
+    /// holds a valid `char`. Oneof fields, map fields and `required` fields are not affected.
     ///
     /// # Examples
     ///
     /// ```rust
     /// # let mut config = prost_build::Config::new();
-    /// // Nothing around uses floats, so we can derive real `Eq` in addition to `PartialEq`.
-    /// config.type_attribute(".", "#[derive(Eq)]");
-    /// // Some messages want to be serializable with serde as well.
-    /// config.type_attribute("my_messages.MyMessageType",
-    ///                       "#[derive(Serialize)] #[serde(rename-all = \"snake_case\")]");
-    /// config.type_attribute("my_messages.MyMessageType.MyNestedMessageType",
-    ///                       "#[derive(Serialize)] #[serde(rename-all = \"snake_case\")]");
+    /// config.char(&[".my_messages.Glyph.code_point"]);
     /// ```
+    pub fn char<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.char_fields.clear();
+        for matcher in paths {
+            self.char_fields.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to generate Rust [`NonZeroU32`][1] and [`NonZeroU64`][2]
+    /// fields for Protobuf `uint32` and `uint64` fields.
     ///
-    /// # Oneof fields
+    /// # Arguments
     ///
-    /// The `oneof` fields don't have a type name of their own inside Protobuf. Therefore, the
-    /// field name can be used both with `type_attribute` and `field_attribute` ‒ the first is
-    /// placed before the `enum` type definition, the other before the field inside corresponding
-    /// message `struct`.
+    /// **`paths`** - paths to specific fields, messages, or packages which should use non-zero
+    /// integer types. For details about matching fields see [`btree_map`](#method.btree_map).
     ///
-    /// In other words, to place an attribute on the `enum` implementing the `oneof`, the match
-    /// would look like `my_messages.MyMessageType.oneofname`.
-    pub fn type_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    /// Non-zero types have no default value, so singular fields are generated as an `Option`,
+    /// which is `None` when the field is not present. Decoding a zero value fails. Oneof fields,
+    /// map fields, `required` fields and fields with a default value are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate `Option<NonZeroU64>` for all `uint64` fields named `id`.
+    /// config.nonzero(&["id"]);
+    /// ```
+    ///
+    /// [1]: https://doc.rust-lang.org/std/num/struct.NonZeroU32.html
+    /// [2]: https://doc.rust-lang.org/std/num/struct.NonZeroU64.html
+    pub fn nonzero<I, S>(&mut self, paths: I) -> &mut Self
     where
-        P: AsRef<str>,
-        A: AsRef<str>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
     {
-        self.type_attributes
-            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self.nonzero_fields.clear();
+        for matcher in paths {
+            self.nonzero_fields.insert(matcher.as_ref().to_string(), ());
+        }
         self
     }
 
-    /// Configures the code generator to use the provided service generator.
-    pub fn service_generator(&mut self, service_generator: Box<dyn ServiceGenerator>) -> &mut Self {
-        self.service_generator = Some(service_generator);
+    /// Configure the code generator to hold message fields in a `Box`.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages whose message fields should
+    /// be boxed. For details about matching fields see [`btree_map`](#method.btree_map). Fields of
+    /// a oneof can be matched with or without the name of the oneof.
+    ///
+    /// By default, only message fields which recursively contain the message holding them are
+    /// boxed. Boxing large message fields keeps the size of the generated structs, and of the
+    /// oneof enums holding them, small, at the cost of an allocation when the field is set. A
+    /// boxed singular field is generated as `Option<Box<T>>`, and a boxed oneof field holds a
+    /// `Box<T>`.
+    ///
+    /// Only singular message fields and oneof fields of message types are boxed, other fields are
+    /// not affected. When [`enable_serde_json`](#method.enable_serde_json) is set, the well-known
+    /// types which are serialized by the helpers of `prost_types::serde`, such as `Timestamp`, are
+    /// not boxed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Box the `payload` field of `Event`, and all message fields in the `documents` package.
+    /// config.boxed(&[".events.Event.payload", ".documents"]);
+    /// ```
+    pub fn boxed<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.boxed.clear();
+        for matcher in paths {
+            self.boxed.insert(matcher.as_ref().to_string(), ());
+        }
         self
     }
 
-    /// Configures the code generator to not use the `prost_types` crate for Protobuf well-known
-    /// types, and instead generate Protobuf well-known types from their `.proto` definitions.
-    pub fn compile_well_known_types(&mut self) -> &mut Self {
-        self.prost_types = false;
+    /// Configure the code generator to box all message fields whose message type is larger than
+    /// the threshold.
+    ///
+    /// # Arguments
+    ///
+    /// **`threshold`** - the size in bytes above which message fields are boxed.
+    ///
+    /// The size of each message is estimated from its fields, as laid out on 64-bit targets, and
+    /// taking into account the fields which are boxed. Singular message fields and oneof fields of
+    /// larger message types are boxed as if they were matched by [`boxed`](#method.boxed), so that
+    /// large generated structs don't exhaust the stack, and oneof enums holding a large variant
+    /// stay small.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Box the fields of messages larger than 512 bytes.
+    /// config.box_large_messages(512);
+    /// ```
+    pub fn box_large_messages(&mut self, threshold: usize) -> &mut Self {
+        self.box_large_messages = Some(threshold);
         self
     }
 
-    /// Configures the code generator to omit documentation comments on generated Protobuf types.
+    /// Configure the code generator to generate stack-safe `Clone`, `PartialEq` and `Drop`
+    /// implementations for recursive messages.
     ///
-    /// # Example
+    /// # Arguments
     ///
-    /// Occasionally `.proto` files contain code blocks which are not valid Rust. To avoid doctest
-    /// failures, annotate the invalid code blocks with an [`ignore` or `no_run` attribute][1], or
-    /// disable doctests for the crate with a [Cargo.toml entry][2]. If neither of these options
-    /// are possible, then omit comments on generated code during doctest builds:
+    /// **`paths`** - paths to specific messages or packages which should use iterative
+    /// implementations. For details about matching messages see [`btree_map`](#method.btree_map),
+    /// just with the field name omitted.
     ///
-    /// ```rust,ignore
-    /// let mut config = prost_build::Config::new();
-    /// config.disable_comments(".");
-    /// config.compile_protos(&["src/frontend.proto", "src/backend.proto"], &["src"])?;
-    /// ```
+    /// The derived `Clone` and `PartialEq` implementations, and the compiler generated drop glue,
+    /// recurse into nested messages, so a deep enough message tree overflows the stack. A matched
+    /// message which contains itself, directly or through other matched messages, instead
+    /// implements [`prost::recursive::Recursive`][1], and walks the tree with a work-list on the
+    /// heap. Matched messages which are not recursive are generated as usual.
     ///
-    /// As with other options which take a set of paths, comments can be disabled on a per-package
-    /// or per-symbol basis.
+    /// Since the generated messages implement `Drop`, their fields can't be moved out by
+    /// destructuring, and struct update syntax (`..Default::default()`) can't be used with them.
     ///
-    /// [1]: https://doc.rust-lang.org/rustdoc/documentation-tests.html#attributes
-    /// [2]: https://doc.rust-lang.org/cargo/reference/cargo-targets.html#configuring-a-target
-    pub fn disable_comments<I, S>(&mut self, paths: I) -> &mut Self
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate iterative implementations for all recursive messages.
+    /// config.iterative_recursion(&["."]);
+    ///
+    /// // Generate iterative implementations for recursive messages in the `syntax` package.
+    /// config.iterative_recursion(&[".syntax"]);
+    /// ```
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/recursive/trait.Recursive.html
+    pub fn iterative_recursion<I, S>(&mut self, paths: I) -> &mut Self
     where
         I: IntoIterator<Item = S>,
         S: AsRef<str>,
     {
-        self.disable_comments.clear();
+        self.iterative_recursion.clear();
         for matcher in paths {
-            self.disable_comments
+            self.iterative_recursion
                 .insert(matcher.as_ref().to_string(), ());
         }
         self
     }
 
-    /// Declare an externally provided Protobuf package or type.
+    /// Configure the code generator to implement `prost_types::pipeline::Pipelined` for messages,
+    /// so they can be decoded and processed by a `prost_types::pipeline::Pipeline`.
     ///
-    /// `extern_path` allows `prost` types in external crates to be referenced in generated code.
+    /// # Arguments
     ///
-    /// When `prost` compiles a `.proto` which includes an import of another `.proto`, it will
-    /// automatically recursively compile the imported file as well. `extern_path` can be used
-    /// to instead substitute types from an external crate.
+    /// **`paths`** - paths to specific messages or packages which should implement `Pipelined`.
+    /// For details about matching messages see [`btree_map`](#method.btree_map), just with the
+    /// field name omitted.
     ///
-    /// # Example
+    /// A pipeline runs asynchronous hooks, such as validation or the resolution of references
+    /// against a cache, on each message it decodes. The generated code refers to `prost_types`.
     ///
-    /// As an example, consider a crate, `uuid`, with a `prost`-generated `Uuid` type:
+    /// # Examples
     ///
-    /// ```proto
-    /// // uuid.proto
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Process the requests of the `orders` package with pipelines.
+    /// config.message_pipeline(&[".orders.*Request"]);
+    /// ```
+    pub fn message_pipeline<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.message_pipeline.clear();
+        for matcher in paths {
+            self.message_pipeline
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to generate builders for messages.
     ///
-    /// syntax = "proto3";
-    /// package uuid;
+    /// # Arguments
     ///
-    /// message Uuid {
-    ///     string uuid_str = 1;
-    /// }
-    /// ```
+    /// **`paths`** - paths to specific messages or packages which should get a builder. For
+    /// details about matching messages see [`btree_map`](#method.btree_map), just with the field
+    /// name omitted.
     ///
-    /// The `uuid` crate implements some traits for `Uuid`, and publicly exports it:
+    /// A matched message `Foo` gets a `Foo::builder()` function, returning a `FooBuilder` with a
+    /// setter for each field, named after the field, and a `build()` method returning the message.
+    /// Fields which are not set hold their default value.
     ///
-    /// ```rust,ignore
-    /// // lib.rs in the uuid crate
+    /// * Setters of scalar, string, bytes and message fields accept any value which converts into
+    ///   the field type with `Into`, and wrap it in `Some` or `Box` as needed.
+    /// * Setters of enum fields accept the enum type, rather than `i32`.
+    /// * Setters of `repeated` and `map` fields accept an iterator over the values or entries.
+    /// * Each field of a oneof gets a setter, which sets the oneof to that field.
     ///
-    /// include!(concat!(env!("OUT_DIR"), "/uuid.rs"));
+    /// The setter of a field named `build` is named `build_`.
     ///
-    /// pub trait DoSomething {
-    ///     fn do_it(&self);
-    /// }
+    /// # Examples
     ///
-    /// impl DoSomething for Uuid {
-    ///     fn do_it(&self) {
-    ///         println!("Done");
-    ///     }
-    /// }
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate builders for all messages in the `orders` package.
+    /// config.builders(&[".orders"]);
     /// ```
     ///
-    /// A separate crate, `my_application`, uses `prost` to generate message types which reference
-    /// `Uuid`:
+    /// With the generated builder, a message is constructed as:
     ///
-    /// ```proto
-    /// // my_application.proto
+    /// ```rust,ignore
+    /// let order = Order::builder()
+    ///     .id(42u32)
+    ///     .customer("ACME")
+    ///     .status(order::Status::Open)
+    ///     .items(vec![Item::builder().sku("123").build()])
+    ///     .build();
+    /// ```
+    pub fn builders<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.builders.clear();
+        for matcher in paths {
+            self.builders.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to generate accessor methods for the fields of messages.
     ///
-    /// syntax = "proto3";
-    /// package my_application;
+    /// # Arguments
     ///
-    /// import "uuid.proto";
+    /// **`paths`** - paths to specific messages or packages which should get accessors. For
+    /// details about matching messages see [`btree_map`](#method.btree_map), just with the field
+    /// name omitted.
     ///
-    /// message MyMessage {
-    ///     uuid.Uuid message_id = 1;
-    ///     string some_payload = 2;
-    /// }
-    /// ```
+    /// The accessors mirror the API of other Protobuf runtimes, and hide the representation of the
+    /// fields, such as `Option<Box<_>>` for recursive messages. For a field `foo`, a matched
+    /// message gets:
     ///
-    /// Additionally, `my_application` depends on the trait impls provided by the `uuid` crate:
+    /// * `foo()`, returning the value of a scalar field, or its default value if it is unset, and
+    ///   `Option<&T>` for a message field.
+    /// * `foo_mut()` for a message field, returning a mutable reference to the message, which is
+    ///   set to the default value first if it is unset.
+    /// * `set_foo(value)`, setting the field to the value.
+    /// * `has_foo()` for a field with explicit presence, returning whether the field is set.
+    /// * `clear_foo()`, unsetting the field, or resetting it to its default value.
     ///
-    /// ```rust,ignore
-    /// // `main.rs` of `my_application`
+    /// Repeated and map fields only get `clear_foo()`, and required fields don't get it. Each
+    /// field of a oneof gets all of the accessors, which read the field only when the oneof is set
+    /// to it. The getters and setters which `prost` already generates for enumeration fields and
+    /// for optional scalar fields are not duplicated.
     ///
-    /// use uuid::{DoSomething, Uuid};
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate accessors for all messages.
+    /// config.accessors(&["."]);
+    /// ```
+    pub fn accessors<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.accessors.clear();
+        for matcher in paths {
+            self.accessors.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to track the presence of the optional scalar and message
+    /// fields of messages in a bitset, instead of holding each of them in an `Option`.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages whose optional fields should use
+    /// presence bits. For details about matching messages see
+    /// [`btree_map`](#method.btree_map), just with the field name omitted.
+    ///
+    /// Optional scalar fields are the `optional` numeric, `bool`, enumeration, `string` and
+    /// `bytes` fields of `proto2` messages, and the fields declared `optional` in `proto3`
+    /// messages. In a matched message, each of them holds its value directly, and is assigned a
+    /// bit of a `_presence: prost::PresenceBits<N>` field, which records whether it is set. For
+    /// messages with hundreds of optional fields, this saves most of the memory taken by the
+    /// `Option` discriminants and their padding.
+    ///
+    /// Optional message fields also hold their message directly, unless they are part of a
+    /// recursion cycle. `Message::clear` clears them in place, so a message which is cleared and
+    /// decoded again reuses the allocations of the messages nested in it. They provide `has_foo()`
+    /// and `clear_foo()`, while their `foo()`, `foo_mut()` and `set_foo(value)` accessors are
+    /// generated with [`accessors`](#method.accessors).
+    ///
+    /// For an optional scalar field `foo`, the generated code provides:
+    ///
+    /// * `foo()`, returning the value of the field, or its default value if it is unset.
+    /// * `set_foo(value)`, setting the value and the presence bit of the field.
+    /// * `has_foo()`, returning whether the field is set.
+    /// * `clear_foo()`, unsetting the field and resetting its value to the default value.
+    ///
+    /// A field is only encoded if its presence bit is set, so assigning its value directly
+    /// without calling `set_foo` has no effect on the encoded message. Presence bits are not used
+    /// for messages generated with [`enable_serde_json`](#method.enable_serde_json), whose JSON
+    /// mapping relies on the `Option`s.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Track the presence of the many optional fields of a telemetry record in a bitset.
+    /// config.presence_bits(&[".telemetry.Sample"]);
+    /// ```
+    pub fn presence_bits<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.presence_bits.clear();
+        for matcher in paths {
+            self.presence_bits.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to track the presence of the `required` fields of `proto2`
+    /// messages, so that decoding can fail when they are missing.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages whose required fields should be
+    /// enforced. For details about matching messages see [`btree_map`](#method.btree_map), just
+    /// with the field name omitted.
+    ///
+    /// By default, a required field which is missing from the encoded message is silently decoded
+    /// as its default value. In a matched message, each required field is assigned a bit of a
+    /// `_presence: prost::PresenceBits<N>` field, shared with the optional fields matched by
+    /// [`presence_bits`](#method.presence_bits), which is set when the field is decoded or set.
+    /// `Message::check_required` then fails if a required field of the message, or of a message
+    /// nested in it, is unset, and `Message::decode_required` decodes a message and checks it.
+    ///
+    /// For a required field `foo`, the generated code provides `has_foo()`, returning whether the
+    /// field is set, and for a scalar field, `foo()`, `set_foo(value)` and `clear_foo()`. Assigning
+    /// the value of a field directly leaves its presence bit unchanged, and required fields are
+    /// encoded whether or not they are set. Required fields are not tracked for messages
+    /// generated with [`enable_serde_json`](#method.enable_serde_json).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Reject legacy records which are missing a required field.
+    /// config.enforce_required(&[".legacy"]);
+    /// ```
+    pub fn enforce_required<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.enforce_required.clear();
+        for matcher in paths {
+            self.enforce_required
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to implement `prost::Validate` for messages, checking the
+    /// validation rules declared with the custom options of their fields.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages which should implement `Validate`.
+    /// For details about matching messages see [`btree_map`](#method.btree_map), just with the
+    /// field name omitted.
+    ///
+    /// The rules are read from the `(validate.rules)` field options of [protoc-gen-validate][1],
+    /// and the `(buf.validate.field)` field options of [protovalidate][2]. The `.proto` files
+    /// defining these options must be importable by `protoc`, but no code needs to be generated
+    /// for them. The following rules are checked:
+    ///
+    ///  * For numbers, `const`, `lt`, `lte`, `gt`, `gte`, `in` and `not_in`, and `finite` for
+    ///    protovalidate floating point numbers.
+    ///  * For `bool` fields, `const`.
+    ///  * For strings, `const`, `len`, `min_len`, `max_len`, `len_bytes`, `min_bytes`,
+    ///    `max_bytes`, `pattern`, `prefix`, `suffix`, `contains`, `not_contains`, `in` and `not_in`.
+    ///    Checking `pattern` requires the crate to depend on the [`regex`][3] crate, and compiles
+    ///    the expression on each check.
+    ///  * For bytes, `const`, `len`, `min_len`, `max_len`, `prefix`, `suffix`, `contains`, `in`
+    ///    and `not_in`.
+    ///  * For enumerations, `const`, `defined_only`, `in` and `not_in`.
+    ///  * For repeated fields, `min_items`, `max_items`, `unique` and the `items` rules of their
+    ///    values.
+    ///  * For map fields, `min_pairs` and `max_pairs`.
+    ///  * The `required` rule of message fields, of fields with explicit presence and, with
+    ///    protovalidate, of any field, and the `required` rule of oneofs.
+    ///  * The `ignore_empty` rule of protoc-gen-validate and the `ignore` rule of protovalidate,
+    ///    and the `skip` rule of protoc-gen-validate message fields.
+    ///
+    /// Other rules, such as string formats, the rules of well-known types and CEL expressions, are
+    /// not checked. The messages embedded in a message are validated as well, if their type also
+    /// implements `Validate`, that is if it is matched by `paths` and not an extern type.
+    ///
+    /// Code generation panics if the rules of a field do not apply to its type.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Validate the requests of the API.
+    /// config.validate(&[".api.v1"]);
+    /// ```
+    ///
+    /// [1]: https://github.com/bufbuild/protoc-gen-validate
+    /// [2]: https://github.com/bufbuild/protovalidate
+    /// [3]: https://docs.rs/regex
+    pub fn validate<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.validate.clear();
+        for matcher in paths {
+            self.validate.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to implement `arbitrary::Arbitrary` for messages and enums,
+    /// so that fuzz targets and property tests can generate them.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages, enums or packages which should implement
+    /// `Arbitrary`. For details about matching messages see [`btree_map`](#method.btree_map), just
+    /// with the field name omitted.
+    ///
+    /// Each field of a generated message is set from the input in turn: optional fields are set
+    /// or not, repeated and map fields get any number of values, and oneofs are set to any of
+    /// their fields. Enum fields only take the values of the enum. Message fields are generated
+    /// with `prost_types::arbitrary::nested`, which bounds the depth of recursive messages, so the
+    /// generated code refers to the `arbitrary` crate and to `prost_types` built with its
+    /// `arbitrary` feature, which must both be dependencies of the crate including it.
+    ///
+    /// The types of the message and enum fields of matched messages must implement `Arbitrary`
+    /// as well, so they should be matched too. `prost_types` implements it for the well-known
+    /// types which have a JSON mapping. Fields with a custom string type are converted from a
+    /// `String`, and must implement `From<String>`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate arbitrary messages of all types, e.g. for fuzzing.
+    /// config.arbitrary(&["."]);
+    /// ```
+    ///
+    /// Round trips through the binary encoding are then checked by fuzz targets with:
+    ///
+    /// ```rust,ignore
+    /// fuzz_target!(|order: Order| prost_types::arbitrary::assert_roundtrip(&order));
+    /// ```
+    pub fn arbitrary<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.arbitrary.clear();
+        for matcher in paths {
+            self.arbitrary.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to implement `proptest::arbitrary::Arbitrary` for messages
+    /// and enums, so that property tests can take them as `any::<T>()`.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages, enums or packages which should implement the
+    /// `proptest` trait. For details about matching messages see [`btree_map`](#method.btree_map),
+    /// just with the field name omitted.
+    ///
+    /// The matched types also implement `arbitrary::Arbitrary`, as with
+    /// [`arbitrary`](#method.arbitrary), and their strategy is `prost_types::arbitrary::strategy`,
+    /// which generates them from random bytes, and shrinks them by shrinking the bytes. The
+    /// generated code refers to the `proptest` crate, and to `prost_types` built with its
+    /// `proptest` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.proptest_arbitrary(&[".orders"]);
+    /// ```
+    ///
+    /// Round trips through the binary and the JSON encodings are then checked with:
+    ///
+    /// ```rust,ignore
+    /// prost_types::proptest_roundtrip!(order_roundtrip, Order, json);
+    /// ```
+    pub fn proptest_arbitrary<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.proptest_arbitrary.clear();
+        for matcher in paths {
+            self.proptest_arbitrary
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to serialize the keys of `map<string, V>` fields as
+    /// timestamps, when [`enable_serde_json`](#method.enable_serde_json) is set.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages whose string-keyed maps hold
+    /// RFC 3339 date-times as keys. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// Protobuf does not allow `google.protobuf.Timestamp` map keys, so time-bucketed data is
+    /// commonly keyed by formatted timestamps. The matched fields are serialized with
+    /// `prost_types::serde::timestamp_key`, which normalizes each key to the canonical RFC 3339
+    /// form in UTC, and rejects keys which are not timestamps. Map fields with other key types
+    /// are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enable_serde_json();
+    /// config.timestamp_map_keys(&[".metrics.Series.buckets"]);
+    /// ```
+    pub fn timestamp_map_keys<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.timestamp_map_keys.clear();
+        for matcher in paths {
+            self.timestamp_map_keys
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to serialize the keys of `map<string, V>` fields as
+    /// durations, when [`enable_serde_json`](#method.enable_serde_json) is set.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages whose string-keyed maps hold
+    /// durations in seconds, such as `"1.5s"`, as keys. For details about matching fields see
+    /// [`btree_map`](#method.btree_map).
+    ///
+    /// The matched fields are serialized with `prost_types::serde::duration_key`, which
+    /// normalizes each key to the canonical form of the Protobuf JSON mapping, and rejects keys
+    /// which are not durations. Map fields with other key types are not affected. A field matched
+    /// by both this and [`timestamp_map_keys`](#method.timestamp_map_keys) uses timestamp keys.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enable_serde_json();
+    /// config.duration_map_keys(&[".metrics.LatencyHistogram.counts"]);
+    /// ```
+    pub fn duration_map_keys<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.duration_map_keys.clear();
+        for matcher in paths {
+            self.duration_map_keys
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to serialize 64-bit integer fields as JSON numbers rather
+    /// than strings, when [`enable_serde_json`](#method.enable_serde_json) is set.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages whose 64-bit integers are
+    /// read by consumers which parse JSON numbers exactly, such as JavaScript with `BigInt`. For
+    /// details about matching fields see [`btree_map`](#method.btree_map); `"."` matches every
+    /// field.
+    ///
+    /// The matched `int64`, `uint64`, `sint64`, `fixed64` and `sfixed64` fields, and
+    /// `google.protobuf.Int64Value` and `UInt64Value` fields, are serialized with
+    /// `prost_types::serde::int64::number`, which writes numbers whatever the runtime mode, and
+    /// still accepts both strings and numbers when deserializing.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enable_serde_json();
+    /// config.int64_numbers(&[".analytics", ".accounts.User.id"]);
+    /// ```
+    pub fn int64_numbers<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.int64_numbers.clear();
+        for matcher in paths {
+            self.int64_numbers.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to serialize enum fields as JSON numbers rather than by the
+    /// names of their values, when [`enable_serde_json`](#method.enable_serde_json) is set.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages whose enum values are read by
+    /// consumers which don't know their names, as with the `always_print_enums_as_ints` option of
+    /// the reference implementations. For details about matching fields see
+    /// [`btree_map`](#method.btree_map); `"."` matches every field.
+    ///
+    /// The matched enum fields, and map fields with enum values, are serialized with
+    /// `prost_types::serde::enumeration::number`, which writes numbers whatever the runtime mode, and still accepts both names and numbers
+    /// when deserializing. Values which the enum doesn't define are always written as numbers.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enable_serde_json();
+    /// config.enum_numbers(&[".telemetry", ".accounts.User.status"]);
+    /// ```
+    pub fn enum_numbers<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.enum_numbers.clear();
+        for matcher in paths {
+            self.enum_numbers.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Add additional attribute to matched fields.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of fields. These fields get the attribute.
+    /// For details about matching fields see [`btree_map`](#method.btree_map).
+    ///
+    /// **`attribute`** - an arbitrary string that'll be placed before each matched field. The
+    /// expected usage are additional attributes, usually in concert with whole-type
+    /// attributes set with [`type_attribute`](method.type_attribute), but it is not
+    /// checked and anything can be put there.
+    ///
+    /// Note that the calls to this method are cumulative ‒ if multiple paths from multiple calls
+    /// match the same field, the field gets all the corresponding attributes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Prost renames fields named `in` to `in_`. But if serialized through serde,
+    /// // they should as `in`.
+    /// config.field_attribute("in", "#[serde(rename = \"in\")]");
+    /// // Fields named `*_at`, in any message of the `events` package, are skipped by serde.
+    /// config.field_attribute(".events.**.*_at", "#[serde(skip)]");
+    /// ```
+    pub fn field_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    where
+        P: AsRef<str>,
+        A: AsRef<str>,
+    {
+        self.field_attributes
+            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self
+    }
+
+    /// Add additional attribute to matched messages, enums and one-ofs.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - a path matching any number of types. It works the same way as in
+    /// [`btree_map`](#method.btree_map), just with the field name omitted.
+    ///
+    /// **`attribute`** - an arbitrary string to be placed before each matched type. The
+    /// expected usage are additional attributes, but anything is allowed.
+    ///
+    /// The calls to this method are cumulative. They don't overwrite previous calls and if a
+    /// type is matched by multiple calls of the method, all relevant attributes are added to
+    /// it.
+    ///
+    /// For things like serde it might be needed to combine with [field
+    /// attributes](#method.field_attribute).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Nothing around uses floats, so we can derive real `Eq` in addition to `PartialEq`.
+    /// config.type_attribute(".", "#[derive(Eq)]");
+    /// // Some messages want to be serializable with serde as well.
+    /// config.type_attribute("my_messages.MyMessageType",
+    ///                       "#[derive(Serialize)] #[serde(rename-all = \"snake_case\")]");
+    /// config.type_attribute("my_messages.MyMessageType.MyNestedMessageType",
+    ///                       "#[derive(Serialize)] #[serde(rename-all = \"snake_case\")]");
+    /// // All the requests of the `my_service` package are `Hash`.
+    /// config.type_attribute(".my_service.*Request", "#[derive(Hash)]");
+    /// ```
+    ///
+    /// # Oneof fields
+    ///
+    /// The `oneof` fields don't have a type name of their own inside Protobuf. Therefore, the
+    /// field name can be used both with `type_attribute` and `field_attribute` ‒ the first is
+    /// placed before the `enum` type definition, the other before the field inside corresponding
+    /// message `struct`.
+    ///
+    /// In other words, to place an attribute on the `enum` implementing the `oneof`, the match
+    /// would look like `my_messages.MyMessageType.oneofname`.
+    pub fn type_attribute<P, A>(&mut self, path: P, attribute: A) -> &mut Self
+    where
+        P: AsRef<str>,
+        A: AsRef<str>,
+    {
+        self.type_attributes
+            .insert(path.as_ref().to_string(), attribute.as_ref().to_string());
+        self
+    }
+
+    /// Derive additional traits on matched messages and enums, where the types of their fields
+    /// allow it.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of types. It works the same way as in
+    /// [`type_attribute`](#method.type_attribute).
+    ///
+    /// **`derives`** - a comma-separated list of traits, such as `"Eq, Hash, Ord"`.
+    ///
+    /// Unlike adding a `#[derive]` with [`type_attribute`](#method.type_attribute), the standard
+    /// traits below are only derived on the messages whose fields implement them, so that the
+    /// generated code always compiles:
+    ///
+    ///  * `Copy` is derived on messages whose fields, including the fields of their oneofs, are
+    ///    all singular scalars other than `string` and `bytes`, such as IDs and coordinates.
+    ///  * `Eq`, `Hash` and `Ord` are not derived on messages with `float` or `double` fields,
+    ///    whose values can be NaN.
+    ///  * `Hash`, `PartialOrd` and `Ord` are only derived on messages whose map fields are
+    ///    generated as `BTreeMap`, see [`btree_map`](#method.btree_map).
+    ///  * A trait is only derived on a message whose message fields have types which derive it in
+    ///    turn. The types of fields mapped with [`extern_path`](#method.extern_path), which
+    ///    include the well-known types, are assumed not to implement the traits.
+    ///
+    /// Other traits are derived as given, such as `::prost::CanonicalHash`, which hashes and
+    /// compares messages with `float`, `double` and map fields canonically, and which the
+    /// well-known types implement. The oneofs of a message derive the same traits as the
+    /// message. Traits which are always derived, such as `Clone`, are ignored, as are `Copy`,
+    /// `Eq`, `Hash`, `PartialOrd` and `Ord` on enums, which always derive them.
+    ///
+    /// The calls to this method are cumulative, like those of
+    /// [`type_attribute`](#method.type_attribute).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Use the messages of the `ids` package as `HashMap` keys, and copy the small ones.
+    /// config.derive(".ids", "Copy, Eq, Hash");
+    /// // Sort the points of the `geo` package, which hold `double` coordinates.
+    /// config.derive(".geo.**", "PartialOrd");
+    /// // Deduplicate any message with `prost::Canonical`.
+    /// config.derive(".", "::prost::CanonicalHash");
+    /// ```
+    pub fn derive<P, D>(&mut self, path: P, derives: D) -> &mut Self
+    where
+        P: AsRef<str>,
+        D: AsRef<str>,
+    {
+        self.derives
+            .insert(path.as_ref().to_string(), derives.as_ref().to_string());
+        self
+    }
+
+    /// Configures the code generator to check matched messages with a function before they are
+    /// encoded by `Message::encode_checked`.
+    ///
+    /// # Arguments
+    ///
+    /// **`path`** - a path matching any number of messages. It works the same way as in
+    /// [`type_attribute`](#method.type_attribute).
+    ///
+    /// **`function`** - the path of a function taking a reference to the message and returning
+    /// `Result<(), prost::InvalidValueError>`, such as a function rejecting messages whose fields
+    /// are inconsistent with each other.
+    ///
+    /// The function is called by the generated `Message::check_encode`, before the values of the
+    /// fields are checked, so an error fails `Message::encode_checked` for the message and for the
+    /// messages embedding it. `Message::encode` doesn't call it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.check_encode(".calendar.Range", "crate::calendar::check_range");
+    /// ```
+    pub fn check_encode<P, F>(&mut self, path: P, function: F) -> &mut Self
+    where
+        P: AsRef<str>,
+        F: AsRef<str>,
+    {
+        self.check_encode
+            .insert(path.as_ref().to_string(), function.as_ref().to_string());
+        self
+    }
+
+    /// Configures the code generator to use the provided service generator.
+    pub fn service_generator(&mut self, service_generator: Box<dyn ServiceGenerator>) -> &mut Self {
+        self.service_generator = Some(service_generator);
+        self
+    }
+
+    /// Configures the code generator to not use the `prost_types` crate for Protobuf well-known
+    /// types, and instead generate Protobuf well-known types from their `.proto` definitions.
+    pub fn compile_well_known_types(&mut self) -> &mut Self {
+        self.prost_types = false;
+        self
+    }
+
+    /// Configures the code generator to omit documentation comments on generated Protobuf types.
+    ///
+    /// # Example
+    ///
+    /// Occasionally `.proto` files contain code blocks which are not valid Rust. To avoid doctest
+    /// failures, annotate the invalid code blocks with an [`ignore` or `no_run` attribute][1], or
+    /// disable doctests for the crate with a [Cargo.toml entry][2], or mark the code blocks as
+    /// text with [`Config::text_code_blocks`]. If none of these options are possible, then omit
+    /// comments on generated code during doctest builds:
+    ///
+    /// ```rust,ignore
+    /// let mut config = prost_build::Config::new();
+    /// config.disable_comments(".");
+    /// config.compile_protos(&["src/frontend.proto", "src/backend.proto"], &["src"])?;
+    /// ```
+    ///
+    /// As with other options which take a set of paths, comments can be disabled on a per-package
+    /// or per-symbol basis.
+    ///
+    /// [1]: https://doc.rust-lang.org/rustdoc/documentation-tests.html#attributes
+    /// [2]: https://doc.rust-lang.org/cargo/reference/cargo-targets.html#configuring-a-target
+    pub fn disable_comments<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.disable_comments.clear();
+        for matcher in paths {
+            self.disable_comments
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configures the code generator to mark the code blocks of documentation comments as text.
+    ///
+    /// Comments in `.proto` files often contain code blocks in other languages, or in Protobuf
+    /// itself, which rustdoc compiles and runs as Rust doctests by default. With this option,
+    /// fenced code blocks without a language are marked as `text`, and indented code blocks are
+    /// converted to fenced `text` code blocks, so that rustdoc renders them as plain text.
+    /// Fenced code blocks which already declare a language, such as `rust` or `json`, are left
+    /// as they are.
+    ///
+    /// Unlike [`Config::disable_comments`], the generated types keep their documentation.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Mark the code blocks of all comments as text.
+    /// config.text_code_blocks(&["."]);
+    /// // Mark only the code blocks of the comments of a package.
+    /// config.text_code_blocks(&[".my_messages"]);
+    /// ```
+    pub fn text_code_blocks<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.text_code_blocks.clear();
+        for matcher in paths {
+            self.text_code_blocks
+                .insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Declare an externally provided Protobuf package or type.
+    ///
+    /// `extern_path` allows `prost` types in external crates to be referenced in generated code.
+    ///
+    /// When `prost` compiles a `.proto` which includes an import of another `.proto`, it will
+    /// automatically recursively compile the imported file as well. `extern_path` can be used
+    /// to instead substitute types from an external crate.
+    ///
+    /// # Example
+    ///
+    /// As an example, consider a crate, `uuid`, with a `prost`-generated `Uuid` type:
+    ///
+    /// ```proto
+    /// // uuid.proto
+    ///
+    /// syntax = "proto3";
+    /// package uuid;
+    ///
+    /// message Uuid {
+    ///     string uuid_str = 1;
+    /// }
+    /// ```
+    ///
+    /// The `uuid` crate implements some traits for `Uuid`, and publicly exports it:
+    ///
+    /// ```rust,ignore
+    /// // lib.rs in the uuid crate
+    ///
+    /// include!(concat!(env!("OUT_DIR"), "/uuid.rs"));
+    ///
+    /// pub trait DoSomething {
+    ///     fn do_it(&self);
+    /// }
+    ///
+    /// impl DoSomething for Uuid {
+    ///     fn do_it(&self) {
+    ///         println!("Done");
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// A separate crate, `my_application`, uses `prost` to generate message types which reference
+    /// `Uuid`:
+    ///
+    /// ```proto
+    /// // my_application.proto
+    ///
+    /// syntax = "proto3";
+    /// package my_application;
+    ///
+    /// import "uuid.proto";
+    ///
+    /// message MyMessage {
+    ///     uuid.Uuid message_id = 1;
+    ///     string some_payload = 2;
+    /// }
+    /// ```
+    ///
+    /// Additionally, `my_application` depends on the trait impls provided by the `uuid` crate:
+    ///
+    /// ```rust,ignore
+    /// // `main.rs` of `my_application`
+    ///
+    /// use uuid::{DoSomething, Uuid};
     ///
     /// include!(concat!(env!("OUT_DIR"), "/my_application.rs"));
     ///
@@ -595,6 +1619,22 @@ impl Config {
     /// // by the `uuid` crate's `Uuid` type.
     /// config.extern_path(".uuid.Uuid", "::uuid::Uuid");
     /// ```
+    ///
+    /// # Well-known types
+    ///
+    /// Unless [`compile_well_known_types`](#method.compile_well_known_types) is set, the
+    /// `google.protobuf` package is declared as provided by `prost_types`, and the wrapper types
+    /// and `Empty` are mapped to Rust primitives and `()`. Paths declared with `extern_path` take
+    /// precedence over these mappings:
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Use a message type instead of `()` for `google.protobuf.Empty`.
+    /// config.extern_path(".google.protobuf.Empty", "::my_protos::Empty");
+    ///
+    /// // Use a re-export of the well-known types.
+    /// config.extern_path(".google.protobuf", "::my_protos::wkt");
+    /// ```
     pub fn extern_path<P1, P2>(&mut self, proto_path: P1, rust_path: P2) -> &mut Self
     where
         P1: Into<String>,
@@ -657,6 +1697,85 @@ impl Config {
         self
     }
 
+    /// Embeds the compiled `FileDescriptorSet` into the generated code.
+    ///
+    /// When enabled, every generated module contains a `file_descriptor_set()` function returning
+    /// the encoded `FileDescriptorSet` of the compiled `.proto` files and their imports, and every
+    /// generated message has a `DESCRIPTOR_FULL_NAME` associated constant holding its
+    /// fully-qualified Protobuf name. Together these allow implementing gRPC server reflection, for
+    /// which `prost_types::reflection::ReflectionCatalog` answers requests from the embedded sets,
+    /// or packing messages into `Any` without running `protoc` again at runtime.
+    ///
+    /// Source code info is stripped from the embedded descriptors to reduce their size. The
+    /// encoded set is written alongside the generated files and included with [`include_bytes!`].
+    ///
+    /// ## Example
+    ///
+    /// In `build.rs`:
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.embed_file_descriptor_set();
+    /// ```
+    ///
+    /// In `lib.rs`:
+    ///
+    /// ```rust,ignore
+    /// let mut pool = prost_types::DescriptorPool::new();
+    /// pool.decode_file_descriptor_set(items::file_descriptor_set())?;
+    /// let shirt = pool.get_message_by_name(items::Shirt::DESCRIPTOR_FULL_NAME).unwrap();
+    /// ```
+    pub fn embed_file_descriptor_set(&mut self) -> &mut Self {
+        self.embed_file_descriptor_set = true;
+        self
+    }
+
+    /// Configures the code generator to implement [`prost::Name`] for generated messages.
+    ///
+    /// The `Name` trait associates each message with its Protobuf package and name, from which
+    /// its fully-qualified name and type URL are derived. This is required to pack and unpack
+    /// messages with `prost_types::Any`.
+    pub fn enable_type_names(&mut self) -> &mut Self {
+        self.enable_type_names = true;
+        self
+    }
+
+    /// Configures the code generator to derive `serde::Serialize` and `serde::Deserialize` for
+    /// generated messages, following the Protobuf [JSON mapping][1].
+    ///
+    /// Fields are renamed to their JSON names, while still accepting their Protobuf names, and
+    /// fields holding their default value are omitted. Fields whose JSON representation differs
+    /// from serde's default, such as 64-bit integers, floating point numbers, `bytes`, enums, and
+    /// `Timestamp`, `Duration` and `Empty` fields, are wired to the helpers in
+    /// `prost_types::serde`, and generated enums implement `prost_types::serde::EnumName`. Oneofs
+    /// are flattened into their message, so the field which is set appears under its own JSON
+    /// name, and messages setting more than one field of a oneof are rejected.
+    ///
+    /// The generated code refers to the `serde` crate and to `prost_types` built with its `serde`
+    /// feature, which must both be dependencies of the crate including it.
+    ///
+    /// The following are not yet handled:
+    ///
+    /// - Map values are serialized with their own `Serialize` implementations, so maps with
+    ///   64-bit integer, floating point, `bytes`, enum, `Timestamp` or `Duration` values need
+    ///   hand-written attributes. Maps with `Empty` values are handled, unless their keys are
+    ///   configured with `timestamp_map_keys` or `duration_map_keys`.
+    /// - `google.protobuf.Any` fields don't implement `Serialize`, since serializing them requires
+    ///   a registry of the packed message types.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// config.enable_serde_json();
+    /// ```
+    ///
+    /// [1]: https://developers.google.com/protocol-buffers/docs/proto3#json
+    pub fn enable_serde_json(&mut self) -> &mut Self {
+        self.enable_serde_json = true;
+        self
+    }
+
     /// Configures the code generator to not strip the enum name from variant names.
     ///
     /// Protobuf enum definitions commonly include the enum name as a prefix of every variant name.
@@ -691,6 +1810,8 @@ impl Config {
 
     /// Add an argument to the `protoc` protobuf compilation invocation.
     ///
+    /// The arguments are ignored with the `no-protoc` feature, which does not run `protoc`.
+    ///
     /// # Example `build.rs`
     ///
     /// ```rust,no_run
@@ -736,6 +1857,8 @@ impl Config {
     /// ```rust,no_run,ignore
     /// include!(concat!(env!("OUT_DIR"), "/_includes.rs"));
     /// ```
+    ///
+    /// The include file is only written with the default [`OutputLayout::Files`].
     pub fn include_file<P>(&mut self, path: P) -> &mut Self
     where
         P: Into<PathBuf>,
@@ -744,6 +1867,77 @@ impl Config {
         self
     }
 
+    /// Configures the layout of the generated files.
+    ///
+    /// By default, a file is written for each package. See [`OutputLayout`] for the other
+    /// layouts, which are easier to navigate when many packages are generated.
+    ///
+    /// # Example `build.rs`
+    ///
+    /// ```rust,no_run
+    /// # use std::io::Result;
+    /// fn main() -> Result<()> {
+    ///   prost_build::Config::new()
+    ///       .out_dir("src/proto")
+    ///       .output_layout(prost_build::OutputLayout::ModuleTree)
+    ///       .compile_protos(&["src/frontend.proto", "src/backend.proto"], &["src"])?;
+    ///   Ok(())
+    /// }
+    /// ```
+    ///
+    /// and in `lib.rs`:
+    ///
+    /// ```rust,ignore
+    /// mod proto;
+    /// ```
+    pub fn output_layout(&mut self, layout: OutputLayout) -> &mut Self {
+        self.output_layout = layout;
+        self
+    }
+
+    /// Gates the module of each package behind a Cargo feature named after the package, such as
+    /// `foo-bar` for the `foo.bar` package, and writes the `[features]` table declaring them to a
+    /// file, relative to the output directory.
+    ///
+    /// Crates depending on a large crate of generated code can then only compile the packages they
+    /// use. In the `[features]` table, each feature enables the features of the enclosing package
+    /// and of the imported packages, so the table can be copied into the `Cargo.toml` of the
+    /// crate.
+    ///
+    /// The modules are declared by the include file with the default [`OutputLayout::Files`],
+    /// which must be configured with [`include_file`](#method.include_file), or by the generated
+    /// files with the other layouts. Files without a package are not gated.
+    ///
+    /// # Example `build.rs`
+    ///
+    /// ```rust,no_run
+    /// # use std::io::Result;
+    /// fn main() -> Result<()> {
+    ///   prost_build::Config::new()
+    ///       .include_file("_includes.rs")
+    ///       .package_features("features.toml")
+    ///       .compile_protos(&["src/frontend.proto", "src/backend.proto"], &["src"])?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn package_features<P>(&mut self, path: P) -> &mut Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.package_features = Some(path.into());
+        self
+    }
+
+    /// Configures whether the generated code is formatted with `prettyplease`, which is the
+    /// default with the `format` feature.
+    ///
+    /// Formatted code is easier to read and review when generated files are checked in.
+    #[cfg(feature = "format")]
+    pub fn format(&mut self, enabled: bool) -> &mut Self {
+        self.fmt = enabled;
+        self
+    }
+
     /// Compile `.proto` files into Rust files during a Cargo build with additional code generator
     /// configuration options.
     ///
@@ -758,16 +1952,121 @@ impl Config {
     /// ```rust,no_run
     /// # use std::io::Result;
     /// fn main() -> Result<()> {
-    ///   let mut prost_build = prost_build::Config::new();
-    ///   prost_build.btree_map(&["."]);
-    ///   prost_build.compile_protos(&["src/frontend.proto", "src/backend.proto"], &["src"])?;
+    ///   let mut prost_build = prost_build::Config::new();
+    ///   prost_build.btree_map(&["."]);
+    ///   prost_build.compile_protos(&["src/frontend.proto", "src/backend.proto"], &["src"])?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn compile_protos(
+        &mut self,
+        protos: &[impl AsRef<Path>],
+        includes: &[impl AsRef<Path>],
+    ) -> Result<()> {
+        // TODO: This should probably emit 'rerun-if-changed=PATH' directives for cargo, however
+        // according to [1] if any are output then those paths replace the default crate root,
+        // which is undesirable. Figure out how to do it in an additive way; perhaps gcc-rs has
+        // this figured out.
+        // [1]: http://doc.crates.io/build-script.html#outputs-of-the-build-script
+
+        let tmp;
+        let file_descriptor_set_path = if let Some(path) = &self.file_descriptor_set_path {
+            path.clone()
+        } else {
+            if self.skip_protoc_run {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    "file_descriptor_set_path is required with skip_protoc_run",
+                ));
+            }
+            tmp = tempfile::Builder::new().prefix("prost-build").tempdir()?;
+            tmp.path().join("prost-descriptor-set")
+        };
+
+        // The `no-protoc` parser returns the custom options, which are otherwise read from the
+        // encoded `FileDescriptorSet`.
+        let mut parsed_custom_options = None;
+        if !self.skip_protoc_run {
+            parsed_custom_options =
+                self.compile_file_descriptor_set(protos, includes, &file_descriptor_set_path)?;
+        }
+
+        let buf = fs::read(file_descriptor_set_path)?;
+        let (file_descriptor_set, decoded_custom_options) = decode_file_descriptor_set(&buf)?;
+        let custom_options = parsed_custom_options.unwrap_or(decoded_custom_options);
+        self.generate_files(file_descriptor_set, &custom_options)
+    }
+
+    /// Compile a `FileDescriptorSet` into Rust files during a Cargo build, instead of running
+    /// `protoc` on `.proto` files.
+    ///
+    /// This is useful when the descriptors are already built by another tool, such as `buf` or
+    /// Bazel. The descriptors should include the imports of the files, and may include source
+    /// info, which is needed to generate documentation comments.
+    ///
+    /// Custom options are dropped when decoding a `FileDescriptorSet`, so they are not available
+    /// to code generation, which requires them for `validate`. Use
+    /// [`compile_fds_path`](#method.compile_fds_path) to compile an encoded `FileDescriptorSet`
+    /// with its custom options.
+    ///
+    /// If `file_descriptor_set_path` is set, the `FileDescriptorSet` is written to it.
+    ///
+    /// # Example `build.rs`
+    ///
+    /// ```rust,no_run
+    /// # use std::io::Result;
+    /// # use prost::Message;
+    /// fn main() -> Result<()> {
+    ///   let buf = std::fs::read("descriptors.bin")?;
+    ///   let file_descriptor_set = prost_types::FileDescriptorSet::decode(&*buf)?;
+    ///   prost_build::Config::new().compile_fds(file_descriptor_set)?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn compile_fds(&mut self, file_descriptor_set: FileDescriptorSet) -> Result<()> {
+        if let Some(path) = &self.file_descriptor_set_path {
+            fs::write(path, file_descriptor_set.encode_to_vec())?;
+        }
+        self.generate_files(file_descriptor_set, &CustomOptionsIndex::default())
+    }
+
+    /// Compile an encoded `FileDescriptorSet`, read from a file, into Rust files during a Cargo
+    /// build, instead of running `protoc` on `.proto` files.
+    ///
+    /// This is like [`compile_fds`](#method.compile_fds), except that the custom options of the
+    /// descriptors are read from the encoded `FileDescriptorSet`, as with `protoc`.
+    ///
+    /// If `file_descriptor_set_path` is set to another path, the `FileDescriptorSet` is copied to
+    /// it.
+    ///
+    /// # Example `build.rs`
+    ///
+    /// ```rust,no_run
+    /// # use std::io::Result;
+    /// fn main() -> Result<()> {
+    ///   // Built with `buf build --as-file-descriptor-set -o descriptors.bin`.
+    ///   prost_build::Config::new().compile_fds_path("descriptors.bin")?;
     ///   Ok(())
     /// }
     /// ```
-    pub fn compile_protos(
+    pub fn compile_fds_path(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let buf = fs::read(path)?;
+        if let Some(file_descriptor_set_path) = &self.file_descriptor_set_path {
+            if file_descriptor_set_path != path {
+                fs::write(file_descriptor_set_path, &buf)?;
+            }
+        }
+        let (file_descriptor_set, custom_options) = decode_file_descriptor_set(&buf)?;
+        self.generate_files(file_descriptor_set, &custom_options)
+    }
+
+    /// Generates the Rust files for the descriptors, and the include file, in the output
+    /// directory.
+    fn generate_files(
         &mut self,
-        protos: &[impl AsRef<Path>],
-        includes: &[impl AsRef<Path>],
+        file_descriptor_set: FileDescriptorSet,
+        custom_options: &CustomOptionsIndex,
     ) -> Result<()> {
         let mut target_is_env = false;
         let target: PathBuf = self.out_dir.clone().map(Ok).unwrap_or_else(|| {
@@ -781,74 +2080,71 @@ impl Config {
                 })
         })?;
 
-        // TODO: This should probably emit 'rerun-if-changed=PATH' directives for cargo, however
-        // according to [1] if any are output then those paths replace the default crate root,
-        // which is undesirable. Figure out how to do it in an additive way; perhaps gcc-rs has
-        // this figured out.
-        // [1]: http://doc.crates.io/build-script.html#outputs-of-the-build-script
-
-        let tmp;
-        let file_descriptor_set_path = if let Some(path) = &self.file_descriptor_set_path {
-            path.clone()
+        let embedded_file_descriptor_set = if self.embed_file_descriptor_set {
+            Some(self.write_embedded_file_descriptor_set(&file_descriptor_set, &target)?)
         } else {
-            if self.skip_protoc_run {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "file_descriptor_set_path is required with skip_protoc_run",
-                ));
-            }
-            tmp = tempfile::Builder::new().prefix("prost-build").tempdir()?;
-            tmp.path().join("prost-descriptor-set")
+            None
         };
 
-        if !self.skip_protoc_run {
-            let mut cmd = Command::new(protoc());
-            cmd.arg("--include_imports")
-                .arg("--include_source_info")
-                .arg("-o")
-                .arg(&file_descriptor_set_path);
-
-            for include in includes {
-                cmd.arg("-I").arg(include.as_ref());
+        let packages = self.packages(&file_descriptor_set.file);
+        let mut modules = self.generate(file_descriptor_set.file, custom_options)?;
+        for (module, content) in modules.iter_mut() {
+            if let Some(ref filename) = embedded_file_descriptor_set {
+                content.push_str(
+                    "/// Returns the encoded `FileDescriptorSet` containing the types in this module.\n",
+                );
+                content.push_str("pub fn file_descriptor_set() -> &'static [u8] {\n");
+                content.push_str(&format!(
+                    "    include_bytes!({})\n",
+                    self.embedded_include(filename, module, target_is_env)
+                ));
+                content.push_str("}\n");
             }
 
-            // Set the protoc include after the user includes in case the user wants to
-            // override one of the built-in .protos.
-            cmd.arg("-I").arg(protoc_include());
-
-            for arg in &self.protoc_args {
-                cmd.arg(arg);
+            #[cfg(feature = "format")]
+            if self.fmt {
+                *content = format_code(content)?;
             }
+        }
 
-            for proto in protos {
-                cmd.arg(proto.as_ref());
+        let features = match self.package_features {
+            Some(ref path) => {
+                let features = package_features(&packages, &modules);
+                write_file(&target.join(path), features_table(&features).as_bytes())?;
+                features
+                    .into_iter()
+                    .map(|(module, (feature, _))| (module, feature))
+                    .collect()
             }
+            None => BTreeMap::new(),
+        };
 
-            let output = cmd.output().map_err(|error| {
-            Error::new(
-                error.kind(),
-                format!("failed to invoke protoc (hint: https://docs.rs/prost-build/#sourcing-protoc): {}", error),
-            )
-        })?;
-
-            if !output.status.success() {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    format!("protoc failed: {}", String::from_utf8_lossy(&output.stderr)),
-                ));
+        match self.output_layout {
+            OutputLayout::Files => self.write_files(&modules, &features, &target, target_is_env),
+            OutputLayout::ModuleTree => write_module_tree(&modules, &features, &target),
+            OutputLayout::SingleFile(ref path) => {
+                let mut content = String::new();
+                write_nested_modules(
+                    &modules,
+                    &features,
+                    &module_children(&modules),
+                    &mut Vec::new(),
+                    &mut content,
+                );
+                write_file(&target.join(path), content.as_bytes())
             }
         }
+    }
 
-        let buf = fs::read(file_descriptor_set_path)?;
-        let file_descriptor_set = FileDescriptorSet::decode(&*buf).map_err(|error| {
-            Error::new(
-                ErrorKind::InvalidInput,
-                format!("invalid FileDescriptorSet: {}", error),
-            )
-        })?;
-
-        let modules = self.generate(file_descriptor_set.file)?;
-        for (module, content) in &modules {
+    /// Writes a file for each module, named after its package, and the include file.
+    fn write_files(
+        &self,
+        modules: &BTreeMap<Module, String>,
+        features: &BTreeMap<Module, String>,
+        target: &Path,
+        target_is_env: bool,
+    ) -> Result<()> {
+        for (module, content) in modules {
             let mut filename = if module.is_empty() {
                 self.default_package_filename.clone()
             } else {
@@ -857,39 +2153,147 @@ impl Config {
 
             filename.push_str(".rs");
 
-            let output_path = target.join(&filename);
-
-            let previous_content = fs::read(&output_path);
-
-            if previous_content
-                .map(|previous_content| previous_content == content.as_bytes())
-                .unwrap_or(false)
-            {
-                trace!("unchanged: {:?}", filename);
-            } else {
-                trace!("writing: {:?}", filename);
-                fs::write(output_path, content)?;
-            }
+            write_file(&target.join(&filename), content.as_bytes())?;
         }
 
         if let Some(ref include_file) = self.include_file {
             trace!("Writing include file: {:?}", target.join(include_file));
             let mut file = fs::File::create(target.join(include_file))?;
-            self.write_includes(
-                modules.keys().collect(),
-                &mut file,
-                0,
-                if target_is_env { None } else { Some(&target) },
-            )?;
+            let target = target.to_path_buf();
+            let basepath = if target_is_env { None } else { Some(&target) };
+            // The code of files without a package is included at the top level.
+            let (root, packages): (Vec<&Module>, Vec<&Module>) =
+                modules.keys().partition(|module| module.is_empty());
+            if !root.is_empty() {
+                self.write_include(&mut file, 0, &self.default_package_filename, basepath)?;
+            }
+            self.write_includes(packages, features, &mut file, 0, basepath)?;
             file.flush()?;
         }
 
         Ok(())
     }
 
+    /// Runs `protoc` on the `.proto` files, writing the encoded `FileDescriptorSet` to a path.
+    #[cfg(not(feature = "no-protoc"))]
+    fn compile_file_descriptor_set(
+        &self,
+        protos: &[impl AsRef<Path>],
+        includes: &[impl AsRef<Path>],
+        file_descriptor_set_path: &Path,
+    ) -> Result<Option<CustomOptionsIndex>> {
+        let mut cmd = Command::new(protoc());
+        cmd.arg("--include_imports")
+            .arg("--include_source_info")
+            .arg("-o")
+            .arg(file_descriptor_set_path);
+
+        for include in includes {
+            cmd.arg("-I").arg(include.as_ref());
+        }
+
+        // Set the protoc include after the user includes in case the user wants to
+        // override one of the built-in .protos.
+        cmd.arg("-I").arg(protoc_include());
+
+        for arg in &self.protoc_args {
+            cmd.arg(arg);
+        }
+
+        for proto in protos {
+            cmd.arg(proto.as_ref());
+        }
+
+        let output = cmd.output().map_err(|error| {
+            Error::new(
+                error.kind(),
+                format!("failed to invoke protoc (hint: https://docs.rs/prost-build/#sourcing-protoc): {}", error),
+            )
+        })?;
+
+        if !output.status.success() {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!("protoc failed: {}", String::from_utf8_lossy(&output.stderr)),
+            ));
+        }
+
+        Ok(None)
+    }
+
+    /// Parses the `.proto` files, writing the encoded `FileDescriptorSet` to a path, and returns
+    /// their custom options, which are not included in the `FileDescriptorSet`.
+    #[cfg(feature = "no-protoc")]
+    fn compile_file_descriptor_set(
+        &self,
+        protos: &[impl AsRef<Path>],
+        includes: &[impl AsRef<Path>],
+        file_descriptor_set_path: &Path,
+    ) -> Result<Option<CustomOptionsIndex>> {
+        let mut include_paths: Vec<PathBuf> = includes
+            .iter()
+            .map(|include| include.as_ref().to_path_buf())
+            .collect();
+        // As with `protoc`, the Protobuf includes follow the user includes, so that they can be
+        // overridden.
+        include_paths.push(protoc_include());
+
+        let (file_descriptor_set, custom_options) = parse::compile(protos, &include_paths)?;
+        fs::write(
+            file_descriptor_set_path,
+            file_descriptor_set.encode_to_vec(),
+        )?;
+        Ok(Some(custom_options))
+    }
+
+    /// Writes the file descriptor set to be embedded in the generated code, and returns the name
+    /// of the written file.
+    ///
+    /// The file name is derived from the content with a hash which is stable across builds and
+    /// toolchains, so that separate invocations writing to the same output directory do not
+    /// overwrite each other's descriptors.
+    fn write_embedded_file_descriptor_set(
+        &self,
+        file_descriptor_set: &FileDescriptorSet,
+        target: &Path,
+    ) -> Result<String> {
+        let mut file_descriptor_set = file_descriptor_set.clone();
+        for file in &mut file_descriptor_set.file {
+            file.source_code_info = None;
+        }
+        let buf = file_descriptor_set.encode_to_vec();
+
+        // FNV-1a, since the hash of `DefaultHasher` may change between Rust releases.
+        let hash = buf.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, &byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        });
+        let filename = format!("file_descriptor_set_{:016x}.bin", hash);
+
+        write_file(&target.join(&filename), &buf)?;
+        Ok(filename)
+    }
+
+    /// Returns the argument to `include_bytes!` which loads the embedded file descriptor set from
+    /// the generated code of `module`.
+    ///
+    /// Outside of `OUT_DIR` the path is relative to the generated file, so that the generated
+    /// code does not depend on the directory it was generated in.
+    fn embedded_include(&self, filename: &str, module: &Module, target_is_env: bool) -> String {
+        if target_is_env {
+            return format!("concat!(env!(\"OUT_DIR\"), \"/{}\")", filename);
+        }
+        let depth = match self.output_layout {
+            OutputLayout::Files => 0,
+            OutputLayout::ModuleTree => module.len(),
+            OutputLayout::SingleFile(ref path) => path.components().count().saturating_sub(1),
+        };
+        format!("{:?}", format!("{}{}", "../".repeat(depth), filename))
+    }
+
     fn write_includes(
         &self,
         mut entries: Vec<&Module>,
+        features: &BTreeMap<Module, String>,
         outfile: &mut fs::File,
         depth: usize,
         basepath: Option<&PathBuf>,
@@ -910,6 +2314,9 @@ impl Config {
                     .collect();
                 entries = _temp;
             }
+            if let Some(feature) = features.get(&matching[0][..=depth]) {
+                self.write_line(outfile, depth, &cfg_feature(feature))?;
+            }
             self.write_line(outfile, depth, &format!("pub mod {} {{", modident))?;
             let subwritten = self.write_includes(
                 matching
@@ -917,6 +2324,7 @@ impl Config {
                     .filter(|v| v.len() > depth + 1)
                     .copied()
                     .collect(),
+                features,
                 outfile,
                 depth + 1,
                 basepath,
@@ -924,19 +2332,7 @@ impl Config {
             written += subwritten;
             if subwritten != matching.len() {
                 let modname = matching[0][..=depth].join(".");
-                if basepath.is_some() {
-                    self.write_line(
-                        outfile,
-                        depth + 1,
-                        &format!("include!(\"{}.rs\");", modname),
-                    )?;
-                } else {
-                    self.write_line(
-                        outfile,
-                        depth + 1,
-                        &format!("include!(concat!(env!(\"OUT_DIR\"), \"/{}.rs\"));", modname),
-                    )?;
-                }
+                self.write_include(outfile, depth + 1, &modname, basepath)?;
                 written += 1;
             }
 
@@ -945,18 +2341,53 @@ impl Config {
         Ok(written)
     }
 
+    /// Writes the `include!` of a generated file, given without its `.rs` extension.
+    fn write_include(
+        &self,
+        outfile: &mut fs::File,
+        depth: usize,
+        modname: &str,
+        basepath: Option<&PathBuf>,
+    ) -> Result<()> {
+        if basepath.is_some() {
+            self.write_line(outfile, depth, &format!("include!(\"{}.rs\");", modname))
+        } else {
+            self.write_line(
+                outfile,
+                depth,
+                &format!("include!(concat!(env!(\"OUT_DIR\"), \"/{}.rs\"));", modname),
+            )
+        }
+    }
+
     fn write_line(&self, outfile: &mut fs::File, depth: usize, line: &str) -> Result<()> {
         outfile.write_all(format!("{}{}\n", ("    ").to_owned().repeat(depth), line).as_bytes())
     }
 
-    fn generate(&mut self, files: Vec<FileDescriptorProto>) -> Result<HashMap<Module, String>> {
-        let mut modules = HashMap::new();
-        let mut packages = HashMap::new();
+    fn generate(
+        &mut self,
+        files: Vec<FileDescriptorProto>,
+        custom_options: &CustomOptionsIndex,
+    ) -> Result<BTreeMap<Module, String>> {
+        // The modules are ordered, so that the output does not depend on the order of iteration
+        // of a `HashMap`.
+        let mut modules = BTreeMap::new();
+        let mut packages = BTreeMap::new();
 
         let message_graph = MessageGraph::new(&files)
             .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
         let extern_paths = ExternPaths::new(&self.extern_paths, self.prost_types)
             .map_err(|error| Error::new(ErrorKind::InvalidInput, error))?;
+        let validation_rules = if self.validate.matchers.is_empty() {
+            ValidationRules::default()
+        } else {
+            ValidationRules::new(custom_options).map_err(|error| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid validation rules: {}", error),
+                )
+            })?
+        };
 
         for file in files {
             let module = self.module(&file);
@@ -967,7 +2398,15 @@ impl Config {
             }
 
             let buf = modules.entry(module).or_insert_with(String::new);
-            CodeGenerator::generate(self, &message_graph, &extern_paths, file, buf);
+            CodeGenerator::generate(
+                self,
+                &message_graph,
+                &extern_paths,
+                custom_options,
+                &validation_rules,
+                file,
+                buf,
+            );
         }
 
         if let Some(ref mut service_generator) = self.service_generator {
@@ -980,6 +2419,31 @@ impl Config {
         Ok(modules)
     }
 
+    /// Returns the modules of the packages of the files, with their names and the modules of the
+    /// packages they import.
+    fn packages(
+        &self,
+        files: &[FileDescriptorProto],
+    ) -> BTreeMap<Module, (String, BTreeSet<Module>)> {
+        let modules: HashMap<&str, Module> = files
+            .iter()
+            .map(|file| (file.name(), self.module(file)))
+            .collect();
+        let mut packages = BTreeMap::new();
+        for file in files {
+            let (_, imports) = packages
+                .entry(self.module(file))
+                .or_insert_with(|| (file.package().to_string(), BTreeSet::new()));
+            imports.extend(
+                file.dependency
+                    .iter()
+                    .filter_map(|dependency| modules.get(dependency.as_str()))
+                    .cloned(),
+            );
+        }
+        packages
+    }
+
     fn module(&self, file: &FileDescriptorProto) -> Module {
         file.package()
             .split('.')
@@ -996,8 +2460,28 @@ impl default::Default for Config {
             service_generator: None,
             map_type: PathMap::default(),
             bytes_type: PathMap::default(),
+            string_type: PathMap::default(),
+            char_fields: PathMap::default(),
+            nonzero_fields: PathMap::default(),
+            iterative_recursion: PathMap::default(),
+            message_pipeline: PathMap::default(),
+            builders: PathMap::default(),
+            accessors: PathMap::default(),
+            presence_bits: PathMap::default(),
+            enforce_required: PathMap::default(),
+            validate: PathMap::default(),
+            arbitrary: PathMap::default(),
+            proptest_arbitrary: PathMap::default(),
+            timestamp_map_keys: PathMap::default(),
+            duration_map_keys: PathMap::default(),
+            int64_numbers: PathMap::default(),
+            enum_numbers: PathMap::default(),
+            boxed: PathMap::default(),
+            box_large_messages: None,
             type_attributes: PathMap::default(),
             field_attributes: PathMap::default(),
+            derives: PathMap::default(),
+            check_encode: PathMap::default(),
             prost_types: true,
             strip_enum_prefix: true,
             out_dir: None,
@@ -1005,8 +2489,16 @@ impl default::Default for Config {
             default_package_filename: "_".to_string(),
             protoc_args: Vec::new(),
             disable_comments: PathMap::default(),
+            text_code_blocks: PathMap::default(),
             skip_protoc_run: false,
             include_file: None,
+            embed_file_descriptor_set: false,
+            enable_type_names: false,
+            enable_serde_json: false,
+            output_layout: OutputLayout::default(),
+            package_features: None,
+            #[cfg(feature = "format")]
+            fmt: true,
         }
     }
 }
@@ -1018,8 +2510,28 @@ impl fmt::Debug for Config {
             .field("service_generator", &self.service_generator.is_some())
             .field("map_type", &self.map_type)
             .field("bytes_type", &self.bytes_type)
+            .field("string_type", &self.string_type)
+            .field("char_fields", &self.char_fields)
+            .field("nonzero_fields", &self.nonzero_fields)
+            .field("iterative_recursion", &self.iterative_recursion)
+            .field("message_pipeline", &self.message_pipeline)
+            .field("builders", &self.builders)
+            .field("accessors", &self.accessors)
+            .field("presence_bits", &self.presence_bits)
+            .field("enforce_required", &self.enforce_required)
+            .field("validate", &self.validate)
+            .field("arbitrary", &self.arbitrary)
+            .field("proptest_arbitrary", &self.proptest_arbitrary)
+            .field("timestamp_map_keys", &self.timestamp_map_keys)
+            .field("duration_map_keys", &self.duration_map_keys)
+            .field("int64_numbers", &self.int64_numbers)
+            .field("enum_numbers", &self.enum_numbers)
+            .field("boxed", &self.boxed)
+            .field("box_large_messages", &self.box_large_messages)
             .field("type_attributes", &self.type_attributes)
             .field("field_attributes", &self.field_attributes)
+            .field("derives", &self.derives)
+            .field("check_encode", &self.check_encode)
             .field("prost_types", &self.prost_types)
             .field("strip_enum_prefix", &self.strip_enum_prefix)
             .field("out_dir", &self.out_dir)
@@ -1027,6 +2539,10 @@ impl fmt::Debug for Config {
             .field("default_package_filename", &self.default_package_filename)
             .field("protoc_args", &self.protoc_args)
             .field("disable_comments", &self.disable_comments)
+            .field("text_code_blocks", &self.text_code_blocks)
+            .field("embed_file_descriptor_set", &self.embed_file_descriptor_set)
+            .field("enable_type_names", &self.enable_type_names)
+            .field("enable_serde_json", &self.enable_serde_json)
             .finish()
     }
 }
@@ -1077,6 +2593,188 @@ pub fn compile_protos(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]
     Config::new().compile_protos(protos, includes)
 }
 
+/// Writes a file, unless it already has the content, so that its modification time is kept.
+fn write_file(path: &Path, content: &[u8]) -> Result<()> {
+    let previous_content = fs::read(path);
+    if previous_content
+        .map(|previous_content| previous_content == content)
+        .unwrap_or(false)
+    {
+        trace!("unchanged: {:?}", path);
+    } else {
+        trace!("writing: {:?}", path);
+        fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+/// Returns the Cargo features gating the modules of the packages with code, with the features
+/// they require: those of the enclosing package, if any, and of the imported packages.
+fn package_features(
+    packages: &BTreeMap<Module, (String, BTreeSet<Module>)>,
+    modules: &BTreeMap<Module, String>,
+) -> BTreeMap<Module, (String, BTreeSet<String>)> {
+    // The modules of packages without code, such as those of extern paths, are not gated.
+    let features: BTreeMap<Module, String> = packages
+        .iter()
+        .filter(|(module, _)| {
+            !module.is_empty()
+                && modules
+                    .get(*module)
+                    .map_or(false, |code| !code.trim().is_empty())
+        })
+        .map(|(module, (package, _))| (module.clone(), package.replace('.', "-")))
+        .collect();
+
+    features
+        .iter()
+        .map(|(module, feature)| {
+            let parent = (1..module.len())
+                .rev()
+                .find_map(|depth| features.get(&module[..depth]));
+            let imports = packages[module]
+                .1
+                .iter()
+                .filter_map(|import| features.get(import));
+            let required = parent
+                .into_iter()
+                .chain(imports)
+                .filter(|required| *required != feature)
+                .cloned()
+                .collect();
+            (module.clone(), (feature.clone(), required))
+        })
+        .collect()
+}
+
+/// Returns the `[features]` table of a `Cargo.toml` file, declaring the features of the packages.
+fn features_table(features: &BTreeMap<Module, (String, BTreeSet<String>)>) -> String {
+    let mut table =
+        "# The features of the Protobuf packages, generated by prost-build.\n[features]\n"
+            .to_string();
+    for (feature, required) in features.values() {
+        let required: Vec<_> = required
+            .iter()
+            .map(|required| format!("\"{}\"", required))
+            .collect();
+        table.push_str(&format!("{} = [{}]\n", feature, required.join(", ")));
+    }
+    table
+}
+
+/// Returns the attribute gating an item behind a Cargo feature.
+fn cfg_feature(feature: &str) -> String {
+    format!("#[cfg(feature = \"{}\")]", feature)
+}
+
+/// Returns the names of the child modules of each module, including the root module and the
+/// modules of packages without code, such as `foo` for a `foo.bar` package.
+fn module_children(modules: &BTreeMap<Module, String>) -> BTreeMap<Module, BTreeSet<String>> {
+    let mut children: BTreeMap<Module, BTreeSet<String>> = BTreeMap::new();
+    for module in modules.keys() {
+        children.entry(module.clone()).or_default();
+        for depth in 0..module.len() {
+            children
+                .entry(module[..depth].to_vec())
+                .or_default()
+                .insert(module[depth].clone());
+        }
+    }
+    children
+}
+
+/// Writes the modules as a module tree, with a `mod.rs` file for each module declaring its child
+/// modules.
+fn write_module_tree(
+    modules: &BTreeMap<Module, String>,
+    features: &BTreeMap<Module, String>,
+    target: &Path,
+) -> Result<()> {
+    for (mut module, children) in module_children(modules) {
+        let mut content = String::new();
+        for child in &children {
+            module.push(child.clone());
+            if let Some(feature) = features.get(&module) {
+                content.push_str(&cfg_feature(feature));
+                content.push('\n');
+            }
+            module.pop();
+            content.push_str(&format!("pub mod {};\n", child));
+        }
+        if let Some(code) = modules.get(&module) {
+            if !content.is_empty() && !code.is_empty() {
+                content.push('\n');
+            }
+            content.push_str(code);
+        }
+
+        // The files of modules with raw identifiers, such as `r#type`, are named without `r#`.
+        let mut dir = target.to_path_buf();
+        for name in &module {
+            dir.push(name.trim_start_matches("r#"));
+        }
+        fs::create_dir_all(&dir)?;
+        write_file(&dir.join("mod.rs"), content.as_bytes())?;
+    }
+    Ok(())
+}
+
+/// Appends the code of a module and of its child modules, nested in `pub mod` items.
+fn write_nested_modules(
+    modules: &BTreeMap<Module, String>,
+    features: &BTreeMap<Module, String>,
+    children: &BTreeMap<Module, BTreeSet<String>>,
+    module: &mut Module,
+    buf: &mut String,
+) {
+    let indent = "    ".repeat(module.len());
+    if let Some(code) = modules.get(module) {
+        for line in code.lines() {
+            if !line.is_empty() {
+                buf.push_str(&indent);
+            }
+            buf.push_str(line);
+            buf.push('\n');
+        }
+    }
+    for child in children.get(module).into_iter().flatten() {
+        module.push(child.clone());
+        if let Some(feature) = features.get(module) {
+            buf.push_str(&format!("{}{}\n", indent, cfg_feature(feature)));
+        }
+        buf.push_str(&format!("{}pub mod {} {{\n", indent, child));
+        write_nested_modules(modules, features, children, module, buf);
+        module.pop();
+        buf.push_str(&format!("{}}}\n", indent));
+    }
+}
+
+/// Formats generated code with `prettyplease`.
+#[cfg(feature = "format")]
+fn format_code(code: &str) -> Result<String> {
+    let file = syn::parse_file(code).map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("failed to parse the generated code: {}", error),
+        )
+    })?;
+    Ok(prettyplease::unparse(&file))
+}
+
+/// Decodes an encoded `FileDescriptorSet`, and reads its custom options.
+fn decode_file_descriptor_set(buf: &[u8]) -> Result<(FileDescriptorSet, CustomOptionsIndex)> {
+    let invalid = |error: DecodeError| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("invalid FileDescriptorSet: {}", error),
+        )
+    };
+    let file_descriptor_set = FileDescriptorSet::decode(buf).map_err(invalid)?;
+    // Custom options are extensions, which are dropped by `FileDescriptorSet::decode`.
+    let custom_options = CustomOptionsIndex::decode(buf).map_err(invalid)?;
+    Ok((file_descriptor_set, custom_options))
+}
+
 /// Returns the path to the `protoc` binary.
 pub fn protoc() -> PathBuf {
     match env::var_os("PROTOC") {
@@ -1099,33 +2797,6 @@ mod tests {
     use std::cell::RefCell;
     use std::rc::Rc;
 
-    /// An example service generator that generates a trait with methods corresponding to the
-    /// service methods.
-    struct ServiceTraitGenerator;
-    impl ServiceGenerator for ServiceTraitGenerator {
-        fn generate(&mut self, service: Service, buf: &mut String) {
-            // Generate a trait for the service.
-            service.comments.append_with_indent(0, buf);
-            buf.push_str(&format!("trait {} {{\n", &service.name));
-
-            // Generate the service methods.
-            for method in service.methods {
-                method.comments.append_with_indent(1, buf);
-                buf.push_str(&format!(
-                    "    fn {}({}) -> {};\n",
-                    method.name, method.input_type, method.output_type
-                ));
-            }
-
-            // Close out the trait.
-            buf.push_str("}\n");
-        }
-        fn finalize(&mut self, buf: &mut String) {
-            // Needs to be present only once, no matter how many services there are
-            buf.push_str("pub mod utils { }\n");
-        }
-    }
-
     /// Implements `ServiceGenerator` and provides some state for assertions.
     struct MockServiceGenerator {
         state: Rc<RefCell<MockState>>,
@@ -1166,7 +2837,7 @@ mod tests {
     fn smoke_test() {
         let _ = env_logger::try_init();
         Config::new()
-            .service_generator(Box::new(ServiceTraitGenerator))
+            .service_generator(Box::new(ServiceTraitGenerator::new()))
             .compile_protos(&["src/smoke_test.proto"], &["src"])
             .unwrap();
     }
@@ -1189,4 +2860,511 @@ mod tests {
         assert_eq!(&state.package_names, &["helloworld"]);
         assert_eq!(state.finalized, 3);
     }
+
+    #[test]
+    fn compile_fds() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        let out_dir = |name: &str| {
+            let out_dir = tmp.path().join(name);
+            fs::create_dir(&out_dir).unwrap();
+            out_dir
+        };
+        let file_descriptor_set_path = tmp.path().join("file_descriptor_set.bin");
+
+        Config::new()
+            .out_dir(out_dir("protos"))
+            .file_descriptor_set_path(&file_descriptor_set_path)
+            .compile_protos(&["src/hello.proto", "src/goodbye.proto"], &["src"])
+            .unwrap();
+        Config::new()
+            .out_dir(out_dir("fds_path"))
+            .compile_fds_path(&file_descriptor_set_path)
+            .unwrap();
+
+        let buf = fs::read(&file_descriptor_set_path).unwrap();
+        let mut file_descriptor_set = FileDescriptorSet::decode(&*buf).unwrap();
+        for file in &mut file_descriptor_set.file {
+            file.source_code_info = None;
+        }
+        Config::new()
+            .out_dir(out_dir("fds"))
+            .compile_fds(file_descriptor_set)
+            .unwrap();
+
+        // The files have no comments, so the source info makes no difference.
+        let expected = fs::read_to_string(tmp.path().join("protos/helloworld.rs")).unwrap();
+        for out_dir in &["fds_path", "fds"] {
+            let path = tmp.path().join(out_dir).join("helloworld.rs");
+            assert_eq!(fs::read_to_string(path).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn deterministic_output() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        let compile = |name: &str, protos: &[&str]| {
+            let out_dir = tmp.path().join(name);
+            fs::create_dir(&out_dir).unwrap();
+            Config::new()
+                .out_dir(&out_dir)
+                .include_file("_includes.rs")
+                .compile_protos(protos, &["src"])
+                .unwrap();
+            fs::read_to_string(out_dir.join("_includes.rs")).unwrap()
+        };
+
+        // The modules are included in order, whatever the order of the files.
+        let expected = "pub mod helloworld {\n    include!(\"helloworld.rs\");\n}\n\
+                        pub mod smoke_test {\n    include!(\"smoke_test.rs\");\n}\n";
+        assert_eq!(
+            compile("a", &["src/smoke_test.proto", "src/hello.proto"]),
+            expected
+        );
+        assert_eq!(
+            compile("b", &["src/hello.proto", "src/smoke_test.proto"]),
+            expected
+        );
+    }
+
+    /// Compiles files in the packages `foo`, `foo.bar` and `foo.type`, and in no package, returning
+    /// the output directory.
+    fn compile_packages(config: &mut Config) -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let protos = [
+            ("foo.proto", "package foo; message Foo {}"),
+            (
+                "bar.proto",
+                "package foo.bar; import \"type.proto\"; message Bar { optional type.Type t = 1; }",
+            ),
+            ("type.proto", "package foo.type; message Type {}"),
+            ("root.proto", "message Root {}"),
+        ];
+        for (name, source) in &protos {
+            fs::write(tmp.path().join(name), source).unwrap();
+        }
+        let names: Vec<_> = protos
+            .iter()
+            .map(|(name, _)| tmp.path().join(name))
+            .collect();
+        config
+            .out_dir(tmp.path())
+            .compile_protos(&names, &[tmp.path()])
+            .unwrap();
+        tmp
+    }
+
+    #[test]
+    fn module_tree() {
+        let _ = env_logger::try_init();
+        let tmp = compile_packages(Config::new().output_layout(OutputLayout::ModuleTree));
+        let read = |path: &str| fs::read_to_string(tmp.path().join(path)).unwrap();
+
+        let root = read("mod.rs");
+        assert!(root.starts_with("pub mod foo;\n\n"));
+        assert!(root.contains("pub struct Root {"));
+        let foo = read("foo/mod.rs");
+        assert!(foo.starts_with("pub mod bar;\npub mod r#type;\n\n"));
+        assert!(foo.contains("pub struct Foo {"));
+        assert!(read("foo/bar/mod.rs").contains("pub struct Bar {"));
+        assert!(read("foo/type/mod.rs").contains("pub struct Type {"));
+    }
+
+    #[test]
+    fn embedded_file_descriptor_set_path() {
+        let _ = env_logger::try_init();
+        let tmp = compile_packages(
+            Config::new()
+                .output_layout(OutputLayout::ModuleTree)
+                .embed_file_descriptor_set(),
+        );
+        let code = fs::read_to_string(tmp.path().join("foo/bar/mod.rs")).unwrap();
+
+        let start = code.find("include_bytes!(\"").unwrap() + "include_bytes!(\"".len();
+        let path = &code[start..start + code[start..].find('"').unwrap()];
+        assert!(path.starts_with("../../file_descriptor_set_"), "{}", path);
+        assert!(tmp.path().join("foo/bar").join(path).is_file());
+    }
+
+    #[test]
+    fn single_file() {
+        let _ = env_logger::try_init();
+        let tmp = compile_packages(
+            Config::new().output_layout(OutputLayout::SingleFile("protos.rs".into())),
+        );
+        let code = fs::read_to_string(tmp.path().join("protos.rs")).unwrap();
+
+        let items: Vec<_> = code
+            .lines()
+            .filter(|line| line.contains("pub mod") || line.contains("pub struct"))
+            .map(|line| line.trim_end_matches(" {}").trim_end_matches(" {"))
+            .collect();
+        assert_eq!(
+            items,
+            [
+                "pub struct Root",
+                "pub mod foo",
+                "    pub struct Foo",
+                "    pub mod bar",
+                "        pub struct Bar",
+                "    pub mod r#type",
+                "        pub struct Type",
+            ]
+        );
+        assert!(code.ends_with("}\n    }\n}\n"));
+    }
+
+    #[test]
+    fn enum_aliases() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        let proto = tmp.path().join("aliases.proto");
+        fs::write(
+            &proto,
+            r#"
+            syntax = "proto3";
+            package aliases;
+            enum Level {
+                option allow_alias = true;
+                LEVEL_LOW = 0;
+                LEVEL_HIGH = 1;
+                // The highest level.
+                LEVEL_TOP = 1;
+                level_high = 1;
+            }
+            "#,
+        )
+        .unwrap();
+        Config::new()
+            .out_dir(tmp.path())
+            .compile_protos(&[&proto], &[tmp.path()])
+            .unwrap();
+        let code = fs::read_to_string(tmp.path().join("aliases.rs")).unwrap();
+
+        // `level_high` is skipped, since its name is that of a variant.
+        let consts: Vec<_> = code
+            .lines()
+            .filter(|line| line.contains("pub const") && !line.contains("NAMES"))
+            .map(str::trim)
+            .collect();
+        assert_eq!(consts, ["pub const Top: Level = Level::High;"]);
+        assert!(code.contains("/// The highest level."));
+
+        // Every name is in the table and converts to a variant, but only the first name of each
+        // number is converted from a variant.
+        assert!(code.contains(
+            "    (\"LEVEL_LOW\", 0),\n        (\"LEVEL_HIGH\", 1),\n        \
+             (\"LEVEL_TOP\", 1),\n        (\"level_high\", 1),\n    ];\n"
+        ));
+        assert!(code.contains(
+            "            Self::Low => \"LEVEL_LOW\",\n            Self::High => \"LEVEL_HIGH\",\n        }\n"
+        ));
+        assert!(code
+            .contains("            \"level_high\" => ::core::option::Option::Some(Self::High),\n"));
+    }
+
+    #[test]
+    fn derives() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        let proto = tmp.path().join("derives.proto");
+        fs::write(
+            &proto,
+            r#"
+            syntax = "proto3";
+            package derives;
+            message Id {
+                uint64 value = 1;
+                Kind kind = 2;
+                oneof scope {
+                    uint32 tenant = 3;
+                    bool global = 4;
+                }
+            }
+            message Point {
+                double x = 1;
+                double y = 2;
+            }
+            message Named {
+                Id id = 1;
+                string name = 2;
+                map<string, uint32> labels = 3;
+                repeated Named children = 4;
+            }
+            message Located {
+                Named named = 1;
+                Point point = 2;
+            }
+            enum Kind {
+                KIND_UNKNOWN = 0;
+            }
+            "#,
+        )
+        .unwrap();
+        Config::new()
+            .out_dir(tmp.path())
+            .derive(".derives", "Copy, Eq, Hash")
+            .derive(".derives", "Hash, PartialOrd, Ord, Clone")
+            .derive(".derives.Named", "Default")
+            .derive(".derives.Kind", "Eq, Default")
+            .compile_protos(&[&proto], &[tmp.path()])
+            .unwrap();
+        let code = fs::read_to_string(tmp.path().join("derives.rs")).unwrap();
+
+        // The derives of each type, by name.
+        let mut derives = HashMap::new();
+        let mut derive = "";
+        for line in code.lines().map(str::trim) {
+            if line.starts_with("#[derive(") {
+                derive = line;
+            } else if let Some(name) = line
+                .strip_prefix("pub struct ")
+                .or_else(|| line.strip_prefix("pub enum "))
+            {
+                derives.insert(name.trim_end_matches(" {"), derive);
+            }
+        }
+        assert_eq!(
+            derives["Id"],
+            "#[derive(Clone, PartialEq, ::prost::Message, Copy, Eq, Hash, PartialOrd, Ord)]"
+        );
+        assert_eq!(
+            derives["Scope"],
+            "#[derive(Clone, PartialEq, ::prost::Oneof, Copy, Eq, Hash, PartialOrd, Ord)]"
+        );
+        // Floats are not `Eq`, `Hash` or `Ord`.
+        assert_eq!(
+            derives["Point"],
+            "#[derive(Clone, PartialEq, ::prost::Message, Copy, PartialOrd)]"
+        );
+        // `HashMap` is not `Hash` or `Ord`.
+        assert_eq!(
+            derives["Named"],
+            "#[derive(Clone, PartialEq, ::prost::Message, Eq)]"
+        );
+        assert_eq!(
+            derives["Located"],
+            "#[derive(Clone, PartialEq, ::prost::Message)]"
+        );
+        assert_eq!(
+            derives["Kind"],
+            "#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]"
+        );
+
+        // With `BTreeMap` fields, `Named` is ordered, and `Located` only misses what `Point` does.
+        Config::new()
+            .out_dir(tmp.path())
+            .btree_map(&["."])
+            .derive(".derives", "Eq, Hash, PartialOrd, Ord")
+            .compile_protos(&[&proto], &[tmp.path()])
+            .unwrap();
+        let code = fs::read_to_string(tmp.path().join("derives.rs")).unwrap();
+        assert!(code.contains(
+            "#[derive(Clone, PartialEq, ::prost::Message, Eq, Hash, PartialOrd, Ord)]\npub struct Named {"
+        ));
+        assert!(code.contains(
+            "#[derive(Clone, PartialEq, ::prost::Message, PartialOrd)]\npub struct Located {"
+        ));
+    }
+
+    #[test]
+    fn check_encode() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        let proto = tmp.path().join("calendar.proto");
+        fs::write(
+            &proto,
+            r#"
+            syntax = "proto3";
+            package calendar;
+            message Range {
+                int64 start = 1;
+                int64 end = 2;
+            }
+            message Event {
+                Range range = 1;
+            }
+            "#,
+        )
+        .unwrap();
+        Config::new()
+            .out_dir(tmp.path())
+            .check_encode(".calendar.Range", "crate::calendar::check_range")
+            .compile_protos(&[&proto], &[tmp.path()])
+            .unwrap();
+        // Compared without whitespace, which the `format` feature adds around the `=`.
+        let squash = |code: &str| code.split_whitespace().collect::<String>();
+        let code = squash(&fs::read_to_string(tmp.path().join("calendar.rs")).unwrap());
+
+        assert!(code.contains(&squash(
+            "#[derive(Clone, PartialEq, ::prost::Message)]\
+             #[prost(check_encode = \"crate::calendar::check_range\")]\
+             pub struct Range {"
+        )));
+        assert_eq!(code.matches("check_encode").count(), 1);
+    }
+
+    #[test]
+    fn arbitrary() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        let proto = tmp.path().join("arbitrary.proto");
+        fs::write(
+            &proto,
+            r#"
+            syntax = "proto3";
+            package arbitrary;
+            message Order {
+                string customer = 1;
+                Status status = 2;
+                repeated Item items = 3;
+                map<string, Status> labels = 4;
+                optional int32 priority = 5;
+                Order next = 6;
+                oneof payment {
+                    string card = 7;
+                    Order parent = 8;
+                }
+            }
+            message Item {
+                bytes sku = 1;
+            }
+            enum Status {
+                STATUS_UNKNOWN = 0;
+                STATUS_OPEN = 1;
+            }
+            "#,
+        )
+        .unwrap();
+        Config::new()
+            .out_dir(tmp.path())
+            .arbitrary(&[".arbitrary.Order", ".arbitrary.Status"])
+            .proptest_arbitrary(&[".arbitrary.Item"])
+            .bytes(&[".arbitrary.Item.sku"])
+            .compile_protos(&[&proto], &[tmp.path()])
+            .unwrap();
+        // The code is compared without whitespace and trailing commas, which the `format` feature
+        // adds when it breaks expressions across lines.
+        let squash = |code: &str| {
+            code.split_whitespace()
+                .collect::<String>()
+                .replace(",)", ")")
+                .replace(",]", "]")
+                .replace(",}", "}")
+        };
+        let code = squash(&fs::read_to_string(tmp.path().join("arbitrary.rs")).unwrap());
+
+        let order = code
+            .split(&squash(
+                "impl<'arbitrary> ::arbitrary::Arbitrary<'arbitrary> for Order {",
+            ))
+            .nth(1)
+            .unwrap();
+        for statement in &[
+            "message.customer = ::arbitrary::Arbitrary::arbitrary(u)?;",
+            "message.status = <Status as ::arbitrary::Arbitrary>::arbitrary(u)? as i32;",
+            "message.items.push(::prost_types::arbitrary::nested(u)?);",
+            "let (key, value) = (::arbitrary::Arbitrary::arbitrary(u)?, \
+             <Status as ::arbitrary::Arbitrary>::arbitrary(u)? as i32);",
+            "message.priority = ::core::option::Option::Some(::arbitrary::Arbitrary::arbitrary(u)?);",
+            "message.next = ::core::option::Option::Some(\
+             ::prost::alloc::boxed::Box::new(::prost_types::arbitrary::nested(u)?));",
+            "message.payment = u.arbitrary()?;",
+        ] {
+            assert!(order.contains(&squash(statement)), "missing {}", statement);
+        }
+        // The oneof chooses one of its fields. The formatter may put the last arm in a block.
+        for line in &[
+            "::core::result::Result::Ok(match u.int_in_range(0..=1)? {",
+            "0 => Self::Card(::arbitrary::Arbitrary::arbitrary(u)?),",
+            "Self::Parent(::prost::alloc::boxed::Box::new(\
+             ::prost_types::arbitrary::nested(u)?))",
+        ] {
+            assert!(code.contains(&squash(line)), "missing {}", line);
+        }
+        assert!(code.contains(&squash(
+            "::core::result::Result::Ok(*u.choose(&[Self::Unknown, Self::Open])?)"
+        )));
+
+        // `Item` implements both traits, and its `Bytes` field is generated from a `Vec<u8>`.
+        assert!(code.contains(&squash("impl ::proptest::arbitrary::Arbitrary for Item {")));
+        assert!(code.contains(&squash(
+            "message.sku = ::core::convert::From::from(\
+             <::prost::alloc::vec::Vec<u8> as ::arbitrary::Arbitrary>::arbitrary(u)?);"
+        )));
+        assert_eq!(code.matches("::proptest::").count(), 2);
+    }
+
+    #[test]
+    fn package_features() {
+        let _ = env_logger::try_init();
+        let tmp = compile_packages(
+            Config::new()
+                .include_file("_includes.rs")
+                .package_features("features.toml"),
+        );
+        let read = |path: &str| fs::read_to_string(tmp.path().join(path)).unwrap();
+
+        assert_eq!(
+            read("features.toml"),
+            "# The features of the Protobuf packages, generated by prost-build.\n\
+             [features]\n\
+             foo = []\n\
+             foo-bar = [\"foo\", \"foo-type\"]\n\
+             foo-type = [\"foo\"]\n"
+        );
+        let includes = read("_includes.rs");
+        assert!(
+            includes.starts_with("include!(\"_.rs\");\n#[cfg(feature = \"foo\")]\npub mod foo {\n")
+        );
+        assert!(includes.contains("    #[cfg(feature = \"foo-bar\")]\n    pub mod bar {\n"));
+        assert!(includes.contains("    #[cfg(feature = \"foo-type\")]\n    pub mod r#type {\n"));
+
+        let tmp = compile_packages(
+            Config::new()
+                .output_layout(OutputLayout::ModuleTree)
+                .package_features("features.toml"),
+        );
+        let read = |path: &str| fs::read_to_string(tmp.path().join(path)).unwrap();
+        assert!(read("mod.rs").starts_with("#[cfg(feature = \"foo\")]\npub mod foo;\n\n"));
+        assert!(read("foo/mod.rs").starts_with(
+            "#[cfg(feature = \"foo-bar\")]\npub mod bar;\n\
+             #[cfg(feature = \"foo-type\")]\npub mod r#type;\n\n"
+        ));
+
+        let tmp = compile_packages(
+            Config::new()
+                .output_layout(OutputLayout::SingleFile("protos.rs".into()))
+                .package_features("features.toml"),
+        );
+        let code = fs::read_to_string(tmp.path().join("protos.rs")).unwrap();
+        assert!(code.contains("\n#[cfg(feature = \"foo\")]\npub mod foo {\n"));
+        assert!(code.contains("\n    #[cfg(feature = \"foo-bar\")]\n    pub mod bar {\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "format")]
+    fn format() {
+        let _ = env_logger::try_init();
+        let tmp = tempfile::tempdir().unwrap();
+        Config::new()
+            .out_dir(tmp.path())
+            .compile_protos(&["src/types.proto"], &["src"])
+            .unwrap();
+        let code = fs::read_to_string(tmp.path().join("helloworld.rs")).unwrap();
+        assert_eq!(
+            code,
+            "#[derive(Clone, PartialEq, ::prost::Message)]\n\
+             pub struct Message {\n    \
+                 #[prost(string, tag = \"1\")]\n    \
+                 pub say: ::prost::alloc::string::String,\n\
+             }\n\
+             #[derive(Clone, PartialEq, ::prost::Message)]\n\
+             pub struct Response {\n    \
+                 #[prost(string, tag = \"1\")]\n    \
+                 pub say: ::prost::alloc::string::String,\n\
+             }\n"
+        );
+    }
 }
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use petgraph::algo::has_path_connecting;
 use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeFiltered;
 use petgraph::Graph;
 
 use prost_types::{field_descriptor_proto, DescriptorProto, FileDescriptorProto};
@@ -9,9 +10,13 @@ use prost_types::{field_descriptor_proto, DescriptorProto, FileDescriptorProto};
 /// `MessageGraph` builds a graph of messages whose edges correspond to nesting.
 /// The goal is to recognize when message types are recursively nested, so
 /// that fields can be boxed when necessary.
+///
+/// Edges are weighted with `true` if the field is not repeated, which are the only edges
+/// considered when deciding whether to box a field.
 pub struct MessageGraph {
     index: HashMap<String, NodeIndex>,
-    graph: Graph<String, ()>,
+    graph: Graph<String, bool>,
+    messages: HashMap<String, DescriptorProto>,
 }
 
 impl MessageGraph {
@@ -19,6 +24,7 @@ impl MessageGraph {
         let mut msg_graph = MessageGraph {
             index: HashMap::new(),
             graph: Graph::new(),
+            messages: HashMap::new(),
         };
 
         for file in files {
@@ -39,6 +45,7 @@ impl MessageGraph {
         let MessageGraph {
             ref mut index,
             ref mut graph,
+            ..
         } = *self;
         assert_eq!(b'.', msg_name.as_bytes()[0]);
         *index
@@ -46,7 +53,7 @@ impl MessageGraph {
             .or_insert_with(|| graph.add_node(msg_name))
     }
 
-    /// Adds message to graph IFF it contains a non-repeated field containing another message.
+    /// Adds message to graph, with an edge for each field containing another message.
     /// The purpose of the message graph is detecting recursively nested messages and co-recursively nested messages.
     /// Because prost does not box message fields, recursively nested messages would not compile in Rust.
     /// To allow recursive messages, the message graph is used to detect recursion and automatically box the recursive field.
@@ -56,30 +63,55 @@ impl MessageGraph {
         let msg_index = self.get_or_insert_index(msg_name.clone());
 
         for field in &msg.field {
-            if field.r#type() == field_descriptor_proto::Type::Message
-                && field.label() != field_descriptor_proto::Label::Repeated
-            {
+            if field.r#type() == field_descriptor_proto::Type::Message {
                 let field_index = self.get_or_insert_index(field.type_name.clone().unwrap());
-                self.graph.add_edge(msg_index, field_index, ());
+                let nested = field.label() != field_descriptor_proto::Label::Repeated;
+                self.graph.add_edge(msg_index, field_index, nested);
             }
         }
 
         for msg in &msg.nested_type {
             self.add_message(&msg_name, msg);
         }
+
+        self.messages.insert(msg_name, msg.clone());
+    }
+
+    /// Returns the descriptor of the message with the fully-qualified name.
+    pub fn get_message(&self, name: &str) -> Option<&DescriptorProto> {
+        self.messages.get(name)
+    }
+
+    /// Returns the fully-qualified names and descriptors of the messages.
+    pub fn messages(&self) -> impl Iterator<Item = (&str, &DescriptorProto)> {
+        self.messages
+            .iter()
+            .map(|(name, message)| (name.as_str(), message))
     }
 
     /// Returns true if message type `inner` is nested in message type `outer`.
     pub fn is_nested(&self, outer: &str, inner: &str) -> bool {
-        let outer = match self.index.get(outer) {
-            Some(outer) => *outer,
+        let (outer, inner) = match self.indices(outer, inner) {
+            Some(indices) => indices,
             None => return false,
         };
-        let inner = match self.index.get(inner) {
-            Some(inner) => *inner,
+
+        let nested = EdgeFiltered::from_fn(&self.graph, |edge| *edge.weight());
+        has_path_connecting(&nested, outer, inner, None)
+    }
+
+    /// Returns true if message type `inner` is contained in message type `outer`, including
+    /// through repeated fields and map values.
+    pub fn is_contained(&self, outer: &str, inner: &str) -> bool {
+        let (outer, inner) = match self.indices(outer, inner) {
+            Some(indices) => indices,
             None => return false,
         };
 
         has_path_connecting(&self.graph, outer, inner, None)
     }
+
+    fn indices(&self, outer: &str, inner: &str) -> Option<(NodeIndex, NodeIndex)> {
+        Some((*self.index.get(outer)?, *self.index.get(inner)?))
+    }
 }
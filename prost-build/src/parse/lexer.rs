@@ -0,0 +1,616 @@
+//! Tokenizer of `.proto` files.
+//!
+//! Comments are attributed to the tokens which follow them with the rules of `protoc`, so that
+//! the leading, trailing and detached comments of declarations match its `SourceCodeInfo`.
+
+use super::ParseError;
+
+/// The kind and value of a token.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum TokenKind {
+    Ident(String),
+    Int(u64),
+    Float(f64),
+    /// A string literal, with its escapes resolved.
+    Str(Vec<u8>),
+    Symbol(char),
+}
+
+/// The position of a token in the source, as zero-based lines and columns.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// The comments preceding a token.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct TokenComments {
+    /// The trailing comment of the previous token.
+    pub trailing: Option<String>,
+    /// The comment blocks which are not attached to the previous or to this token.
+    pub detached: Vec<String>,
+    /// The leading comment of this token.
+    pub leading: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+    pub comments: TokenComments,
+}
+
+/// Splits the source of a `.proto` file into tokens.
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        pos: 0,
+        line: 0,
+        column: 0,
+    };
+    // Skip the byte order mark.
+    if lexer.peek() == Some('\u{feff}') {
+        lexer.pos += 1;
+    }
+    let mut tokens = Vec::new();
+    while let Some(token) = lexer.next_with_comments(tokens.is_empty())? {
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    column: usize,
+}
+
+/// Collects the comments between two tokens, as `protoc`'s `CommentCollector` does.
+struct Collector {
+    buffer: String,
+    has_comment: bool,
+    is_line_comment: bool,
+    can_attach_to_prev: bool,
+    comments: TokenComments,
+}
+
+impl Collector {
+    fn new() -> Collector {
+        Collector {
+            buffer: String::new(),
+            has_comment: false,
+            is_line_comment: false,
+            can_attach_to_prev: true,
+            comments: TokenComments::default(),
+        }
+    }
+
+    /// Starts or continues a block of line comments.
+    fn line_comment(&mut self, comment: String) {
+        if self.has_comment && !self.is_line_comment {
+            self.flush();
+        }
+        self.has_comment = true;
+        self.is_line_comment = true;
+        self.buffer.push_str(&comment);
+    }
+
+    fn block_comment(&mut self, comment: String) {
+        if self.has_comment {
+            self.flush();
+        }
+        self.has_comment = true;
+        self.is_line_comment = false;
+        self.buffer.push_str(&comment);
+    }
+
+    fn clear(&mut self) {
+        self.buffer.clear();
+        self.has_comment = false;
+    }
+
+    /// Ends the current comment block, which is attached to the previous token if it is the
+    /// first one after it, and is detached otherwise.
+    fn flush(&mut self) {
+        if self.has_comment {
+            let comment = std::mem::take(&mut self.buffer);
+            if self.can_attach_to_prev {
+                self.comments.trailing = Some(comment);
+                self.can_attach_to_prev = false;
+            } else {
+                self.comments.detached.push(comment);
+            }
+            self.clear();
+        }
+    }
+
+    fn detach_from_prev(&mut self) {
+        self.can_attach_to_prev = false;
+    }
+
+    /// Returns the comments, the current block being the leading comment of the next token.
+    fn finish(mut self) -> TokenComments {
+        if self.has_comment {
+            self.comments.leading = Some(self.buffer);
+        }
+        self.comments
+    }
+}
+
+enum CommentStart {
+    Line,
+    Block,
+}
+
+impl Lexer {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 0;
+            }
+            // Like `protoc`, tabs advance to the next multiple of 8 columns.
+            '\t' => self.column += 8 - self.column % 8,
+            _ => self.column += 1,
+        }
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError::new(self.line, self.column, message)
+    }
+
+    fn skip_whitespace_no_newline(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '\n' || !c.is_whitespace() {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn comment_start(&mut self) -> Option<CommentStart> {
+        match (self.peek(), self.peek_at(1)) {
+            (Some('/'), Some('/')) => {
+                self.bump();
+                self.bump();
+                Some(CommentStart::Line)
+            }
+            (Some('/'), Some('*')) => {
+                self.bump();
+                self.bump();
+                Some(CommentStart::Block)
+            }
+            _ => None,
+        }
+    }
+
+    /// Consumes the rest of a line comment, including the newline.
+    fn line_comment(&mut self) -> String {
+        let mut content = String::new();
+        while let Some(c) = self.bump() {
+            content.push(c);
+            if c == '\n' {
+                break;
+            }
+        }
+        content
+    }
+
+    /// Consumes the rest of a block comment, stripping the whitespace and the `*` at the start of
+    /// its lines.
+    fn block_comment(&mut self) -> Result<String, ParseError> {
+        let mut content = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error("End-of-file inside block comment.")),
+                Some('\n') => {
+                    self.bump();
+                    content.push('\n');
+                    self.skip_whitespace_no_newline();
+                    if self.eat('*') && self.eat('/') {
+                        break;
+                    }
+                }
+                Some('*') if self.peek_at(1) == Some('/') => {
+                    self.bump();
+                    self.bump();
+                    break;
+                }
+                Some(c) => {
+                    self.bump();
+                    content.push(c);
+                }
+            }
+        }
+        Ok(content)
+    }
+
+    /// Returns the next token, with the comments preceding it, following `protoc`'s
+    /// `Tokenizer::NextWithComments`.
+    fn next_with_comments(&mut self, start: bool) -> Result<Option<Token>, ParseError> {
+        let mut collector = Collector::new();
+        if start {
+            collector.detach_from_prev();
+        } else {
+            // A comment on the same line as the previous token is attached to it.
+            self.skip_whitespace_no_newline();
+            match self.comment_start() {
+                Some(CommentStart::Line) => {
+                    let comment = self.line_comment();
+                    collector.line_comment(comment);
+                    // Comments on the following lines are not part of the trailing comment.
+                    collector.flush();
+                }
+                Some(CommentStart::Block) => {
+                    let comment = self.block_comment()?;
+                    collector.block_comment(comment);
+                    self.skip_whitespace_no_newline();
+                    if !self.eat('\n') {
+                        // The next token is on the same line, so the comment can't be attributed.
+                        collector.clear();
+                        return self.next_token(collector);
+                    }
+                    collector.flush();
+                }
+                None => {
+                    if !self.eat('\n') {
+                        // The next token is on the same line, there are no comments.
+                        return self.next_token(collector);
+                    }
+                }
+            }
+        }
+
+        // The lexer is now on the line after the previous token.
+        loop {
+            self.skip_whitespace_no_newline();
+            match self.comment_start() {
+                Some(CommentStart::Line) => {
+                    let comment = self.line_comment();
+                    collector.line_comment(comment);
+                }
+                Some(CommentStart::Block) => {
+                    let comment = self.block_comment()?;
+                    collector.block_comment(comment);
+                    // Consume the rest of the line, so it isn't seen as a blank line.
+                    self.skip_whitespace_no_newline();
+                    self.eat('\n');
+                }
+                None => {
+                    if self.eat('\n') {
+                        // A blank line ends the comment block.
+                        collector.flush();
+                        collector.detach_from_prev();
+                    } else {
+                        self.skip_whitespace_and_comments()?;
+                        if let Some(c) = self.peek() {
+                            if c == '}' || c == ']' || c == ')' {
+                                // Comments at the end of a scope are not attached to its end.
+                                collector.flush();
+                            }
+                        }
+                        return self.next_token(collector);
+                    }
+                }
+            }
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), ParseError> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                _ => match self.comment_start() {
+                    Some(CommentStart::Line) => {
+                        self.line_comment();
+                    }
+                    Some(CommentStart::Block) => {
+                        self.block_comment()?;
+                    }
+                    None => return Ok(()),
+                },
+            }
+        }
+    }
+
+    fn next_token(&mut self, collector: Collector) -> Result<Option<Token>, ParseError> {
+        self.skip_whitespace_and_comments()?;
+        let (line, column) = (self.line, self.column);
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let kind = if c.is_ascii_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(c) = self.peek() {
+                if !c.is_ascii_alphanumeric() && c != '_' {
+                    break;
+                }
+                ident.push(c);
+                self.bump();
+            }
+            TokenKind::Ident(ident)
+        } else if c.is_ascii_digit()
+            || (c == '.' && self.peek_at(1).map_or(false, |c| c.is_ascii_digit()))
+        {
+            self.number()?
+        } else if c == '"' || c == '\'' {
+            self.bump();
+            self.string(c)?
+        } else if c.is_ascii_graphic() {
+            self.bump();
+            TokenKind::Symbol(c)
+        } else {
+            return Err(self.error(format!(
+                "Invalid control characters encountered in text: {:?}",
+                c
+            )));
+        };
+        Ok(Some(Token {
+            kind,
+            span: Span {
+                line,
+                column,
+                end_line: self.line,
+                end_column: self.column,
+            },
+            comments: collector.finish(),
+        }))
+    }
+
+    fn digits(&mut self, radix: u32) -> String {
+        let mut digits = String::new();
+        while let Some(c) = self.peek() {
+            if !c.is_digit(radix) {
+                break;
+            }
+            digits.push(c);
+            self.bump();
+        }
+        digits
+    }
+
+    fn number(&mut self) -> Result<TokenKind, ParseError> {
+        let (line, column) = (self.line, self.column);
+        let out_of_range = || ParseError::new(line, column, "Integer out of range.");
+        let kind = if self.peek() == Some('0') && matches!(self.peek_at(1), Some('x') | Some('X')) {
+            self.bump();
+            self.bump();
+            let digits = self.digits(16);
+            if digits.is_empty() {
+                return Err(self.error("\"0x\" must be followed by hex digits."));
+            }
+            TokenKind::Int(u64::from_str_radix(&digits, 16).map_err(|_| out_of_range())?)
+        } else {
+            let mut text = self.digits(10);
+            let mut float = false;
+            if self.peek() == Some('.') {
+                float = true;
+                self.bump();
+                text.push('.');
+                text.push_str(&self.digits(10));
+            }
+            if let Some(e) = self.peek().filter(|&c| c == 'e' || c == 'E') {
+                float = true;
+                self.bump();
+                text.push(e);
+                if let Some(sign) = self.peek().filter(|&c| c == '+' || c == '-') {
+                    self.bump();
+                    text.push(sign);
+                }
+                let exponent = self.digits(10);
+                if exponent.is_empty() {
+                    return Err(self.error("\"e\" must be followed by exponent."));
+                }
+                text.push_str(&exponent);
+            }
+            if float {
+                TokenKind::Float(text.parse().map_err(|_| {
+                    ParseError::new(line, column, format!("Invalid float literal: {}", text))
+                })?)
+            } else if text.len() > 1 && text.starts_with('0') {
+                if text.chars().any(|c| c == '8' || c == '9') {
+                    return Err(ParseError::new(
+                        line,
+                        column,
+                        "Numbers starting with leading zero must be in octal.",
+                    ));
+                }
+                TokenKind::Int(u64::from_str_radix(&text[1..], 8).map_err(|_| out_of_range())?)
+            } else {
+                TokenKind::Int(text.parse().map_err(|_| out_of_range())?)
+            }
+        };
+        if self
+            .peek()
+            .map_or(false, |c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            return Err(self.error("Need space between number and identifier."));
+        }
+        Ok(kind)
+    }
+
+    /// Lexes a string literal, after its opening quote.
+    fn string(&mut self, quote: char) -> Result<TokenKind, ParseError> {
+        let mut value = Vec::new();
+        loop {
+            let c = match self.bump() {
+                None | Some('\n') => {
+                    return Err(self.error("String literals cannot cross line boundaries."))
+                }
+                Some(c) => c,
+            };
+            if c == quote {
+                return Ok(TokenKind::Str(value));
+            }
+            if c != '\\' {
+                let mut utf8 = [0; 4];
+                value.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                continue;
+            }
+            let escape = self
+                .bump()
+                .ok_or_else(|| self.error("Invalid escape sequence in string literal."))?;
+            match escape {
+                'a' => value.push(0x07),
+                'b' => value.push(0x08),
+                'f' => value.push(0x0c),
+                'n' => value.push(b'\n'),
+                'r' => value.push(b'\r'),
+                't' => value.push(b'\t'),
+                'v' => value.push(0x0b),
+                '\\' | '?' | '\'' | '"' => value.push(escape as u8),
+                'x' | 'X' => {
+                    let mut byte = 0u32;
+                    let mut count = 0;
+                    while count < 2 {
+                        match self.peek().and_then(|c| c.to_digit(16)) {
+                            Some(digit) => {
+                                byte = byte * 16 + digit;
+                                self.bump();
+                                count += 1;
+                            }
+                            None => break,
+                        }
+                    }
+                    if count == 0 {
+                        return Err(self.error("Expected hex digits for escape sequence."));
+                    }
+                    value.push(byte as u8);
+                }
+                '0'..='7' => {
+                    let mut byte = escape.to_digit(8).unwrap();
+                    for _ in 0..2 {
+                        match self.peek().and_then(|c| c.to_digit(8)) {
+                            Some(digit) => {
+                                byte = byte * 8 + digit;
+                                self.bump();
+                            }
+                            None => break,
+                        }
+                    }
+                    value.push(byte as u8);
+                }
+                'u' | 'U' => {
+                    let len = if escape == 'u' { 4 } else { 8 };
+                    let mut code = 0u32;
+                    for _ in 0..len {
+                        let digit = self.peek().and_then(|c| c.to_digit(16)).ok_or_else(|| {
+                            self.error("Expected hex digits for escape sequence.")
+                        })?;
+                        code = code.wrapping_mul(16).wrapping_add(digit);
+                        self.bump();
+                    }
+                    let c = std::char::from_u32(code)
+                        .ok_or_else(|| self.error("Invalid Unicode escape sequence."))?;
+                    let mut utf8 = [0; 4];
+                    value.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                }
+                _ => return Err(self.error("Invalid escape sequence in string literal.")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        tokenize(source)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(
+            kinds("foo_1 = 0x1F 017 0 1.5 .5e1 'a\\x41\\101\\n\\u00e9' \"b\";"),
+            vec![
+                TokenKind::Ident("foo_1".to_string()),
+                TokenKind::Symbol('='),
+                TokenKind::Int(31),
+                TokenKind::Int(15),
+                TokenKind::Int(0),
+                TokenKind::Float(1.5),
+                TokenKind::Float(5.0),
+                TokenKind::Str(b"aAA\n\xc3\xa9".to_vec()),
+                TokenKind::Str(b"b".to_vec()),
+                TokenKind::Symbol(';'),
+            ]
+        );
+        assert!(tokenize("09").is_err());
+        assert!(tokenize("1foo").is_err());
+        assert!(tokenize("\"unterminated\n\"").is_err());
+        assert!(tokenize("/* unterminated").is_err());
+    }
+
+    #[test]
+    fn comments() {
+        let tokens = tokenize(
+            "// detached\n\
+             \n\
+             // leading\n\
+             a; // trailing\n\
+             /* block\n\
+              * comment */\n\
+             \n\
+             b;\n\
+             // end of scope\n\
+             }",
+        )
+        .unwrap();
+        assert_eq!(
+            tokens[0].comments,
+            TokenComments {
+                trailing: None,
+                detached: vec![" detached\n".to_string()],
+                leading: Some(" leading\n".to_string()),
+            }
+        );
+        assert_eq!(
+            tokens[2].comments,
+            TokenComments {
+                trailing: Some(" trailing\n".to_string()),
+                detached: vec![" block\n comment ".to_string()],
+                leading: None,
+            }
+        );
+        assert_eq!(
+            tokens[4].comments,
+            TokenComments {
+                trailing: Some(" end of scope\n".to_string()),
+                detached: vec![],
+                leading: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! A parser of `.proto` files, used instead of `protoc` with the `no-protoc` feature.
+//!
+//! The files are parsed into the `FileDescriptorSet` which `protoc` would output with
+//! `--include_imports` and `--include_source_info`, with a few differences:
+//!
+//! * Custom options are interpreted, but are not kept in the options of the descriptors, since
+//!   `prost-types` drops extensions. They are returned in a `CustomOptionsIndex` instead.
+//! * Most of the validation of `protoc` is skipped, so invalid files which `protoc` rejects may
+//!   be accepted.
+//! * Editions are not supported.
+
+mod lexer;
+mod options;
+mod parser;
+mod resolve;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+
+use crate::custom_options::CustomOptionsIndex;
+use parser::{ParsedFile, PendingOptions};
+
+/// The file defining the options messages, which is needed to interpret options.
+const DESCRIPTOR_PROTO: &str = "google/protobuf/descriptor.proto";
+
+/// An error parsing a `.proto` file.
+#[derive(Debug)]
+pub(crate) struct ParseError {
+    /// The zero-based line of the error.
+    line: usize,
+    /// The zero-based column of the error.
+    column: usize,
+    message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, column: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line + 1, self.column + 1, self.message)
+    }
+}
+
+/// Parses `.proto` files and their imports, found in the include directories, returning their
+/// descriptors and custom options.
+pub(crate) fn compile(
+    protos: &[impl AsRef<Path>],
+    includes: &[PathBuf],
+) -> Result<(FileDescriptorSet, CustomOptionsIndex)> {
+    let mut loader = Loader {
+        includes,
+        files: Vec::new(),
+        loaded: HashSet::new(),
+        loading: Vec::new(),
+    };
+    for proto in protos {
+        let name = loader.name(proto.as_ref())?;
+        loader.load(&name, None)?;
+    }
+
+    // Only the files given and their imports are output, as `protoc` does.
+    let output = loader.files.len();
+    if !loader.loaded.contains(DESCRIPTOR_PROTO)
+        && loader.files.iter().any(|file| !file.options.is_empty())
+    {
+        loader.load(DESCRIPTOR_PROTO, None)?;
+    }
+
+    let (mut files, pending): (Vec<FileDescriptorProto>, Vec<Vec<PendingOptions>>) = loader
+        .files
+        .into_iter()
+        .map(|file| (file.descriptor, file.options))
+        .unzip();
+    let symbols = resolve::resolve(&mut files)?;
+    let mut custom_options = CustomOptionsIndex::default();
+    options::interpret(&mut files, &pending, &symbols, &mut custom_options)?;
+
+    files.truncate(output);
+    Ok((FileDescriptorSet { file: files }, custom_options))
+}
+
+/// Loads files and their imports, in the order `protoc` outputs them: each file follows its
+/// imports.
+struct Loader<'a> {
+    includes: &'a [PathBuf],
+    files: Vec<ParsedFile>,
+    /// The names of the loaded files.
+    loaded: HashSet<String>,
+    /// The names of the files being loaded, which import the next one.
+    loading: Vec<String>,
+}
+
+impl Loader<'_> {
+    /// Returns the name of a file given as input, relative to the include directory containing
+    /// it, as in `FileDescriptorProto.name`.
+    fn name(&self, path: &Path) -> Result<String> {
+        // As with `protoc`, a relative path which does not exist is taken to be relative to the
+        // includes.
+        if !path.exists() {
+            if path.is_relative() {
+                return Ok(to_name(path));
+            }
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("{}: File not found.", path.display()),
+            ));
+        }
+        for include in self.includes {
+            if let Ok(name) = path.strip_prefix(include) {
+                return Ok(to_name(name));
+            }
+        }
+        let canonical = fs::canonicalize(path)?;
+        for include in self.includes {
+            if let Ok(include) = fs::canonicalize(include) {
+                if let Ok(name) = canonical.strip_prefix(include) {
+                    return Ok(to_name(name));
+                }
+            }
+        }
+        Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "{}: File does not reside within any include directory.",
+                path.display()
+            ),
+        ))
+    }
+
+    /// Loads a file and its imports, unless it is already loaded.
+    ///
+    /// `importer` is the name of the file importing it, if any.
+    fn load(&mut self, name: &str, importer: Option<&str>) -> Result<()> {
+        if self.loaded.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = self.loading.iter().position(|loading| loading == name) {
+            let mut cycle = self.loading[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "{}: File recursively imports itself: {}",
+                    name,
+                    cycle.join(" -> ")
+                ),
+            ));
+        }
+
+        let path = self
+            .includes
+            .iter()
+            .map(|include| include.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                let message = match importer {
+                    Some(importer) => format!("{}: Import \"{}\" was not found.", importer, name),
+                    None => format!("{}: File not found.", name),
+                };
+                Error::new(ErrorKind::NotFound, message)
+            })?;
+        let source = fs::read_to_string(&path)
+            .map_err(|error| Error::new(error.kind(), format!("{}: {}", path.display(), error)))?;
+        let file = parser::parse(name, &source)
+            .map_err(|error| Error::new(ErrorKind::Other, format!("{}:{}", name, error)))?;
+
+        self.loading.push(name.to_string());
+        for dependency in &file.descriptor.dependency {
+            self.load(dependency, Some(name))?;
+        }
+        self.loading.pop();
+
+        self.loaded.insert(name.to_string());
+        self.files.push(file);
+        Ok(())
+    }
+}
+
+/// Returns the name of a file from its path relative to an include directory, with `/` as
+/// separator.
+fn to_name(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, source: &str) {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, source).unwrap();
+    }
+
+    #[test]
+    fn compile_with_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            dir.path(),
+            "options/options.proto",
+            r#"
+            syntax = "proto3";
+            package options;
+            import "google/protobuf/descriptor.proto";
+            message Rules {
+                uint32 max_len = 1;
+                repeated string tags = 2;
+            }
+            extend google.protobuf.FieldOptions {
+                Rules rules = 50000;
+            }
+            "#,
+        );
+        write(
+            dir.path(),
+            "app/app.proto",
+            r#"
+            syntax = "proto3";
+            package app.v1;
+            import "options/options.proto";
+            message Item {
+                message Part {}
+                string name = 1 [(options.rules) = {max_len: 10, tags: "a"}, deprecated = true];
+                Part part = 2;
+                v1.Item.Part other = 3;
+            }
+            "#,
+        );
+
+        let includes = [dir.path().to_path_buf(), crate::protoc_include()];
+        let (set, custom_options) =
+            compile(&[dir.path().join("app/app.proto")], &includes).unwrap();
+
+        // The imports come first, and `descriptor.proto` is only output as an import.
+        let names: Vec<_> = set.file.iter().map(|file| file.name()).collect();
+        assert_eq!(
+            names,
+            [DESCRIPTOR_PROTO, "options/options.proto", "app/app.proto"]
+        );
+
+        let item = &set.file[2].message_type[0];
+        assert_eq!(item.field[1].type_name(), ".app.v1.Item.Part");
+        assert_eq!(item.field[2].type_name(), ".app.v1.Item.Part");
+        assert!(item.field[0].options.as_ref().unwrap().deprecated());
+
+        let options = custom_options.field(".app.v1.Item", "name").unwrap();
+        let rules = options.get(50000).unwrap();
+        // `Rules { max_len: 10, tags: ["a"] }`
+        assert_eq!(rules.value, &[8, 10, 18, 1, b'a'][..]);
+        assert!(custom_options.message(".app.v1.Item").is_none());
+
+        write(
+            dir.path(),
+            "app/invalid.proto",
+            r#"
+            syntax = "proto3";
+            message Invalid {
+                Missing missing = 1;
+            }
+            "#,
+        );
+        let error = compile(&["app/invalid.proto"], &includes).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "app/invalid.proto:4:17: \"Missing\" is not defined."
+        );
+        let error = compile(&["app/missing.proto"], &includes).unwrap_err();
+        assert_eq!(error.to_string(), "app/missing.proto: File not found.");
+    }
+}
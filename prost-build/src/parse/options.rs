@@ -0,0 +1,722 @@
+//! Interpretation of the options of descriptors.
+//!
+//! Options are encoded as `protoc` encodes them: each option statement is encoded on its own, in
+//! the order of the statements, and aggregate values are encoded canonically, with their fields in
+//! order of field number. The encoded options are decoded into the options of the descriptors,
+//! which drops the custom options, and are added as such to the index of custom options.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error, ErrorKind, Result};
+
+use prost::bytes::{BufMut, Bytes};
+use prost::encoding::{encode_key, encode_varint, WireType};
+use prost::{DecodeError, Message};
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, EnumOptions, EnumValueOptions, FieldDescriptorProto,
+    FieldOptions, FileDescriptorProto, FileOptions, MessageOptions, MethodOptions, OneofOptions,
+    ServiceDescriptorProto, ServiceOptions,
+};
+
+use super::parser::{OptionNamePart, OptionStatement, OptionValue, PendingOptions};
+use super::resolve::{Symbol, Symbols};
+use crate::custom_options::{CustomOptionsIndex, Kind};
+
+/// A field of a message, or an extension.
+struct FieldInfo {
+    name: String,
+    number: u32,
+    ty: Type,
+    /// The fully-qualified name of the message or enum type of the field.
+    type_name: String,
+    repeated: bool,
+    packed: bool,
+    /// The fully-qualified name of the extended message, if the field is an extension.
+    extendee: Option<String>,
+}
+
+impl FieldInfo {
+    fn new(field: &FieldDescriptorProto, proto3: bool, pending: Option<&PendingOptions>) -> Self {
+        let ty = field.r#type();
+        let repeated = field.label() == Label::Repeated;
+        let packable = !matches!(ty, Type::String | Type::Bytes | Type::Message | Type::Group);
+        // Options are not yet interpreted, so `packed` is read from the option statements.
+        let packed = pending
+            .into_iter()
+            .flat_map(|pending| &pending.options)
+            .filter_map(|statement| match (&*statement.name, &statement.value) {
+                ([part], OptionValue::Ident(value)) if !part.extension && part.name == "packed" => {
+                    Some(value == "true")
+                }
+                _ => None,
+            })
+            .next_back()
+            .unwrap_or(proto3);
+        FieldInfo {
+            name: field.name().to_string(),
+            number: field.number() as u32,
+            ty,
+            type_name: field.type_name().to_string(),
+            repeated,
+            packed: repeated && packable && packed,
+            extendee: field.extendee.clone(),
+        }
+    }
+}
+
+/// The fields of the messages and the extensions defined by a set of files.
+struct Pool<'a> {
+    symbols: &'a Symbols,
+    messages: HashMap<String, Vec<FieldInfo>>,
+    extensions: HashMap<String, FieldInfo>,
+}
+
+impl<'a> Pool<'a> {
+    fn new(
+        symbols: &'a Symbols,
+        files: &[FileDescriptorProto],
+        pending: &[Vec<PendingOptions>],
+    ) -> Self {
+        let mut pool = Pool {
+            symbols,
+            messages: HashMap::new(),
+            extensions: HashMap::new(),
+        };
+        for (file, pending) in files.iter().zip(pending) {
+            let proto3 = file.syntax() == "proto3";
+            let pending: HashMap<&[i32], &PendingOptions> = pending
+                .iter()
+                .map(|pending| (&*pending.path, pending))
+                .collect();
+            let package = match file.package {
+                Some(ref package) => format!(".{}", package),
+                None => String::new(),
+            };
+            for (i, message) in file.message_type.iter().enumerate() {
+                pool.add_message(message, &package, vec![4, i as i32], proto3, &pending);
+            }
+            for (i, extension) in file.extension.iter().enumerate() {
+                let path = [7, i as i32];
+                let info = FieldInfo::new(extension, proto3, pending.get(&path[..]).copied());
+                let fq_name = format!("{}.{}", package, extension.name());
+                pool.extensions.insert(fq_name, info);
+            }
+        }
+        pool
+    }
+
+    fn add_message(
+        &mut self,
+        message: &DescriptorProto,
+        scope: &str,
+        path: Vec<i32>,
+        proto3: bool,
+        pending: &HashMap<&[i32], &PendingOptions>,
+    ) {
+        let fq_name = format!("{}.{}", scope, message.name());
+        let fields = message
+            .field
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let path = [&*path, &[2, i as i32]].concat();
+                FieldInfo::new(field, proto3, pending.get(&*path).copied())
+            })
+            .collect();
+        for (i, extension) in message.extension.iter().enumerate() {
+            let path = [&*path, &[6, i as i32]].concat();
+            let info = FieldInfo::new(extension, proto3, pending.get(&*path).copied());
+            let name = format!("{}.{}", fq_name, extension.name());
+            self.extensions.insert(name, info);
+        }
+        for (i, nested) in message.nested_type.iter().enumerate() {
+            let path = [&*path, &[3, i as i32]].concat();
+            self.add_message(nested, &fq_name, path, proto3, pending);
+        }
+        self.messages.insert(fq_name, fields);
+    }
+}
+
+/// Interprets the options of the files, setting them on their descriptors and adding the custom
+/// options to the index.
+///
+/// `pending` holds the options of each file, and `files` must include all of their dependencies
+/// and `google/protobuf/descriptor.proto`.
+pub(crate) fn interpret(
+    files: &mut [FileDescriptorProto],
+    pending: &[Vec<PendingOptions>],
+    symbols: &Symbols,
+    index: &mut CustomOptionsIndex,
+) -> Result<()> {
+    let pool = Pool::new(symbols, files, pending);
+    for (file_index, (file, pending)) in files.iter_mut().zip(pending).enumerate() {
+        for options in pending {
+            let interpreter = Interpreter {
+                pool: &pool,
+                file: file_index,
+                scope: &options.scope,
+            };
+            let encoded = interpreter
+                .options(options)
+                .map_err(|(line, column, message)| {
+                    Error::new(
+                        ErrorKind::Other,
+                        format!("{}:{}:{}: {}", file.name(), line + 1, column + 1, message),
+                    )
+                })?;
+            set_options(file, options.kind, &options.path, &encoded).map_err(|error| {
+                Error::new(
+                    ErrorKind::Other,
+                    format!("{}: invalid options: {}", file.name(), error),
+                )
+            })?;
+            if let Some(ref name) = options.index_name {
+                index
+                    .insert(options.kind, name.clone(), Bytes::from(encoded))
+                    .map_err(|error| {
+                        Error::new(
+                            ErrorKind::Other,
+                            format!("{}: invalid options: {}", file.name(), error),
+                        )
+                    })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sets the encoded options of the descriptor at a path of a file.
+fn set_options(
+    file: &mut FileDescriptorProto,
+    kind: Kind,
+    path: &[i32],
+    encoded: &[u8],
+) -> std::result::Result<(), DecodeError> {
+    match *path {
+        [] => file.options = Some(FileOptions::decode(encoded)?),
+        [4, i, ref rest @ ..] => {
+            set_message_options(&mut file.message_type[i as usize], rest, encoded)?
+        }
+        [5, i, ref rest @ ..] => set_enum_options(&mut file.enum_type[i as usize], rest, encoded)?,
+        [6, i, ref rest @ ..] => set_service_options(&mut file.service[i as usize], rest, encoded)?,
+        [7, i] => file.extension[i as usize].options = Some(FieldOptions::decode(encoded)?),
+        _ => unreachable!("invalid path of {:?} options: {:?}", kind, path),
+    }
+    Ok(())
+}
+
+fn set_message_options(
+    message: &mut DescriptorProto,
+    path: &[i32],
+    encoded: &[u8],
+) -> std::result::Result<(), DecodeError> {
+    match *path {
+        [] => message.options = Some(MessageOptions::decode(encoded)?),
+        [2, i] => message.field[i as usize].options = Some(FieldOptions::decode(encoded)?),
+        [3, i, ref rest @ ..] => {
+            set_message_options(&mut message.nested_type[i as usize], rest, encoded)?
+        }
+        [4, i, ref rest @ ..] => {
+            set_enum_options(&mut message.enum_type[i as usize], rest, encoded)?
+        }
+        [6, i] => message.extension[i as usize].options = Some(FieldOptions::decode(encoded)?),
+        [8, i] => message.oneof_decl[i as usize].options = Some(OneofOptions::decode(encoded)?),
+        _ => unreachable!("invalid path of message options: {:?}", path),
+    }
+    Ok(())
+}
+
+fn set_enum_options(
+    enumeration: &mut EnumDescriptorProto,
+    path: &[i32],
+    encoded: &[u8],
+) -> std::result::Result<(), DecodeError> {
+    match *path {
+        [] => enumeration.options = Some(EnumOptions::decode(encoded)?),
+        [2, i] => enumeration.value[i as usize].options = Some(EnumValueOptions::decode(encoded)?),
+        _ => unreachable!("invalid path of enum options: {:?}", path),
+    }
+    Ok(())
+}
+
+fn set_service_options(
+    service: &mut ServiceDescriptorProto,
+    path: &[i32],
+    encoded: &[u8],
+) -> std::result::Result<(), DecodeError> {
+    match *path {
+        [] => service.options = Some(ServiceOptions::decode(encoded)?),
+        [2, i] => service.method[i as usize].options = Some(MethodOptions::decode(encoded)?),
+        _ => unreachable!("invalid path of service options: {:?}", path),
+    }
+    Ok(())
+}
+
+/// An error interpreting an option: its zero-based line and column, and the message.
+type OptionError = (usize, usize, String);
+
+struct Interpreter<'a> {
+    pool: &'a Pool<'a>,
+    file: usize,
+    /// The scope in which the names of extensions are resolved.
+    scope: &'a str,
+}
+
+impl Interpreter<'_> {
+    /// Encodes the options of a descriptor, as its options message.
+    fn options(&self, options: &PendingOptions) -> std::result::Result<Vec<u8>, OptionError> {
+        let message = match options.kind {
+            Kind::File => ".google.protobuf.FileOptions",
+            Kind::Message => ".google.protobuf.MessageOptions",
+            Kind::Field => ".google.protobuf.FieldOptions",
+            Kind::Oneof => ".google.protobuf.OneofOptions",
+            Kind::Enum => ".google.protobuf.EnumOptions",
+            Kind::EnumValue => ".google.protobuf.EnumValueOptions",
+            Kind::Service => ".google.protobuf.ServiceOptions",
+            Kind::Method => ".google.protobuf.MethodOptions",
+        };
+        let mut buf = Vec::new();
+        let mut set = Vec::new();
+        for statement in &options.options {
+            self.statement(message, statement, &mut buf, &mut set)
+                .map_err(|message| (statement.line, statement.column, message))?;
+        }
+        Ok(buf)
+    }
+
+    /// Encodes an option statement, checking that it does not set a singular option which is
+    /// already in `set`.
+    fn statement(
+        &self,
+        message: &str,
+        statement: &OptionStatement,
+        buf: &mut Vec<u8>,
+        set: &mut Vec<Vec<u32>>,
+    ) -> std::result::Result<(), String> {
+        let display_name = statement
+            .name
+            .iter()
+            .map(|part| {
+                if part.extension {
+                    format!("({})", part.name)
+                } else {
+                    part.name.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(".");
+
+        let mut fields = Vec::with_capacity(statement.name.len());
+        let mut message = message.to_string();
+        for (i, part) in statement.name.iter().enumerate() {
+            let field = self
+                .field(&message, part)
+                .map_err(|_| format!("Option \"{}\" unknown.", display_name))?;
+            if i + 1 < statement.name.len() {
+                if field.ty != Type::Message && field.ty != Type::Group {
+                    return Err(format!(
+                        "Option \"{}\" is an atomic type, not a message.",
+                        display_name
+                    ));
+                }
+                if field.repeated {
+                    return Err(format!(
+                        "Option field \"{}\" is a repeated message. Repeated message options \
+                         must be initialized using an aggregate value.",
+                        display_name
+                    ));
+                }
+            }
+            message = field.type_name.clone();
+            fields.push(field);
+        }
+
+        let numbers: Vec<u32> = fields.iter().map(|field| field.number).collect();
+        let last = fields.last().unwrap();
+        if !last.repeated && last.ty != Type::Message && last.ty != Type::Group {
+            if set.contains(&numbers) {
+                return Err(format!("Option \"{}\" was already set.", display_name));
+            }
+            set.push(numbers);
+        }
+
+        let mut value = Vec::new();
+        match (last.ty, &statement.value) {
+            (Type::Message, OptionValue::Aggregate(_))
+            | (Type::Group, OptionValue::Aggregate(_)) => {
+                self.value(last, &statement.value, &mut value)?
+            }
+            (Type::Message, _) | (Type::Group, _) => {
+                return Err(format!(
+                    "Option \"{0}\" is a message. To set the entire message, use syntax like \
+                     \"{0} = {{ <proto text format> }}\". To set fields within it, use syntax \
+                     like \"{0}.foo = value\".",
+                    display_name
+                ));
+            }
+            _ => self
+                .value(last, &statement.value, &mut value)
+                .map_err(|error| format!("{} for option \"{}\".", error, display_name))?,
+        }
+
+        // Wraps the value in the messages of the intermediate fields.
+        for field in fields[..fields.len() - 1].iter().rev() {
+            let mut wrapped = Vec::new();
+            encode_message_field(field, &value, &mut wrapped);
+            value = wrapped;
+        }
+        buf.extend_from_slice(&value);
+        Ok(())
+    }
+
+    /// Returns the field of a message named by a component of an option name, or by the name of
+    /// a field in an aggregate value.
+    fn field(&self, message: &str, part: &OptionNamePart) -> std::result::Result<&FieldInfo, ()> {
+        if part.extension {
+            let (fq_name, symbol) = self
+                .pool
+                .symbols
+                .lookup(self.file, self.scope, &part.name, false)
+                .ok_or(())?;
+            let extension = match symbol {
+                Symbol::Extension => self.pool.extensions.get(&fq_name).ok_or(())?,
+                _ => return Err(()),
+            };
+            if extension.extendee.as_deref() != Some(message) {
+                return Err(());
+            }
+            Ok(extension)
+        } else {
+            self.pool
+                .messages
+                .get(message)
+                .and_then(|fields| {
+                    fields.iter().find(|field| {
+                        field.name == part.name
+                            // Groups are named by their type in the text format.
+                            || (field.ty == Type::Group
+                                && field.type_name.rsplit('.').next() == Some(&*part.name))
+                    })
+                })
+                .ok_or(())
+        }
+    }
+
+    /// Encodes a field and its value, including its key.
+    fn value(
+        &self,
+        field: &FieldInfo,
+        value: &OptionValue,
+        buf: &mut Vec<u8>,
+    ) -> std::result::Result<(), String> {
+        let number = field.number;
+        match field.ty {
+            Type::Message | Type::Group => {
+                let fields = match value {
+                    OptionValue::Aggregate(fields) => fields,
+                    _ => return Err(format!("Expected \"{{\" for field \"{}\"", field.name)),
+                };
+                let mut message = Vec::new();
+                self.message(&field.type_name, fields, &mut message)?;
+                encode_message_field(field, &message, buf);
+            }
+            Type::String | Type::Bytes => match value {
+                OptionValue::Str(value) => {
+                    if field.ty == Type::String && std::str::from_utf8(value).is_err() {
+                        return Err("String is not valid UTF-8".to_string());
+                    }
+                    encode_key(number, WireType::LengthDelimited, buf);
+                    encode_varint(value.len() as u64, buf);
+                    buf.put_slice(value);
+                }
+                _ => {
+                    return Err(format!(
+                        "Value must be quoted string for {}",
+                        field.ty_name()
+                    ))
+                }
+            },
+            Type::Double | Type::Float => {
+                let value = match value {
+                    OptionValue::Float(value) => *value,
+                    OptionValue::Int { negative, value } => {
+                        let value = *value as f64;
+                        if *negative {
+                            -value
+                        } else {
+                            value
+                        }
+                    }
+                    OptionValue::Ident(ident) => match &*ident.to_ascii_lowercase() {
+                        "inf" | "infinity" => f64::INFINITY,
+                        "nan" => f64::NAN,
+                        _ => return Err(format!("Value must be number for {}", field.ty_name())),
+                    },
+                    _ => return Err(format!("Value must be number for {}", field.ty_name())),
+                };
+                if field.ty == Type::Double {
+                    encode_key(number, WireType::SixtyFourBit, buf);
+                    buf.put_f64_le(value);
+                } else {
+                    encode_key(number, WireType::ThirtyTwoBit, buf);
+                    buf.put_f32_le(value as f32);
+                }
+            }
+            Type::Bool => {
+                let value = match value {
+                    OptionValue::Ident(ident) => match &**ident {
+                        "true" | "True" | "t" => true,
+                        "false" | "False" | "f" => false,
+                        _ => {
+                            return Err(format!(
+                                "Value must be \"true\" or \"false\" for {}",
+                                field.ty_name()
+                            ))
+                        }
+                    },
+                    OptionValue::Int {
+                        negative: false,
+                        value,
+                    } if *value <= 1 => *value == 1,
+                    _ => {
+                        return Err(format!(
+                            "Value must be \"true\" or \"false\" for {}",
+                            field.ty_name()
+                        ))
+                    }
+                };
+                encode_key(number, WireType::Varint, buf);
+                encode_varint(value as u64, buf);
+            }
+            Type::Enum => {
+                let values = self.pool.symbols.enum_values(&field.type_name);
+                let number_value = match value {
+                    OptionValue::Ident(ident) => values
+                        .iter()
+                        .find(|(name, _)| name == ident)
+                        .map(|&(_, number)| number)
+                        .ok_or_else(|| {
+                            format!(
+                                "Enum type \"{}\" has no value named \"{}\"",
+                                &field.type_name[1..],
+                                ident
+                            )
+                        })?,
+                    OptionValue::Int { .. } => integer(value, i32::MIN.into(), i32::MAX.into())
+                        .map_err(|error| format!("{} for {}", error, field.ty_name()))?
+                        as i32,
+                    _ => return Err(format!("Value must be identifier for {}", field.ty_name())),
+                };
+                encode_key(number, WireType::Varint, buf);
+                encode_varint(number_value as i64 as u64, buf);
+            }
+            ty => {
+                let (min, max) = match ty {
+                    Type::Int32 | Type::Sint32 | Type::Sfixed32 => {
+                        (i32::MIN.into(), i32::MAX.into())
+                    }
+                    Type::Int64 | Type::Sint64 | Type::Sfixed64 => {
+                        (i64::MIN.into(), i64::MAX.into())
+                    }
+                    Type::Uint32 | Type::Fixed32 => (0, u32::MAX.into()),
+                    _ => (0, u64::MAX.into()),
+                };
+                let value = integer(value, min, max)
+                    .map_err(|error| format!("{} for {}", error, field.ty_name()))?;
+                match ty {
+                    Type::Int32 | Type::Int64 | Type::Uint32 | Type::Uint64 => {
+                        encode_key(number, WireType::Varint, buf);
+                        encode_varint(value as i64 as u64, buf);
+                    }
+                    Type::Sint32 | Type::Sint64 => {
+                        let value = value as i64;
+                        encode_key(number, WireType::Varint, buf);
+                        encode_varint(((value << 1) ^ (value >> 63)) as u64, buf);
+                    }
+                    Type::Fixed32 | Type::Sfixed32 => {
+                        encode_key(number, WireType::ThirtyTwoBit, buf);
+                        buf.put_u32_le(value as u32);
+                    }
+                    _ => {
+                        encode_key(number, WireType::SixtyFourBit, buf);
+                        buf.put_u64_le(value as u64);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the fields of an aggregate value of a message, in order of field number.
+    fn message(
+        &self,
+        message: &str,
+        fields: &[(OptionNamePart, OptionValue)],
+        buf: &mut Vec<u8>,
+    ) -> std::result::Result<(), String> {
+        if message == ".google.protobuf.Any" {
+            if let [(part, OptionValue::Aggregate(value))] = fields {
+                if let Some(slash) = part.name.rfind('/') {
+                    return self.any(&part.name, &part.name[slash + 1..], value, buf);
+                }
+            }
+        }
+
+        let mut values: BTreeMap<u32, (&FieldInfo, Vec<&OptionValue>)> = BTreeMap::new();
+        for (part, value) in fields {
+            let field = self.field(message, part).map_err(|_| {
+                if part.extension {
+                    format!(
+                        "Extension \"{}\" is not defined or is not an extension of \"{}\"",
+                        part.name,
+                        &message[1..]
+                    )
+                } else {
+                    format!(
+                        "Message type \"{}\" has no field named \"{}\"",
+                        &message[1..],
+                        part.name
+                    )
+                }
+            })?;
+            let entry = values
+                .entry(field.number)
+                .or_insert_with(|| (field, Vec::new()));
+            match value {
+                OptionValue::List(list) => {
+                    if !field.repeated {
+                        return Err(format!(
+                            "Non-repeated field \"{}\" is specified multiple times",
+                            part.name
+                        ));
+                    }
+                    entry.1.extend(list);
+                }
+                value => entry.1.push(value),
+            }
+            if !field.repeated && entry.1.len() > 1 {
+                return Err(format!(
+                    "Non-repeated field \"{}\" is specified multiple times",
+                    part.name
+                ));
+            }
+        }
+
+        for (field, values) in values.values() {
+            if field.packed {
+                let mut packed = Vec::new();
+                for value in values {
+                    self.value(field, value, &mut packed)?;
+                }
+                // Strips the keys, which all have the same length.
+                let key_len = {
+                    let mut key = Vec::new();
+                    encode_key(field.number, WireType::Varint, &mut key);
+                    key.len()
+                };
+                let mut payload = Vec::with_capacity(packed.len());
+                let mut rest = &packed[..];
+                while !rest.is_empty() {
+                    rest = &rest[key_len..];
+                    let len = match field.ty {
+                        Type::Double | Type::Fixed64 | Type::Sfixed64 => 8,
+                        Type::Float | Type::Fixed32 | Type::Sfixed32 => 4,
+                        _ => rest.iter().position(|byte| byte & 0x80 == 0).unwrap() + 1,
+                    };
+                    payload.extend_from_slice(&rest[..len]);
+                    rest = &rest[len..];
+                }
+                encode_key(field.number, WireType::LengthDelimited, buf);
+                encode_varint(payload.len() as u64, buf);
+                buf.put_slice(&payload);
+            } else {
+                for value in values {
+                    self.value(field, value, buf)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes the expansion of an `Any`, such as `[type.googleapis.com/pkg.Message] { ... }`.
+    fn any(
+        &self,
+        type_url: &str,
+        type_name: &str,
+        fields: &[(OptionNamePart, OptionValue)],
+        buf: &mut Vec<u8>,
+    ) -> std::result::Result<(), String> {
+        let fq_name = format!(".{}", type_name);
+        if !self.pool.messages.contains_key(&fq_name) {
+            return Err(format!("Could not find type \"{}\"", type_url));
+        }
+        let mut value = Vec::new();
+        self.message(&fq_name, fields, &mut value)?;
+        encode_key(1, WireType::LengthDelimited, buf);
+        encode_varint(type_url.len() as u64, buf);
+        buf.put_slice(type_url.as_bytes());
+        encode_key(2, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(&value);
+        Ok(())
+    }
+}
+
+impl FieldInfo {
+    /// Returns the name of the type of the field, as used in error messages.
+    fn ty_name(&self) -> String {
+        let ty = match self.ty {
+            Type::Double => "double",
+            Type::Float => "float",
+            Type::Int64 => "int64",
+            Type::Uint64 => "uint64",
+            Type::Int32 => "int32",
+            Type::Fixed64 => "fixed64",
+            Type::Fixed32 => "fixed32",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Group => "group",
+            Type::Message => "message",
+            Type::Bytes => "bytes",
+            Type::Uint32 => "uint32",
+            Type::Enum => "enum",
+            Type::Sfixed32 => "sfixed32",
+            Type::Sfixed64 => "sfixed64",
+            Type::Sint32 => "sint32",
+            Type::Sint64 => "sint64",
+        };
+        format!("{} field \"{}\"", ty, self.name)
+    }
+}
+
+/// Encodes a message field, given its encoded value.
+fn encode_message_field(field: &FieldInfo, value: &[u8], buf: &mut Vec<u8>) {
+    if field.ty == Type::Group {
+        encode_key(field.number, WireType::StartGroup, buf);
+        buf.put_slice(value);
+        encode_key(field.number, WireType::EndGroup, buf);
+    } else {
+        encode_key(field.number, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value);
+    }
+}
+
+/// Returns the value of an integer option, if it is in a range.
+fn integer(value: &OptionValue, min: i128, max: i128) -> std::result::Result<i128, &'static str> {
+    match *value {
+        OptionValue::Int { negative, value } => {
+            let value = if negative {
+                -i128::from(value)
+            } else {
+                i128::from(value)
+            };
+            if value < min || value > max {
+                Err("Value out of range")
+            } else {
+                Ok(value)
+            }
+        }
+        _ => Err("Value must be integer"),
+    }
+}
@@ -0,0 +1,1510 @@
+//! Parser of `.proto` files into descriptors.
+//!
+//! The descriptors are those `protoc` would produce, except that type names are left as written
+//! in the file, to be resolved once all the imported files are parsed, and that options are left
+//! uninterpreted.
+
+use prost_types::descriptor_proto::{ExtensionRange, ReservedRange};
+use prost_types::enum_descriptor_proto::EnumReservedRange;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::source_code_info::Location;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+    FileDescriptorProto, MessageOptions, MethodDescriptorProto, OneofDescriptorProto,
+    ServiceDescriptorProto, SourceCodeInfo,
+};
+
+use super::lexer::{tokenize, Token, TokenKind};
+use super::ParseError;
+use crate::custom_options::Kind;
+
+/// The largest field number, plus one.
+const MAX_FIELD_NUMBER: i32 = 536_870_912;
+
+/// A `.proto` file, parsed into a descriptor.
+pub(crate) struct ParsedFile {
+    pub descriptor: FileDescriptorProto,
+    /// The options set in the file, by descriptor.
+    pub options: Vec<PendingOptions>,
+}
+
+/// The options set on a descriptor, to be interpreted once all the files are parsed.
+pub(crate) struct PendingOptions {
+    pub kind: Kind,
+    /// The path of the descriptor in the file, as in `SourceCodeInfo`.
+    pub path: Vec<i32>,
+    /// The name of the descriptor in the index of custom options, if it is indexed.
+    pub index_name: Option<String>,
+    /// The fully-qualified name of the scope enclosing the descriptor, in which the names of
+    /// extensions are resolved.
+    pub scope: String,
+    pub options: Vec<OptionStatement>,
+}
+
+/// An option assignment, such as `option (my.ext).field = 1;`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OptionStatement {
+    pub name: Vec<OptionNamePart>,
+    pub value: OptionValue,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A component of the name of an option, which is an extension if it is in parentheses.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct OptionNamePart {
+    pub name: String,
+    pub extension: bool,
+}
+
+/// The value of an option, in the text format.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum OptionValue {
+    Ident(String),
+    Int {
+        negative: bool,
+        value: u64,
+    },
+    Float(f64),
+    Str(Vec<u8>),
+    /// A message, as a list of field assignments.
+    Aggregate(Vec<(OptionNamePart, OptionValue)>),
+    /// The values of a repeated field of a message.
+    List(Vec<OptionValue>),
+}
+
+/// The inclusive ranges and the names of a `reserved` statement.
+type Reserved = (Vec<(i32, i32)>, Vec<String>);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Syntax {
+    Proto2,
+    Proto3,
+}
+
+/// Parses the source of a `.proto` file, named as in `FileDescriptorProto.name`.
+pub(crate) fn parse(name: &str, source: &str) -> Result<ParsedFile, ParseError> {
+    let mut parser = Parser {
+        name: name.to_string(),
+        tokens: tokenize(source)?,
+        pos: 0,
+        syntax: Syntax::Proto2,
+        package: String::new(),
+        locations: Vec::new(),
+        options: Vec::new(),
+    };
+    let mut descriptor = parser.file()?;
+    descriptor.name = Some(parser.name);
+    descriptor.source_code_info = Some(SourceCodeInfo {
+        location: parser.locations,
+    });
+    Ok(ParsedFile {
+        descriptor,
+        options: parser.options,
+    })
+}
+
+/// Returns the name of the field in JSON, as `protoc` does.
+pub(crate) fn json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut capitalize_next = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            json_name.push(c.to_ascii_uppercase());
+            capitalize_next = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+/// Returns the name of the message holding the entries of a map field.
+fn map_entry_name(field_name: &str) -> String {
+    let mut name = String::with_capacity(field_name.len() + 5);
+    let mut capitalize_next = true;
+    for c in field_name.chars() {
+        if c == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            name.push(c.to_ascii_uppercase());
+            capitalize_next = false;
+        } else {
+            name.push(c);
+        }
+    }
+    name.push_str("Entry");
+    name
+}
+
+/// Escapes bytes as `protoc` does for the default values of `bytes` fields.
+fn c_escape(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for &byte in bytes {
+        match byte {
+            b'\n' => escaped.push_str("\\n"),
+            b'\r' => escaped.push_str("\\r"),
+            b'\t' => escaped.push_str("\\t"),
+            b'"' => escaped.push_str("\\\""),
+            b'\'' => escaped.push_str("\\'"),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    escaped
+}
+
+/// Returns the scalar type named by a keyword.
+fn scalar_type(name: &str) -> Option<Type> {
+    Some(match name {
+        "double" => Type::Double,
+        "float" => Type::Float,
+        "int64" => Type::Int64,
+        "uint64" => Type::Uint64,
+        "int32" => Type::Int32,
+        "fixed64" => Type::Fixed64,
+        "fixed32" => Type::Fixed32,
+        "bool" => Type::Bool,
+        "string" => Type::String,
+        "bytes" => Type::Bytes,
+        "uint32" => Type::Uint32,
+        "sfixed32" => Type::Sfixed32,
+        "sfixed64" => Type::Sfixed64,
+        "sint32" => Type::Sint32,
+        "sint64" => Type::Sint64,
+        _ => return None,
+    })
+}
+
+fn join(scope: &str, name: &str) -> String {
+    format!("{}.{}", scope, name)
+}
+
+fn child_path(parent: &[i32], tag: i32, index: usize) -> Vec<i32> {
+    let mut path = parent.to_vec();
+    path.push(tag);
+    path.push(index as i32);
+    path
+}
+
+/// The message to which the nested types of a scope are added: groups and map entries.
+struct NestedTypes<'a> {
+    types: &'a mut Vec<DescriptorProto>,
+    /// The path prefix of the nested types, such as `[4]` for the messages of a file.
+    path: Vec<i32>,
+}
+
+struct Parser {
+    /// The name of the file, as in `FileDescriptorProto.name`.
+    name: String,
+    tokens: Vec<Token>,
+    pos: usize,
+    syntax: Syntax,
+    /// The fully-qualified name of the package, with a leading `.`, or the empty string.
+    package: String,
+    locations: Vec<Location>,
+    options: Vec<PendingOptions>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|token| &token.kind)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&TokenKind> {
+        self.tokens.get(self.pos + offset).map(|token| &token.kind)
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.peek() == Some(&TokenKind::Symbol(symbol))
+    }
+
+    fn is_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Some(TokenKind::Ident(name)) if name == ident)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        match self.tokens.get(self.pos).or_else(|| self.tokens.last()) {
+            Some(token) if self.pos < self.tokens.len() => {
+                ParseError::new(token.span.line, token.span.column, message)
+            }
+            Some(token) => ParseError::new(token.span.end_line, token.span.end_column, message),
+            None => ParseError::new(0, 0, message),
+        }
+    }
+
+    /// Returns an error describing the current token as unexpected.
+    fn expected(&self, expected: &str) -> ParseError {
+        if self.pos < self.tokens.len() {
+            self.error(format!("Expected {}.", expected))
+        } else {
+            self.error(format!("Unexpected end of file, expected {}.", expected))
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: char) -> bool {
+        if self.is_symbol(symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        if self.is_ident(ident) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes a symbol, returning the index of its token.
+    fn expect_symbol(&mut self, symbol: char) -> Result<usize, ParseError> {
+        if self.eat_symbol(symbol) {
+            Ok(self.pos - 1)
+        } else {
+            Err(self.expected(&format!("\"{}\"", symbol)))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        if self.eat_ident(keyword) {
+            Ok(())
+        } else {
+            Err(self.expected(&format!("\"{}\"", keyword)))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, ParseError> {
+        match self.peek() {
+            Some(TokenKind::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => Err(self.expected("identifier")),
+        }
+    }
+
+    /// Consumes a dotted name, such as `foo.bar`, or `.foo.bar` if `leading_dot` is set.
+    fn full_ident(&mut self, leading_dot: bool) -> Result<String, ParseError> {
+        let mut name = String::new();
+        if leading_dot && self.eat_symbol('.') {
+            name.push('.');
+        }
+        name.push_str(&self.expect_ident()?);
+        while self.is_symbol('.') {
+            self.pos += 1;
+            name.push('.');
+            name.push_str(&self.expect_ident()?);
+        }
+        Ok(name)
+    }
+
+    /// Consumes one or more adjacent string literals.
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let mut value = match self.peek() {
+            Some(TokenKind::Str(value)) => value.clone(),
+            _ => return Err(self.expected("string")),
+        };
+        self.pos += 1;
+        while let Some(TokenKind::Str(next)) = self.peek() {
+            value.extend_from_slice(next);
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn utf8_string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        let value = self.string()?;
+        String::from_utf8(value).map_err(|_| {
+            self.pos = start;
+            self.error("String is not valid UTF-8.")
+        })
+    }
+
+    /// Consumes an integer, with an optional `-` sign.
+    fn integer(&mut self) -> Result<i64, ParseError> {
+        let negative = self.eat_symbol('-');
+        match self.peek() {
+            Some(&TokenKind::Int(value)) if value <= i64::MAX as u64 => {
+                self.pos += 1;
+                Ok(if negative {
+                    -(value as i64)
+                } else {
+                    value as i64
+                })
+            }
+            Some(TokenKind::Int(_)) => Err(self.error("Integer out of range.")),
+            _ => Err(self.expected("integer")),
+        }
+    }
+
+    fn field_number(&mut self) -> Result<i32, ParseError> {
+        let start = self.pos;
+        let number = self.integer()?;
+        if number < 1 || number >= i64::from(MAX_FIELD_NUMBER) {
+            self.pos = start;
+            return Err(self.error("Field numbers must be in the range 1 to 536870911."));
+        }
+        Ok(number as i32)
+    }
+
+    /// Records the location of a declaration, from the token at `start` to the previous token,
+    /// with the comments preceding it and the comment following its token at `end`, which ends
+    /// the declaration, such as `;` or `{`.
+    fn location(&mut self, path: Vec<i32>, start: usize, end: usize) {
+        let slot = self.reserve_location();
+        self.fill_location(slot, path, start, end);
+    }
+
+    /// Reserves the location of a declaration containing others, whose locations follow it as
+    /// with `protoc`, returning its index.
+    fn reserve_location(&mut self) -> usize {
+        self.locations.push(Location::default());
+        self.locations.len() - 1
+    }
+
+    /// Records the location of a declaration at a reserved index, as in `location`.
+    fn fill_location(&mut self, slot: usize, path: Vec<i32>, start: usize, end: usize) {
+        let first = &self.tokens[start];
+        let last = &self.tokens[self.pos - 1];
+        let mut span = vec![first.span.line as i32, first.span.column as i32];
+        if last.span.end_line != first.span.line {
+            span.push(last.span.end_line as i32);
+        }
+        span.push(last.span.end_column as i32);
+        let trailing = self
+            .tokens
+            .get(end + 1)
+            .and_then(|token| token.comments.trailing.clone());
+        self.locations[slot] = Location {
+            path,
+            span,
+            leading_comments: first.comments.leading.clone(),
+            trailing_comments: trailing,
+            leading_detached_comments: first.comments.detached.clone(),
+        };
+    }
+
+    fn file(&mut self) -> Result<FileDescriptorProto, ParseError> {
+        let mut file = FileDescriptorProto::default();
+        if self.is_ident("syntax") {
+            let syntax_start = self.pos;
+            self.pos += 1;
+            self.expect_symbol('=')?;
+            let start = self.pos;
+            match &*self.utf8_string()? {
+                "proto2" => {}
+                "proto3" => {
+                    self.syntax = Syntax::Proto3;
+                    file.syntax = Some("proto3".to_string());
+                }
+                syntax => {
+                    self.pos = start;
+                    return Err(self.error(format!(
+                        "Unrecognized syntax identifier \"{}\". This parser only recognizes \
+                         \"proto2\" and \"proto3\".",
+                        syntax
+                    )));
+                }
+            }
+            let end = self.expect_symbol(';')?;
+            self.location(vec![12], syntax_start, end);
+        } else if self.is_ident("edition") {
+            return Err(self.error("Editions are not supported, use protoc instead."));
+        }
+
+        let mut file_options = Vec::new();
+        while self.pos < self.tokens.len() {
+            if self.eat_symbol(';') {
+                continue;
+            }
+            let start = self.pos;
+            if self.eat_ident("import") {
+                let index = file.dependency.len() as i32;
+                if self.eat_ident("public") {
+                    file.public_dependency.push(index);
+                } else if self.eat_ident("weak") {
+                    file.weak_dependency.push(index);
+                }
+                file.dependency.push(self.utf8_string()?);
+                let end = self.expect_symbol(';')?;
+                self.location(vec![3, index], start, end);
+            } else if self.is_ident("package") {
+                if file.package.is_some() {
+                    return Err(self.error("Multiple package definitions."));
+                }
+                self.pos += 1;
+                let package = self.full_ident(false)?;
+                self.package = format!(".{}", package);
+                file.package = Some(package);
+                let end = self.expect_symbol(';')?;
+                self.location(vec![2], start, end);
+            } else if self.is_ident("option") {
+                file_options.push(self.option_statement()?);
+            } else if self.is_ident("message") {
+                let path = child_path(&[], 4, file.message_type.len());
+                let scope = self.package.clone();
+                let message = self.message(&scope, path)?;
+                file.message_type.push(message);
+            } else if self.is_ident("enum") {
+                let path = child_path(&[], 5, file.enum_type.len());
+                let scope = self.package.clone();
+                let enumeration = self.enumeration(&scope, path)?;
+                file.enum_type.push(enumeration);
+            } else if self.is_ident("service") {
+                let path = child_path(&[], 6, file.service.len());
+                let service = self.service(path)?;
+                file.service.push(service);
+            } else if self.is_ident("extend") {
+                let scope = self.package.clone();
+                let mut nested = NestedTypes {
+                    types: &mut file.message_type,
+                    path: vec![4],
+                };
+                self.extend(&scope, &mut file.extension, &[7], &mut nested)?;
+            } else {
+                return Err(self.expected("top-level statement (e.g. \"message\")"));
+            }
+        }
+
+        if !file_options.is_empty() {
+            self.options.push(PendingOptions {
+                kind: Kind::File,
+                path: Vec::new(),
+                index_name: Some(self.name.clone()),
+                scope: self.package.clone(),
+                options: file_options,
+            });
+        }
+        Ok(file)
+    }
+
+    /// Parses an `option` statement, up to and including its `;`.
+    fn option_statement(&mut self) -> Result<OptionStatement, ParseError> {
+        self.expect_keyword("option")?;
+        let statement = self.option_assignment()?;
+        self.expect_symbol(';')?;
+        Ok(statement)
+    }
+
+    /// Parses an option assignment, such as `(my.ext).field = 1`.
+    fn option_assignment(&mut self) -> Result<OptionStatement, ParseError> {
+        let token = &self.tokens[self.pos.min(self.tokens.len() - 1)];
+        let (line, column) = (token.span.line, token.span.column);
+        let mut name = Vec::new();
+        loop {
+            if self.eat_symbol('(') {
+                let extension = self.full_ident(true)?;
+                self.expect_symbol(')')?;
+                name.push(OptionNamePart {
+                    name: extension,
+                    extension: true,
+                });
+            } else {
+                name.push(OptionNamePart {
+                    name: self.expect_ident()?,
+                    extension: false,
+                });
+            }
+            if !self.eat_symbol('.') {
+                break;
+            }
+        }
+        self.expect_symbol('=')?;
+        let value = self.option_value()?;
+        Ok(OptionStatement {
+            name,
+            value,
+            line,
+            column,
+        })
+    }
+
+    /// Parses the value of an option: a scalar, or a message in the text format.
+    fn option_value(&mut self) -> Result<OptionValue, ParseError> {
+        if self.eat_symbol('{') {
+            return Ok(OptionValue::Aggregate(self.aggregate('}')?));
+        }
+        self.scalar_value()
+    }
+
+    fn scalar_value(&mut self) -> Result<OptionValue, ParseError> {
+        let negative = self.eat_symbol('-');
+        let value = match self.peek() {
+            Some(&TokenKind::Int(value)) => OptionValue::Int { negative, value },
+            Some(&TokenKind::Float(value)) => {
+                OptionValue::Float(if negative { -value } else { value })
+            }
+            Some(TokenKind::Ident(ident)) if negative => match &*ident.to_ascii_lowercase() {
+                "inf" | "infinity" => OptionValue::Float(f64::NEG_INFINITY),
+                "nan" => OptionValue::Float(f64::NAN),
+                _ => return Err(self.expected("number")),
+            },
+            Some(TokenKind::Ident(ident)) => OptionValue::Ident(ident.clone()),
+            Some(TokenKind::Str(_)) if !negative => return Ok(OptionValue::Str(self.string()?)),
+            _ => return Err(self.expected("option value")),
+        };
+        self.pos += 1;
+        Ok(value)
+    }
+
+    /// Parses the fields of a message in the text format, up to and including the `close`
+    /// delimiter.
+    fn aggregate(&mut self, close: char) -> Result<Vec<(OptionNamePart, OptionValue)>, ParseError> {
+        let mut fields = Vec::new();
+        while !self.eat_symbol(close) {
+            let name = if self.eat_symbol('[') {
+                let mut name = self.full_ident(false)?;
+                // Type URLs of `Any` expansions, such as `[type.googleapis.com/pkg.Message]`.
+                while self.eat_symbol('/') {
+                    name.push('/');
+                    name.push_str(&self.full_ident(false)?);
+                }
+                self.expect_symbol(']')?;
+                OptionNamePart {
+                    name,
+                    extension: true,
+                }
+            } else {
+                OptionNamePart {
+                    name: self.expect_ident()?,
+                    extension: false,
+                }
+            };
+            let colon = self.eat_symbol(':');
+            let value = if self.eat_symbol('{') {
+                OptionValue::Aggregate(self.aggregate('}')?)
+            } else if self.eat_symbol('<') {
+                OptionValue::Aggregate(self.aggregate('>')?)
+            } else if colon && self.eat_symbol('[') {
+                let mut values = Vec::new();
+                while !self.eat_symbol(']') {
+                    if !values.is_empty() {
+                        self.expect_symbol(',')?;
+                    }
+                    values.push(if self.eat_symbol('{') {
+                        OptionValue::Aggregate(self.aggregate('}')?)
+                    } else if self.eat_symbol('<') {
+                        OptionValue::Aggregate(self.aggregate('>')?)
+                    } else {
+                        self.scalar_value()?
+                    });
+                }
+                OptionValue::List(values)
+            } else if colon {
+                self.scalar_value()?
+            } else {
+                return Err(self.expected("\":\""));
+            };
+            fields.push((name, value));
+            if !self.eat_symbol(',') {
+                self.eat_symbol(';');
+            }
+        }
+        Ok(fields)
+    }
+
+    /// Parses a list of options in brackets, such as `[deprecated = true]`.
+    fn bracketed_options(&mut self) -> Result<Vec<OptionStatement>, ParseError> {
+        let mut options = Vec::new();
+        if self.eat_symbol('[') {
+            loop {
+                options.push(self.option_assignment()?);
+                if !self.eat_symbol(',') {
+                    break;
+                }
+            }
+            self.expect_symbol(']')?;
+        }
+        Ok(options)
+    }
+
+    fn push_options(
+        &mut self,
+        kind: Kind,
+        path: Vec<i32>,
+        index_name: Option<String>,
+        scope: String,
+        options: Vec<OptionStatement>,
+    ) {
+        if !options.is_empty() {
+            self.options.push(PendingOptions {
+                kind,
+                path,
+                index_name,
+                scope,
+                options,
+            });
+        }
+    }
+
+    fn message(&mut self, scope: &str, path: Vec<i32>) -> Result<DescriptorProto, ParseError> {
+        let start = self.pos;
+        let slot = self.reserve_location();
+        self.expect_keyword("message")?;
+        let name = self.expect_ident()?;
+        let open = self.expect_symbol('{')?;
+        let mut message = DescriptorProto {
+            name: Some(name.clone()),
+            ..DescriptorProto::default()
+        };
+        self.message_body(&mut message, &join(scope, &name), &path)?;
+        self.fill_location(slot, path, start, open);
+        Ok(message)
+    }
+
+    /// Parses the body of a message, up to and including its closing `}`.
+    fn message_body(
+        &mut self,
+        message: &mut DescriptorProto,
+        fq_name: &str,
+        path: &[i32],
+    ) -> Result<(), ParseError> {
+        let mut options = Vec::new();
+        // The fields which are `optional` in proto3, which have synthetic oneofs.
+        let mut proto3_optional = Vec::new();
+        while !self.eat_symbol('}') {
+            if self.pos >= self.tokens.len() {
+                return Err(self.expected("\"}\""));
+            }
+            if self.eat_symbol(';') {
+                continue;
+            }
+            let keyword = match self.peek() {
+                Some(TokenKind::Ident(ident)) => ident.clone(),
+                _ => return Err(self.expected("field, message or enum definition")),
+            };
+            // As with `protoc`, keywords can not be used as the type of a field.
+            match &*keyword {
+                "message" => {
+                    let nested_path = child_path(path, 3, message.nested_type.len());
+                    let nested = self.message(fq_name, nested_path)?;
+                    message.nested_type.push(nested);
+                }
+                "enum" => {
+                    let enum_path = child_path(path, 4, message.enum_type.len());
+                    let enumeration = self.enumeration(fq_name, enum_path)?;
+                    message.enum_type.push(enumeration);
+                }
+                "extend" => {
+                    let mut nested = NestedTypes {
+                        types: &mut message.nested_type,
+                        path: [path, &[3]].concat(),
+                    };
+                    let extension_path = [path, &[6]].concat();
+                    self.extend(
+                        fq_name,
+                        &mut message.extension,
+                        &extension_path,
+                        &mut nested,
+                    )?;
+                }
+                "extensions" => {
+                    let start = self.pos;
+                    self.pos += 1;
+                    loop {
+                        let (start, end) = self.range(MAX_FIELD_NUMBER - 1)?;
+                        message.extension_range.push(ExtensionRange {
+                            start: Some(start),
+                            end: Some(end + 1),
+                            options: None,
+                        });
+                        if !self.eat_symbol(',') {
+                            break;
+                        }
+                    }
+                    // Extension range options have no effect on the generated code.
+                    self.bracketed_options()?;
+                    let end = self.expect_symbol(';')?;
+                    self.location([path, &[5]].concat(), start, end);
+                }
+                "reserved" => {
+                    let start = self.pos;
+                    self.pos += 1;
+                    let (ranges, names) = self.reserved(MAX_FIELD_NUMBER - 1)?;
+                    let tag = if names.is_empty() { 9 } else { 10 };
+                    self.location([path, &[tag]].concat(), start, self.pos - 1);
+                    message
+                        .reserved_range
+                        .extend(ranges.into_iter().map(|(start, end)| ReservedRange {
+                            start: Some(start),
+                            end: Some(end + 1),
+                        }));
+                    message.reserved_name.extend(names);
+                }
+                "option" => {
+                    options.push(self.option_statement()?);
+                }
+                "oneof" => {
+                    self.oneof(message, fq_name, path)?;
+                }
+                _ => {
+                    let field_path = child_path(path, 2, message.field.len());
+                    let mut nested = NestedTypes {
+                        types: &mut message.nested_type,
+                        path: [path, &[3]].concat(),
+                    };
+                    let field = self.field(fq_name, field_path, &mut nested, None, None)?;
+                    if field.proto3_optional == Some(true) {
+                        proto3_optional.push(message.field.len());
+                    }
+                    message.field.push(field);
+                }
+            }
+        }
+
+        // Synthetic oneofs follow the declared oneofs, in the order of their fields.
+        for index in proto3_optional {
+            let mut name = format!("_{}", message.field[index].name());
+            while message.field.iter().any(|field| field.name() == name)
+                || message
+                    .nested_type
+                    .iter()
+                    .any(|nested| nested.name() == name)
+                || message.enum_type.iter().any(|nested| nested.name() == name)
+                || message.oneof_decl.iter().any(|oneof| oneof.name() == name)
+            {
+                name.insert(0, 'X');
+            }
+            message.field[index].oneof_index = Some(message.oneof_decl.len() as i32);
+            message.oneof_decl.push(OneofDescriptorProto {
+                name: Some(name),
+                options: None,
+            });
+        }
+
+        let scope = &fq_name[..fq_name.rfind('.').unwrap()];
+        self.push_options(
+            Kind::Message,
+            path.to_vec(),
+            Some(fq_name.to_string()),
+            scope.to_string(),
+            options,
+        );
+        Ok(())
+    }
+
+    fn oneof(
+        &mut self,
+        message: &mut DescriptorProto,
+        fq_message_name: &str,
+        message_path: &[i32],
+    ) -> Result<(), ParseError> {
+        let start = self.pos;
+        let slot = self.reserve_location();
+        self.expect_keyword("oneof")?;
+        let name = self.expect_ident()?;
+        let open = self.expect_symbol('{')?;
+        let index = message.oneof_decl.len();
+        let oneof_path = child_path(message_path, 8, index);
+        message.oneof_decl.push(OneofDescriptorProto {
+            name: Some(name.clone()),
+            options: None,
+        });
+
+        let mut options = Vec::new();
+        while !self.eat_symbol('}') {
+            if self.pos >= self.tokens.len() {
+                return Err(self.expected("\"}\""));
+            }
+            if self.eat_symbol(';') {
+                continue;
+            }
+            if self.is_ident("option") {
+                options.push(self.option_statement()?);
+                continue;
+            }
+            let field_path = child_path(message_path, 2, message.field.len());
+            let mut nested = NestedTypes {
+                types: &mut message.nested_type,
+                path: [message_path, &[3]].concat(),
+            };
+            let field = self.field(
+                fq_message_name,
+                field_path,
+                &mut nested,
+                Some(index as i32),
+                None,
+            )?;
+            message.field.push(field);
+        }
+        self.fill_location(slot, oneof_path.clone(), start, open);
+        self.push_options(
+            Kind::Oneof,
+            oneof_path,
+            Some(join(fq_message_name, &name)),
+            fq_message_name.to_string(),
+            options,
+        );
+        Ok(())
+    }
+
+    /// Parses a field, a group or a map field, declared in a message or an `extend` block.
+    ///
+    /// `scope` is the fully-qualified name of the message declaring the field, or the scope of
+    /// the `extend` block.
+    fn field(
+        &mut self,
+        scope: &str,
+        path: Vec<i32>,
+        nested: &mut NestedTypes<'_>,
+        oneof_index: Option<i32>,
+        extendee: Option<&str>,
+    ) -> Result<FieldDescriptorProto, ParseError> {
+        let start = self.pos;
+        let slot = self.reserve_location();
+        let label = if oneof_index.is_some() {
+            None
+        } else if self.eat_ident("optional") {
+            Some(Label::Optional)
+        } else if self.eat_ident("required") {
+            if self.syntax == Syntax::Proto3 {
+                return Err(self.error("Required fields are not allowed in proto3."));
+            }
+            Some(Label::Required)
+        } else if self.eat_ident("repeated") {
+            Some(Label::Repeated)
+        } else {
+            None
+        };
+
+        let mut field = FieldDescriptorProto {
+            label: Some(label.unwrap_or(Label::Optional) as i32),
+            oneof_index,
+            extendee: extendee.map(str::to_string),
+            ..FieldDescriptorProto::default()
+        };
+        if label == Some(Label::Optional) && self.syntax == Syntax::Proto3 && extendee.is_none() {
+            field.proto3_optional = Some(true);
+        }
+
+        let is_map = self.is_ident("map") && self.peek_at(1) == Some(&TokenKind::Symbol('<'));
+        if label.is_none() && oneof_index.is_none() && self.syntax == Syntax::Proto2 && !is_map {
+            return Err(self.expected("\"required\", \"optional\", or \"repeated\""));
+        }
+
+        let mut map_entry = None;
+        let mut group = None;
+        if is_map {
+            if label.is_some() {
+                return Err(self.error("Field labels are not allowed on map fields."));
+            }
+            self.pos += 2;
+            let key = self.field_type()?;
+            self.expect_symbol(',')?;
+            let value = self.field_type()?;
+            self.expect_symbol('>')?;
+            field.label = Some(Label::Repeated as i32);
+            field.r#type = Some(Type::Message as i32);
+            map_entry = Some((key, value));
+        } else if self.is_ident("group") && matches!(self.peek_at(1), Some(TokenKind::Ident(_))) {
+            self.pos += 1;
+            field.r#type = Some(Type::Group as i32);
+            group = Some(());
+        } else {
+            let (ty, type_name) = self.field_type()?;
+            field.r#type = ty.map(|ty| ty as i32);
+            field.type_name = type_name;
+        }
+
+        let name = self.expect_ident()?;
+        self.expect_symbol('=')?;
+        field.number = Some(self.field_number()?);
+
+        let mut options = Vec::new();
+        for option in self.bracketed_options()? {
+            match (&*option.name, &option.value) {
+                ([part], value) if !part.extension && part.name == "default" => {
+                    if self.syntax == Syntax::Proto3 {
+                        return Err(
+                            self.error("Explicit default values are not allowed in proto3.")
+                        );
+                    }
+                    if label == Some(Label::Repeated) || map_entry.is_some() {
+                        return Err(self.error("Repeated fields can't have default values."));
+                    }
+                    field.default_value = Some(self.default_value(&field, value)?);
+                }
+                ([part], OptionValue::Str(json_name))
+                    if !part.extension && part.name == "json_name" =>
+                {
+                    field.json_name = Some(String::from_utf8_lossy(json_name).into_owned());
+                }
+                _ => options.push(option),
+            }
+        }
+
+        let field_name = if let Some(()) = group {
+            if !name.starts_with(|c: char| c.is_ascii_uppercase()) {
+                return Err(self.error("Group names must start with a capital letter."));
+            }
+            let mut group_path = nested.path.clone();
+            group_path.push(nested.types.len() as i32);
+            field.type_name = Some(join(scope, &name));
+            let open = self.expect_symbol('{')?;
+            let group_slot = self.reserve_location();
+            let mut message = DescriptorProto {
+                name: Some(name.clone()),
+                ..DescriptorProto::default()
+            };
+            self.message_body(&mut message, &join(scope, &name), &group_path)?;
+            nested.types.push(message);
+            // As with `protoc`, the comments of a group are those of its message.
+            self.fill_location(group_slot, group_path, start, open);
+            name.to_ascii_lowercase()
+        } else {
+            self.expect_symbol(';')?;
+            name
+        };
+
+        if let Some(((key_type, key_type_name), (value_type, value_type_name))) = map_entry {
+            let entry_name = map_entry_name(&field_name);
+            let entry_field =
+                |name: &str, number, ty: Option<Type>, type_name| FieldDescriptorProto {
+                    name: Some(name.to_string()),
+                    number: Some(number),
+                    label: Some(Label::Optional as i32),
+                    r#type: ty.map(|ty| ty as i32),
+                    type_name,
+                    json_name: Some(name.to_string()),
+                    ..FieldDescriptorProto::default()
+                };
+            nested.types.push(DescriptorProto {
+                name: Some(entry_name.clone()),
+                field: vec![
+                    entry_field("key", 1, key_type, key_type_name),
+                    entry_field("value", 2, value_type, value_type_name),
+                ],
+                options: Some(MessageOptions {
+                    map_entry: Some(true),
+                    ..MessageOptions::default()
+                }),
+                ..DescriptorProto::default()
+            });
+            field.type_name = Some(join(scope, &entry_name));
+        }
+
+        if field.json_name.is_none() {
+            field.json_name = Some(json_name(&field_name));
+        }
+        field.name = Some(field_name.clone());
+        self.fill_location(slot, path.clone(), start, self.pos - 1);
+        if group.is_some() {
+            let location = &mut self.locations[slot];
+            location.leading_comments = None;
+            location.trailing_comments = None;
+            location.leading_detached_comments.clear();
+        }
+        // Extensions are not indexed: the index only holds the options of generated items.
+        let index_name = match extendee {
+            None => Some(join(scope, &field_name)),
+            Some(_) => None,
+        };
+        self.push_options(Kind::Field, path, index_name, scope.to_string(), options);
+        Ok(field)
+    }
+
+    /// Parses the type of a field, returning the scalar type, or the name of a message or enum.
+    fn field_type(&mut self) -> Result<(Option<Type>, Option<String>), ParseError> {
+        if let Some(TokenKind::Ident(ident)) = self.peek() {
+            if let Some(ty) = scalar_type(ident) {
+                self.pos += 1;
+                return Ok((Some(ty), None));
+            }
+        }
+        Ok((None, Some(self.full_ident(true)?)))
+    }
+
+    /// Formats the default value of a field as `protoc` does.
+    fn default_value(
+        &self,
+        field: &FieldDescriptorProto,
+        value: &OptionValue,
+    ) -> Result<String, ParseError> {
+        let invalid = || self.error("Invalid default value.");
+        let ty = field.r#type.and_then(Type::from_i32);
+        Ok(match (ty, value) {
+            (Some(Type::String), OptionValue::Str(value)) => {
+                String::from_utf8(value.clone()).map_err(|_| invalid())?
+            }
+            (Some(Type::Bytes), OptionValue::Str(value)) => c_escape(value),
+            (Some(Type::Bool), OptionValue::Ident(value))
+                if value == "true" || value == "false" =>
+            {
+                value.clone()
+            }
+            (Some(Type::Float), _) | (Some(Type::Double), _) => match value {
+                OptionValue::Int { negative, value } => {
+                    format!("{}{}", if *negative { "-" } else { "" }, value)
+                }
+                OptionValue::Float(value) if value.is_nan() => "nan".to_string(),
+                OptionValue::Float(value) if value.is_infinite() => {
+                    if *value > 0.0 { "inf" } else { "-inf" }.to_string()
+                }
+                OptionValue::Float(value) => format!("{}", value),
+                OptionValue::Ident(value) if value == "inf" || value == "nan" => value.clone(),
+                _ => return Err(invalid()),
+            },
+            (
+                Some(Type::Int32) | Some(Type::Sint32) | Some(Type::Sfixed32) | Some(Type::Int64)
+                | Some(Type::Sint64) | Some(Type::Sfixed64),
+                OptionValue::Int { negative, value },
+            ) => format!("{}{}", if *negative { "-" } else { "" }, value),
+            (
+                Some(Type::Uint32) | Some(Type::Fixed32) | Some(Type::Uint64) | Some(Type::Fixed64),
+                OptionValue::Int {
+                    negative: false,
+                    value,
+                },
+            ) => value.to_string(),
+            // Named types are resolved later, only enums can have default values.
+            (None, OptionValue::Ident(value)) => value.clone(),
+            _ => return Err(invalid()),
+        })
+    }
+
+    /// Parses the fields of an `extend` block.
+    fn extend(
+        &mut self,
+        scope: &str,
+        extensions: &mut Vec<FieldDescriptorProto>,
+        path: &[i32],
+        nested: &mut NestedTypes<'_>,
+    ) -> Result<(), ParseError> {
+        self.expect_keyword("extend")?;
+        let extendee = self.full_ident(true)?;
+        self.expect_symbol('{')?;
+        while !self.eat_symbol('}') {
+            if self.pos >= self.tokens.len() {
+                return Err(self.expected("\"}\""));
+            }
+            if self.eat_symbol(';') {
+                continue;
+            }
+            let mut field_path = path.to_vec();
+            field_path.push(extensions.len() as i32);
+            let field = self.field(scope, field_path, nested, None, Some(&extendee))?;
+            extensions.push(field);
+        }
+        Ok(())
+    }
+
+    /// Parses a range of numbers, such as `1`, `2 to 5` or `6 to max`.
+    fn range(&mut self, max: i32) -> Result<(i32, i32), ParseError> {
+        let start = self.integer()?;
+        let end = if self.eat_ident("to") {
+            if self.eat_ident("max") {
+                i64::from(max)
+            } else {
+                self.integer()?
+            }
+        } else {
+            start
+        };
+        if start > end || end > i64::from(max) || start < i64::from(i32::MIN) {
+            return Err(self.error("Invalid range."));
+        }
+        Ok((start as i32, end as i32))
+    }
+
+    /// Parses the ranges or names of a `reserved` statement, up to and including its `;`.
+    fn reserved(&mut self, max: i32) -> Result<Reserved, ParseError> {
+        let mut ranges = Vec::new();
+        let mut names = Vec::new();
+        loop {
+            if let Some(TokenKind::Str(_)) = self.peek() {
+                names.push(self.utf8_string()?);
+            } else {
+                ranges.push(self.range(max)?);
+            }
+            if !self.eat_symbol(',') {
+                break;
+            }
+        }
+        self.expect_symbol(';')?;
+        Ok((ranges, names))
+    }
+
+    fn enumeration(
+        &mut self,
+        scope: &str,
+        path: Vec<i32>,
+    ) -> Result<EnumDescriptorProto, ParseError> {
+        let start = self.pos;
+        let slot = self.reserve_location();
+        self.expect_keyword("enum")?;
+        let name = self.expect_ident()?;
+        let fq_name = join(scope, &name);
+        let open = self.expect_symbol('{')?;
+        let mut enumeration = EnumDescriptorProto {
+            name: Some(name),
+            ..EnumDescriptorProto::default()
+        };
+        let mut options = Vec::new();
+        while !self.eat_symbol('}') {
+            if self.pos >= self.tokens.len() {
+                return Err(self.expected("\"}\""));
+            }
+            if self.eat_symbol(';') {
+                continue;
+            }
+            if self.is_ident("option") {
+                options.push(self.option_statement()?);
+            } else if self.is_ident("reserved") {
+                let start = self.pos;
+                self.pos += 1;
+                let (ranges, names) = self.reserved(i32::MAX)?;
+                let tag = if names.is_empty() { 4 } else { 5 };
+                self.location([&*path, &[tag]].concat(), start, self.pos - 1);
+                enumeration
+                    .reserved_range
+                    .extend(ranges.into_iter().map(|(start, end)| EnumReservedRange {
+                        start: Some(start),
+                        end: Some(end),
+                    }));
+                enumeration.reserved_name.extend(names);
+            } else {
+                let value_start = self.pos;
+                let value_name = self.expect_ident()?;
+                self.expect_symbol('=')?;
+                let number_start = self.pos;
+                let number = self.integer()?;
+                if number < i64::from(i32::MIN) || number > i64::from(i32::MAX) {
+                    self.pos = number_start;
+                    return Err(self.error("Integer out of range."));
+                }
+                let value_options = self.bracketed_options()?;
+                let end = self.expect_symbol(';')?;
+                let value_path = child_path(&path, 2, enumeration.value.len());
+                self.location(value_path.clone(), value_start, end);
+                self.push_options(
+                    Kind::EnumValue,
+                    value_path,
+                    Some(join(&fq_name, &value_name)),
+                    scope.to_string(),
+                    value_options,
+                );
+                enumeration.value.push(EnumValueDescriptorProto {
+                    name: Some(value_name),
+                    number: Some(number as i32),
+                    options: None,
+                });
+            }
+        }
+        if self.syntax == Syntax::Proto3
+            && enumeration.value.first().map(|value| value.number()) != Some(0)
+        {
+            return Err(ParseError::new(
+                self.tokens[start].span.line,
+                self.tokens[start].span.column,
+                "The first enum value must be zero in proto3.",
+            ));
+        }
+        self.fill_location(slot, path.clone(), start, open);
+        self.push_options(Kind::Enum, path, Some(fq_name), scope.to_string(), options);
+        Ok(enumeration)
+    }
+
+    fn service(&mut self, path: Vec<i32>) -> Result<ServiceDescriptorProto, ParseError> {
+        let start = self.pos;
+        let slot = self.reserve_location();
+        self.expect_keyword("service")?;
+        let name = self.expect_ident()?;
+        let fq_name = join(&self.package, &name);
+        let open = self.expect_symbol('{')?;
+        let mut service = ServiceDescriptorProto {
+            name: Some(name),
+            ..ServiceDescriptorProto::default()
+        };
+        let mut options = Vec::new();
+        while !self.eat_symbol('}') {
+            if self.pos >= self.tokens.len() {
+                return Err(self.expected("\"}\""));
+            }
+            if self.eat_symbol(';') {
+                continue;
+            }
+            if self.is_ident("option") {
+                options.push(self.option_statement()?);
+                continue;
+            }
+            let method_start = self.pos;
+            self.expect_keyword("rpc")?;
+            let method_name = self.expect_ident()?;
+            let method_path = child_path(&path, 2, service.method.len());
+            let mut method = MethodDescriptorProto {
+                name: Some(method_name.clone()),
+                ..MethodDescriptorProto::default()
+            };
+            self.expect_symbol('(')?;
+            if self.is_ident("stream")
+                && !matches!(
+                    self.peek_at(1),
+                    Some(TokenKind::Symbol(')')) | Some(TokenKind::Symbol('.'))
+                )
+            {
+                self.pos += 1;
+                method.client_streaming = Some(true);
+            }
+            method.input_type = Some(self.full_ident(true)?);
+            self.expect_symbol(')')?;
+            self.expect_keyword("returns")?;
+            self.expect_symbol('(')?;
+            if self.is_ident("stream")
+                && !matches!(
+                    self.peek_at(1),
+                    Some(TokenKind::Symbol(')')) | Some(TokenKind::Symbol('.'))
+                )
+            {
+                self.pos += 1;
+                method.server_streaming = Some(true);
+            }
+            method.output_type = Some(self.full_ident(true)?);
+            self.expect_symbol(')')?;
+
+            let mut method_options = Vec::new();
+            let end = if self.is_symbol('{') {
+                let end = self.pos;
+                self.pos += 1;
+                while !self.eat_symbol('}') {
+                    if self.pos >= self.tokens.len() {
+                        return Err(self.expected("\"}\""));
+                    }
+                    if !self.eat_symbol(';') {
+                        method_options.push(self.option_statement()?);
+                    }
+                }
+                self.eat_symbol(';');
+                end
+            } else {
+                self.expect_symbol(';')?
+            };
+            self.location(method_path.clone(), method_start, end);
+            self.push_options(
+                Kind::Method,
+                method_path,
+                Some(join(&fq_name, &method_name)),
+                fq_name.clone(),
+                method_options,
+            );
+            service.method.push(method);
+        }
+        self.fill_location(slot, path.clone(), start, open);
+        let scope = self.package.clone();
+        self.push_options(Kind::Service, path, Some(fq_name), scope, options);
+        Ok(service)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let file = parse(
+            "test.proto",
+            r#"
+            syntax = "proto3";
+            package test;
+
+            message Outer {
+                map<string, Inner> inner_by_name = 1;
+                optional int32 maybe = 2;
+                oneof choice {
+                    string text = 3;
+                    bytes data = 4;
+                }
+                repeated .test.Outer children = 5 [json_name = "kids"];
+                reserved 6, 8 to 10, 20 to max;
+                reserved "old";
+            }
+            "#,
+        )
+        .unwrap();
+        let descriptor = file.descriptor;
+        assert_eq!(descriptor.syntax(), "proto3");
+        assert_eq!(descriptor.package(), "test");
+
+        let outer = &descriptor.message_type[0];
+        let names: Vec<_> = outer.field.iter().map(|field| field.name()).collect();
+        assert_eq!(
+            names,
+            ["inner_by_name", "maybe", "text", "data", "children"]
+        );
+
+        let map = &outer.field[0];
+        assert_eq!(map.label(), Label::Repeated);
+        assert_eq!(map.r#type(), Type::Message);
+        assert_eq!(map.type_name(), ".test.Outer.InnerByNameEntry");
+        assert_eq!(map.json_name(), "innerByName");
+        let entry = &outer.nested_type[0];
+        assert_eq!(entry.name(), "InnerByNameEntry");
+        assert!(entry.options.as_ref().unwrap().map_entry());
+        assert_eq!(entry.field[0].r#type(), Type::String);
+        // Message and enum types are resolved once all the files are parsed.
+        assert_eq!(entry.field[1].type_name(), "Inner");
+        assert_eq!(entry.field[1].r#type, None);
+
+        // Fields which are `optional` in proto3 have synthetic oneofs, after the declared ones.
+        let oneofs: Vec<_> = outer.oneof_decl.iter().map(|oneof| oneof.name()).collect();
+        assert_eq!(oneofs, ["choice", "_maybe"]);
+        assert_eq!(outer.field[1].proto3_optional, Some(true));
+        assert_eq!(outer.field[1].oneof_index, Some(1));
+        assert_eq!(outer.field[2].oneof_index, Some(0));
+        assert_eq!(outer.field[3].oneof_index, Some(0));
+
+        assert_eq!(outer.field[4].json_name(), "kids");
+        assert_eq!(outer.field[4].type_name(), ".test.Outer");
+
+        let reserved: Vec<_> = outer
+            .reserved_range
+            .iter()
+            .map(|range| (range.start(), range.end()))
+            .collect();
+        assert_eq!(reserved, [(6, 7), (8, 11), (20, MAX_FIELD_NUMBER)]);
+        assert_eq!(outer.reserved_name, ["old"]);
+    }
+
+    #[test]
+    fn proto2() {
+        let file = parse(
+            "test.proto",
+            r#"
+            message Message {
+                optional bytes data = 1 [default = "\001\"\n"];
+                optional double ratio = 2 [default = -inf];
+                required Kind kind = 3 [default = KIND_A];
+                repeated group Item = 4 {
+                    optional int32 id = 1;
+                }
+                extensions 100 to 199;
+            }
+            extend Message {
+                optional string note = 100;
+            }
+            "#,
+        )
+        .unwrap();
+        let descriptor = file.descriptor;
+        assert_eq!(descriptor.syntax, None);
+
+        let message = &descriptor.message_type[0];
+        assert_eq!(message.field[0].default_value(), "\\001\\\"\\n");
+        assert_eq!(message.field[1].default_value(), "-inf");
+        assert_eq!(message.field[2].default_value(), "KIND_A");
+        assert_eq!(message.field[2].label(), Label::Required);
+
+        let group = &message.field[3];
+        assert_eq!(group.name(), "item");
+        assert_eq!(group.r#type(), Type::Group);
+        assert_eq!(group.type_name(), ".Message.Item");
+        assert_eq!(message.nested_type[0].name(), "Item");
+
+        assert_eq!(message.extension_range[0].start(), 100);
+        assert_eq!(message.extension_range[0].end(), 200);
+        assert_eq!(descriptor.extension[0].extendee(), "Message");
+
+        assert!(parse("test.proto", "message Message { int32 id = 1; }").is_err());
+    }
+
+    #[test]
+    fn comments() {
+        let file = parse(
+            "test.proto",
+            r#"syntax = "proto3";
+
+            // Detached.
+
+            // Leading.
+            message Message { // Trailing.
+                // A field.
+                int32 id = 1;
+            }
+            "#,
+        )
+        .unwrap();
+        let locations = file.descriptor.source_code_info.unwrap().location;
+        let message = locations.iter().find(|location| location.path == [4, 0]);
+        let message = message.unwrap();
+        assert_eq!(message.leading_detached_comments, [" Detached.\n"]);
+        assert_eq!(message.leading_comments(), " Leading.\n");
+        assert_eq!(message.trailing_comments(), " Trailing.\n");
+        let field = locations
+            .iter()
+            .find(|location| location.path == [4, 0, 2, 0]);
+        assert_eq!(field.unwrap().leading_comments(), " A field.\n");
+    }
+
+    #[test]
+    fn options() {
+        let file = parse(
+            "test.proto",
+            r#"
+            syntax = "proto3";
+            package test;
+            option java_package = "com.example";
+            message Message {
+                string name = 1 [(validate.rules).string = {min_len: 1, in: ["a", "b"]}];
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(file.options.len(), 2);
+
+        let field = &file.options[0];
+        assert_eq!(field.kind, Kind::Field);
+        assert_eq!(field.path, [4, 0, 2, 0]);
+        assert_eq!(field.index_name.as_deref(), Some(".test.Message.name"));
+        assert_eq!(field.scope, ".test.Message");
+        let statement = &field.options[0];
+        assert_eq!(
+            statement.name,
+            [
+                OptionNamePart {
+                    name: "validate.rules".to_string(),
+                    extension: true,
+                },
+                OptionNamePart {
+                    name: "string".to_string(),
+                    extension: false,
+                },
+            ]
+        );
+        let part = |name: &str| OptionNamePart {
+            name: name.to_string(),
+            extension: false,
+        };
+        assert_eq!(
+            statement.value,
+            OptionValue::Aggregate(vec![
+                (
+                    part("min_len"),
+                    OptionValue::Int {
+                        negative: false,
+                        value: 1,
+                    },
+                ),
+                (
+                    part("in"),
+                    OptionValue::List(vec![
+                        OptionValue::Str(b"a".to_vec()),
+                        OptionValue::Str(b"b".to_vec()),
+                    ]),
+                ),
+            ])
+        );
+
+        let file_options = &file.options[1];
+        assert_eq!(file_options.kind, Kind::File);
+        assert_eq!(file_options.index_name.as_deref(), Some("test.proto"));
+    }
+}
@@ -0,0 +1,394 @@
+//! Resolution of the type names of descriptors.
+//!
+//! Names are resolved as `protoc` does: a relative name is looked up in the scope of its
+//! declaration, then in each of the enclosing scopes, and only the symbols of the file itself, of
+//! its imports, and of the public imports of its imports are visible.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind, Result};
+
+use prost_types::field_descriptor_proto::Type;
+use prost_types::{
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto, SourceCodeInfo,
+};
+
+/// The kinds of symbols defined by `.proto` files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Symbol {
+    Package,
+    Message,
+    Enum,
+    EnumValue,
+    Field,
+    Oneof,
+    Extension,
+    Service,
+    Method,
+}
+
+impl Symbol {
+    fn is_type(self) -> bool {
+        matches!(self, Symbol::Message | Symbol::Enum)
+    }
+
+    /// Returns `true` if the symbol can contain other symbols.
+    fn is_aggregate(self) -> bool {
+        matches!(
+            self,
+            Symbol::Package | Symbol::Message | Symbol::Enum | Symbol::Service
+        )
+    }
+}
+
+/// The symbols defined by a set of files.
+///
+/// Symbols are identified by fully-qualified name, with a leading `.`, and files by their index.
+pub(crate) struct Symbols {
+    /// The symbols, with the index of the file defining them.
+    symbols: HashMap<String, (Symbol, usize)>,
+    /// The indexes of the files whose symbols are visible from each file.
+    visible: Vec<HashSet<usize>>,
+    /// The values of each enum, in the order they are declared.
+    enum_values: HashMap<String, Vec<(String, i32)>>,
+}
+
+impl Symbols {
+    fn new(files: &[FileDescriptorProto]) -> Result<Symbols> {
+        let mut symbols = Symbols {
+            symbols: HashMap::new(),
+            visible: Vec::with_capacity(files.len()),
+            enum_values: HashMap::new(),
+        };
+
+        let indexes: HashMap<&str, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (file.name(), index))
+            .collect();
+        for (index, file) in files.iter().enumerate() {
+            let mut visible = HashSet::new();
+            visible.insert(index);
+            for dependency in &file.dependency {
+                if let Some(&dependency) = indexes.get(&**dependency) {
+                    add_public_dependencies(files, &indexes, dependency, &mut visible);
+                }
+            }
+            symbols.visible.push(visible);
+        }
+
+        for (index, file) in files.iter().enumerate() {
+            let mut package = String::new();
+            for part in file.package().split('.').filter(|part| !part.is_empty()) {
+                package.push('.');
+                package.push_str(part);
+                symbols.insert(files, index, package.clone(), Symbol::Package)?;
+            }
+            for message in &file.message_type {
+                symbols.add_message(files, index, &package, message)?;
+            }
+            for enumeration in &file.enum_type {
+                symbols.add_enum(files, index, &package, enumeration)?;
+            }
+            for extension in &file.extension {
+                let name = format!("{}.{}", package, extension.name());
+                symbols.insert(files, index, name, Symbol::Extension)?;
+            }
+            for service in &file.service {
+                let fq_name = format!("{}.{}", package, service.name());
+                symbols.insert(files, index, fq_name.clone(), Symbol::Service)?;
+                for method in &service.method {
+                    let name = format!("{}.{}", fq_name, method.name());
+                    symbols.insert(files, index, name, Symbol::Method)?;
+                }
+            }
+        }
+        Ok(symbols)
+    }
+
+    fn add_message(
+        &mut self,
+        files: &[FileDescriptorProto],
+        index: usize,
+        scope: &str,
+        message: &DescriptorProto,
+    ) -> Result<()> {
+        let fq_name = format!("{}.{}", scope, message.name());
+        self.insert(files, index, fq_name.clone(), Symbol::Message)?;
+        for field in &message.field {
+            let name = format!("{}.{}", fq_name, field.name());
+            self.insert(files, index, name, Symbol::Field)?;
+        }
+        for oneof in &message.oneof_decl {
+            let name = format!("{}.{}", fq_name, oneof.name());
+            self.insert(files, index, name, Symbol::Oneof)?;
+        }
+        for extension in &message.extension {
+            let name = format!("{}.{}", fq_name, extension.name());
+            self.insert(files, index, name, Symbol::Extension)?;
+        }
+        for nested in &message.nested_type {
+            self.add_message(files, index, &fq_name, nested)?;
+        }
+        for enumeration in &message.enum_type {
+            self.add_enum(files, index, &fq_name, enumeration)?;
+        }
+        Ok(())
+    }
+
+    fn add_enum(
+        &mut self,
+        files: &[FileDescriptorProto],
+        index: usize,
+        scope: &str,
+        enumeration: &EnumDescriptorProto,
+    ) -> Result<()> {
+        let fq_name = format!("{}.{}", scope, enumeration.name());
+        self.insert(files, index, fq_name.clone(), Symbol::Enum)?;
+        // As in C++, enum values are siblings of their enum.
+        for value in &enumeration.value {
+            let name = format!("{}.{}", scope, value.name());
+            self.insert(files, index, name, Symbol::EnumValue)?;
+        }
+        let values = enumeration
+            .value
+            .iter()
+            .map(|value| (value.name().to_string(), value.number()))
+            .collect();
+        self.enum_values.insert(fq_name, values);
+        Ok(())
+    }
+
+    fn insert(
+        &mut self,
+        files: &[FileDescriptorProto],
+        index: usize,
+        fq_name: String,
+        symbol: Symbol,
+    ) -> Result<()> {
+        match self.symbols.get(&fq_name) {
+            Some(&(Symbol::Package, _)) if symbol == Symbol::Package => Ok(()),
+            Some(&(_, other)) => Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "{}: \"{}\" is already defined in file \"{}\".",
+                    files[index].name(),
+                    &fq_name[1..],
+                    files[other].name()
+                ),
+            )),
+            None => {
+                self.symbols.insert(fq_name, (symbol, index));
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the symbol with a fully-qualified name, if it is visible from a file.
+    fn find(&self, file: usize, fq_name: &str) -> Option<Symbol> {
+        match self.symbols.get(fq_name) {
+            Some(&(Symbol::Package, _)) => Some(Symbol::Package),
+            Some(&(symbol, index)) if self.visible[file].contains(&index) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    /// Looks up a name from a scope of a file, returning the fully-qualified name of the symbol.
+    ///
+    /// If `types_only` is set, symbols which are not types are skipped, unless they are the first
+    /// component of a compound name.
+    pub(crate) fn lookup(
+        &self,
+        file: usize,
+        scope: &str,
+        name: &str,
+        types_only: bool,
+    ) -> Option<(String, Symbol)> {
+        if name.starts_with('.') {
+            return self
+                .find(file, name)
+                .map(|symbol| (name.to_string(), symbol));
+        }
+
+        let first = name.split('.').next().unwrap();
+        let mut scope = scope.to_string();
+        loop {
+            let candidate = format!("{}.{}", scope, first);
+            if let Some(symbol) = self.find(file, &candidate) {
+                if first.len() < name.len() {
+                    // Only the first component of a compound name is looked up in the enclosing
+                    // scopes, the others must be in the symbol it names.
+                    if symbol.is_aggregate() {
+                        let fq_name = format!("{}.{}", scope, name);
+                        return self.find(file, &fq_name).map(|symbol| (fq_name, symbol));
+                    }
+                } else if !types_only || symbol.is_type() {
+                    return Some((candidate, symbol));
+                }
+            }
+            match scope.rfind('.') {
+                Some(dot) => scope.truncate(dot),
+                None => return None,
+            }
+        }
+    }
+
+    /// Returns the values of an enum, by fully-qualified name.
+    pub(crate) fn enum_values(&self, fq_enum_name: &str) -> &[(String, i32)] {
+        self.enum_values
+            .get(fq_enum_name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+/// Adds a dependency, and recursively its public dependencies, to the visible files.
+fn add_public_dependencies(
+    files: &[FileDescriptorProto],
+    indexes: &HashMap<&str, usize>,
+    dependency: usize,
+    visible: &mut HashSet<usize>,
+) {
+    if !visible.insert(dependency) {
+        return;
+    }
+    let file = &files[dependency];
+    for &public in &file.public_dependency {
+        let name = &file.dependency[public as usize];
+        if let Some(&public) = indexes.get(&**name) {
+            add_public_dependencies(files, indexes, public, visible);
+        }
+    }
+}
+
+/// Resolves the type names of the descriptors of the files, which must include all of their
+/// dependencies, returning their symbols.
+pub(crate) fn resolve(files: &mut [FileDescriptorProto]) -> Result<Symbols> {
+    let symbols = Symbols::new(files)?;
+    for (index, file) in files.iter_mut().enumerate() {
+        let source_code_info = file.source_code_info.take();
+        let resolver = Resolver {
+            symbols: &symbols,
+            file: index,
+            file_name: file.name().to_string(),
+            source_code_info: source_code_info.as_ref(),
+        };
+        let package = match file.package {
+            Some(ref package) => format!(".{}", package),
+            None => String::new(),
+        };
+        for (i, message) in file.message_type.iter_mut().enumerate() {
+            resolver.message(message, &package, vec![4, i as i32])?;
+        }
+        for (i, extension) in file.extension.iter_mut().enumerate() {
+            resolver.field(extension, &package, &[7, i as i32])?;
+        }
+        for (i, service) in file.service.iter_mut().enumerate() {
+            let fq_name = format!("{}.{}", package, service.name());
+            for (j, method) in service.method.iter_mut().enumerate() {
+                let path = [6, i as i32, 2, j as i32];
+                let input = resolver.message_type(&fq_name, method.input_type(), &path)?;
+                method.input_type = Some(input);
+                let output = resolver.message_type(&fq_name, method.output_type(), &path)?;
+                method.output_type = Some(output);
+            }
+        }
+        drop(resolver);
+        file.source_code_info = source_code_info;
+    }
+    Ok(symbols)
+}
+
+struct Resolver<'a> {
+    symbols: &'a Symbols,
+    file: usize,
+    file_name: String,
+    source_code_info: Option<&'a SourceCodeInfo>,
+}
+
+impl Resolver<'_> {
+    fn message(&self, message: &mut DescriptorProto, scope: &str, path: Vec<i32>) -> Result<()> {
+        let fq_name = format!("{}.{}", scope, message.name());
+        for (i, field) in message.field.iter_mut().enumerate() {
+            self.field(field, &fq_name, &[&*path, &[2, i as i32]].concat())?;
+        }
+        for (i, extension) in message.extension.iter_mut().enumerate() {
+            self.field(extension, &fq_name, &[&*path, &[6, i as i32]].concat())?;
+        }
+        for (i, nested) in message.nested_type.iter_mut().enumerate() {
+            self.message(nested, &fq_name, [&*path, &[3, i as i32]].concat())?;
+        }
+        Ok(())
+    }
+
+    /// Resolves the type and the extendee of a field, declared in a scope.
+    fn field(&self, field: &mut FieldDescriptorProto, scope: &str, path: &[i32]) -> Result<()> {
+        if let Some(extendee) = field.extendee.take() {
+            field.extendee = Some(self.message_type(scope, &extendee, path)?);
+        }
+        // Groups and map fields are resolved by the parser.
+        if field.r#type.is_some() {
+            return Ok(());
+        }
+
+        let type_name = field.type_name();
+        let (fq_name, ty) = match self.symbols.lookup(self.file, scope, type_name, true) {
+            Some((fq_name, Symbol::Message)) => (fq_name, Type::Message),
+            Some((fq_name, Symbol::Enum)) => (fq_name, Type::Enum),
+            Some(_) => {
+                return Err(self.error(path, format!("\"{}\" is not a type.", type_name)));
+            }
+            None => {
+                return Err(self.error(path, format!("\"{}\" is not defined.", type_name)));
+            }
+        };
+
+        if let Some(default_value) = &field.default_value {
+            if ty == Type::Message {
+                return Err(self.error(path, "Messages can't have default values."));
+            }
+            let values = self.symbols.enum_values(&fq_name);
+            if !values.iter().any(|(name, _)| name == default_value) {
+                return Err(self.error(
+                    path,
+                    format!(
+                        "Enum type \"{}\" has no value named \"{}\".",
+                        &fq_name[1..],
+                        default_value
+                    ),
+                ));
+            }
+        }
+
+        field.type_name = Some(fq_name);
+        field.r#type = Some(ty as i32);
+        Ok(())
+    }
+
+    /// Resolves the name of a message type, such as the input type of a method.
+    fn message_type(&self, scope: &str, name: &str, path: &[i32]) -> Result<String> {
+        match self.symbols.lookup(self.file, scope, name, true) {
+            Some((fq_name, Symbol::Message)) => Ok(fq_name),
+            Some(_) => Err(self.error(path, format!("\"{}\" is not a message type.", name))),
+            None => Err(self.error(path, format!("\"{}\" is not defined.", name))),
+        }
+    }
+
+    /// Returns an error located at the declaration with a path.
+    fn error(&self, path: &[i32], message: impl Into<String>) -> Error {
+        let location = self
+            .source_code_info
+            .and_then(|info| info.location.iter().find(|location| location.path == path));
+        let message = message.into();
+        let message = match location {
+            Some(location) => format!(
+                "{}:{}:{}: {}",
+                self.file_name,
+                location.span[0] + 1,
+                location.span[1] + 1,
+                message
+            ),
+            None => format!("{}: {}", self.file_name, message),
+        };
+        Error::new(ErrorKind::Other, message)
+    }
+}
@@ -45,14 +45,25 @@ impl<T> PathMap<T> {
     }
 
     /// Returns the first value found best matching the path
-    /// See [sub_path_iter()] for paths test order
+    /// The path itself and its suffix paths are tried first, then glob matchers, then prefix
+    /// paths and the global path. See [sub_path_iter()].
     fn find_best_matching(&self, full_path: &str) -> Option<&T> {
-        sub_path_iter(full_path).find_map(|path| {
+        let find = |path: &str| {
             self.matchers
                 .iter()
-                .find(|(p, _)| p == path)
+                .find(|(p, _)| !is_glob(p) && p == path)
                 .map(|(_, v)| v)
-        })
+        };
+        iter::once(full_path)
+            .chain(suffixes(full_path))
+            .find_map(find)
+            .or_else(|| {
+                self.matchers
+                    .iter()
+                    .find(|(p, _)| is_glob(p) && glob_match(p, full_path))
+                    .map(|(_, v)| v)
+            })
+            .or_else(|| prefixes(full_path).chain(iter::once(".")).find_map(find))
     }
 }
 
@@ -71,7 +82,11 @@ impl<'a, T> Iter<'a, T> {
     }
 
     fn is_match(&self, path: &str) -> bool {
-        sub_path_iter(self.path.as_str()).any(|p| p == path)
+        if is_glob(path) {
+            glob_match(path, &self.path)
+        } else {
+            sub_path_iter(self.path.as_str()).any(|p| p == path)
+        }
     }
 }
 
@@ -136,6 +151,62 @@ fn suffixes(fq_path: &str) -> impl Iterator<Item = &str> {
     .skip(1)
 }
 
+/// Returns `true` if the matcher is a glob pattern.
+fn is_glob(matcher: &str) -> bool {
+    matcher.contains('*')
+}
+
+/// Matches a path against a glob pattern.
+///
+/// A `*` matches any part of a single path component, and a `**` component matches any number of
+/// components, including none. Like other matchers, a pattern with a leading `.` must match the
+/// whole fully-qualified path, while other patterns match its suffixes. Unlike other matchers, a
+/// pattern doesn't match the paths nested under the paths it matches, which can be matched with a
+/// trailing `.**`.
+///
+/// Example: glob_match(".a.*.c", ".a.b.c") -> true, glob_match("b.**", ".a.b.c.d") -> true
+fn glob_match(pattern: &str, path: &str) -> bool {
+    let path = path
+        .split('.')
+        .filter(|c| !c.is_empty())
+        .collect::<Vec<_>>();
+    let (anchored, pattern) = match pattern.strip_prefix('.') {
+        Some(pattern) => (true, pattern),
+        None => (false, pattern),
+    };
+    let pattern = pattern.split('.').collect::<Vec<_>>();
+    if anchored {
+        match_components(&pattern, &path)
+    } else {
+        (0..path.len()).any(|start| match_components(&pattern, &path[start..]))
+    }
+}
+
+fn match_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| match_components(rest, &path[skip..])),
+        Some((component, rest)) => match path.split_first() {
+            Some((first, path_rest)) => {
+                match_wildcards(component.as_bytes(), first.as_bytes())
+                    && match_components(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Matches a path component against a pattern component, in which `*` matches any characters.
+fn match_wildcards(pattern: &[u8], component: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => component.is_empty(),
+        Some((b'*', rest)) => {
+            (0..=component.len()).any(|skip| match_wildcards(rest, &component[skip..]))
+        }
+        Some((c, rest)) => component.first() == Some(c) && match_wildcards(rest, &component[1..]),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -218,6 +289,57 @@ mod tests {
         assert_eq!(Some(&4), path_map.get_first_field(".a.b.c", "d"));
     }
 
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(".a.*.c", ".a.b.c"));
+        assert!(!glob_match(".a.*.c", ".a.b.b.c"));
+        assert!(!glob_match(".a.*", ".a.b.c"));
+        assert!(glob_match(".a.**", ".a.b.c"));
+        assert!(glob_match(".a.**", ".a"));
+        assert!(glob_match(".a.**.d", ".a.b.c.d"));
+        assert!(glob_match(".a.**.d", ".a.d"));
+        assert!(!glob_match(".a.**.d", ".a.b.c.e"));
+        assert!(glob_match(".a.b_*.*_id", ".a.b_c.user_id"));
+        assert!(!glob_match(".a.b_*.*_id", ".a.b_c.user_ids"));
+        assert!(glob_match(".*", ".a"));
+        assert!(!glob_match(".*", ".a.b"));
+        assert!(glob_match("**", ".a.b"));
+
+        // Relative patterns match suffixes.
+        assert!(glob_match("*.c", ".a.b.c"));
+        assert!(glob_match("b.*", ".a.b.c"));
+        assert!(!glob_match("b.*", ".a.b.c.d"));
+        assert!(glob_match("*_id", "a.user_id"));
+        assert!(!glob_match("x.*", ".a.b.c"));
+    }
+
+    #[test]
+    fn test_get_glob() {
+        let mut path_map = PathMap::default();
+        path_map.insert(".a.*.c".to_owned(), 1);
+        path_map.insert("*_at".to_owned(), 2);
+        path_map.insert(".a.**".to_owned(), 3);
+
+        assert_eq!(
+            path_map.get_field(".a.b", "c").collect::<Vec<_>>(),
+            vec![&1, &3]
+        );
+        assert_eq!(
+            path_map.get_field(".x.y", "created_at").collect::<Vec<_>>(),
+            vec![&2]
+        );
+        assert_eq!(path_map.get(".x.y").next(), None);
+
+        // Globs are preferred over prefixes, but not over full paths and suffixes.
+        path_map.insert(".a".to_owned(), 4);
+        path_map.insert("b.c".to_owned(), 5);
+        assert_eq!(Some(&5), path_map.get_first(".a.b.c"));
+        assert_eq!(Some(&1), path_map.get_first(".a.d.c"));
+        assert_eq!(Some(&3), path_map.get_first(".a.d"));
+        path_map.insert(".".to_owned(), 6);
+        assert_eq!(Some(&6), path_map.get_first(".x.y"));
+    }
+
     #[test]
     fn test_get_keep_order() {
         let mut path_map = PathMap::default();
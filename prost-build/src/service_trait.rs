@@ -0,0 +1,201 @@
+//! A reference `ServiceGenerator`, which generates a plain Rust trait for each service.
+
+use crate::ast::{Method, Service};
+use crate::ServiceGenerator;
+
+/// A [`ServiceGenerator`] which generates a plain, synchronous Rust trait for each service.
+///
+/// The trait has a method for each RPC, taking the request and returning the response or the
+/// error type of the implementation. Streaming requests and responses are boxed iterators, so the
+/// trait doesn't depend on an RPC framework or an async runtime. It can be used as is, for
+/// in-process implementations of a service, or as a starting point for framework-specific
+/// generators.
+///
+/// For example, the `Greeting` service:
+///
+/// ```proto
+/// package helloworld;
+///
+/// service Greeting {
+///   // Says hello.
+///   rpc Hello (HelloRequest) returns (HelloReply);
+///   rpc HelloAll (stream HelloRequest) returns (stream HelloReply);
+/// }
+/// ```
+///
+/// is generated as:
+///
+/// ```rust,ignore
+/// pub trait Greeting {
+///     /// The fully-qualified name of the service.
+///     const NAME: &'static str = "helloworld.Greeting";
+///
+///     /// The error returned by the methods.
+///     type Error;
+///
+///     /// Says hello.
+///     fn hello(&self, request: HelloRequest) -> ::core::result::Result<HelloReply, Self::Error>;
+///
+///     fn hello_all(
+///         &self,
+///         request: ::prost::alloc::boxed::Box<
+///             dyn ::core::iter::Iterator<Item = HelloRequest> + ::core::marker::Send,
+///         >,
+///     ) -> ::core::result::Result<
+///         ::prost::alloc::boxed::Box<
+///             dyn ::core::iter::Iterator<
+///                 Item = ::core::result::Result<HelloReply, Self::Error>,
+///             > + ::core::marker::Send,
+///         >,
+///         Self::Error,
+///     >;
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ServiceTraitGenerator {
+    _private: (),
+}
+
+impl ServiceTraitGenerator {
+    /// Creates a new service trait generator.
+    pub fn new() -> ServiceTraitGenerator {
+        ServiceTraitGenerator::default()
+    }
+}
+
+impl ServiceGenerator for ServiceTraitGenerator {
+    fn generate(&mut self, service: Service, buf: &mut String) {
+        service.comments.append_with_indent(0, buf);
+        if service.options.deprecated() {
+            buf.push_str("#[deprecated]\n");
+        }
+        buf.push_str(&format!("pub trait {} {{\n", service.name));
+
+        let full_name = if service.package.is_empty() {
+            service.proto_name.clone()
+        } else {
+            format!("{}.{}", service.package, service.proto_name)
+        };
+        buf.push_str("    /// The fully-qualified name of the service.\n");
+        buf.push_str(&format!(
+            "    const NAME: &'static str = \"{}\";\n\n",
+            full_name
+        ));
+        buf.push_str("    /// The error returned by the methods.\n");
+        buf.push_str("    type Error;\n");
+
+        for method in &service.methods {
+            buf.push('\n');
+            append_method(method, buf);
+        }
+
+        buf.push_str("}\n");
+    }
+}
+
+fn append_method(method: &Method, buf: &mut String) {
+    method.comments.append_with_indent(1, buf);
+    if method.options.deprecated() {
+        buf.push_str("    #[deprecated]\n");
+    }
+
+    let request = if method.client_streaming {
+        boxed_iterator(&method.input_type)
+    } else {
+        method.input_type.clone()
+    };
+    let response = if method.server_streaming {
+        boxed_iterator(&format!(
+            "::core::result::Result<{}, Self::Error>",
+            method.output_type
+        ))
+    } else {
+        method.output_type.clone()
+    };
+    buf.push_str(&format!(
+        "    fn {}(&self, request: {}) -> ::core::result::Result<{}, Self::Error>;\n",
+        method.name, request, response
+    ));
+}
+
+fn boxed_iterator(item: &str) -> String {
+    format!(
+        "::prost::alloc::boxed::Box<dyn ::core::iter::Iterator<Item = {}> + ::core::marker::Send>",
+        item
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::ast::Comments;
+    use crate::CustomOptions;
+
+    fn method(name: &str, client_streaming: bool, server_streaming: bool) -> Method {
+        Method {
+            name: name.to_string(),
+            proto_name: name.to_string(),
+            comments: Comments {
+                leading_detached: Vec::new(),
+                leading: Vec::new(),
+                trailing: Vec::new(),
+            },
+            input_type: "Request".to_string(),
+            output_type: "Response".to_string(),
+            input_proto_type: ".test.Request".to_string(),
+            output_proto_type: ".test.Response".to_string(),
+            options: prost_types::MethodOptions::default(),
+            custom_options: CustomOptions::default(),
+            client_streaming,
+            server_streaming,
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut unary = method("get", false, false);
+        unary.comments.leading.push(" Gets it.".to_string());
+        unary.options.deprecated = Some(true);
+        let service = Service {
+            name: "Store".to_string(),
+            proto_name: "Store".to_string(),
+            package: "test".to_string(),
+            comments: Comments {
+                leading_detached: Vec::new(),
+                leading: vec![" A store.".to_string()],
+                trailing: Vec::new(),
+            },
+            methods: vec![
+                unary,
+                method("upload", true, false),
+                method("watch", false, true),
+            ],
+            options: prost_types::ServiceOptions::default(),
+            custom_options: CustomOptions::default(),
+        };
+
+        let mut buf = String::new();
+        ServiceTraitGenerator::new().generate(service, &mut buf);
+        assert_eq!(
+            buf,
+            "/// A store.
+pub trait Store {
+    /// The fully-qualified name of the service.
+    const NAME: &'static str = \"test.Store\";
+
+    /// The error returned by the methods.
+    type Error;
+
+    /// Gets it.
+    #[deprecated]
+    fn get(&self, request: Request) -> ::core::result::Result<Response, Self::Error>;
+
+    fn upload(&self, request: ::prost::alloc::boxed::Box<dyn ::core::iter::Iterator<Item = Request> + ::core::marker::Send>) -> ::core::result::Result<Response, Self::Error>;
+
+    fn watch(&self, request: Request) -> ::core::result::Result<::prost::alloc::boxed::Box<dyn ::core::iter::Iterator<Item = ::core::result::Result<Response, Self::Error>> + ::core::marker::Send>, Self::Error>;
+}
+"
+        );
+    }
+}
@@ -0,0 +1,850 @@
+//! Validation rules declared with custom field options, for `Config::validate`.
+//!
+//! The rules of [protoc-gen-validate][1] (the `validate.rules` field option) and of
+//! [protovalidate][2] (the `buf.validate.field` field option) are extensions of the descriptor
+//! options, read from the `CustomOptionsIndex` of the `FileDescriptorSet`, and rendered as the
+//! checks of the generated `prost::Validate` implementations.
+//!
+//! [1]: https://github.com/bufbuild/protoc-gen-validate
+//! [2]: https://github.com/bufbuild/protovalidate
+
+use std::ascii;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use itertools::Itertools;
+use prost::bytes::{Buf, Bytes};
+use prost::encoding::{decode_varint, WireType};
+use prost::DecodeError;
+use prost_types::field_descriptor_proto::Type;
+
+use crate::custom_options::{decode_fields, decode_string, CustomOptionsIndex, Fields, Kind};
+
+/// The field number of the protoc-gen-validate `validate.rules` field option, and of the
+/// `validate.required` oneof option.
+const PGV_RULES: u32 = 1071;
+/// The field number of the protovalidate `buf.validate.field` field option, and of the
+/// `buf.validate.oneof` oneof option.
+const PROTOVALIDATE_RULES: u32 = 1159;
+
+/// The field numbers of the type-specific rules in `FieldRules`, which are the same for both
+/// protoc-gen-validate and protovalidate.
+const FLOAT: u32 = 1;
+const DOUBLE: u32 = 2;
+const BOOL: u32 = 13;
+const STRING: u32 = 14;
+const BYTES: u32 = 15;
+const ENUM: u32 = 16;
+const REPEATED: u32 = 18;
+const MAP: u32 = 19;
+
+fn decode_u64(value: &Bytes) -> Result<u64, DecodeError> {
+    decode_varint(&mut value.clone())
+}
+
+/// The validation rules of the fields and oneofs of a set of files.
+#[derive(Debug, Default)]
+pub struct ValidationRules {
+    /// The rules of the fields which have any, by fully-qualified field name.
+    fields: HashMap<String, FieldRules>,
+    /// The fully-qualified names of the oneofs which must be set.
+    required_oneofs: HashSet<String>,
+}
+
+impl ValidationRules {
+    /// Reads the validation rules from the custom options of the descriptors.
+    pub fn new(custom_options: &CustomOptionsIndex) -> Result<ValidationRules, DecodeError> {
+        let mut rules = ValidationRules::default();
+        for (field, options) in custom_options.iter(Kind::Field) {
+            let mut field_rules = None;
+            for option in options.iter() {
+                let flavor = match option.number {
+                    PGV_RULES => Flavor::Pgv,
+                    PROTOVALIDATE_RULES => Flavor::Protovalidate,
+                    _ => continue,
+                };
+                field_rules
+                    .get_or_insert_with(FieldRules::default)
+                    .merge(flavor, option.value.clone())?;
+            }
+            if let Some(field_rules) = field_rules {
+                rules.fields.insert(field.to_string(), field_rules);
+            }
+        }
+        for (oneof, options) in custom_options.iter(Kind::Oneof) {
+            let mut required = false;
+            for option in options.iter() {
+                match option.number {
+                    // `validate.required`
+                    PGV_RULES => required = decode_u64(&option.value)? != 0,
+                    // `buf.validate.oneof`, whose `required` rule is field 1.
+                    PROTOVALIDATE_RULES => {
+                        for (tag, _, value) in decode_fields(option.value.clone())? {
+                            if tag == 1 {
+                                required = decode_u64(&value)? != 0;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if required {
+                rules.required_oneofs.insert(oneof.to_string());
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Returns the rules of a field, if it has any.
+    pub fn field(&self, fq_message_name: &str, field_name: &str) -> Option<&FieldRules> {
+        self.fields
+            .get(&format!("{}.{}", fq_message_name, field_name))
+    }
+
+    /// Returns `true` if the oneof must be set.
+    pub fn is_required_oneof(&self, fq_message_name: &str, oneof_name: &str) -> bool {
+        self.required_oneofs
+            .contains(&format!("{}.{}", fq_message_name, oneof_name))
+    }
+}
+
+/// The option which declared a set of rules.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Flavor {
+    /// protoc-gen-validate's `validate.rules`.
+    Pgv,
+    /// protovalidate's `buf.validate.field`.
+    Protovalidate,
+}
+
+/// The validation rules of a field.
+#[derive(Clone, Debug, Default)]
+pub struct FieldRules {
+    /// Whether the field must be set.
+    pub required: bool,
+    /// Whether the rules only apply to a field which is set to a non-default value.
+    pub ignore_empty: bool,
+    /// Whether the field, including the messages it embeds, is not validated.
+    pub skip: bool,
+    /// The field number of the type-specific rules in `FieldRules`, and the rules.
+    rules: Option<(u32, Fields)>,
+    /// The rules of the items of a repeated field.
+    items: Option<Box<FieldRules>>,
+}
+
+impl FieldRules {
+    fn merge(&mut self, flavor: Flavor, rules: Bytes) -> Result<(), DecodeError> {
+        for (tag, _, value) in decode_fields(rules)? {
+            match (flavor, tag) {
+                // protoc-gen-validate's `FieldRules.message`, holding `skip` (1) and
+                // `required` (2).
+                (Flavor::Pgv, 17) => {
+                    for (tag, _, value) in decode_fields(value)? {
+                        match tag {
+                            1 => self.skip = decode_u64(&value)? != 0,
+                            2 => self.required = decode_u64(&value)? != 0,
+                            _ => {}
+                        }
+                    }
+                }
+                // protovalidate's `FieldRules.required`.
+                (Flavor::Protovalidate, 25) => self.required = decode_u64(&value)? != 0,
+                // protovalidate's `FieldRules.ignore`, which ignores the rules of an empty field
+                // (1 and 2), or always (3).
+                (Flavor::Protovalidate, 27) => match decode_u64(&value)? {
+                    1 | 2 => self.ignore_empty = true,
+                    3 => self.skip = true,
+                    _ => {}
+                },
+                (_, 1..=22) => {
+                    let fields = decode_fields(value)?;
+                    for (rule, _, value) in &fields {
+                        match (flavor, tag, *rule) {
+                            // protoc-gen-validate's `ignore_empty` rules.
+                            (Flavor::Pgv, 1..=12, 8)
+                            | (Flavor::Pgv, STRING, 26)
+                            | (Flavor::Pgv, BYTES, 14)
+                            | (Flavor::Pgv, REPEATED, 5)
+                            | (Flavor::Pgv, MAP, 6) => self.ignore_empty = decode_u64(value)? != 0,
+                            (_, REPEATED, 4) => {
+                                let mut items = FieldRules::default();
+                                items.merge(flavor, value.clone())?;
+                                self.items = Some(Box::new(items));
+                            }
+                            _ => {}
+                        }
+                    }
+                    // protovalidate's `finite` rule of floating point numbers shares its field
+                    // number with protoc-gen-validate's `ignore_empty`.
+                    let fields = fields
+                        .into_iter()
+                        .filter(|&(rule, _, _)| {
+                            flavor == Flavor::Protovalidate || !matches!((tag, rule), (1..=12, 8))
+                        })
+                        .collect();
+                    self.rules = Some((tag, fields));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the rules of the items of a repeated field.
+    pub fn items(&self) -> Option<&FieldRules> {
+        self.items.as_deref()
+    }
+
+    /// Returns the checks of the number of items of a repeated field, in terms of `values`, a
+    /// `Vec` of the items.
+    pub fn repeated_checks(&self, field: &str) -> Vec<Check> {
+        let rules = match self.rules(REPEATED, field, "repeated") {
+            Some(rules) => rules,
+            None => return Vec::new(),
+        };
+        let mut checks = Vec::new();
+        for (rule, _, value) in rules {
+            let count = decode_u64(value).unwrap_or_default();
+            match rule {
+                1 => checks.extend(length_check(
+                    "values.len()",
+                    ">=",
+                    count,
+                    format!("value must contain at least {} item(s)", count),
+                )),
+                2 => checks.extend(length_check(
+                    "values.len()",
+                    "<=",
+                    count,
+                    format!("value must contain no more than {} item(s)", count),
+                )),
+                3 if count != 0 => checks.push(Check::new(
+                    "values.iter().enumerate().any(|(index, value)| values[..index].contains(value))",
+                    "repeated value must contain unique items",
+                )),
+                _ => {}
+            }
+        }
+        if self.ignore_empty {
+            checks = ignore_empty(checks, "values.is_empty()", "!values.is_empty()");
+        }
+        checks
+    }
+
+    /// Returns the checks of the number of entries of a map field, in terms of `values`, the map.
+    pub fn map_checks(&self, field: &str) -> Vec<Check> {
+        let rules = match self.rules(MAP, field, "map") {
+            Some(rules) => rules,
+            None => return Vec::new(),
+        };
+        let mut checks = Vec::new();
+        for (rule, _, value) in rules {
+            let count = decode_u64(value).unwrap_or_default();
+            match rule {
+                1 => checks.extend(length_check(
+                    "values.len()",
+                    ">=",
+                    count,
+                    format!("value must contain at least {} pair(s)", count),
+                )),
+                2 => checks.extend(length_check(
+                    "values.len()",
+                    "<=",
+                    count,
+                    format!("value must contain no more than {} pair(s)", count),
+                )),
+                _ => {}
+            }
+        }
+        if self.ignore_empty {
+            checks = ignore_empty(checks, "values.is_empty()", "!values.is_empty()");
+        }
+        checks
+    }
+
+    /// Returns the checks of a value of the type, in terms of `value`.
+    ///
+    /// `value` is bound to a `&str` for strings, to a `&[u8]` for bytes, and to the value itself
+    /// for other types, with enumerations as `i32`. `enum_ty` is the Rust type of enumerations.
+    pub fn value_checks(&self, type_: Type, enum_ty: &str, field: &str) -> Vec<Check> {
+        let kind = match type_ {
+            Type::Float => FLOAT,
+            Type::Double => DOUBLE,
+            Type::Int32 => 3,
+            Type::Int64 => 4,
+            Type::Uint32 => 5,
+            Type::Uint64 => 6,
+            Type::Sint32 => 7,
+            Type::Sint64 => 8,
+            Type::Fixed32 => 9,
+            Type::Fixed64 => 10,
+            Type::Sfixed32 => 11,
+            Type::Sfixed64 => 12,
+            Type::Bool => BOOL,
+            Type::String => STRING,
+            Type::Bytes => BYTES,
+            Type::Enum => ENUM,
+            // The rules of well-known message types, such as `Any`, `Duration` and `Timestamp`,
+            // are not supported.
+            Type::Message | Type::Group => return Vec::new(),
+        };
+        let rules = match self.rules(kind, field, &format!("{:?}", type_)) {
+            Some(rules) => rules,
+            None => return Vec::new(),
+        };
+        let checks = match kind {
+            BOOL => bool_checks(rules),
+            STRING => string_checks(rules),
+            BYTES => bytes_checks(rules),
+            ENUM => enum_checks(rules, enum_ty),
+            _ => number_checks(kind, rules),
+        };
+        if !self.ignore_empty {
+            return checks;
+        }
+        match kind {
+            BOOL => ignore_empty(checks, "!value", "value"),
+            STRING | BYTES => ignore_empty(checks, "value.is_empty()", "!value.is_empty()"),
+            ENUM => ignore_empty(checks, "value == 0", "value != 0"),
+            _ => {
+                let zero = match kind {
+                    FLOAT | DOUBLE => Number::Float(0.0),
+                    5 | 6 | 9 | 10 => Number::Uint(0),
+                    _ => Number::Int(0),
+                }
+                .literal(number_type(kind));
+                ignore_empty(
+                    checks,
+                    &format!("value == {}", zero),
+                    &format!("value != {}", zero),
+                )
+            }
+        }
+    }
+
+    /// Returns the type-specific rules, checking that they apply to the type of the field.
+    fn rules(&self, kind: u32, field: &str, type_name: &str) -> Option<&Fields> {
+        match &self.rules {
+            Some((rules_kind, rules)) if *rules_kind == kind => Some(rules),
+            // `Any`, `Duration` and `Timestamp` rules are not supported.
+            Some((20..=22, _)) | None => None,
+            Some(_) => panic!(
+                "the validation rules of field {} do not apply to its type, {}",
+                field, type_name
+            ),
+        }
+    }
+}
+
+/// A check of a validation rule.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Check {
+    /// The condition which holds when the rule is violated.
+    pub violation: String,
+    /// The description of the rule, used when it is violated.
+    pub description: String,
+}
+
+impl Check {
+    fn new(violation: impl Into<String>, description: impl Into<String>) -> Check {
+        Check {
+            violation: violation.into(),
+            description: description.into(),
+        }
+    }
+}
+
+/// Restricts checks to non-empty values, given the conditions which hold when the value is empty
+/// and when it is not.
+fn ignore_empty(checks: Vec<Check>, empty: &str, non_empty: &str) -> Vec<Check> {
+    checks
+        .into_iter()
+        .filter(|check| check.violation != empty)
+        .map(|check| {
+            let violation = if check.violation == non_empty {
+                check.violation
+            } else if check.violation.contains(" || ") {
+                format!("{} && ({})", non_empty, check.violation)
+            } else {
+                format!("{} && {}", non_empty, check.violation)
+            };
+            Check { violation, ..check }
+        })
+        .collect()
+}
+
+/// Returns the check of a length, or `None` if it always holds.
+///
+/// `len` is the expression of the length, such as `value.len()`, whose receiver is empty if and
+/// only if the length is zero.
+fn length_check(len: &str, op: &str, count: u64, description: String) -> Option<Check> {
+    let receiver = &len[..len.find('.').unwrap_or(len.len())];
+    let violation = match (op, count) {
+        (">=", 0) => return None,
+        (">=", 1) => format!("{}.is_empty()", receiver),
+        (">=", _) => format!("{} < {}", len, count),
+        (_, 0) => format!("!{}.is_empty()", receiver),
+        ("<=", _) => format!("{} > {}", len, count),
+        _ => format!("{} != {}", len, count),
+    };
+    Some(Check::new(violation, description))
+}
+
+/// A number of the rules of a numeric field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Number {
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+}
+
+impl Number {
+    fn decode(kind: u32, buf: &mut Bytes) -> Result<Number, DecodeError> {
+        let size = match kind {
+            FLOAT | 9 | 11 => 4,
+            DOUBLE | 10 | 12 => 8,
+            _ => 0,
+        };
+        if buf.remaining() < size {
+            return Err(DecodeError::new("buffer underflow"));
+        }
+        Ok(match kind {
+            FLOAT => Number::Float(f32::from_bits(buf.get_u32_le()).into()),
+            DOUBLE => Number::Float(f64::from_bits(buf.get_u64_le())),
+            3 | 4 | ENUM => Number::Int(decode_varint(buf)? as i64),
+            5 | 6 => Number::Uint(decode_varint(buf)?),
+            7 | 8 => {
+                let value = decode_varint(buf)?;
+                Number::Int(((value >> 1) as i64) ^ -((value & 1) as i64))
+            }
+            9 => Number::Uint(buf.get_u32_le().into()),
+            10 => Number::Uint(buf.get_u64_le()),
+            11 => Number::Int(buf.get_i32_le().into()),
+            _ => Number::Int(buf.get_i64_le()),
+        })
+    }
+
+    /// Decodes the numbers of a rule, which are packed if the rule is length-delimited.
+    fn decode_all(kind: u32, wire_type: WireType, value: &Bytes) -> Vec<Number> {
+        let mut buf = value.clone();
+        let mut numbers = Vec::new();
+        while buf.has_remaining() {
+            match Number::decode(kind, &mut buf) {
+                Ok(number) => numbers.push(number),
+                Err(_) => break,
+            }
+            if wire_type != WireType::LengthDelimited {
+                break;
+            }
+        }
+        numbers
+    }
+
+    fn is_greater_than(self, other: Number) -> bool {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a > b,
+            (Number::Uint(a), Number::Uint(b)) => a > b,
+            (Number::Float(a), Number::Float(b)) => a > b,
+            _ => false,
+        }
+    }
+
+    /// Returns the next (`1`) or previous (`-1`) integer, or `None` on overflow.
+    fn step(self, delta: i64) -> Option<Number> {
+        match self {
+            Number::Int(value) => value.checked_add(delta).map(Number::Int),
+            Number::Uint(value) if delta < 0 => value.checked_sub(1).map(Number::Uint),
+            Number::Uint(value) => value.checked_add(1).map(Number::Uint),
+            Number::Float(_) => None,
+        }
+    }
+
+    /// Returns the Rust literal of the number, of the Rust type.
+    fn literal(self, ty: &str) -> String {
+        match self {
+            Number::Int(value) => format!("{}{}", value, ty),
+            Number::Uint(value) => format!("{}{}", value, ty),
+            Number::Float(value) if value.is_nan() => format!("{}::NAN", ty),
+            Number::Float(value) if value == f64::INFINITY => format!("{}::INFINITY", ty),
+            Number::Float(value) if value == f64::NEG_INFINITY => format!("{}::NEG_INFINITY", ty),
+            Number::Float(value) if ty == "f32" => format!("{:?}{}", value as f32, ty),
+            Number::Float(value) => format!("{:?}{}", value, ty),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Number::Int(value) => value.fmt(f),
+            Number::Uint(value) => value.fmt(f),
+            // Numbers which were widened from `f32` are displayed as such.
+            Number::Float(value) if value as f32 as f64 == value => (value as f32).fmt(f),
+            Number::Float(value) => value.fmt(f),
+        }
+    }
+}
+
+/// Returns the numbers of all occurrences of a repeated rule.
+fn numbers(kind: u32, rules: &Fields, rule: u32) -> Vec<Number> {
+    rules
+        .iter()
+        .filter(|&&(other, _, _)| other == rule)
+        .flat_map(|(_, wire_type, value)| Number::decode_all(kind, *wire_type, value))
+        .collect()
+}
+
+/// Returns the Rust type of the numbers of a kind of rules.
+fn number_type(kind: u32) -> &'static str {
+    match kind {
+        FLOAT => "f32",
+        DOUBLE => "f64",
+        3 | 7 | 11 => "i32",
+        4 | 8 | 12 => "i64",
+        5 | 9 => "u32",
+        _ => "u64",
+    }
+}
+
+fn number_checks(kind: u32, rules: &Fields) -> Vec<Check> {
+    let ty = number_type(kind);
+    let float = kind == FLOAT || kind == DOUBLE;
+    // The negation of a comparison, which must hold for NaN values of floating point numbers.
+    let negate = |op: &str, number: Number| {
+        let literal = number.literal(ty);
+        let negated = match op {
+            // Unsigned numbers are never less than zero.
+            ">" if number == Number::Uint(0) => "==",
+            "<" => ">=",
+            "<=" => ">",
+            ">" => "<=",
+            _ => "<",
+        };
+        if float {
+            format!("value {} {} || value.is_nan()", negated, literal)
+        } else {
+            format!("value {} {}", negated, literal)
+        }
+    };
+
+    let mut checks = Vec::new();
+    let mut lower = None;
+    let mut upper = None;
+    let mut seen = HashSet::new();
+    for (rule, wire_type, value) in rules {
+        // protovalidate's `finite` rule is a `bool`, rather than a number.
+        if *rule == 8 {
+            if float && decode_u64(value).unwrap_or_default() != 0 {
+                checks.push(Check::new("!value.is_finite()", "value must be finite"));
+            }
+            continue;
+        }
+        let number = match Number::decode_all(kind, *wire_type, value).first() {
+            Some(&number) => number,
+            None => continue,
+        };
+        match rule {
+            1 => checks.push(Check::new(
+                format!("value != {}", number.literal(ty)),
+                format!("value must equal {}", number),
+            )),
+            2 => upper = Some(("<", "less than", number)),
+            3 => upper = Some(("<=", "less than or equal to", number)),
+            4 => lower = Some((">", "greater than", number)),
+            5 => lower = Some((">=", "greater than or equal to", number)),
+            6 | 7 if seen.insert(*rule) => {
+                let numbers = numbers(kind, rules, *rule);
+                let literals = numbers.iter().map(|number| number.literal(ty)).join(", ");
+                let list = numbers.iter().join(", ");
+                checks.push(if *rule == 6 {
+                    Check::new(
+                        format!("![{}].contains(&value)", literals),
+                        format!("value must be in list [{}]", list),
+                    )
+                } else {
+                    Check::new(
+                        format!("[{}].contains(&value)", literals),
+                        format!("value must not be in list [{}]", list),
+                    )
+                });
+            }
+            _ => {}
+        }
+    }
+
+    match (lower, upper) {
+        // A lower bound above the upper bound excludes the range between the bounds.
+        (Some((lower_op, lower_name, lower)), Some((upper_op, upper_name, upper)))
+            if lower.is_greater_than(upper) =>
+        {
+            let description = format!(
+                "value must be {} {} or {} {}",
+                upper_name, upper, lower_name, lower
+            );
+            if float {
+                checks.push(Check::new(
+                    format!(
+                        "({}) && ({})",
+                        negate(upper_op, upper),
+                        negate(lower_op, lower)
+                    ),
+                    description,
+                ));
+            } else {
+                // The excluded range, as an inclusive range of integers.
+                let start = if upper_op == "<" {
+                    Some(upper)
+                } else {
+                    upper.step(1)
+                };
+                let end = if lower_op == ">" {
+                    Some(lower)
+                } else {
+                    lower.step(-1)
+                };
+                if let (Some(start), Some(end)) = (start, end) {
+                    if !start.is_greater_than(end) {
+                        checks.push(Check::new(
+                            format!(
+                                "({}..={}).contains(&value)",
+                                start.literal(ty),
+                                end.literal(ty)
+                            ),
+                            description,
+                        ));
+                    }
+                }
+            }
+        }
+        (lower, upper) => {
+            for (op, name, number) in lower.into_iter().chain(upper) {
+                // Unsigned numbers are always greater than or equal to zero.
+                if (op, number) == (">=", Number::Uint(0)) {
+                    continue;
+                }
+                checks.push(Check::new(
+                    negate(op, number),
+                    format!("value must be {} {}", name, number),
+                ));
+            }
+        }
+    }
+    checks
+}
+
+fn bool_checks(rules: &Fields) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for (rule, _, value) in rules {
+        if *rule == 1 {
+            let expected = decode_u64(value).unwrap_or_default() != 0;
+            checks.push(Check::new(
+                if expected { "!value" } else { "value" },
+                format!("value must equal {}", expected),
+            ));
+        }
+    }
+    checks
+}
+
+fn string_checks(rules: &Fields) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut seen = HashSet::new();
+    for (rule, _, value) in rules {
+        let count = decode_u64(value).unwrap_or_default();
+        let text = decode_string(value).unwrap_or_default();
+        let check = match rule {
+            1 => Check::new(
+                format!("value != {:?}", text),
+                format!("value must equal {:?}", text),
+            ),
+            19 | 2 | 3 => {
+                let (op, description) = match rule {
+                    19 => ("==", "value length must be {} characters"),
+                    2 => (">=", "value length must be at least {} characters"),
+                    _ => ("<=", "value length must be at most {} characters"),
+                };
+                checks.extend(length_check(
+                    "value.chars().count()",
+                    op,
+                    count,
+                    description.replace("{}", &count.to_string()),
+                ));
+                continue;
+            }
+            20 | 4 | 5 => {
+                let (op, description) = match rule {
+                    20 => ("==", "value length must be {} bytes"),
+                    4 => (">=", "value length must be at least {} bytes"),
+                    _ => ("<=", "value length must be at most {} bytes"),
+                };
+                checks.extend(length_check(
+                    "value.len()",
+                    op,
+                    count,
+                    description.replace("{}", &count.to_string()),
+                ));
+                continue;
+            }
+            6 => Check::new(
+                format!(
+                    "::regex::Regex::new({:?}).map_or(true, |regex| !regex.is_match(value))",
+                    text
+                ),
+                format!("value must match regex pattern {:?}", text),
+            ),
+            7 => Check::new(
+                format!("!value.starts_with({:?})", text),
+                format!("value must have prefix {:?}", text),
+            ),
+            8 => Check::new(
+                format!("!value.ends_with({:?})", text),
+                format!("value must have suffix {:?}", text),
+            ),
+            9 => Check::new(
+                format!("!value.contains({:?})", text),
+                format!("value must contain substring {:?}", text),
+            ),
+            23 => Check::new(
+                format!("value.contains({:?})", text),
+                format!("value must not contain substring {:?}", text),
+            ),
+            10 | 11 if seen.insert(*rule) => {
+                let list = rules
+                    .iter()
+                    .filter(|&&(other, _, _)| other == *rule)
+                    .filter_map(|(_, _, value)| decode_string(value).ok())
+                    .map(|text| format!("{:?}", text))
+                    .join(", ");
+                if *rule == 10 {
+                    Check::new(
+                        format!("![{}].contains(&value)", list),
+                        format!("value must be in list [{}]", list),
+                    )
+                } else {
+                    Check::new(
+                        format!("[{}].contains(&value)", list),
+                        format!("value must not be in list [{}]", list),
+                    )
+                }
+            }
+            _ => continue,
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+/// Returns the Rust expression of a byte string, as a `&[u8]`.
+fn bytes_literal(bytes: &[u8]) -> String {
+    let mut literal = String::from("&b\"");
+    for &byte in bytes {
+        literal.extend(ascii::escape_default(byte).map(char::from));
+    }
+    literal.push_str("\"[..]");
+    literal
+}
+
+fn bytes_checks(rules: &Fields) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut seen = HashSet::new();
+    for (rule, _, value) in rules {
+        let count = decode_u64(value).unwrap_or_default();
+        let check = match rule {
+            1 => Check::new(
+                format!("value != {}", bytes_literal(value)),
+                "value must equal the constant",
+            ),
+            13 | 2 | 3 => {
+                let (op, description) = match rule {
+                    13 => ("==", "value length must be {} bytes"),
+                    2 => (">=", "value length must be at least {} bytes"),
+                    _ => ("<=", "value length must be at most {} bytes"),
+                };
+                checks.extend(length_check(
+                    "value.len()",
+                    op,
+                    count,
+                    description.replace("{}", &count.to_string()),
+                ));
+                continue;
+            }
+            5 => Check::new(
+                format!("!value.starts_with({})", bytes_literal(value)),
+                "value must have the prefix",
+            ),
+            6 => Check::new(
+                format!("!value.ends_with({})", bytes_literal(value)),
+                "value must have the suffix",
+            ),
+            7 if !value.is_empty() => Check::new(
+                format!(
+                    "!value.windows({}).any(|window| window == {})",
+                    value.len(),
+                    bytes_literal(value)
+                ),
+                "value must contain the byte sequence",
+            ),
+            8 | 9 if seen.insert(*rule) => {
+                let list = rules
+                    .iter()
+                    .filter(|&&(other, _, _)| other == *rule)
+                    .map(|(_, _, value)| bytes_literal(value))
+                    .join(", ");
+                if *rule == 8 {
+                    Check::new(
+                        format!("![{}].contains(&value)", list),
+                        "value must be in the list of allowed values",
+                    )
+                } else {
+                    Check::new(
+                        format!("[{}].contains(&value)", list),
+                        "value must not be in the list of disallowed values",
+                    )
+                }
+            }
+            _ => continue,
+        };
+        checks.push(check);
+    }
+    checks
+}
+
+fn enum_checks(rules: &Fields, enum_ty: &str) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let mut seen = HashSet::new();
+    for (rule, wire_type, value) in rules {
+        let check = match rule {
+            1 => {
+                let number = match Number::decode_all(ENUM, *wire_type, value).first() {
+                    Some(&number) => number,
+                    None => continue,
+                };
+                Check::new(
+                    format!("value != {}", number),
+                    format!("value must equal {}", number),
+                )
+            }
+            2 if decode_u64(value).unwrap_or_default() != 0 => Check::new(
+                format!("{}::from_i32(value).is_none()", enum_ty),
+                "value must be one of the defined enum values",
+            ),
+            3 | 4 if seen.insert(*rule) => {
+                let list = numbers(ENUM, rules, *rule).iter().join(", ");
+                if *rule == 3 {
+                    Check::new(
+                        format!("![{}].contains(&value)", list),
+                        format!("value must be in list [{}]", list),
+                    )
+                } else {
+                    Check::new(
+                        format!("[{}].contains(&value)", list),
+                        format!("value must not be in list [{}]", list),
+                    )
+                }
+            }
+            _ => continue,
+        };
+        checks.push(check);
+    }
+    checks
+}
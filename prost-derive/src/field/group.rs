@@ -1,14 +1,16 @@
 use anyhow::{bail, Error};
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
-use syn::Meta;
+use syn::{Ident, Meta};
 
-use crate::field::{set_bool, set_option, tag_attr, word_attr, Label};
+use crate::field::{set_bool, set_option, tag_attr, word_attr, Label, PresenceBit};
 
 #[derive(Clone)]
 pub struct Field {
     pub label: Label,
     pub tag: u32,
+    /// The presence bit of a required field.
+    pub presence_bit: Option<PresenceBit>,
 }
 
 impl Field {
@@ -17,6 +19,7 @@ impl Field {
         let mut label = None;
         let mut tag = None;
         let mut boxed = false;
+        let mut presence_bit = None;
 
         let mut unknown_attrs = Vec::new();
 
@@ -29,6 +32,8 @@ impl Field {
                 set_option(&mut tag, t, "duplicate tag attributes")?;
             } else if let Some(l) = Label::from_attr(attr) {
                 set_option(&mut label, l, "duplicate label attributes")?;
+            } else if let Some(b) = PresenceBit::from_attr(attr)? {
+                set_option(&mut presence_bit, b, "duplicate presence_bit attributes")?;
             } else {
                 unknown_attrs.push(attr);
             }
@@ -49,9 +54,15 @@ impl Field {
             None => bail!("group field is missing a tag attribute"),
         };
 
+        let label = label.unwrap_or(Label::Optional);
+        if presence_bit.is_some() && label != Label::Required {
+            bail!("presence_bit attribute may only be applied to required group fields");
+        }
+
         Ok(Some(Field {
-            label: label.unwrap_or(Label::Optional),
+            label,
             tag,
+            presence_bit: presence_bit.map(PresenceBit::new),
         }))
     }
 
@@ -100,8 +111,18 @@ impl Field {
                     ctx,
                 )
             },
-            Label::Required => quote! {
-                ::prost::encoding::group::merge(tag, wire_type, #ident, buf, ctx)
+            Label::Required => match self.presence_bit {
+                Some(ref bit) => {
+                    let bits = bit.bits();
+                    let index = bit.index;
+                    quote! {
+                        ::prost::encoding::group::merge(tag, wire_type, #ident, buf, ctx)
+                            .map(|()| #bits.set(#index))
+                    }
+                }
+                None => quote! {
+                    ::prost::encoding::group::merge(tag, wire_type, #ident, buf, ctx)
+                },
             },
             Label::Repeated => quote! {
                 ::prost::encoding::group::merge_repeated(tag, wire_type, #ident, buf, ctx)
@@ -131,4 +152,84 @@ impl Field {
             Label::Repeated => quote!(#ident.clear()),
         }
     }
+
+    /// Returns an expression which evaluates to `true` if the field holds its default value.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote!(#ident.is_none()),
+            Label::Required => match self.presence_bit {
+                Some(ref bit) => {
+                    let is_set = bit.is_set();
+                    quote!(!#is_set && ::prost::Message::is_default(&#ident))
+                }
+                None => quote!(::prost::Message::is_default(&#ident)),
+            },
+            Label::Repeated => quote!(#ident.is_empty()),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the required fields
+    /// of the groups held by the field are set, and that the field is set if it is required and
+    /// has a presence bit.
+    pub fn check_required(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                match #ident {
+                    ::core::option::Option::Some(ref msg) => ::prost::Message::check_required(msg),
+                    ::core::option::Option::None => ::core::result::Result::Ok(()),
+                }
+            },
+            Label::Required => match self.presence_bit {
+                Some(ref bit) => {
+                    let is_set = bit.is_set();
+                    quote! {
+                        if #is_set {
+                            ::prost::Message::check_required(&#ident)
+                        } else {
+                            ::core::result::Result::Err(
+                                ::prost::DecodeError::new("missing required field"),
+                            )
+                        }
+                    }
+                }
+                None => quote!(::prost::Message::check_required(&#ident)),
+            },
+            Label::Repeated => quote! {
+                #ident.iter().try_for_each(::prost::Message::check_required)
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the values of the
+    /// groups held by the field can be encoded.
+    pub fn check_encode(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                match #ident {
+                    ::core::option::Option::Some(ref msg) => ::prost::Message::check_encode(msg),
+                    ::core::option::Option::None => ::core::result::Result::Ok(()),
+                }
+            },
+            Label::Required => quote!(::prost::Message::check_encode(&#ident)),
+            Label::Repeated => quote! {
+                #ident.iter().try_for_each(::prost::Message::check_encode)
+            },
+        }
+    }
+
+    /// Returns the `has_` method of a required field with a presence bit.
+    pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
+        let bit = self.presence_bit.as_ref()?;
+        let ident_str = ident.to_string();
+        let ident_str = ident_str.trim_start_matches("r#");
+        let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
+        let has_doc = format!("Returns `true` if `{}` is set.", ident_str);
+        let is_set = bit.is_set();
+        Some(quote! {
+            #[doc=#has_doc]
+            pub fn #has(&self) -> bool {
+                #is_set
+            }
+        })
+    }
 }
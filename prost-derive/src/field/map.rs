@@ -1,7 +1,7 @@
 use anyhow::{bail, Error};
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{Ident, Lit, Meta, MetaNameValue, NestedMeta};
+use syn::{parse_str, Ident, Lit, Meta, MetaNameValue, NestedMeta, Path};
 
 use crate::field::{scalar, set_option, tag_attr};
 
@@ -9,6 +9,8 @@ use crate::field::{scalar, set_option, tag_attr};
 pub enum MapTy {
     HashMap,
     BTreeMap,
+    /// A map type implementing `prost::ProstMap`, set by the `map_type` attribute.
+    Custom(Path),
 }
 
 impl MapTy {
@@ -24,13 +26,16 @@ impl MapTy {
         match *self {
             MapTy::HashMap => Ident::new("hash_map", Span::call_site()),
             MapTy::BTreeMap => Ident::new("btree_map", Span::call_site()),
+            MapTy::Custom(_) => Ident::new("prost_map", Span::call_site()),
         }
     }
 
-    fn lib(&self) -> TokenStream {
+    /// Returns the path of the map type, without its type parameters.
+    fn rust_type(&self) -> TokenStream {
         match self {
-            MapTy::HashMap => quote! { std },
-            MapTy::BTreeMap => quote! { prost::alloc },
+            MapTy::HashMap => quote! { ::std::collections::HashMap },
+            MapTy::BTreeMap => quote! { ::prost::alloc::collections::BTreeMap },
+            MapTy::Custom(path) => quote! { #path },
         }
     }
 }
@@ -56,10 +61,19 @@ impl Field {
     pub fn new(attrs: &[Meta], inferred_tag: Option<u32>) -> Result<Option<Field>, Error> {
         let mut types = None;
         let mut tag = None;
+        let mut custom_ty = None;
 
         for attr in attrs {
             if let Some(t) = tag_attr(attr)? {
                 set_option(&mut tag, t, "duplicate tag attributes")?;
+            } else if attr.path().is_ident("map_type") {
+                let path = match attr {
+                    Meta::NameValue(MetaNameValue {
+                        lit: Lit::Str(lit), ..
+                    }) => parse_str::<Path>(&lit.value())?,
+                    _ => bail!("invalid map_type attribute: {:?}", attr),
+                };
+                set_option(&mut custom_ty, path, "duplicate map_type attributes")?;
             } else if let Some(map_ty) = attr
                 .path()
                 .get_ident()
@@ -112,6 +126,15 @@ impl Field {
             }
         }
 
+        let types = match (types, custom_ty) {
+            (Some((MapTy::HashMap, key_ty, value_ty)), Some(path)) => {
+                Some((MapTy::Custom(path), key_ty, value_ty))
+            }
+            (Some(_), Some(_)) => bail!("invalid map_type attribute: only valid with `map`"),
+            (None, Some(_)) => bail!("invalid map_type attribute: missing map key and value types"),
+            (types, None) => types,
+        };
+
         Ok(match (types, tag.or(inferred_tag)) {
             (Some((map_ty, key_ty, value_ty)), Some(tag)) => Some(Field {
                 map_ty,
@@ -194,7 +217,7 @@ impl Field {
                         #km,
                         ::prost::encoding::int32::merge,
                         #default,
-                        &mut #ident,
+                        #ident,
                         buf,
                         ctx,
                     )
@@ -203,13 +226,13 @@ impl Field {
             ValueTy::Scalar(value_ty) => {
                 let val_mod = value_ty.module();
                 let vm = quote!(::prost::encoding::#val_mod::merge);
-                quote!(::prost::encoding::#module::merge(#km, #vm, &mut #ident, buf, ctx))
+                quote!(::prost::encoding::#module::merge(#km, #vm, #ident, buf, ctx))
             }
             ValueTy::Message => quote! {
                 ::prost::encoding::#module::merge(
                     #km,
                     ::prost::encoding::message::merge,
-                    &mut #ident,
+                    #ident,
                     buf,
                     ctx,
                 )
@@ -253,7 +276,44 @@ impl Field {
     }
 
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
-        quote!(#ident.clear())
+        match self.map_ty {
+            MapTy::Custom(_) => quote!(::prost::ProstMap::clear(&mut #ident)),
+            _ => quote!(#ident.clear()),
+        }
+    }
+
+    /// Returns an expression which evaluates to `true` if the map has no entries.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.map_ty {
+            MapTy::Custom(_) => quote!(::prost::ProstMap::is_empty(&#ident)),
+            _ => quote!(#ident.is_empty()),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the required fields
+    /// of the message values of the map are set, or `None` if the values are scalars.
+    pub fn check_required(&self, ident: TokenStream) -> Option<TokenStream> {
+        match self.value_ty {
+            ValueTy::Scalar(_) => None,
+            ValueTy::Message => Some(quote! {
+                (&#ident)
+                    .into_iter()
+                    .try_for_each(|(_, value)| ::prost::Message::check_required(value))
+            }),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the values of the
+    /// message values of the map can be encoded, or `None` if the values are scalars.
+    pub fn check_encode(&self, ident: TokenStream) -> Option<TokenStream> {
+        match self.value_ty {
+            ValueTy::Scalar(_) => None,
+            ValueTy::Message => Some(quote! {
+                (&#ident)
+                    .into_iter()
+                    .try_for_each(|(_, value)| ::prost::Message::check_encode(value))
+            }),
+        }
     }
 
     /// Returns methods to embed in the message.
@@ -276,14 +336,24 @@ impl Field {
                 ident,
             );
             let insert_doc = format!("Inserts a key value pair into `{}`.", ident);
+            let (get_value, insert_value) = match self.map_ty {
+                MapTy::Custom(_) => (
+                    quote!(::prost::ProstMap::get(&self.#ident, #take_ref key)),
+                    quote!(::prost::ProstMap::insert(&mut self.#ident, key, value as i32)),
+                ),
+                _ => (
+                    quote!(self.#ident.get(#take_ref key)),
+                    quote!(self.#ident.insert(key, value as i32)),
+                ),
+            };
             Some(quote! {
                 #[doc=#get_doc]
                 pub fn #get(&self, key: #key_ref_ty) -> ::core::option::Option<#ty> {
-                    self.#ident.get(#take_ref key).cloned().and_then(#ty::from_i32)
+                    #get_value.cloned().and_then(#ty::from_i32)
                 }
                 #[doc=#insert_doc]
                 pub fn #insert(&mut self, key: #key_ty, value: #ty) -> ::core::option::Option<#ty> {
-                    self.#ident.insert(key, value as i32).and_then(#ty::from_i32)
+                    #insert_value.and_then(#ty::from_i32)
                 }
             })
         } else {
@@ -296,16 +366,12 @@ impl Field {
     /// The Debug tries to convert any enumerations met into the variants if possible, instead of
     /// outputting the raw numbers.
     pub fn debug(&self, wrapper_name: TokenStream) -> TokenStream {
-        let type_name = match self.map_ty {
-            MapTy::HashMap => Ident::new("HashMap", Span::call_site()),
-            MapTy::BTreeMap => Ident::new("BTreeMap", Span::call_site()),
-        };
+        let type_name = self.map_ty.rust_type();
 
         // A fake field for generating the debug wrapper
         let key_wrapper = fake_scalar(self.key_ty.clone()).debug(quote!(KeyWrapper));
         let key = self.key_ty.rust_type();
         let value_wrapper = self.value_ty.debug();
-        let libname = self.map_ty.lib();
         let fmt = quote! {
             fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 #key_wrapper
@@ -332,14 +398,14 @@ impl Field {
 
                 let value = ty.rust_type();
                 quote! {
-                    struct #wrapper_name<'a>(&'a ::#libname::collections::#type_name<#key, #value>);
+                    struct #wrapper_name<'a>(&'a #type_name<#key, #value>);
                     impl<'a> ::core::fmt::Debug for #wrapper_name<'a> {
                         #fmt
                     }
                 }
             }
             ValueTy::Message => quote! {
-                struct #wrapper_name<'a, V: 'a>(&'a ::#libname::collections::#type_name<#key, V>);
+                struct #wrapper_name<'a, V: 'a>(&'a #type_name<#key, V>);
                 impl<'a, V> ::core::fmt::Debug for #wrapper_name<'a, V>
                 where
                     V: ::core::fmt::Debug + 'a,
@@ -365,7 +431,7 @@ fn key_ty_from_str(s: &str) -> Result<scalar::Ty, Error> {
         | scalar::Ty::Sfixed32
         | scalar::Ty::Sfixed64
         | scalar::Ty::Bool
-        | scalar::Ty::String => Ok(ty),
+        | scalar::Ty::String(scalar::StringTy::String) => Ok(ty),
         _ => bail!("invalid map key type: {}", s),
     }
 }
@@ -1,14 +1,16 @@
 use anyhow::{bail, Error};
-use proc_macro2::TokenStream;
+use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
-use syn::Meta;
+use syn::{Ident, Meta};
 
-use crate::field::{set_bool, set_option, tag_attr, word_attr, Label};
+use crate::field::{set_bool, set_option, tag_attr, word_attr, Label, PresenceBit};
 
 #[derive(Clone)]
 pub struct Field {
     pub label: Label,
     pub tag: u32,
+    /// The presence bit of an optional or required field.
+    pub presence_bit: Option<PresenceBit>,
 }
 
 impl Field {
@@ -17,6 +19,7 @@ impl Field {
         let mut label = None;
         let mut tag = None;
         let mut boxed = false;
+        let mut presence_bit = None;
 
         let mut unknown_attrs = Vec::new();
 
@@ -29,6 +32,8 @@ impl Field {
                 set_option(&mut tag, t, "duplicate tag attributes")?;
             } else if let Some(l) = Label::from_attr(attr) {
                 set_option(&mut label, l, "duplicate label attributes")?;
+            } else if let Some(b) = PresenceBit::from_attr(attr)? {
+                set_option(&mut presence_bit, b, "duplicate presence_bit attributes")?;
             } else {
                 unknown_attrs.push(attr);
             }
@@ -52,9 +57,15 @@ impl Field {
             None => bail!("message field is missing a tag attribute"),
         };
 
+        let label = label.unwrap_or(Label::Optional);
+        if presence_bit.is_some() && label == Label::Repeated {
+            bail!("presence_bit attribute may only be applied to optional or required fields");
+        }
+
         Ok(Some(Field {
-            label: label.unwrap_or(Label::Optional),
+            label,
             tag,
+            presence_bit: presence_bit.map(PresenceBit::new),
         }))
     }
 
@@ -76,10 +87,20 @@ impl Field {
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
         let tag = self.tag;
         match self.label {
-            Label::Optional => quote! {
-                if let Some(ref msg) = #ident {
-                    ::prost::encoding::message::encode(#tag, msg, buf);
+            Label::Optional => match self.presence_bit {
+                Some(ref bit) => {
+                    let is_set = bit.is_set();
+                    quote! {
+                        if #is_set {
+                            ::prost::encoding::message::encode(#tag, &#ident, buf);
+                        }
+                    }
                 }
+                None => quote! {
+                    if let Some(ref msg) = #ident {
+                        ::prost::encoding::message::encode(#tag, msg, buf);
+                    }
+                },
             },
             Label::Required => quote! {
                 ::prost::encoding::message::encode(#tag, &#ident, buf);
@@ -93,6 +114,14 @@ impl Field {
     }
 
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
+        if let Some(ref bit) = self.presence_bit {
+            let bits = bit.bits();
+            let index = bit.index;
+            return quote! {
+                ::prost::encoding::message::merge(wire_type, #ident, buf, ctx)
+                    .map(|()| #bits.set(#index))
+            };
+        }
         match self.label {
             Label::Optional => quote! {
                 ::prost::encoding::message::merge(wire_type,
@@ -112,8 +141,20 @@ impl Field {
     pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
         let tag = self.tag;
         match self.label {
-            Label::Optional => quote! {
-                #ident.as_ref().map_or(0, |msg| ::prost::encoding::message::encoded_len(#tag, msg))
+            Label::Optional => match self.presence_bit {
+                Some(ref bit) => {
+                    let is_set = bit.is_set();
+                    quote! {
+                        if #is_set {
+                            ::prost::encoding::message::encoded_len(#tag, &#ident)
+                        } else {
+                            0
+                        }
+                    }
+                }
+                None => quote! {
+                    #ident.as_ref().map_or(0, |msg| ::prost::encoding::message::encoded_len(#tag, msg))
+                },
             },
             Label::Required => quote! {
                 ::prost::encoding::message::encoded_len(#tag, &#ident)
@@ -124,11 +165,138 @@ impl Field {
         }
     }
 
+    /// Returns a statement which clears the field.
+    ///
+    /// An optional field with a presence bit is cleared in place, keeping the allocations of the
+    /// message it holds, and its presence bit is left unchanged, as the message clears its
+    /// `presence_bits` field.
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.label {
+            Label::Optional if self.presence_bit.is_some() => quote!(#ident.clear()),
             Label::Optional => quote!(#ident = ::core::option::Option::None),
             Label::Required => quote!(#ident.clear()),
             Label::Repeated => quote!(#ident.clear()),
         }
     }
+
+    /// Returns an expression which evaluates to `true` if the field holds its default value.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => match self.presence_bit {
+                Some(ref bit) => {
+                    let is_set = bit.is_set();
+                    quote!(!#is_set)
+                }
+                None => quote!(#ident.is_none()),
+            },
+            Label::Required => match self.presence_bit {
+                Some(ref bit) => {
+                    let is_set = bit.is_set();
+                    quote!(!#is_set && ::prost::Message::is_default(&#ident))
+                }
+                None => quote!(::prost::Message::is_default(&#ident)),
+            },
+            Label::Repeated => quote!(#ident.is_empty()),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the required fields
+    /// of the messages held by the field are set, and that the field is set if it is required and
+    /// has a presence bit.
+    pub fn check_required(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional if self.presence_bit.is_some() => self.check_if_set(
+                quote!(::prost::Message::check_required(&#ident)),
+                quote!(::core::result::Result::Ok(())),
+            ),
+            Label::Optional => quote! {
+                match #ident {
+                    ::core::option::Option::Some(ref msg) => ::prost::Message::check_required(msg),
+                    ::core::option::Option::None => ::core::result::Result::Ok(()),
+                }
+            },
+            Label::Required => match self.presence_bit {
+                Some(_) => self.check_if_set(
+                    quote!(::prost::Message::check_required(&#ident)),
+                    quote! {
+                        ::core::result::Result::Err(
+                            ::prost::DecodeError::new("missing required field"),
+                        )
+                    },
+                ),
+                None => quote!(::prost::Message::check_required(&#ident)),
+            },
+            Label::Repeated => quote! {
+                #ident.iter().try_for_each(::prost::Message::check_required)
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the values of the
+    /// messages held by the field can be encoded.
+    pub fn check_encode(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional if self.presence_bit.is_some() => self.check_if_set(
+                quote!(::prost::Message::check_encode(&#ident)),
+                quote!(::core::result::Result::Ok(())),
+            ),
+            Label::Optional => quote! {
+                match #ident {
+                    ::core::option::Option::Some(ref msg) => ::prost::Message::check_encode(msg),
+                    ::core::option::Option::None => ::core::result::Result::Ok(()),
+                }
+            },
+            Label::Required => quote!(::prost::Message::check_encode(&#ident)),
+            Label::Repeated => quote! {
+                #ident.iter().try_for_each(::prost::Message::check_encode)
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to `set` if the presence bit of the field is set, and
+    /// to `unset` otherwise.
+    fn check_if_set(&self, set: TokenStream, unset: TokenStream) -> TokenStream {
+        let is_set = self.presence_bit.as_ref().unwrap().is_set();
+        quote! {
+            if #is_set {
+                #set
+            } else {
+                #unset
+            }
+        }
+    }
+
+    /// Returns the `has_` method of a field with a presence bit, and the `clear_` method of an
+    /// optional one.
+    pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
+        let bit = self.presence_bit.as_ref()?;
+        let ident_str = ident.to_string();
+        let ident_str = ident_str.trim_start_matches("r#");
+        let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
+        let has_doc = format!("Returns `true` if `{}` is set.", ident_str);
+        let is_set = bit.is_set();
+        let mut methods = quote! {
+            #[doc=#has_doc]
+            pub fn #has(&self) -> bool {
+                #is_set
+            }
+        };
+        if self.label == Label::Optional {
+            let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+            let clear_doc = format!(
+                "Unsets `{}`, clearing the message it holds in place.",
+                ident_str
+            );
+            let bits = bit.bits();
+            let index = bit.index;
+            methods.extend(quote! {
+                #[doc=#clear_doc]
+                pub fn #clear(&mut self) {
+                    ::prost::Message::clear(&mut self.#ident);
+                    #bits.unset(#index);
+                }
+            });
+        }
+        Some(methods)
+    }
 }
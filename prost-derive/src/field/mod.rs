@@ -34,7 +34,13 @@ impl Field {
     pub fn new(attrs: Vec<Attribute>, inferred_tag: Option<u32>) -> Result<Option<Field>, Error> {
         let attrs = prost_attrs(attrs);
 
-        // TODO: check for ignore attribute.
+        // Skipped fields are not part of the message, and are only reset to their default value.
+        if attrs.iter().any(|attr| word_attr("skip", attr)) {
+            if attrs.len() > 1 {
+                bail!("skipped fields may not have other prost attributes");
+            }
+            return Ok(None);
+        }
 
         let field = if let Some(field) = scalar::Field::new(&attrs, inferred_tag)? {
             Field::Scalar(field)
@@ -87,6 +93,27 @@ impl Field {
         }
     }
 
+    /// Returns `true` if the field holds messages, which may be of a type parameter of the message
+    /// or oneof: message and group fields, oneofs, and maps with message values.
+    pub fn holds_messages(&self) -> bool {
+        match *self {
+            Field::Scalar(_) => false,
+            Field::Message(_) | Field::Oneof(_) | Field::Group(_) => true,
+            Field::Map(ref map) => matches!(map.value_ty, map::ValueTy::Message),
+        }
+    }
+
+    /// Returns `true` if the field is an enumeration, whose values are held as `i32`.
+    pub fn is_enumeration(&self) -> bool {
+        matches!(
+            *self,
+            Field::Scalar(scalar::Field {
+                ty: scalar::Ty::Enumeration(_),
+                ..
+            })
+        )
+    }
+
     /// Returns a statement which encodes the field.
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
         match *self {
@@ -132,6 +159,17 @@ impl Field {
         }
     }
 
+    /// Returns an expression which evaluates to `true` if the field holds its default value.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.is_default(ident),
+            Field::Message(ref message) => message.is_default(ident),
+            Field::Map(ref map) => map.is_default(ident),
+            Field::Oneof(ref oneof) => oneof.is_default(ident),
+            Field::Group(ref group) => group.is_default(ident),
+        }
+    }
+
     pub fn default(&self) -> TokenStream {
         match *self {
             Field::Scalar(ref scalar) => scalar.default(),
@@ -144,10 +182,17 @@ impl Field {
         match *self {
             Field::Scalar(ref scalar) => {
                 let wrapper = scalar.debug(quote!(ScalarWrapper));
+                let value = match scalar.kind {
+                    scalar::Kind::Bit(_, ref bit) => {
+                        let is_set = bit.is_set();
+                        quote!(ScalarWrapper(&#ident, #is_set))
+                    }
+                    _ => quote!(ScalarWrapper(&#ident)),
+                };
                 quote! {
                     {
                         #wrapper
-                        ScalarWrapper(&#ident)
+                        #value
                     }
                 }
             }
@@ -160,14 +205,86 @@ impl Field {
                     }
                 }
             }
+            // Formatted like an `Option`, from the message and its presence bit.
+            Field::Message(message::Field {
+                label: Label::Optional,
+                presence_bit: Some(ref bit),
+                ..
+            }) => {
+                let is_set = bit.is_set();
+                quote! {
+                    if #is_set {
+                        ::core::option::Option::Some(&#ident)
+                    } else {
+                        ::core::option::Option::None
+                    }
+                }
+            }
             _ => quote!(&#ident),
         }
     }
 
+    /// Binds the presence bit of the field, if it has one, to the `presence_bits` field of the
+    /// message.
+    pub fn bind_presence_bits(&mut self, bits: Option<&Ident>) -> Result<(), Error> {
+        let bit = match *self {
+            Field::Scalar(scalar::Field {
+                kind: scalar::Kind::Bit(_, ref mut bit),
+                ..
+            })
+            | Field::Scalar(scalar::Field {
+                kind: scalar::Kind::Required(_, Some(ref mut bit)),
+                ..
+            })
+            | Field::Message(message::Field {
+                presence_bit: Some(ref mut bit),
+                ..
+            })
+            | Field::Group(group::Field {
+                presence_bit: Some(ref mut bit),
+                ..
+            }) => bit,
+            _ => return Ok(()),
+        };
+        match bits {
+            Some(bits) => bit.bits = Some(bits.clone()),
+            None => bail!("presence_bit attribute requires a presence_bits field"),
+        }
+        Ok(())
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the required fields
+    /// of the field and of the messages nested in it are set, or `None` if there is nothing to
+    /// check.
+    pub fn check_required(&self, ident: TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.check_required(),
+            Field::Message(ref message) => Some(message.check_required(ident)),
+            Field::Map(ref map) => map.check_required(ident),
+            Field::Oneof(ref oneof) => Some(oneof.check_required(ident)),
+            Field::Group(ref group) => Some(group.check_required(ident)),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the values of the
+    /// field and of the messages nested in it can be encoded, or `None` if there is nothing to
+    /// check.
+    pub fn check_encode(&self, ident: TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.check_encode(ident),
+            Field::Message(ref message) => Some(message.check_encode(ident)),
+            Field::Map(ref map) => map.check_encode(ident),
+            Field::Oneof(ref oneof) => Some(oneof.check_encode(ident)),
+            Field::Group(ref group) => Some(group.check_encode(ident)),
+        }
+    }
+
     pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
         match *self {
             Field::Scalar(ref scalar) => scalar.methods(ident),
+            Field::Message(ref message) => message.methods(ident),
             Field::Map(ref map) => map.methods(ident),
+            Field::Group(ref group) => group.methods(ident),
             _ => None,
         }
     }
@@ -223,8 +340,114 @@ impl fmt::Display for Label {
     }
 }
 
+/// Returns `true` if the attributes mark the field holding the presence bits of the message,
+/// `#[prost(presence_bits)]`.
+/// The presence bit of an optional scalar field or of a required field, set by the `presence_bit`
+/// attribute.
+#[derive(Clone)]
+pub struct PresenceBit {
+    pub index: usize,
+    /// The `presence_bits` field of the message, bound once all fields are parsed.
+    pub bits: Option<Ident>,
+}
+
+impl PresenceBit {
+    fn new(index: usize) -> PresenceBit {
+        PresenceBit { index, bits: None }
+    }
+
+    fn from_attr(attr: &Meta) -> Result<Option<usize>, Error> {
+        if !attr.path().is_ident("presence_bit") {
+            return Ok(None);
+        }
+        match *attr {
+            Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(ref lit),
+                ..
+            }) => Ok(Some(lit.value().parse()?)),
+            Meta::NameValue(MetaNameValue {
+                lit: Lit::Int(ref lit),
+                ..
+            }) => Ok(Some(lit.base10_parse()?)),
+            _ => bail!("invalid presence_bit attribute: {:?}", attr),
+        }
+    }
+
+    /// Returns the `presence_bits` field of the message.
+    fn bits(&self) -> TokenStream {
+        let bits = self
+            .bits
+            .as_ref()
+            .expect("presence bit is not bound to a presence_bits field");
+        quote!(self.#bits)
+    }
+
+    /// Returns an expression which evaluates to `true` if the field is set.
+    pub fn is_set(&self) -> TokenStream {
+        let bits = self.bits();
+        let index = self.index;
+        quote!(#bits.get(#index))
+    }
+
+    /// Returns an expression which evaluates to an error if the field of a required presence bit
+    /// is unset.
+    fn check(&self) -> TokenStream {
+        let is_set = self.is_set();
+        quote! {
+            if #is_set {
+                ::core::result::Result::Ok(())
+            } else {
+                ::core::result::Result::Err(::prost::DecodeError::new("missing required field"))
+            }
+        }
+    }
+}
+
+pub fn is_presence_bits(attrs: &[Attribute]) -> bool {
+    prost_attrs(attrs.to_vec())
+        .iter()
+        .any(|attr| word_attr("presence_bits", attr))
+}
+
+/// Returns `true` if a field is marked `#[prost(skip)]`.
+pub fn is_skipped(attrs: &[Attribute]) -> bool {
+    prost_attrs(attrs.to_vec())
+        .iter()
+        .any(|attr| word_attr("skip", attr))
+}
+
+/// Returns `true` if a struct is marked `#[prost(transparent)]`.
+pub fn is_transparent(attrs: &[Attribute]) -> bool {
+    prost_attrs(attrs.to_vec())
+        .iter()
+        .any(|attr| word_attr("transparent", attr))
+}
+
+/// Returns the value of a `#[prost(key = "...")]` attribute of a struct, such as the function
+/// named by `#[prost(check_encode = "path")]`.
+pub fn struct_str_attr(key: &str, attrs: &[Attribute]) -> Result<Option<String>, Error> {
+    let mut value = None;
+    for attr in prost_attrs(attrs.to_vec()) {
+        if let Meta::NameValue(MetaNameValue {
+            ref path,
+            lit: Lit::Str(ref lit),
+            ..
+        }) = attr
+        {
+            if path.is_ident(key) {
+                set_option(
+                    &mut value,
+                    lit.value(),
+                    &format!("duplicate {} attributes", key),
+                )?;
+            }
+        }
+    }
+    Ok(value)
+}
+
 /// Get the items belonging to the 'prost' list attribute, e.g. `#[prost(foo, bar="baz")]`.
-fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
+pub fn prost_attrs(attrs: Vec<Attribute>) -> Vec<Meta> {
     attrs
         .iter()
         .flat_map(Attribute::parse_meta)
@@ -96,4 +96,31 @@ impl Field {
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         quote!(#ident = ::core::option::Option::None)
     }
+
+    /// Returns an expression which evaluates to `true` if no variant of the oneof is set.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        quote!(#ident.is_none())
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the required fields
+    /// of the message held by the oneof, if any, are set.
+    pub fn check_required(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            match #ident {
+                ::core::option::Option::Some(ref oneof) => oneof.check_required(),
+                ::core::option::Option::None => ::core::result::Result::Ok(()),
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the value held by the
+    /// oneof, if any, can be encoded.
+    pub fn check_encode(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            match #ident {
+                ::core::option::Option::Some(ref oneof) => oneof.check_encode(),
+                ::core::option::Option::None => ::core::result::Result::Ok(()),
+            }
+        }
+    }
 }
@@ -6,7 +6,7 @@ use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens, TokenStreamExt};
 use syn::{parse_str, Ident, Lit, LitByteStr, Meta, MetaList, MetaNameValue, NestedMeta, Path};
 
-use crate::field::{bool_attr, set_option, tag_attr, Label};
+use crate::field::{bool_attr, set_option, tag_attr, Label, PresenceBit};
 
 /// A scalar protobuf field.
 #[derive(Clone)]
@@ -23,6 +23,7 @@ impl Field {
         let mut packed = None;
         let mut default = None;
         let mut tag = None;
+        let mut presence_bit = None;
 
         let mut unknown_attrs = Vec::new();
 
@@ -37,6 +38,8 @@ impl Field {
                 set_option(&mut label, l, "duplicate label attributes")?;
             } else if let Some(d) = DefaultValue::from_attr(attr)? {
                 set_option(&mut default, d, "duplicate default attributes")?;
+            } else if let Some(b) = PresenceBit::from_attr(attr)? {
+                set_option(&mut presence_bit, b, "duplicate presence_bit attributes")?;
             } else {
                 unknown_attrs.push(attr);
             }
@@ -58,6 +61,22 @@ impl Field {
             None => bail!("missing tag attribute"),
         };
 
+        if ty.is_nonzero() {
+            if default.is_some() {
+                bail!("non-zero fields may not have a default value");
+            }
+            if !matches!(label, Some(Label::Optional) | Some(Label::Repeated)) {
+                bail!("non-zero fields must be optional or repeated");
+            }
+            if presence_bit.is_some() {
+                bail!("non-zero fields may not have a presence bit");
+            }
+        }
+        if presence_bit.is_some() && !matches!(label, Some(Label::Optional) | Some(Label::Required))
+        {
+            bail!("presence_bit attribute may only be applied to optional or required fields");
+        }
+
         let has_default = default.is_some();
         let default = default.map_or_else(
             || Ok(DefaultValue::new(&ty)),
@@ -78,8 +97,13 @@ impl Field {
             }
 
             (None, _, _) => Kind::Plain(default),
-            (Some(Label::Optional), _, _) => Kind::Optional(default),
-            (Some(Label::Required), _, _) => Kind::Required(default),
+            (Some(Label::Optional), _, _) => match presence_bit {
+                Some(index) => Kind::Bit(default, PresenceBit::new(index)),
+                None => Kind::Optional(default),
+            },
+            (Some(Label::Required), _, _) => {
+                Kind::Required(default, presence_bit.map(PresenceBit::new))
+            }
             (Some(Label::Repeated), packed, false) if packed.unwrap_or_else(|| ty.is_numeric()) => {
                 Kind::Packed
             }
@@ -93,11 +117,12 @@ impl Field {
         if let Some(mut field) = Field::new(attrs, None)? {
             match field.kind {
                 Kind::Plain(default) => {
-                    field.kind = Kind::Required(default);
+                    field.kind = Kind::Required(default, None);
                     Ok(Some(field))
                 }
                 Kind::Optional(..) => bail!("invalid optional attribute on oneof field"),
                 Kind::Required(..) => bail!("invalid required attribute on oneof field"),
+                Kind::Bit(..) => bail!("invalid presence_bit attribute on oneof field"),
                 Kind::Packed | Kind::Repeated => bail!("invalid repeated attribute on oneof field"),
             }
         } else {
@@ -108,7 +133,9 @@ impl Field {
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
         let module = self.ty.module();
         let encode_fn = match self.kind {
-            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encode),
+            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) | Kind::Bit(..) => {
+                quote!(encode)
+            }
             Kind::Repeated => quote!(encode_repeated),
             Kind::Packed => quote!(encode_packed),
         };
@@ -117,9 +144,9 @@ impl Field {
 
         match self.kind {
             Kind::Plain(ref default) => {
-                let default = default.typed();
+                let is_set = self.is_set(&ident, default);
                 quote! {
-                    if #ident != #default {
+                    if #is_set {
                         #encode_fn(#tag, &#ident, buf);
                     }
                 }
@@ -129,24 +156,59 @@ impl Field {
                     #encode_fn(#tag, value, buf);
                 }
             },
+            Kind::Bit(_, ref bit) => {
+                let is_set = bit.is_set();
+                quote! {
+                    if #is_set {
+                        #encode_fn(#tag, &#ident, buf);
+                    }
+                }
+            }
             Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
                 #encode_fn(#tag, &#ident, buf);
             },
         }
     }
 
+    /// Returns an expression which evaluates to `true` if a plain field differs from its default
+    /// value, and should therefore be encoded.
+    ///
+    /// Floating point fields are compared by bit pattern, so that `-0.0` is encoded when the
+    /// default is `0.0`, and the sign of zero survives a round-trip.
+    fn is_set(&self, ident: &TokenStream, default: &DefaultValue) -> TokenStream {
+        let default = default.typed();
+        match self.ty {
+            Ty::Float => quote! {
+                ::core::primitive::f32::to_bits(#ident) != ::core::primitive::f32::to_bits(#default)
+            },
+            Ty::Double => quote! {
+                ::core::primitive::f64::to_bits(#ident) != ::core::primitive::f64::to_bits(#default)
+            },
+            _ if self.ty.is_adapted_string() => quote! {
+                ::core::ops::Deref::deref(&#ident) != #default
+            },
+            _ => quote!(#ident != #default),
+        }
+    }
+
     /// Returns an expression which evaluates to the result of merging a decoded
     /// scalar value into the field.
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
         let module = self.ty.module();
         let merge_fn = match self.kind {
-            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(merge),
+            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) | Kind::Bit(..) => {
+                quote!(merge)
+            }
             Kind::Repeated | Kind::Packed => quote!(merge_repeated),
         };
         let merge_fn = quote!(::prost::encoding::#module::#merge_fn);
 
         match self.kind {
-            Kind::Plain(..) | Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
+            Kind::Plain(..) | Kind::Required(_, None) | Kind::Repeated | Kind::Packed => quote! {
+                #merge_fn(wire_type, #ident, buf, ctx)
+            },
+            // Non-zero types have no default value, so they are merged into the `Option` itself.
+            Kind::Optional(..) if self.ty.is_nonzero() => quote! {
                 #merge_fn(wire_type, #ident, buf, ctx)
             },
             Kind::Optional(..) => quote! {
@@ -155,6 +217,13 @@ impl Field {
                           buf,
                           ctx)
             },
+            Kind::Required(_, Some(ref bit)) | Kind::Bit(_, ref bit) => {
+                let bits = bit.bits();
+                let index = bit.index;
+                quote! {
+                    #merge_fn(wire_type, #ident, buf, ctx).map(|()| #bits.set(#index))
+                }
+            }
         }
     }
 
@@ -162,7 +231,9 @@ impl Field {
     pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
         let module = self.ty.module();
         let encoded_len_fn = match self.kind {
-            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encoded_len),
+            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) | Kind::Bit(..) => {
+                quote!(encoded_len)
+            }
             Kind::Repeated => quote!(encoded_len_repeated),
             Kind::Packed => quote!(encoded_len_packed),
         };
@@ -171,9 +242,9 @@ impl Field {
 
         match self.kind {
             Kind::Plain(ref default) => {
-                let default = default.typed();
+                let is_set = self.is_set(&ident, default);
                 quote! {
-                    if #ident != #default {
+                    if #is_set {
                         #encoded_len_fn(#tag, &#ident)
                     } else {
                         0
@@ -183,18 +254,56 @@ impl Field {
             Kind::Optional(..) => quote! {
                 #ident.as_ref().map_or(0, |value| #encoded_len_fn(#tag, value))
             },
+            Kind::Bit(_, ref bit) => {
+                let is_set = bit.is_set();
+                quote! {
+                    if #is_set {
+                        #encoded_len_fn(#tag, &#ident)
+                    } else {
+                        0
+                    }
+                }
+            }
             Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
                 #encoded_len_fn(#tag, &#ident)
             },
         }
     }
 
+    /// Returns a statement which resets the field to its default value.
+    ///
+    /// The presence bit of a field is left unchanged, as the message clears its `presence_bits`
+    /// field as a whole.
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.kind {
-            Kind::Plain(ref default) | Kind::Required(ref default) => {
+            // Cleared fields hold their default value, as in a default message, so fields with a
+            // non-empty default are assigned it rather than cleared in place.
+            Kind::Plain(DefaultValue::String(ref value))
+            | Kind::Required(DefaultValue::String(ref value), _)
+            | Kind::Bit(DefaultValue::String(ref value), _)
+                if !value.is_empty() =>
+            {
+                let default = self.default();
+                quote!(#ident = #default)
+            }
+            Kind::Plain(DefaultValue::Bytes(ref value))
+            | Kind::Required(DefaultValue::Bytes(ref value), _)
+            | Kind::Bit(DefaultValue::Bytes(ref value), _)
+                if !value.is_empty() =>
+            {
+                let default = self.default();
+                quote!(#ident = #default)
+            }
+            Kind::Plain(ref default)
+            | Kind::Required(ref default, _)
+            | Kind::Bit(ref default, _) => {
                 let default = default.typed();
                 match self.ty {
-                    Ty::String | Ty::Bytes(..) => quote!(#ident.clear()),
+                    Ty::String(StringTy::String) | Ty::Bytes(..) => quote!(#ident.clear()),
+                    Ty::String(..) => {
+                        let default = self.default();
+                        quote!(#ident = #default)
+                    }
                     _ => quote!(#ident = #default),
                 }
             }
@@ -203,10 +312,42 @@ impl Field {
         }
     }
 
+    /// Returns an expression which evaluates to `true` if the field holds its default value, and
+    /// its presence bit, if any, is unset.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.kind {
+            Kind::Plain(ref default) | Kind::Required(ref default, None) => {
+                let is_set = self.is_set(&ident, default);
+                quote!(!(#is_set))
+            }
+            Kind::Required(ref default, Some(ref bit)) | Kind::Bit(ref default, ref bit) => {
+                let is_set = self.is_set(&ident, default);
+                let bit_is_set = bit.is_set();
+                quote!(!#bit_is_set && !(#is_set))
+            }
+            Kind::Optional(_) => quote!(#ident.is_none()),
+            Kind::Repeated | Kind::Packed => quote!(#ident.is_empty()),
+        }
+    }
+
     /// Returns an expression which evaluates to the default value of the field.
     pub fn default(&self) -> TokenStream {
         match self.kind {
-            Kind::Plain(ref value) | Kind::Required(ref value) => value.owned(),
+            Kind::Plain(DefaultValue::String(ref value))
+            | Kind::Required(DefaultValue::String(ref value), _)
+            | Kind::Bit(DefaultValue::String(ref value), _)
+                if self.ty.is_adapted_string() =>
+            {
+                let ty = self.ty.rust_type();
+                if value.is_empty() {
+                    quote!(::core::default::Default::default())
+                } else {
+                    quote!(<#ty as ::prost::StringAdapter>::copy_from_str(#value))
+                }
+            }
+            Kind::Plain(ref value) | Kind::Required(ref value, _) | Kind::Bit(ref value, _) => {
+                value.owned()
+            }
             Kind::Optional(_) => quote!(::core::option::Option::None),
             Kind::Repeated | Kind::Packed => quote!(::prost::alloc::vec::Vec::new()),
         }
@@ -238,7 +379,22 @@ impl Field {
         let wrapper = self.debug_inner(quote!(Inner));
         let inner_ty = self.ty.rust_type();
         match self.kind {
-            Kind::Plain(_) | Kind::Required(_) => self.debug_inner(wrapper_name),
+            Kind::Plain(_) | Kind::Required(..) => self.debug_inner(wrapper_name),
+            // Formatted like an `Option`, from the value and its presence bit.
+            Kind::Bit(..) => quote! {
+                struct #wrapper_name<'a>(&'a #inner_ty, bool);
+                impl<'a> ::core::fmt::Debug for #wrapper_name<'a> {
+                    fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                        #wrapper
+                        let value = if self.1 {
+                            ::core::option::Option::Some(Inner(self.0))
+                        } else {
+                            ::core::option::Option::None
+                        };
+                        ::core::fmt::Debug::fmt(&value, f)
+                    }
+                }
+            },
             Kind::Optional(_) => quote! {
                 struct #wrapper_name<'a>(&'a ::core::option::Option<#inner_ty>);
                 impl<'a> ::core::fmt::Debug for #wrapper_name<'a> {
@@ -266,6 +422,37 @@ impl Field {
         }
     }
 
+    /// Returns an expression which evaluates to the result of checking that a required field with
+    /// a presence bit is set.
+    pub fn check_required(&self) -> Option<TokenStream> {
+        match self.kind {
+            Kind::Required(_, Some(ref bit)) => Some(bit.check()),
+            _ => None,
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of checking that the values of a field
+    /// mapped to a custom scalar type can be encoded, or `None` for other types.
+    pub fn check_encode(&self, ident: TokenStream) -> Option<TokenStream> {
+        if !self.ty.is_custom() {
+            return None;
+        }
+        Some(match self.kind {
+            Kind::Plain(..) | Kind::Required(..) | Kind::Bit(..) => {
+                quote!(::prost::CheckEncode::check_encode(&#ident))
+            }
+            Kind::Optional(..) => quote! {
+                match #ident {
+                    ::core::option::Option::Some(ref value) => ::prost::CheckEncode::check_encode(value),
+                    ::core::option::Option::None => ::core::result::Result::Ok(()),
+                }
+            },
+            Kind::Repeated | Kind::Packed => quote! {
+                #ident.iter().try_for_each(::prost::CheckEncode::check_encode)
+            },
+        })
+    }
+
     /// Returns methods to embed in the message.
     pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
         let mut ident_str = ident.to_string();
@@ -277,7 +464,7 @@ impl Field {
             let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
             let set_doc = format!("Sets `{}` to the provided enum value.", ident_str);
             Some(match self.kind {
-                Kind::Plain(ref default) | Kind::Required(ref default) => {
+                Kind::Plain(ref default) | Kind::Required(ref default, None) => {
                     let get_doc = format!(
                         "Returns the enum value of `{}`, \
                          or the default if the field is set to an invalid enum value.",
@@ -313,6 +500,12 @@ impl Field {
                         }
                     }
                 }
+                Kind::Required(ref default, Some(ref bit)) => {
+                    self.bit_methods(ident, &ident_str, default, bit, true)
+                }
+                Kind::Bit(ref default, ref bit) => {
+                    self.bit_methods(ident, &ident_str, default, bit, false)
+                }
                 Kind::Repeated | Kind::Packed => {
                     let iter_doc = format!(
                         "Returns an iterator which yields the valid enum values contained in `{}`.",
@@ -335,6 +528,12 @@ impl Field {
                     }
                 }
             })
+        } else if self.ty.is_nonzero() {
+            None
+        } else if let Kind::Required(ref default, Some(ref bit)) = self.kind {
+            Some(self.bit_methods(ident, &ident_str, default, bit, true))
+        } else if let Kind::Bit(ref default, ref bit) = self.kind {
+            Some(self.bit_methods(ident, &ident_str, default, bit, false))
         } else if let Kind::Optional(ref default) = self.kind {
             let ty = self.ty.rust_ref_type();
 
@@ -362,6 +561,92 @@ impl Field {
             None
         }
     }
+
+    /// Returns the getter, setter, `has_` and `clear_` methods of a field with a presence bit.
+    ///
+    /// The getter of an optional field returns the default value if the field is unset, while the
+    /// getter of a required field returns the value which is encoded, whether or not it is set.
+    fn bit_methods(
+        &self,
+        ident: &Ident,
+        ident_str: &str,
+        default: &DefaultValue,
+        bit: &PresenceBit,
+        required: bool,
+    ) -> TokenStream {
+        let set = Ident::new(&format!("set_{}", ident_str), Span::call_site());
+        let has = Ident::new(&format!("has_{}", ident_str), Span::call_site());
+        let clear = Ident::new(&format!("clear_{}", ident_str), Span::call_site());
+        let bits = bit.bits();
+        let is_set = bit.is_set();
+        let index = bit.index;
+        let clear_value = self.clear(quote!(self.#ident));
+
+        let (get_ty, get_value, set_ty, set_value) = match self.ty {
+            Ty::Enumeration(ref ty) => (
+                quote!(#ty),
+                quote!(#ty::from_i32(self.#ident).unwrap_or(#default)),
+                quote!(#ty),
+                quote!(value as i32),
+            ),
+            _ if self.ty.is_numeric() => (
+                self.ty.rust_ref_type(),
+                quote!(self.#ident),
+                self.ty.rust_type(),
+                quote!(value),
+            ),
+            _ => (
+                self.ty.rust_ref_type(),
+                quote!(&self.#ident[..]),
+                self.ty.rust_type(),
+                quote!(value),
+            ),
+        };
+
+        let (get_doc, get_value) = if required {
+            (format!("Returns the value of `{}`.", ident_str), get_value)
+        } else {
+            (
+                format!(
+                    "Returns the value of `{0}`, or the default value if `{0}` is unset.",
+                    ident_str,
+                ),
+                quote! {
+                    if #is_set {
+                        #get_value
+                    } else {
+                        #default
+                    }
+                },
+            )
+        };
+        let set_doc = format!("Sets `{}` to the provided value.", ident_str);
+        let has_doc = format!("Returns `true` if `{}` is set.", ident_str);
+        let clear_doc = format!("Unsets `{}`, resetting it to the default value.", ident_str);
+        quote! {
+            #[doc=#get_doc]
+            pub fn #ident(&self) -> #get_ty {
+                #get_value
+            }
+
+            #[doc=#set_doc]
+            pub fn #set(&mut self, value: #set_ty) {
+                self.#ident = #set_value;
+                #bits.set(#index);
+            }
+
+            #[doc=#has_doc]
+            pub fn #has(&self) -> bool {
+                #is_set
+            }
+
+            #[doc=#clear_doc]
+            pub fn #clear(&mut self) {
+                #clear_value;
+                #bits.unset(#index);
+            }
+        }
+    }
 }
 
 /// A scalar protobuf field type.
@@ -380,9 +665,15 @@ pub enum Ty {
     Sfixed32,
     Sfixed64,
     Bool,
-    String,
+    String(StringTy),
     Bytes(BytesTy),
     Enumeration(Path),
+    /// A `uint32` field mapped to `char`.
+    Char,
+    /// A `uint32` field mapped to `NonZeroU32`.
+    NonZeroUint32,
+    /// A `uint64` field mapped to `NonZeroU64`.
+    NonZeroUint64,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -408,6 +699,42 @@ impl BytesTy {
     }
 }
 
+/// The Rust type of a `string` field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StringTy {
+    String,
+    Cow,
+    Bytes,
+    SmolStr,
+    /// A type implementing `prost::StringAdapter`.
+    Custom(Path),
+}
+
+impl StringTy {
+    fn try_from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "string" => Ok(StringTy::String),
+            "cow" => Ok(StringTy::Cow),
+            "bytes" => Ok(StringTy::Bytes),
+            "smol_str" => Ok(StringTy::SmolStr),
+            _ => match parse_str::<Path>(s) {
+                Ok(path) => Ok(StringTy::Custom(path)),
+                Err(_) => bail!("Invalid string type: {}", s),
+            },
+        }
+    }
+
+    fn rust_type(&self) -> TokenStream {
+        match self {
+            StringTy::String => quote! { ::prost::alloc::string::String },
+            StringTy::Cow => quote! { ::prost::alloc::borrow::Cow<'static, str> },
+            StringTy::Bytes => quote! { ::prost::ByteStr },
+            StringTy::SmolStr => quote! { ::prost::smol_str::SmolStr },
+            StringTy::Custom(path) => quote! { #path },
+        }
+    }
+}
+
 impl Ty {
     pub fn from_attr(attr: &Meta) -> Result<Option<Ty>, Error> {
         let ty = match *attr {
@@ -424,13 +751,35 @@ impl Ty {
             Meta::Path(ref name) if name.is_ident("sfixed32") => Ty::Sfixed32,
             Meta::Path(ref name) if name.is_ident("sfixed64") => Ty::Sfixed64,
             Meta::Path(ref name) if name.is_ident("bool") => Ty::Bool,
-            Meta::Path(ref name) if name.is_ident("string") => Ty::String,
+            Meta::Path(ref name) if name.is_ident("string") => Ty::String(StringTy::String),
             Meta::Path(ref name) if name.is_ident("bytes") => Ty::Bytes(BytesTy::Vec),
             Meta::NameValue(MetaNameValue {
                 ref path,
                 lit: Lit::Str(ref l),
                 ..
             }) if path.is_ident("bytes") => Ty::Bytes(BytesTy::try_from_str(&l.value())?),
+            Meta::NameValue(MetaNameValue {
+                ref path,
+                lit: Lit::Str(ref l),
+                ..
+            }) if path.is_ident("string") => Ty::String(StringTy::try_from_str(&l.value())?),
+            Meta::NameValue(MetaNameValue {
+                ref path,
+                lit: Lit::Str(ref l),
+                ..
+            }) if path.is_ident("uint32") => match &*l.value() {
+                "char" => Ty::Char,
+                "nonzero" => Ty::NonZeroUint32,
+                other => bail!("invalid uint32 type: {}", other),
+            },
+            Meta::NameValue(MetaNameValue {
+                ref path,
+                lit: Lit::Str(ref l),
+                ..
+            }) if path.is_ident("uint64") => match &*l.value() {
+                "nonzero" => Ty::NonZeroUint64,
+                other => bail!("invalid uint64 type: {}", other),
+            },
             Meta::NameValue(MetaNameValue {
                 ref path,
                 lit: Lit::Str(ref l),
@@ -474,7 +823,7 @@ impl Ty {
             "sfixed32" => Ty::Sfixed32,
             "sfixed64" => Ty::Sfixed64,
             "bool" => Ty::Bool,
-            "string" => Ty::String,
+            "string" => Ty::String(StringTy::String),
             "bytes" => Ty::Bytes(BytesTy::Vec),
             s if s.len() > enumeration_len && &s[..enumeration_len] == "enumeration" => {
                 let s = &s[enumeration_len..].trim();
@@ -510,16 +859,18 @@ impl Ty {
             Ty::Sfixed32 => "sfixed32",
             Ty::Sfixed64 => "sfixed64",
             Ty::Bool => "bool",
-            Ty::String => "string",
+            Ty::String(..) => "string",
             Ty::Bytes(..) => "bytes",
             Ty::Enumeration(..) => "enum",
+            Ty::Char | Ty::NonZeroUint32 => "uint32",
+            Ty::NonZeroUint64 => "uint64",
         }
     }
 
     // TODO: rename to 'owned_type'.
     pub fn rust_type(&self) -> TokenStream {
         match self {
-            Ty::String => quote!(::prost::alloc::string::String),
+            Ty::String(ty) => ty.rust_type(),
             Ty::Bytes(ty) => ty.rust_type(),
             _ => self.rust_ref_type(),
         }
@@ -541,22 +892,45 @@ impl Ty {
             Ty::Sfixed32 => quote!(i32),
             Ty::Sfixed64 => quote!(i64),
             Ty::Bool => quote!(bool),
-            Ty::String => quote!(&str),
+            Ty::String(..) => quote!(&str),
             Ty::Bytes(..) => quote!(&[u8]),
             Ty::Enumeration(..) => quote!(i32),
+            Ty::Char => quote!(char),
+            Ty::NonZeroUint32 => quote!(::core::num::NonZeroU32),
+            Ty::NonZeroUint64 => quote!(::core::num::NonZeroU64),
         }
     }
 
     pub fn module(&self) -> Ident {
         match *self {
             Ty::Enumeration(..) => Ident::new("int32", Span::call_site()),
+            Ty::Char => Ident::new("char", Span::call_site()),
+            Ty::NonZeroUint32 => Ident::new("nonzero_uint32", Span::call_site()),
+            Ty::NonZeroUint64 => Ident::new("nonzero_uint64", Span::call_site()),
+            _ if self.is_adapted_string() => Ident::new("string_adapter", Span::call_site()),
             _ => Ident::new(self.as_str(), Span::call_site()),
         }
     }
 
+    /// Returns true if the scalar type is a non-zero integer, which has no default value.
+    pub fn is_nonzero(&self) -> bool {
+        matches!(self, Ty::NonZeroUint32 | Ty::NonZeroUint64)
+    }
+
+    /// Returns true if the scalar type is a `string` held in another type than `String`.
+    pub fn is_adapted_string(&self) -> bool {
+        matches!(self, Ty::String(ty) if *ty != StringTy::String)
+    }
+
+    /// Returns true if the scalar type is a custom mapping of a Protobuf type, whose values are
+    /// checked with `prost::CheckEncode`: a `char`, a non-zero integer or an adapted `string`.
+    pub fn is_custom(&self) -> bool {
+        *self == Ty::Char || self.is_nonzero() || self.is_adapted_string()
+    }
+
     /// Returns false if the scalar type is length delimited (i.e., `string` or `bytes`).
     pub fn is_numeric(&self) -> bool {
-        !matches!(self, Ty::String | Ty::Bytes(..))
+        !matches!(self, Ty::String(..) | Ty::Bytes(..))
     }
 }
 
@@ -579,8 +953,11 @@ pub enum Kind {
     Plain(DefaultValue),
     /// An optional scalar field.
     Optional(DefaultValue),
-    /// A required proto2 scalar field.
-    Required(DefaultValue),
+    /// A required proto2 scalar field, with an optional presence bit.
+    Required(DefaultValue, Option<PresenceBit>),
+    /// An optional scalar field held as a plain value, whose presence is a bit of the message's
+    /// `presence_bits` field.
+    Bit(DefaultValue, PresenceBit),
     /// A repeated scalar field.
     Repeated,
     /// A packed repeated scalar field.
@@ -599,6 +976,7 @@ pub enum DefaultValue {
     Bool(bool),
     String(String),
     Bytes(Vec<u8>),
+    Char(char),
     Enumeration(TokenStream),
     Path(Path),
 }
@@ -647,8 +1025,17 @@ impl DefaultValue {
             }
             Lit::Int(ref lit) if *ty == Ty::Double => DefaultValue::F64(lit.base10_parse()?),
 
+            Lit::Int(ref lit) if *ty == Ty::Char && empty_or_is("u32", lit.suffix()) => {
+                let value = lit.base10_parse()?;
+                match std::char::from_u32(value) {
+                    Some(value) => DefaultValue::Char(value),
+                    None => bail!("invalid char default value: {}", value),
+                }
+            }
+            Lit::Char(ref lit) if *ty == Ty::Char => DefaultValue::Char(lit.value()),
+
             Lit::Bool(ref lit) if *ty == Ty::Bool => DefaultValue::Bool(lit.value),
-            Lit::Str(ref lit) if *ty == Ty::String => DefaultValue::String(lit.value()),
+            Lit::Str(ref lit) if matches!(ty, Ty::String(..)) => DefaultValue::String(lit.value()),
             Lit::ByteStr(ref lit)
                 if *ty == Ty::Bytes(BytesTy::Bytes) || *ty == Ty::Bytes(BytesTy::Vec) =>
             {
@@ -757,9 +1144,13 @@ impl DefaultValue {
             Ty::Uint64 | Ty::Fixed64 => DefaultValue::U64(0),
 
             Ty::Bool => DefaultValue::Bool(false),
-            Ty::String => DefaultValue::String(String::new()),
+            Ty::String(..) => DefaultValue::String(String::new()),
             Ty::Bytes(..) => DefaultValue::Bytes(Vec::new()),
             Ty::Enumeration(ref path) => DefaultValue::Enumeration(quote!(#path::default())),
+            Ty::Char => DefaultValue::Char('\0'),
+            // Never used, since non-zero fields are always optional or repeated.
+            Ty::NonZeroUint32 => DefaultValue::U32(0),
+            Ty::NonZeroUint64 => DefaultValue::U64(0),
         }
     }
 
@@ -800,6 +1191,7 @@ impl ToTokens for DefaultValue {
             DefaultValue::U32(value) => value.to_tokens(tokens),
             DefaultValue::U64(value) => value.to_tokens(tokens),
             DefaultValue::Bool(value) => value.to_tokens(tokens),
+            DefaultValue::Char(value) => value.to_tokens(tokens),
             DefaultValue::String(ref value) => value.to_tokens(tokens),
             DefaultValue::Bytes(ref value) => {
                 let byte_str = LitByteStr::new(value, Span::call_site());
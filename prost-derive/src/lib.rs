@@ -5,19 +5,43 @@
 extern crate alloc;
 extern crate proc_macro;
 
-use anyhow::{bail, Error};
+use anyhow::{anyhow, bail, Error};
 use itertools::Itertools;
 use proc_macro::TokenStream;
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenTree};
 use quote::quote;
 use syn::{
-    punctuated::Punctuated, Data, DataEnum, DataStruct, DeriveInput, Expr, Fields, FieldsNamed,
-    FieldsUnnamed, Ident, Variant,
+    parse_quote, punctuated::Punctuated, Data, DataEnum, DataStruct, DeriveInput, Expr, Fields,
+    FieldsNamed, FieldsUnnamed, Generics, Ident, Index, Member, Path, Type, Variant,
 };
 
 mod field;
 use crate::field::Field;
 
+/// Returns the generics of a message or oneof, with `Message + Default` bounds on the type
+/// parameters used by the types of its message fields, such as `T` in `Page<T>`.
+///
+/// Type parameters which are only used by other fields, such as skipped fields holding a cache
+/// or a `PhantomData`, keep the bounds they are declared with.
+fn message_generics(generics: &Generics, message_types: &[&Type]) -> Generics {
+    let mut generics = generics.clone();
+    let params: Vec<Ident> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .filter(|param| {
+            let param = [param.to_string()];
+            message_types.iter().any(|ty| uses_type_params(ty, &param))
+        })
+        .collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::prost::Message + ::core::default::Default));
+    }
+    generics
+}
+
 fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     let input: DeriveInput = syn::parse(input)?;
 
@@ -29,10 +53,8 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         Data::Union(..) => bail!("Message can not be derived for a union"),
     };
 
-    let generics = &input.generics;
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-
-    let fields = match variant_data {
+    let is_struct = matches!(variant_data.fields, Fields::Named(..));
+    let fields: Vec<syn::Field> = match variant_data {
         DataStruct {
             fields: Fields::Named(FieldsNamed { named: fields, .. }),
             ..
@@ -50,27 +72,78 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         } => Vec::new(),
     };
 
+    if field::is_transparent(&input.attrs) {
+        let types: Vec<&Type> = fields.iter().map(|field| &field.ty).collect();
+        let generics = message_generics(&input.generics, &types);
+        return try_transparent_message(ident, &generics, is_struct, fields);
+    }
+    let check_encode_fn = field::struct_str_attr("check_encode", &input.attrs)?
+        .map(|path| syn::parse_str::<Path>(&path))
+        .transpose()?;
+
     let mut next_tag: u32 = 1;
+    let mut presence_bits = Vec::new();
+    let mut skipped_fields = Vec::new();
+    let mut message_types = Vec::new();
     let mut fields = fields
         .into_iter()
         .enumerate()
         .flat_map(|(idx, field)| {
-            let field_ident = field
-                .ident
-                .unwrap_or_else(|| Ident::new(&idx.to_string(), Span::call_site()));
+            let field_ident = member(field.ident, idx);
+            if field::is_presence_bits(&field.attrs) {
+                match field_ident {
+                    Member::Named(field_ident) => presence_bits.push(field_ident),
+                    Member::Unnamed(_) => {
+                        return Some(Err(anyhow!(
+                            "presence_bits field of message {} must be named",
+                            ident
+                        )))
+                    }
+                }
+                return None;
+            }
+            let ty = field.ty;
             match Field::new(field.attrs, Some(next_tag)) {
                 Ok(Some(field)) => {
                     next_tag = field.tags().iter().max().map(|t| t + 1).unwrap_or(next_tag);
+                    if field.holds_messages() {
+                        message_types.push(ty);
+                    }
                     Some(Ok((field_ident, field)))
                 }
-                Ok(None) => None,
-                Err(err) => Some(Err(
-                    err.context(format!("invalid message field {}.{}", ident, field_ident))
-                )),
+                Ok(None) => {
+                    skipped_fields.push(field_ident);
+                    None
+                }
+                Err(err) => Some(Err(err.context(format!(
+                    "invalid message field {}.{}",
+                    ident,
+                    quote!(#field_ident)
+                )))),
             }
         })
         .collect::<Result<Vec<_>, _>>()?;
 
+    if presence_bits.len() > 1 {
+        bail!("message {} has multiple presence_bits fields", ident);
+    }
+    let presence_bits = presence_bits.pop();
+    for &mut (ref field_ident, ref mut field) in &mut fields {
+        field
+            .bind_presence_bits(presence_bits.as_ref())
+            .map_err(|err| {
+                err.context(format!(
+                    "invalid message field {}.{}",
+                    ident,
+                    quote!(#field_ident)
+                ))
+            })?;
+    }
+
+    let message_types: Vec<&Type> = message_types.iter().collect();
+    let generics = message_generics(&input.generics, &message_types);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
     // We want Debug to be in declaration order
     let unsorted_fields = fields.clone();
 
@@ -109,7 +182,7 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             #(#tags)* => {
                 let mut value = &mut self.#field_ident;
                 #merge.map_err(|mut error| {
-                    error.push(STRUCT_NAME, stringify!(#field_ident));
+                    error.push_field(STRUCT_NAME, stringify!(#field_ident), tag);
                     error
                 })
             },
@@ -124,21 +197,101 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         )
     };
 
-    // TODO
-    let is_struct = true;
+    let mut clear = fields
+        .iter()
+        .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)))
+        .collect::<Vec<_>>();
 
-    let clear = fields
+    let is_default = fields
         .iter()
-        .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)));
+        .map(|&(ref field_ident, ref field)| field.is_default(quote!(self.#field_ident)));
 
-    let default = fields.iter().map(|&(ref field_ident, ref field)| {
-        let value = field.default();
-        quote!(#field_ident: #value,)
-    });
+    let mut default = fields
+        .iter()
+        .map(|&(ref field_ident, ref field)| {
+            let value = field.default();
+            quote!(#field_ident: #value,)
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(ref presence_bits) = presence_bits {
+        clear.push(quote!(self.#presence_bits = ::core::default::Default::default()));
+        default.push(quote!(#presence_bits: ::core::default::Default::default(),));
+    }
+    for skipped_field in &skipped_fields {
+        clear.push(quote!(self.#skipped_field = ::core::default::Default::default()));
+        default.push(quote!(#skipped_field: ::core::default::Default::default(),));
+    }
+
+    let check_required = fields
+        .iter()
+        .flat_map(|&(ref field_ident, ref field)| {
+            let check = field.check_required(quote!(self.#field_ident))?;
+            // The number of a oneof field depends on its variant, so only its name is pushed.
+            let push = match field.tags().as_slice() {
+                [tag] => quote!(error.push_field(STRUCT_NAME, stringify!(#field_ident), #tag)),
+                _ => quote!(error.push(STRUCT_NAME, stringify!(#field_ident))),
+            };
+            Some(quote! {
+                if let ::core::result::Result::Err(mut error) = #check {
+                    #push;
+                    return ::core::result::Result::Err(error);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    let check_required = if check_required.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn check_required(&self) -> ::core::result::Result<(), ::prost::DecodeError> {
+                #struct_name
+                #(#check_required)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    };
+
+    let check_encode = fields
+        .iter()
+        .flat_map(|&(ref field_ident, ref field)| {
+            let check = field.check_encode(quote!(self.#field_ident))?;
+            Some(quote! {
+                if let ::core::result::Result::Err(mut error) = #check {
+                    error.push(STRUCT_NAME, stringify!(#field_ident));
+                    return ::core::result::Result::Err(error);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    // The function named by `#[prost(check_encode = "...")]` checks the message as a whole, before
+    // its fields.
+    let check_message = check_encode_fn.map(|check| quote!(#check(self)?;));
+    let check_encode = if check_encode.is_empty() && check_message.is_none() {
+        quote!()
+    } else {
+        let struct_name = if check_encode.is_empty() {
+            quote!()
+        } else {
+            struct_name.clone()
+        };
+        quote! {
+            fn check_encode(&self) -> ::core::result::Result<(), ::prost::InvalidValueError> {
+                #struct_name
+                #check_message
+                #(#check_encode)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    };
 
+    // The accessors are named after the fields, so fields of tuple structs have none.
     let methods = fields
         .iter()
-        .flat_map(|&(ref field_ident, ref field)| field.methods(field_ident))
+        .flat_map(|&(ref field_ident, ref field)| match field_ident {
+            Member::Named(field_ident) => field.methods(field_ident),
+            Member::Unnamed(_) => None,
+        })
         .collect::<Vec<_>>();
     let methods = if methods.is_empty() {
         quote!()
@@ -202,6 +355,14 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             fn clear(&mut self) {
                 #(#clear;)*
             }
+
+            fn is_default(&self) -> bool {
+                true #(&& #is_default)*
+            }
+
+            #check_required
+
+            #check_encode
         }
 
         impl #impl_generics ::core::default::Default for #ident #ty_generics #where_clause {
@@ -226,6 +387,106 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     Ok(expanded.into())
 }
 
+/// Returns the member naming a field of a struct, which is its index in a tuple struct.
+fn member(ident: Option<Ident>, idx: usize) -> Member {
+    match ident {
+        Some(ident) => Member::Named(ident),
+        None => Member::Unnamed(Index::from(idx)),
+    }
+}
+
+/// Derives `Message` for a `#[prost(transparent)]` struct, which wraps a single message and is
+/// encoded exactly as the message it wraps.
+fn try_transparent_message(
+    ident: Ident,
+    generics: &Generics,
+    is_struct: bool,
+    fields: Vec<syn::Field>,
+) -> Result<TokenStream, Error> {
+    if fields.len() != 1 {
+        bail!(
+            "transparent message {} must have exactly one field, found {}",
+            ident,
+            fields.len()
+        );
+    }
+    let field = fields.into_iter().next().unwrap();
+    if !field::prost_attrs(field.attrs).is_empty() {
+        bail!(
+            "the field of transparent message {} may not have prost attributes",
+            ident
+        );
+    }
+    let field_ident = member(field.ident, 0);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let debug = if is_struct {
+        quote! {
+            f.debug_struct(stringify!(#ident))
+                .field(stringify!(#field_ident), &self.#field_ident)
+                .finish()
+        }
+    } else {
+        quote!(f.debug_tuple(stringify!(#ident)).field(&self.#field_ident).finish())
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::prost::Message for #ident #ty_generics #where_clause {
+            fn encode_raw<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
+                ::prost::Message::encode_raw(&self.#field_ident, buf)
+            }
+
+            fn merge_field<B>(
+                &mut self,
+                tag: u32,
+                wire_type: ::prost::encoding::WireType,
+                buf: &mut B,
+                ctx: ::prost::encoding::DecodeContext,
+            ) -> ::core::result::Result<(), ::prost::DecodeError>
+            where B: ::prost::bytes::Buf {
+                ::prost::Message::merge_field(&mut self.#field_ident, tag, wire_type, buf, ctx)
+            }
+
+            #[inline]
+            fn encoded_len(&self) -> usize {
+                ::prost::Message::encoded_len(&self.#field_ident)
+            }
+
+            fn clear(&mut self) {
+                ::prost::Message::clear(&mut self.#field_ident)
+            }
+
+            fn is_default(&self) -> bool {
+                ::prost::Message::is_default(&self.#field_ident)
+            }
+
+            fn check_required(&self) -> ::core::result::Result<(), ::prost::DecodeError> {
+                ::prost::Message::check_required(&self.#field_ident)
+            }
+
+            fn check_encode(&self) -> ::core::result::Result<(), ::prost::InvalidValueError> {
+                ::prost::Message::check_encode(&self.#field_ident)
+            }
+        }
+
+        impl #impl_generics ::core::default::Default for #ident #ty_generics #where_clause {
+            fn default() -> Self {
+                #ident {
+                    #field_ident: ::core::default::Default::default(),
+                }
+            }
+        }
+
+        impl #impl_generics ::core::fmt::Debug for #ident #ty_generics #where_clause {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+                #debug
+            }
+        }
+    };
+
+    Ok(expanded.into())
+}
+
 #[proc_macro_derive(Message, attributes(prost))]
 pub fn message(input: TokenStream) -> TokenStream {
     try_message(input).unwrap()
@@ -279,6 +540,11 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
         |&(ref variant, ref value)| quote!(#value => ::core::option::Option::Some(#ident::#variant)),
     );
 
+    let values = variants
+        .iter()
+        .map(|&(ref variant, _)| quote!(#ident::#variant));
+
+    let values_doc = format!("The variants of `{}`, in declaration order.", ident);
     let is_valid_doc = format!("Returns `true` if `value` is a variant of `{}`.", ident);
     let from_i32_doc = format!(
         "Converts an `i32` to a `{}`, or `None` if `value` is not a valid variant.",
@@ -287,6 +553,9 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
 
     let expanded = quote! {
         impl #impl_generics #ident #ty_generics #where_clause {
+            #[doc=#values_doc]
+            pub const VALUES: &'static [#ident #ty_generics] = &[#(#values,)*];
+
             #[doc=#is_valid_doc]
             pub fn is_valid(value: i32) -> bool {
                 match value {
@@ -315,6 +584,19 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
                 value as i32
             }
         }
+
+        impl #impl_generics ::core::convert::TryFrom::<i32> for #ident #ty_generics #where_clause {
+            type Error = ::prost::UnknownEnumValue;
+
+            fn try_from(value: i32) -> ::core::result::Result<#ident, ::prost::UnknownEnumValue> {
+                match #ident::from_i32(value) {
+                    ::core::option::Option::Some(value) => ::core::result::Result::Ok(value),
+                    ::core::option::Option::None => {
+                        ::core::result::Result::Err(::prost::UnknownEnumValue(value))
+                    }
+                }
+            }
+        }
     };
 
     Ok(expanded.into())
@@ -336,11 +618,9 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         Data::Union(..) => bail!("Oneof can not be derived for a union"),
     };
 
-    let generics = &input.generics;
-    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
-
-    // Map the variants into 'fields'.
+    // Map the variants into 'fields', with the types of their values.
     let mut fields: Vec<(Ident, Field)> = Vec::new();
+    let mut types: Vec<Type> = Vec::new();
     for Variant {
         attrs,
         ident: variant_ident,
@@ -358,12 +638,22 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         if variant_fields.len() != 1 {
             bail!("Oneof enum variants must have a single field");
         }
+        types.push(variant_fields.into_iter().next().unwrap().ty);
         match Field::new_oneof(attrs)? {
             Some(field) => fields.push((variant_ident, field)),
             None => bail!("invalid oneof variant: oneof variants may not be ignored"),
         }
     }
 
+    let message_types: Vec<&Type> = fields
+        .iter()
+        .zip(&types)
+        .filter(|((_, field), _)| field.holds_messages())
+        .map(|(_, ty)| ty)
+        .collect();
+    let generics = message_generics(&input.generics, &message_types);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
     let mut tags = fields
         .iter()
         .flat_map(|&(ref variant_ident, ref field)| -> Result<u32, Error> {
@@ -412,6 +702,16 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(#ident::#variant_ident(ref value) => #encoded_len)
     });
 
+    let check_required = fields.iter().flat_map(|&(ref variant_ident, ref field)| {
+        let check = field.check_required(quote!(*value))?;
+        Some(quote!(#ident::#variant_ident(ref value) => #check))
+    });
+
+    let check_encode = fields.iter().flat_map(|&(ref variant_ident, ref field)| {
+        let check = field.check_encode(quote!(*value))?;
+        Some(quote!(#ident::#variant_ident(ref value) => #check))
+    });
+
     let debug = fields.iter().map(|&(ref variant_ident, ref field)| {
         let wrapper = field.debug(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => {
@@ -422,6 +722,83 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         })
     });
 
+    let accessors = fields
+        .iter()
+        .zip(&types)
+        .map(|(&(ref variant_ident, _), ty)| {
+            let name = to_snake(variant_ident);
+            let as_ref = Ident::new(&format!("as_{}", name), Span::call_site());
+            let as_mut = Ident::new(&format!("as_{}_mut", name), Span::call_site());
+            let into = Ident::new(&format!("into_{}", name), Span::call_site());
+            let as_ref_doc = format!(
+                "Returns a reference to the value of the `{}` variant, or `None` if it is another variant.",
+                variant_ident
+            );
+            let as_mut_doc = format!(
+                "Returns a mutable reference to the value of the `{}` variant, or `None` if it is another variant.",
+                variant_ident
+            );
+            let into_doc = format!(
+                "Returns the value of the `{}` variant, or `None` if it is another variant.",
+                variant_ident
+            );
+            quote! {
+                #[doc=#as_ref_doc]
+                #[allow(unreachable_patterns)]
+                pub fn #as_ref(&self) -> ::core::option::Option<&#ty> {
+                    match *self {
+                        #ident::#variant_ident(ref value) => ::core::option::Option::Some(value),
+                        _ => ::core::option::Option::None,
+                    }
+                }
+
+                #[doc=#as_mut_doc]
+                #[allow(unreachable_patterns)]
+                pub fn #as_mut(&mut self) -> ::core::option::Option<&mut #ty> {
+                    match *self {
+                        #ident::#variant_ident(ref mut value) => ::core::option::Option::Some(value),
+                        _ => ::core::option::Option::None,
+                    }
+                }
+
+                #[doc=#into_doc]
+                #[allow(unreachable_patterns)]
+                pub fn #into(self) -> ::core::option::Option<#ty> {
+                    match self {
+                        #ident::#variant_ident(value) => ::core::option::Option::Some(value),
+                        _ => ::core::option::Option::None,
+                    }
+                }
+            }
+        });
+
+    // A variant can be constructed from its value if no other variant has a value of the same
+    // type. Enumeration values are `i32`, so they are left out, as are types using the type
+    // parameters of the oneof, which could be the type of another variant.
+    let type_params: Vec<String> = generics
+        .type_params()
+        .map(|param| param.ident.to_string())
+        .collect();
+    let type_names: Vec<String> = types.iter().map(|ty| quote!(#ty).to_string()).collect();
+    let from = fields
+        .iter()
+        .zip(&types)
+        .zip(&type_names)
+        .filter(|&((&(_, ref field), ty), type_name)| {
+            !field.is_enumeration()
+                && type_names.iter().filter(|name| *name == type_name).count() == 1
+                && !uses_type_params(ty, &type_params)
+        })
+        .map(|((&(ref variant_ident, _), ty), _)| {
+            quote! {
+                impl #impl_generics ::core::convert::From<#ty> for #ident #ty_generics #where_clause {
+                    fn from(value: #ty) -> Self {
+                        #ident::#variant_ident(value)
+                    }
+                }
+            }
+        });
+
     let expanded = quote! {
         impl #impl_generics #ident #ty_generics #where_clause {
             pub fn encode<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
@@ -430,6 +807,8 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                 }
             }
 
+            #(#accessors)*
+
             pub fn merge<B>(
                 field: &mut ::core::option::Option<#ident #ty_generics>,
                 tag: u32,
@@ -450,6 +829,22 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                     #(#encoded_len,)*
                 }
             }
+
+            pub fn check_required(&self) -> ::core::result::Result<(), ::prost::DecodeError> {
+                #[allow(unreachable_patterns)]
+                match *self {
+                    #(#check_required,)*
+                    _ => ::core::result::Result::Ok(()),
+                }
+            }
+
+            pub fn check_encode(&self) -> ::core::result::Result<(), ::prost::InvalidValueError> {
+                #[allow(unreachable_patterns)]
+                match *self {
+                    #(#check_encode,)*
+                    _ => ::core::result::Result::Ok(()),
+                }
+            }
         }
 
         impl #impl_generics ::core::fmt::Debug for #ident #ty_generics #where_clause {
@@ -459,12 +854,146 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                 }
             }
         }
+
+        #(#from)*
     };
 
     Ok(expanded.into())
 }
 
+/// Converts the identifier of a variant to snake case, for the names of its accessors.
+fn to_snake(ident: &Ident) -> String {
+    let name = ident.to_string();
+    let name = name.trim_start_matches("r#");
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Returns `true` if a type mentions one of the type parameters.
+fn uses_type_params(ty: &Type, type_params: &[String]) -> bool {
+    fn uses(tokens: proc_macro2::TokenStream, type_params: &[String]) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => type_params.contains(&ident.to_string()),
+            TokenTree::Group(group) => uses(group.stream(), type_params),
+            _ => false,
+        })
+    }
+    !type_params.is_empty() && uses(quote!(#ty), type_params)
+}
+
 #[proc_macro_derive(Oneof, attributes(prost))]
 pub fn oneof(input: TokenStream) -> TokenStream {
     try_oneof(input).unwrap()
 }
+
+fn try_canonical_hash(input: TokenStream) -> Result<TokenStream, Error> {
+    let input: DeriveInput = syn::parse(input)?;
+
+    let ident = input.ident;
+
+    let mut generics = input.generics.clone();
+    let params: Vec<Ident> = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause
+            .predicates
+            .push(parse_quote!(#param: ::prost::CanonicalHash));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (hash, eq) = match input.data {
+        Data::Struct(DataStruct { fields, .. }) => {
+            let members = canonical_members(fields);
+            let hash = members.iter().map(
+                |member| quote!(::prost::CanonicalHash::canonical_hash(&self.#member, state);),
+            );
+            let eq = members.iter().map(|member| {
+                quote!(&& ::prost::CanonicalHash::canonical_eq(&self.#member, &other.#member))
+            });
+            (quote!(#(#hash)*), quote!(true #(#eq)*))
+        }
+        Data::Enum(DataEnum { variants, .. }) => {
+            let mut hash_arms = Vec::new();
+            let mut eq_arms = Vec::new();
+            for (index, variant) in variants.into_iter().enumerate() {
+                let index = index as u32;
+                let variant_ident = variant.ident;
+                let members = canonical_members(variant.fields);
+                let a = (0..members.len())
+                    .map(|i| Ident::new(&format!("a{}", i), Span::call_site()))
+                    .collect::<Vec<_>>();
+                let b = (0..members.len())
+                    .map(|i| Ident::new(&format!("b{}", i), Span::call_site()))
+                    .collect::<Vec<_>>();
+                hash_arms.push(quote! {
+                    #ident::#variant_ident { #(#members: #a,)* .. } => {
+                        state.write_u32(#index);
+                        #(::prost::CanonicalHash::canonical_hash(#a, state);)*
+                    }
+                });
+                eq_arms.push(quote! {
+                    (
+                        #ident::#variant_ident { #(#members: #a,)* .. },
+                        #ident::#variant_ident { #(#members: #b,)* .. },
+                    ) => true #(&& ::prost::CanonicalHash::canonical_eq(#a, #b))*,
+                });
+            }
+            (
+                quote!(match self { #(#hash_arms)* }),
+                quote! {
+                    #[allow(unreachable_patterns)]
+                    match (self, other) {
+                        #(#eq_arms)*
+                        _ => false,
+                    }
+                },
+            )
+        }
+        Data::Union(..) => bail!("CanonicalHash can not be derived for a union"),
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::prost::CanonicalHash for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn canonical_hash<H: ::core::hash::Hasher>(&self, state: &mut H) {
+                #hash
+            }
+
+            #[allow(unused_variables)]
+            fn canonical_eq(&self, other: &Self) -> bool {
+                #eq
+            }
+        }
+    };
+
+    Ok(expanded.into())
+}
+
+/// Returns the members of a struct or variant which are hashed canonically, which are the
+/// fields not marked `#[prost(skip)]`.
+fn canonical_members(fields: Fields) -> Vec<Member> {
+    fields
+        .into_iter()
+        .enumerate()
+        .filter(|(_, field)| !field::is_skipped(&field.attrs))
+        .map(|(idx, field)| member(field.ident, idx))
+        .collect()
+}
+
+#[proc_macro_derive(CanonicalHash, attributes(prost))]
+pub fn canonical_hash(input: TokenStream) -> TokenStream {
+    try_canonical_hash(input).unwrap()
+}
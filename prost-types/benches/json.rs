@@ -0,0 +1,260 @@
+use std::collections::{BTreeMap, HashMap};
+
+use criterion::{Criterion, Throughput};
+use serde_crate::{Deserialize, Serialize};
+
+// The messages mirror the types and serde attributes which `prost-build` generates with
+// `Config::enable_serde_json`, without the `prost` derives, which aren't needed for JSON.
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", default)]
+struct Scalars {
+    #[serde(
+        rename = "int32",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int32"
+    )]
+    int32: i32,
+    #[serde(
+        rename = "uint32",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int32"
+    )]
+    uint32: u32,
+    #[serde(
+        rename = "int64",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int64"
+    )]
+    int64: i64,
+    #[serde(
+        rename = "uint64",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int64"
+    )]
+    uint64: u64,
+    #[serde(
+        rename = "float",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::float"
+    )]
+    float: f32,
+    #[serde(
+        rename = "double",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::float"
+    )]
+    double: f64,
+    #[serde(
+        rename = "flag",
+        skip_serializing_if = "::prost_types::serde::is_default"
+    )]
+    flag: bool,
+    #[serde(
+        rename = "name",
+        skip_serializing_if = "::prost_types::serde::is_default"
+    )]
+    name: String,
+    #[serde(
+        rename = "payload",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::vec_u8"
+    )]
+    payload: Vec<u8>,
+    #[serde(
+        rename = "sequenceNumber",
+        alias = "sequence_number",
+        skip_serializing_if = "::prost_types::serde::is_none",
+        with = "::prost_types::serde::int64::option"
+    )]
+    sequence_number: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", default)]
+struct Node {
+    #[serde(
+        rename = "id",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int64"
+    )]
+    id: i64,
+    #[serde(
+        rename = "label",
+        skip_serializing_if = "::prost_types::serde::is_default"
+    )]
+    label: String,
+    #[serde(
+        rename = "child",
+        skip_serializing_if = "::prost_types::serde::is_none"
+    )]
+    child: Option<Box<Node>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", default)]
+struct Maps {
+    #[serde(
+        rename = "labels",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::map"
+    )]
+    labels: HashMap<String, String>,
+    #[serde(
+        rename = "counts",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::map"
+    )]
+    counts: BTreeMap<i64, u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", default)]
+struct Repeated {
+    #[serde(
+        rename = "ids",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int64::repeated"
+    )]
+    ids: Vec<i64>,
+    #[serde(
+        rename = "samples",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::float::repeated"
+    )]
+    samples: Vec<f64>,
+    #[serde(
+        rename = "tags",
+        skip_serializing_if = "::prost_types::serde::is_default"
+    )]
+    tags: Vec<String>,
+    #[serde(
+        rename = "rows",
+        skip_serializing_if = "::prost_types::serde::is_default"
+    )]
+    rows: Vec<Scalars>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", default)]
+struct Shorts {
+    #[serde(
+        rename = "points",
+        skip_serializing_if = "::prost_types::serde::is_default"
+    )]
+    points: Vec<Point>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "serde_crate", default)]
+struct Point {
+    #[serde(
+        rename = "keys",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::int64::repeated"
+    )]
+    keys: Vec<i64>,
+    #[serde(
+        rename = "coordinates",
+        skip_serializing_if = "::prost_types::serde::is_default",
+        with = "::prost_types::serde::float::repeated"
+    )]
+    coordinates: Vec<f64>,
+}
+
+fn scalars(seed: u32) -> Scalars {
+    Scalars {
+        int32: -(seed as i32) * 7919,
+        uint32: seed.wrapping_mul(2_654_435_761),
+        int64: -(i64::from(seed) << 40) - 12_345,
+        uint64: u64::from(seed) << 48 | 0xdead_beef,
+        float: seed as f32 / 3.0,
+        double: f64::from(seed) * std::f64::consts::PI,
+        flag: seed % 2 == 0,
+        name: format!("scalars-{}", seed),
+        payload: (0..32).map(|i| (i * seed) as u8).collect(),
+        sequence_number: Some(i64::from(seed) * 1_000_003),
+    }
+}
+
+fn node(depth: i64) -> Node {
+    (0..depth).fold(Node::default(), |child, id| Node {
+        id: id << 33,
+        label: format!("node-{}", id),
+        child: Some(Box::new(child)),
+    })
+}
+
+fn maps() -> Maps {
+    Maps {
+        labels: (0..32)
+            .map(|i| (format!("label-{}", i), format!("value-{}", i)))
+            .collect(),
+        counts: (0..64).map(|i| (i * 1_000_000_007, i as u32)).collect(),
+    }
+}
+
+fn repeated() -> Repeated {
+    Repeated {
+        ids: (0..256).map(|i| i * 104_729 - (1 << 50)).collect(),
+        samples: (0..256).map(|i| f64::from(i) / 7.0).collect(),
+        tags: (0..64).map(|i| format!("tag-{}", i)).collect(),
+        rows: (0..16).map(scalars).collect(),
+    }
+}
+
+fn shorts() -> Shorts {
+    Shorts {
+        points: (0..64)
+            .map(|i| Point {
+                keys: (0..i % 8).map(|j| (i << 32) + j).collect(),
+                coordinates: (0..3).map(|j| (i * 3 + j) as f64 / 4.0).collect(),
+            })
+            .collect(),
+    }
+}
+
+fn benchmark_json<M>(criterion: &mut Criterion, name: &str, message: M)
+where
+    M: Serialize + for<'de> Deserialize<'de> + PartialEq + std::fmt::Debug + 'static,
+{
+    let name = format!("json/{}", name);
+    let json = prost_types::json::to_string(&message).unwrap();
+    assert_eq!(prost_types::json::from_str::<M>(&json).unwrap(), message);
+    let len = json.len() as u64;
+
+    criterion
+        .benchmark_group(&name)
+        .throughput(Throughput::Bytes(len))
+        .bench_function("encode", |b| {
+            b.iter(|| criterion::black_box(prost_types::json::to_string(&message).unwrap()))
+        });
+
+    criterion
+        .benchmark_group(&name)
+        .throughput(Throughput::Bytes(len))
+        .bench_function("decode", |b| {
+            b.iter(|| criterion::black_box(prost_types::json::from_str::<M>(&json).unwrap()))
+        });
+}
+
+fn main() {
+    let mut criterion = Criterion::default().configure_from_args();
+
+    // A message with one field of each scalar type.
+    benchmark_json(&mut criterion, "scalars", scalars(42));
+
+    // A chain of 64 nested messages.
+    benchmark_json(&mut criterion, "nested", node(64));
+
+    // A string-keyed map and an integer-keyed map.
+    benchmark_json(&mut criterion, "maps", maps());
+
+    // Repeated scalars and repeated messages.
+    benchmark_json(&mut criterion, "repeated", repeated());
+
+    // Many short repeated scalar fields, of up to 8 elements each.
+    benchmark_json(&mut criterion, "short_repeated", shorts());
+
+    criterion.final_summary();
+}
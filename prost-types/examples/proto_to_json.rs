@@ -0,0 +1,52 @@
+//! Prints the binary encoding of a message, read from stdin, as JSON.
+//!
+//! ```text
+//! proto_to_json <DESCRIPTOR_SET> <MESSAGE_NAME> [--pretty] < payload.bin
+//! ```
+//!
+//! The descriptor set is the output of `protoc --include_imports --descriptor_set_out`, and the
+//! message name is the fully-qualified name of the payload's type, such as `acme.Order`.
+
+use std::io::{self, Read};
+use std::{env, fs, process};
+
+use prost_types::json::Options;
+use prost_types::reflect::DescriptorPool;
+
+fn main() {
+    if let Err(error) = run() {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let mut pretty = false;
+    let mut args = Vec::new();
+    for arg in env::args().skip(1) {
+        if arg == "--pretty" {
+            pretty = true;
+        } else {
+            args.push(arg);
+        }
+    }
+    let (descriptor_set, message_name) = match args.as_slice() {
+        [descriptor_set, message_name] => (descriptor_set, message_name),
+        _ => {
+            eprintln!("usage: proto_to_json <DESCRIPTOR_SET> <MESSAGE_NAME> [--pretty] < payload");
+            process::exit(2);
+        }
+    };
+
+    let mut pool = DescriptorPool::new();
+    pool.decode_file_descriptor_set(&fs::read(descriptor_set)?)?;
+
+    let mut payload = Vec::new();
+    io::stdin().read_to_end(&mut payload)?;
+
+    let json = Options::new()
+        .pretty(pretty)
+        .decode_to_string(&pool, message_name, &payload)?;
+    println!("{}", json);
+    Ok(())
+}
@@ -0,0 +1,237 @@
+//! Packing and unpacking messages into `google.protobuf.Any`.
+
+use core::any::Any as StdAny;
+use core::fmt;
+
+use prost::alloc::boxed::Box;
+use prost::alloc::collections::BTreeMap;
+use prost::alloc::format;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use prost::{DecodeError, Name};
+
+use crate::{Any, DescriptorPool, DynamicMessage};
+
+impl Any {
+    /// Packs a message into an `Any`, using the message's type URL.
+    pub fn pack<M>(message: &M) -> Any
+    where
+        M: Name,
+    {
+        Any {
+            type_url: M::type_url(),
+            value: message.encode_to_vec(),
+        }
+    }
+
+    /// Unpacks the message held by this `Any`.
+    ///
+    /// Fails if the type URL does not name `M`, or the value is not a valid encoding of `M`.
+    pub fn unpack<M>(&self) -> Result<M, DecodeError>
+    where
+        M: Name + Default,
+    {
+        if !self.is::<M>() {
+            return Err(DecodeError::new(format!(
+                "unexpected type URL: expected {}, found {}",
+                M::type_url(),
+                self.type_url
+            )));
+        }
+        M::decode(&*self.value)
+    }
+
+    /// Returns `true` if this `Any` holds a message of type `M`.
+    ///
+    /// Only the type name following the last `/` of the type URL is compared, so that messages
+    /// packed with a type URL prefix other than `type.googleapis.com` are recognized.
+    pub fn is<M>(&self) -> bool
+    where
+        M: Name,
+    {
+        type_name(&self.type_url) == M::full_name()
+    }
+
+    /// Returns the fully-qualified name of the type held by this `Any`, which is the part of the
+    /// type URL following the last `/`.
+    pub fn type_name(&self) -> &str {
+        type_name(&self.type_url)
+    }
+}
+
+fn type_name(type_url: &str) -> &str {
+    match type_url.rfind('/') {
+        Some(index) => &type_url[index + 1..],
+        None => type_url,
+    }
+}
+
+type UnpackFn = fn(&[u8]) -> Result<Box<dyn StdAny + Send + Sync>, DecodeError>;
+
+/// A registry of message types which can be unpacked from an `Any` at runtime.
+///
+/// Rust message types are registered with [`TypeRegistry::register`], and unpack to a boxed
+/// instance of that type. Types which are not registered are unpacked to a [`DynamicMessage`]
+/// if they are found in the registry's descriptor pool, when one is configured.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut registry = TypeRegistry::new();
+/// registry.register::<prost_types::Duration>();
+///
+/// let any = Any::pack(&prost_types::Duration { seconds: 1, nanos: 0 });
+/// let message = registry.unpack(&any)?;
+/// assert!(message.downcast_ref::<prost_types::Duration>().is_some());
+/// ```
+#[derive(Clone, Default)]
+pub struct TypeRegistry {
+    types: BTreeMap<String, UnpackFn>,
+    pool: Option<DescriptorPool>,
+}
+
+impl TypeRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> TypeRegistry {
+        TypeRegistry::default()
+    }
+
+    /// Registers a message type.
+    pub fn register<M>(&mut self) -> &mut Self
+    where
+        M: Name + Default + 'static,
+    {
+        fn unpack<M>(value: &[u8]) -> Result<Box<dyn StdAny + Send + Sync>, DecodeError>
+        where
+            M: Name + Default + 'static,
+        {
+            Ok(Box::new(M::decode(value)?))
+        }
+
+        self.types.insert(M::full_name(), unpack::<M>);
+        self
+    }
+
+    /// Sets the descriptor pool used to unpack messages with no registered Rust type.
+    pub fn descriptor_pool(&mut self, pool: DescriptorPool) -> &mut Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Returns `true` if messages with the given type URL can be unpacked by this registry.
+    pub fn contains(&self, type_url: &str) -> bool {
+        let name = type_name(type_url);
+        self.types.contains_key(name)
+            || self
+                .pool
+                .as_ref()
+                .map_or(false, |pool| pool.get_message_by_name(name).is_some())
+    }
+
+    /// Unpacks the message held by an `Any`.
+    ///
+    /// Returns a boxed value of the registered Rust type for the type URL, or a boxed
+    /// [`DynamicMessage`] if the type is only known to the descriptor pool.
+    pub fn unpack(&self, any: &Any) -> Result<Box<dyn StdAny + Send + Sync>, DecodeError> {
+        let name = any.type_name();
+        if let Some(unpack) = self.types.get(name) {
+            return unpack(&any.value);
+        }
+        match self
+            .pool
+            .as_ref()
+            .and_then(|pool| pool.get_message_by_name(name))
+        {
+            Some(desc) => Ok(Box::new(DynamicMessage::decode(desc, &*any.value)?)),
+            None => Err(DecodeError::new(format!(
+                "unknown type URL: {}",
+                any.type_url
+            ))),
+        }
+    }
+
+    /// Unpacks the message held by an `Any` into a [`DynamicMessage`], using the descriptor pool.
+    pub fn unpack_dynamic(&self, any: &Any) -> Result<DynamicMessage, DecodeError> {
+        let desc = self
+            .pool
+            .as_ref()
+            .and_then(|pool| pool.get_message_by_name(any.type_name()))
+            .ok_or_else(|| DecodeError::new(format!("unknown type URL: {}", any.type_url)))?;
+        DynamicMessage::decode(desc, &*any.value)
+    }
+}
+
+impl fmt::Debug for TypeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypeRegistry")
+            .field("types", &self.types.keys().collect::<Vec<_>>())
+            .field("pool", &self.pool)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dynamic::Value;
+    use crate::reflect::tests::test_pool;
+    use crate::{Duration, Timestamp};
+
+    #[test]
+    fn pack_unpack() {
+        let duration = Duration {
+            seconds: 3,
+            nanos: 5,
+        };
+        let any = Any::pack(&duration);
+        assert_eq!(any.type_url, "type.googleapis.com/google.protobuf.Duration");
+        assert!(any.is::<Duration>());
+        assert!(!any.is::<Timestamp>());
+        assert_eq!(any.unpack::<Duration>().unwrap(), duration);
+        assert!(any.unpack::<Timestamp>().is_err());
+
+        // Custom type URL prefixes are accepted.
+        let any = Any {
+            type_url: "example.com/types/google.protobuf.Duration".to_string(),
+            ..any
+        };
+        assert_eq!(any.type_name(), "google.protobuf.Duration");
+        assert_eq!(any.unpack::<Duration>().unwrap(), duration);
+    }
+
+    #[test]
+    fn registry() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Duration>();
+        registry.descriptor_pool(test_pool());
+
+        let any = Any::pack(&Duration {
+            seconds: 1,
+            nanos: 0,
+        });
+        assert!(registry.contains(&any.type_url));
+        let unpacked = registry.unpack(&any).unwrap();
+        assert_eq!(unpacked.downcast_ref::<Duration>().unwrap().seconds, 1);
+
+        let any = Any {
+            type_url: "type.googleapis.com/test.Person".to_string(),
+            value: vec![0x0A, 0x01, b'x'],
+        };
+        assert!(registry.contains(&any.type_url));
+        let unpacked = registry.unpack(&any).unwrap();
+        let message = unpacked.downcast_ref::<DynamicMessage>().unwrap();
+        assert_eq!(
+            message.get_field_by_name("name").as_deref(),
+            Some(&Value::String("x".to_string()))
+        );
+        assert_eq!(&registry.unpack_dynamic(&any).unwrap(), message);
+
+        let any = Any {
+            type_url: "type.googleapis.com/test.Missing".to_string(),
+            value: vec![],
+        };
+        assert!(!registry.contains(&any.type_url));
+        assert!(registry.unpack(&any).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Generation of arbitrary messages for fuzzing and property-based tests.
+//!
+//! Messages generated with `prost-build`'s `Config::arbitrary` implement [`Arbitrary`], so that
+//! fuzz targets and property tests can produce them from raw bytes. This module implements
+//! [`Arbitrary`] for the well-known types, provides the helper used by the generated code to
+//! bound the depth of recursive messages, and checks that messages survive a round trip through
+//! their encodings. With the `proptest` feature, a round-trip property test takes one line:
+//!
+//! ```rust,ignore
+//! // Checks that arbitrary orders round-trip through the binary and the JSON encodings.
+//! prost_types::proptest_roundtrip!(order_roundtrip, Order, json);
+//! ```
+//!
+//! The well-known types only take values which can be serialized as JSON: timestamps and
+//! durations are within the range of the JSON mapping, the paths of field masks are in snake case,
+//! and `Value`s hold finite numbers.
+
+use core::cell::Cell;
+use core::fmt;
+
+use arbitrary_crate::{Arbitrary, Result, Unstructured};
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use prost::Message;
+
+use crate::arithmetic::{DURATION_SECONDS_MAX, TIMESTAMP_SECONDS_MAX, TIMESTAMP_SECONDS_MIN};
+use crate::{
+    value, Any, Duration, FieldMask, ListValue, NullValue, Struct, Timestamp, Value, NANOS_MAX,
+};
+
+/// The level of nesting at which [`nested`] generates messages from no input.
+pub const MAX_DEPTH: usize = 16;
+
+std::thread_local! {
+    /// The nesting level of the message being generated on this thread.
+    static DEPTH: Cell<usize> = Cell::new(0);
+}
+
+/// Restores the nesting level when a nested message has been generated, or generation panicked.
+struct DepthGuard(usize);
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(self.0));
+    }
+}
+
+/// Generates a message nested in the message being generated, such as the value of a message
+/// field.
+///
+/// Messages nested [`MAX_DEPTH`] levels deep are generated from no input, which leaves their
+/// optional, repeated and map fields empty, so that recursive messages are finite.
+pub fn nested<'a, T>(u: &mut Unstructured<'a>) -> Result<T>
+where
+    T: Arbitrary<'a>,
+{
+    let depth = DEPTH.with(Cell::get);
+    if depth + 1 >= MAX_DEPTH {
+        return T::arbitrary(&mut Unstructured::new(&[]));
+    }
+    let _guard = DepthGuard(depth);
+    DEPTH.with(|level| level.set(depth + 1));
+    T::arbitrary(u)
+}
+
+/// Encodes and decodes a message, panicking if the decoded message differs or if
+/// `Message::encoded_len` doesn't match the length of the encoding.
+///
+/// Messages holding a NaN are not equal to themselves, so the encodings of the message and the
+/// decoded message are compared by length instead.
+pub fn assert_roundtrip<M>(message: &M)
+where
+    M: Message + Default + PartialEq + fmt::Debug,
+{
+    let encoded = message.encode_to_vec();
+    assert_eq!(
+        encoded.len(),
+        message.encoded_len(),
+        "encoded_len doesn't match the encoding of {:?}",
+        message
+    );
+    let decoded = M::decode(&*encoded)
+        .unwrap_or_else(|error| panic!("failed to decode {:?}: {}", message, error));
+    #[allow(clippy::eq_op)]
+    if message == message {
+        assert_eq!(&decoded, message, "decoded message differs");
+    } else {
+        assert_eq!(
+            decoded.encoded_len(),
+            encoded.len(),
+            "decoded message differs from {:?}: {:?}",
+            message,
+            decoded
+        );
+    }
+}
+
+/// Serializes and deserializes a message as JSON, panicking if either fails or if the
+/// deserialized message differs.
+///
+/// Messages holding a NaN are not equal to themselves, so their JSON serializations are compared
+/// by length instead. This function requires the `serde` and the `serde_json` features.
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+pub fn assert_json_roundtrip<M>(message: &M)
+where
+    M: serde_crate::Serialize + serde_crate::de::DeserializeOwned + PartialEq + fmt::Debug,
+{
+    let json = crate::json::to_string(message)
+        .unwrap_or_else(|error| panic!("failed to serialize {:?}: {}", message, error));
+    let decoded: M = crate::json::from_str(&json)
+        .unwrap_or_else(|error| panic!("failed to deserialize {}: {}", json, error));
+    #[allow(clippy::eq_op)]
+    if message == message {
+        assert_eq!(&decoded, message, "deserialized message differs: {}", json);
+    } else {
+        let reserialized = crate::json::to_string(&decoded).unwrap();
+        assert_eq!(
+            reserialized.len(),
+            json.len(),
+            "deserialized message differs: {} became {}",
+            json,
+            reserialized
+        );
+    }
+}
+
+/// Returns a `proptest` strategy generating arbitrary messages from random bytes, which shrinks
+/// messages by shrinking the bytes.
+///
+/// This function requires the `proptest` feature.
+#[cfg(feature = "proptest")]
+pub fn strategy<T>() -> proptest_crate::strategy::BoxedStrategy<T>
+where
+    T: for<'a> Arbitrary<'a> + Default + fmt::Debug + 'static,
+{
+    use proptest_crate::prelude::*;
+
+    proptest_crate::collection::vec(any::<u8>(), 0..1024)
+        .prop_map(|data| T::arbitrary_take_rest(Unstructured::new(&data)).unwrap_or_default())
+        .boxed()
+}
+
+/// Defines a `proptest` test checking that arbitrary messages of a type round-trip through the
+/// binary encoding, and with `json`, through the JSON serialization as well.
+///
+/// ```rust,ignore
+/// prost_types::proptest_roundtrip!(order_roundtrip, Order);
+/// prost_types::proptest_roundtrip!(order_json_roundtrip, Order, json);
+/// ```
+///
+/// The type must implement [`Arbitrary`], and with `json`, `Serialize` and `Deserialize`. This
+/// macro requires the `proptest` feature, and `json` requires the `serde` and the `serde_json`
+/// features.
+#[cfg(feature = "proptest")]
+#[macro_export]
+macro_rules! proptest_roundtrip {
+    ($name:ident, $ty:ty) => {
+        $crate::arbitrary::__proptest::proptest! {
+            #[test]
+            fn $name(message in $crate::arbitrary::strategy::<$ty>()) {
+                $crate::arbitrary::assert_roundtrip(&message);
+            }
+        }
+    };
+    ($name:ident, $ty:ty, json) => {
+        $crate::arbitrary::__proptest::proptest! {
+            #[test]
+            fn $name(message in $crate::arbitrary::strategy::<$ty>()) {
+                $crate::arbitrary::assert_roundtrip(&message);
+                $crate::arbitrary::assert_json_roundtrip(&message);
+            }
+        }
+    };
+}
+
+#[cfg(feature = "proptest")]
+#[doc(hidden)]
+pub use proptest_crate as __proptest;
+
+impl<'a> Arbitrary<'a> for Timestamp {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Timestamp {
+            seconds: u.int_in_range(TIMESTAMP_SECONDS_MIN..=TIMESTAMP_SECONDS_MAX)?,
+            nanos: u.int_in_range(0..=NANOS_MAX)?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Duration {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let seconds = u.int_in_range(-DURATION_SECONDS_MAX..=DURATION_SECONDS_MAX)?;
+        let nanos = match seconds {
+            0 => u.int_in_range(-NANOS_MAX..=NANOS_MAX)?,
+            seconds if seconds < 0 => -u.int_in_range(0..=NANOS_MAX)?,
+            _ => u.int_in_range(0..=NANOS_MAX)?,
+        };
+        Ok(Duration { seconds, nanos })
+    }
+}
+
+impl<'a> Arbitrary<'a> for FieldMask {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut paths = Vec::new();
+        while u.arbitrary()? {
+            let mut path = String::new();
+            for segment in 0..u.int_in_range(1..=3)? {
+                if segment > 0 {
+                    path.push('.');
+                }
+                push_field_name(u, &mut path)?;
+            }
+            paths.push(path);
+        }
+        Ok(FieldMask { paths })
+    }
+}
+
+/// Appends a field name in snake case, which can be converted to lower camel case and back.
+fn push_field_name(u: &mut Unstructured<'_>, out: &mut String) -> Result<()> {
+    const LETTERS: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+    const CHARS: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+    for word in 0..u.int_in_range(1..=3)? {
+        if word > 0 {
+            out.push('_');
+        }
+        out.push(*u.choose(LETTERS)? as char);
+        for _ in 0..u.int_in_range(0..=7)? {
+            out.push(*u.choose(CHARS)? as char);
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Arbitrary<'a> for NullValue {
+    fn arbitrary(_: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(NullValue::NullValue)
+    }
+}
+
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let kind = match u.int_in_range(0..=5)? {
+            0 => value::Kind::NullValue(NullValue::NullValue as i32),
+            1 => {
+                let number = f64::arbitrary(u)?;
+                value::Kind::NumberValue(if number.is_finite() { number } else { 0.0 })
+            }
+            2 => value::Kind::StringValue(u.arbitrary()?),
+            3 => value::Kind::BoolValue(u.arbitrary()?),
+            4 => value::Kind::StructValue(nested(u)?),
+            _ => value::Kind::ListValue(nested(u)?),
+        };
+        Ok(Value { kind: Some(kind) })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Struct {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut fields = crate::StructMap::new();
+        while u.arbitrary()? {
+            fields.insert(u.arbitrary()?, u.arbitrary()?);
+        }
+        Ok(Struct { fields })
+    }
+}
+
+impl<'a> Arbitrary<'a> for ListValue {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut values = Vec::new();
+        while u.arbitrary()? {
+            values.push(u.arbitrary()?);
+        }
+        Ok(ListValue { values })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Any {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Any {
+            type_url: u.arbitrary()?,
+            value: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Generates messages from a fixed sequence of pseudo-random bytes.
+    fn generate<T>(count: usize) -> Vec<T>
+    where
+        T: for<'a> Arbitrary<'a>,
+    {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let data = (0..64 * 1024)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect::<Vec<u8>>();
+        let mut u = Unstructured::new(&data);
+        (0..count).map(|_| T::arbitrary(&mut u).unwrap()).collect()
+    }
+
+    #[test]
+    fn timestamps_and_durations_are_valid() {
+        for timestamp in generate::<Timestamp>(100) {
+            assert!(timestamp.is_valid(), "{:?}", timestamp);
+            assert_roundtrip(&timestamp);
+        }
+        for duration in generate::<Duration>(100) {
+            assert!(duration.is_valid(), "{:?}", duration);
+            assert_roundtrip(&duration);
+        }
+    }
+
+    #[test]
+    fn depth_is_bounded() {
+        fn depth(value: &Value) -> usize {
+            match &value.kind {
+                Some(value::Kind::StructValue(s)) => {
+                    1 + s.fields.values().map(depth).max().unwrap_or(0)
+                }
+                Some(value::Kind::ListValue(l)) => {
+                    1 + l.values.iter().map(depth).max().unwrap_or(0)
+                }
+                _ => 0,
+            }
+        }
+        for value in generate::<Value>(20) {
+            assert!(depth(&value) <= MAX_DEPTH, "{:?}", value);
+            assert_roundtrip(&value);
+        }
+        assert_eq!(DEPTH.with(Cell::get), 0);
+    }
+
+    #[test]
+    fn nan_roundtrip() {
+        assert_roundtrip(&Value::from(f64::NAN));
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn well_known_types_roundtrip_json() {
+        // Timestamps and durations are serialized through the helpers of the `serde` module.
+        #[derive(Debug, PartialEq, serde_crate::Serialize, serde_crate::Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Times {
+            #[serde(with = "crate::serde::timestamp")]
+            timestamp: Timestamp,
+            #[serde(with = "crate::serde::duration")]
+            duration: Duration,
+        }
+
+        let timestamps = generate::<Timestamp>(100);
+        for (timestamp, duration) in timestamps.into_iter().zip(generate::<Duration>(100)) {
+            assert_json_roundtrip(&Times {
+                timestamp,
+                duration,
+            });
+        }
+        for mask in generate::<FieldMask>(100) {
+            assert_json_roundtrip(&mask);
+        }
+        for value in generate::<Value>(20) {
+            assert_json_roundtrip(&value);
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    crate::proptest_roundtrip!(struct_roundtrip, Struct);
+
+    #[cfg(all(feature = "proptest", feature = "serde", feature = "serde_json"))]
+    crate::proptest_roundtrip!(value_json_roundtrip, Value, json);
+}
@@ -0,0 +1,561 @@
+//! Arithmetic, validation and ordering for `Duration` and `Timestamp`.
+//!
+//! Operations are performed on the total number of nanoseconds, which is exact for every
+//! `Duration` and `Timestamp`, normalized or not, and fits comfortably in an `i128`. Results are
+//! always normalized.
+
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::ops;
+
+use crate::{Duration, Timestamp, NANOS_MAX, NANOS_PER_SECOND};
+
+/// The largest number of seconds in a valid `Duration`, about 10,000 years.
+pub(crate) const DURATION_SECONDS_MAX: i64 = 315_576_000_000;
+
+/// The seconds of the earliest valid `Timestamp`, `0001-01-01T00:00:00Z`.
+pub(crate) const TIMESTAMP_SECONDS_MIN: i64 = -62_135_596_800;
+
+/// The seconds of the latest valid `Timestamp`, `9999-12-31T23:59:59Z`.
+pub(crate) const TIMESTAMP_SECONDS_MAX: i64 = 253_402_300_799;
+
+impl Duration {
+    /// Returns the sum of two durations, or `None` if the result overflows.
+    pub fn checked_add(&self, rhs: &Duration) -> Option<Duration> {
+        Duration::from_nanos(self.total_nanos() + rhs.total_nanos())
+    }
+
+    /// Returns the difference of two durations, or `None` if the result overflows.
+    pub fn checked_sub(&self, rhs: &Duration) -> Option<Duration> {
+        Duration::from_nanos(self.total_nanos() - rhs.total_nanos())
+    }
+
+    /// Returns the sum of two durations, saturating at the bounds of `Duration`.
+    pub fn saturating_add(&self, rhs: &Duration) -> Duration {
+        Duration::saturating_from_nanos(self.total_nanos() + rhs.total_nanos())
+    }
+
+    /// Returns the difference of two durations, saturating at the bounds of `Duration`.
+    pub fn saturating_sub(&self, rhs: &Duration) -> Duration {
+        Duration::saturating_from_nanos(self.total_nanos() - rhs.total_nanos())
+    }
+
+    /// Returns `true` if the duration is normalized and within the range documented for
+    /// `google.protobuf.Duration`, which is about ±10,000 years.
+    ///
+    /// A duration is normalized if `nanos` is in the range `(-1_000_000_000, 1_000_000_000)` and
+    /// does not have the opposite sign of `seconds`.
+    pub fn is_valid(&self) -> bool {
+        (-DURATION_SECONDS_MAX..=DURATION_SECONDS_MAX).contains(&self.seconds)
+            && (-NANOS_MAX..=NANOS_MAX).contains(&self.nanos)
+            && !(self.seconds < 0 && self.nanos > 0)
+            && !(self.seconds > 0 && self.nanos < 0)
+    }
+
+    /// Returns the normalized duration, or `None` if it is not within the range documented for
+    /// `google.protobuf.Duration`.
+    ///
+    /// Unlike [`Duration::normalize`], this never saturates.
+    pub fn checked_normalize(&self) -> Option<Duration> {
+        Duration::from_nanos(self.total_nanos()).filter(Duration::is_valid)
+    }
+
+    pub(crate) fn total_nanos(&self) -> i128 {
+        i128::from(self.seconds) * i128::from(NANOS_PER_SECOND) + i128::from(self.nanos)
+    }
+
+    /// Creates a normalized duration, with `nanos` having the same sign as `seconds`.
+    fn from_nanos(nanos: i128) -> Option<Duration> {
+        let seconds = i64::try_from(nanos / i128::from(NANOS_PER_SECOND)).ok()?;
+        let nanos = (nanos % i128::from(NANOS_PER_SECOND)) as i32;
+        Some(Duration { seconds, nanos })
+    }
+
+    fn saturating_from_nanos(nanos: i128) -> Duration {
+        Duration::from_nanos(nanos).unwrap_or(if nanos < 0 {
+            Duration {
+                seconds: i64::MIN,
+                nanos: -NANOS_MAX,
+            }
+        } else {
+            Duration {
+                seconds: i64::MAX,
+                nanos: NANOS_MAX,
+            }
+        })
+    }
+}
+
+impl Timestamp {
+    /// Returns the current time.
+    #[cfg(feature = "std")]
+    pub fn now() -> Timestamp {
+        Timestamp::from(std::time::SystemTime::now())
+    }
+
+    /// Returns the timestamp offset by a duration, or `None` if the result overflows.
+    pub fn checked_add(&self, duration: &Duration) -> Option<Timestamp> {
+        Timestamp::from_nanos(self.total_nanos() + duration.total_nanos())
+    }
+
+    /// Returns the timestamp offset backwards by a duration, or `None` if the result overflows.
+    pub fn checked_sub(&self, duration: &Duration) -> Option<Timestamp> {
+        Timestamp::from_nanos(self.total_nanos() - duration.total_nanos())
+    }
+
+    /// Returns the timestamp offset by a duration, saturating at the bounds of `Timestamp`.
+    pub fn saturating_add(&self, duration: &Duration) -> Timestamp {
+        Timestamp::saturating_from_nanos(self.total_nanos() + duration.total_nanos())
+    }
+
+    /// Returns the timestamp offset backwards by a duration, saturating at the bounds of
+    /// `Timestamp`.
+    pub fn saturating_sub(&self, duration: &Duration) -> Timestamp {
+        Timestamp::saturating_from_nanos(self.total_nanos() - duration.total_nanos())
+    }
+
+    /// Returns `true` if the timestamp is normalized and within the range documented for
+    /// `google.protobuf.Timestamp`, which is `0001-01-01T00:00:00Z` to
+    /// `9999-12-31T23:59:59.999999999Z`.
+    ///
+    /// A timestamp is normalized if `nanos` is in the range `[0, 1_000_000_000)`.
+    pub fn is_valid(&self) -> bool {
+        (TIMESTAMP_SECONDS_MIN..=TIMESTAMP_SECONDS_MAX).contains(&self.seconds)
+            && (0..=NANOS_MAX).contains(&self.nanos)
+    }
+
+    /// Returns the normalized timestamp, or `None` if it is not within the range documented for
+    /// `google.protobuf.Timestamp`.
+    ///
+    /// Unlike [`Timestamp::normalize`], this never saturates.
+    pub fn checked_normalize(&self) -> Option<Timestamp> {
+        Timestamp::from_nanos(self.total_nanos()).filter(Timestamp::is_valid)
+    }
+
+    /// Returns the signed duration elapsed from `earlier` to this timestamp, or `None` if the
+    /// result overflows. The duration is negative if `earlier` is later than this timestamp.
+    pub fn checked_duration_since(&self, earlier: &Timestamp) -> Option<Duration> {
+        Duration::from_nanos(self.total_nanos() - earlier.total_nanos())
+    }
+
+    pub(crate) fn total_nanos(&self) -> i128 {
+        i128::from(self.seconds) * i128::from(NANOS_PER_SECOND) + i128::from(self.nanos)
+    }
+
+    /// Creates a normalized timestamp, with `nanos` in the range `[0, 999_999_999]`.
+    fn from_nanos(nanos: i128) -> Option<Timestamp> {
+        let seconds = i64::try_from(nanos.div_euclid(i128::from(NANOS_PER_SECOND))).ok()?;
+        let nanos = nanos.rem_euclid(i128::from(NANOS_PER_SECOND)) as i32;
+        Some(Timestamp { seconds, nanos })
+    }
+
+    fn saturating_from_nanos(nanos: i128) -> Timestamp {
+        Timestamp::from_nanos(nanos).unwrap_or(if nanos < 0 {
+            Timestamp {
+                seconds: i64::MIN,
+                nanos: 0,
+            }
+        } else {
+            Timestamp {
+                seconds: i64::MAX,
+                nanos: NANOS_MAX,
+            }
+        })
+    }
+}
+
+impl ops::Add for Duration {
+    type Output = Duration;
+
+    /// # Panics
+    ///
+    /// Panics if the result overflows.
+    fn add(self, rhs: Duration) -> Duration {
+        self.checked_add(&rhs)
+            .expect("overflow when adding durations")
+    }
+}
+
+impl ops::Sub for Duration {
+    type Output = Duration;
+
+    /// # Panics
+    ///
+    /// Panics if the result overflows.
+    fn sub(self, rhs: Duration) -> Duration {
+        self.checked_sub(&rhs)
+            .expect("overflow when subtracting durations")
+    }
+}
+
+impl ops::Neg for Duration {
+    type Output = Duration;
+
+    /// # Panics
+    ///
+    /// Panics if the result overflows.
+    fn neg(self) -> Duration {
+        Duration::from_nanos(-self.total_nanos()).expect("overflow when negating duration")
+    }
+}
+
+impl ops::AddAssign for Duration {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = self.clone() + rhs;
+    }
+}
+
+impl ops::SubAssign for Duration {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = self.clone() - rhs;
+    }
+}
+
+impl ops::Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    /// # Panics
+    ///
+    /// Panics if the result overflows.
+    fn add(self, rhs: Duration) -> Timestamp {
+        self.checked_add(&rhs)
+            .expect("overflow when adding duration to timestamp")
+    }
+}
+
+impl ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    /// # Panics
+    ///
+    /// Panics if the result overflows.
+    fn sub(self, rhs: Duration) -> Timestamp {
+        self.checked_sub(&rhs)
+            .expect("overflow when subtracting duration from timestamp")
+    }
+}
+
+impl ops::Sub for Timestamp {
+    type Output = Duration;
+
+    /// # Panics
+    ///
+    /// Panics if the result overflows.
+    fn sub(self, rhs: Timestamp) -> Duration {
+        self.checked_duration_since(&rhs)
+            .expect("overflow when subtracting timestamps")
+    }
+}
+
+impl ops::AddAssign<Duration> for Timestamp {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = self.clone() + rhs;
+    }
+}
+
+impl ops::SubAssign<Duration> for Timestamp {
+    fn sub_assign(&mut self, rhs: Duration) {
+        *self = self.clone() - rhs;
+    }
+}
+
+impl Eq for Duration {}
+
+/// Orders durations by length, as if they were normalized.
+///
+/// Durations which are equal after normalization, but are not equal field by field, are ordered
+/// by their fields so that the order is consistent with `Eq`.
+impl PartialOrd for Duration {
+    fn partial_cmp(&self, other: &Duration) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Duration {
+    fn cmp(&self, other: &Duration) -> Ordering {
+        self.total_nanos()
+            .cmp(&other.total_nanos())
+            .then_with(|| (self.seconds, self.nanos).cmp(&(other.seconds, other.nanos)))
+    }
+}
+
+/// Orders timestamps chronologically, as if they were normalized.
+///
+/// Timestamps which are equal after normalization, but are not equal field by field, are ordered
+/// by their fields so that the order is consistent with `Eq`.
+impl PartialOrd for Timestamp {
+    fn partial_cmp(&self, other: &Timestamp) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timestamp {
+    fn cmp(&self, other: &Timestamp) -> Ordering {
+        self.total_nanos()
+            .cmp(&other.total_nanos())
+            .then_with(|| (self.seconds, self.nanos).cmp(&(other.seconds, other.nanos)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_duration_arithmetic() {
+        let a = Duration {
+            seconds: 1,
+            nanos: 600_000_000,
+        };
+        let b = Duration {
+            seconds: 2,
+            nanos: 700_000_000,
+        };
+        assert_eq!(
+            a.clone() + b.clone(),
+            Duration {
+                seconds: 4,
+                nanos: 300_000_000
+            }
+        );
+        assert_eq!(
+            a.clone() - b.clone(),
+            Duration {
+                seconds: -1,
+                nanos: -100_000_000
+            }
+        );
+        assert_eq!(
+            -a.clone(),
+            Duration {
+                seconds: -1,
+                nanos: -600_000_000
+            }
+        );
+
+        let mut c = a.clone();
+        c += b.clone();
+        c -= b.clone();
+        assert_eq!(c, a);
+
+        let max = Duration {
+            seconds: i64::MAX,
+            nanos: NANOS_MAX,
+        };
+        let min = Duration {
+            seconds: i64::MIN,
+            nanos: -NANOS_MAX,
+        };
+        assert_eq!(max.checked_add(&a), None);
+        assert_eq!(min.checked_sub(&a), None);
+        assert_eq!(max.saturating_add(&a), max);
+        assert_eq!(min.saturating_sub(&a), min);
+        assert_eq!(max.checked_sub(&max), Some(Duration::default()));
+
+        // Non-normalized operands are handled exactly.
+        let unnormalized = Duration {
+            seconds: 1,
+            nanos: -1_500_000_000,
+        };
+        assert_eq!(
+            unnormalized.checked_add(&Duration::default()),
+            Some(Duration {
+                seconds: 0,
+                nanos: -500_000_000
+            })
+        );
+    }
+
+    #[test]
+    fn check_timestamp_arithmetic() {
+        let epoch = Timestamp::default();
+        let offset = Duration {
+            seconds: -1,
+            nanos: -250_000_000,
+        };
+        let before = epoch.clone() + offset.clone();
+        assert_eq!(
+            before,
+            Timestamp {
+                seconds: -2,
+                nanos: 750_000_000
+            }
+        );
+        assert_eq!(before.clone() - offset.clone(), epoch);
+        assert_eq!(before.clone() - epoch.clone(), offset);
+        assert_eq!(epoch.clone() - before.clone(), -offset.clone());
+
+        let mut t = epoch.clone();
+        t += offset.clone();
+        t -= offset.clone();
+        assert_eq!(t, epoch);
+
+        let latest = Timestamp {
+            seconds: i64::MAX,
+            nanos: NANOS_MAX,
+        };
+        let earliest = Timestamp {
+            seconds: i64::MIN,
+            nanos: 0,
+        };
+        assert_eq!(latest.checked_sub(&offset), None);
+        assert_eq!(earliest.checked_add(&offset), None);
+        assert_eq!(latest.saturating_sub(&offset), latest);
+        assert_eq!(earliest.saturating_add(&offset), earliest);
+        assert_eq!(latest.checked_duration_since(&earliest), None);
+    }
+
+    #[test]
+    fn check_ordering() {
+        let mut durations = [
+            Duration {
+                seconds: 1,
+                nanos: 0,
+            },
+            Duration {
+                seconds: 0,
+                nanos: -1,
+            },
+            Duration {
+                seconds: 0,
+                nanos: 1_500_000_000,
+            },
+            Duration {
+                seconds: -1,
+                nanos: 0,
+            },
+        ];
+        durations.sort();
+        let seconds = durations.iter().map(|d| d.seconds).collect::<Vec<_>>();
+        assert_eq!(seconds, vec![-1, 0, 1, 0]);
+
+        let a = Timestamp {
+            seconds: 1,
+            nanos: 0,
+        };
+        let b = Timestamp {
+            seconds: 0,
+            nanos: 1_000_000_000,
+        };
+        assert!(a > b);
+        assert_eq!(a.cmp(&b.clone()).reverse(), b.cmp(&a));
+        let mut b_normalized = b.clone();
+        b_normalized.normalize();
+        assert_eq!(a.cmp(&b_normalized), Ordering::Equal);
+        assert!(
+            Timestamp {
+                seconds: -1,
+                nanos: 999_999_999
+            } < Timestamp::default()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn check_timestamp_now() {
+        let before = Timestamp::from(std::time::SystemTime::now());
+        let now = Timestamp::now();
+        assert!(now >= before);
+        assert!(now.clone() - before < Duration::from(core::time::Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn check_duration_validity() {
+        let valid = [
+            (0, 0),
+            (1, 999_999_999),
+            (-1, -999_999_999),
+            (0, -1),
+            (DURATION_SECONDS_MAX, NANOS_MAX),
+            (-DURATION_SECONDS_MAX, -NANOS_MAX),
+        ];
+        for &(seconds, nanos) in &valid {
+            let duration = Duration { seconds, nanos };
+            assert!(duration.is_valid(), "{:?}", duration);
+            assert_eq!(duration.checked_normalize(), Some(duration));
+        }
+
+        let invalid = [
+            (0, 1_000_000_000),
+            (1, -1),
+            (-1, 1),
+            (DURATION_SECONDS_MAX + 1, 0),
+        ];
+        for &(seconds, nanos) in &invalid {
+            assert!(!Duration { seconds, nanos }.is_valid());
+        }
+
+        assert_eq!(
+            Duration {
+                seconds: 1,
+                nanos: -1
+            }
+            .checked_normalize(),
+            Some(Duration {
+                seconds: 0,
+                nanos: 999_999_999
+            })
+        );
+        assert_eq!(
+            Duration {
+                seconds: DURATION_SECONDS_MAX,
+                nanos: 1_000_000_000
+            }
+            .checked_normalize(),
+            None
+        );
+        assert_eq!(
+            Duration {
+                seconds: i64::MAX,
+                nanos: i32::MAX
+            }
+            .checked_normalize(),
+            None
+        );
+    }
+
+    #[test]
+    fn check_timestamp_validity() {
+        let valid = [
+            (0, 0),
+            (-1, 999_999_999),
+            (TIMESTAMP_SECONDS_MIN, 0),
+            (TIMESTAMP_SECONDS_MAX, NANOS_MAX),
+        ];
+        for &(seconds, nanos) in &valid {
+            let timestamp = Timestamp { seconds, nanos };
+            assert!(timestamp.is_valid(), "{:?}", timestamp);
+            assert_eq!(timestamp.checked_normalize(), Some(timestamp));
+        }
+
+        let invalid = [
+            (0, -1),
+            (0, 1_000_000_000),
+            (TIMESTAMP_SECONDS_MIN - 1, NANOS_MAX),
+            (TIMESTAMP_SECONDS_MAX + 1, 0),
+        ];
+        for &(seconds, nanos) in &invalid {
+            assert!(!Timestamp { seconds, nanos }.is_valid());
+        }
+
+        assert_eq!(
+            Timestamp {
+                seconds: 0,
+                nanos: -1
+            }
+            .checked_normalize(),
+            Some(Timestamp {
+                seconds: -1,
+                nanos: 999_999_999
+            })
+        );
+        assert_eq!(
+            Timestamp {
+                seconds: TIMESTAMP_SECONDS_MAX,
+                nanos: 1_000_000_000
+            }
+            .checked_normalize(),
+            None
+        );
+    }
+}
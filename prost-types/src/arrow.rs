@@ -0,0 +1,629 @@
+//! Conversions between messages and Arrow record batches.
+//!
+//! [`schema`] derives the Arrow schema of a message type from its descriptor, with a column for
+//! each field, and [`to_record_batch`] and [`from_record_batch`] convert messages to and from the
+//! rows of a [`RecordBatch`], for example to export a stream of messages to Parquet files for
+//! analytics:
+//!
+//! ```rust,ignore
+//! let descriptor = pool.get_message_by_name("shop.Order").unwrap();
+//! let batch = prost_types::arrow::to_record_batch(&descriptor, &orders)?;
+//! parquet_writer.write(&batch)?;
+//! ```
+//!
+//! The fields are mapped to Arrow types as follows:
+//!
+//!  * Integers are mapped to the integers of the same width and signedness, and `float` and
+//!    `double` to `Float32` and `Float64`.
+//!  * `bool`, `string` and `bytes` are mapped to `Boolean`, `Utf8` and `Binary`.
+//!  * Enums are mapped to their numbers, as `Int32`.
+//!  * Messages are mapped to structs, with a field for each of their fields. Recursive messages
+//!    have no Arrow schema.
+//!  * Repeated fields are mapped to lists, and maps to map arrays, which are never null.
+//!
+//! Fields with presence, such as message fields, `optional` fields and the fields of oneofs, are
+//! nullable, and are null when they are not set. Other fields are not nullable.
+//!
+//! This module is only available when the `arrow` feature is enabled.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Float64Type, Int32Type, Int64Type, UInt32Type, UInt64Type};
+use arrow_array::{
+    Array, ArrayRef, BinaryArray, BooleanArray, Float32Array, Float64Array, Int32Array, Int64Array,
+    ListArray, MapArray, RecordBatch, StringArray, StructArray, UInt32Array, UInt64Array,
+};
+use arrow_buffer::{NullBuffer, OffsetBuffer};
+use arrow_schema::{ArrowError, DataType, Field, Fields, Schema};
+use bytes::Bytes;
+use prost::Message;
+
+use crate::dynamic::{DynamicMessage, MapKey, Value};
+use crate::reflect::{FieldDescriptor, Kind, MessageDescriptor};
+
+/// Returns the Arrow schema of a message type, with a column for each field, named after it.
+///
+/// Returns an error if the message is recursive.
+pub fn schema(message: &MessageDescriptor) -> Result<Schema, ArrowError> {
+    Ok(Schema::new(fields(message, &mut Vec::new())?))
+}
+
+/// Converts messages to a record batch with the schema of their type.
+///
+/// Returns an error if the message type is recursive, or if a message doesn't decode as the
+/// message type.
+pub fn to_record_batch<M>(
+    message: &MessageDescriptor,
+    messages: &[M],
+) -> Result<RecordBatch, ArrowError>
+where
+    M: Message,
+{
+    let messages = messages
+        .iter()
+        .map(|m| DynamicMessage::decode(message.clone(), m.encode_to_vec().as_slice()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ArrowError::ExternalError(Box::new(err)))?;
+    dynamic_to_record_batch(message, &messages)
+}
+
+/// Converts the rows of a record batch to messages.
+///
+/// The columns are matched to the fields by name, and must have the types of the schema of the
+/// message type. Fields without a column are not set, and columns which name no field are
+/// ignored.
+pub fn from_record_batch<M>(
+    message: &MessageDescriptor,
+    batch: &RecordBatch,
+) -> Result<Vec<M>, ArrowError>
+where
+    M: Message + Default,
+{
+    dynamic_from_record_batch(message, batch)?
+        .into_iter()
+        .map(|dynamic| M::decode(dynamic.encode_to_vec().as_slice()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ArrowError::ExternalError(Box::new(err)))
+}
+
+/// Converts dynamic messages to a record batch with the schema of their type.
+///
+/// # Panics
+///
+/// Panics if a message is not of the given type.
+pub fn dynamic_to_record_batch(
+    message: &MessageDescriptor,
+    messages: &[DynamicMessage],
+) -> Result<RecordBatch, ArrowError> {
+    let schema = Arc::new(schema(message)?);
+    let columns = message
+        .fields()
+        .map(|field| {
+            let values = messages
+                .iter()
+                .map(|m| {
+                    assert_eq!(m.descriptor().full_name(), message.full_name());
+                    field_value(m, &field)
+                })
+                .collect::<Vec<_>>();
+            field_array(&field, &values)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    RecordBatch::try_new_with_options(
+        schema,
+        columns,
+        &arrow_array::RecordBatchOptions::new().with_row_count(Some(messages.len())),
+    )
+}
+
+/// Converts the rows of a record batch to dynamic messages.
+///
+/// See [`from_record_batch`].
+pub fn dynamic_from_record_batch(
+    message: &MessageDescriptor,
+    batch: &RecordBatch,
+) -> Result<Vec<DynamicMessage>, ArrowError> {
+    let mut columns = Vec::new();
+    for field in message.fields() {
+        let column = match batch.column_by_name(field.name()) {
+            Some(column) => column,
+            None => continue,
+        };
+        let data_type = field_type(&field, &mut Vec::new())?;
+        if !column.data_type().equals_datatype(&data_type) {
+            return Err(ArrowError::SchemaError(format!(
+                "column {} has type {}, expected {}",
+                field.name(),
+                column.data_type(),
+                data_type
+            )));
+        }
+        columns.push((field, column));
+    }
+
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            let mut dynamic = DynamicMessage::new(message.clone());
+            for (field, column) in &columns {
+                read_field(&mut dynamic, field, column.as_ref(), row);
+            }
+            dynamic
+        })
+        .collect())
+}
+
+fn fields(message: &MessageDescriptor, stack: &mut Vec<String>) -> Result<Fields, ArrowError> {
+    if stack.iter().any(|name| name == message.full_name()) {
+        return Err(ArrowError::SchemaError(format!(
+            "message {} is recursive",
+            message.full_name()
+        )));
+    }
+    stack.push(message.full_name().to_string());
+    let fields = message
+        .fields()
+        .map(|field| {
+            let data_type = field_type(&field, stack)?;
+            Ok(Field::new(field.name(), data_type, is_nullable(&field)))
+        })
+        .collect::<Result<Fields, ArrowError>>()?;
+    stack.pop();
+    Ok(fields)
+}
+
+fn is_nullable(field: &FieldDescriptor) -> bool {
+    field.supports_presence() && !field.is_list()
+}
+
+fn field_type(field: &FieldDescriptor, stack: &mut Vec<String>) -> Result<DataType, ArrowError> {
+    if field.is_map() {
+        let entry = entry_message(field);
+        let key = entry.map_entry_key_field();
+        let value = entry.map_entry_value_field();
+        let entries = Fields::from(vec![
+            Field::new("key", kind_type(&key.kind(), stack)?, false),
+            Field::new("value", kind_type(&value.kind(), stack)?, false),
+        ]);
+        Ok(DataType::Map(
+            Arc::new(Field::new("entries", DataType::Struct(entries), false)),
+            false,
+        ))
+    } else if field.is_list() {
+        let item = kind_type(&field.kind(), stack)?;
+        Ok(DataType::List(Arc::new(Field::new_list_field(item, false))))
+    } else {
+        kind_type(&field.kind(), stack)
+    }
+}
+
+fn kind_type(kind: &Kind, stack: &mut Vec<String>) -> Result<DataType, ArrowError> {
+    Ok(match kind {
+        Kind::Double => DataType::Float64,
+        Kind::Float => DataType::Float32,
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 | Kind::Enum(_) => DataType::Int32,
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => DataType::Int64,
+        Kind::Uint32 | Kind::Fixed32 => DataType::UInt32,
+        Kind::Uint64 | Kind::Fixed64 => DataType::UInt64,
+        Kind::Bool => DataType::Boolean,
+        Kind::String => DataType::Utf8,
+        Kind::Bytes => DataType::Binary,
+        Kind::Message(message) => DataType::Struct(fields(message, stack)?),
+    })
+}
+
+fn entry_message(field: &FieldDescriptor) -> MessageDescriptor {
+    match field.kind() {
+        Kind::Message(entry) => entry,
+        _ => unreachable!("map field {} is not a message", field.name()),
+    }
+}
+
+/// Returns the value of a field, or `None` if the field has presence and is not set.
+fn field_value<'a>(message: &'a DynamicMessage, field: &FieldDescriptor) -> Option<Cow<'a, Value>> {
+    if is_nullable(field) && !message.has_field(field) {
+        None
+    } else {
+        Some(message.get_field(field))
+    }
+}
+
+/// Builds the array of the values of a field in each row.
+fn field_array(
+    field: &FieldDescriptor,
+    values: &[Option<Cow<'_, Value>>],
+) -> Result<ArrayRef, ArrowError> {
+    if !field.is_list() && !field.is_map() {
+        let values = values.iter().map(Option::as_deref).collect::<Vec<_>>();
+        return kind_array(&field.kind(), &values);
+    }
+
+    let data_type = field_type(field, &mut Vec::new())?;
+    let (item_field, sorted) = match &data_type {
+        DataType::List(item_field) => (item_field.clone(), false),
+        DataType::Map(entries_field, sorted) => (entries_field.clone(), *sorted),
+        _ => unreachable!(),
+    };
+    let offsets = OffsetBuffer::from_lengths(values.iter().map(|value| match value.as_deref() {
+        Some(Value::List(items)) => items.len(),
+        Some(Value::Map(entries)) => entries.len(),
+        _ => 0,
+    }));
+
+    if field.is_list() {
+        let items = values
+            .iter()
+            .flat_map(|value| value.as_deref().and_then(Value::as_list).unwrap_or(&[]))
+            .map(Some)
+            .collect::<Vec<_>>();
+        let items = kind_array(&field.kind(), &items)?;
+        return Ok(Arc::new(ListArray::try_new(
+            item_field, offsets, items, None,
+        )?));
+    }
+
+    let entry = entry_message(field);
+    let mut keys = Vec::new();
+    let mut entry_values = Vec::new();
+    for value in values {
+        if let Some(Value::Map(entries)) = value.as_deref() {
+            for (key, value) in entries {
+                keys.push(Value::from(key.clone()));
+                entry_values.push(value);
+            }
+        }
+    }
+    let keys = kind_array(
+        &entry.map_entry_key_field().kind(),
+        &keys.iter().map(Some).collect::<Vec<_>>(),
+    )?;
+    let entry_values = kind_array(
+        &entry.map_entry_value_field().kind(),
+        &entry_values.into_iter().map(Some).collect::<Vec<_>>(),
+    )?;
+    let entries = match item_field.data_type() {
+        DataType::Struct(fields) => {
+            StructArray::try_new(fields.clone(), vec![keys, entry_values], None)?
+        }
+        _ => unreachable!(),
+    };
+    Ok(Arc::new(MapArray::try_new(
+        item_field, offsets, entries, None, sorted,
+    )?))
+}
+
+/// Builds the array of singular values of a type, which are null if `None`.
+fn kind_array(kind: &Kind, values: &[Option<&Value>]) -> Result<ArrayRef, ArrowError> {
+    macro_rules! primitive {
+        ($array:ty, $as:ident) => {
+            Arc::new(
+                values
+                    .iter()
+                    .map(|value| value.and_then(|value| value.$as()))
+                    .collect::<$array>(),
+            )
+        };
+    }
+
+    Ok(match kind {
+        Kind::Double => primitive!(Float64Array, as_f64),
+        Kind::Float => primitive!(Float32Array, as_f32),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => primitive!(Int32Array, as_i32),
+        Kind::Enum(_) => primitive!(Int32Array, as_enum_number),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => primitive!(Int64Array, as_i64),
+        Kind::Uint32 | Kind::Fixed32 => primitive!(UInt32Array, as_u32),
+        Kind::Uint64 | Kind::Fixed64 => primitive!(UInt64Array, as_u64),
+        Kind::Bool => primitive!(BooleanArray, as_bool),
+        Kind::String => Arc::new(
+            values
+                .iter()
+                .map(|value| value.and_then(Value::as_str))
+                .collect::<StringArray>(),
+        ),
+        Kind::Bytes => Arc::new(
+            values
+                .iter()
+                .map(|value| value.and_then(Value::as_bytes).map(|bytes| &bytes[..]))
+                .collect::<BinaryArray>(),
+        ),
+        Kind::Message(message) => {
+            let empty = DynamicMessage::new(message.clone());
+            let messages = values
+                .iter()
+                .map(|value| value.and_then(Value::as_message).unwrap_or(&empty))
+                .collect::<Vec<_>>();
+            let fields = fields(message, &mut Vec::new())?;
+            let columns = message
+                .fields()
+                .map(|field| {
+                    let values = messages
+                        .iter()
+                        .map(|m| field_value(m, &field))
+                        .collect::<Vec<_>>();
+                    field_array(&field, &values)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let nulls = if values.iter().all(Option::is_some) {
+                None
+            } else {
+                Some(NullBuffer::from(
+                    values.iter().map(Option::is_some).collect::<Vec<_>>(),
+                ))
+            };
+            if fields.is_empty() {
+                Arc::new(StructArray::new_empty_fields(values.len(), nulls))
+            } else {
+                Arc::new(StructArray::try_new(fields, columns, nulls)?)
+            }
+        }
+    })
+}
+
+/// Sets a field of a message from a row of its column, unless the row is null.
+fn read_field(
+    message: &mut DynamicMessage,
+    field: &FieldDescriptor,
+    column: &dyn Array,
+    row: usize,
+) {
+    if column.is_null(row) {
+        return;
+    }
+    let value = if field.is_map() {
+        let entry = entry_message(field);
+        let key_kind = entry.map_entry_key_field().kind();
+        let value_kind = entry.map_entry_value_field().kind();
+        let entries = column.as_map().value(row);
+        let (keys, values) = (entries.column(0), entries.column(1));
+        Value::Map(
+            (0..entries.len())
+                .filter_map(|i| {
+                    let key = MapKey::from_value(read_value(&key_kind, keys.as_ref(), i))?;
+                    Some((key, read_value(&value_kind, values.as_ref(), i)))
+                })
+                .collect(),
+        )
+    } else if field.is_list() {
+        let kind = field.kind();
+        let items = column.as_list::<i32>().value(row);
+        Value::List(
+            (0..items.len())
+                .map(|i| read_value(&kind, items.as_ref(), i))
+                .collect(),
+        )
+    } else {
+        read_value(&field.kind(), column, row)
+    };
+    message.set_field(field, value);
+}
+
+/// Reads a singular value of a type from a row of an array.
+fn read_value(kind: &Kind, array: &dyn Array, row: usize) -> Value {
+    match kind {
+        Kind::Double => Value::F64(array.as_primitive::<Float64Type>().value(row)),
+        Kind::Float => Value::F32(array.as_primitive::<Float32Type>().value(row)),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+            Value::I32(array.as_primitive::<Int32Type>().value(row))
+        }
+        Kind::Enum(_) => Value::EnumNumber(array.as_primitive::<Int32Type>().value(row)),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => {
+            Value::I64(array.as_primitive::<Int64Type>().value(row))
+        }
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(array.as_primitive::<UInt32Type>().value(row)),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(array.as_primitive::<UInt64Type>().value(row)),
+        Kind::Bool => Value::Bool(array.as_boolean().value(row)),
+        Kind::String => Value::String(array.as_string::<i32>().value(row).to_string()),
+        Kind::Bytes => Value::Bytes(Bytes::copy_from_slice(array.as_binary::<i32>().value(row))),
+        Kind::Message(message) => {
+            let array = array.as_struct();
+            let mut nested = DynamicMessage::new(message.clone());
+            for field in message.fields() {
+                if let Some(column) = array.column_by_name(field.name()) {
+                    read_field(&mut nested, &field, column.as_ref(), row);
+                }
+            }
+            Value::Message(nested)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, test_pool, typed_field};
+    use crate::{
+        DescriptorPool, DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet, MessageOptions,
+    };
+
+    fn pool() -> DescriptorPool {
+        let optional = |field: FieldDescriptorProto| FieldDescriptorProto {
+            proto3_optional: Some(true),
+            ..field
+        };
+        let item = DescriptorProto {
+            name: Some("Item".to_string()),
+            field: vec![
+                field("sku", 1, Label::Optional, Type::String),
+                field("price", 2, Label::Optional, Type::Double),
+            ],
+            ..Default::default()
+        };
+        let entry = DescriptorProto {
+            name: Some("LabelsEntry".to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, Type::String),
+                field("value", 2, Label::Optional, Type::Int64),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let order = DescriptorProto {
+            name: Some("Order".to_string()),
+            field: vec![
+                field("id", 1, Label::Optional, Type::Uint64),
+                optional(field("note", 2, Label::Optional, Type::String)),
+                typed_field("items", 3, Label::Repeated, Type::Message, ".shop.Item"),
+                typed_field("main", 4, Label::Optional, Type::Message, ".shop.Item"),
+                typed_field(
+                    "labels",
+                    5,
+                    Label::Repeated,
+                    Type::Message,
+                    ".shop.Order.LabelsEntry",
+                ),
+                field("paid", 6, Label::Optional, Type::Bool),
+                field("tags", 7, Label::Repeated, Type::Bytes),
+            ],
+            nested_type: vec![entry],
+            ..Default::default()
+        };
+        DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("shop.proto".to_string()),
+                package: Some("shop".to_string()),
+                message_type: vec![item, order],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let pool = pool();
+        let order = pool.get_message_by_name("shop.Order").unwrap();
+        let item = pool.get_message_by_name("shop.Item").unwrap();
+
+        let schema = schema(&order).unwrap();
+        assert_eq!(
+            schema
+                .fields()
+                .iter()
+                .map(|field| (field.name().as_str(), field.is_nullable()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("id", false),
+                ("note", true),
+                ("items", false),
+                ("main", true),
+                ("labels", false),
+                ("paid", false),
+                ("tags", false),
+            ]
+        );
+        assert_eq!(schema.field(0).data_type(), &DataType::UInt64);
+        assert!(matches!(schema.field(4).data_type(), DataType::Map(..)));
+
+        let mut first_item = DynamicMessage::new(item.clone());
+        first_item.set_field_by_name("sku", Value::String("a".to_string()));
+        first_item.set_field_by_name("price", Value::F64(1.5));
+        let mut labels = std::collections::BTreeMap::new();
+        labels.insert(MapKey::String("x".to_string()), Value::I64(-1));
+        labels.insert(MapKey::String("y".to_string()), Value::I64(2));
+
+        let mut first = DynamicMessage::new(order.clone());
+        first.set_field_by_name("id", Value::U64(u64::MAX));
+        first.set_field_by_name("note", Value::String(String::new()));
+        first.set_field_by_name(
+            "items",
+            Value::List(vec![
+                Value::Message(first_item.clone()),
+                Value::Message(DynamicMessage::new(item.clone())),
+            ]),
+        );
+        first.set_field_by_name("main", Value::Message(first_item));
+        first.set_field_by_name("labels", Value::Map(labels));
+        first.set_field_by_name("paid", Value::Bool(true));
+        first.set_field_by_name(
+            "tags",
+            Value::List(vec![Value::Bytes(Bytes::from_static(b"\x00"))]),
+        );
+        let second = DynamicMessage::new(order.clone());
+
+        let batch = dynamic_to_record_batch(&order, &[first.clone(), second.clone()]).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert!(batch.column(1).is_valid(0));
+        assert!(batch.column(1).is_null(1));
+        assert!(batch.column(3).is_null(1));
+
+        let decoded = dynamic_from_record_batch(&order, &batch).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(
+            decoded[0].encode_to_vec(),
+            first.encode_to_vec(),
+            "{:?}",
+            decoded[0]
+        );
+        assert_eq!(decoded[1].encode_to_vec(), second.encode_to_vec());
+
+        let projected = batch.project(&[0, 5]).unwrap();
+        let decoded = dynamic_from_record_batch(&order, &projected).unwrap();
+        assert_eq!(
+            decoded[0].get_field_by_name("id").unwrap().as_u64(),
+            Some(u64::MAX)
+        );
+        assert!(!decoded[0].has_field(&order.get_field_by_name("main").unwrap()));
+
+        let renamed = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1]))],
+        )
+        .unwrap();
+        assert!(dynamic_from_record_batch(&order, &renamed).is_err());
+
+        let empty = dynamic_to_record_batch(&order, &[]).unwrap();
+        assert_eq!(empty.num_rows(), 0);
+    }
+
+    #[test]
+    fn generated_messages() {
+        let pool = DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("google/protobuf/duration.proto".to_string()),
+                package: Some("google.protobuf".to_string()),
+                message_type: vec![DescriptorProto {
+                    name: Some("Duration".to_string()),
+                    field: vec![
+                        field("seconds", 1, Label::Optional, Type::Int64),
+                        field("nanos", 2, Label::Optional, Type::Int32),
+                    ],
+                    ..Default::default()
+                }],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        })
+        .unwrap();
+        let descriptor = pool
+            .get_message_by_name("google.protobuf.Duration")
+            .unwrap();
+        let durations = vec![
+            crate::Duration {
+                seconds: 1,
+                nanos: 5,
+            },
+            crate::Duration::default(),
+        ];
+        let batch = to_record_batch(&descriptor, &durations).unwrap();
+        assert_eq!(
+            batch.column(0).as_primitive::<Int64Type>().values(),
+            &[1, 0]
+        );
+        assert_eq!(
+            from_record_batch::<crate::Duration>(&descriptor, &batch).unwrap(),
+            durations
+        );
+
+        let recursive = test_pool();
+        let error = schema(&recursive.get_message_by_name("test.Person").unwrap()).unwrap_err();
+        assert!(error.to_string().contains("is recursive"), "{}", error);
+    }
+}
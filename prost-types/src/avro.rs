@@ -0,0 +1,443 @@
+//! Avro schemas and binary encoding of messages.
+//!
+//! [`schema`] renders the [Avro schema][1] of a message type from its descriptor, and [`encode`]
+//! encodes messages in the Avro binary encoding of that schema, so that events defined in
+//! Protobuf can be written to Avro files or to Kafka topics of Avro records without maintaining a
+//! parallel schema:
+//!
+//! ```rust,ignore
+//! let descriptor = pool.get_message_by_name("shop.OrderPlaced").unwrap();
+//! let schema = prost_types::avro::schema(&descriptor);
+//! let mut row = Vec::new();
+//! prost_types::avro::encode(&descriptor, &event, &mut row)?;
+//! ```
+//!
+//! The fields are mapped to Avro types as follows:
+//!
+//!  * `int32`, `sint32` and `sfixed32` are mapped to `int`, and the other integers to `long`.
+//!    `uint64` and `fixed64` values above `i64::MAX` wrap around to negative values.
+//!  * `float`, `double`, `bool`, `string` and `bytes` are mapped to the Avro types of the same
+//!    names, with `bool` as `boolean`.
+//!  * Enums are mapped to Avro enums, whose symbols are the names of the enum values. Values which
+//!    the enum doesn't declare are encoded as its default value.
+//!  * Messages are mapped to records. Each message and enum is defined once, with its full name,
+//!    and referenced by name afterwards, so that recursive messages are supported.
+//!  * Repeated fields are mapped to arrays, and maps to Avro maps, whose keys are strings: other
+//!    keys are rendered as decimal numbers or as `true` and `false`.
+//!  * Fields with presence, such as message fields, `optional` fields and the fields of oneofs,
+//!    are unions of `null` and their type, which are `null` when they are not set.
+//!
+//! Every field has a default value, the default value of its type or `null`, so that readers
+//! using a newer version of the schema can read records written with an older one.
+//!
+//! [1]: https://avro.apache.org/docs/1.11.1/specification/
+
+use prost::alloc::collections::BTreeSet;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec;
+use prost::alloc::vec::Vec;
+use prost::{DecodeError, Message};
+
+use crate::dynamic::{DynamicMessage, Value};
+use crate::reflect::{EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor};
+use crate::{prost_struct, Struct};
+
+/// Returns the Avro schema of a message type, as a record.
+pub fn schema(message: &MessageDescriptor) -> crate::Value {
+    SchemaBuilder::default().message(message)
+}
+
+/// Encodes a message as an Avro record of the schema of its type, in the Avro binary encoding.
+///
+/// Returns an error if the message doesn't decode as the message type.
+pub fn encode<M>(
+    descriptor: &MessageDescriptor,
+    message: &M,
+    buf: &mut Vec<u8>,
+) -> Result<(), DecodeError>
+where
+    M: Message,
+{
+    let message = DynamicMessage::decode(descriptor.clone(), message.encode_to_vec().as_slice())?;
+    encode_dynamic(&message, buf);
+    Ok(())
+}
+
+/// Encodes a dynamic message as an Avro record of the schema of its type, in the Avro binary
+/// encoding.
+pub fn encode_dynamic(message: &DynamicMessage, buf: &mut Vec<u8>) {
+    for field in message.descriptor().fields() {
+        if is_nullable(&field) {
+            if message.has_field(&field) {
+                encode_long(1, buf);
+            } else {
+                encode_long(0, buf);
+                continue;
+            }
+        }
+        encode_field(&field, &message.get_field(&field), buf);
+    }
+}
+
+/// Builds schemas, defining each named type once.
+#[derive(Default)]
+struct SchemaBuilder {
+    defined: BTreeSet<String>,
+}
+
+impl SchemaBuilder {
+    fn message(&mut self, message: &MessageDescriptor) -> crate::Value {
+        if !self.defined.insert(message.full_name().to_string()) {
+            return message.full_name().into();
+        }
+        let fields = message
+            .fields()
+            .map(|field| {
+                let mut ty = self.field(&field);
+                let default = if is_nullable(&field) {
+                    ty = vec![crate::Value::from("null"), ty].into();
+                    crate::Value::null()
+                } else {
+                    default_value(&field)
+                };
+                crate::Value::from(prost_struct! {
+                    "name": field.name(),
+                    "type": ty,
+                    "default": default,
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut record = named("record", message.full_name());
+        record.insert("fields", fields);
+        record.into()
+    }
+
+    fn enumeration(&mut self, enum_type: &EnumDescriptor) -> crate::Value {
+        if !self.defined.insert(enum_type.full_name().to_string()) {
+            return enum_type.full_name().into();
+        }
+        let mut schema = named("enum", enum_type.full_name());
+        let symbols = enum_type
+            .values()
+            .map(|(name, _)| crate::Value::from(name))
+            .collect::<Vec<_>>();
+        schema.insert("symbols", symbols);
+        schema.insert("default", default_symbol(enum_type));
+        schema.into()
+    }
+
+    fn field(&mut self, field: &FieldDescriptor) -> crate::Value {
+        if field.is_map() {
+            let value_field = match field.kind() {
+                Kind::Message(entry) => entry.map_entry_value_field(),
+                _ => unreachable!(),
+            };
+            prost_struct! { "type": "map", "values": self.kind(&value_field.kind()) }.into()
+        } else if field.is_list() {
+            prost_struct! { "type": "array", "items": self.kind(&field.kind()) }.into()
+        } else {
+            self.kind(&field.kind())
+        }
+    }
+
+    fn kind(&mut self, kind: &Kind) -> crate::Value {
+        match kind {
+            Kind::Double => "double".into(),
+            Kind::Float => "float".into(),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => "int".into(),
+            Kind::Int64
+            | Kind::Sint64
+            | Kind::Sfixed64
+            | Kind::Uint32
+            | Kind::Fixed32
+            | Kind::Uint64
+            | Kind::Fixed64 => "long".into(),
+            Kind::Bool => "boolean".into(),
+            Kind::String => "string".into(),
+            Kind::Bytes => "bytes".into(),
+            Kind::Enum(enum_type) => self.enumeration(enum_type),
+            Kind::Message(message) => self.message(message),
+        }
+    }
+}
+
+/// Returns a named type, with the name and the namespace of a full name.
+fn named(ty: &str, full_name: &str) -> Struct {
+    match full_name.rfind('.') {
+        Some(index) => prost_struct! {
+            "type": ty,
+            "name": &full_name[index + 1..],
+            "namespace": &full_name[..index],
+        },
+        None => prost_struct! { "type": ty, "name": full_name },
+    }
+}
+
+fn is_nullable(field: &FieldDescriptor) -> bool {
+    field.supports_presence() && !field.is_list()
+}
+
+/// Returns the default value of a field without presence, in the JSON form of Avro defaults.
+fn default_value(field: &FieldDescriptor) -> crate::Value {
+    if field.is_map() {
+        return Struct::default().into();
+    }
+    if field.is_list() {
+        return Vec::<crate::Value>::new().into();
+    }
+    match field.kind() {
+        Kind::Bool => false.into(),
+        Kind::String | Kind::Bytes => "".into(),
+        Kind::Enum(enum_type) => default_symbol(&enum_type).into(),
+        Kind::Message(_) => crate::Value::null(),
+        _ => 0.into(),
+    }
+}
+
+fn default_symbol(enum_type: &EnumDescriptor) -> &str {
+    enum_type
+        .get_value(enum_type.default_value())
+        .unwrap_or_default()
+}
+
+fn encode_field(field: &FieldDescriptor, value: &Value, buf: &mut Vec<u8>) {
+    let kind = field.kind();
+    match value {
+        Value::List(values) => {
+            if !values.is_empty() {
+                encode_long(values.len() as i64, buf);
+                for value in values {
+                    encode_value(&kind, value, buf);
+                }
+            }
+            encode_long(0, buf);
+        }
+        Value::Map(entries) => {
+            let value_kind = match &kind {
+                Kind::Message(entry) => entry.map_entry_value_field().kind(),
+                _ => unreachable!(),
+            };
+            if !entries.is_empty() {
+                encode_long(entries.len() as i64, buf);
+                for (key, value) in entries {
+                    encode_bytes(key.to_string().as_bytes(), buf);
+                    encode_value(&value_kind, value, buf);
+                }
+            }
+            encode_long(0, buf);
+        }
+        value => encode_value(&kind, value, buf),
+    }
+}
+
+fn encode_value(kind: &Kind, value: &Value, buf: &mut Vec<u8>) {
+    match value {
+        Value::Bool(value) => buf.push(*value as u8),
+        Value::I32(value) => encode_long((*value).into(), buf),
+        Value::I64(value) => encode_long(*value, buf),
+        Value::U32(value) => encode_long((*value).into(), buf),
+        Value::U64(value) => encode_long(*value as i64, buf),
+        Value::F32(value) => buf.extend_from_slice(&value.to_le_bytes()),
+        Value::F64(value) => buf.extend_from_slice(&value.to_le_bytes()),
+        Value::String(value) => encode_bytes(value.as_bytes(), buf),
+        Value::Bytes(value) => encode_bytes(value, buf),
+        Value::EnumNumber(number) => {
+            let enum_type = match kind {
+                Kind::Enum(enum_type) => enum_type,
+                _ => unreachable!(),
+            };
+            let index = |number| enum_type.values().position(|(_, n)| n == number);
+            let index = index(*number)
+                .or_else(|| index(enum_type.default_value()))
+                .unwrap_or_default();
+            encode_long(index as i64, buf);
+        }
+        Value::Message(message) => encode_dynamic(message, buf),
+        Value::List(_) | Value::Map(_) => unreachable!("nested repeated value"),
+    }
+}
+
+fn encode_bytes(value: &[u8], buf: &mut Vec<u8>) {
+    encode_long(value.len() as i64, buf);
+    buf.extend_from_slice(value);
+}
+
+/// Encodes a `long` as a zig-zag varint.
+fn encode_long(value: i64, buf: &mut Vec<u8>) {
+    prost::encoding::encode_varint(((value << 1) ^ (value >> 63)) as u64, buf);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Bytes;
+
+    use crate::dynamic::MapKey;
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, test_pool, typed_field};
+    use crate::{
+        DescriptorPool, DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto,
+        FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, MessageOptions,
+    };
+
+    fn pool() -> DescriptorPool {
+        let optional = |field: FieldDescriptorProto| FieldDescriptorProto {
+            proto3_optional: Some(true),
+            ..field
+        };
+        let entry = DescriptorProto {
+            name: Some("LabelsEntry".to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, Type::Int32),
+                field("value", 2, Label::Optional, Type::String),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let event = DescriptorProto {
+            name: Some("OrderPlaced".to_string()),
+            field: vec![
+                field("id", 1, Label::Optional, Type::Sint64),
+                optional(field("note", 2, Label::Optional, Type::String)),
+                typed_field("status", 3, Label::Optional, Type::Enum, ".shop.Status"),
+                field("amounts", 4, Label::Repeated, Type::Double),
+                typed_field(
+                    "labels",
+                    5,
+                    Label::Repeated,
+                    Type::Message,
+                    ".shop.OrderPlaced.LabelsEntry",
+                ),
+                typed_field(
+                    "parent",
+                    6,
+                    Label::Optional,
+                    Type::Message,
+                    ".shop.OrderPlaced",
+                ),
+                field("data", 7, Label::Optional, Type::Bytes),
+            ],
+            nested_type: vec![entry],
+            ..Default::default()
+        };
+        let status = EnumDescriptorProto {
+            name: Some("Status".to_string()),
+            value: ["NEW", "PAID"]
+                .iter()
+                .zip(0..)
+                .map(|(name, number)| EnumValueDescriptorProto {
+                    name: Some(name.to_string()),
+                    number: Some(number),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("shop.proto".to_string()),
+                package: Some("shop".to_string()),
+                message_type: vec![event],
+                enum_type: vec![status],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn schema() {
+        let pool = pool();
+        let schema = super::schema(&pool.get_message_by_name("shop.OrderPlaced").unwrap());
+        assert_eq!(
+            schema,
+            prost_struct! {
+                "type": "record",
+                "name": "OrderPlaced",
+                "namespace": "shop",
+                "fields": vec![
+                    prost_struct! { "name": "id", "type": "long", "default": 0 },
+                    prost_struct! {
+                        "name": "note",
+                        "type": vec!["null", "string"],
+                        "default": crate::Value::null(),
+                    },
+                    prost_struct! {
+                        "name": "status",
+                        "type": prost_struct! {
+                            "type": "enum",
+                            "name": "Status",
+                            "namespace": "shop",
+                            "symbols": vec!["NEW", "PAID"],
+                            "default": "NEW",
+                        },
+                        "default": "NEW",
+                    },
+                    prost_struct! {
+                        "name": "amounts",
+                        "type": prost_struct! { "type": "array", "items": "double" },
+                        "default": Vec::<crate::Value>::new(),
+                    },
+                    prost_struct! {
+                        "name": "labels",
+                        "type": prost_struct! { "type": "map", "values": "string" },
+                        "default": Struct::default(),
+                    },
+                    prost_struct! {
+                        "name": "parent",
+                        "type": vec!["null", "shop.OrderPlaced"],
+                        "default": crate::Value::null(),
+                    },
+                    prost_struct! { "name": "data", "type": "bytes", "default": "" },
+                ],
+            }
+            .into()
+        );
+
+        let recursive = test_pool();
+        let schema = super::schema(&recursive.get_message_by_name("test.Person").unwrap());
+        assert_eq!(schema["name"].as_str(), Some("Person"));
+    }
+
+    #[test]
+    fn encode() {
+        let pool = pool();
+        let descriptor = pool.get_message_by_name("shop.OrderPlaced").unwrap();
+
+        let mut labels = prost::alloc::collections::BTreeMap::new();
+        labels.insert(MapKey::I32(-1), Value::String("a".to_string()));
+        let mut parent = DynamicMessage::new(descriptor.clone());
+        parent.set_field_by_name("status", Value::EnumNumber(7));
+        let mut message = DynamicMessage::new(descriptor.clone());
+        message.set_field_by_name("id", Value::I64(-2));
+        message.set_field_by_name("note", Value::String("hi".to_string()));
+        message.set_field_by_name("status", Value::EnumNumber(1));
+        message.set_field_by_name("amounts", Value::List(vec![Value::F64(0.5)]));
+        message.set_field_by_name("labels", Value::Map(labels));
+        message.set_field_by_name("parent", Value::Message(parent));
+        message.set_field_by_name("data", Value::Bytes(Bytes::from_static(b"\xff")));
+
+        let mut buf = Vec::new();
+        encode_dynamic(&message, &mut buf);
+        let mut expected = vec![
+            3, // id
+            2, 4, b'h', b'i', // note
+            2,    // status
+            2, 0, 0, 0, 0, 0, 0, 0xe0, 0x3f, 0, // amounts
+            2, 4, b'-', b'1', 2, b'a', 0, // labels
+            2, // parent
+        ];
+        expected.extend_from_slice(&[0; 7]); // parent fields
+        expected.extend_from_slice(&[2, 0xff]); // data
+        assert_eq!(buf, expected);
+
+        let mut generated = Vec::new();
+        super::encode(&descriptor, &message, &mut generated).unwrap();
+        assert_eq!(generated, buf);
+    }
+}
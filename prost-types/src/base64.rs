@@ -0,0 +1,249 @@
+//! Base64 encoding and decoding for `bytes` fields in JSON, and binary data in `Value`s.
+//!
+//! Encoding always uses the standard alphabet with padding. Decoding accepts both the standard
+//! and the URL-safe alphabets, with or without padding, as required by the Protobuf JSON mapping.
+//!
+//! Encoding is performed in fixed-size chunks through a [`fmt::Display`] adapter, so that large
+//! values can be written directly to a serializer or formatter without materializing the encoded string. When
+//! the `base64-simd` feature is enabled, chunks are encoded and decoded with SIMD instructions
+//! where the target supports them.
+
+use core::fmt;
+
+use prost::alloc::vec::Vec;
+
+const STANDARD: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Marks bytes which are not part of either alphabet in [`DECODE`].
+const INVALID: u8 = 0xFF;
+
+/// Maps both the standard and the URL-safe alphabets to their 6-bit values.
+const DECODE: [u8; 256] = {
+    let mut table = [INVALID; 256];
+    let mut i = 0;
+    while i < 64 {
+        table[STANDARD[i] as usize] = i as u8;
+        i += 1;
+    }
+    table[b'-' as usize] = 62;
+    table[b'_' as usize] = 63;
+    table
+};
+
+/// The number of input bytes encoded per chunk. Must be a multiple of 3, so that padding only
+/// occurs in the final chunk.
+const CHUNK_LEN: usize = 3 * 1024;
+
+/// The number of encoded bytes produced for a full chunk.
+const ENCODED_CHUNK_LEN: usize = CHUNK_LEN / 3 * 4;
+
+/// The number of input bytes up to which values are encoded into a buffer of
+/// `ENCODED_SMALL_LEN` bytes, rather than zeroing a buffer for a full chunk.
+const SMALL_LEN: usize = 3 * 32;
+
+/// The number of encoded bytes produced for a small value.
+pub(crate) const ENCODED_SMALL_LEN: usize = SMALL_LEN / 3 * 4;
+
+/// An error decoding a base64 string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DecodeError;
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid base64 string")
+    }
+}
+
+/// Displays a byte slice as standard padded base64, encoding one chunk at a time.
+pub(crate) struct Display<'a>(pub(crate) &'a [u8]);
+
+impl<'a> fmt::Display for Display<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut buf = [0u8; ENCODED_SMALL_LEN];
+        if let Some(encoded) = encode_small(self.0, &mut buf) {
+            return f.write_str(encoded);
+        }
+        let mut buf = [0u8; ENCODED_CHUNK_LEN];
+        for chunk in self.0.chunks(CHUNK_LEN) {
+            f.write_str(encode_chunk(chunk, &mut buf))?;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a short value into `buf`, returning the encoded string, or `None` if the value is
+/// longer than `SMALL_LEN` bytes.
+///
+/// Most `bytes` fields are short, such as hashes and identifiers, and are written in one piece.
+pub(crate) fn encode_small<'b>(
+    bytes: &[u8],
+    buf: &'b mut [u8; ENCODED_SMALL_LEN],
+) -> Option<&'b str> {
+    if bytes.len() <= SMALL_LEN {
+        Some(encode_chunk(bytes, buf))
+    } else {
+        None
+    }
+}
+
+/// Returns the length of the padded base64 encoding of `len` bytes.
+pub(crate) fn encoded_len(len: usize) -> usize {
+    (len + 2) / 3 * 4
+}
+
+/// Encodes at most `CHUNK_LEN` bytes into `buf`, which must hold their encoding, returning the
+/// encoded string.
+#[cfg(not(feature = "base64-simd"))]
+fn encode_chunk<'b>(chunk: &[u8], buf: &'b mut [u8]) -> &'b str {
+    debug_assert!(chunk.len() <= CHUNK_LEN && buf.len() >= encoded_len(chunk.len()));
+
+    let mut out = 0;
+    // Encode six input bytes at a time through a single 64-bit load.
+    let mut groups = chunk.chunks_exact(6);
+    for group in &mut groups {
+        let word = u64::from_be_bytes([
+            group[0], group[1], group[2], group[3], group[4], group[5], 0, 0,
+        ]);
+        for (i, b) in buf[out..out + 8].iter_mut().enumerate() {
+            *b = STANDARD[(word >> (58 - 6 * i) & 0x3F) as usize];
+        }
+        out += 8;
+    }
+
+    let mut rest = groups.remainder().chunks(3);
+    for group in &mut rest {
+        let word = (group[0] as u32) << 16
+            | (*group.get(1).unwrap_or(&0) as u32) << 8
+            | *group.get(2).unwrap_or(&0) as u32;
+        buf[out] = STANDARD[(word >> 18 & 0x3F) as usize];
+        buf[out + 1] = STANDARD[(word >> 12 & 0x3F) as usize];
+        buf[out + 2] = if group.len() > 1 {
+            STANDARD[(word >> 6 & 0x3F) as usize]
+        } else {
+            b'='
+        };
+        buf[out + 3] = if group.len() > 2 {
+            STANDARD[(word & 0x3F) as usize]
+        } else {
+            b'='
+        };
+        out += 4;
+    }
+
+    // The buffer only contains characters from the base64 alphabet.
+    core::str::from_utf8(&buf[..out]).expect("base64 output is not ASCII")
+}
+
+#[cfg(feature = "base64-simd")]
+fn encode_chunk<'b>(chunk: &[u8], buf: &'b mut [u8]) -> &'b str {
+    let len = encoded_len(chunk.len());
+    base64_simd::STANDARD.encode_as_str(chunk, base64_simd::Out::from_slice(&mut buf[..len]))
+}
+
+/// Decodes a base64 string in either alphabet, with or without padding.
+pub(crate) fn decode(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    #[cfg(feature = "base64-simd")]
+    {
+        // Fast path for the canonical encoding, which is what conforming encoders emit.
+        if let Ok(decoded) = base64_simd::STANDARD.decode_to_vec(input) {
+            return Ok(decoded);
+        }
+    }
+
+    let input = match input {
+        [rest @ .., b'=', b'='] | [rest @ .., b'='] if input.len() % 4 == 0 => rest,
+        _ => input,
+    };
+    if input.len() % 4 == 1 {
+        return Err(DecodeError);
+    }
+
+    let mut output = Vec::with_capacity(input.len() / 4 * 3 + 2);
+    let mut quads = input.chunks_exact(4);
+    for quad in &mut quads {
+        let word = decode_quad(quad)?;
+        output.extend_from_slice(&word.to_be_bytes()[1..]);
+    }
+
+    let rest = quads.remainder();
+    if !rest.is_empty() {
+        let mut quad = [b'A'; 4];
+        quad[..rest.len()].copy_from_slice(rest);
+        let word = decode_quad(&quad)?;
+        // Two characters encode one byte, and three characters encode two bytes.
+        output.extend_from_slice(&word.to_be_bytes()[1..rest.len()]);
+    }
+    Ok(output)
+}
+
+/// Decodes four base64 characters into the low 24 bits of a word.
+#[inline]
+fn decode_quad(quad: &[u8]) -> Result<u32, DecodeError> {
+    let mut word = 0u32;
+    for &c in quad {
+        let value = DECODE[c as usize];
+        if value == INVALID {
+            return Err(DecodeError);
+        }
+        word = word << 6 | value as u32;
+    }
+    Ok(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::alloc::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn encode_rfc4648_vectors() {
+        let vectors: &[(&[u8], &str)] = &[
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"fooba", "Zm9vYmE="),
+            (b"foobar", "Zm9vYmFy"),
+            (b"\xFB\xFF\xBF", "+/+/"),
+        ];
+        for &(input, expected) in vectors {
+            assert_eq!(Display(input).to_string(), expected);
+            assert_eq!(decode(expected.as_bytes()).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn decode_variants() {
+        assert_eq!(decode(b"-_-_").unwrap(), b"\xFB\xFF\xBF");
+        assert_eq!(decode(b"Zg").unwrap(), b"f");
+        assert_eq!(decode(b"Zm8").unwrap(), b"fo");
+
+        assert_eq!(decode(b"Z"), Err(DecodeError));
+        assert_eq!(decode(b"Zm9v!A=="), Err(DecodeError));
+        assert_eq!(decode(b"Zg=a"), Err(DecodeError));
+        assert_eq!(decode(b"Zg==="), Err(DecodeError));
+    }
+
+    #[test]
+    fn multiple_chunks() {
+        let input = (0..3 * CHUNK_LEN + 7)
+            .map(|i| (i * 31) as u8)
+            .collect::<Vec<u8>>();
+        let encoded = Display(&input).to_string();
+        assert_eq!(encoded.len(), encoded_len(input.len()));
+        assert!(encoded.ends_with('='));
+        assert_eq!(decode(encoded.as_bytes()).unwrap(), input);
+    }
+
+    #[test]
+    fn small_values() {
+        for len in SMALL_LEN - 7..SMALL_LEN + 7 {
+            let input = (0..len).map(|i| (i * 31) as u8).collect::<Vec<u8>>();
+            let encoded = Display(&input).to_string();
+            assert_eq!(encoded.len(), encoded_len(len));
+            assert_eq!(decode(encoded.as_bytes()).unwrap(), input);
+        }
+    }
+}
@@ -0,0 +1,460 @@
+//! Detection of breaking changes between two versions of a schema.
+//!
+//! [`breaking_changes`] compares the messages of two `FileDescriptorSet`s, such as the sets
+//! produced by `protoc --include_imports --descriptor_set_out` from the schema of the main branch
+//! and from a proposed change, and reports the changes which break peers and stored data still
+//! using the old schema, so that CI can reject them:
+//!
+//! ```rust,ignore
+//! let changes = prost_types::compat::breaking_changes(&main, &proposed);
+//! for change in &changes {
+//!     eprintln!("{}", change);
+//! }
+//! assert!(changes.is_empty(), "breaking schema changes");
+//! ```
+//!
+//! Messages are matched by their fully-qualified names, and their fields by number. The reported
+//! changes are:
+//!
+//!  * [`BreakingChange::FieldTypeChanged`]: the type of a field changed, including from singular
+//!    to repeated, or between maps with different key or value types. Changes which keep the wire
+//!    format compatible, such as from `int32` to `int64`, are reported too, since they change the
+//!    generated code and can truncate values.
+//!  * [`BreakingChange::FieldNumberReused`]: a field number is used by a field with a different
+//!    name, or by a field although the old schema reserved it. Peers using the old schema decode
+//!    the new field as the old one.
+//!  * [`BreakingChange::RequiredFieldRemoved`]: a `required` field was removed. Peers using the
+//!    old schema reject the messages which lack it.
+//!  * [`BreakingChange::JsonNameChanged`]: the JSON name of a field changed with the `json_name`
+//!    option. JSON written with the old schema no longer decodes.
+//!
+//! Messages which were removed are not reported, since the fields referring to them must have been
+//! removed or changed type as well. Map entry messages are compared as part of their map fields.
+
+use core::fmt;
+
+use prost::alloc::collections::BTreeMap;
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec::Vec;
+
+use crate::field_descriptor_proto::{Label, Type};
+use crate::reflect::to_json_name;
+use crate::{DescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+
+/// A change of a schema which breaks peers or data using the previous version of the schema.
+///
+/// Messages are named by their fully-qualified names, without a leading `.`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BreakingChange {
+    /// The type of a field changed.
+    FieldTypeChanged {
+        message: String,
+        /// The name of the field in the new schema.
+        field: String,
+        number: i32,
+        /// The type in the old schema, as written in a `.proto` file, such as `repeated int64`,
+        /// `map<string, shop.Item>` or `shop.Order`.
+        old_type: String,
+        new_type: String,
+    },
+    /// A field number is used by a new field.
+    FieldNumberReused {
+        message: String,
+        number: i32,
+        /// The name of the field using the number in the old schema, or `None` if the old schema
+        /// reserved the number.
+        old_field: Option<String>,
+        new_field: String,
+    },
+    /// A `required` field was removed.
+    RequiredFieldRemoved {
+        message: String,
+        field: String,
+        number: i32,
+    },
+    /// The JSON name of a field changed.
+    JsonNameChanged {
+        message: String,
+        field: String,
+        number: i32,
+        old_json_name: String,
+        new_json_name: String,
+    },
+}
+
+impl fmt::Display for BreakingChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BreakingChange::FieldTypeChanged {
+                message,
+                field,
+                number,
+                old_type,
+                new_type,
+            } => write!(
+                f,
+                "{}.{} ({}): type changed from {} to {}",
+                message, field, number, old_type, new_type
+            ),
+            BreakingChange::FieldNumberReused {
+                message,
+                number,
+                old_field: Some(old_field),
+                new_field,
+            } => write!(
+                f,
+                "{}: field number {} of {} is reused by {}",
+                message, number, old_field, new_field
+            ),
+            BreakingChange::FieldNumberReused {
+                message,
+                number,
+                old_field: None,
+                new_field,
+            } => write!(
+                f,
+                "{}: reserved field number {} is used by {}",
+                message, number, new_field
+            ),
+            BreakingChange::RequiredFieldRemoved {
+                message,
+                field,
+                number,
+            } => write!(
+                f,
+                "{}.{} ({}): required field removed",
+                message, field, number
+            ),
+            BreakingChange::JsonNameChanged {
+                message,
+                field,
+                number,
+                old_json_name,
+                new_json_name,
+            } => write!(
+                f,
+                "{}.{} ({}): JSON name changed from {} to {}",
+                message, field, number, old_json_name, new_json_name
+            ),
+        }
+    }
+}
+
+/// Returns the breaking changes from the `old` schema to the `new` one.
+///
+/// The changes are ordered by message name, then by the declaration order of the fields in the
+/// old schema, followed by the fields using numbers which the old schema reserved.
+pub fn breaking_changes(old: &FileDescriptorSet, new: &FileDescriptorSet) -> Vec<BreakingChange> {
+    let old_messages = messages(old);
+    let new_messages = messages(new);
+
+    let mut changes = Vec::new();
+    for (name, old_message) in &old_messages {
+        let new_message = match new_messages.get(name) {
+            Some(new_message) if !is_map_entry(old_message) => new_message,
+            _ => continue,
+        };
+
+        for old_field in &old_message.field {
+            let number = old_field.number();
+            let new_field = match new_message.field.iter().find(|f| f.number() == number) {
+                Some(new_field) => new_field,
+                None => {
+                    if old_field.label() == Label::Required {
+                        changes.push(BreakingChange::RequiredFieldRemoved {
+                            message: name.clone(),
+                            field: old_field.name().to_string(),
+                            number,
+                        });
+                    }
+                    continue;
+                }
+            };
+
+            // A renamed field also changes its JSON name, which is not reported separately.
+            if old_field.name() != new_field.name() {
+                changes.push(BreakingChange::FieldNumberReused {
+                    message: name.clone(),
+                    number,
+                    old_field: Some(old_field.name().to_string()),
+                    new_field: new_field.name().to_string(),
+                });
+            } else if json_name(old_field) != json_name(new_field) {
+                changes.push(BreakingChange::JsonNameChanged {
+                    message: name.clone(),
+                    field: new_field.name().to_string(),
+                    number,
+                    old_json_name: json_name(old_field),
+                    new_json_name: json_name(new_field),
+                });
+            }
+
+            let old_type = field_type(old_field, &old_messages);
+            let new_type = field_type(new_field, &new_messages);
+            if old_type != new_type {
+                changes.push(BreakingChange::FieldTypeChanged {
+                    message: name.clone(),
+                    field: new_field.name().to_string(),
+                    number,
+                    old_type,
+                    new_type,
+                });
+            }
+        }
+
+        for new_field in &new_message.field {
+            let number = new_field.number();
+            let reserved = old_message
+                .reserved_range
+                .iter()
+                .any(|range| range.start() <= number && number < range.end());
+            if reserved && old_message.field.iter().all(|f| f.number() != number) {
+                changes.push(BreakingChange::FieldNumberReused {
+                    message: name.clone(),
+                    number,
+                    old_field: None,
+                    new_field: new_field.name().to_string(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+/// Returns the messages of the files, including nested messages, by fully-qualified name.
+fn messages(files: &FileDescriptorSet) -> BTreeMap<String, &DescriptorProto> {
+    fn add<'a>(
+        prefix: &str,
+        message: &'a DescriptorProto,
+        messages: &mut BTreeMap<String, &'a DescriptorProto>,
+    ) {
+        let name = if prefix.is_empty() {
+            message.name().to_string()
+        } else {
+            format!("{}.{}", prefix, message.name())
+        };
+        for nested in &message.nested_type {
+            add(&name, nested, messages);
+        }
+        messages.insert(name, message);
+    }
+
+    let mut messages = BTreeMap::new();
+    for file in &files.file {
+        for message in &file.message_type {
+            add(file.package(), message, &mut messages);
+        }
+    }
+    messages
+}
+
+fn is_map_entry(message: &DescriptorProto) -> bool {
+    message
+        .options
+        .as_ref()
+        .map_or(false, |options| options.map_entry())
+}
+
+/// Returns the JSON name of a field, which `protoc` sets, or derives it from the field name.
+fn json_name(field: &FieldDescriptorProto) -> String {
+    field
+        .json_name
+        .clone()
+        .unwrap_or_else(|| to_json_name(field.name()))
+}
+
+/// Returns the type of a field as written in a `.proto` file.
+fn field_type(
+    field: &FieldDescriptorProto,
+    messages: &BTreeMap<String, &DescriptorProto>,
+) -> String {
+    let ty = match field.r#type() {
+        Type::Message | Type::Enum | Type::Group => {
+            let type_name = field.type_name().trim_start_matches('.');
+            let entry = messages
+                .get(type_name)
+                .filter(|message| is_map_entry(message));
+            if let Some(entry) = entry {
+                let entry_type = |number| {
+                    entry
+                        .field
+                        .iter()
+                        .find(|field| field.number() == number)
+                        .map_or_else(String::new, |field| field_type(field, messages))
+                };
+                return format!("map<{}, {}>", entry_type(1), entry_type(2));
+            }
+            if field.r#type() == Type::Group {
+                format!("group {}", type_name)
+            } else {
+                type_name.to_string()
+            }
+        }
+        ty => ty.as_str_name()["TYPE_".len()..].to_ascii_lowercase(),
+    };
+    if field.label() == Label::Repeated {
+        format!("repeated {}", ty)
+    } else {
+        ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::descriptor_proto::ReservedRange;
+    use crate::reflect::tests::{field, typed_field};
+    use crate::{FileDescriptorProto, MessageOptions};
+
+    fn schema(messages: Vec<DescriptorProto>) -> FileDescriptorSet {
+        FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("shop.proto".to_string()),
+                package: Some("shop".to_string()),
+                message_type: messages,
+                ..Default::default()
+            }],
+        }
+    }
+
+    fn message(name: &str, fields: Vec<FieldDescriptorProto>) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.to_string()),
+            field: fields,
+            ..Default::default()
+        }
+    }
+
+    fn map_entry(key: Type, value: Type) -> DescriptorProto {
+        DescriptorProto {
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..message(
+                "CountsEntry",
+                vec![
+                    field("key", 1, Label::Optional, key),
+                    field("value", 2, Label::Optional, value),
+                ],
+            )
+        }
+    }
+
+    fn order(fields: Vec<FieldDescriptorProto>, counts: Type) -> DescriptorProto {
+        DescriptorProto {
+            nested_type: vec![map_entry(Type::String, counts)],
+            ..message("Order", fields)
+        }
+    }
+
+    fn old() -> FileDescriptorSet {
+        schema(vec![
+            DescriptorProto {
+                reserved_range: vec![ReservedRange {
+                    start: Some(10),
+                    end: Some(12),
+                }],
+                ..order(
+                    vec![
+                        field("id", 1, Label::Required, Type::String),
+                        field("total", 2, Label::Optional, Type::Int64),
+                        typed_field("item", 3, Label::Optional, Type::Message, ".shop.Item"),
+                        field("note", 4, Label::Optional, Type::String),
+                        field("tags", 5, Label::Repeated, Type::String),
+                        typed_field(
+                            "counts",
+                            6,
+                            Label::Repeated,
+                            Type::Message,
+                            ".shop.Order.CountsEntry",
+                        ),
+                        field("legacy", 7, Label::Optional, Type::Bool),
+                    ],
+                    Type::Int32,
+                )
+            },
+            message("Item", vec![field("sku", 1, Label::Optional, Type::String)]),
+        ])
+    }
+
+    #[test]
+    fn compatible() {
+        assert_eq!(breaking_changes(&old(), &old()), []);
+
+        // Adding fields and messages, and removing optional fields, is compatible.
+        let mut new = old();
+        let order = &mut new.file[0].message_type[0];
+        order.field.retain(|field| field.name() != "legacy");
+        order
+            .field
+            .push(field("currency", 8, Label::Optional, Type::String));
+        new.file[0].message_type.push(message("Refund", Vec::new()));
+        assert_eq!(breaking_changes(&old(), &new), []);
+
+        // `protoc` sets the JSON names, which are otherwise derived from the field names.
+        let mut new = old();
+        for field in &mut new.file[0].message_type[0].field {
+            field.json_name = Some(to_json_name(field.name()));
+        }
+        assert_eq!(breaking_changes(&old(), &new), []);
+    }
+
+    #[test]
+    fn breaking() {
+        let mut new = schema(vec![order(
+            vec![
+                field("total", 2, Label::Optional, Type::String),
+                typed_field("item", 3, Label::Optional, Type::Message, ".shop.Item"),
+                field("comment", 4, Label::Optional, Type::String),
+                field("tags", 5, Label::Optional, Type::String),
+                typed_field(
+                    "counts",
+                    6,
+                    Label::Repeated,
+                    Type::Message,
+                    ".shop.Order.CountsEntry",
+                ),
+                FieldDescriptorProto {
+                    json_name: Some("isLegacy".to_string()),
+                    ..field("legacy", 7, Label::Optional, Type::Bool)
+                },
+                field("discount", 11, Label::Optional, Type::Double),
+            ],
+            Type::Int64,
+        )]);
+        new.file[0].message_type.push(message(
+            "Item",
+            vec![field("sku", 1, Label::Optional, Type::String)],
+        ));
+
+        let changes = breaking_changes(&old(), &new);
+        let messages = changes
+            .iter()
+            .map(|change| change.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "shop.Order.id (1): required field removed",
+                "shop.Order.total (2): type changed from int64 to string",
+                "shop.Order: field number 4 of note is reused by comment",
+                "shop.Order.tags (5): type changed from repeated string to string",
+                "shop.Order.counts (6): type changed from map<string, int32> to map<string, int64>",
+                "shop.Order.legacy (7): JSON name changed from legacy to isLegacy",
+                "shop.Order: reserved field number 11 is used by discount",
+            ]
+        );
+        assert_eq!(
+            changes[0],
+            BreakingChange::RequiredFieldRemoved {
+                message: "shop.Order".to_string(),
+                field: "id".to_string(),
+                number: 1,
+            }
+        );
+    }
+}
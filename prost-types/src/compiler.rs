@@ -1,5 +1,5 @@
 /// The version number of protocol compiler.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Version {
     #[prost(int32, optional, tag="1")]
     pub major: ::core::option::Option<i32>,
@@ -13,7 +13,7 @@ pub struct Version {
     pub suffix: ::core::option::Option<::prost::alloc::string::String>,
 }
 /// An encoded CodeGeneratorRequest is written to the plugin's stdin.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct CodeGeneratorRequest {
     /// The .proto files that were explicitly listed on the command-line.  The
     /// code generator should generate code only for these files.  Each file's
@@ -44,7 +44,7 @@ pub struct CodeGeneratorRequest {
     pub compiler_version: ::core::option::Option<Version>,
 }
 /// The plugin writes an encoded CodeGeneratorResponse to stdout.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct CodeGeneratorResponse {
     /// Error message.  If non-empty, code generation failed.  The plugin process
     /// should exit with status code zero even if it reports an error in this way.
@@ -66,7 +66,7 @@ pub struct CodeGeneratorResponse {
 /// Nested message and enum types in `CodeGeneratorResponse`.
 pub mod code_generator_response {
     /// Represents a single generated file.
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct File {
         /// The file name, relative to the output directory.  The name must not
         /// contain "." or ".." components and must be relative, not be absolute (so,
@@ -130,10 +130,34 @@ pub mod code_generator_response {
         pub generated_code_info: ::core::option::Option<super::super::GeneratedCodeInfo>,
     }
     /// Sync with code_generator.h.
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum Feature {
         None = 0,
         Proto3Optional = 1,
     }
+    impl Feature {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("FEATURE_NONE", 0),
+            ("FEATURE_PROTO3_OPTIONAL", 1),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::None => "FEATURE_NONE",
+                Self::Proto3Optional => "FEATURE_PROTO3_OPTIONAL",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "FEATURE_NONE" => ::core::option::Option::Some(Self::None),
+                "FEATURE_PROTO3_OPTIONAL" => ::core::option::Option::Some(Self::Proto3Optional),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
 }
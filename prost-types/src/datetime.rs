@@ -0,0 +1,935 @@
+//! Formatting and parsing of `Timestamp` and `Duration`, and conversions between them and the
+//! types of the `chrono` and `time` crates.
+//!
+//! Timestamps are formatted as RFC 3339 date-times in UTC, and durations as a number of seconds
+//! with an `s` suffix, both as specified by the Protobuf JSON mapping. Both are parsed from the
+//! same formats with [`str::parse`], and timestamps which are leap seconds with
+//! [`Timestamp::parse_with`].
+//!
+//! Conversions into `Timestamp` and `Duration` are infallible, since they can represent every
+//! value of the other crates' types. Conversions in the other direction fail with
+//! [`TimestampOutOfRangeError`] or [`DurationOutOfRangeError`] when the value can't be
+//! represented.
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+use core::convert::TryFrom;
+use core::fmt;
+use core::str::FromStr;
+
+use crate::{Duration, Timestamp, NANOS_MAX, NANOS_PER_SECOND};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// Formats the timestamp as an RFC 3339 date-time in UTC, e.g. `1972-01-01T10:00:20.021Z`.
+///
+/// The timestamp is normalized before it is formatted. The fractional seconds are formatted with 0,
+/// 3, 6 or 9 digits, whichever is shortest without losing precision. Years outside of the range
+/// `0000` to `9999`, which are not valid Protobuf timestamps, are formatted with their sign.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut timestamp = self.clone();
+        timestamp.normalize();
+
+        let (year, month, day) = civil_from_days(timestamp.seconds.div_euclid(SECONDS_PER_DAY));
+        let seconds = timestamp.seconds.rem_euclid(SECONDS_PER_DAY);
+        if (0..=9999).contains(&year) {
+            write!(f, "{:04}", year)?;
+        } else {
+            write!(f, "{:+}", year)?;
+        }
+        write!(
+            f,
+            "-{:02}-{:02}T{:02}:{:02}:{:02}",
+            month,
+            day,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )?;
+        write_nanos(f, timestamp.nanos as u32)?;
+        f.write_str("Z")
+    }
+}
+
+/// Formats the duration as a number of seconds with an `s` suffix, e.g. `-1.500s`.
+///
+/// The duration is normalized before it is formatted. The fractional seconds are formatted with 0,
+/// 3, 6 or 9 digits, whichever is shortest without losing precision.
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut duration = self.clone();
+        duration.normalize();
+
+        if duration.seconds < 0 || duration.nanos < 0 {
+            f.write_str("-")?;
+        }
+        write!(f, "{}", duration.seconds.unsigned_abs())?;
+        write_nanos(f, duration.nanos.unsigned_abs())?;
+        f.write_str("s")
+    }
+}
+
+fn write_nanos(f: &mut fmt::Formatter<'_>, nanos: u32) -> fmt::Result {
+    if nanos == 0 {
+        Ok(())
+    } else if nanos % 1_000_000 == 0 {
+        write!(f, ".{:03}", nanos / 1_000_000)
+    } else if nanos % 1_000 == 0 {
+        write!(f, ".{:06}", nanos / 1_000)
+    } else {
+        write!(f, ".{:09}", nanos)
+    }
+}
+
+/// How leap seconds, such as `2016-12-31T23:59:60Z`, are parsed.
+///
+/// A `Timestamp` counts seconds as if every day had 86,400 of them, so the 86,401st second of a
+/// day with a leap second has no timestamp of its own. The Protobuf JSON mapping rejects leap
+/// seconds, but data feeds which timestamp events by UTC, such as scientific instruments, emit
+/// them, and the policy selects how they are mapped to a timestamp instead.
+///
+/// A leap second must be the last second of a UTC day, e.g. `2016-12-31T23:59:60Z` or
+/// `2017-01-01T05:29:60+05:30`. Other times with a 60th second are malformed under every policy.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LeapSecondPolicy {
+    /// Leap seconds fail to parse with [`TimestampError::LeapSecond`].
+    Reject,
+    /// Leap seconds are parsed as the last nanosecond of the preceding second, `23:59:59.999999999`,
+    /// so that they sort before the following day.
+    Clamp,
+    /// Leap seconds are parsed as the instant a clock shows during the leap second if it smears
+    /// the leap second linearly over the 24 hours from noon to noon UTC, as Google's and Amazon's
+    /// time services do, and as `google.protobuf.Timestamp` assumes.
+    ///
+    /// The leap second falls in the middle of the smear, so it maps to the second from
+    /// `23:59:59.500005786` to `00:00:00.499994213`. Only the leap second itself is adjusted,
+    /// since the timestamps around it don't show that the day has a leap second.
+    Smear,
+}
+
+impl Default for LeapSecondPolicy {
+    fn default() -> LeapSecondPolicy {
+        LeapSecondPolicy::Reject
+    }
+}
+
+/// An error parsing a [`Timestamp`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampError {
+    /// The string is not an RFC 3339 date-time, such as `1972-01-01T10:00:20.021Z`.
+    ParseFailure,
+    /// The date-time is a leap second, which the [`LeapSecondPolicy`] rejects.
+    LeapSecond,
+    /// The date-time is outside of the range `0001-01-01T00:00:00Z` to
+    /// `9999-12-31T23:59:59.999999999Z`, which a `google.protobuf.Timestamp` can represent.
+    OutOfRange,
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimestampError::ParseFailure => f.write_str("failed to parse timestamp"),
+            TimestampError::LeapSecond => f.write_str("timestamp is a leap second"),
+            TimestampError::OutOfRange => f.write_str("timestamp out of range"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TimestampError {}
+
+/// Parses an RFC 3339 date-time, e.g. `1972-01-01T10:00:20.021-05:00`, as specified by the
+/// Protobuf JSON mapping.
+///
+/// Leap seconds are rejected. Use [`Timestamp::parse_with`] to accept them.
+impl FromStr for Timestamp {
+    type Err = TimestampError;
+
+    fn from_str(s: &str) -> Result<Timestamp, TimestampError> {
+        Timestamp::parse_with(s, LeapSecondPolicy::Reject)
+    }
+}
+
+impl Timestamp {
+    /// Parses an RFC 3339 date-time, e.g. `1972-01-01T10:00:20.021-05:00`, handling leap seconds
+    /// with the given policy.
+    pub fn parse_with(s: &str, policy: LeapSecondPolicy) -> Result<Timestamp, TimestampError> {
+        let bytes = s.as_bytes();
+        if !s.is_ascii()
+            || bytes.len() < 20
+            || bytes[4] != b'-'
+            || bytes[7] != b'-'
+            || !(bytes[10] == b'T' || bytes[10] == b't')
+            || bytes[13] != b':'
+            || bytes[16] != b':'
+        {
+            return Err(TimestampError::ParseFailure);
+        }
+
+        let digits = |s: &str| parse_digits(s).ok_or(TimestampError::ParseFailure);
+        let year = i64::from(digits(&s[0..4])?);
+        let month = digits(&s[5..7])?;
+        let day = digits(&s[8..10])?;
+        let hour = digits(&s[11..13])?;
+        let minute = digits(&s[14..16])?;
+        let second = digits(&s[17..19])?;
+        if !(1..=12).contains(&month)
+            || !(1..=days_in_month(year, month)).contains(&day)
+            || hour >= 24
+            || minute >= 60
+            || second > 60
+        {
+            return Err(TimestampError::ParseFailure);
+        }
+
+        let mut rest = &s[19..];
+        let nanos = match rest.strip_prefix('.') {
+            Some(fraction) => {
+                let len = fraction
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(fraction.len());
+                rest = &fraction[len..];
+                parse_nanos(&fraction[..len]).ok_or(TimestampError::ParseFailure)?
+            }
+            None => 0,
+        };
+
+        let offset = match rest.as_bytes() {
+            b"Z" | b"z" => 0,
+            &[sign, _, _, b':', _, _] if sign == b'+' || sign == b'-' => {
+                let hours = digits(&rest[1..3])?;
+                let minutes = digits(&rest[4..6])?;
+                if hours >= 24 || minutes >= 60 {
+                    return Err(TimestampError::ParseFailure);
+                }
+                let offset = i64::from(hours * 3600 + minutes * 60);
+                if sign == b'-' {
+                    -offset
+                } else {
+                    offset
+                }
+            }
+            _ => return Err(TimestampError::ParseFailure),
+        };
+
+        // The seconds at the start of the following second, which for a leap second is midnight.
+        let next = days_from_civil(year, month, day) * SECONDS_PER_DAY
+            + i64::from(hour * 3600 + minute * 60 + second.min(59))
+            + 1
+            - offset;
+        let timestamp = if second < 60 {
+            Timestamp {
+                seconds: next - 1,
+                nanos,
+            }
+        } else if next.rem_euclid(SECONDS_PER_DAY) != 0 {
+            return Err(TimestampError::ParseFailure);
+        } else {
+            match policy {
+                LeapSecondPolicy::Reject => return Err(TimestampError::LeapSecond),
+                LeapSecondPolicy::Clamp => Timestamp {
+                    seconds: next - 1,
+                    nanos: NANOS_MAX,
+                },
+                LeapSecondPolicy::Smear => {
+                    // Noon to noon is 86,401 seconds of UTC shown as 86,400 seconds, and the leap
+                    // second starts 43,200 seconds after noon.
+                    let nanos =
+                        i64::from(nanos) * SECONDS_PER_DAY - 43_200 * i64::from(NANOS_PER_SECOND);
+                    let nanos = nanos.div_euclid(SECONDS_PER_DAY + 1);
+                    Timestamp {
+                        seconds: next + nanos.div_euclid(i64::from(NANOS_PER_SECOND)),
+                        nanos: nanos.rem_euclid(i64::from(NANOS_PER_SECOND)) as i32,
+                    }
+                }
+            }
+        };
+        if timestamp.is_valid() {
+            Ok(timestamp)
+        } else {
+            Err(TimestampError::OutOfRange)
+        }
+    }
+}
+
+/// Parses an RFC 3339 date-time, e.g. `1972-01-01T10:00:20.021-05:00`.
+///
+/// Returns `None` if the date-time is malformed, is a leap second, or is not a valid Protobuf
+/// timestamp.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    s.parse().ok()
+}
+
+/// An error parsing a [`Duration`], or converting it to a `std::time::Duration`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DurationError {
+    /// The string is not a number of seconds with an `s` suffix, such as `-1.5s`.
+    ParseFailure,
+    /// The duration is negative, and so can't be converted to a `std::time::Duration`. Holds the
+    /// magnitude of the duration.
+    NegativeDuration(core::time::Duration),
+    /// The duration is longer than the 315,576,000,000 seconds, or about 10,000 years, which a
+    /// `google.protobuf.Duration` can represent.
+    OutOfRange,
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DurationError::ParseFailure => f.write_str("failed to parse duration"),
+            DurationError::NegativeDuration(magnitude) => {
+                write!(f, "failed to convert negative duration: -{:?}", magnitude)
+            }
+            DurationError::OutOfRange => f.write_str("duration out of range"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DurationError {}
+
+/// Parses a duration formatted as a number of seconds with an `s` suffix, e.g. `-1.5s`, as
+/// specified by the Protobuf JSON mapping.
+///
+/// The number may have a leading `-` and up to 9 fractional digits, and has at least one integer
+/// digit. Parsing fails with [`DurationError::OutOfRange`] if the duration is longer than a
+/// `google.protobuf.Duration` can represent.
+impl FromStr for Duration {
+    type Err = DurationError;
+
+    fn from_str(s: &str) -> Result<Duration, DurationError> {
+        let s = s.strip_suffix('s').ok_or(DurationError::ParseFailure)?;
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let (seconds, nanos) = match s.find('.') {
+            Some(point) => (
+                &s[..point],
+                parse_nanos(&s[point + 1..]).ok_or(DurationError::ParseFailure)?,
+            ),
+            None => (s, 0),
+        };
+        if seconds.is_empty() || !seconds.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(DurationError::ParseFailure);
+        }
+        // The seconds are only digits, so parsing fails only if they overflow.
+        let seconds = seconds
+            .parse::<i64>()
+            .map_err(|_| DurationError::OutOfRange)?;
+
+        let duration = if negative {
+            Duration {
+                seconds: -seconds,
+                nanos: -nanos,
+            }
+        } else {
+            Duration { seconds, nanos }
+        };
+        if duration.is_valid() {
+            Ok(duration)
+        } else {
+            Err(DurationError::OutOfRange)
+        }
+    }
+}
+
+/// Parses a duration formatted as a number of seconds with an `s` suffix, e.g. `-1.5s`.
+///
+/// Returns `None` if the duration is malformed, or is not a valid Protobuf duration.
+#[cfg_attr(not(feature = "serde"), allow(dead_code))]
+pub(crate) fn parse_duration(s: &str) -> Option<Duration> {
+    s.parse().ok()
+}
+
+/// Parses a non-empty string of ASCII digits.
+fn parse_digits(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Parses 1 to 9 digits of fractional seconds as nanoseconds.
+fn parse_nanos(s: &str) -> Option<i32> {
+    if s.len() > 9 {
+        return None;
+    }
+    let digits = parse_digits(s)?;
+    Some((digits * 10u32.pow(9 - s.len() as u32)) as i32)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days since the Unix epoch of a date in the proleptic Gregorian calendar.
+///
+/// Based on [`days_from_civil`][1].
+/// [1]: http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * i64::from((month + 9) % 12) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the date in the proleptic Gregorian calendar of a number of days since the Unix epoch.
+///
+/// Based on [`civil_from_days`][1].
+/// [1]: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+/// Indicates that a [`Timestamp`] could not be converted to a date-time type because it is out of
+/// range.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct TimestampOutOfRangeError {
+    pub timestamp: Timestamp,
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+impl fmt::Display for TimestampOutOfRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not representable as a date-time because it is out of range",
+            self.timestamp
+        )
+    }
+}
+
+#[cfg(all(feature = "std", any(feature = "chrono", feature = "time")))]
+impl std::error::Error for TimestampOutOfRangeError {}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+/// Indicates that a [`Duration`] could not be converted to another duration type because it is
+/// out of range.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DurationOutOfRangeError {
+    pub duration: Duration,
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+impl fmt::Display for DurationOutOfRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not representable as a duration because it is out of range",
+            self.duration
+        )
+    }
+}
+
+#[cfg(all(feature = "std", any(feature = "chrono", feature = "time")))]
+impl std::error::Error for DurationOutOfRangeError {}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Timestamp {
+    fn from(date_time: chrono::DateTime<Tz>) -> Timestamp {
+        // The sub-second nanoseconds exceed one second during a leap second, which normalizing
+        // folds into the following second.
+        let mut timestamp = Timestamp {
+            seconds: date_time.timestamp(),
+            nanos: date_time.timestamp_subsec_nanos() as i32,
+        };
+        timestamp.normalize();
+        timestamp
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = TimestampOutOfRangeError;
+
+    fn try_from(timestamp: Timestamp) -> Result<chrono::DateTime<chrono::Utc>, Self::Error> {
+        use chrono::TimeZone;
+
+        let mut normalized = timestamp.clone();
+        normalized.normalize();
+        chrono::Utc
+            .timestamp_opt(normalized.seconds, normalized.nanos as u32)
+            .single()
+            .ok_or(TimestampOutOfRangeError { timestamp })
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::Duration> for Duration {
+    fn from(duration: chrono::Duration) -> Duration {
+        // `subsec_nanos` has the same sign as `num_seconds`, so the result is already normalized.
+        Duration {
+            seconds: duration.num_seconds(),
+            nanos: duration.subsec_nanos(),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Duration> for chrono::Duration {
+    type Error = DurationOutOfRangeError;
+
+    fn try_from(duration: Duration) -> Result<chrono::Duration, Self::Error> {
+        chrono::Duration::try_seconds(duration.seconds)
+            .and_then(|seconds| {
+                seconds.checked_add(&chrono::Duration::nanoseconds(i64::from(duration.nanos)))
+            })
+            .ok_or(DurationOutOfRangeError { duration })
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Timestamp {
+    fn from(date_time: time::OffsetDateTime) -> Timestamp {
+        Timestamp {
+            seconds: date_time.unix_timestamp(),
+            nanos: date_time.nanosecond() as i32,
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for time::OffsetDateTime {
+    type Error = TimestampOutOfRangeError;
+
+    fn try_from(timestamp: Timestamp) -> Result<time::OffsetDateTime, Self::Error> {
+        time::OffsetDateTime::from_unix_timestamp_nanos(timestamp.total_nanos())
+            .map_err(|_| TimestampOutOfRangeError { timestamp })
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::Duration> for Duration {
+    fn from(duration: time::Duration) -> Duration {
+        // `subsec_nanoseconds` has the same sign as `whole_seconds`, so the result is already
+        // normalized.
+        Duration {
+            seconds: duration.whole_seconds(),
+            nanos: duration.subsec_nanoseconds(),
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Duration> for time::Duration {
+    type Error = DurationOutOfRangeError;
+
+    fn try_from(duration: Duration) -> Result<time::Duration, Self::Error> {
+        // `time::Duration` covers the full range of `Duration`, but only once it is normalized;
+        // the carry from an out-of-range `nanos` may still overflow `seconds`.
+        let mut normalized = duration.clone();
+        normalized.normalize();
+        if normalized.total_nanos() != duration.total_nanos() {
+            return Err(DurationOutOfRangeError { duration });
+        }
+        Ok(time::Duration::new(normalized.seconds, normalized.nanos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::alloc::string::ToString;
+
+    use super::*;
+    use crate::arithmetic::{TIMESTAMP_SECONDS_MAX, TIMESTAMP_SECONDS_MIN};
+
+    #[test]
+    fn check_timestamp_format_and_parse() {
+        let cases = [
+            (0, 0, "1970-01-01T00:00:00Z"),
+            (-1, 500_000_000, "1969-12-31T23:59:59.500Z"),
+            (63_108_020, 21_000_000, "1972-01-01T10:00:20.021Z"),
+            (951_782_400, 1_000, "2000-02-29T00:00:00.000001Z"),
+            (1_234_567_890, 123_456_789, "2009-02-13T23:31:30.123456789Z"),
+            (TIMESTAMP_SECONDS_MIN, 0, "0001-01-01T00:00:00Z"),
+            (
+                TIMESTAMP_SECONDS_MAX,
+                999_999_999,
+                "9999-12-31T23:59:59.999999999Z",
+            ),
+        ];
+        for &(seconds, nanos, formatted) in &cases {
+            let timestamp = Timestamp { seconds, nanos };
+            assert_eq!(timestamp.to_string(), formatted);
+            assert_eq!(parse_timestamp(formatted), Some(timestamp));
+        }
+
+        // Timestamps are normalized before they are formatted.
+        let timestamp = Timestamp {
+            seconds: 1,
+            nanos: -1,
+        };
+        assert_eq!(timestamp.to_string(), "1970-01-01T00:00:00.999999999Z");
+
+        // Invalid timestamps can be formatted, but not parsed.
+        let timestamp = Timestamp {
+            seconds: TIMESTAMP_SECONDS_MIN - 1,
+            nanos: 0,
+        };
+        assert_eq!(timestamp.to_string(), "0000-12-31T23:59:59Z");
+        assert_eq!(parse_timestamp("0000-12-31T23:59:59Z"), None);
+        let timestamp = Timestamp {
+            seconds: i64::MIN,
+            nanos: 0,
+        };
+        assert!(timestamp.to_string().starts_with("-292277022657-"));
+
+        assert_eq!(
+            parse_timestamp("1972-01-01T10:00:20.021+01:30"),
+            Some(Timestamp {
+                seconds: 63_108_020 - 5400,
+                nanos: 21_000_000,
+            })
+        );
+        assert_eq!(
+            parse_timestamp("1969-12-31t19:00:00.5-05:00"),
+            Some(Timestamp {
+                seconds: 0,
+                nanos: 500_000_000,
+            })
+        );
+
+        let invalid = [
+            "",
+            "1970-01-01",
+            "1970-01-01T00:00:00",
+            "1970-01-01 00:00:00Z",
+            "1970-01-01T00:00:00.Z",
+            "1970-01-01T00:00:00.1234567890Z",
+            "1970-01-01T00:00:00+0100",
+            "1970-01-01T00:00:00+24:00",
+            "1970-13-01T00:00:00Z",
+            "1970-00-01T00:00:00Z",
+            "2021-02-29T00:00:00Z",
+            "1970-01-01T24:00:00Z",
+            "1970-01-01T00:00:60Z",
+            "+970-01-01T00:00:00Z",
+            "0001-01-01T00:00:00+00:01",
+            "9999-12-31T23:59:59-00:01",
+            "1970-01-01T00:00:00Zé",
+        ];
+        for input in &invalid {
+            assert_eq!(parse_timestamp(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn check_timestamp_leap_seconds() {
+        // 2017-01-01T00:00:00Z
+        let midnight = 1_483_228_800;
+        let leap = "2016-12-31T23:59:60Z";
+        assert_eq!(leap.parse::<Timestamp>(), Err(TimestampError::LeapSecond));
+        assert_eq!(
+            Timestamp::parse_with(leap, LeapSecondPolicy::default()),
+            Err(TimestampError::LeapSecond)
+        );
+
+        let clamped = Timestamp {
+            seconds: midnight - 1,
+            nanos: 999_999_999,
+        };
+        for input in &[leap, "2016-12-31T23:59:60.75Z", "2017-01-01T05:29:60+05:30"] {
+            assert_eq!(
+                Timestamp::parse_with(input, LeapSecondPolicy::Clamp),
+                Ok(clamped.clone()),
+                "{}",
+                input
+            );
+        }
+
+        let cases = [
+            ("2016-12-31T23:59:60Z", midnight - 1, 500_005_786),
+            ("2016-12-31T23:59:60.5Z", midnight, 0),
+            ("2016-12-31T18:59:60.999999999-05:00", midnight, 499_994_212),
+        ];
+        for &(input, seconds, nanos) in &cases {
+            assert_eq!(
+                Timestamp::parse_with(input, LeapSecondPolicy::Smear),
+                Ok(Timestamp { seconds, nanos }),
+                "{}",
+                input
+            );
+        }
+
+        // Other seconds are parsed the same under every policy.
+        for &policy in &[
+            LeapSecondPolicy::Reject,
+            LeapSecondPolicy::Clamp,
+            LeapSecondPolicy::Smear,
+        ] {
+            assert_eq!(
+                Timestamp::parse_with("2016-12-31T23:59:59.5Z", policy),
+                Ok(Timestamp {
+                    seconds: midnight - 1,
+                    nanos: 500_000_000,
+                })
+            );
+            for input in &[
+                "2016-12-31T23:58:60Z",
+                "2016-12-31T23:59:60+01:00",
+                "2016-12-31T23:59:61Z",
+            ] {
+                assert_eq!(
+                    Timestamp::parse_with(input, policy),
+                    Err(TimestampError::ParseFailure),
+                    "{}",
+                    input
+                );
+            }
+        }
+
+        assert_eq!(
+            Timestamp::parse_with("9999-12-31T23:59:60Z", LeapSecondPolicy::Clamp),
+            Ok(Timestamp {
+                seconds: TIMESTAMP_SECONDS_MAX,
+                nanos: 999_999_999,
+            })
+        );
+        assert_eq!(
+            Timestamp::parse_with("9999-12-31T23:59:60Z", LeapSecondPolicy::Smear),
+            Ok(Timestamp {
+                seconds: TIMESTAMP_SECONDS_MAX,
+                nanos: 500_005_786,
+            })
+        );
+        assert_eq!(
+            Timestamp::parse_with("9999-12-31T23:59:60.5Z", LeapSecondPolicy::Smear),
+            Err(TimestampError::OutOfRange)
+        );
+        assert_eq!(
+            "0000-12-31T23:59:59Z".parse::<Timestamp>(),
+            Err(TimestampError::OutOfRange)
+        );
+        assert_eq!(
+            "1970-01-01T00:00Z".parse::<Timestamp>(),
+            Err(TimestampError::ParseFailure)
+        );
+    }
+
+    #[test]
+    fn check_duration_format_and_parse() {
+        let cases = [
+            (0, 0, "0s"),
+            (1, 0, "1s"),
+            (1, 500_000_000, "1.500s"),
+            (-1, -500_000_000, "-1.500s"),
+            (0, -1, "-0.000000001s"),
+            (0, 10_000, "0.000010s"),
+            (315_576_000_000, 999_999_999, "315576000000.999999999s"),
+        ];
+        for &(seconds, nanos, formatted) in &cases {
+            let duration = Duration { seconds, nanos };
+            assert_eq!(duration.to_string(), formatted);
+            assert_eq!(parse_duration(formatted), Some(duration));
+        }
+
+        let duration = Duration {
+            seconds: -1,
+            nanos: 1,
+        };
+        assert_eq!(duration.to_string(), "-0.999999999s");
+        assert_eq!(
+            parse_duration("-0.5s"),
+            Some(Duration {
+                seconds: 0,
+                nanos: -500_000_000,
+            })
+        );
+
+        let invalid = [
+            "",
+            "s",
+            "1",
+            "-s",
+            ".5s",
+            "1.s",
+            "+1s",
+            "1.0000000001s",
+            "1.-5s",
+            " 1s",
+            "1 s",
+            "315576000001s",
+        ];
+        for input in &invalid {
+            assert_eq!(parse_duration(input), None, "{}", input);
+        }
+
+        for (input, error) in [
+            ("", DurationError::ParseFailure),
+            ("1.0000000001s", DurationError::ParseFailure),
+            ("+1s", DurationError::ParseFailure),
+            ("1e3s", DurationError::ParseFailure),
+            ("315576000001s", DurationError::OutOfRange),
+            ("-315576000001s", DurationError::OutOfRange),
+            ("99999999999999999999s", DurationError::OutOfRange),
+        ]
+        .iter()
+        .cloned()
+        {
+            assert_eq!(input.parse::<Duration>(), Err(error), "{}", input);
+        }
+        assert_eq!(
+            "-315576000000.999999999s".parse::<Duration>(),
+            Ok(Duration {
+                seconds: -315_576_000_000,
+                nanos: -999_999_999,
+            })
+        );
+        assert_eq!(
+            "-0s".parse::<Duration>().unwrap().to_string(),
+            Duration::default().to_string()
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn check_chrono_timestamp() {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let date_time = Utc.timestamp_opt(-1, 500_000_000).unwrap();
+        let timestamp = Timestamp::from(date_time);
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: -1,
+                nanos: 500_000_000
+            }
+        );
+        assert_eq!(DateTime::<Utc>::try_from(timestamp).unwrap(), date_time);
+
+        // Unnormalized timestamps are accepted.
+        let timestamp = Timestamp {
+            seconds: 1,
+            nanos: -1,
+        };
+        assert_eq!(
+            DateTime::<Utc>::try_from(timestamp).unwrap(),
+            Utc.timestamp_opt(0, 999_999_999).unwrap()
+        );
+
+        // Leap seconds fold into the following second.
+        let leap = Utc.timestamp_opt(59, 1_500_000_000).unwrap();
+        assert_eq!(
+            Timestamp::from(leap),
+            Timestamp {
+                seconds: 60,
+                nanos: 500_000_000
+            }
+        );
+
+        let timestamp = Timestamp {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        let error = DateTime::<Utc>::try_from(timestamp.clone()).unwrap_err();
+        assert_eq!(error.timestamp, timestamp);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn check_chrono_duration() {
+        for &(seconds, nanos) in &[(0, 0), (1, 1), (-1, -1), (0, -999_999_999), (86_400, 5)] {
+            let duration = Duration { seconds, nanos };
+            let chrono = chrono::Duration::try_from(duration.clone()).unwrap();
+            assert_eq!(Duration::from(chrono), duration);
+        }
+
+        assert_eq!(
+            chrono::Duration::try_from(Duration {
+                seconds: 1,
+                nanos: -1,
+            })
+            .unwrap(),
+            chrono::Duration::nanoseconds(999_999_999)
+        );
+        assert!(chrono::Duration::try_from(Duration {
+            seconds: i64::MAX,
+            nanos: 0,
+        })
+        .is_err());
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn check_time_timestamp() {
+        use time::{OffsetDateTime, UtcOffset};
+
+        let date_time = OffsetDateTime::from_unix_timestamp_nanos(-1_500_000_000).unwrap();
+        let timestamp = Timestamp::from(date_time);
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: -2,
+                nanos: 500_000_000
+            }
+        );
+        assert_eq!(OffsetDateTime::try_from(timestamp).unwrap(), date_time);
+
+        // The offset does not change the instant.
+        let offset = date_time.to_offset(UtcOffset::from_hms(5, 30, 0).unwrap());
+        assert_eq!(Timestamp::from(offset), Timestamp::from(date_time));
+
+        let timestamp = Timestamp {
+            seconds: i64::MIN,
+            nanos: 0,
+        };
+        let error = OffsetDateTime::try_from(timestamp.clone()).unwrap_err();
+        assert_eq!(error.timestamp, timestamp);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn check_time_duration() {
+        for &(seconds, nanos) in &[(0, 0), (1, 1), (-1, -1), (i64::MAX, 999_999_999)] {
+            let duration = Duration { seconds, nanos };
+            let time = time::Duration::try_from(duration.clone()).unwrap();
+            assert_eq!(Duration::from(time), duration);
+        }
+
+        assert_eq!(
+            time::Duration::try_from(Duration {
+                seconds: -1,
+                nanos: 1,
+            })
+            .unwrap(),
+            time::Duration::nanoseconds(-999_999_999)
+        );
+        assert!(time::Duration::try_from(Duration {
+            seconds: i64::MAX,
+            nanos: 1_000_000_000,
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,1064 @@
+//! Messages whose schema is only known at runtime.
+//!
+//! A [`DynamicMessage`] holds the fields of a message described by a [`MessageDescriptor`], and
+//! implements [`prost::Message`] so that it can be encoded and decoded like any generated type.
+//! This is useful for tools which handle arbitrary Protobuf payloads, such as proxies, loggers and
+//! test harnesses, given only a `FileDescriptorSet` describing the schema.
+//!
+//! Fields which are not declared in the descriptor are preserved when decoding, and re-emitted
+//! when encoding, with the exception of unknown groups which are discarded.
+
+use core::fmt;
+
+use bytes::{Buf, BufMut, Bytes};
+use prost::alloc::borrow::Cow;
+use prost::alloc::collections::BTreeMap;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use prost::encoding::{
+    self, check_wire_type, decode_varint, encode_key, encode_varint, encoded_len_varint, key_len,
+    merge_loop, skip_field, DecodeContext, WireType,
+};
+use prost::{DecodeError, Message};
+
+use crate::reflect::{Cardinality, FieldDescriptor, Kind, MessageDescriptor};
+
+/// A Protobuf message with a schema determined at runtime.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynamicMessage {
+    desc: MessageDescriptor,
+    fields: BTreeMap<u32, Value>,
+    unknown: Vec<UnknownField>,
+}
+
+/// The value of a field in a [`DynamicMessage`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// An `int32`, `sint32` or `sfixed32` value.
+    I32(i32),
+    /// An `int64`, `sint64` or `sfixed64` value.
+    I64(i64),
+    /// A `uint32` or `fixed32` value.
+    U32(u32),
+    /// A `uint64` or `fixed64` value.
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Bytes(Bytes),
+    /// An enum value, which may not be one of the values declared by the enum.
+    EnumNumber(i32),
+    Message(DynamicMessage),
+    /// The value of a repeated field.
+    List(Vec<Value>),
+    /// The value of a map field.
+    Map(BTreeMap<MapKey, Value>),
+}
+
+/// The key of a map field in a [`DynamicMessage`].
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MapKey {
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    U32(u32),
+    U64(u64),
+    String(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct UnknownField {
+    number: u32,
+    value: UnknownValue,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum UnknownValue {
+    Varint(u64),
+    SixtyFourBit(u64),
+    LengthDelimited(Bytes),
+    ThirtyTwoBit(u32),
+}
+
+impl DynamicMessage {
+    /// Creates an empty message of the given type.
+    pub fn new(desc: MessageDescriptor) -> DynamicMessage {
+        DynamicMessage {
+            desc,
+            fields: BTreeMap::new(),
+            unknown: Vec::new(),
+        }
+    }
+
+    /// Decodes a message of the given type from a buffer.
+    pub fn decode<B>(desc: MessageDescriptor, buf: B) -> Result<DynamicMessage, DecodeError>
+    where
+        B: Buf,
+    {
+        let mut message = DynamicMessage::new(desc);
+        message.merge(buf)?;
+        Ok(message)
+    }
+
+    /// Returns the descriptor of the message type.
+    pub fn descriptor(&self) -> &MessageDescriptor {
+        &self.desc
+    }
+
+    /// Returns `true` if the field is set.
+    ///
+    /// Fields without presence are considered set if they hold a non-default value.
+    pub fn has_field(&self, field: &FieldDescriptor) -> bool {
+        match self.fields.get(&field.number()) {
+            Some(value) => Self::is_set(field, value),
+            None => false,
+        }
+    }
+
+    /// Returns the value of the field, or its default value if it is not set.
+    pub fn get_field(&self, field: &FieldDescriptor) -> Cow<'_, Value> {
+        match self.fields.get(&field.number()) {
+            Some(value) => Cow::Borrowed(value),
+            None => Cow::Owned(field.default_value()),
+        }
+    }
+
+    /// Returns a mutable reference to the value of the field, setting it to its default value if
+    /// it is not set.
+    ///
+    /// Any other field in the same oneof is cleared.
+    pub fn get_field_mut(&mut self, field: &FieldDescriptor) -> &mut Value {
+        self.clear_oneof_siblings(field);
+        self.fields
+            .entry(field.number())
+            .or_insert_with(|| field.default_value())
+    }
+
+    /// Sets the value of the field, clearing any other field in the same oneof.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value does not match the type of the field.
+    pub fn set_field(&mut self, field: &FieldDescriptor, value: Value) {
+        assert!(
+            value.is_valid_for_field(field),
+            "invalid value for field {}.{}: {:?}",
+            self.desc.full_name(),
+            field.name(),
+            value
+        );
+        self.clear_oneof_siblings(field);
+        self.fields.insert(field.number(), value);
+    }
+
+    /// Clears the field, returning its value if it was set.
+    pub fn clear_field(&mut self, field: &FieldDescriptor) -> Option<Value> {
+        self.fields.remove(&field.number())
+    }
+
+    /// Returns the value of the field with the given name, or `None` if the message has no such
+    /// field.
+    pub fn get_field_by_name(&self, name: &str) -> Option<Cow<'_, Value>> {
+        let field = self.desc.get_field_by_name(name)?;
+        Some(match self.fields.get(&field.number()) {
+            Some(value) => Cow::Borrowed(value),
+            None => Cow::Owned(field.default_value()),
+        })
+    }
+
+    /// Sets the value of the field with the given name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message has no such field, or the value does not match the type of the
+    /// field.
+    pub fn set_field_by_name(&mut self, name: &str, value: Value) {
+        let field = match self.desc.get_field_by_name(name) {
+            Some(field) => field,
+            None => panic!("message {} has no field {}", self.desc.full_name(), name),
+        };
+        self.set_field(&field, value);
+    }
+
+    /// Returns an iterator over the set fields of the message, in field number order.
+    pub fn fields(&self) -> impl Iterator<Item = (FieldDescriptor, &Value)> + '_ {
+        self.fields.iter().map(move |(&number, value)| {
+            (
+                self.desc
+                    .get_field(number)
+                    .expect("field not found in message descriptor"),
+                value,
+            )
+        })
+    }
+
+    /// Returns an iterator over the fields of the message which are set, in field number order.
+    ///
+    /// Unlike [`DynamicMessage::fields`], fields without presence which hold their default value
+    /// are skipped, so these are exactly the fields which are encoded. Only the fields stored in
+    /// the message are visited, so this is cheap even for messages with many declared fields.
+    pub fn set_fields(&self) -> impl Iterator<Item = (FieldDescriptor, &Value)> + '_ {
+        self.fields()
+            .filter(|(field, value)| Self::is_set(field, value))
+    }
+
+    fn clear_oneof_siblings(&mut self, field: &FieldDescriptor) {
+        for number in field.oneof_siblings() {
+            self.fields.remove(&number);
+        }
+    }
+
+    /// Returns `true` if a stored field value counts as set, and so is emitted when encoding.
+    fn is_set(field: &FieldDescriptor, value: &Value) -> bool {
+        field.supports_presence() || !value.is_default(&field.kind())
+    }
+}
+
+impl Message for DynamicMessage {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        for (field, value) in self.set_fields() {
+            encode_field(&field, value, buf);
+        }
+        for field in &self.unknown {
+            field.encode(buf);
+        }
+    }
+
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let field = match self.desc.get_field(tag) {
+            Some(field) => field,
+            None => {
+                if let Some(field) = UnknownField::decode(tag, wire_type, buf, ctx)? {
+                    self.unknown.push(field);
+                }
+                return Ok(());
+            }
+        };
+
+        let kind = field.kind();
+        if field.is_map() {
+            let entry_desc = kind.as_message().expect("map field is not a message");
+            let key_field = entry_desc.map_entry_key_field();
+            let value_field = entry_desc.map_entry_value_field();
+
+            let mut entry = DynamicMessage::new(entry_desc.clone());
+            encoding::message::merge(wire_type, &mut entry, buf, ctx)?;
+            let key = entry
+                .fields
+                .remove(&1)
+                .unwrap_or_else(|| key_field.default_value());
+            let key = MapKey::from_value(key).ok_or_else(|| DecodeError::new("invalid map key"))?;
+            let value = entry
+                .fields
+                .remove(&2)
+                .unwrap_or_else(|| value_field.default_value());
+
+            if let Value::Map(map) = self.get_field_mut(&field) {
+                map.insert(key, value);
+            }
+        } else if field.is_list() {
+            let list = match self.get_field_mut(&field) {
+                Value::List(list) => list,
+                _ => unreachable!(),
+            };
+            if wire_type == WireType::LengthDelimited && kind.is_packable() {
+                merge_loop(list, buf, ctx, |list, buf, ctx| {
+                    list.push(decode_value(
+                        &kind,
+                        false,
+                        0,
+                        wire_type_of(&kind),
+                        buf,
+                        ctx,
+                    )?);
+                    Ok(())
+                })?;
+            } else {
+                list.push(decode_value(
+                    &kind,
+                    field.is_group(),
+                    tag,
+                    wire_type,
+                    buf,
+                    ctx,
+                )?);
+            }
+        } else if let Kind::Message(_) = kind {
+            // Repeated occurrences of a message field are merged together.
+            let message = match self.get_field_mut(&field) {
+                Value::Message(message) => message,
+                _ => unreachable!(),
+            };
+            if field.is_group() {
+                encoding::group::merge(tag, wire_type, message, buf, ctx)?;
+            } else {
+                encoding::message::merge(wire_type, message, buf, ctx)?;
+            }
+        } else {
+            let value = decode_value(&kind, false, tag, wire_type, buf, ctx)?;
+            self.clear_oneof_siblings(&field);
+            self.fields.insert(tag, value);
+        }
+        Ok(())
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.set_fields()
+            .map(|(field, value)| encoded_len_field(&field, value))
+            .sum::<usize>()
+            + self
+                .unknown
+                .iter()
+                .map(UnknownField::encoded_len)
+                .sum::<usize>()
+    }
+
+    fn clear(&mut self) {
+        self.fields.clear();
+        self.unknown.clear();
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapKey::Bool(value) => value.fmt(f),
+            MapKey::I32(value) => value.fmt(f),
+            MapKey::I64(value) => value.fmt(f),
+            MapKey::U32(value) => value.fmt(f),
+            MapKey::U64(value) => value.fmt(f),
+            MapKey::String(value) => value.fmt(f),
+        }
+    }
+}
+
+impl MapKey {
+    /// Converts a value into a map key, returning `None` if the value cannot be used as a key.
+    pub fn from_value(value: Value) -> Option<MapKey> {
+        Some(match value {
+            Value::Bool(value) => MapKey::Bool(value),
+            Value::I32(value) => MapKey::I32(value),
+            Value::I64(value) => MapKey::I64(value),
+            Value::U32(value) => MapKey::U32(value),
+            Value::U64(value) => MapKey::U64(value),
+            Value::String(value) => MapKey::String(value),
+            _ => return None,
+        })
+    }
+
+    /// Returns `true` if the key may be used for a map whose key field has the given type.
+    pub fn is_valid(&self, kind: &Kind) -> bool {
+        matches!(
+            (self, kind),
+            (MapKey::Bool(_), Kind::Bool)
+                | (MapKey::I32(_), Kind::Int32)
+                | (MapKey::I32(_), Kind::Sint32)
+                | (MapKey::I32(_), Kind::Sfixed32)
+                | (MapKey::I64(_), Kind::Int64)
+                | (MapKey::I64(_), Kind::Sint64)
+                | (MapKey::I64(_), Kind::Sfixed64)
+                | (MapKey::U32(_), Kind::Uint32)
+                | (MapKey::U32(_), Kind::Fixed32)
+                | (MapKey::U64(_), Kind::Uint64)
+                | (MapKey::U64(_), Kind::Fixed64)
+                | (MapKey::String(_), Kind::String)
+        )
+    }
+}
+
+impl From<MapKey> for Value {
+    fn from(key: MapKey) -> Value {
+        match key {
+            MapKey::Bool(value) => Value::Bool(value),
+            MapKey::I32(value) => Value::I32(value),
+            MapKey::I64(value) => Value::I64(value),
+            MapKey::U32(value) => Value::U32(value),
+            MapKey::U64(value) => Value::U64(value),
+            MapKey::String(value) => Value::String(value),
+        }
+    }
+}
+
+impl Value {
+    /// Returns the default value for a field: an empty list or map for repeated fields, or the
+    /// default value of the field type otherwise.
+    ///
+    /// This does not take `proto2` default values into account; see
+    /// [`FieldDescriptor::default_value`] for that.
+    pub fn default_for_field(field: &FieldDescriptor) -> Value {
+        if field.is_map() {
+            Value::Map(BTreeMap::new())
+        } else if field.is_list() {
+            Value::List(Vec::new())
+        } else {
+            Value::default_for_kind(&field.kind())
+        }
+    }
+
+    /// Returns the default value for a singular field of the given type.
+    pub fn default_for_kind(kind: &Kind) -> Value {
+        match kind {
+            Kind::Double => Value::F64(0.0),
+            Kind::Float => Value::F32(0.0),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(0),
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(0),
+            Kind::Uint32 | Kind::Fixed32 => Value::U32(0),
+            Kind::Uint64 | Kind::Fixed64 => Value::U64(0),
+            Kind::Bool => Value::Bool(false),
+            Kind::String => Value::String(String::new()),
+            Kind::Bytes => Value::Bytes(Bytes::new()),
+            Kind::Message(desc) => Value::Message(DynamicMessage::new(desc.clone())),
+            Kind::Enum(desc) => Value::EnumNumber(desc.default_value()),
+        }
+    }
+
+    /// Returns `true` if the value may be assigned to the field.
+    pub fn is_valid_for_field(&self, field: &FieldDescriptor) -> bool {
+        let kind = field.kind();
+        match self {
+            Value::Map(map) if field.is_map() => {
+                let entry = kind.as_message().expect("map field is not a message");
+                let key_kind = entry.map_entry_key_field().kind();
+                let value_kind = entry.map_entry_value_field().kind();
+                map.iter()
+                    .all(|(key, value)| key.is_valid(&key_kind) && value.is_valid(&value_kind))
+            }
+            Value::List(list) if field.is_list() => list.iter().all(|value| value.is_valid(&kind)),
+            _ => field.cardinality() != Cardinality::Repeated && self.is_valid(&kind),
+        }
+    }
+
+    /// Returns `true` if the value may be assigned to a singular field of the given type.
+    pub fn is_valid(&self, kind: &Kind) -> bool {
+        match (self, kind) {
+            (Value::Bool(_), Kind::Bool)
+            | (Value::I32(_), Kind::Int32)
+            | (Value::I32(_), Kind::Sint32)
+            | (Value::I32(_), Kind::Sfixed32)
+            | (Value::I64(_), Kind::Int64)
+            | (Value::I64(_), Kind::Sint64)
+            | (Value::I64(_), Kind::Sfixed64)
+            | (Value::U32(_), Kind::Uint32)
+            | (Value::U32(_), Kind::Fixed32)
+            | (Value::U64(_), Kind::Uint64)
+            | (Value::U64(_), Kind::Fixed64)
+            | (Value::F32(_), Kind::Float)
+            | (Value::F64(_), Kind::Double)
+            | (Value::String(_), Kind::String)
+            | (Value::Bytes(_), Kind::Bytes)
+            | (Value::EnumNumber(_), Kind::Enum(_)) => true,
+            (Value::Message(message), Kind::Message(desc)) => message.descriptor() == desc,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if this is the default value of a field of the given type, and so is not
+    /// encoded for fields without presence.
+    fn is_default(&self, kind: &Kind) -> bool {
+        match self {
+            Value::Bool(value) => !value,
+            Value::I32(value) => *value == 0,
+            Value::I64(value) => *value == 0,
+            Value::U32(value) => *value == 0,
+            Value::U64(value) => *value == 0,
+            Value::F32(value) => value.to_bits() == 0,
+            Value::F64(value) => value.to_bits() == 0,
+            Value::String(value) => value.is_empty(),
+            Value::Bytes(value) => value.is_empty(),
+            Value::EnumNumber(value) => match kind {
+                Kind::Enum(desc) => *value == desc.default_value(),
+                _ => *value == 0,
+            },
+            Value::Message(_) => false,
+            Value::List(values) => values.is_empty(),
+            Value::Map(map) => map.is_empty(),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Value::I32(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::I64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match *self {
+            Value::U32(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::U64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        match *self {
+            Value::F32(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::F64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            Value::Bytes(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_enum_number(&self) -> Option<i32> {
+        match *self {
+            Value::EnumNumber(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_message(&self) -> Option<&DynamicMessage> {
+        match self {
+            Value::Message(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<MapKey, Value>> {
+        match self {
+            Value::Map(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl UnknownField {
+    /// Decodes an unknown field, returning `None` for groups, which are skipped.
+    fn decode<B>(
+        number: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<Option<UnknownField>, DecodeError>
+    where
+        B: Buf,
+    {
+        let value = match wire_type {
+            WireType::Varint => UnknownValue::Varint(decode_varint(buf)?),
+            WireType::SixtyFourBit => {
+                if buf.remaining() < 8 {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                UnknownValue::SixtyFourBit(buf.get_u64_le())
+            }
+            WireType::ThirtyTwoBit => {
+                if buf.remaining() < 4 {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                UnknownValue::ThirtyTwoBit(buf.get_u32_le())
+            }
+            WireType::LengthDelimited => {
+                let len = decode_varint(buf)?;
+                if len > buf.remaining() as u64 {
+                    return Err(DecodeError::new("buffer underflow"));
+                }
+                UnknownValue::LengthDelimited(buf.copy_to_bytes(len as usize))
+            }
+            WireType::StartGroup | WireType::EndGroup => {
+                skip_field(wire_type, number, buf, ctx)?;
+                return Ok(None);
+            }
+        };
+        Ok(Some(UnknownField { number, value }))
+    }
+
+    fn encode<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        match &self.value {
+            UnknownValue::Varint(value) => {
+                encode_key(self.number, WireType::Varint, buf);
+                encode_varint(*value, buf);
+            }
+            UnknownValue::SixtyFourBit(value) => {
+                encode_key(self.number, WireType::SixtyFourBit, buf);
+                buf.put_u64_le(*value);
+            }
+            UnknownValue::LengthDelimited(value) => {
+                encode_key(self.number, WireType::LengthDelimited, buf);
+                encode_varint(value.len() as u64, buf);
+                buf.put_slice(value);
+            }
+            UnknownValue::ThirtyTwoBit(value) => {
+                encode_key(self.number, WireType::ThirtyTwoBit, buf);
+                buf.put_u32_le(*value);
+            }
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        key_len(self.number)
+            + match &self.value {
+                UnknownValue::Varint(value) => encoded_len_varint(*value),
+                UnknownValue::SixtyFourBit(_) => 8,
+                UnknownValue::LengthDelimited(value) => {
+                    encoded_len_varint(value.len() as u64) + value.len()
+                }
+                UnknownValue::ThirtyTwoBit(_) => 4,
+            }
+    }
+}
+
+/// Returns the wire type of a singular, non-group value of the given type.
+fn wire_type_of(kind: &Kind) -> WireType {
+    match kind {
+        Kind::Double | Kind::Fixed64 | Kind::Sfixed64 => WireType::SixtyFourBit,
+        Kind::Float | Kind::Fixed32 | Kind::Sfixed32 => WireType::ThirtyTwoBit,
+        Kind::String | Kind::Bytes | Kind::Message(_) => WireType::LengthDelimited,
+        _ => WireType::Varint,
+    }
+}
+
+/// Returns the varint encoding of a varint-typed scalar value.
+fn to_varint(value: &Value) -> u64 {
+    match *value {
+        Value::Bool(value) => value as u64,
+        Value::I32(value) | Value::EnumNumber(value) => value as u64,
+        Value::I64(value) => value as u64,
+        Value::U32(value) => value as u64,
+        Value::U64(value) => value,
+        _ => unreachable!("not a varint value: {:?}", value),
+    }
+}
+
+/// Returns the zig-zag encoding of a `sint32` or `sint64` value.
+fn to_zigzag(value: &Value) -> u64 {
+    match *value {
+        Value::I32(value) => ((value << 1) ^ (value >> 31)) as u32 as u64,
+        Value::I64(value) => ((value << 1) ^ (value >> 63)) as u64,
+        _ => unreachable!("not a zig-zag value: {:?}", value),
+    }
+}
+
+/// Encodes a numeric scalar value without a key, as in a packed field.
+fn encode_packable<B>(kind: &Kind, value: &Value, buf: &mut B)
+where
+    B: BufMut,
+{
+    match (kind, value) {
+        (_, Value::F64(value)) => buf.put_f64_le(*value),
+        (_, Value::F32(value)) => buf.put_f32_le(*value),
+        (Kind::Fixed64, Value::U64(value)) => buf.put_u64_le(*value),
+        (Kind::Sfixed64, Value::I64(value)) => buf.put_i64_le(*value),
+        (Kind::Fixed32, Value::U32(value)) => buf.put_u32_le(*value),
+        (Kind::Sfixed32, Value::I32(value)) => buf.put_i32_le(*value),
+        (Kind::Sint32, _) | (Kind::Sint64, _) => encode_varint(to_zigzag(value), buf),
+        _ => encode_varint(to_varint(value), buf),
+    }
+}
+
+/// Returns the encoded length of a numeric scalar value without a key.
+fn encoded_len_packable(kind: &Kind, value: &Value) -> usize {
+    match wire_type_of(kind) {
+        WireType::SixtyFourBit => 8,
+        WireType::ThirtyTwoBit => 4,
+        _ => match kind {
+            Kind::Sint32 | Kind::Sint64 => encoded_len_varint(to_zigzag(value)),
+            _ => encoded_len_varint(to_varint(value)),
+        },
+    }
+}
+
+/// Encodes a singular value with the given field number.
+fn encode_value<B>(number: u32, kind: &Kind, group: bool, value: &Value, buf: &mut B)
+where
+    B: BufMut,
+{
+    match value {
+        Value::String(value) => encoding::string::encode(number, value, buf),
+        Value::Bytes(value) => encoding::bytes::encode(number, value, buf),
+        Value::Message(message) if group => encoding::group::encode(number, message, buf),
+        Value::Message(message) => encoding::message::encode(number, message, buf),
+        _ => {
+            encode_key(number, wire_type_of(kind), buf);
+            encode_packable(kind, value, buf);
+        }
+    }
+}
+
+/// Returns the encoded length of a singular value with the given field number.
+fn encoded_len_value(number: u32, kind: &Kind, group: bool, value: &Value) -> usize {
+    match value {
+        Value::String(value) => encoding::string::encoded_len(number, value),
+        Value::Bytes(value) => encoding::bytes::encoded_len(number, value),
+        Value::Message(message) if group => encoding::group::encoded_len(number, message),
+        Value::Message(message) => encoding::message::encoded_len(number, message),
+        _ => key_len(number) + encoded_len_packable(kind, value),
+    }
+}
+
+/// Returns the encoded length of a map entry, without its key and length prefix.
+fn encoded_len_entry(key_kind: &Kind, value_kind: &Kind, key: &MapKey, value: &Value) -> usize {
+    let key_len = match key {
+        MapKey::String(key) => encoding::string::encoded_len(1, key),
+        key => encoded_len_value(1, key_kind, false, &key.clone().into()),
+    };
+    key_len + encoded_len_value(2, value_kind, false, value)
+}
+
+fn encode_field<B>(field: &FieldDescriptor, value: &Value, buf: &mut B)
+where
+    B: BufMut,
+{
+    let number = field.number();
+    let kind = field.kind();
+    match value {
+        Value::Map(map) => {
+            let entry = kind.as_message().expect("map field is not a message");
+            let key_kind = entry.map_entry_key_field().kind();
+            let value_kind = entry.map_entry_value_field().kind();
+            for (key, value) in map {
+                encode_key(number, WireType::LengthDelimited, buf);
+                encode_varint(
+                    encoded_len_entry(&key_kind, &value_kind, key, value) as u64,
+                    buf,
+                );
+                match key {
+                    MapKey::String(key) => encoding::string::encode(1, key, buf),
+                    key => encode_value(1, &key_kind, false, &key.clone().into(), buf),
+                }
+                encode_value(2, &value_kind, false, value, buf);
+            }
+        }
+        Value::List(values) if field.is_packed() => {
+            if values.is_empty() {
+                return;
+            }
+            encode_key(number, WireType::LengthDelimited, buf);
+            let len: usize = values
+                .iter()
+                .map(|value| encoded_len_packable(&kind, value))
+                .sum();
+            encode_varint(len as u64, buf);
+            for value in values {
+                encode_packable(&kind, value, buf);
+            }
+        }
+        Value::List(values) => {
+            for value in values {
+                encode_value(number, &kind, field.is_group(), value, buf);
+            }
+        }
+        value => encode_value(number, &kind, field.is_group(), value, buf),
+    }
+}
+
+fn encoded_len_field(field: &FieldDescriptor, value: &Value) -> usize {
+    let number = field.number();
+    let kind = field.kind();
+    match value {
+        Value::Map(map) => {
+            let entry = kind.as_message().expect("map field is not a message");
+            let key_kind = entry.map_entry_key_field().kind();
+            let value_kind = entry.map_entry_value_field().kind();
+            map.iter()
+                .map(|(key, value)| {
+                    let len = encoded_len_entry(&key_kind, &value_kind, key, value);
+                    key_len(number) + encoded_len_varint(len as u64) + len
+                })
+                .sum()
+        }
+        Value::List(values) if field.is_packed() => {
+            if values.is_empty() {
+                return 0;
+            }
+            let len: usize = values
+                .iter()
+                .map(|value| encoded_len_packable(&kind, value))
+                .sum();
+            key_len(number) + encoded_len_varint(len as u64) + len
+        }
+        Value::List(values) => values
+            .iter()
+            .map(|value| encoded_len_value(number, &kind, field.is_group(), value))
+            .sum(),
+        value => encoded_len_value(number, &kind, field.is_group(), value),
+    }
+}
+
+/// Decodes a singular value of the given type.
+fn decode_value<B>(
+    kind: &Kind,
+    group: bool,
+    number: u32,
+    wire_type: WireType,
+    buf: &mut B,
+    ctx: DecodeContext,
+) -> Result<Value, DecodeError>
+where
+    B: Buf,
+{
+    macro_rules! merge {
+        ($module:ident, $variant:ident) => {{
+            let mut value = Default::default();
+            encoding::$module::merge(wire_type, &mut value, buf, ctx)?;
+            Value::$variant(value)
+        }};
+    }
+
+    Ok(match kind {
+        Kind::Double => merge!(double, F64),
+        Kind::Float => merge!(float, F32),
+        Kind::Int32 => merge!(int32, I32),
+        Kind::Int64 => merge!(int64, I64),
+        Kind::Uint32 => merge!(uint32, U32),
+        Kind::Uint64 => merge!(uint64, U64),
+        Kind::Sint32 => merge!(sint32, I32),
+        Kind::Sint64 => merge!(sint64, I64),
+        Kind::Fixed32 => merge!(fixed32, U32),
+        Kind::Fixed64 => merge!(fixed64, U64),
+        Kind::Sfixed32 => merge!(sfixed32, I32),
+        Kind::Sfixed64 => merge!(sfixed64, I64),
+        Kind::Bool => merge!(bool, Bool),
+        Kind::String => merge!(string, String),
+        Kind::Bytes => {
+            let mut value = Bytes::new();
+            encoding::bytes::merge(wire_type, &mut value, buf, ctx)?;
+            Value::Bytes(value)
+        }
+        Kind::Enum(_) => merge!(int32, EnumNumber),
+        Kind::Message(desc) => {
+            let mut message = DynamicMessage::new(desc.clone());
+            if group {
+                encoding::group::merge(number, wire_type, &mut message, buf, ctx)?;
+            } else {
+                check_wire_type(WireType::LengthDelimited, wire_type)?;
+                encoding::message::merge(wire_type, &mut message, buf, ctx)?;
+            }
+            Value::Message(message)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::reflect::tests::test_pool;
+
+    #[test]
+    fn round_trip() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Person").unwrap();
+
+        let mut friend = DynamicMessage::new(desc.clone());
+        friend.set_field_by_name("name", Value::String("Bob".to_string()));
+
+        let mut labels = BTreeMap::new();
+        labels.insert(MapKey::String("a".to_string()), Value::U32(1));
+        labels.insert(MapKey::String("b".to_string()), Value::U32(0));
+
+        let mut person = DynamicMessage::new(desc.clone());
+        person.set_field_by_name("name", Value::String("Alice".to_string()));
+        person.set_field_by_name("id", Value::I32(-7));
+        person.set_field_by_name("scores", Value::List(vec![Value::I64(-1), Value::I64(300)]));
+        person.set_field_by_name("kind", Value::EnumNumber(1));
+        person.set_field_by_name("friend", Value::Message(friend));
+        person.set_field_by_name("labels", Value::Map(labels));
+        person.set_field_by_name("b", Value::I32(5));
+        person.set_field_by_name("data", Value::Bytes(Bytes::from_static(b"\x00\x01")));
+        person.set_field_by_name("ratio", Value::F64(0.5));
+
+        let encoded = person.encode_to_vec();
+        assert_eq!(encoded.len(), person.encoded_len());
+        let decoded = DynamicMessage::decode(desc, encoded.as_slice()).unwrap();
+        assert_eq!(decoded, person);
+        assert_eq!(
+            decoded
+                .get_field_by_name("friend")
+                .unwrap()
+                .as_message()
+                .unwrap()
+                .get_field_by_name("name")
+                .unwrap()
+                .as_str(),
+            Some("Bob")
+        );
+    }
+
+    #[test]
+    fn negative_zero() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Person").unwrap();
+        let ratio = desc.get_field_by_name("ratio").unwrap();
+
+        let mut person = DynamicMessage::new(desc.clone());
+        person.set_field(&ratio, Value::F64(0.0));
+        assert!(!person.has_field(&ratio));
+        assert_eq!(person.encoded_len(), 0);
+
+        person.set_field(&ratio, Value::F64(-0.0));
+        assert!(person.has_field(&ratio));
+        let decoded = DynamicMessage::decode(desc, person.encode_to_vec().as_slice()).unwrap();
+        let value = decoded.get_field(&ratio).as_f64().unwrap();
+        assert_eq!(value.to_bits(), (-0.0f64).to_bits());
+    }
+
+    #[test]
+    fn proto2_semantics() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Legacy").unwrap();
+
+        let mut legacy = DynamicMessage::new(desc.clone());
+        assert_eq!(
+            legacy.get_field_by_name("answer").unwrap().as_i32(),
+            Some(42)
+        );
+        assert!(!legacy.has_field(&desc.get_field(1).unwrap()));
+
+        legacy.set_field_by_name("answer", Value::I32(0));
+        legacy.set_field_by_name("values", Value::List(vec![Value::I32(1), Value::I32(2)]));
+        assert!(legacy.has_field(&desc.get_field(1).unwrap()));
+
+        // Proto2 fields with presence are encoded even when set to zero, and repeated scalars
+        // are not packed.
+        assert_eq!(
+            legacy.encode_to_vec(),
+            vec![0x08, 0x00, 0x20, 0x01, 0x20, 0x02]
+        );
+
+        // Packed encoding is accepted for unpacked fields.
+        let decoded = DynamicMessage::decode(desc, &[0x22, 0x02, 0x03, 0x04][..]).unwrap();
+        assert_eq!(
+            decoded.get_field_by_name("values").unwrap().as_list(),
+            Some(&[Value::I32(3), Value::I32(4)][..])
+        );
+    }
+
+    #[test]
+    fn proto3_defaults_and_oneofs() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Person").unwrap();
+
+        let mut person = DynamicMessage::new(desc.clone());
+        person.set_field_by_name("id", Value::I32(0));
+        assert!(!person.has_field(&desc.get_field(2).unwrap()));
+        assert_eq!(person.encoded_len(), 0);
+
+        person.set_field_by_name("a", Value::String(String::new()));
+        assert_eq!(person.encode_to_vec(), vec![0x3A, 0x00]);
+        person.set_field_by_name("b", Value::I32(3));
+        assert!(!person.has_field(&desc.get_field(7).unwrap()));
+        assert_eq!(person.encode_to_vec(), vec![0x40, 0x03]);
+
+        // Decoding a oneof field clears the other fields of the oneof.
+        let decoded = DynamicMessage::decode(desc, &[0x40, 0x03, 0x3A, 0x01, b'x'][..]).unwrap();
+        assert_eq!(decoded.fields().count(), 1);
+        assert_eq!(decoded.get_field_by_name("a").unwrap().as_str(), Some("x"));
+    }
+
+    #[test]
+    fn set_fields() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Person").unwrap();
+
+        let mut person = DynamicMessage::new(desc.clone());
+        assert_eq!(person.set_fields().count(), 0);
+
+        person.set_field_by_name("ratio", Value::F64(0.5));
+        person.set_field_by_name("id", Value::I32(0));
+        person.set_field_by_name("scores", Value::List(Vec::new()));
+        person.set_field_by_name("a", Value::String(String::new()));
+        person.set_field_by_name("name", Value::String("Alice".to_string()));
+        assert_eq!(person.fields().count(), 5);
+
+        // Fields without presence holding their default value are skipped, and oneof fields are
+        // yielded even when empty.
+        let set = person
+            .set_fields()
+            .map(|(field, value)| (field.name().to_string(), value.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            set,
+            vec![
+                ("name".to_string(), Value::String("Alice".to_string())),
+                ("a".to_string(), Value::String(String::new())),
+                ("ratio".to_string(), Value::F64(0.5)),
+            ]
+        );
+        for (field, _) in person.set_fields() {
+            assert!(person.has_field(&field));
+        }
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Person").unwrap();
+
+        // Field 1 (name), then unknown fields 20 (varint), 21 (fixed32), 22 (bytes) and a group.
+        let encoded = [
+            0x0A, 0x01, b'x', 0xA0, 0x01, 0x96, 0x01, 0xAD, 0x01, 1, 2, 3, 4, 0xB2, 0x01, 0x01,
+            0xFF, 0xBB, 0x01, 0x08, 0x01, 0xBC, 0x01,
+        ];
+        let message = DynamicMessage::decode(desc, &encoded[..]).unwrap();
+        assert_eq!(message.encode_to_vec(), &encoded[..encoded.len() - 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value for field test.Person.id")]
+    fn set_invalid_value() {
+        let pool = test_pool();
+        let desc = pool.get_message_by_name("test.Person").unwrap();
+        DynamicMessage::new(desc).set_field_by_name("id", Value::U32(1));
+    }
+}
@@ -0,0 +1,287 @@
+//! Set operations on `google.protobuf.FieldMask`, and applying masks to messages.
+
+use prost::alloc::format;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use prost::{DecodeError, Message, Name};
+
+use crate::dynamic::Value;
+use crate::reflect::MessageDescriptor;
+use crate::{DescriptorPool, DynamicMessage, FieldMask};
+
+impl FieldMask {
+    /// Normalizes the mask to a canonical form.
+    ///
+    /// The paths are sorted, and duplicate paths and paths covered by another path in the mask,
+    /// such as `a.b` when the mask also contains `a`, are removed.
+    pub fn normalize(&mut self) {
+        self.paths.sort();
+        self.paths.dedup();
+        // Field names only contain characters which sort after `.`, so the paths covered by a
+        // path immediately follow it.
+        let mut covering: Option<String> = None;
+        self.paths.retain(|path| match &covering {
+            Some(prefix) if is_covered_by(path, prefix) => false,
+            _ => {
+                covering = Some(path.clone());
+                true
+            }
+        });
+    }
+
+    /// Returns the canonical mask selecting the fields selected by either mask.
+    pub fn union(&self, other: &FieldMask) -> FieldMask {
+        let mut mask = FieldMask {
+            paths: self.paths.iter().chain(&other.paths).cloned().collect(),
+        };
+        mask.normalize();
+        mask
+    }
+
+    /// Returns the canonical mask selecting the fields selected by both masks.
+    pub fn intersect(&self, other: &FieldMask) -> FieldMask {
+        let mut paths = Vec::new();
+        for a in &self.paths {
+            for b in &other.paths {
+                if is_covered_by(a, b) {
+                    paths.push(a.clone());
+                } else if is_covered_by(b, a) {
+                    paths.push(b.clone());
+                }
+            }
+        }
+        let mut mask = FieldMask { paths };
+        mask.normalize();
+        mask
+    }
+
+    /// Returns `true` if every path in the mask names a field of the message.
+    ///
+    /// Every component of a path but the last must name a singular message field.
+    pub fn is_valid(&self, desc: &MessageDescriptor) -> bool {
+        self.paths.iter().all(|path| is_valid_path(path, desc))
+    }
+
+    /// Returns `true` if every path in the mask names a field of `M`, which is looked up in
+    /// `pool`.
+    ///
+    /// Returns `false` if `M` is not in the pool.
+    pub fn is_valid_for<M>(&self, pool: &DescriptorPool) -> bool
+    where
+        M: Name,
+    {
+        pool.get_message_by_name(&M::full_name())
+            .map_or(false, |desc| self.is_valid(&desc))
+    }
+
+    /// Copies the fields selected by the mask from `src` to `dst`, leaving the other fields of
+    /// `dst` unchanged.
+    ///
+    /// This implements the replace semantics of partial updates: a selected field which is not set
+    /// in `src` is cleared in `dst`, and selected repeated, map and message fields are replaced
+    /// rather than merged.
+    ///
+    /// Fails if the messages are of different types, or the mask is not valid for them.
+    pub fn merge(&self, src: &DynamicMessage, dst: &mut DynamicMessage) -> Result<(), DecodeError> {
+        if src.descriptor() != dst.descriptor() {
+            return Err(DecodeError::new(format!(
+                "mismatched message types: {} and {}",
+                src.descriptor().full_name(),
+                dst.descriptor().full_name()
+            )));
+        }
+        if let Some(path) = self
+            .paths
+            .iter()
+            .find(|path| !is_valid_path(path, dst.descriptor()))
+        {
+            return Err(DecodeError::new(format!(
+                "invalid field mask path for {}: {}",
+                dst.descriptor().full_name(),
+                path
+            )));
+        }
+        for path in &self.paths {
+            let path = path.split('.').collect::<Vec<_>>();
+            merge_path(&path, Some(src), dst);
+        }
+        Ok(())
+    }
+
+    /// Copies the fields selected by the mask from `src` to `dst`, as with [`FieldMask::merge`],
+    /// using the descriptor of `M` in `pool`.
+    pub fn apply<M>(&self, pool: &DescriptorPool, src: &M, dst: &mut M) -> Result<(), DecodeError>
+    where
+        M: Name + Default,
+    {
+        let desc = pool
+            .get_message_by_name(&M::full_name())
+            .ok_or_else(|| DecodeError::new(format!("unknown message type: {}", M::full_name())))?;
+        let src = DynamicMessage::decode(desc.clone(), &*src.encode_to_vec())?;
+        let mut message = DynamicMessage::decode(desc, &*dst.encode_to_vec())?;
+        self.merge(&src, &mut message)?;
+        *dst = M::decode(&*message.encode_to_vec())?;
+        Ok(())
+    }
+}
+
+/// Returns `true` if `path` is `prefix` or names a field nested within it.
+fn is_covered_by(path: &str, prefix: &str) -> bool {
+    path.starts_with(prefix)
+        && (path.len() == prefix.len() || path.as_bytes()[prefix.len()] == b'.')
+}
+
+fn is_valid_path(path: &str, desc: &MessageDescriptor) -> bool {
+    let mut desc = desc.clone();
+    let mut components = path.split('.').peekable();
+    while let Some(name) = components.next() {
+        let field = match desc.get_field_by_name(name) {
+            Some(field) => field,
+            None => return false,
+        };
+        if components.peek().is_none() {
+            return true;
+        }
+        desc = match field.kind().as_message() {
+            Some(message) if !field.is_list() && !field.is_map() => message.clone(),
+            _ => return false,
+        };
+    }
+    false
+}
+
+/// Copies the field named by a valid path from `src` to `dst`. A missing `src` stands for an unset
+/// parent message.
+fn merge_path(path: &[&str], src: Option<&DynamicMessage>, dst: &mut DynamicMessage) {
+    let field = dst
+        .descriptor()
+        .get_field_by_name(path[0])
+        .expect("invalid field mask path");
+    let src = src.filter(|src| src.has_field(&field));
+    if path.len() == 1 {
+        match src {
+            Some(src) => dst.set_field(&field, src.get_field(&field).into_owned()),
+            None => {
+                dst.clear_field(&field);
+            }
+        }
+        return;
+    }
+
+    let value = src.map(|src| src.get_field(&field));
+    let src = value.as_ref().and_then(|value| value.as_message());
+    if src.is_none() && !dst.has_field(&field) {
+        // Don't create an empty parent message just to clear one of its fields.
+        return;
+    }
+    if let Value::Message(dst) = dst.get_field_mut(&field) {
+        merge_path(&path[1..], src, dst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::string::ToString;
+    use prost::alloc::vec;
+
+    use crate::reflect::tests::test_pool;
+
+    fn mask(paths: &[&str]) -> FieldMask {
+        FieldMask {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut a = mask(&["b.c", "a", "a.b", "b", "aa", "a"]);
+        a.normalize();
+        assert_eq!(a, mask(&["a", "aa", "b"]));
+
+        let a = mask(&["a.b", "c"]);
+        let b = mask(&["a", "c.d", "e"]);
+        assert_eq!(a.union(&b), mask(&["a", "c", "e"]));
+        assert_eq!(a.intersect(&b), mask(&["a.b", "c.d"]));
+        assert_eq!(a.intersect(&mask(&["ab", "c.d.e"])), mask(&["c.d.e"]));
+        assert_eq!(a.intersect(&FieldMask::default()), FieldMask::default());
+    }
+
+    #[test]
+    fn validity() {
+        let pool = test_pool();
+        let person = pool.get_message_by_name("test.Person").unwrap();
+        assert!(mask(&[]).is_valid(&person));
+        assert!(mask(&["name", "friend.friend.labels", "friend.a"]).is_valid(&person));
+        for path in &[
+            "nom",
+            "",
+            "name.length",
+            "labels.key",
+            "friend.",
+            "friend.nom",
+        ] {
+            assert!(!mask(&[path]).is_valid(&person), "{}", path);
+        }
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    struct Person {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(int32, tag = "2")]
+        id: i32,
+        #[prost(sint64, repeated, tag = "3")]
+        scores: Vec<i64>,
+        #[prost(message, optional, boxed, tag = "5")]
+        friend: Option<prost::alloc::boxed::Box<Person>>,
+    }
+
+    impl Name for Person {
+        const NAME: &'static str = "Person";
+        const PACKAGE: &'static str = "test";
+    }
+
+    fn person(name: &str, id: i32, friend: Option<Person>) -> Person {
+        Person {
+            name: name.to_string(),
+            id,
+            scores: vec![id.into()],
+            friend: friend.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let pool = test_pool();
+        assert!(mask(&["friend.name"]).is_valid_for::<Person>(&pool));
+        assert!(!mask(&["friend.nom"]).is_valid_for::<Person>(&pool));
+        assert!(!mask(&["name"]).is_valid_for::<crate::Duration>(&pool));
+
+        let src = person("src", 1, Some(person("src friend", 2, None)));
+        let mut dst = person("dst", 3, Some(person("dst friend", 4, None)));
+        mask(&["name", "friend.id", "friend.scores"])
+            .apply(&pool, &src, &mut dst)
+            .unwrap();
+        assert_eq!(dst, person("src", 3, Some(person("dst friend", 2, None))));
+
+        // Unset fields are cleared, without creating missing parents.
+        let src = person("", 0, None);
+        let mut dst = person("dst", 3, None);
+        mask(&["name", "friend.name"])
+            .apply(&pool, &src, &mut dst)
+            .unwrap();
+        assert_eq!(dst, person("", 3, None));
+
+        let mut dst = person("dst", 3, Some(person("dst friend", 4, None)));
+        mask(&["friend.name"]).apply(&pool, &src, &mut dst).unwrap();
+        assert_eq!(dst, person("dst", 3, Some(person("", 4, None))));
+
+        mask(&["friend"]).apply(&pool, &src, &mut dst).unwrap();
+        assert_eq!(dst, person("dst", 3, None));
+
+        assert!(mask(&["nom"]).apply(&pool, &src, &mut dst).is_err());
+        assert_eq!(dst, person("dst", 3, None));
+    }
+}
@@ -0,0 +1,515 @@
+use core::fmt::{self, Write};
+use core::slice;
+
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec::Vec;
+
+use crate::api::{http_rule::Pattern, HttpRule};
+
+impl HttpRule {
+    /// Returns the HTTP method and the path template of the rule, such as
+    /// `("GET", "/v1/{name=shelves/*}")`.
+    ///
+    /// The method of a custom pattern is its kind, as given. Returns `None` if the rule has no
+    /// pattern.
+    pub fn method_and_path(&self) -> Option<(&str, &str)> {
+        let (method, path) = match self.pattern.as_ref()? {
+            Pattern::Get(path) => ("GET", path),
+            Pattern::Put(path) => ("PUT", path),
+            Pattern::Post(path) => ("POST", path),
+            Pattern::Delete(path) => ("DELETE", path),
+            Pattern::Patch(path) => ("PATCH", path),
+            Pattern::Custom(custom) => (custom.kind.as_str(), &custom.path),
+        };
+        Some((method, path.as_str()))
+    }
+
+    /// Returns the rule and its additional bindings, in order.
+    pub fn bindings(&self) -> impl Iterator<Item = &HttpRule> {
+        Some(self).into_iter().chain(&self.additional_bindings)
+    }
+}
+
+/// A parsed URL path template of an [`HttpRule`], such as `/v1/{name=shelves/*/books/*}:publish`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+    verb: Option<String>,
+}
+
+/// A segment of a [`PathTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    /// A segment matching itself.
+    Literal(String),
+    /// `*`, which matches a single segment.
+    Wildcard,
+    /// `**`, which matches any number of segments, and must be the last segment.
+    DoubleWildcard,
+    /// A variable binding the segments matching its own segments to a field of the request.
+    ///
+    /// The segments of a variable are neither variables nor empty. The segments of `{name}` are a
+    /// single [`Segment::Wildcard`].
+    Variable {
+        /// The path of the bound field, such as `book.name`, which are the names of the fields
+        /// separated by `.`.
+        field_path: String,
+        segments: Vec<Segment>,
+    },
+}
+
+impl PathTemplate {
+    /// Parses a path template.
+    pub fn parse(template: &str) -> Result<PathTemplate, PathTemplateError> {
+        let error = |description: &str| PathTemplateError {
+            template: template.to_string(),
+            description: description.to_string(),
+        };
+
+        let path = template
+            .strip_prefix('/')
+            .ok_or_else(|| error("must start with '/'"))?;
+        let (path, verb) = match path.rfind(':') {
+            Some(index) if !path[index..].contains(|c| c == '/' || c == '}') => {
+                let verb = &path[index + 1..];
+                if verb.is_empty() {
+                    return Err(error("empty verb"));
+                }
+                (&path[..index], Some(verb.to_string()))
+            }
+            _ => (path, None),
+        };
+
+        let mut segments = Vec::new();
+        for segment in split_segments(path).map_err(error)? {
+            let segment = match segment.strip_prefix('{') {
+                Some(variable) => {
+                    let variable = variable
+                        .strip_suffix('}')
+                        .ok_or_else(|| error("unterminated variable"))?;
+                    let (field_path, template) = match variable.find('=') {
+                        Some(index) => (&variable[..index], &variable[index + 1..]),
+                        None => (variable, "*"),
+                    };
+                    if !is_field_path(field_path) {
+                        return Err(error("invalid field path"));
+                    }
+                    let segments = template
+                        .split('/')
+                        .map(|segment| parse_segment(segment).map_err(error))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Segment::Variable {
+                        field_path: field_path.to_string(),
+                        segments,
+                    }
+                }
+                None => parse_segment(segment).map_err(error)?,
+            };
+            segments.push(segment);
+        }
+
+        let mut leaves = segments.iter().flat_map(|segment| match segment {
+            Segment::Variable { segments, .. } => segments.as_slice(),
+            segment => slice::from_ref(segment),
+        });
+        leaves.next_back();
+        if leaves.any(|segment| *segment == Segment::DoubleWildcard) {
+            return Err(error("'**' must be the last segment"));
+        }
+
+        Ok(PathTemplate { segments, verb })
+    }
+
+    /// Returns the segments of the template, excluding the verb.
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Returns the verb of the template, which follows the last segment after a `:`.
+    pub fn verb(&self) -> Option<&str> {
+        self.verb.as_deref()
+    }
+
+    /// Returns the field paths of the variables of the template, in order.
+    pub fn variables(&self) -> impl Iterator<Item = &str> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Variable { field_path, .. } => Some(field_path.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Matches a URL path, without its query string, against the template.
+    ///
+    /// Returns the field path and the percent-decoded value of each variable, in order, or `None`
+    /// if the path doesn't match. As in gRPC transcoding, `%2F` is left encoded in the values of
+    /// variables matching several segments, such as `{name=shelves/*/books/*}`.
+    pub fn match_path(&self, path: &str) -> Option<Vec<(&str, String)>> {
+        let path = path.strip_prefix('/')?;
+        let path = match &self.verb {
+            Some(verb) => path.strip_suffix(verb.as_str())?.strip_suffix(':')?,
+            None => path,
+        };
+
+        let path = path.split('/').collect::<Vec<_>>();
+        let mut rest = path.as_slice();
+        let mut variables = Vec::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Variable {
+                    field_path,
+                    segments,
+                } => {
+                    let matched = match_segments(segments, rest)?;
+                    let single = segments[..] == [Segment::Wildcard];
+                    let value = percent_decode(&rest[..matched].join("/"), !single)?;
+                    variables.push((field_path.as_str(), value));
+                    rest = &rest[matched..];
+                }
+                segment => rest = &rest[match_segments(slice::from_ref(segment), rest)?..],
+            }
+        }
+        if rest.is_empty() {
+            Some(variables)
+        } else {
+            None
+        }
+    }
+
+    /// Expands the template into a URL path, with the value of each variable given by its field
+    /// path, percent-encoded.
+    ///
+    /// Returns `None` if a variable has no value, or if its value doesn't match the segments of
+    /// the variable, such as `shelves/1` for `{name=shelves/*/books/*}`.
+    pub fn expand<F>(&self, mut value: F) -> Option<String>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        let mut path = String::new();
+        for segment in &self.segments {
+            path.push('/');
+            match segment {
+                Segment::Variable {
+                    field_path,
+                    segments,
+                } => {
+                    let value = value(field_path)?;
+                    if segments[..] == [Segment::Wildcard] {
+                        if value.is_empty() {
+                            return None;
+                        }
+                        percent_encode(&value, &mut path);
+                    } else {
+                        let values = value.split('/').collect::<Vec<_>>();
+                        if match_segments(segments, &values)? != values.len() {
+                            return None;
+                        }
+                        for (i, value) in values.iter().enumerate() {
+                            if i > 0 {
+                                path.push('/');
+                            }
+                            percent_encode(value, &mut path);
+                        }
+                    }
+                }
+                segment => write!(path, "{}", segment).unwrap(),
+            }
+        }
+        if let Some(verb) = &self.verb {
+            path.push(':');
+            path.push_str(verb);
+        }
+        Some(path)
+    }
+}
+
+impl fmt::Display for PathTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.segments {
+            write!(f, "/{}", segment)?;
+        }
+        if let Some(verb) = &self.verb {
+            write!(f, ":{}", verb)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Segment::Literal(literal) => f.write_str(literal),
+            Segment::Wildcard => f.write_str("*"),
+            Segment::DoubleWildcard => f.write_str("**"),
+            Segment::Variable {
+                field_path,
+                segments,
+            } => {
+                write!(f, "{{{}", field_path)?;
+                if segments[..] != [Segment::Wildcard] {
+                    for (i, segment) in segments.iter().enumerate() {
+                        f.write_str(if i == 0 { "=" } else { "/" })?;
+                        write!(f, "{}", segment)?;
+                    }
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+/// Splits a path at the `/` which are not in a variable.
+fn split_segments(path: &str) -> Result<Vec<&str>, &'static str> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut in_variable = false;
+    for (index, c) in path.char_indices() {
+        match c {
+            '{' if in_variable => return Err("nested variable"),
+            '{' => in_variable = true,
+            '}' if !in_variable => return Err("unmatched '}'"),
+            '}' => in_variable = false,
+            '/' if !in_variable => {
+                segments.push(&path[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    if in_variable {
+        return Err("unterminated variable");
+    }
+    segments.push(&path[start..]);
+    Ok(segments)
+}
+
+/// Parses a segment other than a variable.
+fn parse_segment(segment: &str) -> Result<Segment, &'static str> {
+    match segment {
+        "" => Err("empty segment"),
+        "*" => Ok(Segment::Wildcard),
+        "**" => Ok(Segment::DoubleWildcard),
+        _ if segment.contains(|c| c == '{' || c == '}' || c == '=') => Err("invalid literal"),
+        _ => Ok(Segment::Literal(segment.to_string())),
+    }
+}
+
+/// Matches the leading segments of a path against segments other than variables, returning the
+/// number of matched segments.
+fn match_segments(segments: &[Segment], path: &[&str]) -> Option<usize> {
+    let mut matched = 0;
+    for segment in segments {
+        match segment {
+            Segment::Literal(literal) if path.get(matched) == Some(&literal.as_str()) => (),
+            Segment::Wildcard if path.get(matched).map_or(false, |s| !s.is_empty()) => (),
+            Segment::DoubleWildcard => return Some(path.len()),
+            _ => return None,
+        }
+        matched += 1;
+    }
+    Some(matched)
+}
+
+/// Appends a string to a URL, percent-encoding the bytes other than the unreserved characters of
+/// RFC 3986.
+pub(crate) fn percent_encode(value: &str, url: &mut String) {
+    for &byte in value.as_bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            url.push(char::from(byte));
+        } else {
+            write!(url, "%{:02X}", byte).unwrap();
+        }
+    }
+}
+
+/// Decodes a percent-encoded string, leaving `%2F` encoded if `keep_slash` is set.
+///
+/// Returns `None` if an escape is invalid, or if the decoded string is not UTF-8.
+pub(crate) fn percent_decode(value: &str, keep_slash: bool) -> Option<String> {
+    let mut bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    while let Some((&byte, rest)) = bytes.split_first() {
+        bytes = rest;
+        if byte != b'%' {
+            decoded.push(byte);
+            continue;
+        }
+        let escape = bytes.get(..2)?;
+        let byte = u8::from_str_radix(core::str::from_utf8(escape).ok()?, 16).ok()?;
+        if keep_slash && byte == b'/' {
+            decoded.extend_from_slice(b"%2F");
+        } else {
+            decoded.push(byte);
+        }
+        bytes = &bytes[2..];
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Returns `true` if the path is a sequence of identifiers separated by `.`.
+fn is_field_path(path: &str) -> bool {
+    path.split('.').all(|ident| {
+        let mut chars = ident.chars();
+        matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// An error parsing a [`PathTemplate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathTemplateError {
+    template: String,
+    description: String,
+}
+
+impl fmt::Display for PathTemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid path template {:?}: {}",
+            self.template, self.description
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PathTemplateError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::api::CustomHttpPattern;
+
+    #[test]
+    fn parse() {
+        let template = PathTemplate::parse("/v1/{name=shelves/*/books/*}:publish").unwrap();
+        assert_eq!(
+            template.segments(),
+            &[
+                Segment::Literal("v1".to_string()),
+                Segment::Variable {
+                    field_path: "name".to_string(),
+                    segments: vec![
+                        Segment::Literal("shelves".to_string()),
+                        Segment::Wildcard,
+                        Segment::Literal("books".to_string()),
+                        Segment::Wildcard,
+                    ],
+                },
+            ]
+        );
+        assert_eq!(template.verb(), Some("publish"));
+        assert_eq!(template.variables().collect::<Vec<_>>(), vec!["name"]);
+
+        for template in &[
+            "/v1/{name=shelves/*/books/*}:publish",
+            "/v1/shelves/{shelf}/books/{book.id}",
+            "/v1/{name=**}",
+            "/static/**",
+            "/v1/*:list",
+        ] {
+            assert_eq!(
+                PathTemplate::parse(template).unwrap().to_string(),
+                *template
+            );
+        }
+        assert_eq!(
+            PathTemplate::parse("/v1/{name=*}").unwrap().to_string(),
+            "/v1/{name}"
+        );
+
+        for template in &[
+            "v1/books",
+            "/v1//books",
+            "/v1/{name",
+            "/v1/name}",
+            "/v1/{a={b}}",
+            "/v1/{1a}",
+            "/v1/{a.}",
+            "/v1/**/books",
+            "/v1/{name=**}/books",
+            "/v1/books:",
+        ] {
+            assert!(PathTemplate::parse(template).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn match_path() {
+        let template = PathTemplate::parse("/v1/{name=shelves/*/books/*}:publish").unwrap();
+        assert_eq!(
+            template.match_path("/v1/shelves/1/books/a%20b%2Fc:publish"),
+            Some(vec![("name", "shelves/1/books/a b%2Fc".to_string())])
+        );
+        assert_eq!(template.match_path("/v1/shelves/1/books/2"), None);
+        assert_eq!(template.match_path("/v1/shelves/1:publish"), None);
+        assert_eq!(template.match_path("/v1/shelves//books/2:publish"), None);
+
+        let template = PathTemplate::parse("/v1/shelves/{shelf}/books/{book.id}").unwrap();
+        assert_eq!(
+            template.match_path("/v1/shelves/a%2Fb/books/%E2%9C%93"),
+            Some(vec![
+                ("shelf", "a/b".to_string()),
+                ("book.id", "\u{2713}".to_string())
+            ])
+        );
+        assert_eq!(template.match_path("/v1/shelves/1/books/%ZZ"), None);
+        assert_eq!(template.match_path("/v1/shelves/1/books/2/3"), None);
+
+        let template = PathTemplate::parse("/v1/{name=files/**}").unwrap();
+        assert_eq!(
+            template.match_path("/v1/files/a/b/c.txt"),
+            Some(vec![("name", "files/a/b/c.txt".to_string())])
+        );
+    }
+
+    #[test]
+    fn expand() {
+        let template = PathTemplate::parse("/v1/{name=shelves/*/books/*}:publish").unwrap();
+        assert_eq!(
+            template.expand(|_| Some("shelves/1/books/a b".to_string())),
+            Some("/v1/shelves/1/books/a%20b:publish".to_string())
+        );
+        assert_eq!(template.expand(|_| Some("shelves/1".to_string())), None);
+        assert_eq!(template.expand(|_| None), None);
+
+        let template = PathTemplate::parse("/v1/shelves/{shelf}/books/{book.id}").unwrap();
+        let path = template
+            .expand(|field_path| Some(format!("{}/\u{2713}", field_path)))
+            .unwrap();
+        assert_eq!(
+            path,
+            "/v1/shelves/shelf%2F%E2%9C%93/books/book.id%2F%E2%9C%93"
+        );
+        assert_eq!(
+            template.match_path(&path),
+            Some(vec![
+                ("shelf", "shelf/\u{2713}".to_string()),
+                ("book.id", "book.id/\u{2713}".to_string())
+            ])
+        );
+        assert_eq!(template.expand(|_| Some(String::new())), None);
+    }
+
+    #[test]
+    fn method_and_path() {
+        let rule = HttpRule {
+            pattern: Some(Pattern::Get("/v1/books/{id}".to_string())),
+            additional_bindings: vec![HttpRule {
+                pattern: Some(Pattern::Custom(CustomHttpPattern {
+                    kind: "HEAD".to_string(),
+                    path: "/v1/books/{id}".to_string(),
+                })),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            rule.bindings()
+                .map(|rule| rule.method_and_path().unwrap())
+                .collect::<Vec<_>>(),
+            vec![("GET", "/v1/books/{id}"), ("HEAD", "/v1/books/{id}")]
+        );
+        assert_eq!(HttpRule::default().method_and_path(), None);
+    }
+}
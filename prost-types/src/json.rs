@@ -0,0 +1,500 @@
+//! Serialization of messages to and from JSON, following the Protobuf [JSON mapping][1].
+//!
+//! Messages generated with `prost-build`'s `Config::enable_serde_json` implement `Serialize` and
+//! `Deserialize` according to the mapping. The functions of this module serialize them with
+//! `serde_json`, so that the common case takes a single call:
+//!
+//! ```rust,ignore
+//! let json = prost_types::json::to_string(&response)?;
+//! let request: CreateUserRequest = prost_types::json::from_str(&body)?;
+//! ```
+//!
+//! The rendering can be adjusted with [`Options`], for example to pretty-print the output, to
+//! escape the characters outside of ASCII, to format floating point numbers, to render enum values
+//! as numbers, or to render 64-bit integers and enum values as in [`Mode::Legacy`]:
+//!
+//! ```rust,ignore
+//! use prost_types::json::Options;
+//! use prost_types::serde::escape::Escaping;
+//!
+//! let json = Options::new().pretty(true).escaping(Escaping::Ascii).to_string(&response)?;
+//! ```
+//!
+//! Payloads whose type is only known at runtime, such as messages stored in queues and databases,
+//! can be rendered from their binary encoding with [`decode_to_string`], given the pool of
+//! descriptors defining their type:
+//!
+//! ```rust,ignore
+//! let mut pool = prost_types::reflect::DescriptorPool::new();
+//! pool.decode_file_descriptor_set(&std::fs::read("descriptors.bin")?)?;
+//! let json = prost_types::json::decode_to_string(&pool, "acme.Order", &payload)?;
+//! ```
+//!
+//! Errors of `serde_json` are mapped to [`Error`]. This module requires both the `serde` and the
+//! `serde_json` features.
+//!
+//! [1]: https://developers.google.com/protocol-buffers/docs/proto3#json
+
+use core::fmt;
+
+use prost::alloc::borrow::Cow;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use prost::DecodeError;
+use serde_crate::de::DeserializeOwned;
+use serde_crate::Serialize;
+
+use crate::dynamic::DynamicMessage;
+use crate::reflect::DescriptorPool;
+#[cfg(feature = "std")]
+use crate::serde::enumeration;
+use crate::serde::escape::Escaping;
+use crate::serde::float_format::FloatFormat;
+#[cfg(feature = "std")]
+use crate::serde::mode::{self, Mode};
+
+/// Serializes a message as JSON.
+pub fn to_string<T>(message: &T) -> Result<String, Error>
+where
+    T: ?Sized + Serialize,
+{
+    Options::new().to_string(message)
+}
+
+/// Serializes a message as JSON bytes, encoded in UTF-8.
+pub fn to_vec<T>(message: &T) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize,
+{
+    Options::new().to_vec(message)
+}
+
+/// Deserializes a message from JSON.
+pub fn from_str<T>(json: &str) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_str(json).map_err(Error)
+}
+
+/// Deserializes a message from JSON bytes, encoded in UTF-8.
+pub fn from_slice<T>(json: &[u8]) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_slice(json).map_err(Error)
+}
+
+/// Decodes a message of the named type from its binary encoding, and serializes it as JSON.
+///
+/// See [`Options::decode_to_string`].
+pub fn decode_to_string(
+    pool: &DescriptorPool,
+    message_name: &str,
+    buf: &[u8],
+) -> Result<String, DecodeError> {
+    Options::new().decode_to_string(pool, message_name, buf)
+}
+
+/// Options for serializing messages as JSON.
+///
+/// Deserialization accepts every rendering, so it has no options.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pretty: bool,
+    escaping: Escaping,
+    floats: FloatFormat,
+    #[cfg(feature = "std")]
+    mode: Option<Mode>,
+    #[cfg(feature = "std")]
+    enums_as_ints: bool,
+}
+
+impl Options {
+    /// Creates the default options: compact output, with characters outside of ASCII written as
+    /// raw UTF-8 and floating point numbers with their shortest digits, in the current
+    /// [`mode`](crate::serde::mode).
+    pub fn new() -> Options {
+        Options::default()
+    }
+
+    /// Sets whether the output is pretty-printed.
+    pub fn pretty(&mut self, pretty: bool) -> &mut Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Sets how characters outside of ASCII are written.
+    pub fn escaping(&mut self, escaping: Escaping) -> &mut Self {
+        self.escaping = escaping;
+        self
+    }
+
+    /// Sets how floating point numbers are written.
+    pub fn floats(&mut self, floats: FloatFormat) -> &mut Self {
+        self.floats = floats;
+        self
+    }
+
+    /// Sets the mode used for the serialization, instead of the current mode of the thread.
+    ///
+    /// This requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn mode(&mut self, mode: Mode) -> &mut Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Sets whether enum values are rendered as numbers rather than by their name, as with the
+    /// `always_print_enums_as_ints` option of the reference implementations. 64-bit integers are
+    /// still rendered as the mode requires.
+    ///
+    /// This requires the `std` feature.
+    #[cfg(feature = "std")]
+    pub fn enums_as_ints(&mut self, enums_as_ints: bool) -> &mut Self {
+        self.enums_as_ints = enums_as_ints;
+        self
+    }
+
+    /// Serializes a message as JSON with the options.
+    pub fn to_string<T>(&self, message: &T) -> Result<String, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        #[cfg(feature = "std")]
+        {
+            if self.enums_as_ints {
+                let options = Options {
+                    enums_as_ints: false,
+                    ..*self
+                };
+                return enumeration::with_numbers(|| options.to_string(message));
+            }
+            if let Some(mode) = self.mode {
+                return mode::with(mode, || self.serialize(message));
+            }
+        }
+        self.serialize(message)
+    }
+
+    /// Serializes a message as JSON bytes with the options, encoded in UTF-8.
+    pub fn to_vec<T>(&self, message: &T) -> Result<Vec<u8>, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.to_string(message).map(String::into_bytes)
+    }
+
+    /// Decodes a message of the named type from its binary encoding, and serializes it as JSON
+    /// with the options.
+    ///
+    /// The type is looked up by its fully-qualified name in the pool, and the message is decoded
+    /// as a [`DynamicMessage`]. Its fields are rendered as for generated types, and fields which
+    /// the descriptor doesn't declare are omitted. An error is returned if the pool doesn't define
+    /// the type, if the payload is not a valid encoding of it, or if the message can't be
+    /// serialized, such as an `Any` holding a type which the pool doesn't define.
+    pub fn decode_to_string(
+        &self,
+        pool: &DescriptorPool,
+        message_name: &str,
+        buf: &[u8],
+    ) -> Result<String, DecodeError> {
+        let descriptor = pool.get_message_by_name(message_name).ok_or_else(|| {
+            DecodeError::new(prost::alloc::format!(
+                "unknown message type: {}",
+                message_name
+            ))
+        })?;
+        let message = DynamicMessage::decode(descriptor, buf)?;
+        Ok(self.to_string(&message)?)
+    }
+
+    fn serialize<T>(&self, message: &T) -> Result<String, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let json = if self.pretty {
+            serde_json::to_string_pretty(message)
+        } else {
+            serde_json::to_string(message)
+        };
+        let json = json.map_err(Error)?;
+        let json = match self.floats.apply(&json) {
+            Cow::Borrowed(_) => json,
+            Cow::Owned(formatted) => formatted,
+        };
+        Ok(match self.escaping.apply(&json) {
+            Cow::Borrowed(_) => json,
+            Cow::Owned(escaped) => escaped,
+        })
+    }
+}
+
+/// An error serializing or deserializing a message as JSON.
+pub struct Error(serde_json::Error);
+
+impl Error {
+    /// Returns `true` if the input is not syntactically valid JSON.
+    pub fn is_syntax(&self) -> bool {
+        self.0.is_syntax()
+    }
+
+    /// Returns `true` if the input is valid JSON, but does not match the message, or if the
+    /// message can not be serialized, such as a timestamp out of range.
+    pub fn is_data(&self) -> bool {
+        self.0.is_data()
+    }
+
+    /// Returns `true` if the input ended before the end of the JSON value.
+    pub fn is_eof(&self) -> bool {
+        self.0.is_eof()
+    }
+
+    /// Returns the one-based line of the input at which the error occurred, or 0 if the error did
+    /// not occur while deserializing.
+    pub fn line(&self) -> usize {
+        self.0.line()
+    }
+
+    /// Returns the one-based column of the input at which the error occurred, or 0 if the error
+    /// did not occur while deserializing.
+    pub fn column(&self) -> usize {
+        self.0.column()
+    }
+
+    /// Returns the underlying `serde_json` error.
+    pub fn into_inner(self) -> serde_json::Error {
+        self.0
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid Protobuf JSON: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+impl From<Error> for DecodeError {
+    fn from(error: Error) -> DecodeError {
+        DecodeError::new(prost::alloc::format!("{}", error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_crate::Deserialize;
+
+    use crate::Timestamp;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", rename_all = "camelCase")]
+    struct Event {
+        #[serde(with = "crate::serde::int64")]
+        sequence_number: i64,
+        #[serde(with = "crate::serde::timestamp")]
+        created_at: Timestamp,
+        description: String,
+    }
+
+    fn event() -> Event {
+        Event {
+            sequence_number: 7,
+            created_at: Timestamp {
+                seconds: 1,
+                nanos: 0,
+            },
+            description: "café".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = to_string(&event()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"sequenceNumber":"7","createdAt":"1970-01-01T00:00:01Z","description":"café"}"#
+        );
+        assert_eq!(to_vec(&event()).unwrap(), json.as_bytes());
+        assert_eq!(from_str::<Event>(&json).unwrap(), event());
+        assert_eq!(from_slice::<Event>(json.as_bytes()).unwrap(), event());
+    }
+
+    #[test]
+    fn options() {
+        let json = Options::new()
+            .escaping(Escaping::Ascii)
+            .mode(Mode::Legacy)
+            .to_string(&event())
+            .unwrap();
+        assert_eq!(
+            json,
+            r#"{"sequenceNumber":7,"createdAt":"1970-01-01T00:00:01Z","description":"caf\u00e9"}"#
+        );
+        assert_eq!(from_str::<Event>(&json).unwrap(), event());
+        // The mode is only overridden during the serialization.
+        assert_eq!(mode::current(), Mode::Spec);
+
+        let json = Options::new().pretty(true).to_vec(&event()).unwrap();
+        assert!(json.starts_with(b"{\n  \"sequenceNumber\": \"7\""));
+        assert_eq!(from_slice::<Event>(&json).unwrap(), event());
+
+        let values = crate::Value::from(vec![crate::Value::from(0.1), crate::Value::from(-2.0)]);
+        let json = Options::new()
+            .floats(FloatFormat::Fixed(2))
+            .to_string(&values)
+            .unwrap();
+        assert_eq!(json, "[0.10,-2.00]");
+    }
+
+    #[test]
+    fn enums_as_ints() {
+        #[derive(Serialize)]
+        #[serde(crate = "serde_crate")]
+        struct Sample {
+            #[serde(with = "crate::serde::int64")]
+            count: u64,
+            #[serde(serialize_with = "enumeration::serialize::<crate::NullValue, _>")]
+            null: i32,
+        }
+
+        let sample = Sample { count: 1, null: 0 };
+        assert_eq!(
+            to_string(&sample).unwrap(),
+            r#"{"count":"1","null":"NULL_VALUE"}"#
+        );
+        let mut options = Options::new();
+        options.enums_as_ints(true);
+        assert_eq!(
+            options.to_string(&sample).unwrap(),
+            r#"{"count":"1","null":0}"#
+        );
+        assert_eq!(
+            options.mode(Mode::Legacy).to_string(&sample).unwrap(),
+            r#"{"count":1,"null":0}"#
+        );
+    }
+
+    #[test]
+    fn decode() {
+        use prost::alloc::collections::BTreeMap;
+        use prost::Message;
+
+        use crate::dynamic::{MapKey, Value};
+
+        let pool = crate::reflect::tests::test_pool();
+        let mut person = DynamicMessage::new(pool.get_message_by_name("test.Person").unwrap());
+        person.set_field_by_name("name", Value::String("Ferris".to_string()));
+        person.set_field_by_name("scores", Value::List(vec![Value::I64(-1), Value::I64(2)]));
+        person.set_field_by_name("kind", Value::EnumNumber(1));
+        let mut labels = BTreeMap::new();
+        labels.insert(MapKey::String("x".to_string()), Value::U32(3));
+        person.set_field_by_name("labels", Value::Map(labels));
+        person.set_field_by_name("b", Value::I32(5));
+        person.set_field_by_name("data", Value::Bytes(b"hi"[..].into()));
+        person.set_field_by_name("ratio", Value::F64(f64::INFINITY));
+        let buf = person.encode_to_vec();
+
+        assert_eq!(
+            decode_to_string(&pool, ".test.Person", &buf).unwrap(),
+            r#"{"name":"Ferris","scores":["-1","2"],"kind":"ADMIN","labels":{"x":3},"b":5,"data":"aGk=","ratio":"Infinity"}"#
+        );
+        assert_eq!(
+            Options::new()
+                .mode(Mode::Legacy)
+                .decode_to_string(&pool, "test.Person", &buf)
+                .unwrap(),
+            r#"{"name":"Ferris","scores":[-1,2],"kind":1,"labels":{"x":3},"b":5,"data":"aGk=","ratio":"Infinity"}"#
+        );
+        let json = Options::new()
+            .pretty(true)
+            .decode_to_string(&pool, "test.Person", &buf)
+            .unwrap();
+        assert!(json.starts_with("{\n  \"name\": \"Ferris\""));
+
+        assert!(decode_to_string(&pool, "test.Missing", &buf)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown message type: test.Missing"));
+        assert!(decode_to_string(&pool, "test.Person", &[0x0a]).is_err());
+
+        // The well-known types are rendered by their special forms, including within an `Any`.
+        let pool = crate::reflect::DescriptorPool::new();
+        let timestamp = Timestamp {
+            seconds: 1,
+            nanos: 0,
+        };
+        assert_eq!(
+            decode_to_string(
+                &pool,
+                "google.protobuf.Timestamp",
+                &timestamp.encode_to_vec()
+            )
+            .unwrap(),
+            r#""1970-01-01T00:00:01Z""#
+        );
+        let any = crate::Any {
+            type_url: "type.googleapis.com/google.protobuf.Timestamp".to_string(),
+            value: timestamp.encode_to_vec(),
+        };
+        assert_eq!(
+            decode_to_string(&pool, "google.protobuf.Any", &any.encode_to_vec()).unwrap(),
+            r#"{"@type":"type.googleapis.com/google.protobuf.Timestamp","value":"1970-01-01T00:00:01Z"}"#
+        );
+        let any = crate::Any {
+            type_url: "type.googleapis.com/google.protobuf.Api".to_string(),
+            value: crate::Api {
+                name: "Library".to_string(),
+                ..Default::default()
+            }
+            .encode_to_vec(),
+        };
+        assert_eq!(
+            decode_to_string(&pool, "google.protobuf.Any", &any.encode_to_vec()).unwrap(),
+            r#"{"@type":"type.googleapis.com/google.protobuf.Api","name":"Library"}"#
+        );
+        let value = crate::Value::from(vec![crate::Value::from(1.5), crate::Value::null()]);
+        assert_eq!(
+            decode_to_string(&pool, "google.protobuf.Value", &value.encode_to_vec()).unwrap(),
+            "[1.5,null]"
+        );
+        let any = crate::Any {
+            type_url: "type.googleapis.com/acme.Missing".to_string(),
+            value: Vec::new(),
+        };
+        assert!(decode_to_string(&pool, "google.protobuf.Any", &any.encode_to_vec()).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        let error = from_str::<Event>(r#"{"sequenceNumber":"7","#).unwrap_err();
+        assert!(error.is_eof());
+        let error = from_str::<Event>("{\n\"sequenceNumber\":true}").unwrap_err();
+        assert!(error.is_data());
+        assert_eq!((error.line(), error.column()), (2, 21));
+        assert!(error.to_string().starts_with("invalid Protobuf JSON: "));
+
+        let event = Event {
+            created_at: Timestamp {
+                seconds: i64::MAX,
+                nanos: 0,
+            },
+            ..event()
+        };
+        assert!(to_string(&event).unwrap_err().is_data());
+    }
+}
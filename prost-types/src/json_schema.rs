@@ -0,0 +1,420 @@
+//! JSON Schemas describing the JSON form of messages.
+//!
+//! A [`SchemaGenerator`] describes messages of a [`DescriptorPool`](crate::DescriptorPool) with
+//! [JSON Schema][1] (draft 2020-12), following the canonical Protobuf [JSON mapping][2], as
+//! rendered by `prost_types::json` and `DynamicMessage`. This lets HTTP APIs which expose
+//! messages as JSON publish their schemas, for example as the component schemas of an OpenAPI
+//! 3.1 document, whose schemas are JSON Schemas.
+//!
+//! Each message and enum is described once, in a definition named by its full name, which is
+//! referenced wherever the type is used, so that recursive messages can be described. The
+//! well-known types are described inline by their special JSON forms, such as an RFC 3339 string
+//! for `google.protobuf.Timestamp`.
+//!
+//! ```rust,ignore
+//! let message = pool.get_message_by_name("shop.v1.Order").unwrap();
+//! let schema = prost_types::json_schema::json_schema(&message);
+//! println!("{}", serde_json::Value::try_from(prost_types::Value::from(schema))?);
+//! ```
+//!
+//! The schemas are built as [`Struct`]s, which convert to `serde_json::Value` with the
+//! `serde_json` feature, or serialize as JSON with the `serde` feature.
+//!
+//! [1]: https://json-schema.org/draft/2020-12/json-schema-core.html
+//! [2]: https://developers.google.com/protocol-buffers/docs/proto3#json
+
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec;
+use prost::alloc::vec::Vec;
+
+use crate::reflect::{Cardinality, EnumDescriptor, FieldDescriptor, Kind, MessageDescriptor};
+use crate::{prost_struct, Struct, Value};
+
+/// The URI identifying JSON Schema draft 2020-12, the dialect of the generated schemas.
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Returns a JSON Schema document describing the JSON form of a message.
+///
+/// The document references the definitions of the message, and of the messages and enums it
+/// uses, under `$defs`.
+pub fn json_schema(message: &MessageDescriptor) -> Struct {
+    let mut generator = SchemaGenerator::new();
+    let schema = generator.message(message);
+    let mut document = prost_struct! { "$schema": DIALECT };
+    if let Some(schema) = schema.as_struct() {
+        document.fields.extend(schema.fields.clone());
+    }
+    document.insert("$defs", generator.into_definitions());
+    document
+}
+
+/// Generates JSON Schemas for messages and enums, and collects the definitions they reference.
+#[derive(Clone, Debug)]
+pub struct SchemaGenerator {
+    ref_prefix: String,
+    definitions: Struct,
+}
+
+impl SchemaGenerator {
+    /// Creates a generator referencing the definitions under `#/$defs/`.
+    pub fn new() -> SchemaGenerator {
+        SchemaGenerator::with_ref_prefix("#/$defs/")
+    }
+
+    /// Creates a generator referencing the definitions under a prefix, such as
+    /// `#/components/schemas/` for the component schemas of an OpenAPI document.
+    pub fn with_ref_prefix(ref_prefix: impl Into<String>) -> SchemaGenerator {
+        SchemaGenerator {
+            ref_prefix: ref_prefix.into(),
+            definitions: Struct::default(),
+        }
+    }
+
+    /// Returns the schema of a message, which references its definition, or describes a
+    /// well-known type inline.
+    ///
+    /// The definitions of the message, and of the messages and enums it uses, are added to the
+    /// generator if they are missing.
+    pub fn message(&mut self, message: &MessageDescriptor) -> Value {
+        if let Some(schema) = well_known_schema(message) {
+            return schema;
+        }
+        if let Some(schema) = self.wrapper_schema(message) {
+            return schema;
+        }
+        let name = message.full_name();
+        if !self.definitions.fields.contains_key(name) {
+            // Reserve the name first, so that recursive references end.
+            self.definitions.insert(name, Value::null());
+            let definition = self.message_definition(message);
+            self.definitions.insert(name, definition);
+        }
+        self.reference(name)
+    }
+
+    /// Returns the schema of an enum, which references its definition.
+    ///
+    /// The definition of the enum is added to the generator if it is missing.
+    pub fn enumeration(&mut self, enum_type: &EnumDescriptor) -> Value {
+        if enum_type.full_name() == "google.protobuf.NullValue" {
+            return prost_struct! { "type": "null" }.into();
+        }
+        let name = enum_type.full_name();
+        if !self.definitions.fields.contains_key(name) {
+            let names = enum_type.values().map(|(name, _)| name).collect::<Vec<_>>();
+            self.definitions.insert(
+                name,
+                prost_struct! { "title": enum_type.name(), "type": "string", "enum": names },
+            );
+        }
+        self.reference(name)
+    }
+
+    /// Returns the schema of the value of a field, which is a list for repeated fields, and an
+    /// object for map fields.
+    pub fn field(&mut self, field: &FieldDescriptor) -> Value {
+        if field.is_map() {
+            let entry = field.kind();
+            let entry = entry.as_message().unwrap();
+            let key = entry.map_entry_key_field();
+            let value = entry.map_entry_value_field();
+            let mut schema = prost_struct! {
+                "type": "object",
+                "additionalProperties": self.kind(&value.kind()),
+            };
+            if let Some(names) = map_key_schema(&key.kind()) {
+                schema.insert("propertyNames", names);
+            }
+            schema.into()
+        } else if field.is_list() {
+            prost_struct! { "type": "array", "items": self.kind(&field.kind()) }.into()
+        } else {
+            self.kind(&field.kind())
+        }
+    }
+
+    /// Returns the definitions added to the generator, by the full names of their types.
+    pub fn definitions(&self) -> &Struct {
+        &self.definitions
+    }
+
+    /// Returns the definitions added to the generator, by the full names of their types.
+    pub fn into_definitions(self) -> Struct {
+        self.definitions
+    }
+
+    fn reference(&self, name: &str) -> Value {
+        prost_struct! { "$ref": format!("{}{}", self.ref_prefix, name) }.into()
+    }
+
+    fn message_definition(&mut self, message: &MessageDescriptor) -> Value {
+        let mut properties = Struct::default();
+        let mut required = Vec::new();
+        for field in message.fields() {
+            properties.insert(field.json_name(), self.field(&field));
+            if field.cardinality() == Cardinality::Required {
+                required.push(field.json_name().to_string());
+            }
+        }
+        let mut definition = prost_struct! {
+            "title": message.name(),
+            "type": "object",
+            "properties": properties,
+        };
+        if !required.is_empty() {
+            definition.insert("required", required);
+        }
+        definition.into()
+    }
+
+    fn kind(&mut self, kind: &Kind) -> Value {
+        let schema = match kind {
+            Kind::Message(message) => return self.message(message),
+            Kind::Enum(enum_type) => return self.enumeration(enum_type),
+            Kind::Double => float_schema("double"),
+            Kind::Float => float_schema("float"),
+            Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => prost_struct! {
+                "type": "integer",
+                "format": "int32",
+                "minimum": f64::from(i32::MIN),
+                "maximum": f64::from(i32::MAX),
+            },
+            Kind::Uint32 | Kind::Fixed32 => prost_struct! {
+                "type": "integer",
+                "format": "uint32",
+                "minimum": 0,
+                "maximum": f64::from(u32::MAX),
+            },
+            Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => prost_struct! {
+                "type": "string",
+                "format": "int64",
+                "pattern": "^-?[0-9]+$",
+            },
+            Kind::Uint64 | Kind::Fixed64 => prost_struct! {
+                "type": "string",
+                "format": "uint64",
+                "pattern": "^[0-9]+$",
+            },
+            Kind::Bool => prost_struct! { "type": "boolean" },
+            Kind::String => prost_struct! { "type": "string" },
+            Kind::Bytes => prost_struct! { "type": "string", "contentEncoding": "base64" },
+        };
+        schema.into()
+    }
+
+    /// Returns the schema of a wrapper type, which is the schema of the wrapped value.
+    fn wrapper_schema(&mut self, message: &MessageDescriptor) -> Option<Value> {
+        match message.full_name() {
+            "google.protobuf.DoubleValue"
+            | "google.protobuf.FloatValue"
+            | "google.protobuf.Int64Value"
+            | "google.protobuf.UInt64Value"
+            | "google.protobuf.Int32Value"
+            | "google.protobuf.UInt32Value"
+            | "google.protobuf.BoolValue"
+            | "google.protobuf.StringValue"
+            | "google.protobuf.BytesValue" => {
+                let value = message.get_field(1)?;
+                Some(self.kind(&value.kind()))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for SchemaGenerator {
+    fn default() -> SchemaGenerator {
+        SchemaGenerator::new()
+    }
+}
+
+/// Returns the schema of a well-known type with a special JSON form, other than the wrappers.
+fn well_known_schema(message: &MessageDescriptor) -> Option<Value> {
+    let schema = match message.full_name() {
+        "google.protobuf.Timestamp" => prost_struct! { "type": "string", "format": "date-time" },
+        "google.protobuf.Duration" => prost_struct! {
+            "type": "string",
+            "pattern": "^-?[0-9]+(\\.[0-9]{1,9})?s$",
+        },
+        "google.protobuf.FieldMask" => prost_struct! { "type": "string" },
+        "google.protobuf.Struct" => prost_struct! { "type": "object" },
+        "google.protobuf.ListValue" => prost_struct! { "type": "array" },
+        // Any JSON value.
+        "google.protobuf.Value" => prost_struct! {},
+        "google.protobuf.Any" => prost_struct! {
+            "type": "object",
+            "properties": prost_struct! { "@type": prost_struct! { "type": "string" } },
+            "required": vec!["@type"],
+        },
+        _ => return None,
+    };
+    Some(schema.into())
+}
+
+/// Returns the schema of a `float` or `double`, which is a number, or a string for the values
+/// which are not finite.
+fn float_schema(format: &str) -> Struct {
+    prost_struct! {
+        "anyOf": vec![
+            Value::from(prost_struct! { "type": "number", "format": format }),
+            Value::from(prost_struct! { "enum": vec!["NaN", "Infinity", "-Infinity"] }),
+        ],
+    }
+}
+
+/// Returns the schema of the property names of a map, whose keys are rendered as strings.
+fn map_key_schema(kind: &Kind) -> Option<Struct> {
+    let schema = match kind {
+        Kind::Bool => prost_struct! { "enum": vec!["true", "false"] },
+        Kind::Int32
+        | Kind::Int64
+        | Kind::Sint32
+        | Kind::Sint64
+        | Kind::Sfixed32
+        | Kind::Sfixed64 => {
+            prost_struct! { "pattern": "^-?[0-9]+$" }
+        }
+        Kind::Uint32 | Kind::Uint64 | Kind::Fixed32 | Kind::Fixed64 => {
+            prost_struct! { "pattern": "^[0-9]+$" }
+        }
+        _ => return None,
+    };
+    Some(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, test_pool, typed_field};
+    use crate::{DescriptorPool, DescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+    #[test]
+    fn message() {
+        let pool = test_pool();
+        let person = pool.get_message_by_name("test.Person").unwrap();
+        let schema = json_schema(&person);
+
+        assert_eq!(schema["$schema"].as_str(), Some(DIALECT));
+        assert_eq!(schema["$ref"].as_str(), Some("#/$defs/test.Person"));
+        let definitions = schema["$defs"].as_struct().unwrap();
+        assert_eq!(definitions.len(), 2);
+
+        let properties = &definitions["test.Person"]["properties"];
+        assert_eq!(
+            properties["name"],
+            prost_struct! { "type": "string" }.into()
+        );
+        assert_eq!(properties["id"]["format"].as_str(), Some("int32"));
+        assert_eq!(properties["scores"]["type"].as_str(), Some("array"));
+        assert_eq!(
+            properties["scores"]["items"]["type"].as_str(),
+            Some("string")
+        );
+        assert_eq!(
+            properties["kind"]["$ref"].as_str(),
+            Some("#/$defs/test.Person.Kind")
+        );
+        assert_eq!(
+            properties["friend"]["$ref"].as_str(),
+            Some("#/$defs/test.Person")
+        );
+        assert_eq!(
+            properties["labels"]["additionalProperties"]["format"].as_str(),
+            Some("uint32")
+        );
+        assert!(properties["labels"]["propertyNames"].is_null());
+        assert_eq!(properties["a"]["type"].as_str(), Some("string"));
+        assert_eq!(
+            properties["data"]["contentEncoding"].as_str(),
+            Some("base64")
+        );
+        assert_eq!(
+            properties["ratio"]["anyOf"][0]["type"].as_str(),
+            Some("number")
+        );
+
+        assert_eq!(
+            definitions["test.Person.Kind"],
+            prost_struct! { "title": "Kind", "type": "string", "enum": vec!["UNKNOWN", "ADMIN"] }
+                .into()
+        );
+    }
+
+    #[test]
+    fn well_known_types() {
+        let event = DescriptorProto {
+            name: Some("Event".to_string()),
+            field: vec![
+                typed_field(
+                    "at",
+                    1,
+                    Label::Optional,
+                    Type::Message,
+                    ".google.protobuf.Timestamp",
+                ),
+                typed_field(
+                    "count",
+                    2,
+                    Label::Optional,
+                    Type::Message,
+                    ".google.protobuf.Int64Value",
+                ),
+                field("id", 3, Label::Required, Type::Uint64),
+            ],
+            ..Default::default()
+        };
+        let timestamp = DescriptorProto {
+            name: Some("Timestamp".to_string()),
+            field: vec![
+                field("seconds", 1, Label::Optional, Type::Int64),
+                field("nanos", 2, Label::Optional, Type::Int32),
+            ],
+            ..Default::default()
+        };
+        let int64_value = DescriptorProto {
+            name: Some("Int64Value".to_string()),
+            field: vec![field("value", 1, Label::Optional, Type::Int64)],
+            ..Default::default()
+        };
+        let pool = DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    name: Some("google/protobuf/wrappers.proto".to_string()),
+                    package: Some("google.protobuf".to_string()),
+                    message_type: vec![timestamp, int64_value],
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    name: Some("events.proto".to_string()),
+                    package: Some("events".to_string()),
+                    message_type: vec![event],
+                    ..Default::default()
+                },
+            ],
+        })
+        .unwrap();
+
+        let mut generator = SchemaGenerator::with_ref_prefix("#/components/schemas/");
+        let event = pool.get_message_by_name("events.Event").unwrap();
+        assert_eq!(
+            generator.message(&event),
+            prost_struct! { "$ref": "#/components/schemas/events.Event" }.into()
+        );
+        let definitions = generator.into_definitions();
+        assert_eq!(definitions.len(), 1);
+        let event = &definitions["events.Event"];
+        assert_eq!(
+            event["properties"]["at"]["format"].as_str(),
+            Some("date-time")
+        );
+        assert_eq!(
+            event["properties"]["count"]["format"].as_str(),
+            Some("int64")
+        );
+        assert_eq!(event["properties"]["id"]["format"].as_str(), Some("uint64"));
+        assert_eq!(event["required"], vec!["id"].into());
+    }
+}
@@ -21,12 +21,155 @@ pub mod compiler {
     include!("compiler.rs");
 }
 
+mod any;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+mod arithmetic;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod avro;
+mod base64;
+pub mod compat;
+mod datetime;
+pub mod dynamic;
+mod field_mask;
+mod http_rule;
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+pub mod json;
+pub mod json_schema;
+pub mod openapi;
+mod operation;
+pub mod pipeline;
+pub mod reflect;
+mod reflection_catalog;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod struct_value;
+pub mod tabular;
+pub mod transcoding;
+pub mod validity;
+
+pub use crate::any::TypeRegistry;
+pub use crate::datetime::{DurationError, LeapSecondPolicy, TimestampError};
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub use crate::datetime::{DurationOutOfRangeError, TimestampOutOfRangeError};
+pub use crate::dynamic::DynamicMessage;
+pub use crate::reflect::DescriptorPool;
+#[cfg(feature = "serde_json")]
+pub use crate::struct_value::InvalidValueError;
+pub use crate::struct_value::StructMap;
+
+macro_rules! impl_name {
+    ($package:literal, $($ty:ident),*) => {$(
+        impl prost::Name for $ty {
+            const NAME: &'static str = stringify!($ty);
+            const PACKAGE: &'static str = $package;
+        }
+    )*};
+}
+
+impl_name!(
+    "google.protobuf",
+    Any,
+    Api,
+    DescriptorProto,
+    Duration,
+    Enum,
+    EnumDescriptorProto,
+    EnumValue,
+    Field,
+    FieldDescriptorProto,
+    FieldMask,
+    FileDescriptorProto,
+    FileDescriptorSet,
+    ListValue,
+    Method,
+    Mixin,
+    Option,
+    SourceContext,
+    Struct,
+    Timestamp,
+    Type,
+    Value
+);
+
+/// The `google.longrunning` package, with long-running operations.
+///
+/// See [`Operation::result`](longrunning::Operation::result) for unpacking the response and
+/// metadata of an operation.
+pub mod longrunning {
+    include!("longrunning.rs");
+
+    pub use crate::operation::OperationResult;
+
+    impl_name!(
+        "google.longrunning",
+        CancelOperationRequest,
+        DeleteOperationRequest,
+        GetOperationRequest,
+        ListOperationsRequest,
+        ListOperationsResponse,
+        Operation,
+        OperationInfo,
+        WaitOperationRequest
+    );
+}
+
+/// The `google.api` package, with the `google.api.http` annotations mapping RPC methods to REST
+/// endpoints.
+///
+/// The annotation of a method is the extension `72295728` of its `MethodOptions`, which decodes as
+/// an [`HttpRule`](api::HttpRule).
+pub mod api {
+    include!("api.rs");
+
+    pub use crate::http_rule::{PathTemplate, PathTemplateError, Segment};
+
+    /// The field number of the `google.api.http` extension of `google.protobuf.MethodOptions`.
+    pub const HTTP_EXTENSION: u32 = 72295728;
+
+    impl_name!("google.api", CustomHttpPattern, Http, HttpRule);
+}
+
+/// The `google.rpc` package, with the error model of RPC APIs.
+pub mod rpc {
+    include!("rpc.rs");
+
+    impl_name!("google.rpc", Status);
+}
+
+/// The `grpc.reflection.v1` package, with the messages of the [gRPC server reflection][1]
+/// protocol.
+///
+/// A [`ReflectionCatalog`](reflection::ReflectionCatalog) answers the requests of the protocol
+/// from the file descriptor sets of the services of a server. The messages of the `v1alpha`
+/// version of the protocol are encoded identically, so they also answer its requests.
+///
+/// [1]: https://github.com/grpc/grpc/blob/master/doc/server-reflection.md
+pub mod reflection {
+    include!("reflection.rs");
+
+    pub use crate::reflection_catalog::ReflectionCatalog;
+
+    impl_name!(
+        "grpc.reflection.v1",
+        ErrorResponse,
+        ExtensionNumberResponse,
+        ExtensionRequest,
+        FileDescriptorResponse,
+        ListServiceResponse,
+        ServerReflectionRequest,
+        ServerReflectionResponse,
+        ServiceResponse
+    );
+}
+
 // The Protobuf `Duration` and `Timestamp` types can't delegate to the standard library equivalents
 // because the Protobuf versions are signed. To make them easier to work with, `From` conversions
 // are defined in both directions.
 
-const NANOS_PER_SECOND: i32 = 1_000_000_000;
-const NANOS_MAX: i32 = NANOS_PER_SECOND - 1;
+pub(crate) const NANOS_PER_SECOND: i32 = 1_000_000_000;
+pub(crate) const NANOS_MAX: i32 = NANOS_PER_SECOND - 1;
 
 impl Duration {
     /// Normalizes the duration to a canonical format.
@@ -100,23 +243,25 @@ impl From<time::Duration> for Duration {
     }
 }
 
+/// Converts a `Duration` to a `std::time::Duration`, failing with
+/// [`DurationError::NegativeDuration`] if it is negative.
+///
+/// The conversion is exact, including for durations which are not normalized or are outside of
+/// the range of `google.protobuf.Duration`.
 impl TryFrom<Duration> for time::Duration {
-    type Error = time::Duration;
-
-    /// Converts a `Duration` to a result containing a positive (`Ok`) or negative (`Err`)
-    /// `std::time::Duration`.
-    fn try_from(mut duration: Duration) -> Result<time::Duration, time::Duration> {
-        duration.normalize();
-        if duration.seconds >= 0 {
-            Ok(time::Duration::new(
-                duration.seconds as u64,
-                duration.nanos as u32,
-            ))
+    type Error = DurationError;
+
+    fn try_from(duration: Duration) -> Result<time::Duration, DurationError> {
+        let nanos = duration.total_nanos();
+        let magnitude = nanos.unsigned_abs();
+        let magnitude = time::Duration::new(
+            (magnitude / NANOS_PER_SECOND as u128) as u64,
+            (magnitude % NANOS_PER_SECOND as u128) as u32,
+        );
+        if nanos < 0 {
+            Err(DurationError::NegativeDuration(magnitude))
         } else {
-            Err(time::Duration::new(
-                (-duration.seconds) as u64,
-                (-duration.nanos) as u32,
-            ))
+            Ok(magnitude)
         }
     }
 }
@@ -126,7 +271,6 @@ impl Timestamp {
     ///
     /// Based on [`google::protobuf::util::CreateNormalized`][1].
     /// [1]: https://github.com/google/protobuf/blob/v3.3.2/src/google/protobuf/util/time_util.cc#L59-L77
-    #[cfg(feature = "std")]
     pub fn normalize(&mut self) {
         // Make sure nanos is in the range.
         if self.nanos <= -NANOS_PER_SECOND || self.nanos >= NANOS_PER_SECOND {
@@ -167,7 +311,6 @@ impl Timestamp {
 
 /// Implements the unstable/naive version of `Eq`: a basic equality check on the internal fields of the `Timestamp`.
 /// This implies that `normalized_ts != non_normalized_ts` even if `normalized_ts == non_normalized_ts.normalized()`.
-#[cfg(feature = "std")]
 impl Eq for Timestamp {}
 
 #[cfg(feature = "std")]
@@ -259,12 +402,12 @@ impl TryFrom<Timestamp> for std::time::SystemTime {
 mod tests {
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-    use proptest::prelude::*;
+    use proptest_crate::prelude::*;
 
     use super::*;
 
     #[cfg(feature = "std")]
-    proptest! {
+    proptest_crate::proptest! {
         #[test]
         fn check_system_time_roundtrip(
             system_time in SystemTime::arbitrary(),
@@ -429,6 +572,66 @@ mod tests {
         }
     }
 
+    #[test]
+    fn check_duration_to_std() {
+        let cases = [
+            (0, 0, Ok(Duration::new(0, 0))),
+            (1, 500_000_000, Ok(Duration::new(1, 500_000_000))),
+            (2, -500_000_000, Ok(Duration::new(1, 500_000_000))),
+            (0, 2_000_000_001, Ok(Duration::new(2, 1))),
+            (
+                i64::MAX,
+                999_999_999,
+                Ok(Duration::new(i64::MAX as u64, 999_999_999)),
+            ),
+            (
+                i64::MAX,
+                i32::MAX,
+                Ok(Duration::new(i64::MAX as u64 + 2, 147_483_647)),
+            ),
+            (
+                -1,
+                -500_000_000,
+                Err(DurationError::NegativeDuration(Duration::new(
+                    1,
+                    500_000_000,
+                ))),
+            ),
+            (
+                0,
+                -1,
+                Err(DurationError::NegativeDuration(Duration::new(0, 1))),
+            ),
+            (
+                i64::MIN,
+                0,
+                Err(DurationError::NegativeDuration(Duration::new(
+                    i64::MIN.unsigned_abs(),
+                    0,
+                ))),
+            ),
+        ];
+        for (seconds, nanos, expected) in cases.iter().cloned() {
+            let duration = crate::Duration { seconds, nanos };
+            assert_eq!(
+                Duration::try_from(duration),
+                expected,
+                "{}, {}",
+                seconds,
+                nanos
+            );
+        }
+        assert_eq!(
+            Duration::try_from(crate::Duration {
+                seconds: -1,
+                nanos: 0,
+            })
+            .unwrap_err()
+            .to_string(),
+            "failed to convert negative duration: -1s"
+        );
+    }
+
     #[cfg(feature = "std")]
     #[test]
     fn check_timestamp_normalize() {
@@ -505,4 +708,77 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn check_float_field_bits() {
+        use prost::Message;
+
+        #[derive(Clone, PartialEq, Message)]
+        struct Sample {
+            #[prost(double, tag = "1")]
+            double: f64,
+            #[prost(float, tag = "2")]
+            float: f32,
+        }
+
+        assert!(Sample::default().encode_to_vec().is_empty());
+
+        // Negative zero is not the default value of a proto3 field, so it must be encoded.
+        let bits = [
+            (0x8000_0000_0000_0000, 0x8000_0000),
+            (0x0000_0000_0000_0001, 0x0000_0001),
+            (0xFFF8_0000_0000_0001, 0xFFC0_0001),
+        ];
+        for &(double, float) in &bits {
+            let sample = Sample {
+                double: f64::from_bits(double),
+                float: f32::from_bits(float),
+            };
+            assert_eq!(sample.encoded_len(), 14);
+            let decoded = Sample::decode(sample.encode_to_vec().as_slice()).unwrap();
+            assert_eq!(decoded.double.to_bits(), double);
+            assert_eq!(decoded.float.to_bits(), float);
+        }
+    }
+
+    #[test]
+    fn check_char_and_nonzero_fields() {
+        use core::num::{NonZeroU32, NonZeroU64};
+        use prost::Message;
+
+        #[derive(Clone, PartialEq, Message)]
+        struct Sample {
+            #[prost(uint32 = "char", tag = "1")]
+            glyph: char,
+            #[prost(uint64 = "nonzero", optional, tag = "2")]
+            id: ::core::option::Option<NonZeroU64>,
+            #[prost(uint32 = "nonzero", repeated, tag = "3")]
+            ids: Vec<NonZeroU32>,
+        }
+
+        assert!(Sample::default().encode_to_vec().is_empty());
+
+        let sample = Sample {
+            glyph: '€',
+            id: NonZeroU64::new(u64::MAX),
+            ids: vec![NonZeroU32::new(1).unwrap(), NonZeroU32::new(300).unwrap()],
+        };
+        let buf = sample.encode_to_vec();
+        assert_eq!(sample.encoded_len(), buf.len());
+        assert_eq!(Sample::decode(buf.as_slice()).unwrap(), sample);
+
+        // The fields share their wire format with plain integer fields.
+        let plain = Sample::decode(&[0x08, 0xAC, 0x41, 0x10, 0x07][..]).unwrap();
+        assert_eq!(plain.glyph, '\u{20AC}');
+        assert_eq!(plain.id, NonZeroU64::new(7));
+
+        // Zero is rejected by non-zero fields, and surrogates by char fields.
+        for buf in &[
+            &[0x10, 0x00][..],
+            &[0x1A, 0x02, 0x01, 0x00],
+            &[0x08, 0x80, 0xB0, 0x03],
+        ] {
+            assert!(Sample::decode(*buf).is_err());
+        }
+    }
 }
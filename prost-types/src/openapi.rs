@@ -0,0 +1,587 @@
+//! OpenAPI documents describing the REST endpoints of annotated services.
+//!
+//! Methods annotated with `google.api.http` options are mapped to REST endpoints by [gRPC
+//! transcoding][1], which gateways built on the JSON mapping of messages implement. [`OpenApi`]
+//! describes these endpoints in an [OpenAPI 3.1][2] document, given the encoded
+//! `FileDescriptorSet` of the services, as produced by `protoc --include_imports
+//! --descriptor_set_out` or embedded by `prost-build`'s `Config::embed_file_descriptor_set`:
+//!
+//! ```rust,ignore
+//! let document = prost_types::openapi::OpenApi::new("Orders", "1.0.0")
+//!     .generate(gateway::v1::file_descriptor_set())?;
+//! ```
+//!
+//! Each binding of a method is described by an operation, with:
+//!
+//!  * A path parameter for each variable of the path template, named by its field path.
+//!  * A query parameter for each field of the request which is bound to neither the path nor the
+//!    body, unless the body is `*`. Maps, and messages other than the well-known types rendered as
+//!    JSON scalars, such as `google.protobuf.FieldMask`, are not listed.
+//!  * A request body, if the rule has a `body`.
+//!  * A `200` response, whose body is the response message, or its `response_body` field.
+//!
+//! The messages and enums used by the operations are described by the component schemas of the
+//! document, which [`SchemaGenerator`] generates. Methods without `google.api.http` options, and
+//! bindings whose method is not one of the standard HTTP methods, are omitted.
+//!
+//! [1]: https://cloud.google.com/endpoints/docs/grpc/transcoding
+//! [2]: https://spec.openapis.org/oas/v3.1.0
+
+use prost::alloc::collections::BTreeMap;
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec;
+use prost::alloc::vec::Vec;
+use prost::wire::{WireReader, WireValue};
+use prost::{DecodeError, Message};
+
+use crate::api::{HttpRule, PathTemplate, Segment, HTTP_EXTENSION};
+use crate::json_schema::SchemaGenerator;
+use crate::reflect::{DescriptorError, FieldDescriptor, Kind, MessageDescriptor};
+use crate::{prost_struct, DescriptorPool, FileDescriptorSet, Struct, Value};
+
+/// The version of the OpenAPI Specification of the generated documents.
+pub const VERSION: &str = "3.1.0";
+
+/// The HTTP methods which are operations of an OpenAPI path item.
+const OPERATIONS: &[&str] = &[
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Generates OpenAPI documents from the `google.api.http` options of services.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenApi {
+    title: String,
+    version: String,
+}
+
+impl OpenApi {
+    /// Creates a generator of documents with the given title and API version, which make up the
+    /// required `info` of the documents.
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> OpenApi {
+        OpenApi {
+            title: title.into(),
+            version: version.into(),
+        }
+    }
+
+    /// Generates a document describing the annotated methods of the services defined in an
+    /// encoded `FileDescriptorSet`.
+    ///
+    /// The set is read in its encoded form, since the `google.api.http` options are extensions,
+    /// which are dropped when decoding a [`FileDescriptorSet`]. Fails if the set can't be decoded
+    /// or resolved, or if an option is invalid, such as a path template binding a field which the
+    /// request doesn't have.
+    pub fn generate(&self, file_descriptor_set: &[u8]) -> Result<Struct, DescriptorError> {
+        let set = FileDescriptorSet::decode(file_descriptor_set).map_err(decode_error)?;
+        let pool = DescriptorPool::from_file_descriptor_set(&set)?;
+        let mut generator = SchemaGenerator::with_ref_prefix("#/components/schemas/");
+
+        let mut paths = BTreeMap::<String, Struct>::new();
+        for method in annotated_methods(file_descriptor_set).map_err(decode_error)? {
+            let error = |description: String| {
+                DescriptorError::new(format!("method {}: {}", method.full_name, description))
+            };
+            let message = |name: &str| {
+                pool.get_message_by_name(name)
+                    .ok_or_else(|| error(format!("message {} is not defined", name)))
+            };
+            let input = message(&method.input_type)?;
+            let output = message(&method.output_type)?;
+
+            for (index, binding) in method.rule.bindings().enumerate() {
+                let (http_method, path) = match binding.method_and_path() {
+                    Some((http_method, path)) => (http_method.to_ascii_lowercase(), path),
+                    None => continue,
+                };
+                if !OPERATIONS.contains(&http_method.as_str()) {
+                    continue;
+                }
+                let template = PathTemplate::parse(path).map_err(|err| error(err.to_string()))?;
+
+                let mut operation_id = format!("{}_{}", method.service, method.name);
+                if index > 0 {
+                    operation_id.push_str(&format!("_{}", index));
+                }
+                let mut operation = prost_struct! {
+                    "operationId": operation_id,
+                    "tags": vec![method.service.as_str()],
+                };
+                let binding = Binding {
+                    rule: binding,
+                    template: &template,
+                    input: &input,
+                    output: &output,
+                };
+                binding
+                    .describe(&mut generator, &mut operation)
+                    .map_err(error)?;
+
+                paths
+                    .entry(openapi_path(&template))
+                    .or_default()
+                    .insert(http_method, operation);
+            }
+        }
+
+        Ok(prost_struct! {
+            "openapi": VERSION,
+            "info": prost_struct! { "title": self.title.as_str(), "version": self.version.as_str() },
+            "paths": paths.into_iter().collect::<Struct>(),
+            "components": prost_struct! { "schemas": generator.into_definitions() },
+        })
+    }
+}
+
+/// A method with a `google.api.http` option.
+struct AnnotatedMethod {
+    /// The unqualified name of the service.
+    service: String,
+    name: String,
+    full_name: String,
+    input_type: String,
+    output_type: String,
+    rule: HttpRule,
+}
+
+/// Reads the methods with a `google.api.http` option from an encoded `FileDescriptorSet`.
+fn annotated_methods(file_descriptor_set: &[u8]) -> Result<Vec<AnnotatedMethod>, DecodeError> {
+    let mut methods = Vec::new();
+    // `FileDescriptorSet.file`
+    for file in fields(file_descriptor_set, 1) {
+        let file = file?;
+        // `FileDescriptorProto.package`
+        let package = last_string(file, 2)?;
+        // `FileDescriptorProto.service`
+        for service in fields(file, 6) {
+            let service = service?;
+            // `ServiceDescriptorProto.name`
+            let service_name = last_string(service, 1)?;
+            // `ServiceDescriptorProto.method`
+            for method in fields(service, 2) {
+                let method = method?;
+                let mut rule = None;
+                // `MethodDescriptorProto.options`
+                for options in fields(method, 4) {
+                    for http in fields(options?, HTTP_EXTENSION) {
+                        rule.get_or_insert_with(HttpRule::default).merge(http?)?;
+                    }
+                }
+                let rule = match rule {
+                    Some(rule) => rule,
+                    None => continue,
+                };
+                let name = last_string(method, 1)?;
+                let full_name = if package.is_empty() {
+                    format!("{}.{}", service_name, name)
+                } else {
+                    format!("{}.{}.{}", package, service_name, name)
+                };
+                methods.push(AnnotatedMethod {
+                    service: service_name.clone(),
+                    name,
+                    full_name,
+                    // `MethodDescriptorProto.input_type` and `output_type`
+                    input_type: last_string(method, 2)?,
+                    output_type: last_string(method, 3)?,
+                    rule,
+                });
+            }
+        }
+    }
+    Ok(methods)
+}
+
+/// Returns the values of the length-delimited fields with the tag.
+fn fields(buf: &[u8], tag: u32) -> impl Iterator<Item = Result<&[u8], DecodeError>> {
+    WireReader::new(buf).filter_map(move |field| match field {
+        Ok((field_tag, WireValue::LengthDelimited(value))) if field_tag == tag => Some(Ok(value)),
+        Ok(_) => None,
+        Err(err) => Some(Err(err)),
+    })
+}
+
+/// Returns the last value of the string field with the tag, or an empty string.
+fn last_string(buf: &[u8], tag: u32) -> Result<String, DecodeError> {
+    let mut string = String::new();
+    for value in fields(buf, tag) {
+        string = String::from_utf8(value?.to_vec())
+            .map_err(|_| DecodeError::new("invalid string value: data is not UTF-8 encoded"))?;
+    }
+    Ok(string)
+}
+
+fn decode_error(err: DecodeError) -> DescriptorError {
+    DescriptorError::new(format!("failed to decode file descriptor set: {}", err))
+}
+
+/// A binding of a method, which is described by an operation.
+struct Binding<'a> {
+    rule: &'a HttpRule,
+    template: &'a PathTemplate,
+    input: &'a MessageDescriptor,
+    output: &'a MessageDescriptor,
+}
+
+impl Binding<'_> {
+    /// Adds the parameters, request body and responses of the binding to its operation.
+    fn describe(
+        &self,
+        generator: &mut SchemaGenerator,
+        operation: &mut Struct,
+    ) -> Result<(), String> {
+        let mut parameters = Vec::new();
+        for field_path in self.template.variables() {
+            let field = find_field(self.input, field_path)?;
+            parameters.push(prost_struct! {
+                "name": field_path,
+                "in": "path",
+                "required": true,
+                "schema": generator.field(&field),
+            });
+        }
+
+        let body = self.rule.body.as_str();
+        if body != "*" {
+            for field in self.input.fields() {
+                if field.name() == body
+                    || self.template.variables().any(|path| path == field.name())
+                {
+                    continue;
+                }
+                if !is_query_parameter(generator, &field) {
+                    continue;
+                }
+                parameters.push(prost_struct! {
+                    "name": field.name(),
+                    "in": "query",
+                    "schema": generator.field(&field),
+                });
+            }
+        }
+        if !parameters.is_empty() {
+            operation.insert("parameters", parameters);
+        }
+
+        let request = match body {
+            "" => None,
+            "*" => Some(generator.message(self.input)),
+            body => Some(generator.field(&find_field(self.input, body)?)),
+        };
+        if let Some(request) = request {
+            operation.insert(
+                "requestBody",
+                prost_struct! { "required": true, "content": json_content(request) },
+            );
+        }
+
+        let response = match self.rule.response_body.as_str() {
+            "" => generator.message(self.output),
+            response_body => generator.field(&find_field(self.output, response_body)?),
+        };
+        operation.insert(
+            "responses",
+            prost_struct! {
+                "200": prost_struct! { "description": "OK", "content": json_content(response) },
+            },
+        );
+        Ok(())
+    }
+}
+
+/// Finds the field at a path of field names separated by `.`, such as `book.name`.
+fn find_field(message: &MessageDescriptor, field_path: &str) -> Result<FieldDescriptor, String> {
+    let mut message = message.clone();
+    let mut names = field_path.split('.').peekable();
+    loop {
+        let name = names.next().unwrap_or_default();
+        let field = message
+            .get_field_by_name(name)
+            .ok_or_else(|| format!("no field {} in message {}", name, message.full_name()))?;
+        if names.peek().is_none() {
+            return Ok(field);
+        }
+        message = match field.kind() {
+            Kind::Message(nested) if !field.is_list() && !field.is_map() => nested,
+            _ => return Err(format!("field {} is not a message", field_path)),
+        };
+    }
+}
+
+/// Returns `true` if a field can be a query parameter, because its values are JSON scalars.
+fn is_query_parameter(generator: &mut SchemaGenerator, field: &FieldDescriptor) -> bool {
+    if field.is_map() {
+        return false;
+    }
+    match field.kind() {
+        Kind::Message(message) => {
+            let schema = generator.message(&message);
+            match schema["type"].as_str() {
+                Some(ty) => ty != "object" && ty != "array",
+                None => !schema["anyOf"].is_null(),
+            }
+        }
+        _ => true,
+    }
+}
+
+fn json_content(schema: Value) -> Struct {
+    prost_struct! { "application/json": prost_struct! { "schema": schema } }
+}
+
+/// Returns the OpenAPI path of a template, whose variables are named by their field paths.
+fn openapi_path(template: &PathTemplate) -> String {
+    let mut path = String::new();
+    for segment in template.segments() {
+        path.push('/');
+        match segment {
+            Segment::Variable { field_path, .. } => {
+                path.push('{');
+                path.push_str(field_path);
+                path.push('}');
+            }
+            segment => path.push_str(&segment.to_string()),
+        }
+    }
+    if let Some(verb) = template.verb() {
+        path.push(':');
+        path.push_str(verb);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::wire::WireWriter;
+
+    use crate::api::http_rule::Pattern;
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, typed_field};
+    use crate::{
+        DescriptorProto, FileDescriptorProto, MethodDescriptorProto, ServiceDescriptorProto,
+    };
+
+    /// Encodes a method with a `google.api.http` option, which `MethodOptions` can't hold.
+    fn method(name: &str, input: &str, output: &str, rule: &HttpRule) -> Vec<u8> {
+        let mut options = WireWriter::new(Vec::new());
+        options.write_field(
+            HTTP_EXTENSION,
+            WireValue::LengthDelimited(&rule.encode_to_vec()),
+        );
+        let mut method = MethodDescriptorProto {
+            name: Some(name.to_string()),
+            input_type: Some(input.to_string()),
+            output_type: Some(output.to_string()),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        WireWriter::new(&mut method)
+            .write_field(4, WireValue::LengthDelimited(&options.into_inner()));
+        method
+    }
+
+    fn file_descriptor_set() -> Vec<u8> {
+        let book = DescriptorProto {
+            name: Some("Book".to_string()),
+            field: vec![
+                field("name", 1, Label::Optional, Type::String),
+                field("pages", 2, Label::Optional, Type::Int64),
+            ],
+            ..Default::default()
+        };
+        let get_book = DescriptorProto {
+            name: Some("GetBookRequest".to_string()),
+            field: vec![
+                field("name", 1, Label::Optional, Type::String),
+                field("revisions", 2, Label::Repeated, Type::Uint32),
+                typed_field("book", 3, Label::Optional, Type::Message, ".library.Book"),
+            ],
+            ..Default::default()
+        };
+        let update_book = DescriptorProto {
+            name: Some("UpdateBookRequest".to_string()),
+            field: vec![
+                typed_field("book", 1, Label::Optional, Type::Message, ".library.Book"),
+                field("validate_only", 2, Label::Optional, Type::Bool),
+            ],
+            ..Default::default()
+        };
+        let file = FileDescriptorProto {
+            name: Some("library.proto".to_string()),
+            package: Some("library".to_string()),
+            message_type: vec![book, get_book, update_book],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Library".to_string()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("DeleteBook".to_string()),
+                    input_type: Some(".library.GetBookRequest".to_string()),
+                    output_type: Some(".library.Book".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".to_string()),
+            ..Default::default()
+        };
+
+        let get = HttpRule {
+            pattern: Some(Pattern::Get("/v1/{name=shelves/*/books/*}".to_string())),
+            response_body: "name".to_string(),
+            additional_bindings: vec![HttpRule {
+                pattern: Some(Pattern::Post(
+                    "/v1/{name=shelves/*/books/*}:get".to_string(),
+                )),
+                body: "*".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let update = HttpRule {
+            pattern: Some(Pattern::Patch(
+                "/v1/{book.name=shelves/*/books/*}".to_string(),
+            )),
+            body: "book".to_string(),
+            ..Default::default()
+        };
+
+        let mut service = file.service[0].encode_to_vec();
+        let mut writer = WireWriter::new(&mut service);
+        for method in &[
+            method("GetBook", ".library.GetBookRequest", ".library.Book", &get),
+            method(
+                "UpdateBook",
+                ".library.UpdateBookRequest",
+                ".library.Book",
+                &update,
+            ),
+        ] {
+            writer.write_field(2, WireValue::LengthDelimited(method));
+        }
+        let mut file = FileDescriptorProto {
+            service: Vec::new(),
+            ..file
+        }
+        .encode_to_vec();
+        WireWriter::new(&mut file).write_field(6, WireValue::LengthDelimited(&service));
+        let mut set = Vec::new();
+        WireWriter::new(&mut set).write_field(1, WireValue::LengthDelimited(&file));
+        set
+    }
+
+    #[test]
+    fn generate() {
+        let document = OpenApi::new("Library", "1.0.0")
+            .generate(&file_descriptor_set())
+            .unwrap();
+
+        assert_eq!(document["openapi"].as_str(), Some(VERSION));
+        assert_eq!(document["info"]["title"].as_str(), Some("Library"));
+        let paths = document["paths"].as_struct().unwrap();
+        assert_eq!(
+            paths
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/v1/{book.name}", "/v1/{name}", "/v1/{name}:get"]
+        );
+
+        let get = &paths["/v1/{name}"]["get"];
+        assert_eq!(get["operationId"].as_str(), Some("Library_GetBook"));
+        assert_eq!(get["tags"], vec!["Library"].into());
+        let parameters = &get["parameters"];
+        assert_eq!(parameters.as_list().unwrap().len(), 2);
+        assert_eq!(
+            parameters[0],
+            prost_struct! {
+                "name": "name",
+                "in": "path",
+                "required": true,
+                "schema": prost_struct! { "type": "string" },
+            }
+            .into()
+        );
+        assert_eq!(parameters[1]["name"].as_str(), Some("revisions"));
+        assert_eq!(parameters[1]["schema"]["type"].as_str(), Some("array"));
+        assert!(get["requestBody"].is_null());
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"],
+            prost_struct! { "type": "string" }.into()
+        );
+
+        let post = &paths["/v1/{name}:get"]["post"];
+        assert_eq!(post["operationId"].as_str(), Some("Library_GetBook_1"));
+        assert!(post["parameters"][1].is_null());
+        assert_eq!(
+            post["requestBody"]["content"]["application/json"]["schema"]["$ref"].as_str(),
+            Some("#/components/schemas/library.GetBookRequest")
+        );
+        assert_eq!(
+            post["responses"]["200"]["content"]["application/json"]["schema"]["$ref"].as_str(),
+            Some("#/components/schemas/library.Book")
+        );
+
+        let patch = &paths["/v1/{book.name}"]["patch"];
+        let parameters = &patch["parameters"];
+        assert_eq!(parameters[0]["name"].as_str(), Some("book.name"));
+        assert_eq!(parameters[1]["name"].as_str(), Some("validate_only"));
+        assert_eq!(parameters.as_list().unwrap().len(), 2);
+        assert_eq!(
+            patch["requestBody"]["content"]["application/json"]["schema"]["$ref"].as_str(),
+            Some("#/components/schemas/library.Book")
+        );
+
+        let schemas = document["components"]["schemas"].as_struct().unwrap();
+        assert_eq!(
+            schemas
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["library.Book", "library.GetBookRequest"]
+        );
+    }
+
+    #[test]
+    fn invalid_binding() {
+        let set = FileDescriptorSet::decode(&file_descriptor_set()[..]).unwrap();
+        assert!(OpenApi::new("Library", "1.0.0")
+            .generate(&set.encode_to_vec())
+            .unwrap()["paths"]
+            .as_struct()
+            .unwrap()
+            .is_empty());
+
+        let rule = HttpRule {
+            pattern: Some(Pattern::Get("/v1/{missing}".to_string())),
+            ..Default::default()
+        };
+        let mut file = FileDescriptorProto {
+            name: Some("library.proto".to_string()),
+            message_type: vec![DescriptorProto {
+                name: Some("Empty".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let mut service = ServiceDescriptorProto {
+            name: Some("Library".to_string()),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        WireWriter::new(&mut service).write_field(
+            2,
+            WireValue::LengthDelimited(&method("Get", ".Empty", ".Empty", &rule)),
+        );
+        WireWriter::new(&mut file).write_field(6, WireValue::LengthDelimited(&service));
+        let mut set = Vec::new();
+        WireWriter::new(&mut set).write_field(1, WireValue::LengthDelimited(&file));
+
+        let err = OpenApi::new("Library", "1.0.0").generate(&set).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid descriptor: method Library.Get: no field missing in message Empty"
+        );
+    }
+}
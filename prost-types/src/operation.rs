@@ -0,0 +1,244 @@
+//! Packing and unpacking the response and metadata of `google.longrunning.Operation`.
+
+use prost::alloc::format;
+use prost::alloc::string::String;
+use prost::{DecodeError, Name};
+
+use crate::longrunning::{operation, Operation};
+use crate::rpc::Status;
+use crate::Any;
+
+/// The state of a long-running operation, with its response and metadata unpacked.
+#[derive(Clone, Debug, PartialEq)]
+pub enum OperationResult<T, M> {
+    /// The operation is in progress.
+    Running {
+        /// The metadata of the operation, if any.
+        metadata: Option<M>,
+    },
+    /// The operation completed successfully.
+    Succeeded {
+        /// The response of the operation.
+        response: T,
+        /// The metadata of the operation, if any.
+        metadata: Option<M>,
+    },
+    /// The operation failed or was cancelled.
+    Failed {
+        /// The error of the operation.
+        error: Status,
+        /// The metadata of the operation, if any.
+        metadata: Option<M>,
+    },
+}
+
+impl<T, M> OperationResult<T, M>
+where
+    T: Name,
+    M: Name,
+{
+    /// Packs the result into an operation with the name.
+    pub fn into_operation<S>(self, name: S) -> Operation
+    where
+        S: Into<String>,
+    {
+        let (done, result, metadata) = match self {
+            OperationResult::Running { metadata } => (false, None, metadata),
+            OperationResult::Succeeded { response, metadata } => (
+                true,
+                Some(operation::Result::Response(Any::pack(&response))),
+                metadata,
+            ),
+            OperationResult::Failed { error, metadata } => {
+                (true, Some(operation::Result::Error(error)), metadata)
+            }
+        };
+        Operation {
+            name: name.into(),
+            metadata: metadata.as_ref().map(Any::pack),
+            done,
+            result,
+        }
+    }
+}
+
+impl Operation {
+    /// Creates an operation which is in progress, without metadata.
+    pub fn new<S>(name: S) -> Operation
+    where
+        S: Into<String>,
+    {
+        Operation {
+            name: name.into(),
+            ..Operation::default()
+        }
+    }
+
+    /// Packs the metadata of the operation.
+    pub fn set_metadata<M>(&mut self, metadata: &M)
+    where
+        M: Name,
+    {
+        self.metadata = Some(Any::pack(metadata));
+    }
+
+    /// Marks the operation as completed successfully, packing its response.
+    pub fn set_response<T>(&mut self, response: &T)
+    where
+        T: Name,
+    {
+        self.done = true;
+        self.result = Some(operation::Result::Response(Any::pack(response)));
+    }
+
+    /// Marks the operation as failed.
+    pub fn set_error(&mut self, error: Status) {
+        self.done = true;
+        self.result = Some(operation::Result::Error(error));
+    }
+
+    /// Unpacks the metadata of the operation, or returns `None` if it has no metadata.
+    ///
+    /// Fails if the metadata is not a valid `M`.
+    pub fn metadata<M>(&self) -> Result<Option<M>, DecodeError>
+    where
+        M: Name + Default,
+    {
+        self.metadata.as_ref().map(Any::unpack).transpose()
+    }
+
+    /// Unpacks the response of the operation, or returns `None` if it is in progress or failed.
+    ///
+    /// Fails if the response is not a valid `T`.
+    pub fn response<T>(&self) -> Result<Option<T>, DecodeError>
+    where
+        T: Name + Default,
+    {
+        match &self.result {
+            Some(operation::Result::Response(response)) if self.done => response.unpack().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the error of the operation, or `None` if it is in progress or succeeded.
+    pub fn error(&self) -> Option<&Status> {
+        match &self.result {
+            Some(operation::Result::Error(error)) if self.done => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Unpacks the state of the operation.
+    ///
+    /// Fails if the response or metadata are not valid `T` and `M` messages, or if the operation
+    /// is done without a result.
+    pub fn result<T, M>(&self) -> Result<OperationResult<T, M>, DecodeError>
+    where
+        T: Name + Default,
+        M: Name + Default,
+    {
+        let metadata = self.metadata()?;
+        if !self.done {
+            return Ok(OperationResult::Running { metadata });
+        }
+        match &self.result {
+            Some(operation::Result::Response(response)) => Ok(OperationResult::Succeeded {
+                response: response.unpack()?,
+                metadata,
+            }),
+            Some(operation::Result::Error(error)) => Ok(OperationResult::Failed {
+                error: error.clone(),
+                metadata,
+            }),
+            None => Err(DecodeError::new(format!(
+                "operation {} is done without a result",
+                self.name
+            ))),
+        }
+    }
+}
+
+impl Status {
+    /// Creates a status with the code, which should be a `google.rpc.Code` value, and message.
+    pub fn new<S>(code: i32, message: S) -> Status
+    where
+        S: Into<String>,
+    {
+        Status {
+            code,
+            message: message.into(),
+            details: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Duration, Timestamp};
+
+    type Outcome = OperationResult<Duration, Timestamp>;
+
+    #[test]
+    fn round_trip() {
+        let started = Timestamp {
+            seconds: 10,
+            nanos: 0,
+        };
+        let elapsed = Duration {
+            seconds: 5,
+            nanos: 0,
+        };
+
+        let mut operation = Operation::new("operations/1");
+        assert_eq!(
+            operation.result::<Duration, Timestamp>().unwrap(),
+            Outcome::Running { metadata: None }
+        );
+        operation.set_metadata(&started);
+        assert_eq!(operation.metadata().unwrap(), Some(started.clone()));
+        assert_eq!(operation.response::<Duration>().unwrap(), None);
+
+        operation.set_response(&elapsed);
+        let result = operation.result().unwrap();
+        assert_eq!(
+            result,
+            Outcome::Succeeded {
+                response: elapsed.clone(),
+                metadata: Some(started.clone()),
+            }
+        );
+        assert_eq!(result.into_operation("operations/1"), operation);
+        assert_eq!(operation.response().unwrap(), Some(elapsed));
+        assert_eq!(operation.error(), None);
+
+        operation.set_error(Status::new(1, "cancelled"));
+        assert_eq!(operation.error(), Some(&Status::new(1, "cancelled")));
+        assert_eq!(operation.response::<Duration>().unwrap(), None);
+        assert_eq!(
+            operation.result().unwrap(),
+            Outcome::Failed {
+                error: Status::new(1, "cancelled"),
+                metadata: Some(started),
+            }
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let mut operation = Operation::new("operations/2");
+        operation.set_metadata(&Duration::default());
+        assert!(operation.result::<Duration, Timestamp>().is_err());
+
+        let mut operation = Operation::new("operations/3");
+        operation.done = true;
+        assert_eq!(
+            operation
+                .result::<Duration, Timestamp>()
+                .unwrap_err()
+                .to_string(),
+            "failed to decode Protobuf message: operation operations/3 is done without a result"
+        );
+    }
+}
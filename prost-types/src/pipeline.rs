@@ -0,0 +1,322 @@
+//! Asynchronous processing of decoded messages.
+//!
+//! Services commonly check and complete a message after decoding it, before handling it: fields
+//! are validated, references to other entities are resolved against a cache, and defaults are
+//! filled in from configuration. An [`AsyncMessageHook`] implements one such step for a message
+//! type, and a [`Pipeline`] runs a sequence of hooks, in order, on each message it decodes:
+//!
+//! ```rust,ignore
+//! struct ResolveCustomer(CustomerCache);
+//!
+//! impl AsyncMessageHook<Order> for ResolveCustomer {
+//!     type Error = LookupError;
+//!
+//!     fn run<'a>(&'a self, order: &'a mut Order) -> BoxFuture<'a, Result<(), LookupError>> {
+//!         Box::pin(async move {
+//!             order.customer = Some(self.0.get(&order.customer_id).await?);
+//!             Ok(())
+//!         })
+//!     }
+//! }
+//!
+//! let pipeline = Pipeline::new()
+//!     .hook(ValidateOrder)
+//!     .hook(ResolveCustomer(cache));
+//! let order = pipeline.decode(body).await?;
+//! ```
+//!
+//! Messages opt in by implementing [`Pipelined`], which `prost-build` generates for the messages
+//! matched by `Config::message_pipeline`. The hooks are not run on nested messages.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+
+use bytes::Buf;
+use prost::alloc::boxed::Box;
+use prost::alloc::vec::Vec;
+use prost::{DecodeError, Message};
+
+/// A boxed future, as returned by [`AsyncMessageHook::run`].
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A message type which can be processed by a [`Pipeline`].
+pub trait Pipelined: Message + Default + Send + 'static {
+    /// The fully-qualified Protobuf name of the message, used in errors.
+    const FULL_NAME: &'static str;
+}
+
+/// A processing step for a message type.
+pub trait AsyncMessageHook<M>: Send + Sync {
+    /// The error returned when the message is rejected.
+    type Error;
+
+    /// Returns the name of the hook, used in errors.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+
+    /// Checks or updates the message.
+    fn run<'a>(&'a self, message: &'a mut M) -> BoxFuture<'a, Result<(), Self::Error>>;
+}
+
+/// The error returned by a [`Pipeline`].
+#[derive(Debug)]
+pub enum PipelineError<E> {
+    /// The message could not be decoded.
+    Decode {
+        /// The fully-qualified name of the message.
+        message: &'static str,
+        /// The decoding error.
+        error: DecodeError,
+    },
+    /// A hook rejected the message.
+    Hook {
+        /// The fully-qualified name of the message.
+        message: &'static str,
+        /// The name of the hook.
+        hook: &'static str,
+        /// The error returned by the hook.
+        error: E,
+    },
+}
+
+impl<E> fmt::Display for PipelineError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipelineError::Decode { message, error } => {
+                write!(f, "failed to decode {}: {}", message, error)
+            }
+            PipelineError::Hook {
+                message,
+                hook,
+                error,
+            } => write!(f, "{} rejected by {}: {}", message, hook, error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for PipelineError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PipelineError::Decode { error, .. } => Some(error),
+            PipelineError::Hook { error, .. } => Some(error),
+        }
+    }
+}
+
+/// A sequence of hooks run on each message, in the order they were added.
+pub struct Pipeline<M, E> {
+    hooks: Vec<Box<dyn AsyncMessageHook<M, Error = E>>>,
+}
+
+impl<M, E> Pipeline<M, E>
+where
+    M: Pipelined,
+{
+    /// Creates a pipeline without hooks.
+    pub fn new() -> Pipeline<M, E> {
+        Pipeline { hooks: Vec::new() }
+    }
+
+    /// Appends a hook to the pipeline.
+    pub fn hook<H>(mut self, hook: H) -> Pipeline<M, E>
+    where
+        H: AsyncMessageHook<M, Error = E> + 'static,
+    {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Runs the hooks on the message, stopping at the first error.
+    pub async fn run(&self, message: &mut M) -> Result<(), PipelineError<E>> {
+        for hook in &self.hooks {
+            if let Err(error) = hook.run(message).await {
+                return Err(PipelineError::Hook {
+                    message: M::FULL_NAME,
+                    hook: hook.name(),
+                    error,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a message from the buffer, and runs the hooks on it.
+    pub async fn decode<B>(&self, buf: B) -> Result<M, PipelineError<E>>
+    where
+        B: Buf,
+    {
+        let mut message = M::decode(buf).map_err(|error| PipelineError::Decode {
+            message: M::FULL_NAME,
+            error,
+        })?;
+        self.run(&mut message).await?;
+        Ok(message)
+    }
+}
+
+impl<M, E> Default for Pipeline<M, E>
+where
+    M: Pipelined,
+{
+    fn default() -> Pipeline<M, E> {
+        Pipeline::new()
+    }
+}
+
+impl<M, E> fmt::Debug for Pipeline<M, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pipeline")
+            .field(
+                "hooks",
+                &self
+                    .hooks
+                    .iter()
+                    .map(|hook| hook.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+    use prost::alloc::string::{String, ToString};
+
+    use crate::Duration;
+
+    impl Pipelined for Duration {
+        const FULL_NAME: &'static str = "google.protobuf.Duration";
+    }
+
+    /// Polls the future until it completes, without a runtime.
+    fn block_on<F>(future: F) -> F::Output
+    where
+        F: Future,
+    {
+        fn raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(core::ptr::null(), &VTABLE)
+        }
+
+        // Safety: the waker's functions do nothing, so they uphold the `RawWaker` contract.
+        let waker = unsafe { Waker::from_raw(raw_waker()) };
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// A future which is pending when first polled, like a lookup in a remote cache.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                Poll::Pending
+            }
+        }
+    }
+
+    struct Validate;
+
+    impl AsyncMessageHook<Duration> for Validate {
+        type Error = String;
+
+        fn name(&self) -> &'static str {
+            "validate"
+        }
+
+        fn run<'a>(&'a self, duration: &'a mut Duration) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                if duration.seconds < 0 {
+                    Err("negative duration".to_string())
+                } else {
+                    Ok(())
+                }
+            })
+        }
+    }
+
+    struct RoundUp;
+
+    impl AsyncMessageHook<Duration> for RoundUp {
+        type Error = String;
+
+        fn run<'a>(&'a self, duration: &'a mut Duration) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                YieldOnce(false).await;
+                if duration.nanos > 0 {
+                    duration.seconds += 1;
+                    duration.nanos = 0;
+                }
+                Ok(())
+            })
+        }
+    }
+
+    #[test]
+    fn pipeline() {
+        let pipeline = Pipeline::new().hook(Validate).hook(RoundUp);
+
+        let encoded = Duration {
+            seconds: 1,
+            nanos: 5,
+        }
+        .encode_to_vec();
+        let decoded = block_on(pipeline.decode(&encoded[..])).unwrap();
+        assert_eq!(
+            decoded,
+            Duration {
+                seconds: 2,
+                nanos: 0,
+            }
+        );
+
+        let mut duration = Duration {
+            seconds: -1,
+            nanos: 5,
+        };
+        let error = block_on(pipeline.run(&mut duration)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "google.protobuf.Duration rejected by validate: negative duration"
+        );
+        assert_eq!(duration.nanos, 5);
+
+        let error = block_on(pipeline.decode(&[0x08][..])).unwrap_err();
+        assert!(matches!(error, PipelineError::Decode { .. }), "{:?}", error);
+
+        assert_eq!(
+            format!("{:?}", pipeline),
+            format!(
+                "Pipeline {{ hooks: [\"validate\", {:?}] }}",
+                core::any::type_name::<RoundUp>()
+            )
+        );
+    }
+}
@@ -1,12 +1,12 @@
 /// The protocol compiler can output a FileDescriptorSet containing the .proto
 /// files it parses.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct FileDescriptorSet {
     #[prost(message, repeated, tag="1")]
     pub file: ::prost::alloc::vec::Vec<FileDescriptorProto>,
 }
 /// Describes a complete .proto file.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct FileDescriptorProto {
     /// file name, relative to root of source tree
     #[prost(string, optional, tag="1")]
@@ -47,7 +47,7 @@ pub struct FileDescriptorProto {
     pub syntax: ::core::option::Option<::prost::alloc::string::String>,
 }
 /// Describes a message type.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct DescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -74,7 +74,7 @@ pub struct DescriptorProto {
 }
 /// Nested message and enum types in `DescriptorProto`.
 pub mod descriptor_proto {
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct ExtensionRange {
         /// Inclusive.
         #[prost(int32, optional, tag="1")]
@@ -88,7 +88,7 @@ pub mod descriptor_proto {
     /// Range of reserved tag numbers. Reserved tag numbers may not be used by
     /// fields or extension ranges in the same message. Reserved ranges may
     /// not overlap.
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct ReservedRange {
         /// Inclusive.
         #[prost(int32, optional, tag="1")]
@@ -98,14 +98,14 @@ pub mod descriptor_proto {
         pub end: ::core::option::Option<i32>,
     }
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct ExtensionRangeOptions {
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 /// Describes a field within a message.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct FieldDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -173,7 +173,7 @@ pub struct FieldDescriptorProto {
 }
 /// Nested message and enum types in `FieldDescriptorProto`.
 pub mod field_descriptor_proto {
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum Type {
         /// 0 is reserved for errors.
@@ -209,7 +209,79 @@ pub mod field_descriptor_proto {
         /// Uses ZigZag encoding.
         Sint64 = 18,
     }
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    impl Type {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("TYPE_DOUBLE", 1),
+            ("TYPE_FLOAT", 2),
+            ("TYPE_INT64", 3),
+            ("TYPE_UINT64", 4),
+            ("TYPE_INT32", 5),
+            ("TYPE_FIXED64", 6),
+            ("TYPE_FIXED32", 7),
+            ("TYPE_BOOL", 8),
+            ("TYPE_STRING", 9),
+            ("TYPE_GROUP", 10),
+            ("TYPE_MESSAGE", 11),
+            ("TYPE_BYTES", 12),
+            ("TYPE_UINT32", 13),
+            ("TYPE_ENUM", 14),
+            ("TYPE_SFIXED32", 15),
+            ("TYPE_SFIXED64", 16),
+            ("TYPE_SINT32", 17),
+            ("TYPE_SINT64", 18),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Double => "TYPE_DOUBLE",
+                Self::Float => "TYPE_FLOAT",
+                Self::Int64 => "TYPE_INT64",
+                Self::Uint64 => "TYPE_UINT64",
+                Self::Int32 => "TYPE_INT32",
+                Self::Fixed64 => "TYPE_FIXED64",
+                Self::Fixed32 => "TYPE_FIXED32",
+                Self::Bool => "TYPE_BOOL",
+                Self::String => "TYPE_STRING",
+                Self::Group => "TYPE_GROUP",
+                Self::Message => "TYPE_MESSAGE",
+                Self::Bytes => "TYPE_BYTES",
+                Self::Uint32 => "TYPE_UINT32",
+                Self::Enum => "TYPE_ENUM",
+                Self::Sfixed32 => "TYPE_SFIXED32",
+                Self::Sfixed64 => "TYPE_SFIXED64",
+                Self::Sint32 => "TYPE_SINT32",
+                Self::Sint64 => "TYPE_SINT64",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "TYPE_DOUBLE" => ::core::option::Option::Some(Self::Double),
+                "TYPE_FLOAT" => ::core::option::Option::Some(Self::Float),
+                "TYPE_INT64" => ::core::option::Option::Some(Self::Int64),
+                "TYPE_UINT64" => ::core::option::Option::Some(Self::Uint64),
+                "TYPE_INT32" => ::core::option::Option::Some(Self::Int32),
+                "TYPE_FIXED64" => ::core::option::Option::Some(Self::Fixed64),
+                "TYPE_FIXED32" => ::core::option::Option::Some(Self::Fixed32),
+                "TYPE_BOOL" => ::core::option::Option::Some(Self::Bool),
+                "TYPE_STRING" => ::core::option::Option::Some(Self::String),
+                "TYPE_GROUP" => ::core::option::Option::Some(Self::Group),
+                "TYPE_MESSAGE" => ::core::option::Option::Some(Self::Message),
+                "TYPE_BYTES" => ::core::option::Option::Some(Self::Bytes),
+                "TYPE_UINT32" => ::core::option::Option::Some(Self::Uint32),
+                "TYPE_ENUM" => ::core::option::Option::Some(Self::Enum),
+                "TYPE_SFIXED32" => ::core::option::Option::Some(Self::Sfixed32),
+                "TYPE_SFIXED64" => ::core::option::Option::Some(Self::Sfixed64),
+                "TYPE_SINT32" => ::core::option::Option::Some(Self::Sint32),
+                "TYPE_SINT64" => ::core::option::Option::Some(Self::Sint64),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum Label {
         /// 0 is reserved for errors
@@ -217,9 +289,36 @@ pub mod field_descriptor_proto {
         Required = 2,
         Repeated = 3,
     }
+    impl Label {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("LABEL_OPTIONAL", 1),
+            ("LABEL_REQUIRED", 2),
+            ("LABEL_REPEATED", 3),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Optional => "LABEL_OPTIONAL",
+                Self::Required => "LABEL_REQUIRED",
+                Self::Repeated => "LABEL_REPEATED",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "LABEL_OPTIONAL" => ::core::option::Option::Some(Self::Optional),
+                "LABEL_REQUIRED" => ::core::option::Option::Some(Self::Required),
+                "LABEL_REPEATED" => ::core::option::Option::Some(Self::Repeated),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
 }
 /// Describes a oneof.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct OneofDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -227,7 +326,7 @@ pub struct OneofDescriptorProto {
     pub options: ::core::option::Option<OneofOptions>,
 }
 /// Describes an enum type.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct EnumDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -253,7 +352,7 @@ pub mod enum_descriptor_proto {
     /// Note that this is distinct from DescriptorProto.ReservedRange in that it
     /// is inclusive such that it can appropriately represent the entire int32
     /// domain.
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct EnumReservedRange {
         /// Inclusive.
         #[prost(int32, optional, tag="1")]
@@ -264,7 +363,7 @@ pub mod enum_descriptor_proto {
     }
 }
 /// Describes a value within an enum.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct EnumValueDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -274,7 +373,7 @@ pub struct EnumValueDescriptorProto {
     pub options: ::core::option::Option<EnumValueOptions>,
 }
 /// Describes a service.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct ServiceDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -284,7 +383,7 @@ pub struct ServiceDescriptorProto {
     pub options: ::core::option::Option<ServiceOptions>,
 }
 /// Describes a method of a service.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct MethodDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -335,7 +434,7 @@ pub struct MethodDescriptorProto {
 //   If this turns out to be popular, a web service will be set up
 //   to automatically assign option numbers.
 
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct FileOptions {
     /// Sets the Java package where classes generated from this .proto will be
     /// placed.  By default, the proto package is used, but this is often
@@ -447,7 +546,7 @@ pub struct FileOptions {
 /// Nested message and enum types in `FileOptions`.
 pub mod file_options {
     /// Generated classes can be optimized for speed or code size.
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum OptimizeMode {
         /// Generate complete code for parsing, serialization,
@@ -459,8 +558,35 @@ pub mod file_options {
         /// Generate code using MessageLite and the lite runtime.
         LiteRuntime = 3,
     }
+    impl OptimizeMode {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("SPEED", 1),
+            ("CODE_SIZE", 2),
+            ("LITE_RUNTIME", 3),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Speed => "SPEED",
+                Self::CodeSize => "CODE_SIZE",
+                Self::LiteRuntime => "LITE_RUNTIME",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "SPEED" => ::core::option::Option::Some(Self::Speed),
+                "CODE_SIZE" => ::core::option::Option::Some(Self::CodeSize),
+                "LITE_RUNTIME" => ::core::option::Option::Some(Self::LiteRuntime),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct MessageOptions {
     /// Set true to use the old proto1 MessageSet wire format for extensions.
     /// This is provided for backwards-compatibility with the MessageSet wire
@@ -520,7 +646,7 @@ pub struct MessageOptions {
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct FieldOptions {
     /// The ctype option instructs the C++ code generator to use a different
     /// representation of the field than it normally would.  See the specific
@@ -593,7 +719,7 @@ pub struct FieldOptions {
 }
 /// Nested message and enum types in `FieldOptions`.
 pub mod field_options {
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum CType {
         /// Default mode.
@@ -601,7 +727,34 @@ pub mod field_options {
         Cord = 1,
         StringPiece = 2,
     }
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    impl CType {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("STRING", 0),
+            ("CORD", 1),
+            ("STRING_PIECE", 2),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::String => "STRING",
+                Self::Cord => "CORD",
+                Self::StringPiece => "STRING_PIECE",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "STRING" => ::core::option::Option::Some(Self::String),
+                "CORD" => ::core::option::Option::Some(Self::Cord),
+                "STRING_PIECE" => ::core::option::Option::Some(Self::StringPiece),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum JsType {
         /// Use the default type.
@@ -611,14 +764,41 @@ pub mod field_options {
         /// Use JavaScript numbers.
         JsNumber = 2,
     }
+    impl JsType {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("JS_NORMAL", 0),
+            ("JS_STRING", 1),
+            ("JS_NUMBER", 2),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::JsNormal => "JS_NORMAL",
+                Self::JsString => "JS_STRING",
+                Self::JsNumber => "JS_NUMBER",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "JS_NORMAL" => ::core::option::Option::Some(Self::JsNormal),
+                "JS_STRING" => ::core::option::Option::Some(Self::JsString),
+                "JS_NUMBER" => ::core::option::Option::Some(Self::JsNumber),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct OneofOptions {
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct EnumOptions {
     /// Set this option to true to allow mapping different tag names to the same
     /// value.
@@ -634,7 +814,7 @@ pub struct EnumOptions {
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct EnumValueOptions {
     /// Is this enum value deprecated?
     /// Depending on the target platform, this can emit Deprecated annotations
@@ -646,7 +826,7 @@ pub struct EnumValueOptions {
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct ServiceOptions {
     // Note:  Field numbers 1 through 32 are reserved for Google's internal RPC
     //   framework.  We apologize for hoarding these numbers to ourselves, but
@@ -663,7 +843,7 @@ pub struct ServiceOptions {
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct MethodOptions {
     // Note:  Field numbers 1 through 32 are reserved for Google's internal RPC
     //   framework.  We apologize for hoarding these numbers to ourselves, but
@@ -687,7 +867,7 @@ pub mod method_options {
     /// Is this method side-effect-free (or safe in HTTP parlance), or idempotent,
     /// or neither? HTTP based RPC implementation may choose GET verb for safe
     /// methods, and PUT verb for idempotent methods instead of the default POST.
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum IdempotencyLevel {
         IdempotencyUnknown = 0,
@@ -696,6 +876,33 @@ pub mod method_options {
         /// idempotent, but may have side effects
         Idempotent = 2,
     }
+    impl IdempotencyLevel {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("IDEMPOTENCY_UNKNOWN", 0),
+            ("NO_SIDE_EFFECTS", 1),
+            ("IDEMPOTENT", 2),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::IdempotencyUnknown => "IDEMPOTENCY_UNKNOWN",
+                Self::NoSideEffects => "NO_SIDE_EFFECTS",
+                Self::Idempotent => "IDEMPOTENT",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "IDEMPOTENCY_UNKNOWN" => ::core::option::Option::Some(Self::IdempotencyUnknown),
+                "NO_SIDE_EFFECTS" => ::core::option::Option::Some(Self::NoSideEffects),
+                "IDEMPOTENT" => ::core::option::Option::Some(Self::Idempotent),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
 }
 /// A message representing a option the parser does not recognize. This only
 /// appears in options protos created by the compiler::Parser class.
@@ -703,7 +910,7 @@ pub mod method_options {
 /// options protos in descriptor objects (e.g. returned by Descriptor::options(),
 /// or produced by Descriptor::CopyTo()) will never have UninterpretedOptions
 /// in them.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct UninterpretedOption {
     #[prost(message, repeated, tag="2")]
     pub name: ::prost::alloc::vec::Vec<uninterpreted_option::NamePart>,
@@ -729,7 +936,7 @@ pub mod uninterpreted_option {
     /// extension (denoted with parentheses in options specs in .proto files).
     /// E.g.,{ ["foo", false], ["bar.baz", true], ["qux", false] } represents
     /// "foo.(bar.baz).qux".
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct NamePart {
         #[prost(string, required, tag="1")]
         pub name_part: ::prost::alloc::string::String,
@@ -742,7 +949,7 @@ pub mod uninterpreted_option {
 
 /// Encapsulates information about the original source file from which a
 /// FileDescriptorProto was generated.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct SourceCodeInfo {
     /// A Location identifies a piece of source code in a .proto file which
     /// corresponds to a particular definition.  This information is intended
@@ -792,7 +999,7 @@ pub struct SourceCodeInfo {
 }
 /// Nested message and enum types in `SourceCodeInfo`.
 pub mod source_code_info {
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct Location {
         /// Identifies which part of the FileDescriptorProto was defined at this
         /// location.
@@ -884,7 +1091,7 @@ pub mod source_code_info {
 /// Describes the relationship between generated code and its original source
 /// file. A GeneratedCodeInfo message is associated with only one generated
 /// source file, but may contain references to different source .proto files.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct GeneratedCodeInfo {
     /// An Annotation connects some span of text in generated code to an element
     /// of its generating .proto file.
@@ -893,7 +1100,7 @@ pub struct GeneratedCodeInfo {
 }
 /// Nested message and enum types in `GeneratedCodeInfo`.
 pub mod generated_code_info {
-    #[derive(Clone, PartialEq, ::prost::Message)]
+    #[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
     pub struct Annotation {
         /// Identifies the element in the original source .proto file. This field
         /// is formatted the same as SourceCodeInfo.Location.path.
@@ -996,7 +1203,7 @@ pub mod generated_code_info {
 ///       "value": "1.212s"
 ///     }
 ///
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Any {
     /// A URL/resource name that uniquely identifies the type of the serialized
     /// protocol buffer message. This string must contain at least
@@ -1034,7 +1241,7 @@ pub struct Any {
 }
 /// `SourceContext` represents information about the source of a
 /// protobuf element, like the file in which it is defined.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct SourceContext {
     /// The path-qualified name of the .proto file that contained the associated
     /// protobuf element.  For example: `"google/protobuf/source_context.proto"`.
@@ -1042,7 +1249,7 @@ pub struct SourceContext {
     pub file_name: ::prost::alloc::string::String,
 }
 /// A protocol buffer message type.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Type {
     /// The fully qualified message name.
     #[prost(string, tag="1")]
@@ -1064,7 +1271,7 @@ pub struct Type {
     pub syntax: i32,
 }
 /// A single field of a message type.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Field {
     /// The field type.
     #[prost(enumeration="field::Kind", tag="1")]
@@ -1102,7 +1309,7 @@ pub struct Field {
 /// Nested message and enum types in `Field`.
 pub mod field {
     /// Basic field types.
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum Kind {
         /// Field type unknown.
@@ -1144,8 +1351,83 @@ pub mod field {
         /// Field type sint64.
         TypeSint64 = 18,
     }
+    impl Kind {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("TYPE_UNKNOWN", 0),
+            ("TYPE_DOUBLE", 1),
+            ("TYPE_FLOAT", 2),
+            ("TYPE_INT64", 3),
+            ("TYPE_UINT64", 4),
+            ("TYPE_INT32", 5),
+            ("TYPE_FIXED64", 6),
+            ("TYPE_FIXED32", 7),
+            ("TYPE_BOOL", 8),
+            ("TYPE_STRING", 9),
+            ("TYPE_GROUP", 10),
+            ("TYPE_MESSAGE", 11),
+            ("TYPE_BYTES", 12),
+            ("TYPE_UINT32", 13),
+            ("TYPE_ENUM", 14),
+            ("TYPE_SFIXED32", 15),
+            ("TYPE_SFIXED64", 16),
+            ("TYPE_SINT32", 17),
+            ("TYPE_SINT64", 18),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::TypeUnknown => "TYPE_UNKNOWN",
+                Self::TypeDouble => "TYPE_DOUBLE",
+                Self::TypeFloat => "TYPE_FLOAT",
+                Self::TypeInt64 => "TYPE_INT64",
+                Self::TypeUint64 => "TYPE_UINT64",
+                Self::TypeInt32 => "TYPE_INT32",
+                Self::TypeFixed64 => "TYPE_FIXED64",
+                Self::TypeFixed32 => "TYPE_FIXED32",
+                Self::TypeBool => "TYPE_BOOL",
+                Self::TypeString => "TYPE_STRING",
+                Self::TypeGroup => "TYPE_GROUP",
+                Self::TypeMessage => "TYPE_MESSAGE",
+                Self::TypeBytes => "TYPE_BYTES",
+                Self::TypeUint32 => "TYPE_UINT32",
+                Self::TypeEnum => "TYPE_ENUM",
+                Self::TypeSfixed32 => "TYPE_SFIXED32",
+                Self::TypeSfixed64 => "TYPE_SFIXED64",
+                Self::TypeSint32 => "TYPE_SINT32",
+                Self::TypeSint64 => "TYPE_SINT64",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "TYPE_UNKNOWN" => ::core::option::Option::Some(Self::TypeUnknown),
+                "TYPE_DOUBLE" => ::core::option::Option::Some(Self::TypeDouble),
+                "TYPE_FLOAT" => ::core::option::Option::Some(Self::TypeFloat),
+                "TYPE_INT64" => ::core::option::Option::Some(Self::TypeInt64),
+                "TYPE_UINT64" => ::core::option::Option::Some(Self::TypeUint64),
+                "TYPE_INT32" => ::core::option::Option::Some(Self::TypeInt32),
+                "TYPE_FIXED64" => ::core::option::Option::Some(Self::TypeFixed64),
+                "TYPE_FIXED32" => ::core::option::Option::Some(Self::TypeFixed32),
+                "TYPE_BOOL" => ::core::option::Option::Some(Self::TypeBool),
+                "TYPE_STRING" => ::core::option::Option::Some(Self::TypeString),
+                "TYPE_GROUP" => ::core::option::Option::Some(Self::TypeGroup),
+                "TYPE_MESSAGE" => ::core::option::Option::Some(Self::TypeMessage),
+                "TYPE_BYTES" => ::core::option::Option::Some(Self::TypeBytes),
+                "TYPE_UINT32" => ::core::option::Option::Some(Self::TypeUint32),
+                "TYPE_ENUM" => ::core::option::Option::Some(Self::TypeEnum),
+                "TYPE_SFIXED32" => ::core::option::Option::Some(Self::TypeSfixed32),
+                "TYPE_SFIXED64" => ::core::option::Option::Some(Self::TypeSfixed64),
+                "TYPE_SINT32" => ::core::option::Option::Some(Self::TypeSint32),
+                "TYPE_SINT64" => ::core::option::Option::Some(Self::TypeSint64),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
     /// Whether a field is optional, required, or repeated.
-    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
     #[repr(i32)]
     pub enum Cardinality {
         /// For fields with unknown cardinality.
@@ -1157,9 +1439,39 @@ pub mod field {
         /// For repeated fields.
         Repeated = 3,
     }
+    impl Cardinality {
+        /// The Protobuf names and numbers of the values of the enum, in declaration order,
+        /// including aliases.
+        pub const NAMES: &'static [(&'static str, i32)] = &[
+            ("CARDINALITY_UNKNOWN", 0),
+            ("CARDINALITY_OPTIONAL", 1),
+            ("CARDINALITY_REQUIRED", 2),
+            ("CARDINALITY_REPEATED", 3),
+        ];
+        /// Returns the name of the value in the Protobuf definition, which is stable as long as
+        /// the definition doesn't change.
+        pub fn as_str_name(&self) -> &'static str {
+            match self {
+                Self::Unknown => "CARDINALITY_UNKNOWN",
+                Self::Optional => "CARDINALITY_OPTIONAL",
+                Self::Required => "CARDINALITY_REQUIRED",
+                Self::Repeated => "CARDINALITY_REPEATED",
+            }
+        }
+        /// Returns the value with the name in the Protobuf definition, including aliases.
+        pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+            match value {
+                "CARDINALITY_UNKNOWN" => ::core::option::Option::Some(Self::Unknown),
+                "CARDINALITY_OPTIONAL" => ::core::option::Option::Some(Self::Optional),
+                "CARDINALITY_REQUIRED" => ::core::option::Option::Some(Self::Required),
+                "CARDINALITY_REPEATED" => ::core::option::Option::Some(Self::Repeated),
+                _ => ::core::option::Option::None,
+            }
+        }
+    }
 }
 /// Enum type definition.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Enum {
     /// Enum type name.
     #[prost(string, tag="1")]
@@ -1178,7 +1490,7 @@ pub struct Enum {
     pub syntax: i32,
 }
 /// Enum value definition.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct EnumValue {
     /// Enum value name.
     #[prost(string, tag="1")]
@@ -1192,7 +1504,7 @@ pub struct EnumValue {
 }
 /// A protocol buffer option, which can be attached to a message, field,
 /// enumeration, etc.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Option {
     /// The option's name. For protobuf built-in options (options defined in
     /// descriptor.proto), this is the short name. For example, `"map_entry"`.
@@ -1208,7 +1520,7 @@ pub struct Option {
     pub value: ::core::option::Option<Any>,
 }
 /// The syntax in which a protocol buffer element is defined.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
 #[repr(i32)]
 pub enum Syntax {
     /// Syntax `proto2`.
@@ -1216,6 +1528,30 @@ pub enum Syntax {
     /// Syntax `proto3`.
     Proto3 = 1,
 }
+impl Syntax {
+    /// The Protobuf names and numbers of the values of the enum, in declaration order,
+    /// including aliases.
+    pub const NAMES: &'static [(&'static str, i32)] = &[
+        ("SYNTAX_PROTO2", 0),
+        ("SYNTAX_PROTO3", 1),
+    ];
+    /// Returns the name of the value in the Protobuf definition, which is stable as long as
+    /// the definition doesn't change.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::Proto2 => "SYNTAX_PROTO2",
+            Self::Proto3 => "SYNTAX_PROTO3",
+        }
+    }
+    /// Returns the value with the name in the Protobuf definition, including aliases.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SYNTAX_PROTO2" => ::core::option::Option::Some(Self::Proto2),
+            "SYNTAX_PROTO3" => ::core::option::Option::Some(Self::Proto3),
+            _ => ::core::option::Option::None,
+        }
+    }
+}
 /// Api is a light-weight descriptor for an API Interface.
 ///
 /// Interfaces are also described as "protocol buffer services" in some contexts,
@@ -1225,7 +1561,7 @@ pub enum Syntax {
 /// sometimes simply referred to as "APIs" in other contexts, such as the name of
 /// this message itself. See <https://cloud.google.com/apis/design/glossary> for
 /// detailed terminology.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Api {
     /// The fully qualified name of this interface, including package name
     /// followed by the interface's simple name.
@@ -1272,7 +1608,7 @@ pub struct Api {
     pub syntax: i32,
 }
 /// Method represents a method of an API interface.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Method {
     /// The simple name of this method.
     #[prost(string, tag="1")]
@@ -1374,7 +1710,7 @@ pub struct Method {
 ///       }
 ///       ...
 ///     }
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Mixin {
     /// The fully qualified name of the interface which is included.
     #[prost(string, tag="1")]
@@ -1444,7 +1780,8 @@ pub struct Mixin {
 /// microsecond should be expressed in JSON format as "3.000001s".
 ///
 ///
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
+#[prost(check_encode="crate::validity::check_duration")]
 pub struct Duration {
     /// Signed seconds of the span of time. Must be from -315,576,000,000
     /// to +315,576,000,000 inclusive. Note: these bounds are computed from:
@@ -1659,7 +1996,7 @@ pub struct Duration {
 /// The implementation of any API method which has a FieldMask type field in the
 /// request should verify the included field paths, and return an
 /// `INVALID_ARGUMENT` error if any path is unmappable.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct FieldMask {
     /// The set of field mask paths.
     #[prost(string, repeated, tag="1")]
@@ -1673,11 +2010,11 @@ pub struct FieldMask {
 /// with the proto support for the language.
 ///
 /// The JSON representation for `Struct` is JSON object.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Struct {
     /// Unordered map of dynamically typed values.
-    #[prost(btree_map="string, message", tag="1")]
-    pub fields: ::prost::alloc::collections::BTreeMap<::prost::alloc::string::String, Value>,
+    #[prost(map="string, message", map_type="crate::StructMap", tag="1")]
+    pub fields: crate::StructMap<::prost::alloc::string::String, Value>,
 }
 /// `Value` represents a dynamically typed value which can be either
 /// null, a number, a string, a boolean, a recursive struct value, or a
@@ -1685,7 +2022,7 @@ pub struct Struct {
 /// variants, absence of any variant indicates an error.
 ///
 /// The JSON representation for `Value` is JSON value.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct Value {
     /// The kind of value.
     #[prost(oneof="value::Kind", tags="1, 2, 3, 4, 5, 6")]
@@ -1694,7 +2031,7 @@ pub struct Value {
 /// Nested message and enum types in `Value`.
 pub mod value {
     /// The kind of value.
-    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    #[derive(Clone, PartialEq, ::prost::Oneof, ::prost::CanonicalHash)]
     pub enum Kind {
         /// Represents a null value.
         #[prost(enumeration="super::NullValue", tag="1")]
@@ -1719,7 +2056,7 @@ pub mod value {
 /// `ListValue` is a wrapper around a repeated field of values.
 ///
 /// The JSON representation for `ListValue` is JSON array.
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
 pub struct ListValue {
     /// Repeated field of dynamically typed values.
     #[prost(message, repeated, tag="1")]
@@ -1729,12 +2066,33 @@ pub struct ListValue {
 /// `Value` type union.
 ///
 ///  The JSON representation for `NullValue` is JSON `null`.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration, ::prost::CanonicalHash)]
 #[repr(i32)]
 pub enum NullValue {
     /// Null value.
     NullValue = 0,
 }
+impl NullValue {
+    /// The Protobuf names and numbers of the values of the enum, in declaration order,
+    /// including aliases.
+    pub const NAMES: &'static [(&'static str, i32)] = &[
+        ("NULL_VALUE", 0),
+    ];
+    /// Returns the name of the value in the Protobuf definition, which is stable as long as
+    /// the definition doesn't change.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Self::NullValue => "NULL_VALUE",
+        }
+    }
+    /// Returns the value with the name in the Protobuf definition, including aliases.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "NULL_VALUE" => ::core::option::Option::Some(Self::NullValue),
+            _ => ::core::option::Option::None,
+        }
+    }
+}
 /// A Timestamp represents a point in time independent of any time zone or local
 /// calendar, encoded as a count of seconds and fractions of seconds at
 /// nanosecond resolution. The count is relative to an epoch at UTC midnight on
@@ -1828,7 +2186,8 @@ pub enum NullValue {
 /// ) to obtain a formatter capable of generating timestamps in this format.
 ///
 ///
-#[derive(Clone, PartialEq, ::prost::Message)]
+#[derive(Clone, PartialEq, ::prost::Message, ::prost::CanonicalHash)]
+#[prost(check_encode="crate::validity::check_timestamp")]
 pub struct Timestamp {
     /// Represents seconds of UTC time since Unix epoch
     /// 1970-01-01T00:00:00Z. Must be from 0001-01-01T00:00:00Z to
@@ -0,0 +1,1476 @@
+//! Runtime descriptors for Protobuf messages and enums.
+//!
+//! A [`DescriptorPool`] is built from [`FileDescriptorSet`]s, as produced by `protoc
+//! --include_imports --descriptor_set_out` or `prost_build::Config::file_descriptor_set_path`.
+//! Building the pool resolves the imports of every file and every type reference, so that the
+//! descriptors handed out by the pool can be navigated without further lookups or error handling.
+//!
+//! [`DescriptorPool::new`] creates a pool with the files of the `google.protobuf` package
+//! preloaded, so that sets which were compiled without `--include_imports` can still use the
+//! well-known types. Files are added with [`DescriptorPool::add_file_descriptor_set`], which may be
+//! called once for each set of a program:
+//!
+//! ```rust,ignore
+//! let mut pool = DescriptorPool::new();
+//! pool.decode_file_descriptor_set(orders::v1::file_descriptor_set())?;
+//! pool.decode_file_descriptor_set(billing::v1::file_descriptor_set())?;
+//! ```
+//!
+//! Descriptors are cheap to clone; they share ownership of the pool they were created from.
+
+use core::fmt;
+
+use prost::alloc::collections::{BTreeMap, BTreeSet};
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::sync::Arc;
+use prost::alloc::vec::Vec;
+use prost::wire::{FieldKind, WireSchema};
+use prost::Message;
+
+use crate::dynamic::Value;
+use crate::field_descriptor_proto::{Label, Type};
+use crate::{DescriptorProto, EnumDescriptorProto, FileDescriptorProto, FileDescriptorSet};
+
+/// The encoded `FileDescriptorSet` of the files of the `google.protobuf` package, which
+/// [`DescriptorPool::new`] preloads.
+const WELL_KNOWN_TYPES: &[u8] = include_bytes!("well_known_types.bin");
+
+/// An error building a [`DescriptorPool`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorError {
+    description: String,
+}
+
+impl DescriptorError {
+    pub(crate) fn new(description: impl Into<String>) -> DescriptorError {
+        DescriptorError {
+            description: description.into(),
+        }
+    }
+}
+
+impl fmt::Display for DescriptorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid descriptor: {}", self.description)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DescriptorError {}
+
+/// A collection of resolved message and enum descriptors, and the files defining them.
+#[derive(Clone)]
+pub struct DescriptorPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    /// The files of the pool, with the imports of each file preceding it.
+    files: Vec<FileDescriptorProto>,
+    file_names: BTreeMap<String, usize>,
+    messages: Vec<MessageInner>,
+    enums: Vec<EnumInner>,
+    names: BTreeMap<String, TypeRef>,
+}
+
+#[derive(Clone, Copy)]
+enum TypeRef {
+    Message(usize),
+    Enum(usize),
+}
+
+struct MessageInner {
+    full_name: String,
+    proto3: bool,
+    map_entry: bool,
+    fields: Vec<FieldInner>,
+    field_numbers: BTreeMap<u32, usize>,
+    field_names: BTreeMap<String, usize>,
+    oneofs: Vec<OneofInner>,
+}
+
+struct OneofInner {
+    name: String,
+    fields: Vec<usize>,
+}
+
+struct FieldInner {
+    name: String,
+    json_name: String,
+    number: u32,
+    cardinality: Cardinality,
+    kind: KindRef,
+    group: bool,
+    packed: bool,
+    presence: bool,
+    oneof: Option<usize>,
+    default: Option<Value>,
+}
+
+#[derive(Clone, Copy)]
+enum KindRef {
+    Scalar(Type),
+    Message(usize),
+    Enum(usize),
+}
+
+struct EnumInner {
+    full_name: String,
+    values: Vec<(String, i32)>,
+}
+
+impl DescriptorPool {
+    /// Creates a pool with the files of the `google.protobuf` package, which define the
+    /// well-known types, `descriptor.proto` and `compiler/plugin.proto`.
+    pub fn new() -> DescriptorPool {
+        let mut pool = DescriptorPool::build(Vec::new()).expect("empty pool");
+        pool.decode_file_descriptor_set(WELL_KNOWN_TYPES)
+            .expect("invalid well-known types");
+        pool
+    }
+
+    /// Builds a pool from the types defined in a file descriptor set, without the preloaded
+    /// well-known types of [`DescriptorPool::new`].
+    ///
+    /// The set must be self-contained: every file imported by a file, and every type referenced by
+    /// a field, must be defined by one of the files in the set.
+    pub fn from_file_descriptor_set(
+        file_descriptor_set: &FileDescriptorSet,
+    ) -> Result<DescriptorPool, DescriptorError> {
+        DescriptorPool::build(merge_files(&[], &file_descriptor_set.file)?)
+    }
+
+    /// Adds the files of a file descriptor set to the pool.
+    ///
+    /// Every file imported by a file of the set must be defined by the set or by the pool, and
+    /// every type referenced by a field must be defined by one of the files. A file which is already
+    /// in the pool is skipped if it is identical, ignoring source code info, so that sets which
+    /// share imports can be added to the same pool. The preloaded files of the `google.protobuf`
+    /// package are always kept in favor of those of a set, which may have been compiled by another
+    /// version of `protoc`.
+    ///
+    /// An error is returned if a file is defined differently by the set and the pool, if the
+    /// imports of a file are missing or cyclic, or if a type is defined twice or can't be resolved,
+    /// in which case the pool is left unchanged.
+    ///
+    /// Descriptors and clones of the pool which were obtained before adding the files refer to the
+    /// pool as it was.
+    pub fn add_file_descriptor_set(
+        &mut self,
+        file_descriptor_set: &FileDescriptorSet,
+    ) -> Result<(), DescriptorError> {
+        let files = merge_files(&self.inner.files, &file_descriptor_set.file)?;
+        *self = DescriptorPool::build(files)?;
+        Ok(())
+    }
+
+    /// Decodes a file descriptor set, such as the one embedded by `prost-build`'s
+    /// `Config::embed_file_descriptor_set`, and adds its files to the pool.
+    ///
+    /// See [`DescriptorPool::add_file_descriptor_set`].
+    pub fn decode_file_descriptor_set(&mut self, buf: &[u8]) -> Result<(), DescriptorError> {
+        let file_descriptor_set = FileDescriptorSet::decode(buf).map_err(|err| {
+            DescriptorError::new(format!("failed to decode file descriptor set: {}", err))
+        })?;
+        self.add_file_descriptor_set(&file_descriptor_set)
+    }
+
+    /// Returns the file with the given name, e.g. `google/protobuf/duration.proto`.
+    pub fn get_file_by_name(&self, name: &str) -> Option<&FileDescriptorProto> {
+        let index = *self.inner.file_names.get(name)?;
+        Some(&self.inner.files[index])
+    }
+
+    /// Returns an iterator over all files in the pool, in an order where the imports of each file
+    /// precede it.
+    pub fn files(&self) -> impl ExactSizeIterator<Item = &FileDescriptorProto> + '_ {
+        self.inner.files.iter()
+    }
+
+    fn build(files: Vec<FileDescriptorProto>) -> Result<DescriptorPool, DescriptorError> {
+        let mut builder = PoolBuilder::default();
+        for file in &files {
+            let proto3 = file.syntax() == "proto3";
+            for message in &file.message_type {
+                builder.add_message(file.name(), file.package(), message, proto3)?;
+            }
+            for enum_type in &file.enum_type {
+                builder.add_enum(file.name(), file.package(), enum_type)?;
+            }
+        }
+        let mut inner = builder.build()?;
+        inner.file_names = files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (file.name().to_string(), index))
+            .collect();
+        inner.files = files;
+        Ok(DescriptorPool {
+            inner: Arc::new(inner),
+        })
+    }
+
+    /// Returns the message with the given fully-qualified name, e.g. `google.protobuf.Duration`.
+    ///
+    /// A leading `.` is permitted.
+    pub fn get_message_by_name(&self, name: &str) -> Option<MessageDescriptor> {
+        match self.inner.names.get(name.trim_start_matches('.')) {
+            Some(&TypeRef::Message(index)) => Some(MessageDescriptor {
+                pool: self.clone(),
+                index,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the enum with the given fully-qualified name.
+    ///
+    /// A leading `.` is permitted.
+    pub fn get_enum_by_name(&self, name: &str) -> Option<EnumDescriptor> {
+        match self.inner.names.get(name.trim_start_matches('.')) {
+            Some(&TypeRef::Enum(index)) => Some(EnumDescriptor {
+                pool: self.clone(),
+                index,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over all messages in the pool, including map entry messages.
+    pub fn messages(&self) -> impl Iterator<Item = MessageDescriptor> + '_ {
+        (0..self.inner.messages.len()).map(move |index| MessageDescriptor {
+            pool: self.clone(),
+            index,
+        })
+    }
+
+    /// Returns an iterator over all enums in the pool.
+    pub fn enums(&self) -> impl Iterator<Item = EnumDescriptor> + '_ {
+        (0..self.inner.enums.len()).map(move |index| EnumDescriptor {
+            pool: self.clone(),
+            index,
+        })
+    }
+}
+
+impl Default for DescriptorPool {
+    fn default() -> DescriptorPool {
+        DescriptorPool::new()
+    }
+}
+
+impl PartialEq for DescriptorPool {
+    fn eq(&self, other: &DescriptorPool) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl Eq for DescriptorPool {}
+
+impl fmt::Debug for DescriptorPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.inner.names.keys()).finish()
+    }
+}
+
+/// A resolved message descriptor.
+#[derive(Clone, PartialEq, Eq)]
+pub struct MessageDescriptor {
+    pool: DescriptorPool,
+    index: usize,
+}
+
+impl MessageDescriptor {
+    fn inner(&self) -> &MessageInner {
+        &self.pool.inner.messages[self.index]
+    }
+
+    /// Returns the pool containing this message.
+    pub fn parent_pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    /// Returns the fully-qualified name of the message, without a leading `.`.
+    pub fn full_name(&self) -> &str {
+        &self.inner().full_name
+    }
+
+    /// Returns the unqualified name of the message.
+    pub fn name(&self) -> &str {
+        let full_name = self.full_name();
+        full_name.rsplit('.').next().unwrap_or(full_name)
+    }
+
+    /// Returns `true` if the message is defined in a `proto3` file.
+    pub fn is_proto3(&self) -> bool {
+        self.inner().proto3
+    }
+
+    /// Returns `true` if this is a synthetic map entry message.
+    pub fn is_map_entry(&self) -> bool {
+        self.inner().map_entry
+    }
+
+    /// Returns an iterator over the fields of the message, in declaration order.
+    pub fn fields(&self) -> impl ExactSizeIterator<Item = FieldDescriptor> + '_ {
+        (0..self.inner().fields.len()).map(move |index| FieldDescriptor {
+            message: self.clone(),
+            index,
+        })
+    }
+
+    /// Returns the field with the given number.
+    pub fn get_field(&self, number: u32) -> Option<FieldDescriptor> {
+        self.inner()
+            .field_numbers
+            .get(&number)
+            .map(|&index| FieldDescriptor {
+                message: self.clone(),
+                index,
+            })
+    }
+
+    /// Returns the field with the given Protobuf name.
+    pub fn get_field_by_name(&self, name: &str) -> Option<FieldDescriptor> {
+        self.inner()
+            .field_names
+            .get(name)
+            .map(|&index| FieldDescriptor {
+                message: self.clone(),
+                index,
+            })
+    }
+
+    /// Returns the field with the given JSON name.
+    pub fn get_field_by_json_name(&self, json_name: &str) -> Option<FieldDescriptor> {
+        self.fields().find(|field| field.json_name() == json_name)
+    }
+
+    /// Returns the names of the oneofs declared in the message, in declaration order.
+    pub fn oneofs(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        self.inner().oneofs.iter().map(|oneof| oneof.name.as_str())
+    }
+
+    /// Returns the key field of a map entry message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message is not a map entry.
+    pub fn map_entry_key_field(&self) -> FieldDescriptor {
+        assert!(
+            self.is_map_entry(),
+            "{} is not a map entry",
+            self.full_name()
+        );
+        self.get_field(1).expect("map entry has no key field")
+    }
+
+    /// Returns the value field of a map entry message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message is not a map entry.
+    pub fn map_entry_value_field(&self) -> FieldDescriptor {
+        assert!(
+            self.is_map_entry(),
+            "{} is not a map entry",
+            self.full_name()
+        );
+        self.get_field(2).expect("map entry has no value field")
+    }
+}
+
+impl fmt::Debug for MessageDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MessageDescriptor")
+            .field(&self.full_name())
+            .finish()
+    }
+}
+
+/// Checks strings and embedded messages, including map entries, with
+/// [`prost::wire::validate_wire_format_with`].
+impl WireSchema for MessageDescriptor {
+    fn field(&self, tag: u32) -> FieldKind<MessageDescriptor> {
+        match self.get_field(tag).map(|field| field.kind()) {
+            Some(Kind::String) => FieldKind::String,
+            Some(Kind::Message(message)) => FieldKind::Message(message),
+            _ => FieldKind::Other,
+        }
+    }
+}
+
+/// The cardinality of a message field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cardinality {
+    Optional,
+    Required,
+    Repeated,
+}
+
+/// The type of a message field.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+    /// A message or group field.
+    Message(MessageDescriptor),
+    Enum(EnumDescriptor),
+}
+
+impl Kind {
+    /// Returns `true` if repeated fields of this kind can use the packed encoding.
+    pub fn is_packable(&self) -> bool {
+        !matches!(self, Kind::String | Kind::Bytes | Kind::Message(_))
+    }
+
+    /// Returns the message descriptor if this is a message kind.
+    pub fn as_message(&self) -> Option<&MessageDescriptor> {
+        match self {
+            Kind::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Returns the enum descriptor if this is an enum kind.
+    pub fn as_enum(&self) -> Option<&EnumDescriptor> {
+        match self {
+            Kind::Enum(enum_type) => Some(enum_type),
+            _ => None,
+        }
+    }
+}
+
+/// A resolved message field descriptor.
+#[derive(Clone, PartialEq, Eq)]
+pub struct FieldDescriptor {
+    message: MessageDescriptor,
+    index: usize,
+}
+
+impl FieldDescriptor {
+    fn inner(&self) -> &FieldInner {
+        &self.message.inner().fields[self.index]
+    }
+
+    /// Returns the message containing this field.
+    pub fn containing_message(&self) -> &MessageDescriptor {
+        &self.message
+    }
+
+    /// Returns the Protobuf name of the field.
+    pub fn name(&self) -> &str {
+        &self.inner().name
+    }
+
+    /// Returns the JSON name of the field.
+    pub fn json_name(&self) -> &str {
+        &self.inner().json_name
+    }
+
+    /// Returns the field number.
+    pub fn number(&self) -> u32 {
+        self.inner().number
+    }
+
+    /// Returns the cardinality of the field.
+    pub fn cardinality(&self) -> Cardinality {
+        self.inner().cardinality
+    }
+
+    /// Returns the type of the field. For map fields this is the map entry message.
+    pub fn kind(&self) -> Kind {
+        let pool = &self.message.pool;
+        match self.inner().kind {
+            KindRef::Message(index) => Kind::Message(MessageDescriptor {
+                pool: pool.clone(),
+                index,
+            }),
+            KindRef::Enum(index) => Kind::Enum(EnumDescriptor {
+                pool: pool.clone(),
+                index,
+            }),
+            KindRef::Scalar(ty) => match ty {
+                Type::Double => Kind::Double,
+                Type::Float => Kind::Float,
+                Type::Int32 => Kind::Int32,
+                Type::Int64 => Kind::Int64,
+                Type::Uint32 => Kind::Uint32,
+                Type::Uint64 => Kind::Uint64,
+                Type::Sint32 => Kind::Sint32,
+                Type::Sint64 => Kind::Sint64,
+                Type::Fixed32 => Kind::Fixed32,
+                Type::Fixed64 => Kind::Fixed64,
+                Type::Sfixed32 => Kind::Sfixed32,
+                Type::Sfixed64 => Kind::Sfixed64,
+                Type::Bool => Kind::Bool,
+                Type::String => Kind::String,
+                Type::Bytes => Kind::Bytes,
+                Type::Message | Type::Group | Type::Enum => unreachable!(),
+            },
+        }
+    }
+
+    /// Returns `true` if the field is a repeated field, but not a map.
+    pub fn is_list(&self) -> bool {
+        self.cardinality() == Cardinality::Repeated && !self.is_map()
+    }
+
+    /// Returns `true` if the field is a map.
+    pub fn is_map(&self) -> bool {
+        match self.inner().kind {
+            KindRef::Message(index) => {
+                self.cardinality() == Cardinality::Repeated
+                    && self.message.pool.inner.messages[index].map_entry
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the field uses the group wire format.
+    pub fn is_group(&self) -> bool {
+        self.inner().group
+    }
+
+    /// Returns `true` if the repeated field is encoded using the packed encoding.
+    pub fn is_packed(&self) -> bool {
+        self.inner().packed
+    }
+
+    /// Returns `true` if the field distinguishes between an unset value and the default value.
+    pub fn supports_presence(&self) -> bool {
+        self.inner().presence
+    }
+
+    /// Returns the name of the oneof containing this field, including synthetic oneofs generated
+    /// for `proto3` optional fields.
+    pub fn containing_oneof(&self) -> Option<&str> {
+        self.inner()
+            .oneof
+            .map(|index| self.message.inner().oneofs[index].name.as_str())
+    }
+
+    /// Returns the other fields belonging to the same oneof as this field.
+    pub(crate) fn oneof_siblings(&self) -> impl Iterator<Item = u32> + '_ {
+        let message = self.message.inner();
+        self.inner()
+            .oneof
+            .into_iter()
+            .flat_map(move |oneof| message.oneofs[oneof].fields.iter())
+            .filter(move |&&index| index != self.index)
+            .map(move |&index| message.fields[index].number)
+    }
+
+    /// Returns the default value of the field, taking `proto2` default values into account.
+    pub fn default_value(&self) -> Value {
+        match &self.inner().default {
+            Some(value) => value.clone(),
+            None => Value::default_for_field(self),
+        }
+    }
+}
+
+impl fmt::Debug for FieldDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldDescriptor")
+            .field("message", &self.message.full_name())
+            .field("name", &self.name())
+            .field("number", &self.number())
+            .finish()
+    }
+}
+
+/// A resolved enum descriptor.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EnumDescriptor {
+    pool: DescriptorPool,
+    index: usize,
+}
+
+impl EnumDescriptor {
+    fn inner(&self) -> &EnumInner {
+        &self.pool.inner.enums[self.index]
+    }
+
+    /// Returns the fully-qualified name of the enum, without a leading `.`.
+    pub fn full_name(&self) -> &str {
+        &self.inner().full_name
+    }
+
+    /// Returns the unqualified name of the enum.
+    pub fn name(&self) -> &str {
+        let full_name = self.full_name();
+        full_name.rsplit('.').next().unwrap_or(full_name)
+    }
+
+    /// Returns the `(name, number)` pairs of the enum values, in declaration order.
+    pub fn values(&self) -> impl ExactSizeIterator<Item = (&str, i32)> + '_ {
+        self.inner()
+            .values
+            .iter()
+            .map(|(name, number)| (name.as_str(), *number))
+    }
+
+    /// Returns the number of the value with the given name.
+    pub fn get_value_by_name(&self, name: &str) -> Option<i32> {
+        self.values()
+            .find(|&(value_name, _)| value_name == name)
+            .map(|(_, number)| number)
+    }
+
+    /// Returns the name of the first value with the given number.
+    pub fn get_value(&self, number: i32) -> Option<&str> {
+        self.values()
+            .find(|&(_, value_number)| value_number == number)
+            .map(|(name, _)| name)
+    }
+
+    /// Returns the default value of the enum, which is the first declared value.
+    pub fn default_value(&self) -> i32 {
+        self.inner().values.first().map_or(0, |&(_, number)| number)
+    }
+}
+
+impl fmt::Debug for EnumDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("EnumDescriptor")
+            .field(&self.full_name())
+            .finish()
+    }
+}
+
+/// Returns the files of the pool followed by the added files which are not in it, ordered so that
+/// the imports of each file precede it.
+fn merge_files(
+    pool: &[FileDescriptorProto],
+    added: &[FileDescriptorProto],
+) -> Result<Vec<FileDescriptorProto>, DescriptorError> {
+    let mut files = pool.to_vec();
+    let mut file_names = pool
+        .iter()
+        .map(|file| file.name().to_string())
+        .collect::<BTreeSet<_>>();
+
+    let mut pending = BTreeMap::new();
+    for file in added {
+        let previous = match pool.iter().find(|previous| previous.name() == file.name()) {
+            // The preloaded `google.protobuf` files are kept.
+            Some(_) if file.name().starts_with("google/protobuf/") => continue,
+            Some(previous) => Some(previous),
+            None => pending.get(file.name()).copied(),
+        };
+        match previous {
+            Some(previous) if same_file(previous, file) => continue,
+            Some(_) => {
+                return Err(DescriptorError::new(format!(
+                    "conflicting definitions of file {}",
+                    file.name()
+                )))
+            }
+            None => {
+                pending.insert(file.name(), file);
+            }
+        }
+    }
+
+    /// Appends the file after its imports, which are visited depth-first.
+    fn visit<'a>(
+        name: &'a str,
+        importer: Option<&str>,
+        pending: &BTreeMap<&'a str, &'a FileDescriptorProto>,
+        visiting: &mut BTreeSet<&'a str>,
+        file_names: &mut BTreeSet<String>,
+        files: &mut Vec<FileDescriptorProto>,
+    ) -> Result<(), DescriptorError> {
+        if file_names.contains(name) {
+            return Ok(());
+        }
+        let file = match (pending.get(name), importer) {
+            (Some(file), _) => *file,
+            (None, Some(importer)) => {
+                return Err(DescriptorError::new(format!(
+                    "{} imports {}, which is not in the pool",
+                    importer, name
+                )))
+            }
+            (None, None) => unreachable!("added file is not pending"),
+        };
+        if !visiting.insert(name) {
+            return Err(DescriptorError::new(format!(
+                "import cycle through {}",
+                name
+            )));
+        }
+        for dependency in &file.dependency {
+            visit(dependency, Some(name), pending, visiting, file_names, files)?;
+        }
+        file_names.insert(name.to_string());
+        files.push(file.clone());
+        Ok(())
+    }
+
+    let mut visiting = BTreeSet::new();
+    for file in added {
+        if pending.contains_key(file.name()) {
+            visit(
+                file.name(),
+                None,
+                &pending,
+                &mut visiting,
+                &mut file_names,
+                &mut files,
+            )?;
+        }
+    }
+    Ok(files)
+}
+
+/// Returns whether two definitions of a file are identical, ignoring source code info.
+fn same_file(a: &FileDescriptorProto, b: &FileDescriptorProto) -> bool {
+    let strip = |file: &FileDescriptorProto| FileDescriptorProto {
+        source_code_info: None,
+        ..file.clone()
+    };
+    strip(a) == strip(b)
+}
+
+/// Collects message and enum definitions, and then resolves references between them.
+#[derive(Default)]
+struct PoolBuilder<'a> {
+    messages: Vec<(String, &'a DescriptorProto, bool)>,
+    enums: Vec<(String, &'a EnumDescriptorProto)>,
+    names: BTreeMap<String, TypeRef>,
+    /// The file defining each type name.
+    name_files: BTreeMap<String, &'a str>,
+}
+
+fn join_name(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        let mut full_name = String::with_capacity(scope.len() + name.len() + 1);
+        full_name.push_str(scope);
+        full_name.push('.');
+        full_name.push_str(name);
+        full_name
+    }
+}
+
+impl<'a> PoolBuilder<'a> {
+    fn insert_name(
+        &mut self,
+        file: &'a str,
+        full_name: &str,
+        type_ref: TypeRef,
+    ) -> Result<(), DescriptorError> {
+        if self.names.insert(full_name.to_string(), type_ref).is_some() {
+            return Err(DescriptorError::new(format!(
+                "duplicate type name: {} is defined by both {} and {}",
+                full_name, self.name_files[full_name], file
+            )));
+        }
+        self.name_files.insert(full_name.to_string(), file);
+        Ok(())
+    }
+
+    fn add_message(
+        &mut self,
+        file: &'a str,
+        scope: &str,
+        message: &'a DescriptorProto,
+        proto3: bool,
+    ) -> Result<(), DescriptorError> {
+        let full_name = join_name(scope, message.name());
+        self.insert_name(file, &full_name, TypeRef::Message(self.messages.len()))?;
+        self.messages.push((full_name.clone(), message, proto3));
+
+        for nested in &message.nested_type {
+            self.add_message(file, &full_name, nested, proto3)?;
+        }
+        for enum_type in &message.enum_type {
+            self.add_enum(file, &full_name, enum_type)?;
+        }
+        Ok(())
+    }
+
+    fn add_enum(
+        &mut self,
+        file: &'a str,
+        scope: &str,
+        enum_type: &'a EnumDescriptorProto,
+    ) -> Result<(), DescriptorError> {
+        let full_name = join_name(scope, enum_type.name());
+        self.insert_name(file, &full_name, TypeRef::Enum(self.enums.len()))?;
+        self.enums.push((full_name, enum_type));
+        Ok(())
+    }
+
+    fn build(self) -> Result<PoolInner, DescriptorError> {
+        let enums = self
+            .enums
+            .iter()
+            .map(|(full_name, enum_type)| EnumInner {
+                full_name: full_name.clone(),
+                values: enum_type
+                    .value
+                    .iter()
+                    .map(|value| (value.name().to_string(), value.number()))
+                    .collect(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut messages = Vec::with_capacity(self.messages.len());
+        for (full_name, message, proto3) in &self.messages {
+            messages.push(self.build_message(full_name, message, *proto3, &enums)?);
+        }
+
+        Ok(PoolInner {
+            files: Vec::new(),
+            file_names: BTreeMap::new(),
+            messages,
+            enums,
+            names: self.names,
+        })
+    }
+
+    fn build_message(
+        &self,
+        full_name: &str,
+        message: &DescriptorProto,
+        proto3: bool,
+        enums: &[EnumInner],
+    ) -> Result<MessageInner, DescriptorError> {
+        let mut oneofs = message
+            .oneof_decl
+            .iter()
+            .map(|oneof| OneofInner {
+                name: oneof.name().to_string(),
+                fields: Vec::new(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut fields = Vec::with_capacity(message.field.len());
+        let mut field_numbers = BTreeMap::new();
+        let mut field_names = BTreeMap::new();
+        for (index, field) in message.field.iter().enumerate() {
+            let number = field.number();
+            if number <= 0 {
+                return Err(DescriptorError::new(format!(
+                    "invalid field number for {}.{}: {}",
+                    full_name,
+                    field.name(),
+                    number
+                )));
+            }
+            let number = number as u32;
+            if field_numbers.insert(number, index).is_some() {
+                return Err(DescriptorError::new(format!(
+                    "duplicate field number in {}: {}",
+                    full_name, number
+                )));
+            }
+            field_names.insert(field.name().to_string(), index);
+
+            let ty = field.r#type();
+            let kind = match ty {
+                Type::Message | Type::Group | Type::Enum => {
+                    let type_name = field.type_name().trim_start_matches('.');
+                    match (ty, self.names.get(type_name)) {
+                        (Type::Enum, Some(&TypeRef::Enum(index))) => KindRef::Enum(index),
+                        (Type::Message, Some(&TypeRef::Message(index)))
+                        | (Type::Group, Some(&TypeRef::Message(index))) => KindRef::Message(index),
+                        _ => {
+                            return Err(DescriptorError::new(format!(
+                                "unresolved type for {}.{}: {}",
+                                full_name,
+                                field.name(),
+                                field.type_name()
+                            )))
+                        }
+                    }
+                }
+                ty => KindRef::Scalar(ty),
+            };
+
+            let cardinality = match field.label() {
+                Label::Optional => Cardinality::Optional,
+                Label::Required => Cardinality::Required,
+                Label::Repeated => Cardinality::Repeated,
+            };
+
+            let packable = !matches!(ty, Type::String | Type::Bytes | Type::Message | Type::Group);
+            let packed = cardinality == Cardinality::Repeated
+                && packable
+                && field
+                    .options
+                    .as_ref()
+                    .and_then(|options| options.packed)
+                    .unwrap_or(proto3);
+
+            let oneof = match field.oneof_index {
+                Some(oneof_index) => {
+                    let oneof = oneofs.get_mut(oneof_index as usize).ok_or_else(|| {
+                        DescriptorError::new(format!(
+                            "invalid oneof index for {}.{}: {}",
+                            full_name,
+                            field.name(),
+                            oneof_index
+                        ))
+                    })?;
+                    oneof.fields.push(index);
+                    Some(oneof_index as usize)
+                }
+                None => None,
+            };
+
+            let presence = cardinality != Cardinality::Repeated
+                && (!proto3
+                    || oneof.is_some()
+                    || matches!(ty, Type::Message | Type::Group)
+                    || field.proto3_optional());
+
+            let default = match &field.default_value {
+                Some(default) => {
+                    Some(parse_default(ty, kind, default, enums).ok_or_else(|| {
+                        DescriptorError::new(format!(
+                            "invalid default value for {}.{}: {}",
+                            full_name,
+                            field.name(),
+                            default
+                        ))
+                    })?)
+                }
+                None => None,
+            };
+
+            fields.push(FieldInner {
+                name: field.name().to_string(),
+                json_name: field
+                    .json_name
+                    .clone()
+                    .unwrap_or_else(|| to_json_name(field.name())),
+                number,
+                cardinality,
+                kind,
+                group: ty == Type::Group,
+                packed,
+                presence,
+                oneof,
+                default,
+            });
+        }
+
+        Ok(MessageInner {
+            full_name: full_name.to_string(),
+            proto3,
+            map_entry: matches!(&message.options, Some(options) if options.map_entry()),
+            fields,
+            field_numbers,
+            field_names,
+            oneofs,
+        })
+    }
+}
+
+/// Converts a field name to its default JSON name, following `protoc`'s `ToJsonName`.
+pub(crate) fn to_json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut capitalize_next = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            json_name.push(c.to_ascii_uppercase());
+            capitalize_next = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+/// Parses a `proto2` default value, as it is represented in a `FieldDescriptorProto`.
+fn parse_default(ty: Type, kind: KindRef, default: &str, enums: &[EnumInner]) -> Option<Value> {
+    fn parse_float(default: &str) -> Option<f64> {
+        match default {
+            "inf" => Some(f64::INFINITY),
+            "-inf" => Some(f64::NEG_INFINITY),
+            "nan" => Some(f64::NAN),
+            _ => default.parse().ok(),
+        }
+    }
+
+    Some(match ty {
+        Type::Double => Value::F64(parse_float(default)?),
+        Type::Float => Value::F32(parse_float(default)? as f32),
+        Type::Int32 | Type::Sint32 | Type::Sfixed32 => Value::I32(default.parse().ok()?),
+        Type::Int64 | Type::Sint64 | Type::Sfixed64 => Value::I64(default.parse().ok()?),
+        Type::Uint32 | Type::Fixed32 => Value::U32(default.parse().ok()?),
+        Type::Uint64 | Type::Fixed64 => Value::U64(default.parse().ok()?),
+        Type::Bool => Value::Bool(default.parse().ok()?),
+        Type::String => Value::String(default.to_string()),
+        Type::Bytes => Value::Bytes(unescape_bytes(default)?.into()),
+        Type::Enum => match kind {
+            KindRef::Enum(index) => Value::EnumNumber(
+                enums[index]
+                    .values
+                    .iter()
+                    .find(|(name, _)| name == default)?
+                    .1,
+            ),
+            _ => return None,
+        },
+        Type::Message | Type::Group => return None,
+    })
+}
+
+/// Unescapes a C-escaped `bytes` default value.
+fn unescape_bytes(default: &str) -> Option<Vec<u8>> {
+    let mut bytes = default.bytes().peekable();
+    let mut unescaped = Vec::with_capacity(default.len());
+    while let Some(b) = bytes.next() {
+        if b != b'\\' {
+            unescaped.push(b);
+            continue;
+        }
+        let escaped = match bytes.next()? {
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'f' => 0x0C,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0B,
+            b'x' | b'X' => {
+                let mut value = 0u8;
+                for _ in 0..2 {
+                    let digit = (*bytes.peek()? as char).to_digit(16)?;
+                    bytes.next();
+                    value = value.wrapping_mul(16).wrapping_add(digit as u8);
+                }
+                value
+            }
+            digit @ b'0'..=b'7' => {
+                let mut value = digit - b'0';
+                for _ in 0..2 {
+                    match bytes.peek() {
+                        Some(&digit @ b'0'..=b'7') => {
+                            bytes.next();
+                            value = value.wrapping_mul(8).wrapping_add(digit - b'0');
+                        }
+                        _ => break,
+                    }
+                }
+                value
+            }
+            other => other,
+        };
+        unescaped.push(escaped);
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    use crate::{
+        field_descriptor_proto, FieldDescriptorProto, FileDescriptorProto, MessageOptions,
+        OneofDescriptorProto,
+    };
+
+    pub(crate) fn field(name: &str, number: i32, label: Label, ty: Type) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_string()),
+            number: Some(number),
+            label: Some(label as i32),
+            r#type: Some(ty as i32),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn typed_field(
+        name: &str,
+        number: i32,
+        label: Label,
+        ty: Type,
+        type_name: &str,
+    ) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            type_name: Some(type_name.to_string()),
+            ..field(name, number, label, ty)
+        }
+    }
+
+    /// A small schema exercising scalars, nested messages, enums, maps and oneofs.
+    pub(crate) fn test_pool() -> DescriptorPool {
+        let mut oneof_a = field("a", 7, Label::Optional, Type::String);
+        oneof_a.oneof_index = Some(0);
+        let mut oneof_b = field("b", 8, Label::Optional, Type::Int32);
+        oneof_b.oneof_index = Some(0);
+
+        let person = DescriptorProto {
+            name: Some("Person".to_string()),
+            field: vec![
+                field("name", 1, Label::Optional, Type::String),
+                field("id", 2, Label::Optional, Type::Int32),
+                field("scores", 3, Label::Repeated, Type::Sint64),
+                typed_field("kind", 4, Label::Optional, Type::Enum, ".test.Person.Kind"),
+                typed_field("friend", 5, Label::Optional, Type::Message, ".test.Person"),
+                typed_field(
+                    "labels",
+                    6,
+                    Label::Repeated,
+                    Type::Message,
+                    ".test.Person.LabelsEntry",
+                ),
+                oneof_a,
+                oneof_b,
+                field("data", 9, Label::Optional, Type::Bytes),
+                field("ratio", 10, Label::Optional, Type::Double),
+            ],
+            nested_type: vec![DescriptorProto {
+                name: Some("LabelsEntry".to_string()),
+                field: vec![
+                    field("key", 1, Label::Optional, Type::String),
+                    field("value", 2, Label::Optional, Type::Uint32),
+                ],
+                options: Some(MessageOptions {
+                    map_entry: Some(true),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Kind".to_string()),
+                value: vec![
+                    crate::EnumValueDescriptorProto {
+                        name: Some("UNKNOWN".to_string()),
+                        number: Some(0),
+                        options: None,
+                    },
+                    crate::EnumValueDescriptorProto {
+                        name: Some("ADMIN".to_string()),
+                        number: Some(1),
+                        options: None,
+                    },
+                ],
+                ..Default::default()
+            }],
+            oneof_decl: vec![OneofDescriptorProto {
+                name: Some("choice".to_string()),
+                options: None,
+            }],
+            ..Default::default()
+        };
+
+        let legacy = DescriptorProto {
+            name: Some("Legacy".to_string()),
+            field: vec![
+                FieldDescriptorProto {
+                    default_value: Some("42".to_string()),
+                    ..field("answer", 1, Label::Optional, Type::Int32)
+                },
+                FieldDescriptorProto {
+                    default_value: Some("a\\001\\x02".to_string()),
+                    ..field("blob", 2, Label::Optional, Type::Bytes)
+                },
+                FieldDescriptorProto {
+                    default_value: Some("ADMIN".to_string()),
+                    ..typed_field("kind", 3, Label::Optional, Type::Enum, ".test.Person.Kind")
+                },
+                field("values", 4, Label::Repeated, Type::Int32),
+            ],
+            ..Default::default()
+        };
+
+        let files = FileDescriptorSet {
+            file: vec![
+                FileDescriptorProto {
+                    name: Some("test.proto".to_string()),
+                    package: Some("test".to_string()),
+                    message_type: vec![person],
+                    syntax: Some("proto3".to_string()),
+                    ..Default::default()
+                },
+                FileDescriptorProto {
+                    name: Some("legacy.proto".to_string()),
+                    package: Some("test".to_string()),
+                    message_type: vec![legacy],
+                    ..Default::default()
+                },
+            ],
+        };
+        DescriptorPool::from_file_descriptor_set(&files).unwrap()
+    }
+
+    #[test]
+    fn resolve_types() {
+        let pool = test_pool();
+        let person = pool.get_message_by_name(".test.Person").unwrap();
+        assert_eq!(person.name(), "Person");
+        assert!(person.is_proto3());
+
+        let friend = person.get_field_by_name("friend").unwrap();
+        assert_eq!(friend.kind(), Kind::Message(person.clone()));
+        assert!(friend.supports_presence());
+
+        let kind = person.get_field(4).unwrap();
+        let kind_enum = kind.kind().as_enum().cloned().unwrap();
+        assert_eq!(kind_enum.full_name(), "test.Person.Kind");
+        assert_eq!(kind_enum.get_value_by_name("ADMIN"), Some(1));
+        assert!(!kind.supports_presence());
+
+        let labels = person.get_field_by_json_name("labels").unwrap();
+        assert!(labels.is_map());
+        assert!(!labels.is_list());
+        let entry = labels.kind().as_message().cloned().unwrap();
+        assert_eq!(entry.map_entry_value_field().kind(), Kind::Uint32);
+
+        let scores = person.get_field(3).unwrap();
+        assert!(scores.is_list());
+        assert!(scores.is_packed());
+
+        let a = person.get_field_by_name("a").unwrap();
+        assert_eq!(a.containing_oneof(), Some("choice"));
+        assert_eq!(a.oneof_siblings().collect::<Vec<_>>(), vec![8]);
+        assert!(a.supports_presence());
+
+        let legacy = pool.get_message_by_name("test.Legacy").unwrap();
+        assert!(!legacy.get_field(4).unwrap().is_packed());
+        assert_eq!(legacy.get_field(1).unwrap().default_value(), Value::I32(42));
+        assert_eq!(
+            legacy.get_field(2).unwrap().default_value(),
+            Value::Bytes(vec![b'a', 1, 2].into())
+        );
+        assert_eq!(
+            legacy.get_field(3).unwrap().default_value(),
+            Value::EnumNumber(1)
+        );
+    }
+
+    #[test]
+    fn wire_schema() {
+        let person = test_pool().get_message_by_name("test.Person").unwrap();
+        let check = |buf: &[u8]| prost::wire::validate_wire_format_with(buf, &person, 10);
+
+        // A friend with a label whose key is "a".
+        assert!(check(b"\x0a\x02hi\x2a\x05\x32\x03\x0a\x01a").is_ok());
+        assert!(check(b"\x0a\x02\xff\xff").is_err());
+        assert!(check(b"\x2a\x05\x32\x03\x0a\x01\xff").is_err());
+        // Bytes and unknown fields aren't checked for UTF-8.
+        assert!(check(b"\x4a\x01\xff\x7a\x01\xff").is_ok());
+    }
+
+    #[test]
+    fn unresolved_type() {
+        let files = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                message_type: vec![DescriptorProto {
+                    name: Some("Msg".to_string()),
+                    field: vec![typed_field(
+                        "missing",
+                        1,
+                        Label::Optional,
+                        field_descriptor_proto::Type::Message,
+                        ".Missing",
+                    )],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+        let error = DescriptorPool::from_file_descriptor_set(&files).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid descriptor: unresolved type for Msg.missing: .Missing"
+        );
+    }
+
+    /// A file importing the files with the names, and defining a message named after the file.
+    fn importing_file(name: &str, dependencies: &[&str]) -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some(format!("{}.proto", name)),
+            package: Some("app".to_string()),
+            dependency: dependencies
+                .iter()
+                .map(|dependency| dependency.to_string())
+                .collect(),
+            message_type: vec![DescriptorProto {
+                name: Some(name.to_string()),
+                field: vec![typed_field(
+                    "at",
+                    1,
+                    Label::Optional,
+                    field_descriptor_proto::Type::Message,
+                    ".google.protobuf.Timestamp",
+                )],
+                ..Default::default()
+            }],
+            syntax: Some("proto3".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn well_known_types() {
+        let pool = DescriptorPool::new();
+        for name in &[
+            "google.protobuf.Any",
+            "google.protobuf.Timestamp",
+            "google.protobuf.StringValue",
+            "google.protobuf.FileDescriptorSet",
+            "google.protobuf.compiler.CodeGeneratorRequest",
+        ] {
+            assert!(pool.get_message_by_name(name).is_some(), "{}", name);
+        }
+        assert_eq!(
+            pool.get_file_by_name("google/protobuf/duration.proto")
+                .unwrap()
+                .package(),
+            "google.protobuf"
+        );
+
+        // The imports of each file precede it.
+        let files = pool.files().map(|file| file.name()).collect::<Vec<_>>();
+        for (index, file) in pool.files().enumerate() {
+            for dependency in &file.dependency {
+                assert!(
+                    files[..index].contains(&dependency.as_str()),
+                    "{}",
+                    dependency
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_imports() {
+        let time = FileDescriptorSet {
+            file: vec![
+                importing_file("Event", &["Clock.proto", "google/protobuf/timestamp.proto"]),
+                importing_file("Clock", &["google/protobuf/timestamp.proto"]),
+            ],
+        };
+
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_set(&time).unwrap();
+        let event = pool.get_message_by_name("app.Event").unwrap();
+        assert_eq!(
+            event
+                .fields()
+                .next()
+                .unwrap()
+                .kind()
+                .as_message()
+                .unwrap()
+                .full_name(),
+            "google.protobuf.Timestamp"
+        );
+        let files = pool.files().map(|file| file.name()).collect::<Vec<_>>();
+        assert_eq!(files[files.len() - 2..], ["Clock.proto", "Event.proto"]);
+
+        // Without the preloaded well-known types, the imports are missing.
+        assert_eq!(
+            DescriptorPool::from_file_descriptor_set(&time)
+                .unwrap_err()
+                .to_string(),
+            "invalid descriptor: Clock.proto imports google/protobuf/timestamp.proto, which is not \
+             in the pool"
+        );
+
+        let cycle = FileDescriptorSet {
+            file: vec![
+                importing_file("Ping", &["Pong.proto"]),
+                importing_file("Pong", &["Ping.proto"]),
+            ],
+        };
+        assert_eq!(
+            pool.add_file_descriptor_set(&cycle)
+                .unwrap_err()
+                .to_string(),
+            "invalid descriptor: import cycle through Ping.proto"
+        );
+    }
+
+    #[test]
+    fn merge_sets() {
+        let mut pool = DescriptorPool::new();
+        let files = pool.files().len();
+        let clock = importing_file("Clock", &["google/protobuf/timestamp.proto"]);
+
+        // Sets sharing files can be added to the same pool, and the preloaded well-known types are
+        // kept.
+        let mut timestamp = pool
+            .get_file_by_name("google/protobuf/timestamp.proto")
+            .unwrap()
+            .clone();
+        timestamp.options = None;
+        pool.add_file_descriptor_set(&FileDescriptorSet {
+            file: vec![timestamp, clock.clone()],
+        })
+        .unwrap();
+        pool.add_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                source_code_info: Some(Default::default()),
+                ..clock.clone()
+            }],
+        })
+        .unwrap();
+        assert_eq!(pool.files().len(), files + 1);
+        assert!(pool
+            .get_file_by_name("google/protobuf/timestamp.proto")
+            .unwrap()
+            .options
+            .is_some());
+
+        let mut changed = clock;
+        changed.syntax = None;
+        let previous = pool.clone();
+        assert_eq!(
+            pool.add_file_descriptor_set(&FileDescriptorSet {
+                file: vec![changed],
+            })
+            .unwrap_err()
+            .to_string(),
+            "invalid descriptor: conflicting definitions of file Clock.proto"
+        );
+        assert_eq!(pool, previous);
+
+        let mut duplicate = importing_file("Other", &[]);
+        duplicate.message_type[0].name = Some("Clock".to_string());
+        assert_eq!(
+            pool.add_file_descriptor_set(&FileDescriptorSet {
+                file: vec![duplicate],
+            })
+            .unwrap_err()
+            .to_string(),
+            "invalid descriptor: duplicate type name: app.Clock is defined by both Clock.proto and \
+             Other.proto"
+        );
+
+        assert!(pool.decode_file_descriptor_set(b"\x0A\x05").is_err());
+        assert_eq!(pool, previous);
+    }
+
+    #[test]
+    fn json_names() {
+        assert_eq!(to_json_name("foo_bar_baz"), "fooBarBaz");
+        assert_eq!(to_json_name("foo"), "foo");
+        assert_eq!(to_json_name("_foo"), "Foo");
+    }
+}
@@ -0,0 +1,670 @@
+//! Answering the requests of the gRPC server reflection protocol from file descriptor sets.
+
+use prost::alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use prost::alloc::format;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use prost::wire::{WireReader, WireValue};
+use prost::{DecodeError, Message};
+
+use crate::reflect::DescriptorError;
+use crate::reflection::server_reflection_request::MessageRequest;
+use crate::reflection::server_reflection_response::MessageResponse;
+use crate::reflection::{
+    ErrorResponse, ExtensionNumberResponse, FileDescriptorResponse, ListServiceResponse,
+    ServerReflectionRequest, ServerReflectionResponse, ServiceResponse,
+};
+use crate::{DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorProto};
+
+/// The `INVALID_ARGUMENT` gRPC status code.
+const INVALID_ARGUMENT: i32 = 3;
+
+/// The `NOT_FOUND` gRPC status code.
+const NOT_FOUND: i32 = 5;
+
+/// An index of the files, symbols and extensions of file descriptor sets, which answers the
+/// requests of the gRPC server reflection protocol.
+///
+/// The catalog is built from encoded `FileDescriptorSet`s, such as those embedded by
+/// `prost-build`'s `Config::embed_file_descriptor_set`, or produced by `protoc --include_imports
+/// --descriptor_set_out`. Files are answered with their encoding as it appears in the sets, so
+/// that custom options are preserved.
+///
+/// The symbols of a file are its messages, fields, oneofs, enums, enum values, services, methods
+/// and extensions, named by their fully-qualified names without a leading dot. As in Protobuf,
+/// enum values are scoped by the parent of their enum.
+#[derive(Clone, Debug, Default)]
+pub struct ReflectionCatalog {
+    files: Vec<File>,
+    file_names: BTreeMap<String, usize>,
+    symbols: BTreeMap<String, usize>,
+    /// The files of the extensions, by the extended message and the field number.
+    extensions: BTreeMap<String, BTreeMap<i32, usize>>,
+    services: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug)]
+struct File {
+    name: String,
+    encoded: Vec<u8>,
+    dependencies: Vec<String>,
+}
+
+impl ReflectionCatalog {
+    /// Creates an empty catalog.
+    pub fn new() -> ReflectionCatalog {
+        ReflectionCatalog::default()
+    }
+
+    /// Adds the files of an encoded `FileDescriptorSet` to the catalog.
+    ///
+    /// A file which is already in the catalog is skipped if its encoding is identical, so that
+    /// the sets of several packages may share imports. An error is returned if a file is defined
+    /// differently, or if a symbol or an extension is defined by two files, in which case the
+    /// catalog is left unchanged.
+    pub fn add_file_descriptor_set(
+        &mut self,
+        file_descriptor_set: &[u8],
+    ) -> Result<&mut ReflectionCatalog, DescriptorError> {
+        let mut catalog = self.clone();
+        // `FileDescriptorSet.file`
+        for encoded in fields(file_descriptor_set, 1) {
+            let encoded = encoded.map_err(decode_error)?;
+            let file = FileDescriptorProto::decode(encoded).map_err(decode_error)?;
+            catalog.add_file(encoded, &file)?;
+        }
+        *self = catalog;
+        Ok(self)
+    }
+
+    /// Returns the fully-qualified names of the services, in lexicographic order.
+    pub fn list_services(&self) -> impl Iterator<Item = &str> {
+        self.services.iter().map(String::as_str)
+    }
+
+    /// Returns the encoded file with the name, followed by its transitive dependencies which are
+    /// in the catalog.
+    pub fn file_by_filename(&self, filename: &str) -> Option<Vec<&[u8]>> {
+        self.file_names
+            .get(filename)
+            .map(|&index| self.with_dependencies(index))
+    }
+
+    /// Returns the encoded file defining the symbol, followed by its transitive dependencies which
+    /// are in the catalog.
+    pub fn file_containing_symbol(&self, symbol: &str) -> Option<Vec<&[u8]>> {
+        self.symbols
+            .get(symbol)
+            .map(|&index| self.with_dependencies(index))
+    }
+
+    /// Returns the encoded file defining the extension of the message type with the field number,
+    /// followed by its transitive dependencies which are in the catalog.
+    pub fn file_containing_extension(
+        &self,
+        containing_type: &str,
+        extension_number: i32,
+    ) -> Option<Vec<&[u8]>> {
+        self.extensions
+            .get(containing_type)
+            .and_then(|extensions| extensions.get(&extension_number))
+            .map(|&index| self.with_dependencies(index))
+    }
+
+    /// Returns the field numbers of the extensions of the message type, in increasing order, or
+    /// `None` if the type is not in the catalog.
+    pub fn all_extension_numbers_of_type(&self, message_type: &str) -> Option<Vec<i32>> {
+        match self.extensions.get(message_type) {
+            Some(extensions) => Some(extensions.keys().copied().collect()),
+            None if self.symbols.contains_key(message_type) => Some(Vec::new()),
+            None => None,
+        }
+    }
+
+    /// Answers a request of the reflection protocol.
+    ///
+    /// Files are answered with all of their transitive dependencies, including those which were
+    /// sent in earlier responses of the stream. Unknown files, symbols and types are answered
+    /// with a `NOT_FOUND` error response, and requests without a message request with an
+    /// `INVALID_ARGUMENT` error response.
+    pub fn respond(&self, request: &ServerReflectionRequest) -> ServerReflectionResponse {
+        let message_response = match &request.message_request {
+            Some(MessageRequest::FileByFilename(filename)) => {
+                file_response(self.file_by_filename(filename), || {
+                    format!("file {} not found", filename)
+                })
+            }
+            Some(MessageRequest::FileContainingSymbol(symbol)) => {
+                file_response(self.file_containing_symbol(symbol), || {
+                    format!("symbol {} not found", symbol)
+                })
+            }
+            Some(MessageRequest::FileContainingExtension(extension)) => file_response(
+                self.file_containing_extension(
+                    &extension.containing_type,
+                    extension.extension_number,
+                ),
+                || {
+                    format!(
+                        "extension {} of {} not found",
+                        extension.extension_number, extension.containing_type
+                    )
+                },
+            ),
+            Some(MessageRequest::AllExtensionNumbersOfType(message_type)) => {
+                match self.all_extension_numbers_of_type(message_type) {
+                    Some(extension_number) => {
+                        MessageResponse::AllExtensionNumbersResponse(ExtensionNumberResponse {
+                            base_type_name: message_type.clone(),
+                            extension_number,
+                        })
+                    }
+                    None => error_response(NOT_FOUND, format!("type {} not found", message_type)),
+                }
+            }
+            Some(MessageRequest::ListServices(_)) => {
+                MessageResponse::ListServicesResponse(ListServiceResponse {
+                    service: self
+                        .list_services()
+                        .map(|name| ServiceResponse { name: name.into() })
+                        .collect(),
+                })
+            }
+            None => error_response(INVALID_ARGUMENT, "missing message request".into()),
+        };
+        ServerReflectionResponse {
+            valid_host: request.host.clone(),
+            original_request: Some(request.clone()),
+            message_response: Some(message_response),
+        }
+    }
+
+    fn add_file(
+        &mut self,
+        encoded: &[u8],
+        file: &FileDescriptorProto,
+    ) -> Result<(), DescriptorError> {
+        let name = file.name();
+        if let Some(&index) = self.file_names.get(name) {
+            if self.files[index].encoded == encoded {
+                return Ok(());
+            }
+            return Err(DescriptorError::new(format!(
+                "file {} is defined differently by two file descriptor sets",
+                name
+            )));
+        }
+
+        let index = self.files.len();
+        self.files.push(File {
+            name: name.into(),
+            encoded: encoded.to_vec(),
+            dependencies: file.dependency.clone(),
+        });
+        self.file_names.insert(name.into(), index);
+
+        let package = file.package();
+        for message in &file.message_type {
+            self.add_message(index, package, message)?;
+        }
+        for enum_type in &file.enum_type {
+            self.add_enum(index, package, enum_type)?;
+        }
+        for extension in &file.extension {
+            self.add_extension(index, package, extension)?;
+        }
+        for service in &file.service {
+            let service_name = full_name(package, service.name());
+            self.add_symbol(index, service_name.clone())?;
+            for method in &service.method {
+                self.add_symbol(index, full_name(&service_name, method.name()))?;
+            }
+            self.services.insert(service_name);
+        }
+        Ok(())
+    }
+
+    fn add_message(
+        &mut self,
+        index: usize,
+        scope: &str,
+        message: &DescriptorProto,
+    ) -> Result<(), DescriptorError> {
+        let name = full_name(scope, message.name());
+        self.add_symbol(index, name.clone())?;
+        for field in &message.field {
+            self.add_symbol(index, full_name(&name, field.name()))?;
+        }
+        for oneof in &message.oneof_decl {
+            self.add_symbol(index, full_name(&name, oneof.name()))?;
+        }
+        for nested in &message.nested_type {
+            self.add_message(index, &name, nested)?;
+        }
+        for enum_type in &message.enum_type {
+            self.add_enum(index, &name, enum_type)?;
+        }
+        for extension in &message.extension {
+            self.add_extension(index, &name, extension)?;
+        }
+        Ok(())
+    }
+
+    fn add_enum(
+        &mut self,
+        index: usize,
+        scope: &str,
+        enum_type: &EnumDescriptorProto,
+    ) -> Result<(), DescriptorError> {
+        self.add_symbol(index, full_name(scope, enum_type.name()))?;
+        for value in &enum_type.value {
+            self.add_symbol(index, full_name(scope, value.name()))?;
+        }
+        Ok(())
+    }
+
+    fn add_extension(
+        &mut self,
+        index: usize,
+        scope: &str,
+        extension: &FieldDescriptorProto,
+    ) -> Result<(), DescriptorError> {
+        let name = full_name(scope, extension.name());
+        let extendee = extension.extendee();
+        let extendee = extendee.strip_prefix('.').unwrap_or(extendee);
+        let previous = self
+            .extensions
+            .entry(extendee.into())
+            .or_default()
+            .insert(extension.number(), index);
+        if let Some(previous) = previous {
+            return Err(DescriptorError::new(format!(
+                "extension {} of {} is defined by both {} and {}",
+                extension.number(),
+                extendee,
+                self.files[previous].name,
+                self.files[index].name
+            )));
+        }
+        self.add_symbol(index, name)
+    }
+
+    fn add_symbol(&mut self, index: usize, symbol: String) -> Result<(), DescriptorError> {
+        if let Some(&previous) = self.symbols.get(&symbol) {
+            return Err(DescriptorError::new(format!(
+                "symbol {} is defined by both {} and {}",
+                symbol, self.files[previous].name, self.files[index].name
+            )));
+        }
+        self.symbols.insert(symbol, index);
+        Ok(())
+    }
+
+    /// Returns the encoded file, followed by its transitive dependencies in breadth-first order.
+    fn with_dependencies(&self, index: usize) -> Vec<&[u8]> {
+        let mut seen = BTreeSet::new();
+        seen.insert(index);
+        let mut queue = VecDeque::new();
+        queue.push_back(index);
+        let mut files = Vec::new();
+        while let Some(index) = queue.pop_front() {
+            let file = &self.files[index];
+            files.push(&file.encoded[..]);
+            for dependency in &file.dependencies {
+                if let Some(&dependency) = self.file_names.get(dependency) {
+                    if seen.insert(dependency) {
+                        queue.push_back(dependency);
+                    }
+                }
+            }
+        }
+        files
+    }
+}
+
+fn full_name(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.into()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn file_response(files: Option<Vec<&[u8]>>, not_found: impl FnOnce() -> String) -> MessageResponse {
+    match files {
+        Some(files) => MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+            file_descriptor_proto: files.into_iter().map(<[u8]>::to_vec).collect(),
+        }),
+        None => error_response(NOT_FOUND, not_found()),
+    }
+}
+
+fn error_response(error_code: i32, error_message: String) -> MessageResponse {
+    MessageResponse::ErrorResponse(ErrorResponse {
+        error_code,
+        error_message,
+    })
+}
+
+/// Returns the values of the length-delimited fields with the tag.
+fn fields(buf: &[u8], tag: u32) -> impl Iterator<Item = Result<&[u8], DecodeError>> {
+    WireReader::new(buf).filter_map(move |field| match field {
+        Ok((field_tag, WireValue::LengthDelimited(value))) if field_tag == tag => Some(Ok(value)),
+        Ok(_) => None,
+        Err(err) => Some(Err(err)),
+    })
+}
+
+fn decode_error(err: DecodeError) -> DescriptorError {
+    DescriptorError::new(format!("failed to decode file descriptor set: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::vec;
+
+    use crate::reflection::ExtensionRequest;
+    use crate::{
+        EnumValueDescriptorProto, FileDescriptorSet, MethodDescriptorProto, OneofDescriptorProto,
+        ServiceDescriptorProto,
+    };
+
+    fn field(name: &str, number: i32) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.into()),
+            number: Some(number),
+            ..Default::default()
+        }
+    }
+
+    fn common() -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some("common.proto".into()),
+            package: Some("shop.common".into()),
+            message_type: vec![DescriptorProto {
+                name: Some("Money".into()),
+                field: vec![field("units", 1)],
+                ..Default::default()
+            }],
+            enum_type: vec![EnumDescriptorProto {
+                name: Some("Currency".into()),
+                value: vec![EnumValueDescriptorProto {
+                    name: Some("EUR".into()),
+                    number: Some(0),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn orders() -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some("orders.proto".into()),
+            package: Some("shop.orders".into()),
+            dependency: vec!["common.proto".into(), "missing.proto".into()],
+            message_type: vec![DescriptorProto {
+                name: Some("Order".into()),
+                field: vec![field("id", 1)],
+                oneof_decl: vec![OneofDescriptorProto {
+                    name: Some("payment".into()),
+                    ..Default::default()
+                }],
+                nested_type: vec![DescriptorProto {
+                    name: Some("Line".into()),
+                    ..Default::default()
+                }],
+                extension: vec![FieldDescriptorProto {
+                    extendee: Some(".shop.common.Money".into()),
+                    ..field("discount", 100)
+                }],
+                ..Default::default()
+            }],
+            extension: vec![FieldDescriptorProto {
+                extendee: Some(".shop.common.Money".into()),
+                ..field("tax", 101)
+            }],
+            service: vec![ServiceDescriptorProto {
+                name: Some("Orders".into()),
+                method: vec![MethodDescriptorProto {
+                    name: Some("Place".into()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn encode(files: Vec<FileDescriptorProto>) -> Vec<u8> {
+        FileDescriptorSet { file: files }.encode_to_vec()
+    }
+
+    fn catalog() -> ReflectionCatalog {
+        let mut catalog = ReflectionCatalog::new();
+        catalog
+            .add_file_descriptor_set(&encode(vec![common(), orders()]))
+            .unwrap();
+        catalog
+    }
+
+    #[test]
+    fn symbols() {
+        let catalog = catalog();
+        let common = common().encode_to_vec();
+        let orders = orders().encode_to_vec();
+
+        for symbol in &[
+            "shop.orders.Order",
+            "shop.orders.Order.id",
+            "shop.orders.Order.payment",
+            "shop.orders.Order.Line",
+            "shop.orders.Order.discount",
+            "shop.orders.tax",
+            "shop.orders.Orders",
+            "shop.orders.Orders.Place",
+        ] {
+            assert_eq!(
+                catalog.file_containing_symbol(symbol),
+                Some(vec![&orders[..], &common[..]]),
+                "{}",
+                symbol
+            );
+        }
+        for symbol in &[
+            "shop.common.Money",
+            "shop.common.Currency",
+            "shop.common.EUR",
+        ] {
+            assert_eq!(
+                catalog.file_containing_symbol(symbol),
+                Some(vec![&common[..]]),
+                "{}",
+                symbol
+            );
+        }
+        assert_eq!(
+            catalog.file_containing_symbol("shop.common.Currency.EUR"),
+            None
+        );
+        assert_eq!(catalog.file_containing_symbol(".shop.orders.Order"), None);
+        assert_eq!(
+            catalog.file_by_filename("common.proto"),
+            Some(vec![&common[..]])
+        );
+        assert_eq!(catalog.file_by_filename("missing.proto"), None);
+        assert_eq!(
+            catalog.list_services().collect::<Vec<_>>(),
+            ["shop.orders.Orders"]
+        );
+    }
+
+    #[test]
+    fn extensions() {
+        let catalog = catalog();
+        let orders = orders().encode_to_vec();
+
+        assert_eq!(
+            catalog
+                .file_containing_extension("shop.common.Money", 101)
+                .unwrap()[0],
+            &orders[..]
+        );
+        assert_eq!(
+            catalog.file_containing_extension("shop.common.Money", 102),
+            None
+        );
+        assert_eq!(
+            catalog.all_extension_numbers_of_type("shop.common.Money"),
+            Some(vec![100, 101])
+        );
+        assert_eq!(
+            catalog.all_extension_numbers_of_type("shop.orders.Order"),
+            Some(vec![])
+        );
+        assert_eq!(catalog.all_extension_numbers_of_type("shop.Unknown"), None);
+    }
+
+    #[test]
+    fn preserves_encoding() {
+        // An unknown field, such as a custom option, is answered as it was encoded.
+        let mut encoded = common().encode_to_vec();
+        encoded.extend_from_slice(&[0xF8, 0x07, 0x01]);
+        let mut set = Vec::new();
+        prost::encoding::bytes::encode(1, &encoded, &mut set);
+
+        let mut catalog = ReflectionCatalog::new();
+        catalog.add_file_descriptor_set(&set).unwrap();
+        assert_eq!(
+            catalog.file_by_filename("common.proto"),
+            Some(vec![&encoded[..]])
+        );
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut catalog = catalog();
+        catalog
+            .add_file_descriptor_set(&encode(vec![common()]))
+            .unwrap();
+
+        let mut changed = common();
+        changed.enum_type.clear();
+        assert_eq!(
+            catalog
+                .add_file_descriptor_set(&encode(vec![changed]))
+                .unwrap_err()
+                .to_string(),
+            "invalid descriptor: file common.proto is defined differently by two file descriptor sets"
+        );
+
+        let mut conflicting = orders();
+        conflicting.name = Some("orders_v2.proto".into());
+        conflicting.message_type.clear();
+        conflicting.extension.clear();
+        let other = FileDescriptorProto {
+            name: Some("other.proto".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            catalog
+                .add_file_descriptor_set(&encode(vec![other, conflicting]))
+                .unwrap_err()
+                .to_string(),
+            "invalid descriptor: symbol shop.orders.Orders is defined by both orders.proto and \
+             orders_v2.proto"
+        );
+        // The catalog is unchanged by the failed set.
+        assert_eq!(catalog.file_by_filename("other.proto"), None);
+
+        let mut extension = orders();
+        extension.name = Some("tax.proto".into());
+        extension.package = Some("shop.tax".into());
+        extension.message_type.clear();
+        extension.service.clear();
+        assert_eq!(
+            catalog
+                .add_file_descriptor_set(&encode(vec![extension]))
+                .unwrap_err()
+                .to_string(),
+            "invalid descriptor: extension 101 of shop.common.Money is defined by both \
+             orders.proto and tax.proto"
+        );
+
+        assert!(catalog.add_file_descriptor_set(b"\x0A\x05").is_err());
+    }
+
+    #[test]
+    fn respond() {
+        let catalog = catalog();
+        let request = |message_request| ServerReflectionRequest {
+            host: "localhost".into(),
+            message_request: Some(message_request),
+        };
+
+        let list = request(MessageRequest::ListServices("*".into()));
+        assert_eq!(
+            catalog.respond(&list),
+            ServerReflectionResponse {
+                valid_host: "localhost".into(),
+                original_request: Some(list.clone()),
+                message_response: Some(MessageResponse::ListServicesResponse(
+                    ListServiceResponse {
+                        service: vec![ServiceResponse {
+                            name: "shop.orders.Orders".into()
+                        }],
+                    }
+                )),
+            }
+        );
+
+        let symbol = request(MessageRequest::FileContainingSymbol(
+            "shop.common.Money".into(),
+        ));
+        assert_eq!(
+            catalog.respond(&symbol).message_response,
+            Some(MessageResponse::FileDescriptorResponse(
+                FileDescriptorResponse {
+                    file_descriptor_proto: vec![common().encode_to_vec()],
+                }
+            ))
+        );
+
+        let extension = request(MessageRequest::FileContainingExtension(ExtensionRequest {
+            containing_type: "shop.common.Money".into(),
+            extension_number: 7,
+        }));
+        assert_eq!(
+            catalog.respond(&extension).message_response,
+            Some(MessageResponse::ErrorResponse(ErrorResponse {
+                error_code: NOT_FOUND,
+                error_message: "extension 7 of shop.common.Money not found".into(),
+            }))
+        );
+
+        let numbers = request(MessageRequest::AllExtensionNumbersOfType(
+            "shop.common.Money".into(),
+        ));
+        assert_eq!(
+            catalog.respond(&numbers).message_response,
+            Some(MessageResponse::AllExtensionNumbersResponse(
+                ExtensionNumberResponse {
+                    base_type_name: "shop.common.Money".into(),
+                    extension_number: vec![100, 101],
+                }
+            ))
+        );
+
+        let empty = ServerReflectionRequest::default();
+        assert_eq!(
+            catalog.respond(&empty).message_response,
+            Some(MessageResponse::ErrorResponse(ErrorResponse {
+                error_code: INVALID_ARGUMENT,
+                error_message: "missing message request".into(),
+            }))
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Serializers and deserializers for `uint32` fields mapped to `char`.
+//!
+//! Serde represents a `char` as a one character string, but the Protobuf JSON mapping represents
+//! `uint32` values as numbers. These functions serialize a `char` as its code point, and accept
+//! either a number or a string holding a number when deserializing, as the JSON mapping requires.
+//! Code points which are not Unicode scalar values are rejected.
+//!
+//! `NonZeroU32` and `NonZeroU64` fields need no helpers: serde's own implementations already
+//! serialize them as numbers, and reject zero when deserializing.
+
+use core::convert::TryFrom;
+use core::fmt;
+
+use serde_crate::de::{self, Deserializer, MapAccess, Visitor};
+use serde_crate::ser::Serializer;
+
+use crate::serde::mode;
+use crate::serde::number;
+
+/// Serializes a `char` as its code point.
+pub fn serialize<S>(value: &char, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_u32(u32::from(*value))
+}
+
+/// Deserializes a `char` from its code point.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<char, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if mode::is_binary() {
+        deserializer.deserialize_u32(CharVisitor)
+    } else {
+        deserializer.deserialize_any(CharVisitor)
+    }
+}
+
+/// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional `char` as its code point or `null`.
+    pub fn serialize<S>(value: &Option<char>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&u32::from(*value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional `char` from its code point or `null`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<char>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<char>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a Unicode code point or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<char>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<char>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<char>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+struct CharVisitor;
+
+impl<'de> Visitor<'de> for CharVisitor {
+    type Value = char;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a Unicode code point")
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<char, E>
+    where
+        E: de::Error,
+    {
+        u32::try_from(value)
+            .ok()
+            .and_then(core::char::from_u32)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<char, E>
+    where
+        E: de::Error,
+    {
+        u64::try_from(value)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+            .and_then(|value| self.visit_u64(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<char, E>
+    where
+        E: de::Error,
+    {
+        value
+            .parse::<u64>()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+            .and_then(|value| self.visit_u64(value))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<char, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        number::visit_map(map, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Glyph {
+        #[serde(with = "super")]
+        code_point: char,
+        #[serde(with = "super::option", default)]
+        fallback: Option<char>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let glyph = Glyph {
+            code_point: '€',
+            fallback: Some('E'),
+        };
+        let json = serde_json::to_string(&glyph).unwrap();
+        assert_eq!(json, r#"{"code_point":8364,"fallback":69}"#);
+        assert_eq!(serde_json::from_str::<Glyph>(&json).unwrap(), glyph);
+
+        let glyph: Glyph = serde_json::from_str(r#"{"code_point":"128512"}"#).unwrap();
+        assert_eq!(glyph.code_point, '😀');
+        assert_eq!(glyph.fallback, None);
+    }
+
+    #[test]
+    fn invalid_code_points() {
+        for json in &[
+            r#"{"code_point":55296}"#,
+            r#"{"code_point":1114112}"#,
+            r#"{"code_point":-1}"#,
+            r#"{"code_point":"a"}"#,
+            r#"{"code_point":4294967361}"#,
+        ] {
+            assert!(serde_json::from_str::<Glyph>(json).is_err(), "{}", json);
+        }
+    }
+}
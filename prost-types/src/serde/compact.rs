@@ -0,0 +1,1162 @@
+//! Integer-keyed serialization of messages, for binary formats.
+//!
+//! Messages which implement `Serialize` and `Deserialize` following the JSON mapping can be
+//! serialized with binary serde formats such as CBOR (`ciborium`) and MessagePack (`rmp-serde`),
+//! producing the same document as in JSON: fields keyed by their JSON name, 64-bit integers as
+//! strings, enum values by name, and so on. MessagePack must serialize structs as maps, with
+//! `rmp_serde::to_vec_named`, since fields holding their default value are omitted.
+//!
+//! The field names often make up most of a small message. [`Compact`] serializes a message with
+//! each field keyed by its field number instead, using the message's descriptor, and
+//! [`deserialize`] reads the result back. The values are unchanged, and the fields of nested,
+//! repeated and map fields are keyed by number as well. The well-known types keep their JSON
+//! representations, including the keys of `google.protobuf.Struct`.
+//!
+//! ```rust,ignore
+//! use prost_types::serde::compact::{self, Compact};
+//!
+//! let descriptor = pool.get_message_by_name("shop.OrderPlaced").unwrap();
+//! let buf = rmp_serde::to_vec(&Compact::new(&descriptor, &event))?;
+//!
+//! let mut deserializer = rmp_serde::Deserializer::new(&buf[..]);
+//! let event: OrderPlaced = compact::deserialize(&descriptor, &mut deserializer)?;
+//! ```
+//!
+//! Deserialization takes the format's `Deserializer`, which some crates, such as `ciborium`, don't
+//! expose; `serde_cbor` does. Fields keyed by name are accepted too, so payloads written without
+//! compaction can still be read. Keys are read with `deserialize_any`, so the format must be
+//! self-describing.
+
+use core::fmt;
+use core::mem;
+
+use prost::alloc::string::{String, ToString};
+use serde_crate::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_crate::ser::{self, Impossible, Serialize, Serializer};
+use serde_crate::Deserialize;
+
+use crate::reflect::{FieldDescriptor, Kind, MessageDescriptor};
+
+/// Serializes a message with its fields keyed by field number.
+#[derive(Debug)]
+pub struct Compact<'a, T: ?Sized> {
+    descriptor: &'a MessageDescriptor,
+    message: &'a T,
+}
+
+impl<'a, T: ?Sized> Compact<'a, T> {
+    /// Wraps a message of the descriptor's type.
+    pub fn new(descriptor: &'a MessageDescriptor, message: &'a T) -> Compact<'a, T> {
+        Compact {
+            descriptor,
+            message,
+        }
+    }
+}
+
+impl<'a, T> Serialize for Compact<'a, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Keyed {
+            value: self.message,
+            context: Context::message(self.descriptor),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializes a message of the descriptor's type, with its fields keyed by field number.
+pub fn deserialize<'de, T, D>(
+    descriptor: &MessageDescriptor,
+    deserializer: D,
+) -> Result<T, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Seed {
+        seed: core::marker::PhantomData,
+        context: Context::message(descriptor),
+    }
+    .deserialize(deserializer)
+}
+
+/// Where a value sits in a message, which determines the keys to rewrite.
+#[derive(Clone)]
+enum Context {
+    /// A message, whose fields are keyed by number.
+    Message(MessageDescriptor),
+    /// A repeated or map field, whose elements or values are messages.
+    Messages(MessageDescriptor),
+    /// Any other value, which is left as is.
+    Other,
+}
+
+impl Context {
+    fn message(descriptor: &MessageDescriptor) -> Context {
+        if descriptor.full_name().starts_with("google.protobuf.") {
+            Context::Other
+        } else {
+            Context::Message(descriptor.clone())
+        }
+    }
+
+    fn field(field: &FieldDescriptor) -> Context {
+        let kind = match field.kind() {
+            Kind::Message(entry) if field.is_map() => entry.map_entry_value_field().kind(),
+            kind => kind,
+        };
+        match kind {
+            Kind::Message(message) => match Context::message(&message) {
+                Context::Message(message) if field.is_list() || field.is_map() => {
+                    Context::Messages(message)
+                }
+                context => context,
+            },
+            _ => Context::Other,
+        }
+    }
+
+    /// Returns the context of the elements of a list, or of the values of a map.
+    fn element(&self) -> Context {
+        match self {
+            Context::Messages(message) => Context::Message(message.clone()),
+            _ => Context::Other,
+        }
+    }
+
+    /// Returns the field with the JSON or Protobuf name, if this is the context of a message.
+    fn field_by_name(&self, name: &str) -> Option<FieldDescriptor> {
+        match self {
+            Context::Message(message) => message
+                .get_field_by_json_name(name)
+                .or_else(|| message.get_field_by_name(name)),
+            _ => None,
+        }
+    }
+}
+
+/// A value serialized in a context.
+struct Keyed<'a, T: ?Sized> {
+    value: &'a T,
+    context: Context,
+}
+
+impl<'a, T> Serialize for Keyed<'a, T>
+where
+    T: ?Sized + Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match &self.context {
+            Context::Other => self.value.serialize(serializer),
+            context => self.value.serialize(KeyedSerializer {
+                inner: serializer,
+                context: context.clone(),
+            }),
+        }
+    }
+}
+
+/// Serializes structs and maps in a context as maps keyed by field number.
+struct KeyedSerializer<S> {
+    inner: S,
+    context: Context,
+}
+
+macro_rules! forward_serialize {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {$(
+        fn $method(self, $($arg: $ty),*) -> Result<S::Ok, S::Error> {
+            self.inner.$method($($arg),*)
+        }
+    )*};
+}
+
+impl<S> Serializer for KeyedSerializer<S>
+where
+    S: Serializer,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = KeyedSeq<S::SerializeSeq>;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = KeyedMap<S::SerializeMap>;
+    type SerializeStruct = KeyedMap<S::SerializeMap>;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    forward_serialize! {
+        serialize_bool(value: bool);
+        serialize_i8(value: i8);
+        serialize_i16(value: i16);
+        serialize_i32(value: i32);
+        serialize_i64(value: i64);
+        serialize_i128(value: i128);
+        serialize_u8(value: u8);
+        serialize_u16(value: u16);
+        serialize_u32(value: u32);
+        serialize_u64(value: u64);
+        serialize_u128(value: u128);
+        serialize_f32(value: f32);
+        serialize_f64(value: f64);
+        serialize_char(value: char);
+        serialize_str(value: &str);
+        serialize_bytes(value: &[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(name: &'static str);
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str);
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_some(&Keyed {
+            value,
+            context: self.context,
+        })
+    }
+
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let value = Keyed {
+            value,
+            context: self.context,
+        };
+        self.inner.serialize_newtype_struct(name, &value)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner
+            .serialize_newtype_variant(name, index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        Ok(KeyedSeq {
+            inner: self.inner.serialize_seq(len)?,
+            context: self.context.element(),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<S::SerializeTuple, S::Error> {
+        self.inner.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleStruct, S::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleVariant, S::Error> {
+        self.inner
+            .serialize_tuple_variant(name, index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        Ok(KeyedMap {
+            inner: self.inner.serialize_map(len)?,
+            context: self.context,
+            value_context: Context::Other,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeStructVariant, S::Error> {
+        self.inner
+            .serialize_struct_variant(name, index, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+struct KeyedSeq<S> {
+    inner: S,
+    context: Context,
+}
+
+impl<S> ser::SerializeSeq for KeyedSeq<S>
+where
+    S: ser::SerializeSeq,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_element(&Keyed {
+            value,
+            context: self.context.clone(),
+        })
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
+
+/// Serializes the fields of a message, or the entries of a map field.
+///
+/// Messages with a oneof are serialized as maps rather than structs, since the oneof is
+/// flattened into them, so both key their fields here.
+struct KeyedMap<S> {
+    inner: S,
+    context: Context,
+    value_context: Context,
+}
+
+impl<S> KeyedMap<S>
+where
+    S: ser::SerializeMap,
+{
+    fn serialize_field_key(&mut self, name: &str) -> Result<(), S::Error> {
+        match self.context.field_by_name(name) {
+            Some(field) => {
+                self.value_context = Context::field(&field);
+                self.inner.serialize_key(&field.number())
+            }
+            None => {
+                self.value_context = Context::Other;
+                self.inner.serialize_key(name)
+            }
+        }
+    }
+}
+
+impl<S> ser::SerializeMap for KeyedMap<S>
+where
+    S: ser::SerializeMap,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if let Context::Message(_) = self.context {
+            if let Ok(name) = key.serialize(FieldName) {
+                return self.serialize_field_key(&name);
+            }
+        }
+        self.value_context = self.context.element();
+        self.inner.serialize_key(key)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.inner.serialize_value(&Keyed {
+            value,
+            context: mem::replace(&mut self.value_context, Context::Other),
+        })
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
+
+impl<S> ser::SerializeStruct for KeyedMap<S>
+where
+    S: ser::SerializeMap,
+{
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.serialize_field_key(key)?;
+        ser::SerializeMap::serialize_value(self, value)
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        self.inner.end()
+    }
+}
+
+/// Extracts the name of a field from a map key, failing for keys which are not strings.
+struct FieldName;
+
+#[derive(Debug)]
+struct NotAString;
+
+impl fmt::Display for NotAString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("map key is not a string")
+    }
+}
+
+impl ser::StdError for NotAString {}
+
+impl ser::Error for NotAString {
+    fn custom<T>(_msg: T) -> NotAString
+    where
+        T: fmt::Display,
+    {
+        NotAString
+    }
+}
+
+macro_rules! not_a_string {
+    ($($method:ident($($ty:ty),*);)*) => {$(
+        fn $method(self, $(_: $ty),*) -> Result<String, NotAString> {
+            Err(NotAString)
+        }
+    )*};
+}
+
+impl Serializer for FieldName {
+    type Ok = String;
+    type Error = NotAString;
+    type SerializeSeq = Impossible<String, NotAString>;
+    type SerializeTuple = Impossible<String, NotAString>;
+    type SerializeTupleStruct = Impossible<String, NotAString>;
+    type SerializeTupleVariant = Impossible<String, NotAString>;
+    type SerializeMap = Impossible<String, NotAString>;
+    type SerializeStruct = Impossible<String, NotAString>;
+    type SerializeStructVariant = Impossible<String, NotAString>;
+
+    fn serialize_str(self, value: &str) -> Result<String, NotAString> {
+        Ok(value.to_string())
+    }
+
+    not_a_string! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T>(self, _: &T) -> Result<String, NotAString>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotAString)
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<String, NotAString>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<String, NotAString>
+    where
+        T: ?Sized + Serialize,
+    {
+        Err(NotAString)
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, NotAString> {
+        Err(NotAString)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, NotAString> {
+        Err(NotAString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotAString> {
+        Err(NotAString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotAString> {
+        Err(NotAString)
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, NotAString> {
+        Err(NotAString)
+    }
+
+    fn serialize_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStruct, NotAString> {
+        Err(NotAString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, NotAString> {
+        Err(NotAString)
+    }
+}
+
+/// Deserializes a value in a context.
+struct Seed<T> {
+    seed: T,
+    context: Context,
+}
+
+impl<'de, T> DeserializeSeed<'de> for Seed<T>
+where
+    T: DeserializeSeed<'de>,
+{
+    type Value = T::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match self.context {
+            Context::Other => self.seed.deserialize(deserializer),
+            context => self.seed.deserialize(KeyedDeserializer {
+                inner: deserializer,
+                context,
+            }),
+        }
+    }
+}
+
+/// Presents the maps of a context to the visitors with their fields keyed by name.
+struct KeyedDeserializer<D> {
+    inner: D,
+    context: Context,
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {$(
+        fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, D::Error>
+        where
+            V: Visitor<'de>,
+        {
+            let visitor = KeyedVisitor {
+                inner: visitor,
+                context: self.context,
+            };
+            self.inner.$method($($arg,)* visitor)
+        }
+    )*};
+}
+
+impl<'de, D> Deserializer<'de> for KeyedDeserializer<D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    forward_deserialize! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+struct KeyedVisitor<V> {
+    inner: V,
+    context: Context,
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($ty:ty);)*) => {$(
+        fn $method<E>(self, value: $ty) -> Result<V::Value, E>
+        where
+            E: de::Error,
+        {
+            self.inner.$method(value)
+        }
+    )*};
+}
+
+impl<'de, V> Visitor<'de> for KeyedVisitor<V>
+where
+    V: Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    forward_visit! {
+        visit_bool(bool);
+        visit_i8(i8);
+        visit_i16(i16);
+        visit_i32(i32);
+        visit_i64(i64);
+        visit_i128(i128);
+        visit_u8(u8);
+        visit_u16(u16);
+        visit_u32(u32);
+        visit_u64(u64);
+        visit_u128(u128);
+        visit_f32(f32);
+        visit_f64(f64);
+        visit_char(char);
+        visit_str(&str);
+        visit_borrowed_str(&'de str);
+        visit_string(String);
+        visit_bytes(&[u8]);
+        visit_borrowed_bytes(&'de [u8]);
+        visit_byte_buf(prost::alloc::vec::Vec<u8>);
+    }
+
+    fn visit_none<E>(self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E>(self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.inner.visit_some(KeyedDeserializer {
+            inner: deserializer,
+            context: self.context,
+        })
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.inner.visit_newtype_struct(KeyedDeserializer {
+            inner: deserializer,
+            context: self.context,
+        })
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<V::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.inner.visit_seq(KeyedSeqAccess {
+            inner: seq,
+            context: self.context.element(),
+        })
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<V::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.inner.visit_map(KeyedMapAccess {
+            inner: map,
+            context: self.context,
+            value_context: Context::Other,
+        })
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<V::Value, A::Error>
+    where
+        A: de::EnumAccess<'de>,
+    {
+        self.inner.visit_enum(data)
+    }
+}
+
+struct KeyedSeqAccess<A> {
+    inner: A,
+    context: Context,
+}
+
+impl<'de, A> SeqAccess<'de> for KeyedSeqAccess<A>
+where
+    A: SeqAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.inner.next_element_seed(Seed {
+            seed,
+            context: self.context.clone(),
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+struct KeyedMapAccess<A> {
+    inner: A,
+    context: Context,
+    value_context: Context,
+}
+
+impl<'de, A> MapAccess<'de> for KeyedMapAccess<A>
+where
+    A: MapAccess<'de>,
+{
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, A::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let message = match &self.context {
+            Context::Message(message) => message,
+            context => {
+                self.value_context = context.element();
+                return self.inner.next_key_seed(seed);
+            }
+        };
+        let field = match self.inner.next_key_seed(FieldKeySeed)? {
+            Some(FieldKey::Number(number)) => match message.get_field(number) {
+                Some(field) => Ok(field),
+                None => Err(number.to_string()),
+            },
+            Some(FieldKey::Name(name)) => self.context.field_by_name(&name).ok_or(name),
+            None => return Ok(None),
+        };
+        // Keys which are not fields of the message are passed on, to be ignored.
+        let name = match field {
+            Ok(field) => {
+                self.value_context = Context::field(&field);
+                field.json_name().to_string()
+            }
+            Err(name) => {
+                self.value_context = Context::Other;
+                name
+            }
+        };
+        seed.deserialize(de::value::StringDeserializer::<A::Error>::new(name))
+            .map(Some)
+    }
+
+    fn next_value_seed<T>(&mut self, seed: T) -> Result<T::Value, A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.inner.next_value_seed(Seed {
+            seed,
+            context: mem::replace(&mut self.value_context, Context::Other),
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+/// The key of a field, either its number or its name.
+enum FieldKey {
+    Number(u32),
+    Name(String),
+}
+
+struct FieldKeySeed;
+
+impl<'de> DeserializeSeed<'de> for FieldKeySeed {
+    type Value = FieldKey;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<FieldKey, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for FieldKeySeed {
+    type Value = FieldKey;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a field number or name")
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<FieldKey, E>
+    where
+        E: de::Error,
+    {
+        core::convert::TryFrom::try_from(value)
+            .map(FieldKey::Number)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<FieldKey, E>
+    where
+        E: de::Error,
+    {
+        core::convert::TryFrom::try_from(value)
+            .map(FieldKey::Number)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<FieldKey, E>
+    where
+        E: de::Error,
+    {
+        // Formats with string keys only, such as JSON, write field numbers as strings.
+        match value.parse() {
+            Ok(number) => Ok(FieldKey::Number(number)),
+            Err(_) => Ok(FieldKey::Name(value.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::collections::BTreeMap;
+    use prost::alloc::vec::Vec;
+    use serde_crate::Serialize;
+
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, typed_field};
+    use crate::serde::OneofFields;
+    use crate::{
+        DescriptorPool, DescriptorProto, FieldDescriptorProto, FileDescriptorProto,
+        FileDescriptorSet, MessageOptions, OneofDescriptorProto,
+    };
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", default)]
+    struct Order {
+        #[serde(
+            rename = "id",
+            skip_serializing_if = "crate::serde::is_default",
+            with = "crate::serde::int64"
+        )]
+        id: i64,
+        #[serde(
+            rename = "lineItems",
+            alias = "line_items",
+            skip_serializing_if = "crate::serde::is_default"
+        )]
+        line_items: Vec<Item>,
+        #[serde(
+            rename = "bySku",
+            alias = "by_sku",
+            skip_serializing_if = "crate::serde::is_default",
+            with = "crate::serde::map"
+        )]
+        by_sku: BTreeMap<String, Item>,
+        #[serde(flatten, deserialize_with = "crate::serde::oneof::deserialize")]
+        detail: Option<Detail>,
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", default)]
+    struct Item {
+        #[serde(rename = "sku", skip_serializing_if = "crate::serde::is_default")]
+        sku: String,
+        #[serde(rename = "quantity", skip_serializing_if = "crate::serde::is_default")]
+        quantity: u32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    enum Detail {
+        #[serde(rename = "note")]
+        Note(String),
+        #[serde(rename = "code")]
+        Code(i32),
+    }
+
+    impl OneofFields for Detail {
+        const NAME: &'static str = "test.Order.detail";
+        const FIELDS: &'static [&'static str] = &["note", "code"];
+    }
+
+    fn descriptor() -> MessageDescriptor {
+        let json = |field: FieldDescriptorProto, json_name: &str| FieldDescriptorProto {
+            json_name: Some(json_name.to_string()),
+            ..field
+        };
+        let oneof = |field: FieldDescriptorProto| FieldDescriptorProto {
+            oneof_index: Some(0),
+            ..field
+        };
+        let entry = DescriptorProto {
+            name: Some("BySkuEntry".to_string()),
+            field: vec![
+                field("key", 1, Label::Optional, Type::String),
+                typed_field("value", 2, Label::Optional, Type::Message, ".test.Item"),
+            ],
+            options: Some(MessageOptions {
+                map_entry: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let order = DescriptorProto {
+            name: Some("Order".to_string()),
+            field: vec![
+                field("id", 1, Label::Optional, Type::Int64),
+                json(
+                    typed_field(
+                        "line_items",
+                        2,
+                        Label::Repeated,
+                        Type::Message,
+                        ".test.Item",
+                    ),
+                    "lineItems",
+                ),
+                json(
+                    typed_field(
+                        "by_sku",
+                        3,
+                        Label::Repeated,
+                        Type::Message,
+                        ".test.Order.BySkuEntry",
+                    ),
+                    "bySku",
+                ),
+                oneof(field("note", 5, Label::Optional, Type::String)),
+                oneof(field("code", 6, Label::Optional, Type::Int32)),
+            ],
+            nested_type: vec![entry],
+            oneof_decl: vec![OneofDescriptorProto {
+                name: Some("detail".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let item = DescriptorProto {
+            name: Some("Item".to_string()),
+            field: vec![
+                field("sku", 1, Label::Optional, Type::String),
+                field("quantity", 2, Label::Optional, Type::Uint32),
+            ],
+            ..Default::default()
+        };
+        let pool = DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("test.proto".to_string()),
+                package: Some("test".to_string()),
+                message_type: vec![order, item],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        })
+        .unwrap();
+        pool.get_message_by_name("test.Order").unwrap()
+    }
+
+    fn order() -> Order {
+        let item = |sku: &str, quantity| Item {
+            sku: sku.to_string(),
+            quantity,
+        };
+        let mut by_sku = BTreeMap::new();
+        by_sku.insert("b".to_string(), item("b", 1));
+        Order {
+            id: -3,
+            line_items: vec![item("a", 2), Item::default()],
+            by_sku,
+            detail: Some(Detail::Note("hi".to_string())),
+        }
+    }
+
+    #[test]
+    fn cbor() {
+        use ciborium::value::{Integer, Value};
+
+        let descriptor = descriptor();
+        let order = order();
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&Compact::new(&descriptor, &order), &mut cbor).unwrap();
+
+        let item = |fields: Vec<(u32, Value)>| {
+            Value::Map(
+                fields
+                    .into_iter()
+                    .map(|(number, value)| (Value::Integer(Integer::from(number)), value))
+                    .collect(),
+            )
+        };
+        let expected = item(vec![
+            (1, Value::Text("-3".to_string())),
+            (
+                2,
+                Value::Array(vec![
+                    item(vec![
+                        (1, Value::Text("a".to_string())),
+                        (2, Value::Integer(Integer::from(2))),
+                    ]),
+                    item(vec![]),
+                ]),
+            ),
+            (
+                3,
+                Value::Map(vec![(
+                    Value::Text("b".to_string()),
+                    item(vec![
+                        (1, Value::Text("b".to_string())),
+                        (2, Value::Integer(Integer::from(1))),
+                    ]),
+                )]),
+            ),
+            (5, Value::Text("hi".to_string())),
+        ]);
+        let value: Value = ciborium::de::from_reader(&cbor[..]).unwrap();
+        assert_eq!(value, expected);
+
+        let mut deserializer = serde_cbor::Deserializer::from_slice(&cbor);
+        let decoded: Order = deserialize(&descriptor, &mut deserializer).unwrap();
+        assert_eq!(decoded, order);
+
+        // Fields keyed by name are accepted too.
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&order, &mut cbor).unwrap();
+        let mut deserializer = serde_cbor::Deserializer::from_slice(&cbor);
+        let decoded: Order = deserialize(&descriptor, &mut deserializer).unwrap();
+        assert_eq!(decoded, order);
+    }
+
+    #[test]
+    fn message_pack() {
+        let descriptor = descriptor();
+        let order = order();
+        let compact = rmp_serde::to_vec_named(&Compact::new(&descriptor, &order)).unwrap();
+        let named = rmp_serde::to_vec_named(&order).unwrap();
+        assert!(compact.len() < named.len());
+
+        for buf in &[compact, named] {
+            let mut deserializer = rmp_serde::Deserializer::new(&buf[..]);
+            let decoded: Order = deserialize(&descriptor, &mut deserializer).unwrap();
+            assert_eq!(decoded, order);
+        }
+    }
+
+    #[test]
+    fn json() {
+        let descriptor = descriptor();
+        let order = Order {
+            id: 7,
+            detail: Some(Detail::Code(4)),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&Compact::new(&descriptor, &order)).unwrap();
+        assert_eq!(json, r#"{"1":"7","6":4}"#);
+
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let decoded: Order = deserialize(&descriptor, &mut deserializer).unwrap();
+        assert_eq!(decoded, order);
+
+        // Unknown field numbers are ignored, like unknown field names.
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{"1":"7","9":[1]}"#);
+        let decoded: Order = deserialize(&descriptor, &mut deserializer).unwrap();
+        assert_eq!(decoded.id, 7);
+    }
+}
@@ -0,0 +1,330 @@
+//! Serializers and deserializers for `google.protobuf.Duration` fields.
+//!
+//! Durations are serialized as a number of seconds with an `s` suffix, e.g. `"-1.500s"`, with 0, 3,
+//! 6 or 9 fractional digits, and deserialized from a number of seconds with up to 9 fractional
+//! digits.
+//!
+//! Serialization normalizes the duration, and fails only if the normalized duration is longer
+//! than about 10,000 years. The [`strict`] module instead fails for any duration which is not
+//! already valid, as checked by [`Duration::is_valid`], matching the reference implementation.
+
+use core::fmt;
+
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde_crate::ser::{self, Serialize, Serializer};
+
+use crate::{Duration, DurationError};
+
+/// Serializes a duration as a string in seconds, normalizing it first.
+pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value.checked_normalize() {
+        Some(duration) => serializer.collect_str(&duration),
+        None => Err(ser::Error::custom(format_args!(
+            "duration out of range: {:?}",
+            value
+        ))),
+    }
+}
+
+/// Deserializes a duration from a string in seconds.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_string(DurationVisitor)
+}
+
+/// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional duration as a string in seconds or `null`.
+    pub fn serialize<S>(value: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&Normalized(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional duration from a string in seconds or `null`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<Duration>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a duration in seconds or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<Duration>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<Duration>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<Duration>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of durations as an array of strings in seconds.
+    pub fn serialize<S>(values: &[Duration], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(Normalized))
+    }
+
+    /// Deserializes a list of durations from an array of strings in seconds.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Duration>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor)
+    }
+
+    struct SeqVisitor;
+
+    impl<'de> Visitor<'de> for SeqVisitor {
+        type Value = Vec<Duration>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of strings in seconds")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Duration>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element::<Element>()? {
+                values.push(value.0);
+            }
+            Ok(values)
+        }
+    }
+
+    struct Element(Duration);
+
+    impl<'de> Deserialize<'de> for Element {
+        fn deserialize<D>(deserializer: D) -> Result<Element, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Element)
+        }
+    }
+}
+
+/// Serializers and deserializers which reject durations that are not valid, rather than
+/// normalizing them.
+pub mod strict {
+    use super::*;
+
+    pub use super::deserialize;
+
+    /// Serializes a duration as a string in seconds, failing if it is not valid.
+    pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if value.is_valid() {
+            serializer.collect_str(value)
+        } else {
+            Err(ser::Error::custom(format_args!(
+                "invalid duration: {:?}",
+                value
+            )))
+        }
+    }
+}
+
+struct Normalized<'a>(&'a Duration);
+
+impl Serialize for Normalized<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(self.0, serializer)
+    }
+}
+
+struct DurationVisitor;
+
+impl<'de> Visitor<'de> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a duration in seconds")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Duration, E>
+    where
+        E: de::Error,
+    {
+        value.parse().map_err(|err| match err {
+            DurationError::OutOfRange => E::invalid_value(
+                de::Unexpected::Str(value),
+                &"a duration of at most 315576000000 seconds",
+            ),
+            _ => E::invalid_value(de::Unexpected::Str(value), &self),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    use crate::Duration;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Timeout {
+        #[serde(with = "super")]
+        after: Duration,
+        #[serde(with = "super::option", default)]
+        retry: Option<Duration>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Strict {
+        #[serde(with = "super::strict")]
+        after: Duration,
+    }
+
+    #[test]
+    fn round_trip() {
+        let timeout = Timeout {
+            after: Duration {
+                seconds: -1,
+                nanos: -500_000_000,
+            },
+            retry: Some(Duration::default()),
+        };
+        let json = serde_json::to_string(&timeout).unwrap();
+        assert_eq!(json, r#"{"after":"-1.500s","retry":"0s"}"#);
+        assert_eq!(serde_json::from_str::<Timeout>(&json).unwrap(), timeout);
+
+        let timeout: Timeout = serde_json::from_str(r#"{"after":"0.000000001s"}"#).unwrap();
+        assert_eq!(
+            timeout.after,
+            Duration {
+                seconds: 0,
+                nanos: 1
+            }
+        );
+        assert_eq!(timeout.retry, None);
+
+        for json in &[
+            r#"{"after":"1"}"#,
+            r#"{"after":"315576000001s"}"#,
+            r#"{"after":1.5}"#,
+        ] {
+            assert!(serde_json::from_str::<Timeout>(json).is_err(), "{}", json);
+        }
+
+        let error = serde_json::from_str::<Timeout>(r#"{"after":"1.5"}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value: string \"1.5\", expected a duration in seconds at line 1 column 14"
+        );
+        let error = serde_json::from_str::<Timeout>(r#"{"after":"315576000001s"}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value: string \"315576000001s\", expected a duration of at most 315576000000 \
+             seconds at line 1 column 24"
+        );
+    }
+
+    #[test]
+    fn normalization() {
+        let unnormalized = Duration {
+            seconds: 1,
+            nanos: -500_000_000,
+        };
+        let timeout = Timeout {
+            after: unnormalized.clone(),
+            retry: Some(unnormalized.clone()),
+        };
+        assert_eq!(
+            serde_json::to_string(&timeout).unwrap(),
+            r#"{"after":"0.500s","retry":"0.500s"}"#
+        );
+        assert!(serde_json::to_string(&Strict {
+            after: unnormalized
+        })
+        .is_err());
+
+        let out_of_range = Duration {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        assert!(serde_json::to_string(&Strict {
+            after: out_of_range.clone()
+        })
+        .is_err());
+        let timeout = Timeout {
+            after: out_of_range,
+            retry: None,
+        };
+        assert!(serde_json::to_string(&timeout).is_err());
+    }
+
+    #[test]
+    fn repeated() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Backoff {
+            #[serde(with = "super::repeated")]
+            delays: Vec<Duration>,
+        }
+
+        let backoff = Backoff {
+            delays: vec![
+                Duration {
+                    seconds: 0,
+                    nanos: 1_500_000_000,
+                },
+                Duration::default(),
+            ],
+        };
+        let json = serde_json::to_string(&backoff).unwrap();
+        assert_eq!(json, r#"{"delays":["1.500s","0s"]}"#);
+        let decoded: Backoff = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.delays[0].seconds, 1);
+        assert!(serde_json::from_str::<Backoff>(r#"{"delays":[null]}"#).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+//! Serializers and deserializers for string-keyed `map` fields whose keys are durations.
+//!
+//! Protobuf does not allow message map keys, so aggregates bucketed by age or latency are commonly
+//! stored in a `map<string, V>` keyed by the bound of each bucket as a number of seconds. The same
+//! duration can be spelled in many ways, with different numbers of fractional digits, which breaks
+//! lookups, round-trips and hashes computed over the serialized form.
+//!
+//! The [`serialize`] and [`deserialize`] functions can be used with `#[serde(with = "...")]` on
+//! such maps, or wired by `prost-build` with `Config::duration_map_keys`, to normalize each key to
+//! the canonical form produced by the `Display` implementation of [`Duration`](crate::Duration),
+//! e.g. `"1.500s"`. Keys which are not valid durations are rejected, as are maps with two keys
+//! holding the same duration.
+//!
+//! Maps keyed by [`Duration`](crate::Duration) itself can use [`map`](super::map), which renders
+//! the keys in the same canonical form.
+
+use prost::alloc::string::{String, ToString};
+use serde_crate::de::{Deserialize, Deserializer};
+use serde_crate::ser::{Serialize, Serializer};
+
+use super::map;
+use crate::datetime::parse_duration;
+
+/// Returns the canonical form of a duration map key, or `None` if the key is not a valid
+/// duration.
+pub fn canonicalize(key: &str) -> Option<String> {
+    parse_duration(key).map(|duration| duration.to_string())
+}
+
+/// Serializes a string-keyed map, canonicalizing the keys.
+///
+/// Fails if a key is not a valid duration, or if two keys have the same canonical form.
+pub fn serialize<'a, M, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a String, &'a V)>,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    map::serialize_canonical(map, serializer, canonicalize, "duration")
+}
+
+/// Deserializes a string-keyed map, canonicalizing the keys.
+///
+/// Fails if a key is not a valid duration, or if two keys have the same canonical form.
+pub fn deserialize<'de, M, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: Default + Extend<(String, V)> + IntoIterator<Item = (String, V)>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    map::deserialize_canonical(deserializer, canonicalize, "duration")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn canonical_keys() {
+        assert_eq!(canonicalize("1.5s").as_deref(), Some("1.500s"));
+        assert_eq!(canonicalize("60.000000000s").as_deref(), Some("60s"));
+        assert_eq!(canonicalize("-0.000001s").as_deref(), Some("-0.000001s"));
+        assert_eq!(canonicalize("1m"), None);
+        assert_eq!(canonicalize("1"), None);
+    }
+
+    #[test]
+    fn serde_map() {
+        let mut de = serde_json::Deserializer::from_str(r#"{"0.25s": 1, "10s": 2}"#);
+        let map: BTreeMap<String, i32> = deserialize(&mut de).unwrap();
+        assert_eq!(
+            map.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["0.250s", "10s"]
+        );
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"10s": 1, "10.0s": 2}"#);
+        assert!(deserialize::<BTreeMap<String, i32>, _, _>(&mut de).is_err());
+
+        let mut map = BTreeMap::new();
+        map.insert("3.100s".to_string(), 1);
+        let mut buf = Vec::new();
+        serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+        assert_eq!(std::str::from_utf8(&buf).unwrap(), r#"{"3.100s":1}"#);
+
+        map.insert("3.1s".to_string(), 2);
+        let mut buf = Vec::new();
+        assert!(serialize(&map, &mut serde_json::Serializer::new(&mut buf)).is_err());
+    }
+}
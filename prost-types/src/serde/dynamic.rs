@@ -0,0 +1,201 @@
+//! Serialization of dynamic messages, following the JSON mapping.
+//!
+//! Fields are rendered as by the serializers which `prost-build` generates, honoring the current
+//! [`mode`](crate::serde::mode), and the well-known types by their special JSON forms. Fields which
+//! the descriptor doesn't declare are omitted. A `google.protobuf.Any` is rendered with the message
+//! it holds, whose type must be in the pool of its descriptor.
+
+use prost::alloc::string::ToString;
+use prost::Message;
+use serde_crate::ser::{self, Serialize, SerializeMap, Serializer};
+
+use crate::dynamic::{self, DynamicMessage};
+use crate::reflect::{FieldDescriptor, Kind};
+use crate::serde::enumeration;
+use crate::serde::mode::{self, Mode};
+use crate::{Duration, FieldMask, ListValue, Struct, Timestamp, Value};
+
+impl Serialize for DynamicMessage {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if is_well_known(self.descriptor().full_name()) {
+            return WellKnown(self).serialize(serializer);
+        }
+        let mut map = serializer.serialize_map(None)?;
+        serialize_fields(self, &mut map)?;
+        map.end()
+    }
+}
+
+/// Returns `true` if the message type is rendered by a special JSON form.
+fn is_well_known(full_name: &str) -> bool {
+    matches!(
+        full_name,
+        "google.protobuf.Any"
+            | "google.protobuf.Timestamp"
+            | "google.protobuf.Duration"
+            | "google.protobuf.FieldMask"
+            | "google.protobuf.Struct"
+            | "google.protobuf.Value"
+            | "google.protobuf.ListValue"
+            | "google.protobuf.Empty"
+            | "google.protobuf.DoubleValue"
+            | "google.protobuf.FloatValue"
+            | "google.protobuf.Int64Value"
+            | "google.protobuf.UInt64Value"
+            | "google.protobuf.Int32Value"
+            | "google.protobuf.UInt32Value"
+            | "google.protobuf.BoolValue"
+            | "google.protobuf.StringValue"
+            | "google.protobuf.BytesValue"
+    )
+}
+
+/// Serializes the set fields of a message as the entries of a map, by their JSON names.
+fn serialize_fields<M>(message: &DynamicMessage, map: &mut M) -> Result<(), M::Error>
+where
+    M: SerializeMap,
+{
+    for (field, value) in message.set_fields() {
+        map.serialize_entry(field.json_name(), &FieldValue(&field, value))?;
+    }
+    Ok(())
+}
+
+/// Converts a dynamic message of a well-known type to the corresponding Rust type.
+fn convert<T, E>(message: &DynamicMessage) -> Result<T, E>
+where
+    T: Message + Default,
+    E: ser::Error,
+{
+    T::decode(message.encode_to_vec().as_slice()).map_err(E::custom)
+}
+
+/// A message of a well-known type, rendered by its special JSON form.
+struct WellKnown<'a>(&'a DynamicMessage);
+
+impl Serialize for WellKnown<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let message = self.0;
+        match message.descriptor().full_name() {
+            "google.protobuf.Any" => serialize_any(message, serializer),
+            "google.protobuf.Timestamp" => {
+                crate::serde::timestamp::serialize(&convert::<Timestamp, _>(message)?, serializer)
+            }
+            "google.protobuf.Duration" => {
+                crate::serde::duration::serialize(&convert::<Duration, _>(message)?, serializer)
+            }
+            "google.protobuf.FieldMask" => convert::<FieldMask, _>(message)?.serialize(serializer),
+            "google.protobuf.Struct" => convert::<Struct, _>(message)?.serialize(serializer),
+            "google.protobuf.Value" => convert::<Value, _>(message)?.serialize(serializer),
+            "google.protobuf.ListValue" => convert::<ListValue, _>(message)?.serialize(serializer),
+            "google.protobuf.Empty" => crate::serde::empty::serialize(&(), serializer),
+            // The wrapper types, which are rendered as their value.
+            _ => {
+                let field = message
+                    .descriptor()
+                    .get_field(1)
+                    .ok_or_else(|| ser::Error::custom("wrapper type without a value field"))?;
+                FieldValue(&field, &message.get_field(&field)).serialize(serializer)
+            }
+        }
+    }
+}
+
+/// Serializes a `google.protobuf.Any` with its `@type` and the fields of the message it holds, or
+/// the special JSON form of a well-known type as its `value`.
+fn serialize_any<S>(message: &DynamicMessage, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let any = convert::<crate::Any, _>(message)?;
+    let descriptor = message
+        .descriptor()
+        .parent_pool()
+        .get_message_by_name(any.type_name())
+        .ok_or_else(|| ser::Error::custom(format_args!("unknown type in Any: {}", any.type_url)))?;
+    let packed = DynamicMessage::decode(descriptor, &*any.value).map_err(ser::Error::custom)?;
+
+    let mut map = serializer.serialize_map(None)?;
+    map.serialize_entry("@type", &any.type_url)?;
+    if is_well_known(packed.descriptor().full_name()) {
+        map.serialize_entry("value", &WellKnown(&packed))?;
+    } else {
+        serialize_fields(&packed, &mut map)?;
+    }
+    map.end()
+}
+
+/// The value of a field, which is a list for repeated fields and a map for map fields.
+struct FieldValue<'a>(&'a FieldDescriptor, &'a dynamic::Value);
+
+impl Serialize for FieldValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let FieldValue(field, value) = *self;
+        let kind = field.kind();
+        match value {
+            dynamic::Value::List(values) => {
+                serializer.collect_seq(values.iter().map(|value| Element(&kind, value)))
+            }
+            dynamic::Value::Map(entries) => {
+                let kind = match &kind {
+                    Kind::Message(entry) => entry.map_entry_value_field().kind(),
+                    _ => return Err(ser::Error::custom("map field without a map entry")),
+                };
+                // Map keys are always rendered as strings.
+                serializer.collect_map(
+                    entries
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), Element(&kind, value))),
+                )
+            }
+            value => Element(&kind, value).serialize(serializer),
+        }
+    }
+}
+
+/// A singular value, or an element of a list or a map.
+struct Element<'a>(&'a Kind, &'a dynamic::Value);
+
+impl Serialize for Element<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match (self.0, self.1) {
+            (_, dynamic::Value::Bool(value)) => serializer.serialize_bool(*value),
+            (_, dynamic::Value::I32(value)) => serializer.serialize_i32(*value),
+            (_, dynamic::Value::U32(value)) => serializer.serialize_u32(*value),
+            (_, dynamic::Value::I64(value)) => crate::serde::int64::serialize(value, serializer),
+            (_, dynamic::Value::U64(value)) => crate::serde::int64::serialize(value, serializer),
+            (_, dynamic::Value::F32(value)) => crate::serde::float::serialize(value, serializer),
+            (_, dynamic::Value::F64(value)) => crate::serde::float::serialize(value, serializer),
+            (_, dynamic::Value::String(value)) => serializer.serialize_str(value),
+            (_, dynamic::Value::Bytes(value)) => crate::serde::vec_u8::serialize(value, serializer),
+            (Kind::Enum(descriptor), dynamic::Value::EnumNumber(value)) => {
+                if descriptor.full_name() == "google.protobuf.NullValue" {
+                    return serializer.serialize_unit();
+                }
+                match descriptor.get_value(*value) {
+                    Some(name) if mode::current() == Mode::Spec && !enumeration::numbers() => {
+                        serializer.serialize_str(name)
+                    }
+                    _ => serializer.serialize_i32(*value),
+                }
+            }
+            (_, dynamic::Value::EnumNumber(value)) => serializer.serialize_i32(*value),
+            (_, dynamic::Value::Message(message)) => message.serialize(serializer),
+            (_, dynamic::Value::List(_)) | (_, dynamic::Value::Map(_)) => {
+                Err(ser::Error::custom("nested repeated field"))
+            }
+        }
+    }
+}
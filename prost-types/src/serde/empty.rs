@@ -0,0 +1,329 @@
+//! Serializers and deserializers for `google.protobuf.Empty` fields.
+//!
+//! `prost` maps `google.protobuf.Empty` to `()`, which serde represents as `null`, but the
+//! Protobuf JSON mapping represents it as an empty object, `{}`. Deserialization accepts `{}`,
+//! ignoring any fields it holds.
+//!
+//! The JSON mapping also accepts `null` for a singular field, meaning the field's default value,
+//! so the top-level functions accept `null` as `()`, and the [`option`] module as `None`. It
+//! doesn't accept `null` for the elements of repeated fields or the values of map fields, which
+//! the [`repeated`] and [`map`] modules reject.
+//!
+//! `prost-build` wires fields of every shape, including oneof fields, to these modules when
+//! `Config::enable_serde_json` is set.
+
+use core::fmt;
+
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, Visitor};
+use serde_crate::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::serde::map::{KeyString, MapKey};
+use crate::serde::mode;
+
+/// Serializes `()` as an empty object.
+pub fn serialize<S>(_: &(), serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_map(Some(0))?.end()
+}
+
+/// Deserializes `()` from an object or `null`.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<(), D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let visitor = EmptyVisitor { null: true };
+    if mode::is_binary() {
+        deserializer.deserialize_map(visitor)
+    } else {
+        deserializer.deserialize_any(visitor)
+    }
+}
+
+/// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+///
+/// Since `null` means `None` here, a present `Empty` must be written as `{}`.
+pub mod option {
+    use super::*;
+
+    /// Serializes `Some(())` as an empty object, and `None` as `null`.
+    pub fn serialize<S>(value: &Option<()>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(()) => serializer.serialize_some(&Empty),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes `Some(())` from an object, and `None` from `null`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<()>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor)
+    }
+
+    struct OptionVisitor;
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<()>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an object or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<()>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<()>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<()>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            Element::deserialize(deserializer).map(|Element| Some(()))
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of `()` as an array of empty objects.
+    pub fn serialize<S>(values: &[()], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(|()| Empty))
+    }
+
+    /// Deserializes a list of `()` from an array of objects.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<()>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let elements = Vec::<Element>::deserialize(deserializer)?;
+        Ok(elements.into_iter().map(|Element| ()).collect())
+    }
+}
+
+/// Serializers and deserializers for `map` fields with `google.protobuf.Empty` values.
+///
+/// Keys are handled as by [`crate::serde::map`], and values must be objects.
+pub mod map {
+    use super::*;
+
+    /// Serializes a map, rendering each key as a JSON string and each value as an empty object.
+    pub fn serialize<'a, M, K, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a M: IntoIterator<Item = (&'a K, &'a ())>,
+        K: MapKey + 'a,
+        S: Serializer,
+    {
+        serializer.collect_map(map.into_iter().map(|(key, ())| (KeyString(key), Empty)))
+    }
+
+    /// Deserializes a map, requiring every key to be a JSON string and every value an object.
+    pub fn deserialize<'de, M, K, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: Default + Extend<(K, ())> + IntoIterator<Item = (K, ())>,
+        K: MapKey,
+        D: Deserializer<'de>,
+    {
+        let entries: Vec<(K, Element)> = crate::serde::map::deserialize(deserializer)?;
+        let mut map = M::default();
+        map.extend(entries.into_iter().map(|(key, Element)| (key, ())));
+        Ok(map)
+    }
+}
+
+struct Empty;
+
+impl Serialize for Empty {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(&(), serializer)
+    }
+}
+
+/// An element of a repeated field or a map value, which must be an object.
+struct Element;
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D>(deserializer: D) -> Result<Element, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_map(EmptyVisitor { null: false })
+            .map(|()| Element)
+    }
+}
+
+struct EmptyVisitor {
+    /// Whether `null` is accepted.
+    null: bool,
+}
+
+impl EmptyVisitor {
+    fn null<E>(self, unexpected: de::Unexpected<'_>) -> Result<(), E>
+    where
+        E: de::Error,
+    {
+        if self.null {
+            Ok(())
+        } else {
+            Err(E::invalid_type(unexpected, &self))
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for EmptyVisitor {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.null {
+            formatter.write_str("an empty object or null")
+        } else {
+            formatter.write_str("an empty object")
+        }
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E>
+    where
+        E: de::Error,
+    {
+        self.null(de::Unexpected::Unit)
+    }
+
+    fn visit_none<E>(self) -> Result<(), E>
+    where
+        E: de::Error,
+    {
+        self.null(de::Unexpected::Option)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Sample {
+        #[serde(with = "super")]
+        empty: (),
+        #[serde(with = "super::option", default)]
+        maybe: Option<()>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let sample = Sample {
+            empty: (),
+            maybe: Some(()),
+        };
+        let json = serde_json::to_string(&sample).unwrap();
+        assert_eq!(json, r#"{"empty":{},"maybe":{}}"#);
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+
+        let json = r#"{"empty":null,"maybe":null}"#;
+        let sample: Sample = serde_json::from_str(json).unwrap();
+        assert_eq!(sample.maybe, None);
+        let json = r#"{"empty":{"unknown":[1]},"maybe":{"unknown":2}}"#;
+        let sample: Sample = serde_json::from_str(json).unwrap();
+        assert_eq!(sample.maybe, Some(()));
+
+        for json in &[
+            r#"{"empty":[]}"#,
+            r#"{"empty":0}"#,
+            r#"{"empty":{},"maybe":1}"#,
+        ] {
+            assert!(serde_json::from_str::<Sample>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn collections() {
+        use prost::alloc::collections::BTreeMap;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Collections {
+            #[serde(with = "super::repeated")]
+            list: Vec<()>,
+            #[serde(with = "super::map")]
+            set: BTreeMap<i32, ()>,
+        }
+
+        let collections = Collections {
+            list: vec![(), ()],
+            set: vec![(1, ()), (-2, ())].into_iter().collect(),
+        };
+        let json = serde_json::to_string(&collections).unwrap();
+        assert_eq!(json, r#"{"list":[{},{}],"set":{"-2":{},"1":{}}}"#);
+        assert_eq!(
+            serde_json::from_str::<Collections>(&json).unwrap(),
+            collections
+        );
+
+        let json = r#"{"list":[{"unknown":1}],"set":{"3":{"unknown":[]}}}"#;
+        let collections: Collections = serde_json::from_str(json).unwrap();
+        assert_eq!(collections.list, vec![()]);
+        assert_eq!(collections.set.keys().collect::<Vec<_>>(), vec![&3]);
+
+        // `null` is not an `Empty` in a repeated or map field.
+        let error = serde_json::from_str::<Collections>(r#"{"list":[null],"set":{}}"#)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "invalid type: null, expected an empty object at line 1 column 13"
+        );
+        for json in &[
+            r#"{"list":[],"set":{"1":null}}"#,
+            r#"{"list":[],"set":{"one":{}}}"#,
+            r#"{"list":[[]],"set":{}}"#,
+            r#"{"list":{},"set":{}}"#,
+        ] {
+            assert!(
+                serde_json::from_str::<Collections>(json).is_err(),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn cbor_null() {
+        let mut cbor = Vec::new();
+        let value = serde_json::json!({ "empty": null, "maybe": null });
+        ciborium::ser::into_writer(&value, &mut cbor).unwrap();
+        let sample: Sample = ciborium::de::from_reader(&cbor[..]).unwrap();
+        assert_eq!(sample.maybe, None);
+    }
+}
@@ -0,0 +1,735 @@
+//! Serializers and deserializers for Protobuf enum fields.
+//!
+//! `prost` represents enum fields as `i32`, which would be serialized as numbers. The Protobuf JSON
+//! mapping instead renders enum values by the name of the value, as written in the `.proto` file.
+//! Values which are not defined by the enum, which can be received from peers with a newer
+//! version of the schema, are rendered as numbers.
+//!
+//! In [`Mode::Legacy`] and [`Mode::Binary`], every value is rendered as a number. Values can also
+//! be rendered as numbers in every mode, as the `always_print_enums_as_ints` option of the
+//! reference implementations does: for chosen fields with [`number`], or during a scope with
+//! [`with_numbers`].
+//!
+//! Deserialization accepts names and numbers, including numbers in strings, as query strings
+//! provide them. A name which is not defined by the enum is mapped to the enum's default value
+//! and reported as an [`Event::UnknownEnumValue`].
+//!
+//! The functions are generic over the enum type, which implements [`EnumName`], so fields name the
+//! type explicitly:
+//!
+//! ```rust,ignore
+//! #[serde(
+//!     serialize_with = "::prost_types::serde::enumeration::serialize::<Color, _>",
+//!     deserialize_with = "::prost_types::serde::enumeration::deserialize::<Color, _>"
+//! )]
+//! pub color: i32,
+//! ```
+//!
+//! Map fields with enum values use the functions of [`map`], which are also generic over the map
+//! type.
+//!
+//! `prost-build` implements `EnumName` and generates these attributes when
+//! `Config::enable_serde_json` is set.
+
+use core::fmt;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_crate::ser::{Serialize, Serializer};
+use serde_crate::Deserialize;
+
+use crate::serde::events::{self, Event};
+use crate::serde::mode::{self, Mode};
+use crate::NullValue;
+
+/// A Protobuf enum whose values have names.
+pub trait EnumName: Default + Into<i32> {
+    /// The fully-qualified Protobuf name of the enum.
+    const FULL_NAME: &'static str;
+
+    /// Returns the name of the value, or `None` if the enum doesn't define it.
+    fn name(value: i32) -> Option<&'static str>;
+
+    /// Returns the value with the name, or `None` if the enum doesn't define it.
+    fn from_name(name: &str) -> Option<i32>;
+}
+
+impl EnumName for NullValue {
+    const FULL_NAME: &'static str = "google.protobuf.NullValue";
+
+    fn name(value: i32) -> Option<&'static str> {
+        match value {
+            0 => Some("NULL_VALUE"),
+            _ => None,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<i32> {
+        match name {
+            "NULL_VALUE" => Some(0),
+            _ => None,
+        }
+    }
+}
+
+/// Serializes an enum value as its name, or as a number if the enum doesn't define it, in
+/// [`Mode::Legacy`] and [`Mode::Binary`], or within [`with_numbers`].
+pub fn serialize<T, S>(value: &i32, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: EnumName,
+    S: Serializer,
+{
+    match T::name(*value) {
+        Some(name) if mode::current() == Mode::Spec && !numbers() => serializer.serialize_str(name),
+        _ => serializer.serialize_i32(*value),
+    }
+}
+
+/// Deserializes an enum value from its name or number.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<i32, D::Error>
+where
+    T: EnumName,
+    D: Deserializer<'de>,
+{
+    EnumVisitor::<T>(PhantomData).deserialize(deserializer)
+}
+
+/// Serializers and deserializers for `optional` enum fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional enum value as its name, a number or `null`.
+    pub fn serialize<T, S>(value: &Option<i32>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: EnumName,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&Wrapper::<T>(*value, PhantomData)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional enum value from its name, a number or `null`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+    where
+        T: EnumName,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor::<T>(PhantomData))
+    }
+
+    struct OptionVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for OptionVisitor<T>
+    where
+        T: EnumName,
+    {
+        type Value = Option<i32>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(formatter, "a {} value or null", T::FULL_NAME)
+        }
+
+        fn visit_none<E>(self) -> Result<Option<i32>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<i32>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<i32>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize::<T, D>(deserializer).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` enum fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of enum values as an array of names and numbers.
+    pub fn serialize<T, S>(values: &[i32], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: EnumName,
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(|&value| Wrapper::<T>(value, PhantomData)))
+    }
+
+    /// Deserializes a list of enum values from an array of names and numbers.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<i32>, D::Error>
+    where
+        T: EnumName,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor::<T>(PhantomData))
+    }
+
+    struct SeqVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for SeqVisitor<T>
+    where
+        T: EnumName,
+    {
+        type Value = Vec<i32>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(formatter, "an array of {} values", T::FULL_NAME)
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<i32>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element_seed(EnumVisitor::<T>(PhantomData))? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
+/// Serializers for enum fields rendered as numbers in every mode, for consumers which don't know
+/// the names of the values.
+///
+/// Deserialization still accepts names and numbers. The functions keep the enum type parameter,
+/// so fields switch between the modules by changing the path alone:
+///
+/// ```rust,ignore
+/// #[serde(
+///     serialize_with = "::prost_types::serde::enumeration::number::serialize::<Color, _>",
+///     deserialize_with = "::prost_types::serde::enumeration::number::deserialize::<Color, _>"
+/// )]
+/// pub color: i32,
+/// ```
+pub mod number {
+    use super::*;
+
+    pub use super::deserialize;
+
+    /// Serializes an enum value as a number.
+    pub fn serialize<T, S>(value: &i32, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: EnumName,
+        S: Serializer,
+    {
+        serializer.serialize_i32(*value)
+    }
+
+    /// Serializers for `optional` enum fields rendered as numbers, mapping `None` to `null`.
+    pub mod option {
+        use super::*;
+
+        pub use super::super::option::deserialize;
+
+        /// Serializes an optional enum value as a number or `null`.
+        pub fn serialize<T, S>(value: &Option<i32>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: EnumName,
+            S: Serializer,
+        {
+            value.serialize(serializer)
+        }
+    }
+
+    /// Serializers for `repeated` enum fields rendered as numbers.
+    pub mod repeated {
+        use super::*;
+
+        pub use super::super::repeated::deserialize;
+
+        /// Serializes a list of enum values as an array of numbers.
+        pub fn serialize<T, S>(values: &[i32], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: EnumName,
+            S: Serializer,
+        {
+            values.serialize(serializer)
+        }
+    }
+
+    /// Serializers for `map` fields with enum values rendered as numbers.
+    pub mod map {
+        use super::*;
+
+        use crate::serde::map::MapKey;
+
+        pub use super::super::map::deserialize;
+
+        /// Serializes a map, rendering each key as a JSON string and each value as a number.
+        pub fn serialize<'a, T, M, K, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: EnumName,
+            &'a M: IntoIterator<Item = (&'a K, &'a i32)>,
+            K: MapKey + 'a,
+            S: Serializer,
+        {
+            crate::serde::map::serialize(map, serializer)
+        }
+    }
+}
+
+/// Serializers and deserializers for `map` fields with enum values.
+///
+/// Keys are handled as by [`crate::serde::map`], and values as by [`serialize`] and
+/// [`deserialize`]. The functions are generic over the map type, so they work for both `HashMap`
+/// and `BTreeMap` fields:
+///
+/// ```rust,ignore
+/// #[serde(
+///     serialize_with = "::prost_types::serde::enumeration::map::serialize::<Color, _, _, _>",
+///     deserialize_with = "::prost_types::serde::enumeration::map::deserialize::<Color, _, _, _>"
+/// )]
+/// pub colors: HashMap<String, i32>,
+/// ```
+pub mod map {
+    use super::*;
+
+    use crate::serde::map::{KeyString, MapKey};
+
+    /// Serializes a map, rendering each key as a JSON string and each value as its name or a
+    /// number.
+    pub fn serialize<'a, T, M, K, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: EnumName,
+        &'a M: IntoIterator<Item = (&'a K, &'a i32)>,
+        K: MapKey + 'a,
+        S: Serializer,
+    {
+        serializer.collect_map(
+            map.into_iter()
+                .map(|(key, &value)| (KeyString(key), Wrapper::<T>(value, PhantomData))),
+        )
+    }
+
+    /// Deserializes a map, requiring every key to be a JSON string, and accepting each value as a
+    /// name or a number.
+    pub fn deserialize<'de, T, M, K, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        T: EnumName,
+        M: Default + Extend<(K, i32)> + IntoIterator<Item = (K, i32)>,
+        K: MapKey,
+        D: Deserializer<'de>,
+    {
+        let entries: Vec<(K, Wrapper<T>)> = crate::serde::map::deserialize(deserializer)?;
+        let mut map = M::default();
+        map.extend(entries.into_iter().map(|(key, value)| (key, value.0)));
+        Ok(map)
+    }
+}
+
+/// Set by the first call to [`with_numbers`], so that serializers can skip the thread-local lookup
+/// until then.
+#[cfg(feature = "std")]
+static SCOPED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static NUMBERS: core::cell::Cell<bool> = core::cell::Cell::new(false);
+}
+
+/// Returns `true` if enum values are rendered as numbers on the current thread.
+pub(crate) fn numbers() -> bool {
+    #[cfg(feature = "std")]
+    {
+        SCOPED.load(Ordering::Relaxed) && NUMBERS.with(core::cell::Cell::get)
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        false
+    }
+}
+
+/// Runs the closure with every enum value serialized by this module rendered as a number on the
+/// current thread, whatever the [`mode`](crate::serde::mode).
+///
+/// Calls can be nested. This requires the `std` feature.
+#[cfg(feature = "std")]
+pub fn with_numbers<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    /// Restores the enclosing setting, even if the closure panics.
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            NUMBERS.with(|numbers| numbers.set(self.0));
+        }
+    }
+
+    SCOPED.store(true, Ordering::Relaxed);
+    let _restore = Restore(NUMBERS.with(|numbers| numbers.replace(true)));
+    f()
+}
+
+struct Wrapper<T>(i32, PhantomData<fn() -> T>);
+
+impl<T> Serialize for Wrapper<T>
+where
+    T: EnumName,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize::<T, S>(&self.0, serializer)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Wrapper<T>
+where
+    T: EnumName,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Wrapper<T>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        EnumVisitor::<T>(PhantomData)
+            .deserialize(deserializer)
+            .map(|value| Wrapper(value, PhantomData))
+    }
+}
+
+struct EnumVisitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> DeserializeSeed<'de> for EnumVisitor<T>
+where
+    T: EnumName,
+{
+    type Value = i32;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<i32, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if mode::is_binary() {
+            deserializer.deserialize_i32(self)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for EnumVisitor<T>
+where
+    T: EnumName,
+{
+    type Value = i32;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "a {} value", T::FULL_NAME)
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<i32, E>
+    where
+        E: de::Error,
+    {
+        core::convert::TryFrom::try_from(value)
+            .map_err(|_| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<i32, E>
+    where
+        E: de::Error,
+    {
+        core::convert::TryFrom::try_from(value)
+            .map_err(|_| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<i32, E>
+    where
+        E: de::Error,
+    {
+        // Rust accepts a leading `+`, which is not valid JSON.
+        let number = || value.parse().ok().filter(|_| !value.starts_with('+'));
+        Ok(T::from_name(value).or_else(number).unwrap_or_else(|| {
+            events::report(&Event::UnknownEnumValue {
+                enumeration: T::FULL_NAME,
+                value,
+            });
+            T::default().into()
+        }))
+    }
+
+    fn visit_unit<E>(self) -> Result<i32, E>
+    where
+        E: de::Error,
+    {
+        // `null` stands for the default value, and is how `google.protobuf.NullValue` is written.
+        Ok(T::default().into())
+    }
+
+    fn visit_none<E>(self) -> Result<i32, E>
+    where
+        E: de::Error,
+    {
+        // CBOR deserializers visit `null` as `None` rather than as unit.
+        self.visit_unit()
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<i32, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        crate::serde::number::visit_map(map, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_crate::{Deserialize, Serialize};
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+    enum Color {
+        Red = 0,
+        Green = 1,
+    }
+
+    impl EnumName for Color {
+        const FULL_NAME: &'static str = "test.Color";
+
+        fn name(value: i32) -> Option<&'static str> {
+            match value {
+                0 => Some("COLOR_RED"),
+                1 => Some("COLOR_GREEN"),
+                _ => None,
+            }
+        }
+
+        fn from_name(name: &str) -> Option<i32> {
+            match name {
+                "COLOR_RED" => Some(0),
+                "COLOR_GREEN" => Some(1),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Sample {
+        #[serde(
+            serialize_with = "serialize::<Color, _>",
+            deserialize_with = "deserialize::<Color, _>"
+        )]
+        color: i32,
+        #[serde(
+            serialize_with = "option::serialize::<Color, _>",
+            deserialize_with = "option::deserialize::<Color, _>",
+            default
+        )]
+        maybe: Option<i32>,
+        #[serde(
+            serialize_with = "repeated::serialize::<Color, _>",
+            deserialize_with = "repeated::deserialize::<Color, _>",
+            default
+        )]
+        many: Vec<i32>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let sample = Sample {
+            color: Color::Green as i32,
+            maybe: Some(7),
+            many: vec![0, 1, -1],
+        };
+        let json = serde_json::to_string(&sample).unwrap();
+        assert_eq!(
+            json,
+            r#"{"color":"COLOR_GREEN","maybe":7,"many":["COLOR_RED","COLOR_GREEN",-1]}"#
+        );
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+
+        let sample: Sample = serde_json::from_str(r#"{"color":1,"maybe":null}"#).unwrap();
+        assert_eq!((sample.color, sample.maybe), (1, None));
+        let sample: Sample = serde_json::from_str(r#"{"color":"1","many":["-1"]}"#).unwrap();
+        assert_eq!((sample.color, sample.many), (1, vec![-1]));
+
+        for json in &[
+            r#"{"color":2147483648}"#,
+            r#"{"color":1.0}"#,
+            r#"{"color":true}"#,
+        ] {
+            assert!(serde_json::from_str::<Sample>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn legacy_mode() {
+        let sample = Sample {
+            color: Color::Green as i32,
+            maybe: Some(0),
+            many: vec![1, 7],
+        };
+        let json = mode::with(Mode::Legacy, || serde_json::to_string(&sample).unwrap());
+        assert_eq!(json, r#"{"color":1,"maybe":0,"many":[1,7]}"#);
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+    }
+
+    #[test]
+    fn numbers() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Numbers {
+            #[serde(
+                serialize_with = "number::serialize::<Color, _>",
+                deserialize_with = "number::deserialize::<Color, _>"
+            )]
+            color: i32,
+            #[serde(
+                serialize_with = "number::option::serialize::<Color, _>",
+                deserialize_with = "number::option::deserialize::<Color, _>",
+                default
+            )]
+            maybe: Option<i32>,
+            #[serde(
+                serialize_with = "number::repeated::serialize::<Color, _>",
+                deserialize_with = "number::repeated::deserialize::<Color, _>",
+                default
+            )]
+            many: Vec<i32>,
+        }
+
+        let numbers = Numbers {
+            color: Color::Green as i32,
+            maybe: Some(7),
+            many: vec![0, -1],
+        };
+        let json = serde_json::to_string(&numbers).unwrap();
+        assert_eq!(json, r#"{"color":1,"maybe":7,"many":[0,-1]}"#);
+        assert_eq!(serde_json::from_str::<Numbers>(&json).unwrap(), numbers);
+        let json = r#"{"color":"COLOR_GREEN","maybe":null,"many":["COLOR_RED","-1"]}"#;
+        let decoded: Numbers = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            decoded,
+            Numbers {
+                maybe: None,
+                ..numbers
+            }
+        );
+
+        let sample = Sample {
+            color: Color::Green as i32,
+            maybe: Some(0),
+            many: vec![1, 7],
+        };
+        let json = with_numbers(|| {
+            let inner = with_numbers(|| serde_json::to_string(&sample).unwrap());
+            assert_eq!(serde_json::to_string(&sample).unwrap(), inner);
+            inner
+        });
+        assert_eq!(json, r#"{"color":1,"maybe":0,"many":[1,7]}"#);
+        assert_eq!(
+            serde_json::to_string(&sample).unwrap(),
+            r#"{"color":"COLOR_GREEN","maybe":"COLOR_RED","many":["COLOR_GREEN",7]}"#
+        );
+    }
+
+    #[test]
+    fn maps() {
+        use std::collections::{BTreeMap, HashMap};
+
+        #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate", default)]
+        struct Maps {
+            #[serde(
+                serialize_with = "map::serialize::<Color, _, _, _>",
+                deserialize_with = "map::deserialize::<Color, _, _, _>"
+            )]
+            by_name: HashMap<String, i32>,
+            #[serde(
+                serialize_with = "map::serialize::<Color, _, _, _>",
+                deserialize_with = "map::deserialize::<Color, _, _, _>"
+            )]
+            by_id: BTreeMap<i32, i32>,
+            #[serde(
+                serialize_with = "number::map::serialize::<Color, _, _, _>",
+                deserialize_with = "number::map::deserialize::<Color, _, _, _>"
+            )]
+            numbers: BTreeMap<bool, i32>,
+        }
+
+        let mut maps = Maps::default();
+        maps.by_name
+            .insert("grass".to_string(), Color::Green as i32);
+        maps.by_id.insert(-1, Color::Red as i32);
+        maps.by_id.insert(2, 7);
+        maps.numbers.insert(true, Color::Green as i32);
+        let json = serde_json::to_string(&maps).unwrap();
+        assert_eq!(
+            json,
+            r#"{"by_name":{"grass":"COLOR_GREEN"},"by_id":{"-1":"COLOR_RED","2":7},"numbers":{"true":1}}"#
+        );
+        assert_eq!(serde_json::from_str::<Maps>(&json).unwrap(), maps);
+        let json =
+            r#"{"by_name":{"grass":1},"by_id":{"-1":0,"2":"7"},"numbers":{"true":"COLOR_GREEN"}}"#;
+        assert_eq!(serde_json::from_str::<Maps>(json).unwrap(), maps);
+
+        let json = mode::with(Mode::Legacy, || serde_json::to_string(&maps).unwrap());
+        assert_eq!(
+            json,
+            r#"{"by_name":{"grass":1},"by_id":{"-1":0,"2":7},"numbers":{"true":1}}"#
+        );
+        let bytes = mode::with(Mode::Binary, || bincode::serialize(&maps).unwrap());
+        let decoded = mode::with(Mode::Binary, || {
+            bincode::deserialize::<Maps>(&bytes).unwrap()
+        });
+        assert_eq!(decoded, maps);
+        assert!(serde_json::from_str::<Maps>(r#"{"by_id":{"1":true}}"#).is_err());
+    }
+
+    #[test]
+    fn unknown_names() {
+        let events = crate::serde::events::tests::record(|| {
+            let json = r#"{"color":"COLOR_BLUE","many":["COLOR_GREEN","green","+1"]}"#;
+            let sample: Sample = serde_json::from_str(json).unwrap();
+            assert_eq!(sample.color, 0);
+            assert_eq!(sample.many, vec![1, 0, 0]);
+        });
+        assert_eq!(
+            events,
+            [
+                r#"UnknownEnumValue { enumeration: "test.Color", value: "COLOR_BLUE" }"#,
+                r#"UnknownEnumValue { enumeration: "test.Color", value: "green" }"#,
+                r#"UnknownEnumValue { enumeration: "test.Color", value: "+1" }"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn cbor_null() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Nullable {
+            #[serde(
+                serialize_with = "serialize::<NullValue, _>",
+                deserialize_with = "deserialize::<NullValue, _>"
+            )]
+            null: i32,
+        }
+
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&serde_json::json!({ "null": null }), &mut cbor).unwrap();
+        let nullable: Nullable = ciborium::de::from_reader(&cbor[..]).unwrap();
+        assert_eq!(nullable.null, 0);
+    }
+}
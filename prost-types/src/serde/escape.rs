@@ -0,0 +1,160 @@
+//! Control over the escaping of non-ASCII characters in JSON output.
+//!
+//! JSON strings may hold any Unicode character as raw UTF-8, which is what serde serializers such
+//! as `serde_json` emit. Some consumers, such as log processors, only accept ASCII, and require
+//! every other character to be written as a `\uXXXX` escape, using a surrogate pair for
+//! characters outside the Basic Multilingual Plane. Both forms decode to the same string.
+//!
+//! Outside of strings, serialized JSON is ASCII, so escaping a serialized document escapes every
+//! string it contains alike: string fields, map keys, `Struct` field names and enum value names.
+//!
+//! ```rust,ignore
+//! use prost_types::serde::escape::{self, Escaping};
+//!
+//! let json = escape::to_string(&event, Escaping::Ascii)?;
+//! assert!(json.is_ascii());
+//! ```
+
+use core::fmt::Write;
+
+use prost::alloc::borrow::Cow;
+use prost::alloc::string::String;
+
+/// How characters outside of ASCII are written in JSON strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Escaping {
+    /// Characters are written as raw UTF-8, as serializers do by default.
+    Utf8,
+    /// Characters outside of ASCII are written as `\uXXXX` escapes, so the output is ASCII.
+    Ascii,
+}
+
+impl Default for Escaping {
+    fn default() -> Escaping {
+        Escaping::Utf8
+    }
+}
+
+impl Escaping {
+    /// Applies the escaping to serialized JSON.
+    ///
+    /// The JSON is returned unchanged with [`Escaping::Utf8`], see [`escape_ascii`] for
+    /// [`Escaping::Ascii`].
+    pub fn apply(self, json: &str) -> Cow<'_, str> {
+        match self {
+            Escaping::Utf8 => Cow::Borrowed(json),
+            Escaping::Ascii => escape_ascii(json),
+        }
+    }
+}
+
+/// Escapes the characters outside of ASCII in serialized JSON as `\uXXXX` escapes.
+///
+/// The JSON must be well-formed, so that every character outside of ASCII is part of a string,
+/// as is the case for the output of a serializer. The JSON is borrowed if it is already ASCII.
+pub fn escape_ascii(json: &str) -> Cow<'_, str> {
+    let first = match json.bytes().position(|byte| !byte.is_ascii()) {
+        Some(first) => first,
+        None => return Cow::Borrowed(json),
+    };
+
+    let mut escaped = String::with_capacity(json.len() + 8);
+    escaped.push_str(&json[..first]);
+    for c in json[first..].chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+        } else {
+            let mut units = [0; 2];
+            for unit in c.encode_utf16(&mut units) {
+                // Writing to a `String` can't fail.
+                let _ = write!(escaped, "\\u{:04x}", unit);
+            }
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Serializes the value as JSON, with the escaping.
+///
+/// This requires the `serde_json` feature.
+#[cfg(feature = "serde_json")]
+pub fn to_string<T>(value: &T, escaping: Escaping) -> Result<String, serde_json::Error>
+where
+    T: ?Sized + serde_crate::Serialize,
+{
+    serde_json::to_string(value).map(|json| escape_owned(json, escaping))
+}
+
+/// Serializes the value as pretty-printed JSON, with the escaping.
+///
+/// This requires the `serde_json` feature.
+#[cfg(feature = "serde_json")]
+pub fn to_string_pretty<T>(value: &T, escaping: Escaping) -> Result<String, serde_json::Error>
+where
+    T: ?Sized + serde_crate::Serialize,
+{
+    serde_json::to_string_pretty(value).map(|json| escape_owned(json, escaping))
+}
+
+#[cfg(feature = "serde_json")]
+fn escape_owned(json: String, escaping: Escaping) -> String {
+    match escaping.apply(&json) {
+        Cow::Borrowed(_) => json,
+        Cow::Owned(escaped) => escaped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert!(matches!(escape_ascii(r#"{"a":"b"}"#), Cow::Borrowed(_)));
+        assert_eq!(escape_ascii(r#""café""#), r#""caf\u00e9""#);
+        assert_eq!(escape_ascii(r#""日本""#), r#""\u65e5\u672c""#);
+        // Characters outside of the Basic Multilingual Plane are escaped as surrogate pairs.
+        assert_eq!(escape_ascii(r#""🦀""#), r#""\ud83e\udd80""#);
+        // Existing escapes are preserved.
+        assert_eq!(escape_ascii(r#""\"é\n""#), r#""\"\u00e9\n""#);
+        assert_eq!(Escaping::Utf8.apply(r#""é""#), r#""é""#);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn round_trip() {
+        use std::collections::BTreeMap;
+
+        use crate::{Struct, Value};
+
+        let mut labels = BTreeMap::new();
+        labels.insert("zone".to_string(), "zürich".to_string());
+        labels.insert("région".to_string(), "île-de-france 🗼".to_string());
+        let value = Value::from(
+            vec![
+                ("naïve".to_string(), Value::from("ok".to_string())),
+                ("plain".to_string(), Value::from("日本".to_string())),
+            ]
+            .into_iter()
+            .collect::<Struct>(),
+        );
+
+        let json = to_string(&labels, Escaping::Ascii).unwrap();
+        assert!(json.is_ascii(), "{}", json);
+        assert_eq!(
+            json,
+            r#"{"r\u00e9gion":"\u00eele-de-france \ud83d\uddfc","zone":"z\u00fcrich"}"#
+        );
+        let decoded: BTreeMap<String, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, labels);
+
+        let json = to_string_pretty(&value, Escaping::Ascii).unwrap();
+        assert!(json.is_ascii(), "{}", json);
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+        assert_eq!(
+            to_string(&value, Escaping::Utf8).unwrap(),
+            r#"{"naïve":"ok","plain":"日本"}"#
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Hooks for observing lenient conversions made while deserializing JSON.
+//!
+//! The JSON mapping accepts some input which does not exactly match the schema: fields which are
+//! not defined by the message can be ignored, enum value names which are not defined by the enum
+//! can be mapped to the enum's default value, and numbers can be converted to the type of a field
+//! with a loss of precision. None of these are errors, but a rising rate of them usually means
+//! that a peer is using a different version of the schema.
+//!
+//! An [`Observer`] installed with [`set_observer`] is notified of each such [`Event`], for
+//! example to count them in a metrics system:
+//!
+//! ```rust,ignore
+//! struct Metrics;
+//!
+//! impl Observer for Metrics {
+//!     fn event(&self, event: &Event<'_>) {
+//!         match event {
+//!             Event::UnknownField { message, .. } => UNKNOWN_FIELDS.with_label_values(&[message]).inc(),
+//!             _ => OTHER_EVENTS.inc(),
+//!         }
+//!     }
+//! }
+//!
+//! prost_types::serde::events::set_observer(&Metrics).unwrap();
+//! ```
+//!
+//! Events are reported by the deserializers in this crate, and can be reported by custom
+//! deserializers with [`report`].
+
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use prost::alloc::boxed::Box;
+
+/// A lenient conversion made while deserializing JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    /// A field which is not defined by the message was ignored.
+    UnknownField {
+        /// The fully-qualified name of the message.
+        message: &'a str,
+        /// The JSON name of the field.
+        field: &'a str,
+    },
+    /// An enum value name which is not defined by the enum was mapped to the enum's default value.
+    UnknownEnumValue {
+        /// The fully-qualified name of the enum.
+        enumeration: &'a str,
+        /// The enum value name.
+        value: &'a str,
+    },
+    /// A number was converted to the type of a field with a loss of precision.
+    LossyNumber {
+        /// The Rust type of the field, e.g. `f64`.
+        ty: &'static str,
+        /// The number which was converted.
+        value: &'a str,
+    },
+}
+
+/// Receives the events reported while deserializing JSON.
+///
+/// Events are reported synchronously from within deserialization, so observers should be cheap,
+/// and must not deserialize JSON themselves.
+pub trait Observer: Sync {
+    /// Called for each event.
+    fn event(&self, event: &Event<'_>);
+}
+
+static OBSERVER: AtomicPtr<&'static dyn Observer> = AtomicPtr::new(ptr::null_mut());
+
+/// Installs the observer which is notified of events.
+///
+/// The observer can only be installed once per process. Fails if an observer is already
+/// installed.
+pub fn set_observer(observer: &'static dyn Observer) -> Result<(), SetObserverError> {
+    let observer = Box::into_raw(Box::new(observer));
+    match OBSERVER.compare_exchange(
+        ptr::null_mut(),
+        observer,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            // Safety: the pointer was created above from a `Box`, and was not published.
+            drop(unsafe { Box::from_raw(observer) });
+            Err(SetObserverError(()))
+        }
+    }
+}
+
+/// Returns `true` if an observer is installed.
+///
+/// Deserializers can check this to avoid preparing the details of an event which would not be
+/// observed.
+pub fn enabled() -> bool {
+    !OBSERVER.load(Ordering::Acquire).is_null()
+}
+
+/// Reports an event to the installed observer, if any.
+pub fn report(event: &Event<'_>) {
+    let observer = OBSERVER.load(Ordering::Acquire);
+    if !observer.is_null() {
+        // Safety: a non-null pointer was published by `set_observer`, and is never freed.
+        unsafe { (*observer).event(event) }
+    }
+}
+
+/// The error returned by [`set_observer`] when an observer is already installed.
+#[derive(Debug)]
+pub struct SetObserverError(());
+
+impl fmt::Display for SetObserverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON event observer is already installed")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SetObserverError {}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use core::cell::RefCell;
+
+    use prost::alloc::format;
+    use prost::alloc::string::{String, ToString};
+    use prost::alloc::vec::Vec;
+
+    use super::*;
+
+    std::thread_local! {
+        static EVENTS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+    }
+
+    struct Recorder;
+
+    impl Observer for Recorder {
+        fn event(&self, event: &Event<'_>) {
+            EVENTS.with(|events| {
+                if let Some(events) = &mut *events.borrow_mut() {
+                    events.push(format!("{:?}", event));
+                }
+            });
+        }
+    }
+
+    /// Returns the events reported on the current thread while running `f`.
+    ///
+    /// Tests which record events share a single process-wide observer, so events are recorded per
+    /// thread.
+    pub(crate) fn record<F>(f: F) -> Vec<String>
+    where
+        F: FnOnce(),
+    {
+        let _ = set_observer(&Recorder);
+        EVENTS.with(|events| *events.borrow_mut() = Some(Vec::new()));
+        f();
+        EVENTS.with(|events| events.borrow_mut().take().unwrap())
+    }
+
+    #[test]
+    fn observer() {
+        let events = record(|| {
+            assert!(enabled());
+            report(&Event::UnknownField {
+                message: "test.Person",
+                field: "nickname",
+            });
+        });
+        assert_eq!(
+            events,
+            ["UnknownField { message: \"test.Person\", field: \"nickname\" }".to_string()]
+        );
+        assert!(set_observer(&Recorder).is_err());
+
+        // Events are not recorded outside of `record`.
+        report(&Event::LossyNumber {
+            ty: "f32",
+            value: "1",
+        });
+        assert!(record(|| ()).is_empty());
+    }
+}
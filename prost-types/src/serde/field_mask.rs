@@ -0,0 +1,139 @@
+//! `Serialize` and `Deserialize` implementations for `google.protobuf.FieldMask`.
+//!
+//! A field mask is serialized as a single string holding its paths separated by commas, with the
+//! field names in each path converted to lower camel case, e.g. `"user.displayName,photo"`.
+//! Serialization fails for paths which can't be converted back to the same field names, namely
+//! paths with upper case letters, or with underscores which are not followed by a lower case
+//! letter.
+
+use core::fmt;
+
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, Deserialize, Deserializer, Visitor};
+use serde_crate::ser::{self, Serialize, Serializer};
+
+use crate::FieldMask;
+
+impl Serialize for FieldMask {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut json = String::new();
+        for (i, path) in self.paths.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            if !to_camel_case(path, &mut json) {
+                return Err(ser::Error::custom(format_args!(
+                    "field mask path can't be converted to JSON: {}",
+                    path
+                )));
+            }
+        }
+        serializer.serialize_str(&json)
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldMask {
+    fn deserialize<D>(deserializer: D) -> Result<FieldMask, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_string(FieldMaskVisitor)
+    }
+}
+
+/// Appends the path converted to lower camel case, returning `false` if the conversion would not
+/// be reversible.
+fn to_camel_case(path: &str, out: &mut String) -> bool {
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '_' => match chars.next() {
+                Some(next) if next.is_ascii_lowercase() => out.push(next.to_ascii_uppercase()),
+                _ => return false,
+            },
+            c if c.is_ascii_uppercase() => return false,
+            c => out.push(c),
+        }
+    }
+    true
+}
+
+fn to_snake_case(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for c in path.chars() {
+        if c.is_ascii_uppercase() {
+            out.push('_');
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+struct FieldMaskVisitor;
+
+impl<'de> Visitor<'de> for FieldMaskVisitor {
+    type Value = FieldMask;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a comma-separated list of field paths")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<FieldMask, E>
+    where
+        E: de::Error,
+    {
+        let paths = if value.is_empty() {
+            Vec::new()
+        } else {
+            value.split(',').map(to_snake_case).collect()
+        };
+        Ok(FieldMask { paths })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::string::ToString;
+    use prost::alloc::vec;
+
+    fn mask(paths: &[&str]) -> FieldMask {
+        FieldMask {
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let value = mask(&["user.display_name", "photo", "a_b1.c_d"]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#""user.displayName,photo,aB1.cD""#);
+        assert_eq!(serde_json::from_str::<FieldMask>(&json).unwrap(), value);
+
+        assert_eq!(serde_json::to_string(&mask(&[])).unwrap(), r#""""#);
+        assert_eq!(
+            serde_json::from_str::<FieldMask>(r#""""#).unwrap(),
+            mask(&[])
+        );
+        assert_eq!(
+            serde_json::from_str::<FieldMask>(r#""fooBar""#)
+                .unwrap()
+                .paths,
+            vec!["foo_bar".to_string()]
+        );
+    }
+
+    #[test]
+    fn irreversible() {
+        for path in &["fooBar", "foo__bar", "foo_", "foo_1"] {
+            assert!(serde_json::to_string(&mask(&[path])).is_err(), "{}", path);
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! Serializers and deserializers for Protobuf `float` and `double` fields.
+//!
+//! Finite values are serialized as JSON numbers, using the shortest representation which parses
+//! back to the same value. The bit pattern of every finite value therefore survives a JSON
+//! round-trip, including `-0.0`, which is serialized as `-0.0` rather than being normalized to
+//! `0`, and subnormal numbers. Non-finite values are serialized as the strings `"NaN"`,
+//! `"Infinity"` and `"-Infinity"`. JSON has a single NaN, so the sign and payload of a NaN are not
+//! preserved.
+//!
+//! Deserialization accepts JSON numbers, and strings holding either a number or one of the
+//! non-finite spellings. A `float` value which is finite but out of range for `f32` is an error,
+//! rather than being rounded to infinity.
+//!
+//! The [`option`] and [`repeated`] modules apply the same mapping to `optional` and `repeated`
+//! fields. In [`Mode::Binary`], non-finite values are serialized as numbers too.
+//!
+//! `serde_json` parses numbers with a fast algorithm by default, which can return a `double` one
+//! ULP away from the value that was serialized. Enable the `float_roundtrip` feature of this crate,
+//! which enables that of `serde_json` as well as the `serde` and `serde_json` features, for the
+//! exact round-trip described above, at some cost in decoding speed.
+
+use core::any;
+use core::fmt;
+use core::marker::PhantomData;
+
+use prost::alloc::string::ToString;
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_crate::ser::{Serialize, Serializer};
+
+use crate::serde::events::{self, Event};
+use crate::serde::mode::{self, Mode};
+use crate::serde::number;
+
+/// A floating point type which can be serialized with this module, either `f32` or `f64`.
+pub trait Float: Copy + private::Sealed {
+    #[doc(hidden)]
+    fn serialize_native<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer;
+
+    #[doc(hidden)]
+    fn to_f64(self) -> f64;
+
+    #[doc(hidden)]
+    fn from_f64(value: f64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn parse(text: &str) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Visitor<'de>;
+}
+
+impl Float for f32 {
+    fn serialize_native<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f32(self)
+    }
+
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+
+    fn from_f64(value: f64) -> Option<f32> {
+        // The shortest representation of an `f32` is parsed exactly by way of `f64`, since double
+        // rounding is harmless when the intermediate type has more than twice the precision.
+        let narrowed = value as f32;
+        if narrowed.is_infinite() && value.is_finite() {
+            None
+        } else {
+            Some(narrowed)
+        }
+    }
+
+    fn parse(text: &str) -> Option<f32> {
+        let value = text.parse::<f32>().ok()?;
+        if value.is_infinite() {
+            // Reject out of range values such as "1e39", which `parse` rounds to infinity.
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Visitor<'de>,
+    {
+        deserializer.deserialize_f32(visitor)
+    }
+}
+
+impl Float for f64 {
+    fn serialize_native<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_f64(self)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Option<f64> {
+        Some(value)
+    }
+
+    fn parse(text: &str) -> Option<f64> {
+        let value = text.parse::<f64>().ok()?;
+        if value.is_infinite() {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Visitor<'de>,
+    {
+        deserializer.deserialize_f64(visitor)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
+}
+
+/// Serializes a `float` or `double` value.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Float,
+    S: Serializer,
+{
+    let value = *value;
+    let f = value.to_f64();
+    if mode::current() == Mode::Binary {
+        value.serialize_native(serializer)
+    } else if f.is_nan() {
+        serializer.serialize_str("NaN")
+    } else if f == f64::INFINITY {
+        serializer.serialize_str("Infinity")
+    } else if f == f64::NEG_INFINITY {
+        serializer.serialize_str("-Infinity")
+    } else {
+        value.serialize_native(serializer)
+    }
+}
+
+/// Deserializes a `float` or `double` value from a number or a string.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Float,
+    D: Deserializer<'de>,
+{
+    FloatVisitor(PhantomData).deserialize(deserializer)
+}
+
+/// Serializers and deserializers for `optional` `float` and `double` fields, mapping `None` to
+/// `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional value as a number, a string or `null`.
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Float,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&Wrapper(*value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional value from a number, a string or `null`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Float,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor(PhantomData))
+    }
+
+    struct OptionVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for OptionVisitor<T>
+    where
+        T: Float,
+    {
+        type Value = Option<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a floating point number or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` `float` and `double` fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of values as an array.
+    pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Float,
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(|&value| Wrapper(value)))
+    }
+
+    /// Deserializes a list of values from an array.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Float,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+
+    struct SeqVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for SeqVisitor<T>
+    where
+        T: Float,
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of floating point numbers")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<T>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element_seed(FloatVisitor(PhantomData))? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
+struct Wrapper<T>(T);
+
+impl<T> Serialize for Wrapper<T>
+where
+    T: Float,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(&self.0, serializer)
+    }
+}
+
+struct FloatVisitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> DeserializeSeed<'de> for FloatVisitor<T>
+where
+    T: Float,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if mode::is_binary() {
+            T::deserialize_native(deserializer, self)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for FloatVisitor<T>
+where
+    T: Float,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a floating point number")
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_f64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Float(value), &self))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        let converted = self.visit_f64(value as f64)?;
+        report_if_inexact(converted, value.into());
+        Ok(converted)
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        let converted = self.visit_f64(value as f64)?;
+        report_if_inexact(converted, value.into());
+        Ok(converted)
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        let parsed = match value {
+            "NaN" => T::from_f64(f64::NAN),
+            "Infinity" => T::from_f64(f64::INFINITY),
+            "-Infinity" => T::from_f64(f64::NEG_INFINITY),
+            // Rust accepts spellings such as "inf" and "nan", which are not valid JSON numbers.
+            _ if value
+                .bytes()
+                .any(|b| b.is_ascii_alphabetic() && b != b'e' && b != b'E') =>
+            {
+                None
+            }
+            _ => T::parse(value),
+        };
+        parsed.ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<T, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        number::visit_map(map, self)
+    }
+}
+
+/// Reports an integer which was rounded when converted to a floating point value.
+fn report_if_inexact<T>(converted: T, value: i128)
+where
+    T: Float,
+{
+    // Every `u64` and `i64` is in range for `f32`, and integral floats in range convert to `i128`
+    // exactly.
+    if events::enabled() && converted.to_f64() as i128 != value {
+        events::report(&Event::LossyNumber {
+            ty: any::type_name::<T>(),
+            value: &value.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Sample {
+        #[serde(with = "super")]
+        double: f64,
+        #[serde(with = "super")]
+        float: f32,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Samples {
+        #[serde(with = "super::option", default)]
+        maybe: Option<f64>,
+        #[serde(with = "super::repeated")]
+        many: Vec<f32>,
+    }
+
+    fn round_trip(double: f64, float: f32) -> Sample {
+        let json = serde_json::to_string(&Sample { double, float }).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn negative_zero() {
+        let json = serde_json::to_string(&Sample {
+            double: -0.0,
+            float: -0.0,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"double":-0.0,"float":-0.0}"#);
+
+        let sample = round_trip(-0.0, -0.0);
+        assert_eq!(sample.double.to_bits(), (-0.0f64).to_bits());
+        assert_eq!(sample.float.to_bits(), (-0.0f32).to_bits());
+
+        let sample: Sample = serde_json::from_str(r#"{"double":"-0","float":-0}"#).unwrap();
+        assert!(sample.double.is_sign_negative());
+    }
+
+    #[test]
+    fn exact_bits() {
+        let doubles = [
+            0x0000_0000_0000_0001u64,
+            0x000F_FFFF_FFFF_FFFF,
+            0x0010_0000_0000_0000,
+            0x7FEF_FFFF_FFFF_FFFF,
+            0x3FB9_9999_9999_999A,
+            0xC05E_DD2F_1A9F_BE77,
+        ];
+        let floats = [
+            0x0000_0001u32,
+            0x007F_FFFF,
+            0x0080_0000,
+            0x7F7F_FFFF,
+            0x3DCC_CCCD,
+            0xC2F6_E979,
+        ];
+        for (&d, &f) in doubles.iter().zip(floats.iter()) {
+            for &(d, f) in &[(d, f), (d | 1 << 63, f | 1 << 31)] {
+                let sample = round_trip(f64::from_bits(d), f32::from_bits(f));
+                assert_eq!(sample.double.to_bits(), d);
+                assert_eq!(sample.float.to_bits(), f);
+            }
+        }
+
+        // Sweep a range of `f32` bit patterns, including all of the low subnormals.
+        for f in (0..0x0010_0000u32).chain((0x3F80_0000..0x3F90_0000).step_by(7)) {
+            let sample = round_trip(0.0, f32::from_bits(f));
+            assert_eq!(sample.float.to_bits(), f);
+        }
+    }
+
+    #[test]
+    fn non_finite() {
+        let json = serde_json::to_string(&Sample {
+            double: f64::NAN,
+            float: f32::NEG_INFINITY,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"double":"NaN","float":"-Infinity"}"#);
+
+        let sample: Sample = serde_json::from_str(&json).unwrap();
+        assert!(sample.double.is_nan());
+        assert_eq!(sample.float, f32::NEG_INFINITY);
+
+        for json in &[
+            r#"{"double":"inf","float":0}"#,
+            r#"{"double":"nan","float":0}"#,
+            r#"{"double":1e999,"float":0}"#,
+            r#"{"double":"1e999","float":0}"#,
+            r#"{"double":0,"float":1e39}"#,
+            r#"{"double":0,"float":"-1e39"}"#,
+            r#"{"double":true,"float":0}"#,
+        ] {
+            assert!(serde_json::from_str::<Sample>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn optional_and_repeated() {
+        let samples = Samples {
+            maybe: Some(-0.0),
+            many: vec![1.5, f32::INFINITY, -0.0, f32::from_bits(1)],
+        };
+        let json = serde_json::to_string(&samples).unwrap();
+        assert_eq!(json, r#"{"maybe":-0.0,"many":[1.5,"Infinity",-0.0,1e-45]}"#);
+        let decoded: Samples = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.maybe.unwrap().to_bits(), (-0.0f64).to_bits());
+        assert_eq!(decoded.many[2].to_bits(), (-0.0f32).to_bits());
+        assert_eq!(decoded.many[3].to_bits(), 1);
+
+        let samples: Samples = serde_json::from_str(r#"{"maybe":null,"many":["2",3]}"#).unwrap();
+        assert_eq!(samples.maybe, None);
+        assert_eq!(samples.many, vec![2.0, 3.0]);
+    }
+
+    #[test]
+    fn lossy_integers() {
+        let events = crate::serde::events::tests::record(|| {
+            let json = r#"{"double":9007199254740993,"float":16777217}"#;
+            let sample: Sample = serde_json::from_str(json).unwrap();
+            assert_eq!(sample.double, 9007199254740992.0);
+            assert_eq!(sample.float, 16777216.0);
+
+            let json = r#"{"double":-9007199254740992,"float":16777216}"#;
+            serde_json::from_str::<Sample>(json).unwrap();
+        });
+        assert_eq!(
+            events,
+            [
+                r#"LossyNumber { ty: "f64", value: "9007199254740993" }"#,
+                r#"LossyNumber { ty: "f32", value: "16777217" }"#,
+            ]
+        );
+    }
+}
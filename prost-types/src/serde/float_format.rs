@@ -0,0 +1,199 @@
+//! Control over the formatting of floating point numbers in JSON output.
+//!
+//! Serializers such as `serde_json` write floating point numbers with the shortest digits which
+//! parse back to the same value, switching to exponent notation for very small and very large
+//! magnitudes. This is exact, but a small change in a computed value can change every digit, and
+//! the notation of a value depends on its magnitude. Consumers which compare documents as text,
+//! such as golden-file tests, can instead write floats with a fixed number of decimal places, or
+//! choose the magnitudes written in exponent notation.
+//!
+//! The format applies to every floating point number of a serialized document: `float` and
+//! `double` fields, their wrapper types, and the numbers held by `Value`s. Integers are not
+//! affected. Non-finite values are written as strings, as the JSON mapping requires, and are not
+//! affected either.
+//!
+//! ```rust,ignore
+//! use prost_types::json::Options;
+//! use prost_types::serde::float_format::FloatFormat;
+//!
+//! let json = Options::new().floats(FloatFormat::Fixed(3)).to_string(&measurement)?;
+//! ```
+
+use core::fmt::Write;
+
+use prost::alloc::borrow::Cow;
+use prost::alloc::format;
+use prost::alloc::string::String;
+
+/// How floating point numbers are written in JSON.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FloatFormat {
+    /// The shortest digits which parse back to the same value, as serializers write them.
+    Shortest,
+    /// A fixed number of digits after the decimal point, rounding to the nearest, e.g. `1.50` for
+    /// `1.5` with two places. Values are no longer exact unless they have as many places, and
+    /// large magnitudes are written with all the digits of their binary value.
+    Fixed(u8),
+    /// The shortest digits, written in exponent notation if the magnitude of the value is below
+    /// `10^-n` or at least `10^n`, and in decimal notation otherwise. For example, with `3`,
+    /// `1500.0` is written as `1.5e3` and `0.002` as `0.002`.
+    Exponent(u8),
+}
+
+impl Default for FloatFormat {
+    fn default() -> FloatFormat {
+        FloatFormat::Shortest
+    }
+}
+
+impl FloatFormat {
+    /// Applies the format to the floating point numbers of serialized JSON.
+    ///
+    /// The JSON must be well-formed, as is the case for the output of a serializer. Numbers with a
+    /// fraction or an exponent are floating point numbers, since serializers write integers
+    /// without. The JSON is returned unchanged with [`FloatFormat::Shortest`], and is borrowed if
+    /// it holds no floating point numbers.
+    pub fn apply(self, json: &str) -> Cow<'_, str> {
+        if self == FloatFormat::Shortest {
+            return Cow::Borrowed(json);
+        }
+
+        let bytes = json.as_bytes();
+        let mut formatted = String::new();
+        // The end of the input copied to the output so far.
+        let mut copied = 0;
+        let mut in_string = false;
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' if in_string => i += 2,
+                b'"' => {
+                    in_string = !in_string;
+                    i += 1;
+                }
+                b'-' | b'0'..=b'9' if !in_string => {
+                    let start = i;
+                    while i < bytes.len()
+                        && matches!(bytes[i], b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+                    {
+                        i += 1;
+                    }
+                    let number = &json[start..i];
+                    if !number.contains(&['.', 'e', 'E'][..]) {
+                        continue;
+                    }
+                    if let Ok(value) = number.parse::<f64>() {
+                        formatted.push_str(&json[copied..start]);
+                        self.write(&mut formatted, value);
+                        copied = i;
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+
+        if copied == 0 {
+            Cow::Borrowed(json)
+        } else {
+            formatted.push_str(&json[copied..]);
+            Cow::Owned(formatted)
+        }
+    }
+
+    fn write(self, out: &mut String, value: f64) {
+        // Writing to a `String` can't fail.
+        let _ = match self {
+            FloatFormat::Shortest => write!(out, "{:?}", value),
+            FloatFormat::Fixed(places) => write!(out, "{:.*}", usize::from(places), value),
+            FloatFormat::Exponent(threshold) => {
+                // Both notations hold the shortest digits which parse back to the value.
+                let exponential = format!("{:e}", value);
+                let exponent = exponential
+                    .rsplit('e')
+                    .next()
+                    .and_then(|exponent| exponent.parse::<i32>().ok())
+                    .unwrap_or(0);
+                let threshold = i32::from(threshold);
+                if -threshold <= exponent && exponent < threshold {
+                    let decimal = format!("{}", value);
+                    out.push_str(&decimal);
+                    if !decimal.contains('.') {
+                        out.push_str(".0");
+                    }
+                } else {
+                    out.push_str(&exponential);
+                }
+                Ok(())
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply() {
+        let json = r#"{"a":"1.5","b\"2.5":[1.5,-2,1e-7,12345.678,0.0],"c":-0.125}"#;
+        assert!(matches!(
+            FloatFormat::Shortest.apply(json),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(
+            FloatFormat::Fixed(2).apply(r#"{"a":[1,-2],"b":"3.5"}"#),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(
+            FloatFormat::Fixed(2).apply(json),
+            r#"{"a":"1.5","b\"2.5":[1.50,-2,0.00,12345.68,0.00],"c":-0.12}"#
+        );
+        assert_eq!(FloatFormat::Fixed(0).apply("2.5e1"), "25");
+        assert_eq!(
+            FloatFormat::Exponent(3).apply(json),
+            r#"{"a":"1.5","b\"2.5":[1.5,-2,1e-7,1.2345678e4,0.0],"c":-0.125}"#
+        );
+        assert_eq!(
+            FloatFormat::Exponent(20).apply("[1e-7,1e16,-1.5e300]"),
+            "[0.0000001,10000000000000000.0,-1.5e300]"
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn round_trip() {
+        use crate::{ListValue, Value};
+
+        let value = Value::from(ListValue {
+            values: vec![
+                Value::from(0.1),
+                Value::from(2.0),
+                Value::from(-1e-9),
+                Value::from(6.02214076e23),
+            ],
+        });
+        let json = serde_json::to_string(&value).unwrap();
+        for &format in &[
+            FloatFormat::Shortest,
+            FloatFormat::Exponent(0),
+            FloatFormat::Exponent(4),
+            FloatFormat::Exponent(u8::MAX),
+        ] {
+            let formatted = format.apply(&json);
+            assert_eq!(
+                serde_json::from_str::<Value>(&formatted).unwrap(),
+                value,
+                "{}",
+                formatted
+            );
+        }
+        assert_eq!(
+            FloatFormat::Exponent(4).apply(&json),
+            "[0.1,2.0,-1e-9,6.02214076e23]"
+        );
+        assert_eq!(
+            FloatFormat::Fixed(3).apply(&json),
+            "[0.100,2.000,-0.000,602214075999999987023872.000]"
+        );
+    }
+}
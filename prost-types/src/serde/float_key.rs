@@ -0,0 +1,220 @@
+//! Canonical formatting for numeric-looking map keys.
+//!
+//! Protobuf does not allow floating point map keys, but `Struct` based payloads commonly use
+//! string keys holding numbers, such as `"1.50"` or `"2e3"`. Equivalent numbers may be spelled
+//! many different ways, which breaks round-trips and hashes computed over the serialized form.
+//!
+//! The helpers in this module normalize such keys to a single canonical spelling: the shortest
+//! decimal representation which round-trips through `f64`, without trailing zeros and without an
+//! exponent. Non-finite values use the Protobuf JSON spellings `NaN`, `Infinity` and `-Infinity`,
+//! and `-0` is formatted as `0`, so that two keys are equal exactly when their values are.
+//!
+//! The [`serialize`] and [`deserialize`] functions can be used with `#[serde(with = "...")]` on
+//! string-keyed maps to canonicalize numeric keys in both directions. Keys which are not numbers
+//! are passed through unchanged.
+
+use core::fmt;
+
+use prost::alloc::collections::BTreeSet;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec::Vec;
+use serde_crate::de::{Deserialize, Deserializer, Error as _};
+use serde_crate::ser::{Error as _, Serialize, Serializer};
+
+use super::map::{self, MapKey};
+
+/// Formats a number as a canonical map key.
+pub fn format(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value.is_sign_positive() {
+            "Infinity".to_string()
+        } else {
+            "-Infinity".to_string()
+        }
+    } else if value == 0.0 {
+        "0".to_string()
+    } else {
+        // `Display` for floats emits the shortest round-trip digits, and never uses an exponent.
+        value.to_string()
+    }
+}
+
+/// Parses a numeric map key.
+///
+/// Accepts decimal numbers with an optional sign, fraction and exponent, as well as `NaN`,
+/// `Infinity` and `-Infinity`. Returns `None` if the key is not a number.
+pub fn parse(key: &str) -> Option<f64> {
+    match key {
+        "NaN" => return Some(f64::NAN),
+        "Infinity" => return Some(f64::INFINITY),
+        "-Infinity" => return Some(f64::NEG_INFINITY),
+        _ => (),
+    }
+
+    // Reject the spellings accepted by `f64::from_str` which are not JSON-like numbers, such as
+    // `inf`, `nan`, or a bare `.`.
+    let digits = key.strip_prefix('-').unwrap_or(key);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        || !digits
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-'))
+    {
+        return None;
+    }
+    key.parse().ok()
+}
+
+/// Returns the canonical form of a numeric map key, or `None` if the key is not a number.
+pub fn canonicalize(key: &str) -> Option<String> {
+    parse(key).map(format)
+}
+
+/// A string map key with numeric values in canonical form.
+struct CanonicalKey(String);
+
+impl fmt::Display for CanonicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl MapKey for CanonicalKey {
+    fn from_key_str(key: &str) -> Option<CanonicalKey> {
+        Some(CanonicalKey(
+            canonicalize(key).unwrap_or_else(|| key.to_string()),
+        ))
+    }
+}
+
+/// Serializes a string-keyed map, canonicalizing numeric keys.
+///
+/// Fails if two keys have the same canonical form.
+pub fn serialize<'a, M, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a String, &'a V)>,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    let mut seen = BTreeSet::new();
+    let mut entries = Vec::new();
+    for (key, value) in map {
+        let key = canonicalize(key).unwrap_or_else(|| key.clone());
+        if !seen.insert(key.clone()) {
+            return Err(S::Error::custom(format_args!(
+                "duplicate map key after canonicalization: {}",
+                key
+            )));
+        }
+        entries.push((key, value));
+    }
+    serializer.collect_map(entries)
+}
+
+/// Deserializes a string-keyed map, canonicalizing numeric keys.
+///
+/// Fails if two keys have the same canonical form.
+pub fn deserialize<'de, M, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: Default + Extend<(String, V)> + IntoIterator<Item = (String, V)>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let entries: Vec<(CanonicalKey, V)> = map::deserialize(deserializer)?;
+    let mut seen = BTreeSet::new();
+    let mut map = M::default();
+    for (CanonicalKey(key), value) in entries {
+        if !seen.insert(key.clone()) {
+            return Err(D::Error::custom(format_args!(
+                "duplicate map key after canonicalization: {}",
+                key
+            )));
+        }
+        map.extend(Some((key, value)));
+    }
+    Ok(map)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn format_canonical() {
+        assert_eq!(format(1.0), "1");
+        assert_eq!(format(1.5), "1.5");
+        assert_eq!(format(-0.25), "-0.25");
+        assert_eq!(format(-0.0), "0");
+        assert_eq!(format(1e21), "1000000000000000000000");
+        assert_eq!(format(1e-7), "0.0000001");
+        assert_eq!(format(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format(f64::NAN), "NaN");
+        assert_eq!(format(f64::INFINITY), "Infinity");
+        assert_eq!(format(f64::NEG_INFINITY), "-Infinity");
+    }
+
+    #[test]
+    fn parse_numeric_keys() {
+        assert_eq!(parse("1.50"), Some(1.5));
+        assert_eq!(parse("2e3"), Some(2000.0));
+        assert_eq!(parse("-2E-3"), Some(-0.002));
+        assert_eq!(parse(".5"), Some(0.5));
+        assert_eq!(parse("Infinity"), Some(f64::INFINITY));
+        assert!(parse("NaN").unwrap().is_nan());
+
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("-"), None);
+        assert_eq!(parse("."), None);
+        assert_eq!(parse("+1"), None);
+        assert_eq!(parse(" 1"), None);
+        assert_eq!(parse("inf"), None);
+        assert_eq!(parse("nan"), None);
+        assert_eq!(parse("0x10"), None);
+        assert_eq!(parse("abc"), None);
+    }
+
+    #[test]
+    fn canonicalize_round_trip() {
+        for key in &[
+            "1",
+            "1.5",
+            "0",
+            "-3",
+            "0.0000001",
+            "123456789012",
+            "Infinity",
+        ] {
+            assert_eq!(canonicalize(key).as_deref(), Some(*key));
+        }
+        assert_eq!(canonicalize("1.0").as_deref(), Some("1"));
+        assert_eq!(canonicalize("1.500e1").as_deref(), Some("15"));
+        assert_eq!(canonicalize("-0.0").as_deref(), Some("0"));
+        assert_eq!(canonicalize("name"), None);
+    }
+
+    #[test]
+    fn serde_map() {
+        let mut de = serde_json::Deserializer::from_str(r#"{"1.0": 1, "2e1": 2, "name": 3}"#);
+        let map: BTreeMap<String, i32> = deserialize(&mut de).unwrap();
+        assert_eq!(
+            map.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["1", "20", "name"]
+        );
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"1.0": 1, "1": 2}"#);
+        assert!(deserialize::<BTreeMap<String, i32>, _, _>(&mut de).is_err());
+
+        let mut map = BTreeMap::new();
+        map.insert("0.50".to_string(), true);
+        let mut buf = Vec::new();
+        serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+        assert_eq!(std::str::from_utf8(&buf).unwrap(), r#"{"0.5":true}"#);
+
+        map.insert(".5".to_string(), false);
+        let mut buf = Vec::new();
+        assert!(serialize(&map, &mut serde_json::Serializer::new(&mut buf)).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! Serializers and deserializers for Protobuf 32-bit integer fields.
+//!
+//! The Protobuf JSON mapping renders `int32`, `uint32`, `sint32`, `fixed32` and `sfixed32` values
+//! as JSON numbers, like plain serde derives do, but parsers must also accept them as decimal
+//! strings, and as numbers in exponent notation. Deserialization accepts all of these forms, as
+//! long as the value is an integer in range for the field.
+//!
+//! The [`option`] and [`repeated`] modules apply the same mapping to `optional` and `repeated`
+//! fields.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_crate::ser::{Serialize, Serializer};
+
+use crate::serde::mode;
+use crate::serde::number;
+
+/// A 32-bit integer type which can be serialized with this module, either `i32` or `u32`.
+pub trait Int32: Copy + Serialize + private::Sealed {
+    #[doc(hidden)]
+    fn from_i64(value: i64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn from_u64(value: u64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn from_f64(value: f64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn parse(text: &str) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Visitor<'de>;
+}
+
+macro_rules! int32 {
+    ($($ty:ty => $deserialize:ident),*) => {$(
+        impl Int32 for $ty {
+            fn from_i64(value: i64) -> Option<$ty> {
+                core::convert::TryFrom::try_from(value).ok()
+            }
+
+            fn from_u64(value: u64) -> Option<$ty> {
+                core::convert::TryFrom::try_from(value).ok()
+            }
+
+            fn from_f64(value: f64) -> Option<$ty> {
+                // Every 32-bit integer is exact as a double.
+                if value.fract() == 0.0 && value >= <$ty>::MIN as f64 && value <= <$ty>::MAX as f64 {
+                    Some(value as $ty)
+                } else {
+                    None
+                }
+            }
+
+            fn parse(text: &str) -> Option<$ty> {
+                // Rust accepts a leading `+`, which is not valid JSON.
+                if text.starts_with('+') {
+                    return None;
+                }
+                text.parse()
+                    .ok()
+                    .or_else(|| text.parse::<f64>().ok().and_then(<$ty>::from_f64))
+            }
+
+            fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+                V: Visitor<'de>,
+            {
+                deserializer.$deserialize(visitor)
+            }
+        }
+    )*};
+}
+
+int32!(i32 => deserialize_i32, u32 => deserialize_u32);
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for i32 {}
+    impl Sealed for u32 {}
+}
+
+/// Serializes a 32-bit integer value as a number.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Int32,
+    S: Serializer,
+{
+    value.serialize(serializer)
+}
+
+/// Deserializes a 32-bit integer value from a number or a string.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Int32,
+    D: Deserializer<'de>,
+{
+    Int32Visitor(PhantomData).deserialize(deserializer)
+}
+
+/// Serializers and deserializers for `optional` 32-bit integer fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional value as a number or `null`.
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Int32,
+        S: Serializer,
+    {
+        value.serialize(serializer)
+    }
+
+    /// Deserializes an optional value from a number, a string or `null`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Int32,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor(PhantomData))
+    }
+
+    struct OptionVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for OptionVisitor<T>
+    where
+        T: Int32,
+    {
+        type Value = Option<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a 32-bit integer or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` 32-bit integer fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of values as an array of numbers.
+    pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Int32,
+        S: Serializer,
+    {
+        serializer.collect_seq(values)
+    }
+
+    /// Deserializes a list of values from an array of numbers or strings.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Int32,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+
+    struct SeqVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for SeqVisitor<T>
+    where
+        T: Int32,
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of 32-bit integers")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<T>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element_seed(Int32Visitor(PhantomData))? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
+struct Int32Visitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> DeserializeSeed<'de> for Int32Visitor<T>
+where
+    T: Int32,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if mode::is_binary() {
+            T::deserialize_native(deserializer, self)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for Int32Visitor<T>
+where
+    T: Int32,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a 32-bit integer")
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_i64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_u64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_f64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Float(value), &self))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::parse(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<T, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        number::visit_map(map, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Sample {
+        #[serde(with = "super")]
+        signed: i32,
+        #[serde(with = "super")]
+        unsigned: u32,
+        #[serde(with = "super::option", default)]
+        maybe: Option<i32>,
+        #[serde(with = "super::repeated", default)]
+        many: Vec<u32>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let sample = Sample {
+            signed: i32::MIN,
+            unsigned: u32::MAX,
+            maybe: Some(0),
+            many: vec![0, 7],
+        };
+        let json = serde_json::to_string(&sample).unwrap();
+        assert_eq!(
+            json,
+            r#"{"signed":-2147483648,"unsigned":4294967295,"maybe":0,"many":[0,7]}"#
+        );
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+    }
+
+    #[test]
+    fn lenient_input() {
+        let json = r#"{"signed":"-5","unsigned":"1e3","maybe":null,"many":[7,"8",9.0,"1.0e1"]}"#;
+        let sample: Sample = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            sample,
+            Sample {
+                signed: -5,
+                unsigned: 1000,
+                maybe: None,
+                many: vec![7, 8, 9, 10],
+            }
+        );
+        let sample: Sample =
+            serde_json::from_str(r#"{"signed":0,"unsigned":0,"maybe":"3"}"#).unwrap();
+        assert_eq!(sample.maybe, Some(3));
+
+        for json in &[
+            r#"{"signed":"1.5","unsigned":0}"#,
+            r#"{"signed":0,"unsigned":-1}"#,
+            r#"{"signed":0,"unsigned":"-1"}"#,
+            r#"{"signed":2147483648,"unsigned":0}"#,
+            r#"{"signed":"2147483648","unsigned":0}"#,
+            r#"{"signed":0,"unsigned":4294967296}"#,
+            r#"{"signed":"+1","unsigned":0}"#,
+            r#"{"signed":" 1","unsigned":0}"#,
+            r#"{"signed":"NaN","unsigned":0}"#,
+            r#"{"signed":true,"unsigned":0}"#,
+        ] {
+            assert!(serde_json::from_str::<Sample>(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! Serializers and deserializers for Protobuf 64-bit integer fields.
+//!
+//! The Protobuf JSON mapping renders `int64`, `uint64`, `sint64`, `fixed64` and `sfixed64` values
+//! as decimal strings, since JSON numbers are commonly parsed as doubles, which can't represent
+//! every 64-bit integer. Deserialization accepts both strings and numbers, including numbers in
+//! exponent notation, as long as the value is an integer in range for the field.
+//!
+//! The [`option`] and [`repeated`] modules apply the same mapping to `optional` and `repeated`
+//! fields. In [`Mode::Legacy`] and [`Mode::Binary`], values are serialized as numbers instead, and
+//! the [`number`] module always serializes them as numbers.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_crate::ser::{Serialize, Serializer};
+
+use crate::serde::mode::{self, Mode};
+
+/// A 64-bit integer type which can be serialized with this module, either `i64` or `u64`.
+pub trait Int64: Copy + fmt::Display + Serialize + private::Sealed {
+    #[doc(hidden)]
+    fn from_i64(value: i64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn from_u64(value: u64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn from_f64(value: f64) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn parse(text: &str) -> Option<Self>;
+
+    #[doc(hidden)]
+    fn serialize_string<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer;
+
+    #[doc(hidden)]
+    fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+        V: Visitor<'de>;
+}
+
+macro_rules! int64 {
+    ($($ty:ty => $deserialize:ident),*) => {$(
+        impl Int64 for $ty {
+            fn from_i64(value: i64) -> Option<$ty> {
+                core::convert::TryFrom::try_from(value).ok()
+            }
+
+            fn from_u64(value: u64) -> Option<$ty> {
+                core::convert::TryFrom::try_from(value).ok()
+            }
+
+            fn from_f64(value: f64) -> Option<$ty> {
+                // The bounds are powers of two, so they are exact as floats, and every integral
+                // float between them converts exactly.
+                let min = <$ty>::MIN as f64;
+                let max = <$ty>::MAX as f64 + 1.0;
+                if value.fract() == 0.0 && value >= min && value < max {
+                    Some(value as $ty)
+                } else {
+                    None
+                }
+            }
+
+            fn parse(text: &str) -> Option<$ty> {
+                // Rust accepts a leading `+`, which is not valid JSON.
+                if text.starts_with('+') {
+                    return None;
+                }
+                text.parse()
+                    .ok()
+                    .or_else(|| text.parse::<f64>().ok().and_then(<$ty>::from_f64))
+            }
+
+            fn serialize_string<S>(self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                // Formatting on the stack is considerably faster than `collect_str`, which goes
+                // through `fmt::Display` and escapes each piece of the output separately.
+                serializer.serialize_str(itoa::Buffer::new().format(self))
+            }
+
+            fn deserialize_native<'de, D, V>(deserializer: D, visitor: V) -> Result<V::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+                V: Visitor<'de>,
+            {
+                deserializer.$deserialize(visitor)
+            }
+        }
+    )*};
+}
+
+int64!(i64 => deserialize_i64, u64 => deserialize_u64);
+
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for i64 {}
+    impl Sealed for u64 {}
+}
+
+/// Serializes a 64-bit integer value as a string, or as a number in [`Mode::Legacy`] and
+/// [`Mode::Binary`].
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Int64,
+    S: Serializer,
+{
+    match mode::current() {
+        Mode::Spec => value.serialize_string(serializer),
+        Mode::Legacy | Mode::Binary => value.serialize(serializer),
+    }
+}
+
+/// Deserializes a 64-bit integer value from a string or a number.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Int64,
+    D: Deserializer<'de>,
+{
+    Int64Visitor(PhantomData).deserialize(deserializer)
+}
+
+/// Serializers and deserializers for `optional` 64-bit integer fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional value as a string or `null`.
+    pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Int64,
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&Wrapper(*value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional value from a string, a number or `null`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: Int64,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor(PhantomData))
+    }
+
+    struct OptionVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for OptionVisitor<T>
+    where
+        T: Int64,
+    {
+        type Value = Option<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a 64-bit integer or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` 64-bit integer fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of values as an array of strings.
+    pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Int64,
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(|&value| Wrapper(value)))
+    }
+
+    /// Deserializes a list of values from an array of strings or numbers.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: Int64,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+
+    struct SeqVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for SeqVisitor<T>
+    where
+        T: Int64,
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of 64-bit integers")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<T>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element_seed(Int64Visitor(PhantomData))? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
+/// Serializers and deserializers which render 64-bit integers as JSON numbers.
+///
+/// Some consumers, such as JavaScript with `BigInt` or Python, read 64-bit integers from JSON
+/// numbers exactly, and expect numbers rather than the strings of the JSON mapping. Values are
+/// serialized as numbers in every [`Mode`], and deserialized as in the parent module, from either
+/// strings or numbers.
+pub mod number {
+    use super::*;
+
+    pub use super::deserialize;
+
+    /// Serializes a 64-bit integer value as a number.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Int64,
+        S: Serializer,
+    {
+        value.serialize(serializer)
+    }
+
+    /// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+    pub mod option {
+        use super::*;
+
+        pub use super::super::option::deserialize;
+
+        /// Serializes an optional value as a number or `null`.
+        pub fn serialize<T, S>(value: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: Int64,
+            S: Serializer,
+        {
+            value.serialize(serializer)
+        }
+    }
+
+    /// Serializers and deserializers for `repeated` fields.
+    pub mod repeated {
+        use super::*;
+
+        pub use super::super::repeated::deserialize;
+
+        /// Serializes a list of values as an array of numbers.
+        pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+        where
+            T: Int64,
+            S: Serializer,
+        {
+            serializer.collect_seq(values)
+        }
+    }
+}
+
+struct Wrapper<T>(T);
+
+impl<T> Serialize for Wrapper<T>
+where
+    T: Int64,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(&self.0, serializer)
+    }
+}
+
+struct Int64Visitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> DeserializeSeed<'de> for Int64Visitor<T>
+where
+    T: Int64,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if mode::is_binary() {
+            T::deserialize_native(deserializer, self)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for Int64Visitor<T>
+where
+    T: Int64,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a 64-bit integer")
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_i64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_u64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Unsigned(value), &self))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::from_f64(value).ok_or_else(|| E::invalid_value(de::Unexpected::Float(value), &self))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        T::parse(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<T, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        crate::serde::number::visit_map(map, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    use crate::serde::mode::{self, Mode};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Sample {
+        #[serde(with = "super")]
+        signed: i64,
+        #[serde(with = "super")]
+        unsigned: u64,
+        #[serde(with = "super::option", default)]
+        maybe: Option<i64>,
+        #[serde(with = "super::repeated", default)]
+        many: Vec<u64>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let sample = Sample {
+            signed: i64::MIN,
+            unsigned: u64::MAX,
+            maybe: Some(-1),
+            many: vec![0, 1 << 53 | 1],
+        };
+        let json = serde_json::to_string(&sample).unwrap();
+        assert_eq!(
+            json,
+            r#"{"signed":"-9223372036854775808","unsigned":"18446744073709551615","maybe":"-1","many":["0","9007199254740993"]}"#
+        );
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+    }
+
+    #[test]
+    fn legacy_mode() {
+        let sample = Sample {
+            signed: -1,
+            unsigned: u64::MAX,
+            maybe: Some(2),
+            many: vec![3],
+        };
+        let json = mode::with(Mode::Legacy, || serde_json::to_string(&sample).unwrap());
+        assert_eq!(
+            json,
+            r#"{"signed":-1,"unsigned":18446744073709551615,"maybe":2,"many":[3]}"#
+        );
+        assert_eq!(serde_json::from_str::<Sample>(&json).unwrap(), sample);
+    }
+
+    #[test]
+    fn numbers() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Numbers {
+            #[serde(with = "super::number")]
+            id: u64,
+            #[serde(with = "super::number::option", default)]
+            parent: Option<i64>,
+            #[serde(with = "super::number::repeated", default)]
+            children: Vec<i64>,
+        }
+
+        let numbers = Numbers {
+            id: u64::MAX,
+            parent: Some(i64::MIN),
+            children: vec![-1, 1 << 53 | 1],
+        };
+        let json = r#"{"id":18446744073709551615,"parent":-9223372036854775808,"children":[-1,9007199254740993]}"#;
+        assert_eq!(serde_json::to_string(&numbers).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Numbers>(json).unwrap(), numbers);
+
+        let strings = r#"{"id":"18446744073709551615","parent":"-9223372036854775808","children":["-1",9007199254740993]}"#;
+        assert_eq!(serde_json::from_str::<Numbers>(strings).unwrap(), numbers);
+
+        let numbers = Numbers {
+            id: 1,
+            parent: None,
+            children: vec![],
+        };
+        let json = mode::with(Mode::Spec, || serde_json::to_string(&numbers).unwrap());
+        assert_eq!(json, r#"{"id":1,"parent":null,"children":[]}"#);
+    }
+
+    #[test]
+    fn lenient_input() {
+        let json = r#"{"signed":-5,"unsigned":"1e3","maybe":null,"many":[7,"8",9.0,"1.0e1"]}"#;
+        let sample: Sample = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            sample,
+            Sample {
+                signed: -5,
+                unsigned: 1000,
+                maybe: None,
+                many: vec![7, 8, 9, 10],
+            }
+        );
+
+        for json in &[
+            r#"{"signed":"1.5","unsigned":0}"#,
+            r#"{"signed":0,"unsigned":-1}"#,
+            r#"{"signed":0,"unsigned":"-1"}"#,
+            r#"{"signed":"9223372036854775808","unsigned":0}"#,
+            r#"{"signed":9.3e18,"unsigned":0}"#,
+            r#"{"signed":"+1","unsigned":0}"#,
+            r#"{"signed":" 1","unsigned":0}"#,
+            r#"{"signed":"NaN","unsigned":0}"#,
+            r#"{"signed":true,"unsigned":0}"#,
+        ] {
+            assert!(serde_json::from_str::<Sample>(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -0,0 +1,473 @@
+//! Serializers and deserializers for Protobuf `map` fields.
+//!
+//! The Protobuf JSON mapping renders every map key as a JSON string, including integer and `bool`
+//! keys. The top-level [`serialize`] and [`deserialize`] functions implement the mapping
+//! strictly: integer keys must be provided as strings, and keys encoded as numbers are rejected.
+//!
+//! Some producers emit integer map keys as numbers in non-conforming payloads. The [`lenient`]
+//! module accepts both forms for integer keys, while still always serializing keys as strings.
+//!
+//! Both modules work with any map type which can be iterated by reference and collected from
+//! key/value pairs, such as `HashMap` and `BTreeMap`. Besides the Protobuf map key types, keys can
+//! also be `Timestamp` and `Duration`, rendered as in the JSON mapping, for maps which are not
+//! Protobuf fields, such as time-bucketed aggregates built from decoded messages.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use prost::alloc::collections::BTreeSet;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, DeserializeSeed, Deserializer, MapAccess, Unexpected, Visitor};
+use serde_crate::ser::{self, Serialize, Serializer};
+use serde_crate::Deserialize;
+
+use crate::datetime::{parse_duration, parse_timestamp};
+use crate::serde::mode;
+use crate::{Duration, Timestamp};
+
+/// A Protobuf map key type.
+///
+/// Implemented for each of the Rust types which `prost` generates for map keys.
+pub trait MapKey: Sized + fmt::Display {
+    /// Parses a key from its JSON string representation.
+    fn from_key_str(key: &str) -> Option<Self>;
+
+    /// Converts a key which was encoded as a signed JSON number.
+    ///
+    /// Returns `None` if the key type is not an integer, or the value is out of range.
+    fn from_key_i64(_key: i64) -> Option<Self> {
+        None
+    }
+
+    /// Converts a key which was encoded as an unsigned JSON number.
+    ///
+    /// Returns `None` if the key type is not an integer, or the value is out of range.
+    fn from_key_u64(_key: u64) -> Option<Self> {
+        None
+    }
+
+    #[doc(hidden)]
+    fn serialize_key<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+macro_rules! int_map_key {
+    ($($ty:ty),*) => {$(
+        impl MapKey for $ty {
+            fn from_key_str(key: &str) -> Option<$ty> {
+                key.parse().ok()
+            }
+
+            fn from_key_i64(key: i64) -> Option<$ty> {
+                core::convert::TryFrom::try_from(key).ok()
+            }
+
+            fn from_key_u64(key: u64) -> Option<$ty> {
+                core::convert::TryFrom::try_from(key).ok()
+            }
+
+            fn serialize_key<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_str(itoa::Buffer::new().format(*self))
+            }
+        }
+    )*};
+}
+
+int_map_key!(i32, i64, u32, u64);
+
+impl MapKey for bool {
+    fn from_key_str(key: &str) -> Option<bool> {
+        match key {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn serialize_key<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(if *self { "true" } else { "false" })
+    }
+}
+
+impl MapKey for String {
+    fn from_key_str(key: &str) -> Option<String> {
+        Some(key.into())
+    }
+
+    fn serialize_key<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self)
+    }
+}
+
+impl MapKey for Timestamp {
+    fn from_key_str(key: &str) -> Option<Timestamp> {
+        parse_timestamp(key)
+    }
+}
+
+impl MapKey for Duration {
+    fn from_key_str(key: &str) -> Option<Duration> {
+        parse_duration(key)
+    }
+}
+
+/// Serializes a map, rendering each key as a JSON string.
+pub fn serialize<'a, M, K, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+    K: MapKey + 'a,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    serializer.collect_map(map.into_iter().map(|(key, value)| (KeyString(key), value)))
+}
+
+/// Deserializes a map, requiring every key to be a JSON string.
+pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: Default + Extend<(K, V)> + IntoIterator<Item = (K, V)>,
+    K: MapKey,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(MapVisitor::new(false))
+}
+
+/// Map serialization which additionally accepts integer keys encoded as JSON numbers.
+pub mod lenient {
+    use super::*;
+
+    pub use super::serialize;
+
+    /// Deserializes a map, accepting integer keys as either JSON strings or JSON numbers.
+    pub fn deserialize<'de, M, K, V, D>(deserializer: D) -> Result<M, D::Error>
+    where
+        M: Default + Extend<(K, V)> + IntoIterator<Item = (K, V)>,
+        K: MapKey,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(MapVisitor::new(true))
+    }
+}
+
+/// Serializes a string-keyed map, replacing each key with its canonical form.
+///
+/// Fails if a key has no canonical form, in which case it is not a valid `kind`, or if two keys
+/// have the same canonical form.
+pub(super) fn serialize_canonical<'a, M, V, S>(
+    map: &'a M,
+    serializer: S,
+    canonicalize: fn(&str) -> Option<String>,
+    kind: &str,
+) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a String, &'a V)>,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    let mut seen = BTreeSet::new();
+    let mut entries = Vec::new();
+    for (key, value) in map {
+        let key = canonicalize(key)
+            .ok_or_else(|| ser::Error::custom(format_args!("invalid {} map key: {}", kind, key)))?;
+        if !seen.insert(key.clone()) {
+            return Err(ser::Error::custom(format_args!(
+                "duplicate map key after canonicalization: {}",
+                key
+            )));
+        }
+        entries.push((key, value));
+    }
+    serializer.collect_map(entries)
+}
+
+/// Deserializes a string-keyed map, replacing each key with its canonical form.
+///
+/// Fails if a key has no canonical form, in which case it is not a valid `kind`, or if two keys
+/// have the same canonical form.
+pub(super) fn deserialize_canonical<'de, M, V, D>(
+    deserializer: D,
+    canonicalize: fn(&str) -> Option<String>,
+    kind: &str,
+) -> Result<M, D::Error>
+where
+    M: Default + Extend<(String, V)> + IntoIterator<Item = (String, V)>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    let entries: Vec<(String, V)> = deserialize(deserializer)?;
+    let mut seen = BTreeSet::new();
+    let mut map = M::default();
+    for (key, value) in entries {
+        let key = canonicalize(&key)
+            .ok_or_else(|| de::Error::custom(format_args!("invalid {} map key: {}", kind, key)))?;
+        if !seen.insert(key.clone()) {
+            return Err(de::Error::custom(format_args!(
+                "duplicate map key after canonicalization: {}",
+                key
+            )));
+        }
+        map.extend(Some((key, value)));
+    }
+    Ok(map)
+}
+
+/// Serializes a map key using its JSON string representation.
+pub(super) struct KeyString<'a, K>(pub(super) &'a K);
+
+impl<'a, K> Serialize for KeyString<'a, K>
+where
+    K: MapKey,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize_key(serializer)
+    }
+}
+
+struct MapVisitor<M, K, V> {
+    lenient: bool,
+    map: PhantomData<fn() -> M>,
+    entry: PhantomData<fn() -> (K, V)>,
+}
+
+impl<M, K, V> MapVisitor<M, K, V> {
+    fn new(lenient: bool) -> MapVisitor<M, K, V> {
+        MapVisitor {
+            lenient,
+            map: PhantomData,
+            entry: PhantomData,
+        }
+    }
+}
+
+impl<'de, M, K, V> Visitor<'de> for MapVisitor<M, K, V>
+where
+    M: Default + Extend<(K, V)> + IntoIterator<Item = (K, V)>,
+    K: MapKey,
+    V: Deserialize<'de>,
+{
+    type Value = M;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<M, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut map = M::default();
+        while let Some(key) = access.next_key_seed(KeySeed::<K>::new(self.lenient))? {
+            let value = access.next_value()?;
+            map.extend(Some((key, value)));
+        }
+        Ok(map)
+    }
+}
+
+struct KeySeed<K> {
+    lenient: bool,
+    marker: PhantomData<fn() -> K>,
+}
+
+impl<K> KeySeed<K> {
+    fn new(lenient: bool) -> KeySeed<K> {
+        KeySeed {
+            lenient,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, K> DeserializeSeed<'de> for KeySeed<K>
+where
+    K: MapKey,
+{
+    type Value = K;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<K, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if mode::is_binary() {
+            deserializer.deserialize_string(self)
+        } else {
+            deserializer.deserialize_any(self)
+        }
+    }
+}
+
+impl<'de, K> Visitor<'de> for KeySeed<K>
+where
+    K: MapKey,
+{
+    type Value = K;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.lenient {
+            formatter.write_str("a map key string or integer")
+        } else {
+            formatter.write_str("a map key string")
+        }
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<K, E>
+    where
+        E: de::Error,
+    {
+        K::from_key_str(value).ok_or_else(|| E::invalid_value(Unexpected::Str(value), &self))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<K, E>
+    where
+        E: de::Error,
+    {
+        if !self.lenient {
+            return Err(E::invalid_type(Unexpected::Signed(value), &self));
+        }
+        K::from_key_i64(value).ok_or_else(|| E::invalid_value(Unexpected::Signed(value), &self))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<K, E>
+    where
+        E: de::Error,
+    {
+        if !self.lenient {
+            return Err(E::invalid_type(Unexpected::Unsigned(value), &self));
+        }
+        K::from_key_u64(value).ok_or_else(|| E::invalid_value(Unexpected::Unsigned(value), &self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+    use std::string::ToString;
+
+    use serde_crate::de::value::{Error, MapDeserializer};
+
+    use super::*;
+
+    type NumericKeys = MapDeserializer<'static, std::vec::IntoIter<(i64, &'static str)>, Error>;
+
+    fn numeric_keys(keys: &[i64]) -> NumericKeys {
+        MapDeserializer::new(
+            keys.iter()
+                .map(|&key| (key, "v"))
+                .collect::<Vec<_>>()
+                .into_iter(),
+        )
+    }
+
+    #[test]
+    fn string_keys() {
+        let mut de = serde_json::Deserializer::from_str(r#"{"-1": "a", "7": "b"}"#);
+        let map: BTreeMap<i32, String> = deserialize(&mut de).unwrap();
+        assert_eq!(map[&-1], "a");
+        assert_eq!(map[&7], "b");
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"7": "b"}"#);
+        let map: HashMap<u64, String> = lenient::deserialize(&mut de).unwrap();
+        assert_eq!(map[&7], "b");
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"true": 1, "false": 2}"#);
+        let map: BTreeMap<bool, i32> = deserialize(&mut de).unwrap();
+        assert_eq!(map[&true], 1);
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"x": 1}"#);
+        assert!(deserialize::<BTreeMap<i32, i32>, _, _, _>(&mut de).is_err());
+    }
+
+    #[test]
+    fn numeric_keys_strict() {
+        let result: Result<BTreeMap<i32, String>, Error> = deserialize(numeric_keys(&[1, 2]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn numeric_keys_lenient() {
+        let map: BTreeMap<i32, String> = lenient::deserialize(numeric_keys(&[-1, 2])).unwrap();
+        assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![-1, 2]);
+
+        // Out of range for the key type.
+        let result: Result<BTreeMap<i32, String>, Error> =
+            lenient::deserialize(numeric_keys(&[i64::MAX]));
+        assert!(result.is_err());
+        let result: Result<BTreeMap<u32, String>, Error> =
+            lenient::deserialize(numeric_keys(&[-1]));
+        assert!(result.is_err());
+
+        // Numbers are never accepted for non-integer keys.
+        let result: Result<BTreeMap<String, String>, Error> =
+            lenient::deserialize(numeric_keys(&[1]));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn time_keys() {
+        let mut map = BTreeMap::new();
+        map.insert(
+            Timestamp {
+                seconds: 60,
+                nanos: 500_000_000,
+            },
+            1,
+        );
+        map.insert(Timestamp::default(), 2);
+
+        let mut buf = Vec::new();
+        serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+        let json = std::str::from_utf8(&buf).unwrap();
+        assert_eq!(
+            json,
+            r#"{"1970-01-01T00:00:00Z":2,"1970-01-01T00:01:00.500Z":1}"#
+        );
+        let mut de = serde_json::Deserializer::from_str(json);
+        assert_eq!(
+            deserialize::<BTreeMap<_, _>, _, _, _>(&mut de).unwrap(),
+            map
+        );
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"-1.5s": "a", "3600s": "b"}"#);
+        let map: BTreeMap<Duration, String> = deserialize(&mut de).unwrap();
+        assert_eq!(
+            map.keys().map(ToString::to_string).collect::<Vec<_>>(),
+            vec!["-1.500s", "3600s"]
+        );
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"1h": "a"}"#);
+        assert!(deserialize::<BTreeMap<Duration, String>, _, _, _>(&mut de).is_err());
+    }
+
+    #[test]
+    fn serialize_keys_as_strings() {
+        let mut map = BTreeMap::new();
+        map.insert(-5i64, true);
+        map.insert(10i64, false);
+
+        let mut buf = Vec::new();
+        serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            r#"{"-5":true,"10":false}"#
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! Serde support for the Protobuf [JSON mapping][1].
+//!
+//! The modules defined here are intended to be used with serde's `#[serde(with = "...")]` field
+//! attribute on `prost` generated types, for example:
+//!
+//! ```rust,ignore
+//! #[derive(Clone, PartialEq, ::prost::Message, ::serde::Serialize, ::serde::Deserialize)]
+//! pub struct Inventory {
+//!     #[prost(btree_map="int32, string", tag="1")]
+//!     #[serde(with = "::prost_types::serde::map")]
+//!     pub items: ::prost::alloc::collections::BTreeMap<i32, ::prost::alloc::string::String>,
+//! }
+//! ```
+//!
+//! `prost-build` can generate these attributes, along with the serde derives, for every field of
+//! the generated types; see `Config::enable_serde_json`. The `Value`, `Struct`, `ListValue` and
+//! `FieldMask` types implement `Serialize` and `Deserialize` directly, following the JSON mapping;
+//! see [`value`] for the precision of the numbers they hold.
+//!
+//! The rendering of 64-bit integers and enum values can be switched at runtime to the legacy
+//! rendering of plain serde derives, to roll out the JSON mapping gradually; see [`mode`].
+//!
+//! Characters outside of ASCII can be escaped in the JSON output, for consumers which only accept
+//! ASCII; see [`escape`]. Floating point numbers can be written with a fixed number of decimal
+//! places, for consumers which compare the output as text; see [`float_format`].
+//!
+//! Binary formats such as CBOR and MessagePack produce the same document as JSON with these
+//! implementations, and can key fields by field number instead of by name; see [`compact`].
+//!
+//! This module is only available when the `serde` feature is enabled.
+//!
+//! [1]: https://developers.google.com/protocol-buffers/docs/proto3#json
+
+pub mod char;
+pub mod compact;
+pub mod duration;
+pub mod duration_key;
+mod dynamic;
+pub mod empty;
+pub mod enumeration;
+pub mod escape;
+pub mod events;
+mod field_mask;
+pub mod float;
+pub mod float_format;
+pub mod float_key;
+pub mod int32;
+pub mod int64;
+pub mod map;
+pub mod mode;
+mod number;
+pub mod oneof;
+pub mod query;
+pub mod timestamp;
+pub mod timestamp_key;
+pub mod value;
+pub mod vec_u8;
+
+pub use self::enumeration::EnumName;
+pub use self::map::MapKey;
+pub use self::oneof::OneofFields;
+
+/// Returns `true` if the value is the default value of its type.
+///
+/// This is used with `#[serde(skip_serializing_if = "...")]` to omit fields holding their default
+/// value, as the JSON mapping does for fields without presence. In [`mode::Mode::Binary`], fields
+/// are never omitted, so this returns `false`.
+pub fn is_default<T>(value: &T) -> bool
+where
+    T: Default + PartialEq,
+{
+    !mode::is_binary() && *value == T::default()
+}
+
+/// Returns `true` if the optional value is `None`.
+///
+/// This is used with `#[serde(skip_serializing_if = "...")]` to omit fields with presence which
+/// are not set. Like [`is_default`], this returns `false` in [`mode::Mode::Binary`].
+pub fn is_none<T>(value: &Option<T>) -> bool {
+    !mode::is_binary() && value.is_none()
+}
@@ -0,0 +1,259 @@
+//! Runtime selection between the JSON mapping and the legacy rendering of fields.
+//!
+//! Services which serialized `prost` types with plain serde derives before adopting the Protobuf
+//! JSON mapping render 64-bit integers and enum values as JSON numbers, while the mapping renders
+//! them as strings and enum value names. Switching every producer at once would break consumers
+//! which only understand one of the forms, so the serializers in this module's parent consult the
+//! current [`Mode`], which can be changed at runtime, for example from a configuration flag:
+//!
+//! ```rust,ignore
+//! use prost_types::serde::mode::{self, Mode};
+//!
+//! // Keep the legacy rendering until the flag is flipped for this deployment.
+//! mode::set_default(if config.spec_json { Mode::Spec } else { Mode::Legacy });
+//!
+//! // Render a response for a client which already understands the JSON mapping.
+//! let json = mode::with(Mode::Spec, || serde_json::to_string(&response))?;
+//! ```
+//!
+//! The mode only affects serialization. The deserializers accept both renderings in every mode,
+//! so consumers can be upgraded before producers.
+//!
+//! [`Mode::Binary`] is the exception. Formats which are not self-describing, such as `bincode` and
+//! `postcard`, can't tell a number from a string, so each value must be read back in the form it
+//! was written, and every field must be written. In this mode, values are serialized in a single
+//! form per type, and deserialized by asking the format for that form, so both sides must use the
+//! mode:
+//!
+//! ```rust,ignore
+//! let bytes = mode::with(Mode::Binary, || bincode::serialize(&event))?;
+//! let event: Event = mode::with(Mode::Binary, || bincode::deserialize(&bytes))?;
+//! ```
+//!
+//! Messages with a oneof, which serde flattens into the message, and the `Value`, `Struct` and
+//! `ListValue` types, which hold arbitrary values, still require a self-describing format.
+
+#[cfg(feature = "std")]
+use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+/// The rendering used by the serializers in `prost_types::serde`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// The Protobuf JSON mapping: 64-bit integers are rendered as strings, and enum values by
+    /// their name.
+    Spec,
+    /// The rendering of plain serde derives: 64-bit integers and enum values are rendered as
+    /// numbers.
+    Legacy,
+    /// The rendering for formats which are not self-describing: 64-bit integers, enum values and
+    /// non-finite floats are rendered as numbers, `bytes` as byte strings, and fields are never
+    /// omitted. Deserialization only accepts this rendering.
+    Binary,
+}
+
+impl Default for Mode {
+    fn default() -> Mode {
+        Mode::Spec
+    }
+}
+
+impl Mode {
+    fn from_u8(value: u8) -> Mode {
+        match value {
+            0 => Mode::Spec,
+            1 => Mode::Legacy,
+            _ => Mode::Binary,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            Mode::Spec => 0,
+            Mode::Legacy => 1,
+            Mode::Binary => 2,
+        }
+    }
+}
+
+static DEFAULT: AtomicU8 = AtomicU8::new(0);
+
+/// Set by the first call to [`with`]. Until then no thread has overridden the mode, so the
+/// serializers, which consult the mode for every field, can skip the thread-local lookup.
+#[cfg(feature = "std")]
+static SCOPED: AtomicBool = AtomicBool::new(false);
+
+/// Sets the process-wide mode, which is used on threads not running [`with`].
+///
+/// The mode is [`Mode::Spec`] until it is set.
+pub fn set_default(mode: Mode) {
+    DEFAULT.store(mode.to_u8(), Ordering::Relaxed);
+}
+
+/// Returns the mode used by serializers on the current thread.
+pub fn current() -> Mode {
+    #[cfg(feature = "std")]
+    {
+        if SCOPED.load(Ordering::Relaxed) {
+            if let Some(mode) = scoped::current() {
+                return mode;
+            }
+        }
+    }
+    Mode::from_u8(DEFAULT.load(Ordering::Relaxed))
+}
+
+/// Returns `true` if deserializers must ask the format for the form of the value.
+pub(crate) fn is_binary() -> bool {
+    current() == Mode::Binary
+}
+
+#[cfg(feature = "std")]
+pub use self::scoped::with;
+
+#[cfg(feature = "std")]
+mod scoped {
+    use core::cell::Cell;
+    use core::sync::atomic::Ordering;
+
+    use super::{Mode, SCOPED};
+
+    std::thread_local! {
+        static MODE: Cell<Option<Mode>> = Cell::new(None);
+    }
+
+    pub(super) fn current() -> Option<Mode> {
+        MODE.with(Cell::get)
+    }
+
+    /// Restores the enclosing mode, even if the closure panics.
+    struct Restore(Option<Mode>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            MODE.with(|mode| mode.set(self.0));
+        }
+    }
+
+    /// Runs the closure with the mode overridden on the current thread.
+    ///
+    /// Calls can be nested, the innermost mode applies. This requires the `std` feature.
+    pub fn with<F, R>(mode: Mode, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        // A thread always observes its own store, and other threads have no override to find.
+        SCOPED.store(true, Ordering::Relaxed);
+        let _restore = Restore(MODE.with(|current| current.replace(Some(mode))));
+        f()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scoped() {
+        assert_eq!(current(), Mode::Spec);
+        let modes = with(Mode::Legacy, || {
+            let inner = with(Mode::Spec, current);
+            (current(), inner)
+        });
+        assert_eq!(modes, (Mode::Legacy, Mode::Spec));
+        assert_eq!(current(), Mode::Spec);
+
+        let result = std::panic::catch_unwind(|| with(Mode::Legacy, || panic!("oops")));
+        assert!(result.is_err());
+        assert_eq!(current(), Mode::Spec);
+    }
+
+    #[test]
+    fn binary() {
+        use prost::alloc::collections::BTreeMap;
+        use serde_crate::{Deserialize, Serialize};
+
+        use crate::{Duration, NullValue, Timestamp};
+
+        #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate", default)]
+        struct Sample {
+            #[serde(
+                skip_serializing_if = "crate::serde::is_default",
+                with = "crate::serde::int64"
+            )]
+            signed: i64,
+            #[serde(
+                skip_serializing_if = "crate::serde::is_none",
+                with = "crate::serde::int64::option"
+            )]
+            unsigned: Option<u64>,
+            #[serde(
+                skip_serializing_if = "crate::serde::is_default",
+                with = "crate::serde::int32"
+            )]
+            small: u32,
+            #[serde(with = "crate::serde::float::repeated")]
+            floats: Vec<f32>,
+            #[serde(
+                serialize_with = "crate::serde::enumeration::serialize::<NullValue, _>",
+                deserialize_with = "crate::serde::enumeration::deserialize::<NullValue, _>"
+            )]
+            null: i32,
+            #[serde(with = "crate::serde::vec_u8")]
+            data: Vec<u8>,
+            #[serde(with = "crate::serde::timestamp::option")]
+            time: Option<Timestamp>,
+            #[serde(with = "crate::serde::duration")]
+            duration: Duration,
+            #[serde(
+                skip_serializing_if = "crate::serde::is_default",
+                with = "crate::serde::map"
+            )]
+            map: BTreeMap<i32, String>,
+            #[serde(with = "crate::serde::empty")]
+            empty: (),
+            #[serde(with = "crate::serde::char")]
+            letter: char,
+        }
+
+        let mut map = BTreeMap::new();
+        map.insert(-1, "a".to_string());
+        let sample = Sample {
+            signed: 0,
+            unsigned: Some(u64::MAX),
+            small: 7,
+            floats: vec![f32::NEG_INFINITY, 0.5],
+            null: 3,
+            data: vec![0, 255],
+            time: Some(Timestamp {
+                seconds: 1,
+                nanos: 5,
+            }),
+            duration: Duration {
+                seconds: -2,
+                nanos: 0,
+            },
+            map,
+            empty: (),
+            letter: 'λ',
+        };
+
+        let bytes = with(Mode::Binary, || bincode::serialize(&sample).unwrap());
+        let decoded: Sample = with(Mode::Binary, || bincode::deserialize(&bytes).unwrap());
+        assert_eq!(decoded, sample);
+
+        let bytes = with(Mode::Binary, || postcard::to_allocvec(&sample).unwrap());
+        let decoded: Sample = with(Mode::Binary, || postcard::from_bytes(&bytes).unwrap());
+        assert_eq!(decoded, sample);
+
+        let empty = Sample::default();
+        let bytes = with(Mode::Binary, || bincode::serialize(&empty).unwrap());
+        let decoded: Sample = with(Mode::Binary, || bincode::deserialize(&bytes).unwrap());
+        assert_eq!(decoded, empty);
+
+        // Without the mode, omitted fields and strings can't be read back.
+        let bytes = bincode::serialize(&sample).unwrap();
+        assert!(bincode::deserialize::<Sample>(&bytes).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Support for the arbitrary precision numbers of `serde_json`.
+//!
+//! With its `arbitrary_precision` feature, `serde_json` provides integers in range for `i64` or
+//! `u64` as usual, but provides other numbers to `deserialize_any` as a map holding the text of
+//! the number under a private key, so that they can be parsed without a loss of precision. Any
+//! crate in the build can enable the feature, so the visitors of this crate which accept numbers
+//! recognize this map whether or not the `arbitrary_precision` feature of this crate is enabled.
+
+use serde_crate::de::{self, MapAccess, Visitor};
+
+use prost::alloc::string::String;
+
+/// The key under which `serde_json` provides the text of a number.
+pub(crate) const TOKEN: &str = "$serde_json::private::Number";
+
+/// Visits the number held by a map provided by `serde_json`, or rejects any other map.
+pub(crate) fn visit_map<'de, A, V>(mut map: A, visitor: V) -> Result<V::Value, A::Error>
+where
+    A: MapAccess<'de>,
+    V: Visitor<'de>,
+{
+    match map.next_key::<String>()? {
+        Some(key) if key == TOKEN => {
+            let text = map.next_value::<String>()?;
+            visit(&text, visitor)
+        }
+        _ => Err(de::Error::invalid_type(de::Unexpected::Map, &visitor)),
+    }
+}
+
+/// Visits the text of a number as an `i64` or `u64` if it is an integer in range for one, and
+/// otherwise as the nearest `f64`.
+pub(crate) fn visit<'de, V, E>(text: &str, visitor: V) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: de::Error,
+{
+    if let Ok(value) = text.parse::<u64>() {
+        return visitor.visit_u64(value);
+    }
+    // `-0` is a float, as `serde_json` parses it.
+    if text != "-0" {
+        if let Ok(value) = text.parse::<i64>() {
+            return visitor.visit_i64(value);
+        }
+    }
+    match parse_f64(text) {
+        Some(value) => visitor.visit_f64(value),
+        None => Err(E::custom(format_args!("number out of range: {}", text))),
+    }
+}
+
+/// Parses the text of a number as the nearest `f64`, if it is finite.
+pub(crate) fn parse_f64(text: &str) -> Option<f64> {
+    text.parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_crate::de::value::{Error, MapDeserializer};
+
+    use super::*;
+
+    /// Returns a deserializer providing the text of a number as `serde_json` does with its
+    /// `arbitrary_precision` feature.
+    pub(crate) fn number(
+        text: &'static str,
+    ) -> MapDeserializer<'static, core::iter::Once<(&'static str, &'static str)>, Error> {
+        MapDeserializer::new(core::iter::once((TOKEN, text)))
+    }
+
+    #[test]
+    fn numeric_types() {
+        use crate::serde::{char, enumeration, float, int32, int64, timestamp};
+        use crate::Timestamp;
+
+        assert_eq!(int64::deserialize::<i64, _>(number("-1")), Ok(-1));
+        assert_eq!(
+            int64::deserialize::<u64, _>(number("18446744073709551615")),
+            Ok(u64::MAX)
+        );
+        assert_eq!(int64::deserialize::<i64, _>(number("1.5e3")), Ok(1500));
+        assert!(int64::deserialize::<u64, _>(number("18446744073709551616")).is_err());
+        assert_eq!(int32::deserialize::<i32, _>(number("-2.0")), Ok(-2));
+        assert_eq!(float::deserialize::<f64, _>(number("0.1")), Ok(0.1));
+        assert_eq!(float::deserialize::<f32, _>(number("-0")), Ok(-0.0));
+        assert!(float::deserialize::<f64, _>(number("1e400")).is_err());
+        assert_eq!(
+            enumeration::deserialize::<crate::NullValue, _>(number("0")),
+            Ok(0)
+        );
+        assert!(enumeration::deserialize::<crate::NullValue, _>(number("0.5")).is_err());
+        assert_eq!(char::deserialize(number("65")), Ok('A'));
+        assert_eq!(
+            timestamp::epoch_seconds::deserialize(number("1700000000.5")),
+            Ok(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 500_000_000,
+            })
+        );
+
+        let map = MapDeserializer::<_, Error>::new(core::iter::once(("seconds", "1")));
+        assert!(float::deserialize::<f64, _>(map).is_err());
+    }
+}
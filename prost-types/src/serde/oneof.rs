@@ -0,0 +1,268 @@
+//! Deserializer for Protobuf oneof fields.
+//!
+//! The Protobuf JSON mapping has no representation for a oneof itself: the field of the oneof
+//! which is set appears directly in its message, under the field's JSON name. `prost` generates
+//! an enum for each oneof, which serde would serialize as a field named after the oneof, holding
+//! an object with a single key. Marking the message's oneof field with `#[serde(flatten)]` moves
+//! that key into the message.
+//!
+//! Deserializing a flattened `Option` ignores errors, and accepts any number of the oneof's
+//! fields. The [`deserialize`] function is used instead, which reports errors in the field's
+//! value, and rejects messages which set more than one field of the oneof:
+//!
+//! ```rust,ignore
+//! #[prost(oneof="message::Result", tags="1, 2")]
+//! #[serde(flatten, deserialize_with = "::prost_types::serde::oneof::deserialize")]
+//! pub result: ::core::option::Option<message::Result>,
+//! ```
+//!
+//! The oneof enum must implement [`OneofFields`], which `prost-build` generates when
+//! `Config::enable_serde_json` is set.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use prost::alloc::string::String;
+use serde_crate::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, IgnoredAny, IntoDeserializer, MapAccess,
+    VariantAccess, Visitor,
+};
+use serde_crate::Deserialize;
+
+/// A Protobuf oneof, represented as an enum with a newtype variant for each of its fields.
+pub trait OneofFields {
+    /// The fully-qualified Protobuf name of the oneof.
+    const NAME: &'static str;
+
+    /// The names of the oneof's fields, as accepted by the enum's `Deserialize` implementation.
+    const FIELDS: &'static [&'static str];
+}
+
+/// Deserializes a flattened oneof from the fields of its message.
+///
+/// Fields which don't belong to the oneof are ignored.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: OneofFields + Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_map(OneofVisitor(PhantomData))
+}
+
+struct OneofVisitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> Visitor<'de> for OneofVisitor<T>
+where
+    T: OneofFields + Deserialize<'de>,
+{
+    type Value = Option<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "at most one field of oneof {}", T::NAME)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Option<T>, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut oneof: Option<(String, T)> = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if !T::FIELDS.contains(&&*key) {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            if let Some((ref set, _)) = oneof {
+                return Err(de::Error::custom(format_args!(
+                    "multiple fields of oneof {} are set: `{}` and `{}`",
+                    T::NAME,
+                    set,
+                    key
+                )));
+            }
+            let value = map.next_value_seed(FieldSeed {
+                key: &key,
+                marker: PhantomData,
+            })?;
+            oneof = Some((key, value));
+        }
+        Ok(oneof.map(|(_, value)| value))
+    }
+}
+
+/// Deserializes the oneof enum from the value of one of its fields.
+struct FieldSeed<'a, T> {
+    key: &'a str,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, 'de, T> DeserializeSeed<'de> for FieldSeed<'a, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(Field {
+            key: self.key,
+            value: deserializer,
+        })
+    }
+}
+
+/// Presents a field of the oneof to the enum's `Deserialize` implementation as the newtype variant
+/// with the field's name.
+struct Field<'a, D> {
+    key: &'a str,
+    value: D,
+}
+
+impl<'a, 'de, D> Deserializer<'de> for Field<'a, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, D::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self)
+    }
+
+    serde_crate::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'a, 'de, D> EnumAccess<'de> for Field<'a, D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+    type Variant = FieldValue<D>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, FieldValue<D>), D::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(self.key.into_deserializer())?;
+        Ok((variant, FieldValue(self.value)))
+    }
+}
+
+struct FieldValue<D>(D);
+
+impl<'de, D> VariantAccess<'de> for FieldValue<D>
+where
+    D: Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn unit_variant(self) -> Result<(), D::Error> {
+        Err(de::Error::invalid_type(
+            de::Unexpected::NewtypeVariant,
+            &"unit variant",
+        ))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, D::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.0)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, _visitor: V) -> Result<V::Value, D::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::NewtypeVariant,
+            &"tuple variant",
+        ))
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, D::Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(de::Error::invalid_type(
+            de::Unexpected::NewtypeVariant,
+            &"struct variant",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::string::ToString;
+    use serde_crate::Serialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    enum Outcome {
+        #[serde(rename = "ok")]
+        Ok(String),
+        #[serde(rename = "errorCode", alias = "error_code")]
+        ErrorCode(i32),
+    }
+
+    impl OneofFields for Outcome {
+        const NAME: &'static str = "test.Response.result";
+        const FIELDS: &'static [&'static str] = &["ok", "errorCode", "error_code"];
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate", default)]
+    struct Response {
+        id: i32,
+        #[serde(flatten, deserialize_with = "deserialize")]
+        result: Option<Outcome>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let response = Response {
+            id: 1,
+            result: Some(Outcome::Ok("done".to_string())),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#"{"id":1,"ok":"done"}"#);
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+
+        let response = Response::default();
+        let json = serde_json::to_string(&response).unwrap();
+        assert_eq!(json, r#"{"id":0}"#);
+        assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+
+        let json = r#"{"error_code":5,"unknown":true}"#;
+        assert_eq!(
+            serde_json::from_str::<Response>(json).unwrap().result,
+            Some(Outcome::ErrorCode(5))
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let error = serde_json::from_str::<Response>(r#"{"ok":"done","errorCode":5}"#)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "multiple fields of oneof test.Response.result are set: `ok` and `errorCode` \
+             at line 1 column 27"
+        );
+        assert!(serde_json::from_str::<Response>(r#"{"ok":"a","ok":"b"}"#).is_err());
+        assert!(serde_json::from_str::<Response>(r#"{"ok":1}"#).is_err());
+    }
+}
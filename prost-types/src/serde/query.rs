@@ -0,0 +1,422 @@
+//! Deserialization of messages from URL query strings.
+//!
+//! REST endpoints commonly take the fields of a request as query parameters, such as
+//! `GET /v1/books?filter.author=Tolkien&tags=x&tags=y&pageSize=10`. [`from_str`] deserializes such
+//! a query string into a message generated with `prost-build`'s `Config::enable_serde_json`:
+//!
+//! ```rust,ignore
+//! let request: ListBooksRequest = prost_types::serde::query::from_str(uri.query().unwrap_or(""))?;
+//! ```
+//!
+//! The parameters are mapped to the fields of the message as follows:
+//!
+//!  * Parameters are named by their field path, the names of the fields separated by `.`, such as
+//!    `filter.author`. The names are the JSON names of the fields, or their names in the Protobuf
+//!    definition. The entries of a `map` field are named by their key, such as `labels.env`.
+//!  * Each parameter of a repeated field appends a value to it.
+//!  * Values are parsed as the JSON string values of their fields are, by the same visitors:
+//!    numbers are decimal strings, enum values are names or numbers, `bytes` values are base64,
+//!    and timestamps are RFC 3339 strings. `bool` values are `true` or `false`.
+//!  * Names and values are percent-decoded, and `+` is decoded as a space.
+//!
+//! Parameters which name no field are ignored, as unknown JSON fields are. `bool` fields of oneofs
+//! can't be set, because serde buffers the values of oneofs without their type.
+
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec;
+use prost::alloc::vec::Vec;
+use serde_crate::de::value::{Error, MapDeserializer, SeqDeserializer, StringDeserializer};
+use serde_crate::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, Visitor};
+
+use crate::http_rule::percent_decode;
+
+/// Deserializes a message from a query string, without the leading `?`.
+pub fn from_str<T>(query: &str) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let mut fields = Vec::new();
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (name, value) = match parameter.find('=') {
+            Some(index) => (&parameter[..index], &parameter[index + 1..]),
+            None => (parameter, ""),
+        };
+        let decode = |s: &str| {
+            percent_decode(&s.replace('+', " "), false).ok_or_else(|| {
+                de::Error::custom(format_args!("invalid query parameter {:?}", parameter))
+            })
+        };
+        insert(&mut fields, &decode(name)?, decode(value)?)?;
+    }
+    T::deserialize(Node::Fields(fields))
+}
+
+/// The parameters of a query string, as a tree of field paths.
+enum Node {
+    /// The values of the parameters named by a field path.
+    Values(Vec<String>),
+    /// The nested fields of a field path, in the order of their first parameter.
+    Fields(Vec<(String, Node)>),
+}
+
+/// Adds the value of a parameter to the tree.
+fn insert(fields: &mut Vec<(String, Node)>, name: &str, value: String) -> Result<(), Error> {
+    let (first, rest) = match name.find('.') {
+        Some(index) => (&name[..index], Some(&name[index + 1..])),
+        None => (name, None),
+    };
+    if first.is_empty() {
+        return Err(de::Error::custom(format_args!(
+            "invalid query parameter name {:?}",
+            name
+        )));
+    }
+    let index = match fields.iter().position(|(field, _)| field == first) {
+        Some(index) => index,
+        None => {
+            let node = match rest {
+                Some(_) => Node::Fields(Vec::new()),
+                None => Node::Values(Vec::new()),
+            };
+            fields.push((first.to_string(), node));
+            fields.len() - 1
+        }
+    };
+    match (&mut fields[index].1, rest) {
+        (Node::Values(values), None) => values.push(value),
+        (Node::Fields(fields), Some(rest)) => insert(fields, rest, value)?,
+        _ => {
+            return Err(de::Error::custom(format_args!(
+                "query parameter {} is both a value and a message",
+                first
+            )))
+        }
+    }
+    Ok(())
+}
+
+impl Node {
+    /// Returns the value of a field holding a single value.
+    fn into_value(self) -> Result<String, Error> {
+        match self {
+            Node::Values(mut values) if values.len() == 1 => Ok(values.remove(0)),
+            Node::Values(_) => Err(de::Error::invalid_type(
+                de::Unexpected::Seq,
+                &"a single query parameter",
+            )),
+            Node::Fields(_) => Err(de::Error::invalid_type(
+                de::Unexpected::Map,
+                &"a single query parameter",
+            )),
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Node {
+    type Deserializer = Node;
+
+    fn into_deserializer(self) -> Node {
+        self
+    }
+}
+
+/// Deserializes a number or a `char` by parsing a single value.
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident,)*) => {$(
+        fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            let value = self.into_value()?;
+            match value.parse() {
+                Ok(parsed) => visitor.$visit(parsed),
+                Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&value), &visitor)),
+            }
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for Node {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match &self {
+            Node::Values(values) if values.len() == 1 => self.deserialize_string(visitor),
+            Node::Values(_) => self.deserialize_seq(visitor),
+            Node::Fields(_) => self.deserialize_map(visitor),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.into_value()?.as_str() {
+            "true" => visitor.visit_bool(true),
+            "false" => visitor.visit_bool(false),
+            value => Err(de::Error::invalid_value(
+                de::Unexpected::Str(value),
+                &visitor,
+            )),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.into_value()?)
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Values(values) => {
+                let values = values.into_iter().map(|value| Node::Values(vec![value]));
+                let mut seq = SeqDeserializer::new(values);
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Node::Fields(_) => Err(de::Error::invalid_type(de::Unexpected::Map, &visitor)),
+        }
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Node::Fields(fields) => {
+                let mut map = MapDeserializer::new(fields.into_iter());
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Node::Values(_) => Err(de::Error::invalid_type(de::Unexpected::Str(""), &visitor)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let variant: StringDeserializer<Error> = self.into_value()?.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::collections::BTreeMap;
+    use serde_crate::Deserialize;
+
+    use crate::Timestamp;
+
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    #[serde(crate = "serde_crate", default)]
+    struct Filter {
+        #[serde(rename = "minPages", alias = "min_pages", with = "crate::serde::int64")]
+        min_pages: i64,
+        #[serde(with = "crate::serde::timestamp::option")]
+        since: Option<Timestamp>,
+        labels: BTreeMap<String, String>,
+    }
+
+    #[derive(Debug, Default, PartialEq, Deserialize)]
+    #[serde(crate = "serde_crate", default)]
+    struct ListBooksRequest {
+        #[serde(rename = "pageSize", alias = "page_size", with = "crate::serde::int32")]
+        page_size: i32,
+        tags: Vec<String>,
+        #[serde(with = "crate::serde::float::repeated")]
+        scores: Vec<f64>,
+        descending: bool,
+        filter: Option<Filter>,
+    }
+
+    #[test]
+    fn from_str() {
+        let request: ListBooksRequest = super::from_str(
+            "page_size=10&tags=a+b&tags=%E2%9C%93&scores=1.5&scores=-Infinity&descending=true\
+             &filter.minPages=100&filter.since=1970-01-01T00:00:01Z&filter.labels.genre=fantasy\
+             &unknown=1",
+        )
+        .unwrap();
+        let mut labels = BTreeMap::new();
+        labels.insert("genre".to_string(), "fantasy".to_string());
+        assert_eq!(
+            request,
+            ListBooksRequest {
+                page_size: 10,
+                tags: vec!["a b".to_string(), "\u{2713}".to_string()],
+                scores: vec![1.5, f64::NEG_INFINITY],
+                descending: true,
+                filter: Some(Filter {
+                    min_pages: 100,
+                    since: Some(Timestamp {
+                        seconds: 1,
+                        nanos: 0
+                    }),
+                    labels,
+                }),
+            }
+        );
+
+        let request: ListBooksRequest = super::from_str("tags=x&pageSize=1e1").unwrap();
+        assert_eq!(
+            request,
+            ListBooksRequest {
+                page_size: 10,
+                tags: vec!["x".to_string()],
+                ..ListBooksRequest::default()
+            }
+        );
+        assert_eq!(
+            super::from_str::<ListBooksRequest>("").unwrap(),
+            ListBooksRequest::default()
+        );
+
+        for query in &[
+            "page_size=1.5",
+            "page_size=1&page_size=2",
+            "page_size.a=1",
+            "descending=yes",
+            "filter=x",
+            "filter.since=yesterday",
+            "tags=%FF",
+            ".a=1",
+            "unknown=1&unknown.nested=2",
+        ] {
+            assert!(
+                super::from_str::<ListBooksRequest>(query).is_err(),
+                "{}",
+                query
+            );
+        }
+    }
+}
@@ -0,0 +1,1019 @@
+//! Serializers and deserializers for `google.protobuf.Timestamp` fields.
+//!
+//! Timestamps are serialized as RFC 3339 date-times in UTC, e.g. `"1972-01-01T10:00:20.021Z"`,
+//! and deserialized from RFC 3339 date-times with any UTC offset.
+//!
+//! Serialization normalizes the timestamp, and fails only if the normalized timestamp is outside
+//! of the range `0001-01-01T00:00:00Z` to `9999-12-31T23:59:59.999999999Z`. The [`strict`] module
+//! instead fails for any timestamp which is not already valid, as checked by
+//! [`Timestamp::is_valid`], matching the reference implementation.
+//!
+//! Producers which serialized `Timestamp` with plain serde derives render it as an object with
+//! `seconds` and `nanos` fields instead, e.g. `{"seconds": 63108020, "nanos": 21000000}`. The
+//! [`lenient`] module additionally accepts this object form, while still serializing timestamps as
+//! RFC 3339 strings. The other modules reject it, as the JSON mapping does.
+//!
+//! Many producers instead render timestamps as numbers of seconds or milliseconds since the Unix
+//! epoch, e.g. `63108020.021` or `63108020021`. The [`epoch_seconds`] and [`epoch_millis`] modules
+//! additionally accept these numbers, with a fractional part for sub-second precision.
+//!
+//! Leap seconds, such as `"2016-12-31T23:59:60Z"`, are rejected, as the JSON mapping does. Feeds
+//! which timestamp events by UTC emit them, and the [`clamp_leap_seconds`] and
+//! [`smear_leap_seconds`] modules accept them with [`LeapSecondPolicy::Clamp`] and
+//! [`LeapSecondPolicy::Smear`].
+
+use core::convert::TryFrom;
+use core::fmt;
+
+use prost::alloc::format;
+use prost::alloc::string::String;
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_crate::ser::{self, Serialize, Serializer};
+
+use crate::arithmetic::{TIMESTAMP_SECONDS_MAX, TIMESTAMP_SECONDS_MIN};
+use crate::serde::mode;
+use crate::serde::number;
+use crate::{LeapSecondPolicy, Timestamp, NANOS_PER_SECOND};
+
+/// Serializes a timestamp as an RFC 3339 string, normalizing it first.
+pub fn serialize<S>(value: &Timestamp, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value.checked_normalize() {
+        Some(timestamp) => serializer.collect_str(&timestamp),
+        None => Err(ser::Error::custom(format_args!(
+            "timestamp out of range: {:?}",
+            value
+        ))),
+    }
+}
+
+/// Deserializes a timestamp from an RFC 3339 string.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_string(TimestampVisitor {
+        accept: Accept::Rfc3339,
+    })
+}
+
+/// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes an optional timestamp as an RFC 3339 string or `null`.
+    pub fn serialize<S>(value: &Option<Timestamp>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(value) => serializer.serialize_some(&Normalized(value)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes an optional timestamp from an RFC 3339 string or `null`.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor {
+            accept: Accept::Rfc3339,
+        })
+    }
+
+    pub(super) struct OptionVisitor {
+        pub(super) accept: Accept,
+    }
+
+    impl<'de> Visitor<'de> for OptionVisitor {
+        type Value = Option<Timestamp>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            TimestampVisitor {
+                accept: self.accept,
+            }
+            .expecting(formatter)?;
+            formatter.write_str(" or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<Timestamp>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<Timestamp>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<Timestamp>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserialize_timestamp(deserializer, self.accept).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of timestamps as an array of RFC 3339 strings.
+    pub fn serialize<S>(values: &[Timestamp], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(Normalized))
+    }
+
+    /// Deserializes a list of timestamps from an array of RFC 3339 strings.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Timestamp>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor {
+            accept: Accept::Rfc3339,
+        })
+    }
+
+    pub(super) struct SeqVisitor {
+        pub(super) accept: Accept,
+    }
+
+    impl<'de> Visitor<'de> for SeqVisitor {
+        type Value = Vec<Timestamp>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of RFC 3339 strings")?;
+            match self.accept {
+                Accept::Rfc3339 | Accept::LeapSeconds(_) => Ok(()),
+                Accept::Object => formatter.write_str(" or timestamp objects"),
+                Accept::EpochSeconds => {
+                    formatter.write_str(" or numbers of seconds since the Unix epoch")
+                }
+                Accept::EpochMillis => {
+                    formatter.write_str(" or numbers of milliseconds since the Unix epoch")
+                }
+            }
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Timestamp>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element_seed(TimestampVisitor {
+                accept: self.accept,
+            })? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
+/// Serializers and deserializers which also accept timestamps in the object form of plain serde
+/// derives, such as `{"seconds": 1, "nanos": 500000000}`.
+///
+/// Either field may be omitted, in which case it is zero. Serialization is the same as in the
+/// parent module.
+pub mod lenient {
+    use super::*;
+
+    pub use super::serialize;
+
+    /// Deserializes a timestamp from an RFC 3339 string or a timestamp object.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_timestamp(deserializer, Accept::Object)
+    }
+
+    /// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+    pub mod option {
+        use super::super::option::OptionVisitor;
+        use super::*;
+
+        pub use super::super::option::serialize;
+
+        /// Deserializes an optional timestamp from an RFC 3339 string, a timestamp object or
+        /// `null`.
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_option(OptionVisitor {
+                accept: Accept::Object,
+            })
+        }
+    }
+
+    /// Serializers and deserializers for `repeated` fields.
+    pub mod repeated {
+        use super::super::repeated::SeqVisitor;
+        use super::*;
+
+        pub use super::super::repeated::serialize;
+
+        /// Deserializes a list of timestamps from an array of RFC 3339 strings or timestamp
+        /// objects.
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Timestamp>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_seq(SeqVisitor {
+                accept: Accept::Object,
+            })
+        }
+    }
+}
+
+macro_rules! epoch_module {
+    ($module:ident, $accept:ident, $unit:literal, $example:literal) => {
+        #[doc = concat!(
+            "Serializers and deserializers which also accept timestamps as numbers of ",
+            $unit,
+            " since the Unix epoch, such as `",
+            $example,
+            "`.\n\n",
+            "Numbers may have a fractional part, which is rounded to the nearest nanosecond. ",
+            "Serialization is the same as in the parent module."
+        )]
+        pub mod $module {
+            use super::*;
+
+            pub use super::serialize;
+
+            #[doc = concat!(
+                "Deserializes a timestamp from an RFC 3339 string or a number of ",
+                $unit,
+                " since the Unix epoch."
+            )]
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserialize_timestamp(deserializer, Accept::$accept)
+            }
+
+            /// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+            pub mod option {
+                use super::super::option::OptionVisitor;
+                use super::*;
+
+                pub use super::super::option::serialize;
+
+                #[doc = concat!(
+                    "Deserializes an optional timestamp from an RFC 3339 string, a number of ",
+                    $unit,
+                    " since the Unix epoch or `null`."
+                )]
+                pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    deserializer.deserialize_option(OptionVisitor {
+                        accept: Accept::$accept,
+                    })
+                }
+            }
+
+            /// Serializers and deserializers for `repeated` fields.
+            pub mod repeated {
+                use super::super::repeated::SeqVisitor;
+                use super::*;
+
+                pub use super::super::repeated::serialize;
+
+                #[doc = concat!(
+                    "Deserializes a list of timestamps from an array of RFC 3339 strings or ",
+                    "numbers of ",
+                    $unit,
+                    " since the Unix epoch."
+                )]
+                pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Timestamp>, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    deserializer.deserialize_seq(SeqVisitor {
+                        accept: Accept::$accept,
+                    })
+                }
+            }
+        }
+    };
+}
+
+epoch_module!(epoch_seconds, EpochSeconds, "seconds", "1700000000.5");
+epoch_module!(epoch_millis, EpochMillis, "milliseconds", "1700000000500");
+
+macro_rules! leap_seconds_module {
+    ($module:ident, $policy:ident) => {
+        /// Serializers and deserializers which accept leap seconds, such as
+        /// `"2016-12-31T23:59:60Z"`,
+        #[doc = concat!("parsing them with [`LeapSecondPolicy::", stringify!($policy), "`].")]
+        ///
+        /// Serialization is the same as in the parent module.
+        pub mod $module {
+            use super::*;
+
+            pub use super::serialize;
+
+            /// Deserializes a timestamp from an RFC 3339 string, which may be a leap second.
+            pub fn deserialize<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserialize_timestamp(deserializer, Accept::LeapSeconds(LeapSecondPolicy::$policy))
+            }
+
+            /// Serializers and deserializers for `optional` fields, mapping `None` to `null`.
+            pub mod option {
+                use super::super::option::OptionVisitor;
+                use super::*;
+
+                pub use super::super::option::serialize;
+
+                /// Deserializes an optional timestamp from an RFC 3339 string, which may be a leap
+                /// second, or `null`.
+                pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    deserializer.deserialize_option(OptionVisitor {
+                        accept: Accept::LeapSeconds(LeapSecondPolicy::$policy),
+                    })
+                }
+            }
+
+            /// Serializers and deserializers for `repeated` fields.
+            pub mod repeated {
+                use super::super::repeated::SeqVisitor;
+                use super::*;
+
+                pub use super::super::repeated::serialize;
+
+                /// Deserializes a list of timestamps from an array of RFC 3339 strings, which may be
+                /// leap seconds.
+                pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Timestamp>, D::Error>
+                where
+                    D: Deserializer<'de>,
+                {
+                    deserializer.deserialize_seq(SeqVisitor {
+                        accept: Accept::LeapSeconds(LeapSecondPolicy::$policy),
+                    })
+                }
+            }
+        }
+    };
+}
+
+leap_seconds_module!(clamp_leap_seconds, Clamp);
+leap_seconds_module!(smear_leap_seconds, Smear);
+
+/// Serializers and deserializers which reject timestamps that are not valid, rather than
+/// normalizing them.
+pub mod strict {
+    use super::*;
+
+    pub use super::deserialize;
+
+    /// Serializes a timestamp as an RFC 3339 string, failing if it is not valid.
+    pub fn serialize<S>(value: &Timestamp, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if value.is_valid() {
+            serializer.collect_str(value)
+        } else {
+            Err(ser::Error::custom(format_args!(
+                "invalid timestamp: {:?}",
+                value
+            )))
+        }
+    }
+}
+
+struct Normalized<'a>(&'a Timestamp);
+
+impl Serialize for Normalized<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(self.0, serializer)
+    }
+}
+
+fn deserialize_timestamp<'de, D>(deserializer: D, accept: Accept) -> Result<Timestamp, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    de::DeserializeSeed::deserialize(TimestampVisitor { accept }, deserializer)
+}
+
+/// The form of timestamps which is accepted in addition to RFC 3339 strings.
+#[derive(Clone, Copy, PartialEq)]
+enum Accept {
+    /// Only RFC 3339 strings are accepted.
+    Rfc3339,
+    /// Timestamp objects with `seconds` and `nanos` fields.
+    Object,
+    /// Numbers of seconds since the Unix epoch.
+    EpochSeconds,
+    /// Numbers of milliseconds since the Unix epoch.
+    EpochMillis,
+    /// Only RFC 3339 strings are accepted, including leap seconds, which are parsed with the policy.
+    LeapSeconds(LeapSecondPolicy),
+}
+
+impl Accept {
+    /// Returns the number of units of the epoch numbers per second.
+    fn units_per_second(self) -> Option<i64> {
+        match self {
+            Accept::EpochSeconds => Some(1),
+            Accept::EpochMillis => Some(1_000),
+            Accept::Rfc3339 | Accept::Object | Accept::LeapSeconds(_) => None,
+        }
+    }
+
+    /// Returns `true` if only RFC 3339 strings are accepted.
+    fn strings_only(self) -> bool {
+        matches!(self, Accept::Rfc3339 | Accept::LeapSeconds(_))
+    }
+}
+
+/// Deserializes a timestamp from an RFC 3339 string, or also from the form it accepts.
+#[derive(Clone, Copy)]
+struct TimestampVisitor {
+    accept: Accept,
+}
+
+impl TimestampVisitor {
+    fn epoch<E>(
+        self,
+        timestamp: Option<Timestamp>,
+        unexpected: de::Unexpected<'_>,
+    ) -> Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        if self.accept.units_per_second().is_none() {
+            return Err(E::invalid_type(unexpected, &self));
+        }
+        timestamp.ok_or_else(|| E::invalid_value(unexpected, &"a timestamp within range"))
+    }
+}
+
+impl<'de> de::DeserializeSeed<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Timestamp, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if !self.accept.strings_only() && !mode::is_binary() {
+            deserializer.deserialize_any(self)
+        } else {
+            deserializer.deserialize_string(self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = Timestamp;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("an RFC 3339 timestamp")?;
+        match self.accept {
+            Accept::Rfc3339 | Accept::LeapSeconds(_) => Ok(()),
+            Accept::Object => formatter.write_str(" or a timestamp object"),
+            Accept::EpochSeconds => {
+                formatter.write_str(" or a number of seconds since the Unix epoch")
+            }
+            Accept::EpochMillis => {
+                formatter.write_str(" or a number of milliseconds since the Unix epoch")
+            }
+        }
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        let timestamp = self
+            .accept
+            .units_per_second()
+            .and_then(|units| epoch_from_i64(value, units));
+        self.epoch(timestamp, de::Unexpected::Signed(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        let timestamp = self.accept.units_per_second().and_then(|units| {
+            i64::try_from(value)
+                .ok()
+                .and_then(|value| epoch_from_i64(value, units))
+        });
+        self.epoch(timestamp, de::Unexpected::Unsigned(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        let timestamp = self
+            .accept
+            .units_per_second()
+            .and_then(|units| epoch_from_f64(value, units));
+        self.epoch(timestamp, de::Unexpected::Float(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Timestamp, E>
+    where
+        E: de::Error,
+    {
+        let policy = match self.accept {
+            Accept::LeapSeconds(policy) => policy,
+            _ => LeapSecondPolicy::Reject,
+        };
+        Timestamp::parse_with(value, policy)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Timestamp, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut next = map.next_key::<String>()?;
+        if next.as_deref() == Some(number::TOKEN) {
+            let text = map.next_value::<String>()?;
+            return number::visit(&text, self);
+        }
+        if self.accept != Accept::Object {
+            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+        }
+        let mut seconds = None;
+        let mut nanos = None;
+        while let Some(key) = next {
+            match &*key {
+                "seconds" if seconds.is_none() => seconds = Some(map.next_value::<Seconds>()?.0),
+                "nanos" if nanos.is_none() => nanos = Some(map.next_value::<Nanos>()?.0),
+                "seconds" | "nanos" => {
+                    return Err(de::Error::custom(format_args!("duplicate field `{}`", key)))
+                }
+                _ => return Err(de::Error::unknown_field(&key, &["seconds", "nanos"])),
+            }
+            next = map.next_key::<String>()?;
+        }
+        let timestamp = Timestamp {
+            seconds: seconds.unwrap_or(0),
+            nanos: nanos.unwrap_or(0),
+        };
+        if timestamp.is_valid() {
+            Ok(timestamp)
+        } else {
+            Err(de::Error::custom(format_args!(
+                "invalid timestamp: {:?}",
+                timestamp
+            )))
+        }
+    }
+}
+
+/// Converts a whole number of units since the Unix epoch to a valid timestamp.
+fn epoch_from_i64(value: i64, units_per_second: i64) -> Option<Timestamp> {
+    let nanos_per_unit = i64::from(NANOS_PER_SECOND) / units_per_second;
+    Some(Timestamp {
+        seconds: value.div_euclid(units_per_second),
+        nanos: (value.rem_euclid(units_per_second) * nanos_per_unit) as i32,
+    })
+    .filter(Timestamp::is_valid)
+}
+
+/// Converts a number of units since the Unix epoch to a valid timestamp, rounding to the nearest
+/// nanosecond.
+///
+/// The fractional part is taken from the shortest decimal representation of the number, which is
+/// what producers wrote, so that e.g. `0.021` seconds are 21 milliseconds rather than the
+/// 20.999999999999999 milliseconds of the closest `f64`.
+fn epoch_from_f64(value: f64, units_per_second: i64) -> Option<Timestamp> {
+    let min = (TIMESTAMP_SECONDS_MIN * units_per_second) as f64;
+    let max = ((TIMESTAMP_SECONDS_MAX + 1) * units_per_second) as f64;
+    // Also rejects NaN.
+    if !(min..max).contains(&value) {
+        return None;
+    }
+
+    // `Display` never uses exponent notation for `f64`.
+    let decimal = format!("{}", value.abs());
+    let (whole, fraction) = match decimal.find('.') {
+        Some(point) => (&decimal[..point], &decimal[point + 1..]),
+        None => (&decimal[..], ""),
+    };
+    let mut units = whole.parse::<i64>().ok()?;
+
+    // The number of fractional digits of a unit which are nanoseconds.
+    let nanos_per_unit = i64::from(NANOS_PER_SECOND) / units_per_second;
+    let digits = match nanos_per_unit {
+        1_000_000_000 => 9,
+        _ => 6,
+    };
+    let mut fraction_nanos = 0;
+    for i in 0..digits {
+        let digit = fraction.as_bytes().get(i).map_or(0, |digit| digit - b'0');
+        fraction_nanos = fraction_nanos * 10 + i64::from(digit);
+    }
+    if fraction.as_bytes().get(digits) >= Some(&b'5') {
+        fraction_nanos += 1;
+    }
+
+    if value.is_sign_negative() && fraction_nanos > 0 {
+        units = -units - 1;
+        fraction_nanos = nanos_per_unit - fraction_nanos;
+    } else if value.is_sign_negative() {
+        units = -units;
+    }
+    let mut seconds = units.div_euclid(units_per_second);
+    let mut nanos = units.rem_euclid(units_per_second) * nanos_per_unit + fraction_nanos;
+    if nanos >= i64::from(NANOS_PER_SECOND) {
+        seconds += 1;
+        nanos -= i64::from(NANOS_PER_SECOND);
+    }
+    Some(Timestamp {
+        seconds,
+        nanos: nanos as i32,
+    })
+    .filter(Timestamp::is_valid)
+}
+
+/// The `seconds` field of a timestamp object, as a number or a string.
+struct Seconds(i64);
+
+impl<'de> Deserialize<'de> for Seconds {
+    fn deserialize<D>(deserializer: D) -> Result<Seconds, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        crate::serde::int64::deserialize(deserializer).map(Seconds)
+    }
+}
+
+/// The `nanos` field of a timestamp object, as a number or a string.
+struct Nanos(i32);
+
+impl<'de> Deserialize<'de> for Nanos {
+    fn deserialize<D>(deserializer: D) -> Result<Nanos, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        crate::serde::int32::deserialize(deserializer).map(Nanos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    use crate::Timestamp;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Event {
+        #[serde(with = "super")]
+        at: Timestamp,
+        #[serde(with = "super::option", default)]
+        until: Option<Timestamp>,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Strict {
+        #[serde(with = "super::strict")]
+        at: Timestamp,
+    }
+
+    #[test]
+    fn round_trip() {
+        let event = Event {
+            at: Timestamp {
+                seconds: 63_108_020,
+                nanos: 21_000_000,
+            },
+            until: Some(Timestamp::default()),
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert_eq!(
+            json,
+            r#"{"at":"1972-01-01T10:00:20.021Z","until":"1970-01-01T00:00:00Z"}"#
+        );
+        assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+
+        let event: Event = serde_json::from_str(r#"{"at":"1970-01-01T01:00:00+01:00"}"#).unwrap();
+        assert_eq!(event.at, Timestamp::default());
+        assert_eq!(event.until, None);
+
+        for json in &[
+            r#"{"at":"1970-01-01T00:00:00"}"#,
+            r#"{"at":"0000-01-01T00:00:00Z"}"#,
+            r#"{"at":0}"#,
+        ] {
+            assert!(serde_json::from_str::<Event>(json).is_err(), "{}", json);
+        }
+    }
+
+    #[test]
+    fn normalization() {
+        let unnormalized = Timestamp {
+            seconds: 1,
+            nanos: -500_000_000,
+        };
+        let event = Event {
+            at: unnormalized.clone(),
+            until: Some(unnormalized.clone()),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"at":"1970-01-01T00:00:00.500Z","until":"1970-01-01T00:00:00.500Z"}"#
+        );
+        assert!(serde_json::to_string(&Strict { at: unnormalized }).is_err());
+
+        let out_of_range = Timestamp {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        assert!(serde_json::to_string(&Strict {
+            at: out_of_range.clone()
+        })
+        .is_err());
+        let event = Event {
+            at: out_of_range,
+            until: None,
+        };
+        assert!(serde_json::to_string(&event).is_err());
+    }
+
+    #[test]
+    fn repeated() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct History {
+            #[serde(with = "super::repeated")]
+            at: Vec<Timestamp>,
+        }
+
+        let history = History {
+            at: vec![
+                Timestamp {
+                    seconds: 0,
+                    nanos: 1_500_000_000,
+                },
+                Timestamp::default(),
+            ],
+        };
+        let json = serde_json::to_string(&history).unwrap();
+        assert_eq!(
+            json,
+            r#"{"at":["1970-01-01T00:00:01.500Z","1970-01-01T00:00:00Z"]}"#
+        );
+        let decoded: History = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.at[0].seconds, 1);
+        assert!(serde_json::from_str::<History>(r#"{"at":[null]}"#).is_err());
+    }
+
+    #[test]
+    fn lenient() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Legacy {
+            #[serde(with = "super::lenient")]
+            at: Timestamp,
+            #[serde(with = "super::lenient::option", default)]
+            until: Option<Timestamp>,
+            #[serde(with = "super::lenient::repeated", default)]
+            history: Vec<Timestamp>,
+        }
+
+        let json = r#"{
+            "at": {"seconds": 63108020, "nanos": 21000000},
+            "until": {"seconds": "1"},
+            "history": [{}, "1970-01-01T00:00:02Z", {"nanos": 5}]
+        }"#;
+        let legacy: Legacy = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            legacy,
+            Legacy {
+                at: Timestamp {
+                    seconds: 63_108_020,
+                    nanos: 21_000_000,
+                },
+                until: Some(Timestamp {
+                    seconds: 1,
+                    nanos: 0,
+                }),
+                history: vec![
+                    Timestamp::default(),
+                    Timestamp {
+                        seconds: 2,
+                        nanos: 0,
+                    },
+                    Timestamp {
+                        seconds: 0,
+                        nanos: 5,
+                    },
+                ],
+            }
+        );
+        // Timestamps are still serialized as RFC 3339 strings.
+        assert_eq!(
+            serde_json::to_string(&legacy).unwrap(),
+            r#"{"at":"1972-01-01T10:00:20.021Z","until":"1970-01-01T00:00:01Z","history":["1970-01-01T00:00:00Z","1970-01-01T00:00:02Z","1970-01-01T00:00:00.000000005Z"]}"#
+        );
+
+        let legacy: Legacy =
+            serde_json::from_str(r#"{"at":"1970-01-01T00:00:00Z","until":null}"#).unwrap();
+        assert_eq!(legacy.until, None);
+
+        for json in &[
+            r#"{"at":{"seconds":0,"nanos":-1}}"#,
+            r#"{"at":{"seconds":0,"nanos":1000000000}}"#,
+            r#"{"at":{"seconds":-62135596801}}"#,
+            r#"{"at":{"seconds":0,"seconds":1}}"#,
+            r#"{"at":{"seconds":0,"millis":1}}"#,
+            r#"{"at":{"seconds":1.5}}"#,
+            r#"{"at":0}"#,
+            r#"{"at":[0,0]}"#,
+        ] {
+            assert!(serde_json::from_str::<Legacy>(json).is_err(), "{}", json);
+        }
+
+        // The object form is rejected by the strict modules.
+        assert!(serde_json::from_str::<Event>(r#"{"at":{"seconds":0,"nanos":0}}"#).is_err());
+        assert!(serde_json::from_str::<Event>(
+            r#"{"at":"1970-01-01T00:00:00Z","until":{"seconds":0}}"#
+        )
+        .is_err());
+        assert!(serde_json::from_str::<Strict>(r#"{"at":{"seconds":0,"nanos":0}}"#).is_err());
+    }
+
+    #[test]
+    fn epoch() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Seconds {
+            #[serde(with = "super::epoch_seconds")]
+            at: Timestamp,
+            #[serde(with = "super::epoch_seconds::option", default)]
+            until: Option<Timestamp>,
+            #[serde(with = "super::epoch_seconds::repeated", default)]
+            history: Vec<Timestamp>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Millis {
+            #[serde(with = "super::epoch_millis")]
+            at: Timestamp,
+        }
+
+        let timestamp = |seconds, nanos| Timestamp { seconds, nanos };
+
+        let json = r#"{
+            "at": 63108020.021,
+            "until": 1,
+            "history": [-1.5, "1970-01-01T00:00:02Z", 0.000000001]
+        }"#;
+        let seconds: Seconds = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            seconds,
+            Seconds {
+                at: timestamp(63_108_020, 21_000_000),
+                until: Some(timestamp(1, 0)),
+                history: vec![timestamp(-2, 500_000_000), timestamp(2, 0), timestamp(0, 1)],
+            }
+        );
+        // Timestamps are still serialized as RFC 3339 strings.
+        assert_eq!(
+            serde_json::to_string(&seconds).unwrap(),
+            r#"{"at":"1972-01-01T10:00:20.021Z","until":"1970-01-01T00:00:01Z","history":["1969-12-31T23:59:58.500Z","1970-01-01T00:00:02Z","1970-01-01T00:00:00.000000001Z"]}"#
+        );
+        let seconds: Seconds = serde_json::from_str(r#"{"at":0.9999999999,"until":null}"#).unwrap();
+        assert_eq!(seconds.at, timestamp(1, 0));
+        assert_eq!(seconds.until, None);
+
+        for (json, expected) in &[
+            (r#"{"at":63108020021}"#, timestamp(63_108_020, 21_000_000)),
+            (r#"{"at":-1}"#, timestamp(-1, 999_000_000)),
+            (r#"{"at":1.5}"#, timestamp(0, 1_500_000)),
+            (r#"{"at":"1970-01-01T00:00:00Z"}"#, timestamp(0, 0)),
+        ] {
+            assert_eq!(
+                serde_json::from_str::<Millis>(json).unwrap().at,
+                *expected,
+                "{}",
+                json
+            );
+        }
+
+        for json in &[
+            r#"{"at":253402300800}"#,
+            r#"{"at":-62135596801}"#,
+            r#"{"at":253402300799.9999999999}"#,
+            r#"{"at":18446744073709551615}"#,
+            r#"{"at":1e300}"#,
+            r#"{"at":{"seconds":0}}"#,
+            r#"{"at":"0"}"#,
+        ] {
+            assert!(serde_json::from_str::<Seconds>(json).is_err(), "{}", json);
+        }
+        assert!(serde_json::from_str::<Millis>(r#"{"at":253402300800000}"#).is_err());
+
+        let error = serde_json::from_str::<Seconds>(r#"{"at":true}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid type: boolean `true`, expected an RFC 3339 timestamp or a number of seconds \
+             since the Unix epoch at line 1 column 10"
+        );
+        let error = serde_json::from_str::<Seconds>(r#"{"at":1e12}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value: floating point `1000000000000.0`, expected a timestamp within range at \
+             line 1 column 10"
+        );
+    }
+
+    #[test]
+    fn leap_seconds() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Clamped {
+            #[serde(with = "super::clamp_leap_seconds")]
+            at: Timestamp,
+            #[serde(with = "super::clamp_leap_seconds::option", default)]
+            until: Option<Timestamp>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Smeared {
+            #[serde(with = "super::smear_leap_seconds::repeated")]
+            history: Vec<Timestamp>,
+        }
+
+        let json = r#"{"at":"2016-12-31T23:59:60.5Z","until":"2016-12-31T23:59:59Z"}"#;
+        let clamped: Clamped = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            clamped,
+            Clamped {
+                at: Timestamp {
+                    seconds: 1_483_228_799,
+                    nanos: 999_999_999,
+                },
+                until: Some(Timestamp {
+                    seconds: 1_483_228_799,
+                    nanos: 0,
+                }),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&clamped).unwrap(),
+            r#"{"at":"2016-12-31T23:59:59.999999999Z","until":"2016-12-31T23:59:59Z"}"#
+        );
+
+        let smeared: Smeared =
+            serde_json::from_str(r#"{"history":["2016-12-31T23:59:60.5Z"]}"#).unwrap();
+        assert_eq!(
+            smeared.history,
+            vec![Timestamp {
+                seconds: 1_483_228_800,
+                nanos: 0,
+            }]
+        );
+
+        let error = serde_json::from_str::<Event>(r#"{"at":"2016-12-31T23:59:60Z"}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid value: string \"2016-12-31T23:59:60Z\", expected an RFC 3339 timestamp at \
+             line 1 column 28"
+        );
+        for json in &[
+            r#"{"at":"2016-12-31T12:59:60Z"}"#,
+            r#"{"at":{"seconds":0}}"#,
+            r#"{"at":0}"#,
+        ] {
+            assert!(serde_json::from_str::<Clamped>(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Serializers and deserializers for string-keyed `map` fields whose keys are timestamps.
+//!
+//! Protobuf does not allow message map keys, so time-bucketed aggregates are commonly stored in a
+//! `map<string, V>` keyed by the start of each bucket as an RFC 3339 date-time. The same timestamp
+//! can be spelled in many ways, with different UTC offsets or numbers of fractional digits, which
+//! breaks lookups, round-trips and hashes computed over the serialized form.
+//!
+//! The [`serialize`] and [`deserialize`] functions can be used with `#[serde(with = "...")]` on
+//! such maps, or wired by `prost-build` with `Config::timestamp_map_keys`, to normalize each key to
+//! the canonical form produced by the `Display` implementation of [`Timestamp`](crate::Timestamp),
+//! e.g. `"2021-03-01T10:00:00Z"`. Keys which are not valid timestamps are rejected, as are maps
+//! with two keys holding the same timestamp.
+//!
+//! Maps keyed by [`Timestamp`](crate::Timestamp) itself can use [`map`](super::map), which renders
+//! the keys in the same canonical form.
+
+use prost::alloc::string::{String, ToString};
+use serde_crate::de::{Deserialize, Deserializer};
+use serde_crate::ser::{Serialize, Serializer};
+
+use super::map;
+use crate::datetime::parse_timestamp;
+
+/// Returns the canonical form of a timestamp map key, or `None` if the key is not a valid
+/// timestamp.
+pub fn canonicalize(key: &str) -> Option<String> {
+    parse_timestamp(key).map(|timestamp| timestamp.to_string())
+}
+
+/// Serializes a string-keyed map, canonicalizing the keys.
+///
+/// Fails if a key is not a valid timestamp, or if two keys have the same canonical form.
+pub fn serialize<'a, M, V, S>(map: &'a M, serializer: S) -> Result<S::Ok, S::Error>
+where
+    &'a M: IntoIterator<Item = (&'a String, &'a V)>,
+    V: Serialize + 'a,
+    S: Serializer,
+{
+    map::serialize_canonical(map, serializer, canonicalize, "timestamp")
+}
+
+/// Deserializes a string-keyed map, canonicalizing the keys.
+///
+/// Fails if a key is not a valid timestamp, or if two keys have the same canonical form.
+pub fn deserialize<'de, M, V, D>(deserializer: D) -> Result<M, D::Error>
+where
+    M: Default + Extend<(String, V)> + IntoIterator<Item = (String, V)>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    map::deserialize_canonical(deserializer, canonicalize, "timestamp")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn canonical_keys() {
+        assert_eq!(
+            canonicalize("2021-03-01T12:30:00.000+02:30").as_deref(),
+            Some("2021-03-01T10:00:00Z")
+        );
+        assert_eq!(
+            canonicalize("2021-03-01t10:00:00.120000z").as_deref(),
+            Some("2021-03-01T10:00:00.120Z")
+        );
+        assert_eq!(canonicalize("2021-03-01"), None);
+        assert_eq!(canonicalize("bucket"), None);
+    }
+
+    #[test]
+    fn serde_map() {
+        let mut de = serde_json::Deserializer::from_str(
+            r#"{"2021-03-01T11:00:00+01:00": 1, "2021-03-01T11:00:00Z": 2}"#,
+        );
+        let map: BTreeMap<String, i32> = deserialize(&mut de).unwrap();
+        assert_eq!(
+            map.keys().map(String::as_str).collect::<Vec<_>>(),
+            vec!["2021-03-01T10:00:00Z", "2021-03-01T11:00:00Z"]
+        );
+
+        let mut de = serde_json::Deserializer::from_str(
+            r#"{"2021-03-01T11:00:00+01:00": 1, "2021-03-01T10:00:00.000Z": 2}"#,
+        );
+        assert!(deserialize::<BTreeMap<String, i32>, _, _>(&mut de).is_err());
+
+        let mut de = serde_json::Deserializer::from_str(r#"{"yesterday": 1}"#);
+        let error = deserialize::<BTreeMap<String, i32>, _, _>(&mut de).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("invalid timestamp map key: yesterday"),
+            "{}",
+            error
+        );
+
+        let mut map = BTreeMap::new();
+        map.insert("2021-03-01T10:00:00.500-00:00".to_string(), true);
+        let mut buf = Vec::new();
+        serialize(&map, &mut serde_json::Serializer::new(&mut buf)).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf).unwrap(),
+            r#"{"2021-03-01T10:00:00.500Z":true}"#
+        );
+
+        map.insert("later".to_string(), false);
+        let mut buf = Vec::new();
+        assert!(serialize(&map, &mut serde_json::Serializer::new(&mut buf)).is_err());
+    }
+}
@@ -0,0 +1,632 @@
+//! `Serialize` and `Deserialize` implementations for `google.protobuf.Value`, `Struct` and
+//! `ListValue`.
+//!
+//! These types represent arbitrary JSON values, so unlike the other well-known types they are
+//! serialized through their own implementations rather than with `#[serde(with = "...")]`, and can
+//! be nested freely in options, lists and maps. A `Value` without a kind, or holding a non-finite
+//! number, has no JSON representation and fails to serialize.
+//!
+//! # Precision
+//!
+//! A `Value` holds numbers as `f64`, so deserialization converts each JSON number to the nearest
+//! `f64`. Integers of up to 53 bits, such as those produced by JavaScript, and the numbers written
+//! by serializing an `f64` are exact. Larger integers, such as 64-bit IDs, are rounded, which is
+//! reported as an [`Event::LossyNumber`].
+//!
+//! The [`lossless`] module instead keeps integers which an `f64` can't hold exactly as a
+//! `string_value` of their decimal digits, as the JSON mapping renders 64-bit integers. Integers
+//! beyond the 64-bit range are only kept if the format provides them as 128-bit integers, as
+//! CBOR does, or as text, as `serde_json` does with its `arbitrary_precision` feature. The
+//! `arbitrary_precision` feature of this crate enables it; without it, `serde_json` provides
+//! them as the nearest `f64` instead.
+
+use core::convert::TryFrom;
+use core::fmt;
+
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec::Vec;
+use serde_crate::de::{
+    self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde_crate::ser::{self, Serialize, Serializer};
+
+use crate::serde::events::{self, Event};
+use crate::serde::number;
+use crate::value::Kind;
+use crate::{ListValue, NullValue, Struct, Value};
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match &self.kind {
+            Some(Kind::NullValue(_)) => serializer.serialize_unit(),
+            Some(Kind::NumberValue(value)) if value.is_finite() => serializer.serialize_f64(*value),
+            Some(Kind::NumberValue(value)) => Err(ser::Error::custom(format_args!(
+                "non-finite number in google.protobuf.Value: {}",
+                value
+            ))),
+            Some(Kind::StringValue(value)) => serializer.serialize_str(value),
+            Some(Kind::BoolValue(value)) => serializer.serialize_bool(*value),
+            Some(Kind::StructValue(value)) => value.serialize(serializer),
+            Some(Kind::ListValue(value)) => value.serialize(serializer),
+            None => Err(ser::Error::custom("google.protobuf.Value has no kind")),
+        }
+    }
+}
+
+impl Serialize for Struct {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_map(&self.fields)
+    }
+}
+
+impl Serialize for ListValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(&self.values)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor { lossless: false })
+    }
+}
+
+impl<'de> Deserialize<'de> for Struct {
+    fn deserialize<D>(deserializer: D) -> Result<Struct, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_struct(deserializer, ValueVisitor { lossless: false })
+    }
+}
+
+impl<'de> Deserialize<'de> for ListValue {
+    fn deserialize<D>(deserializer: D) -> Result<ListValue, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize_list(deserializer, ValueVisitor { lossless: false })
+    }
+}
+
+/// Serializers and deserializers which keep integers that an `f64` can't hold exactly.
+///
+/// Such integers are deserialized as a `string_value` of their decimal digits, e.g. the JSON
+/// number `9007199254740993` as the string `"9007199254740993"`, in the value itself and in the
+/// values nested in it. Other numbers are deserialized as in the parent module, and serialization
+/// is the same.
+///
+/// The functions apply to `Value`, `Struct` and `ListValue` fields, and to `optional` and
+/// `repeated` fields holding them:
+///
+/// ```rust,ignore
+/// #[serde(with = "::prost_types::serde::value::lossless")]
+/// pub attributes: ::core::option::Option<::prost_types::Struct>,
+/// ```
+pub mod lossless {
+    use super::*;
+
+    /// A `Value`, `Struct` or `ListValue`, or an `Option` or `Vec` of one.
+    pub trait Lossless: sealed::Lossless {}
+
+    impl<T> Lossless for T where T: sealed::Lossless {}
+
+    /// Serializes the value, as its `Serialize` implementation does.
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Lossless + Serialize,
+        S: Serializer,
+    {
+        value.serialize(serializer)
+    }
+
+    /// Deserializes the value, keeping integers which an `f64` can't hold exactly as strings.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Lossless,
+        D: Deserializer<'de>,
+    {
+        T::deserialize_lossless(deserializer)
+    }
+
+    mod sealed {
+        use super::*;
+
+        pub trait Lossless: Sized {
+            fn deserialize_lossless<'de, D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>;
+        }
+
+        impl Lossless for Value {
+            fn deserialize_lossless<'de, D>(deserializer: D) -> Result<Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_any(ValueVisitor { lossless: true })
+            }
+        }
+
+        impl Lossless for Struct {
+            fn deserialize_lossless<'de, D>(deserializer: D) -> Result<Struct, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserialize_struct(deserializer, ValueVisitor { lossless: true })
+            }
+        }
+
+        impl Lossless for ListValue {
+            fn deserialize_lossless<'de, D>(deserializer: D) -> Result<ListValue, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserialize_list(deserializer, ValueVisitor { lossless: true })
+            }
+        }
+
+        impl<T> Lossless for Option<T>
+        where
+            T: Lossless,
+        {
+            fn deserialize_lossless<'de, D>(deserializer: D) -> Result<Option<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let value = Option::<Element<T>>::deserialize(deserializer)?;
+                Ok(value.map(|Element(value)| value))
+            }
+        }
+
+        impl<T> Lossless for Vec<T>
+        where
+            T: Lossless,
+        {
+            fn deserialize_lossless<'de, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                let values = Vec::<Element<T>>::deserialize(deserializer)?;
+                Ok(values.into_iter().map(|Element(value)| value).collect())
+            }
+        }
+
+        /// Deserializes the elements of options and lists.
+        struct Element<T>(T);
+
+        impl<'de, T> Deserialize<'de> for Element<T>
+        where
+            T: Lossless,
+        {
+            fn deserialize<D>(deserializer: D) -> Result<Element<T>, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                T::deserialize_lossless(deserializer).map(Element)
+            }
+        }
+    }
+}
+
+fn deserialize_struct<'de, D>(deserializer: D, visitor: ValueVisitor) -> Result<Struct, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer
+        .deserialize_map(visitor)
+        .and_then(|value| match value.kind {
+            Some(Kind::StructValue(value)) => Ok(value),
+            _ => Err(de::Error::custom("expected a JSON object")),
+        })
+}
+
+fn deserialize_list<'de, D>(deserializer: D, visitor: ValueVisitor) -> Result<ListValue, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer
+        .deserialize_seq(visitor)
+        .and_then(|value| match value.kind {
+            Some(Kind::ListValue(value)) => Ok(value),
+            _ => Err(de::Error::custom("expected a JSON array")),
+        })
+}
+
+/// Deserializes a `Value`, and the values nested in it.
+#[derive(Clone, Copy)]
+struct ValueVisitor {
+    /// Whether integers which an `f64` can't hold exactly are kept as strings.
+    lossless: bool,
+}
+
+impl ValueVisitor {
+    /// Converts an integer to a number value, or to a string value if it would be rounded and
+    /// integers are kept, reporting it if it was rounded.
+    fn integer(self, converted: f64, value: i128) -> Value {
+        if converted as i128 == value {
+            Value::from(converted)
+        } else if self.lossless {
+            Value::from(value.to_string())
+        } else {
+            if events::enabled() {
+                events::report(&Event::LossyNumber {
+                    ty: "f64",
+                    value: &value.to_string(),
+                });
+            }
+            Value::from(converted)
+        }
+    }
+
+    /// Converts the text of a number provided by `serde_json` with its `arbitrary_precision`
+    /// feature, which is an integer beyond the range of 64-bit integers or has a fraction or an
+    /// exponent.
+    fn number<E>(self, text: &str) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        if text.contains(&['.', 'e', 'E'][..]) {
+            return number::visit(text, self);
+        }
+        // Formatting with a precision writes the exact digits of the float, so the integer is exact
+        // if it is written back as it was.
+        match number::parse_f64(text) {
+            Some(converted) if format!("{:.0}", converted) == text => Ok(Value::from(converted)),
+            _ if self.lossless => Ok(Value::from(text)),
+            Some(converted) => {
+                events::report(&Event::LossyNumber {
+                    ty: "f64",
+                    value: text,
+                });
+                Ok(Value::from(converted))
+            }
+            None => Err(E::custom(format_args!("number out of range: {}", text))),
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(NullValue::NullValue))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        self.visit_unit()
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(self.integer(value as f64, value.into()))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(self.integer(value as f64, value.into()))
+    }
+
+    fn visit_i128<E>(self, value: i128) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        // CBOR bignums beyond the range of 64-bit integers.
+        Ok(self.integer(value as f64, value))
+    }
+
+    fn visit_u128<E>(self, value: u128) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        match i128::try_from(value) {
+            Ok(value) => Ok(self.integer(value as f64, value)),
+            Err(_) if self.lossless => Ok(Value::from(value.to_string())),
+            Err(_) => {
+                // Exact if the significant bits fit in the mantissa of an `f64`.
+                let bits = 128 - value.leading_zeros() - value.trailing_zeros();
+                if events::enabled() && bits > f64::MANTISSA_DIGITS {
+                    events::report(&Event::LossyNumber {
+                        ty: "f64",
+                        value: &value.to_string(),
+                    });
+                }
+                Ok(Value::from(value as f64))
+            }
+        }
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(value))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(Value::from(value))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(value) = seq.next_element_seed(self)? {
+            values.push(value);
+        }
+        Ok(Value::from(ListValue { values }))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut fields = Struct::default();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == number::TOKEN && fields.fields.is_empty() {
+                let text = map.next_value::<String>()?;
+                return self.number(&text);
+            }
+            let value = map.next_value_seed(self)?;
+            fields.fields.insert(key, value);
+        }
+        Ok(Value::from(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use prost::alloc::vec;
+
+    use crate::prost_struct;
+
+    #[test]
+    fn round_trip() {
+        let value = Value::from(prost_struct! {
+            "null": Value::null(),
+            "number": -1.5,
+            "string": "text",
+            "bool": true,
+            "list": vec![Value::from(1), Value::from("two"), Value::null()],
+            "struct": prost_struct! { "nested": false },
+        });
+        let json = serde_json::to_string(&value).unwrap();
+        if cfg!(feature = "preserve_order") {
+            assert_eq!(
+                json,
+                r#"{"null":null,"number":-1.5,"string":"text","bool":true,"list":[1.0,"two",null],"struct":{"nested":false}}"#
+            );
+        } else {
+            assert_eq!(
+                json,
+                r#"{"bool":true,"list":[1.0,"two",null],"null":null,"number":-1.5,"string":"text","struct":{"nested":false}}"#
+            );
+        }
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+        let list: ListValue = serde_json::from_str("[1, {}]").unwrap();
+        assert_eq!(
+            list.values,
+            [Value::from(1), Value::from(Struct::default())]
+        );
+        assert!(serde_json::from_str::<ListValue>("{}").is_err());
+        assert!(serde_json::from_str::<Struct>("[]").is_err());
+        assert!(serde_json::from_str::<Struct>("null").is_err());
+    }
+
+    #[test]
+    fn unrepresentable() {
+        assert!(serde_json::to_string(&Value::default()).is_err());
+        assert!(serde_json::to_string(&Value::from(f64::NAN)).is_err());
+        assert!(serde_json::to_string(&Value::from(f64::INFINITY)).is_err());
+    }
+
+    #[test]
+    fn lossy_integers() {
+        let events = crate::serde::events::tests::record(|| {
+            let value: Value =
+                serde_json::from_str("[9007199254740993, 9007199254740992]").unwrap();
+            assert_eq!(value[0].as_f64(), Some(9007199254740992.0));
+        });
+        assert_eq!(
+            events,
+            [r#"LossyNumber { ty: "f64", value: "9007199254740993" }"#]
+        );
+    }
+
+    #[test]
+    fn cbor_bignums() {
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&vec![1u128 << 70, u128::MAX], &mut cbor).unwrap();
+        let events = crate::serde::events::tests::record(|| {
+            let value: Value = ciborium::de::from_reader(&cbor[..]).unwrap();
+            assert_eq!(value[0].as_f64(), Some(2f64.powi(70)));
+        });
+        assert_eq!(
+            events,
+            [r#"LossyNumber { ty: "f64", value: "340282366920938463463374607431768211455" }"#]
+        );
+    }
+
+    #[test]
+    fn lossless_integers() {
+        use prost::alloc::collections::BTreeMap;
+        use serde_crate::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        #[serde(crate = "serde_crate")]
+        struct Event {
+            #[serde(with = "super::lossless")]
+            attributes: Struct,
+            #[serde(with = "super::lossless", default)]
+            payload: Option<Value>,
+            #[serde(with = "super::lossless", default)]
+            tags: Vec<ListValue>,
+        }
+
+        let events = crate::serde::events::tests::record(|| {
+            let event: Event = serde_json::from_str(
+                r#"{
+                    "attributes": {"id": 9007199254740993, "nested": {"max": 18446744073709551615}},
+                    "payload": [-9223372036854775808, 9007199254740992, 0.5],
+                    "tags": [[9007199254740993]]
+                }"#,
+            )
+            .unwrap();
+            assert_eq!(
+                event,
+                Event {
+                    attributes: prost_struct! {
+                        "id": "9007199254740993",
+                        "nested": prost_struct! { "max": "18446744073709551615" },
+                    },
+                    payload: Some(Value::from(vec![
+                        Value::from(-9223372036854775808.0),
+                        Value::from(9007199254740992.0),
+                        Value::from(0.5),
+                    ])),
+                    tags: vec![ListValue {
+                        values: vec![Value::from("9007199254740993")],
+                    }],
+                }
+            );
+            let event: Event =
+                serde_json::from_str(r#"{"attributes": {}, "payload": null}"#).unwrap();
+            assert_eq!(event.payload, None);
+        });
+        assert!(events.is_empty());
+
+        #[derive(Serialize)]
+        #[serde(crate = "serde_crate")]
+        struct Bignums {
+            attributes: BTreeMap<&'static str, u128>,
+        }
+
+        let bignums = Bignums {
+            attributes: [("large", 1u128 << 70), ("max", u128::MAX)]
+                .iter()
+                .copied()
+                .collect(),
+        };
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&bignums, &mut cbor).unwrap();
+        let event: Event = ciborium::de::from_reader(&cbor[..]).unwrap();
+        assert_eq!(
+            event.attributes,
+            prost_struct! {
+                "large": 2f64.powi(70),
+                "max": "340282366920938463463374607431768211455",
+            }
+        );
+    }
+
+    #[test]
+    fn arbitrary_precision_numbers() {
+        use crate::serde::number::tests::number;
+
+        let events = crate::serde::events::tests::record(|| {
+            assert_eq!(Value::deserialize(number("0.1")), Ok(Value::from(0.1)));
+            assert_eq!(Value::deserialize(number("-0")), Ok(Value::from(-0.0)));
+            assert_eq!(
+                Value::deserialize(number("18446744073709551616")),
+                Ok(Value::from(2f64.powi(64)))
+            );
+            assert_eq!(
+                Value::deserialize(number("-123456789012345678901234567890")),
+                Ok(Value::from(-123456789012345678901234567890.0))
+            );
+            assert!(Value::deserialize(number("1e400")).is_err());
+        });
+        assert_eq!(
+            events,
+            [r#"LossyNumber { ty: "f64", value: "-123456789012345678901234567890" }"#]
+        );
+
+        let lossless = |text| super::lossless::deserialize::<Value, _>(number(text));
+        assert_eq!(
+            lossless("18446744073709551616"),
+            Ok(Value::from(2f64.powi(64)))
+        );
+        assert_eq!(
+            lossless("-123456789012345678901234567890"),
+            Ok(Value::from("-123456789012345678901234567890"))
+        );
+        assert_eq!(lossless("1e2"), Ok(Value::from(100.0)));
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn serde_json_arbitrary_precision() {
+        let json = r#"{"id":"340282366920938463463374607431768211457","ratio":0.1}"#;
+        let value: Struct = super::lossless::deserialize(&mut serde_json::Deserializer::from_str(
+            r#"{"id": 340282366920938463463374607431768211457, "ratio": 0.1}"#,
+        ))
+        .unwrap();
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Struct>(json).unwrap(), value);
+    }
+}
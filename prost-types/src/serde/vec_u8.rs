@@ -0,0 +1,338 @@
+//! Serializers and deserializers for Protobuf `bytes` fields.
+//!
+//! The Protobuf JSON mapping renders `bytes` as a base64 string using the standard alphabet with
+//! padding. Both the standard and the URL-safe alphabets are accepted when deserializing, with or
+//! without padding.
+//!
+//! Binary serde formats hold the same base64 strings, but byte strings of the format, such as
+//! MessagePack's `bin` values, are also accepted when deserializing, as the bytes themselves. In
+//! [`Mode::Binary`], the bytes are serialized as byte strings.
+//!
+//! Values of up to 96 bytes are encoded on the stack and written with
+//! [`Serializer::serialize_str`], and longer values are encoded in fixed-size chunks directly into
+//! the serializer through [`Serializer::collect_str`], so serializing a field never allocates an
+//! intermediate `String` holding its encoding. The [`option`] and [`repeated`] modules apply the same mapping to
+//! `optional` and `repeated` `bytes` fields, and [`Base64`] can be used to serialize bytes from
+//! hand-written `Serialize` implementations. These functions work with both `Vec<u8>` and
+//! `bytes::Bytes` fields.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use prost::alloc::vec::Vec;
+use serde_crate::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde_crate::ser::{Serialize, Serializer};
+
+use crate::base64;
+use crate::serde::mode::{self, Mode};
+
+/// Serializes and displays a byte slice as a base64 string, without allocating.
+#[derive(Clone, Copy, Debug)]
+pub struct Base64<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Display for Base64<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        base64::Display(self.0).fmt(f)
+    }
+}
+
+impl<'a> Serialize for Base64<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // `collect_str` goes through `fmt::Display`, whose output serializers write, and escape,
+        // one piece at a time, so short values are encoded on the stack and written at once.
+        let mut buf = [0u8; base64::ENCODED_SMALL_LEN];
+        match base64::encode_small(self.0, &mut buf) {
+            Some(encoded) => serializer.serialize_str(encoded),
+            None => serializer.collect_str(self),
+        }
+    }
+}
+
+/// Serializes bytes as a base64 string, or as a byte string in [`Mode::Binary`].
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]> + ?Sized,
+    S: Serializer,
+{
+    if mode::current() == Mode::Binary {
+        serializer.serialize_bytes(bytes.as_ref())
+    } else {
+        Base64(bytes.as_ref()).serialize(serializer)
+    }
+}
+
+/// Deserializes bytes from a base64 string.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: From<Vec<u8>>,
+    D: Deserializer<'de>,
+{
+    BytesVisitor(PhantomData).deserialize(deserializer)
+}
+
+/// Serializers and deserializers for `optional` `bytes` fields, mapping `None` to `null`.
+pub mod option {
+    use super::*;
+
+    /// Serializes optional bytes as a base64 string or `null`.
+    pub fn serialize<T, S>(bytes: &Option<T>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        match bytes {
+            Some(bytes) => serializer.serialize_some(&Wrapper(bytes.as_ref())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Deserializes optional bytes from a base64 string or `null`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_option(OptionVisitor(PhantomData))
+    }
+
+    struct OptionVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for OptionVisitor<T>
+    where
+        T: From<Vec<u8>>,
+    {
+        type Value = Option<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("a base64 string or null")
+        }
+
+        fn visit_none<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Option<T>, E>
+        where
+            E: de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Option<T>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            super::deserialize(deserializer).map(Some)
+        }
+    }
+}
+
+/// Serializers and deserializers for `repeated` `bytes` fields.
+pub mod repeated {
+    use super::*;
+
+    /// Serializes a list of bytes as an array of base64 strings.
+    pub fn serialize<T, S>(values: &[T], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]>,
+        S: Serializer,
+    {
+        serializer.collect_seq(values.iter().map(|value| Wrapper(value.as_ref())))
+    }
+
+    /// Deserializes a list of bytes from an array of base64 strings.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Vec<T>, D::Error>
+    where
+        T: From<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor(PhantomData))
+    }
+
+    struct SeqVisitor<T>(PhantomData<fn() -> T>);
+
+    impl<'de, T> Visitor<'de> for SeqVisitor<T>
+    where
+        T: From<Vec<u8>>,
+    {
+        type Value = Vec<T>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("an array of base64 strings")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<T>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let mut values = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(value) = seq.next_element_seed(BytesVisitor(PhantomData))? {
+                values.push(value);
+            }
+            Ok(values)
+        }
+    }
+}
+
+struct Wrapper<'a>(&'a [u8]);
+
+impl<'a> Serialize for Wrapper<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize(self.0, serializer)
+    }
+}
+
+struct BytesVisitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> DeserializeSeed<'de> for BytesVisitor<T>
+where
+    T: From<Vec<u8>>,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        if mode::is_binary() {
+            return deserializer.deserialize_byte_buf(self);
+        }
+        // CBOR deserializers only lend strings which fit their scratch buffer to `visit_str`;
+        // `deserialize_string` accepts strings of any length.
+        deserializer.deserialize_string(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for BytesVisitor<T>
+where
+    T: From<Vec<u8>>,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a base64 string")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        base64::decode(value.as_bytes())
+            .map(T::from)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        // Binary formats such as MessagePack can hold the bytes themselves.
+        Ok(T::from(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<T, E>
+    where
+        E: de::Error,
+    {
+        Ok(T::from(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_crate::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Blob {
+        #[serde(with = "super")]
+        data: Vec<u8>,
+        #[serde(with = "super")]
+        shared: bytes::Bytes,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(crate = "serde_crate")]
+    struct Blobs {
+        #[serde(with = "super::option", default)]
+        maybe: Option<Vec<u8>>,
+        #[serde(with = "super::repeated")]
+        many: Vec<bytes::Bytes>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let blob = Blob {
+            data: b"hello, world".to_vec(),
+            shared: bytes::Bytes::from_static(b"\xFB\xFF"),
+        };
+        let json = serde_json::to_string(&blob).unwrap();
+        assert_eq!(json, r#"{"data":"aGVsbG8sIHdvcmxk","shared":"+/8="}"#);
+        assert_eq!(serde_json::from_str::<Blob>(&json).unwrap(), blob);
+    }
+
+    #[test]
+    fn url_safe_unpadded() {
+        let blob: Blob = serde_json::from_str(r#"{"data":"aGk","shared":"-_8"}"#).unwrap();
+        assert_eq!(blob.data, b"hi");
+        assert_eq!(&blob.shared[..], b"\xFB\xFF");
+
+        let error = serde_json::from_str::<Blob>(r#"{"data":"a","shared":""}"#).unwrap_err();
+        assert!(error.to_string().contains("expected a base64 string"));
+    }
+
+    #[test]
+    fn optional_and_repeated() {
+        let blobs = Blobs {
+            maybe: Some(b"hi".to_vec()),
+            many: vec![bytes::Bytes::from_static(b"a"), bytes::Bytes::new()],
+        };
+        let json = serde_json::to_string(&blobs).unwrap();
+        assert_eq!(json, r#"{"maybe":"aGk=","many":["YQ==",""]}"#);
+        assert_eq!(serde_json::from_str::<Blobs>(&json).unwrap(), blobs);
+
+        let blobs: Blobs = serde_json::from_str(r#"{"maybe":null,"many":[]}"#).unwrap();
+        assert_eq!(blobs.maybe, None);
+        let blobs: Blobs = serde_json::from_str(r#"{"many":["YQ"]}"#).unwrap();
+        assert_eq!(blobs.many, vec![bytes::Bytes::from_static(b"a")]);
+
+        assert!(serde_json::from_str::<Blobs>(r#"{"many":["!"]}"#).is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Base64(b"foobar").to_string(), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn binary_formats() {
+        // Longer than the scratch buffer of `ciborium`.
+        let blob = Blob {
+            data: vec![7; 5000],
+            shared: bytes::Bytes::new(),
+        };
+        let mut cbor = Vec::new();
+        ciborium::ser::into_writer(&blob, &mut cbor).unwrap();
+        assert_eq!(
+            ciborium::de::from_reader::<Blob, _>(&cbor[..]).unwrap(),
+            blob
+        );
+
+        // MessagePack `{"data": bin(00 FF), "shared": "YQ=="}`.
+        let native = b"\x82\xA4data\xC4\x02\x00\xFF\xA6shared\xA4YQ==";
+        let blob: Blob = rmp_serde::from_slice(native).unwrap();
+        assert_eq!(blob.data, b"\x00\xFF");
+        assert_eq!(&blob.shared[..], b"a");
+    }
+}
@@ -0,0 +1,933 @@
+//! Construction of and access to `google.protobuf.Struct` and `google.protobuf.Value`.
+
+#[cfg(feature = "serde_json")]
+use core::convert::{TryFrom, TryInto};
+#[cfg(feature = "serde_json")]
+use core::fmt;
+use core::iter::FromIterator;
+use core::ops::Index;
+use core::slice;
+
+#[cfg(feature = "preserve_order")]
+use indexmap::map;
+#[cfg(not(feature = "preserve_order"))]
+use prost::alloc::collections::btree_map as map;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec::{self, Vec};
+use prost::DecodeError;
+
+use crate::base64;
+use crate::value::Kind;
+use crate::{ListValue, NullValue, Struct, Value};
+
+/// The map type of [`Struct::fields`].
+///
+/// This is a `BTreeMap`, which orders the fields by name. With the `preserve_order` feature, it is
+/// an `IndexMap`, which keeps the fields in the order they were inserted, decoded or deserialized,
+/// and encodes and serializes them in that order.
+#[cfg(not(feature = "preserve_order"))]
+pub type StructMap<K, V> = map::BTreeMap<K, V>;
+
+/// The map type of [`Struct::fields`], an `IndexMap` which keeps the fields in the order they
+/// were inserted, decoded or deserialized, and encodes and serializes them in that order.
+#[cfg(feature = "preserve_order")]
+pub type StructMap<K, V> = map::IndexMap<K, V>;
+
+/// The type URL of the struct holding binary data, see [`Value::from_bytes`].
+const BYTES_VALUE_TYPE_URL: &str = "type.googleapis.com/google.protobuf.BytesValue";
+
+static NULL: Value = Value {
+    kind: Some(Kind::NullValue(NullValue::NullValue as i32)),
+};
+
+impl Value {
+    /// Returns a `null` value.
+    pub fn null() -> Value {
+        NULL.clone()
+    }
+
+    /// Returns `true` if the value is `null`.
+    pub fn is_null(&self) -> bool {
+        matches!(self.kind, Some(Kind::NullValue(_)))
+    }
+
+    /// Returns the number held by the value, if it is a number.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.kind {
+            Some(Kind::NumberValue(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the string held by the value, if it is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match &self.kind {
+            Some(Kind::StringValue(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the boolean held by the value, if it is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.kind {
+            Some(Kind::BoolValue(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the struct held by the value, if it is a struct.
+    pub fn as_struct(&self) -> Option<&Struct> {
+        match &self.kind {
+            Some(Kind::StructValue(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the list held by the value, if it is a list.
+    pub fn as_list(&self) -> Option<&ListValue> {
+        match &self.kind {
+            Some(Kind::ListValue(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value at a path of struct fields and list indices, such as `a.b[2]`, or `None`
+    /// if there is no such value.
+    ///
+    /// Struct fields are separated by `.`, and list indices are enclosed in `[]`. Fields whose
+    /// names contain `.` or `[` can't be reached with a path.
+    pub fn try_get(&self, path: &str) -> Option<&Value> {
+        get_path(Node::Value(self), path)
+    }
+
+    /// Returns a value holding binary data.
+    ///
+    /// A `Value` has no binary kind, so the data is held in a struct following the JSON mapping of
+    /// a `google.protobuf.Any` packing a `google.protobuf.BytesValue`, with the data encoded as
+    /// standard base64:
+    ///
+    /// ```json
+    /// {"@type": "type.googleapis.com/google.protobuf.BytesValue", "value": "aGVsbG8="}
+    /// ```
+    ///
+    /// The data can be extracted with [`Value::to_bytes`], and Protobuf JSON parsers in other
+    /// languages parse the struct as an `Any`.
+    pub fn from_bytes(data: &[u8]) -> Value {
+        let mut fields = StructMap::new();
+        fields.insert("@type".to_string(), BYTES_VALUE_TYPE_URL.into());
+        fields.insert(
+            "value".to_string(),
+            base64::Display(data).to_string().into(),
+        );
+        Value::from(Struct { fields })
+    }
+
+    /// Returns `true` if the value holds binary data, as created by [`Value::from_bytes`].
+    ///
+    /// The base64 encoding of the data is not checked.
+    pub fn is_bytes(&self) -> bool {
+        self.bytes_base64().is_some()
+    }
+
+    /// Returns the binary data held by the value, or `None` if it doesn't hold binary data, as
+    /// created by [`Value::from_bytes`].
+    ///
+    /// Both the standard and the URL-safe base64 alphabets are accepted, with or without padding,
+    /// and the type URL may have any host. Fails if the value holds binary data which is not
+    /// valid base64.
+    pub fn to_bytes(&self) -> Result<Option<Vec<u8>>, DecodeError> {
+        match self.bytes_base64() {
+            Some(encoded) => base64::decode(encoded.as_bytes())
+                .map(Some)
+                .map_err(|_| DecodeError::new("invalid base64 in google.protobuf.BytesValue")),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the base64 string of a struct holding binary data.
+    fn bytes_base64(&self) -> Option<&str> {
+        let fields = &self.as_struct()?.fields;
+        if fields.len() != 2 {
+            return None;
+        }
+        let type_url = fields.get("@type")?.as_str()?;
+        let type_name = &type_url[type_url.rfind('/')? + 1..];
+        if type_name == "google.protobuf.BytesValue" {
+            fields.get("value")?.as_str()
+        } else {
+            None
+        }
+    }
+}
+
+impl Struct {
+    /// Returns the value of a field.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.fields.get(key)
+    }
+
+    /// Sets the value of a field, returning the previous value, if any.
+    pub fn insert<K, V>(&mut self, key: K, value: V) -> Option<Value>
+    where
+        K: Into<String>,
+        V: Into<Value>,
+    {
+        self.fields.insert(key.into(), value.into())
+    }
+
+    /// Sets the value of a field to binary data, returning the previous value, if any.
+    ///
+    /// See [`Value::from_bytes`].
+    pub fn insert_bytes<K>(&mut self, key: K, data: &[u8]) -> Option<Value>
+    where
+        K: Into<String>,
+    {
+        self.fields.insert(key.into(), Value::from_bytes(data))
+    }
+
+    /// Removes a field, returning its value, if any.
+    ///
+    /// The other fields keep their order.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        #[cfg(not(feature = "preserve_order"))]
+        let value = self.fields.remove(key);
+        #[cfg(feature = "preserve_order")]
+        let value = self.fields.shift_remove(key);
+        value
+    }
+
+    /// Returns the binary data held by a field, or `None` if there is no such field or it doesn't
+    /// hold binary data.
+    ///
+    /// See [`Value::to_bytes`].
+    pub fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>, DecodeError> {
+        match self.fields.get(key) {
+            Some(value) => value.to_bytes(),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the value at a path of struct fields and list indices, such as `a.b[2]`, or `None`
+    /// if there is no such value.
+    ///
+    /// See [`Value::try_get`].
+    pub fn try_get(&self, path: &str) -> Option<&Value> {
+        get_path(Node::Struct(self), path)
+    }
+
+    /// Returns the number of fields.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns `true` if the struct has no fields.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns an iterator over the fields, in the order of [`StructMap`].
+    pub fn iter(&self) -> map::Iter<'_, String, Value> {
+        self.fields.iter()
+    }
+
+    /// Returns an iterator over the fields, in the order of [`StructMap`], with mutable values.
+    pub fn iter_mut(&mut self) -> map::IterMut<'_, String, Value> {
+        self.fields.iter_mut()
+    }
+}
+
+impl ListValue {
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns `true` if the list has no values.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the value at an index, or `None` if the index is out of bounds.
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.values.get(index)
+    }
+
+    /// Appends a value to the list.
+    pub fn push<V>(&mut self, value: V)
+    where
+        V: Into<Value>,
+    {
+        self.values.push(value.into());
+    }
+
+    /// Returns an iterator over the values.
+    pub fn iter(&self) -> slice::Iter<'_, Value> {
+        self.values.iter()
+    }
+
+    /// Returns an iterator over the values, which allows modifying them.
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Value> {
+        self.values.iter_mut()
+    }
+}
+
+enum Node<'a> {
+    Struct(&'a Struct),
+    Value(&'a Value),
+}
+
+fn get_path<'a>(mut node: Node<'a>, path: &str) -> Option<&'a Value> {
+    for (i, component) in path.split('.').enumerate() {
+        let (key, mut indices) = match component.find('[') {
+            Some(index) => component.split_at(index),
+            None => (component, ""),
+        };
+        if !key.is_empty() {
+            let fields = match node {
+                Node::Struct(fields) => fields,
+                Node::Value(value) => value.as_struct()?,
+            };
+            node = Node::Value(fields.get(key)?);
+        } else if i > 0 || indices.is_empty() {
+            // Only a path into a list value may start with an index.
+            return None;
+        }
+        while let Some(rest) = indices.strip_prefix('[') {
+            let end = rest.find(']')?;
+            let index = rest[..end].parse::<usize>().ok()?;
+            let list = match node {
+                Node::Struct(_) => return None,
+                Node::Value(value) => value.as_list()?,
+            };
+            node = Node::Value(list.values.get(index)?);
+            indices = &rest[end + 1..];
+        }
+        if !indices.is_empty() {
+            return None;
+        }
+    }
+    match node {
+        Node::Struct(_) => None,
+        Node::Value(value) => Some(value),
+    }
+}
+
+/// Returns the value of a field of a struct value, or `null` if the value is not a struct or has no
+/// such field.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.as_struct().map_or(&NULL, |fields| &fields[key])
+    }
+}
+
+/// Returns an element of a list value, or `null` if the value is not a list or the index is out of
+/// bounds.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        self.as_list()
+            .and_then(|list| list.values.get(index))
+            .unwrap_or(&NULL)
+    }
+}
+
+/// Returns the value of a field, or `null` if there is no such field.
+impl Index<&str> for Struct {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        self.fields.get(key).unwrap_or(&NULL)
+    }
+}
+
+macro_rules! from_kind {
+    ($($ty:ty => |$value:ident| $kind:expr),* $(,)?) => {$(
+        impl From<$ty> for Value {
+            fn from($value: $ty) -> Value {
+                Value { kind: Some($kind) }
+            }
+        }
+    )*};
+}
+
+from_kind!(
+    NullValue => |value| Kind::NullValue(value as i32),
+    f64 => |value| Kind::NumberValue(value),
+    f32 => |value| Kind::NumberValue(value.into()),
+    i32 => |value| Kind::NumberValue(value.into()),
+    u32 => |value| Kind::NumberValue(value.into()),
+    String => |value| Kind::StringValue(value),
+    &str => |value| Kind::StringValue(value.into()),
+    bool => |value| Kind::BoolValue(value),
+    Struct => |value| Kind::StructValue(value),
+    ListValue => |value| Kind::ListValue(value),
+);
+
+/// Converts `None` to `null`.
+impl<T> From<Option<T>> for Value
+where
+    T: Into<Value>,
+{
+    fn from(value: Option<T>) -> Value {
+        value.map_or_else(Value::null, Into::into)
+    }
+}
+
+impl<T> From<Vec<T>> for Value
+where
+    T: Into<Value>,
+{
+    fn from(values: Vec<T>) -> Value {
+        values.into_iter().collect::<ListValue>().into()
+    }
+}
+
+impl<T> FromIterator<T> for ListValue
+where
+    T: Into<Value>,
+{
+    fn from_iter<I>(values: I) -> ListValue
+    where
+        I: IntoIterator<Item = T>,
+    {
+        ListValue {
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl<T> Extend<T> for ListValue
+where
+    T: Into<Value>,
+{
+    fn extend<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.values.extend(values.into_iter().map(Into::into));
+    }
+}
+
+impl IntoIterator for ListValue {
+    type Item = Value;
+    type IntoIter = vec::IntoIter<Value>;
+
+    fn into_iter(self) -> vec::IntoIter<Value> {
+        self.values.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ListValue {
+    type Item = &'a Value;
+    type IntoIter = slice::Iter<'a, Value>;
+
+    fn into_iter(self) -> slice::Iter<'a, Value> {
+        self.values.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut ListValue {
+    type Item = &'a mut Value;
+    type IntoIter = slice::IterMut<'a, Value>;
+
+    fn into_iter(self) -> slice::IterMut<'a, Value> {
+        self.values.iter_mut()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Struct
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn from_iter<I>(fields: I) -> Struct
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        Struct {
+            fields: fields
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+impl<K, V> Extend<(K, V)> for Struct
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn extend<I>(&mut self, fields: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.fields.extend(
+            fields
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into())),
+        );
+    }
+}
+
+impl IntoIterator for Struct {
+    type Item = (String, Value);
+    type IntoIter = map::IntoIter<String, Value>;
+
+    fn into_iter(self) -> map::IntoIter<String, Value> {
+        self.fields.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Struct {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = map::Iter<'a, String, Value>;
+
+    fn into_iter(self) -> map::Iter<'a, String, Value> {
+        self.fields.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Struct {
+    type Item = (&'a String, &'a mut Value);
+    type IntoIter = map::IterMut<'a, String, Value>;
+
+    fn into_iter(self) -> map::IterMut<'a, String, Value> {
+        self.fields.iter_mut()
+    }
+}
+
+/// Builds a [`Struct`] from field names and values which convert into [`Value`].
+///
+/// Nested structs are built with nested invocations, and lists from `Vec`s. `None` converts to
+/// `null`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let value = prost_types::prost_struct! {
+///     "name": "Ferris",
+///     "age": 12,
+///     "address": prost_types::prost_struct! { "city": "Rustville" },
+///     "tags": vec!["crab", "mascot"],
+///     "nickname": None::<String>,
+/// };
+/// assert_eq!(value.try_get("address.city").unwrap().as_str(), Some("Rustville"));
+/// ```
+#[macro_export]
+macro_rules! prost_struct {
+    ($($key:literal : $value:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut fields = $crate::Struct::default();
+        $(
+            fields.insert($key, $value);
+        )*
+        fields
+    }};
+}
+
+/// Converts JSON to a `Value`. JSON numbers are converted to the nearest `f64`.
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Value {
+        match json {
+            serde_json::Value::Null => Value::null(),
+            serde_json::Value::Bool(value) => value.into(),
+            serde_json::Value::Number(value) => value.as_f64().unwrap_or(f64::NAN).into(),
+            serde_json::Value::String(value) => value.into(),
+            serde_json::Value::Array(values) => values.into(),
+            serde_json::Value::Object(fields) => Struct::from(fields).into(),
+        }
+    }
+}
+
+/// Converts a JSON object to a `Struct`. JSON numbers are converted to the nearest `f64`.
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Map<String, serde_json::Value>> for Struct {
+    fn from(json: serde_json::Map<String, serde_json::Value>) -> Struct {
+        json.into_iter().collect()
+    }
+}
+
+/// Converts a JSON array to a `ListValue`. JSON numbers are converted to the nearest `f64`.
+#[cfg(feature = "serde_json")]
+impl From<Vec<serde_json::Value>> for ListValue {
+    fn from(json: Vec<serde_json::Value>) -> ListValue {
+        json.into_iter().collect()
+    }
+}
+
+/// Converts a `Value` to JSON.
+///
+/// Fails if the value or a nested value has no JSON representation, because it is a number which
+/// is not finite or it holds no kind.
+#[cfg(feature = "serde_json")]
+impl TryFrom<Value> for serde_json::Value {
+    type Error = InvalidValueError;
+
+    fn try_from(value: Value) -> Result<serde_json::Value, InvalidValueError> {
+        Ok(match value.kind {
+            Some(Kind::NullValue(_)) => serde_json::Value::Null,
+            Some(Kind::NumberValue(number)) => match serde_json::Number::from_f64(number) {
+                Some(number) => serde_json::Value::Number(number),
+                None => {
+                    return Err(InvalidValueError {
+                        value: number.into(),
+                    })
+                }
+            },
+            Some(Kind::StringValue(value)) => serde_json::Value::String(value),
+            Some(Kind::BoolValue(value)) => serde_json::Value::Bool(value),
+            Some(Kind::StructValue(value)) => serde_json::Value::Object(value.try_into()?),
+            Some(Kind::ListValue(value)) => serde_json::Value::Array(value.try_into()?),
+            None => {
+                return Err(InvalidValueError {
+                    value: Value { kind: None },
+                })
+            }
+        })
+    }
+}
+
+/// Converts a `Struct` to a JSON object.
+///
+/// Fails if a field value has no JSON representation, as when converting a [`Value`].
+#[cfg(feature = "serde_json")]
+impl TryFrom<Struct> for serde_json::Map<String, serde_json::Value> {
+    type Error = InvalidValueError;
+
+    fn try_from(value: Struct) -> Result<Self, InvalidValueError> {
+        value
+            .fields
+            .into_iter()
+            .map(|(key, value)| Ok((key, serde_json::Value::try_from(value)?)))
+            .collect()
+    }
+}
+
+/// Converts a `Struct` to a JSON object value.
+///
+/// Fails if a field value has no JSON representation, as when converting a [`Value`].
+#[cfg(feature = "serde_json")]
+impl TryFrom<Struct> for serde_json::Value {
+    type Error = InvalidValueError;
+
+    fn try_from(value: Struct) -> Result<serde_json::Value, InvalidValueError> {
+        value.try_into().map(serde_json::Value::Object)
+    }
+}
+
+/// Converts a `ListValue` to a JSON array.
+///
+/// Fails if an element has no JSON representation, as when converting a [`Value`].
+#[cfg(feature = "serde_json")]
+impl TryFrom<ListValue> for Vec<serde_json::Value> {
+    type Error = InvalidValueError;
+
+    fn try_from(value: ListValue) -> Result<Self, InvalidValueError> {
+        value
+            .values
+            .into_iter()
+            .map(serde_json::Value::try_from)
+            .collect()
+    }
+}
+
+/// The error returned when converting a [`Value`] which has no JSON representation to JSON.
+#[cfg(feature = "serde_json")]
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct InvalidValueError {
+    /// The value, or nested value, which has no JSON representation.
+    pub value: Value,
+}
+
+#[cfg(feature = "serde_json")]
+impl fmt::Display for InvalidValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "value has no JSON representation: {:?}", self.value)
+    }
+}
+
+#[cfg(all(feature = "std", feature = "serde_json"))]
+impl std::error::Error for InvalidValueError {}
+
+#[cfg(test)]
+mod tests {
+    use prost::alloc::vec;
+
+    use super::*;
+
+    fn sample() -> Struct {
+        prost_struct! {
+            "name": "Ferris",
+            "age": 12,
+            "address": prost_struct! { "city": "Rustville", "zip": None::<String> },
+            "tags": vec!["crab", "mascot"],
+            "matrix": vec![vec![1.0, 2.0], vec![3.0]],
+            "a.b": true,
+        }
+    }
+
+    #[test]
+    fn construct() {
+        let mut fields = sample();
+        assert_eq!(fields.fields.len(), 6);
+        assert_eq!(fields.get("age"), Some(&Value::from(12.0)));
+        assert!(fields["address"]["zip"].is_null());
+        assert_eq!(fields.insert("age", 13u32), Some(Value::from(12)));
+        assert_eq!(fields["age"].as_f64(), Some(13.0));
+        assert_eq!(prost_struct! {}, Struct::default());
+
+        let list: ListValue = vec![Value::from("a"), 1.5.into()].into_iter().collect();
+        assert_eq!(list.values[1].as_f64(), Some(1.5));
+        let fields: Struct = vec![("a", 1)].into_iter().collect();
+        assert_eq!(fields["a"].as_f64(), Some(1.0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn canonical_hash() {
+        use prost::Canonical;
+        use std::collections::HashSet;
+
+        let mut set = HashSet::new();
+        assert!(set.insert(Canonical(sample())));
+        assert!(!set.insert(Canonical(sample())));
+        assert!(set.insert(Canonical(prost_struct! { "nan": f64::NAN })));
+        assert!(!set.insert(Canonical(prost_struct! { "nan": f64::NAN })));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn bytes() {
+        let value = Value::from_bytes(b"hello");
+        assert_eq!(
+            value,
+            Value::from(prost_struct! {
+                "@type": "type.googleapis.com/google.protobuf.BytesValue",
+                "value": "aGVsbG8=",
+            })
+        );
+        assert!(value.is_bytes());
+        assert_eq!(value.to_bytes().unwrap(), Some(b"hello".to_vec()));
+
+        let mut fields = sample();
+        assert_eq!(fields.insert_bytes("avatar", &[0xfb, 0xff]), None);
+        assert_eq!(fields.get_bytes("avatar").unwrap(), Some(vec![0xfb, 0xff]));
+        assert_eq!(fields.get_bytes("name").unwrap(), None);
+        assert_eq!(fields.get_bytes("nickname").unwrap(), None);
+
+        // URL-safe base64 without padding, and other type URL hosts, are accepted.
+        let value = Value::from(prost_struct! {
+            "@type": "example.com/types/google.protobuf.BytesValue",
+            "value": "-_8",
+        });
+        assert_eq!(value.to_bytes().unwrap(), Some(vec![0xfb, 0xff]));
+
+        let invalid = Value::from(prost_struct! {
+            "@type": "type.googleapis.com/google.protobuf.BytesValue",
+            "value": "a!",
+        });
+        assert!(invalid.is_bytes());
+        assert!(invalid.to_bytes().is_err());
+
+        for other in &[
+            Value::from("aGVsbG8="),
+            Value::from(prost_struct! { "value": "aGVsbG8=" }),
+            Value::from(prost_struct! {
+                "@type": "type.googleapis.com/google.protobuf.StringValue",
+                "value": "aGVsbG8=",
+            }),
+            Value::from(prost_struct! {
+                "@type": "google.protobuf.BytesValue",
+                "value": "aGVsbG8=",
+            }),
+            Value::from(prost_struct! {
+                "@type": "type.googleapis.com/google.protobuf.BytesValue",
+                "value": "aGVsbG8=",
+                "extra": true,
+            }),
+        ] {
+            assert!(!other.is_bytes(), "{:?}", other);
+            assert_eq!(other.to_bytes().unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn access() {
+        let fields = sample();
+        assert_eq!(fields.try_get("name").unwrap().as_str(), Some("Ferris"));
+        assert_eq!(
+            fields.try_get("address.city").unwrap().as_str(),
+            Some("Rustville")
+        );
+        assert_eq!(fields.try_get("tags[1]").unwrap().as_str(), Some("mascot"));
+        assert_eq!(fields.try_get("matrix[0][1]").unwrap().as_f64(), Some(2.0));
+        let matrix = fields.try_get("matrix").unwrap();
+        assert_eq!(matrix.try_get("[1][0]").unwrap().as_f64(), Some(3.0));
+        assert_eq!(matrix[1][0].as_f64(), Some(3.0));
+        assert_eq!(
+            Value::from(fields.clone()).try_get("address.zip"),
+            Some(&Value::null())
+        );
+
+        for path in &[
+            "",
+            "nickname",
+            "name.first",
+            "tags[2]",
+            "tags[x]",
+            "tags[0",
+            "tags[0]x",
+            "matrix.[0]",
+            "a.b",
+            "address..city",
+            "[0]",
+        ] {
+            assert_eq!(fields.try_get(path), None, "{}", path);
+        }
+
+        assert!(fields["nickname"].is_null());
+        assert!(fields["tags"][5].is_null());
+        assert!(fields["name"]["first"].is_null());
+        assert_eq!(fields["a.b"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn collections() {
+        let mut list = ListValue::default();
+        assert!(list.is_empty());
+        list.push("a");
+        list.extend(vec![1.0, 2.0]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.get(0), Some(&Value::from("a")));
+        for value in &mut list {
+            if let Some(number) = value.as_f64() {
+                *value = (number * 10.0).into();
+            }
+        }
+        let numbers: Vec<f64> = list.iter().filter_map(Value::as_f64).collect();
+        assert_eq!(numbers, [10.0, 20.0]);
+        let strings: Vec<Value> = list
+            .into_iter()
+            .filter(|value| value.as_str().is_some())
+            .collect();
+        assert_eq!(strings, [Value::from("a")]);
+
+        let mut fields = sample();
+        assert!(!fields.is_empty());
+        fields.extend(vec![("age", 13), ("weight", 5)]);
+        assert_eq!(fields.len(), 7);
+        for (_, value) in &mut fields {
+            if let Some(number) = value.as_f64() {
+                *value = (number + 1.0).into();
+            }
+        }
+        let keys: Vec<&str> = fields.iter().map(|(key, _)| key.as_str()).collect();
+        if cfg!(feature = "preserve_order") {
+            assert_eq!(
+                keys,
+                ["name", "age", "address", "tags", "matrix", "a.b", "weight"]
+            );
+        } else {
+            assert_eq!(
+                keys,
+                ["a.b", "address", "age", "matrix", "name", "tags", "weight"]
+            );
+        }
+        let numbers: Struct = fields
+            .into_iter()
+            .filter(|(_, value)| value.as_f64().is_some())
+            .collect();
+        assert_eq!(numbers, prost_struct! { "age": 14, "weight": 6 });
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json() {
+        let json = serde_json::json!({
+            "name": "Ferris",
+            "age": 12.0,
+            "address": { "city": "Rustville", "zip": null },
+            "tags": ["crab", "mascot"],
+            "matrix": [[1.0, 2.0], [3.0]],
+            "a.b": true,
+        });
+        let value = Value::from(json.clone());
+        assert_eq!(value, Value::from(sample()));
+        assert_eq!(serde_json::Value::try_from(value).unwrap(), json);
+
+        let invalid = Value::from(prost_struct! { "list": vec![f64::INFINITY] });
+        assert_eq!(
+            serde_json::Value::try_from(invalid).unwrap_err().value,
+            Value::from(f64::INFINITY)
+        );
+        assert!(serde_json::Value::try_from(Value::default()).is_err());
+
+        let object = match json.clone() {
+            serde_json::Value::Object(object) => object,
+            _ => unreachable!(),
+        };
+        assert_eq!(Struct::from(object.clone()), sample());
+        assert_eq!(
+            serde_json::Map::<String, serde_json::Value>::try_from(sample()).unwrap(),
+            object
+        );
+        assert_eq!(serde_json::Value::try_from(sample()).unwrap(), json);
+        let invalid = prost_struct! { "low": f64::NEG_INFINITY };
+        assert_eq!(
+            serde_json::Map::<String, serde_json::Value>::try_from(invalid)
+                .unwrap_err()
+                .value,
+            Value::from(f64::NEG_INFINITY)
+        );
+
+        let array = vec![serde_json::json!(1.5), serde_json::json!({ "a": [] })];
+        let list = ListValue::from(array.clone());
+        assert_eq!(
+            list,
+            vec![
+                Value::from(1.5),
+                prost_struct! { "a": Vec::<Value>::new() }.into()
+            ]
+            .into_iter()
+            .collect::<ListValue>()
+        );
+        assert_eq!(Vec::<serde_json::Value>::try_from(list).unwrap(), array);
+        let invalid: ListValue = vec![Value::default()].into_iter().collect();
+        assert!(Vec::<serde_json::Value>::try_from(invalid).is_err());
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn preserve_order() {
+        use prost::Message;
+
+        let keys = |fields: &Struct| -> Vec<String> {
+            fields.iter().map(|(key, _)| key.clone()).collect()
+        };
+        let mut fields = sample();
+        assert_eq!(
+            keys(&fields),
+            ["name", "age", "address", "tags", "matrix", "a.b"]
+        );
+
+        let decoded = Struct::decode(&*fields.encode_to_vec()).unwrap();
+        assert_eq!(keys(&decoded), keys(&fields));
+
+        assert_eq!(fields.remove("age"), Some(Value::from(12)));
+        assert_eq!(keys(&fields), ["name", "address", "tags", "matrix", "a.b"]);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = r#"{"zulu":1.0,"alpha":{"mike":true,"bravo":null}}"#;
+            let fields: Struct = serde_json::from_str(json).unwrap();
+            assert_eq!(keys(&fields), ["zulu", "alpha"]);
+            assert_eq!(serde_json::to_string(&fields).unwrap(), json);
+        }
+    }
+}
@@ -0,0 +1,579 @@
+//! Tables of flat messages, as CSV or TSV files.
+//!
+//! A [`Table`] maps messages whose fields are all singular scalars or enums to the rows of a
+//! delimited text file, with a header naming the columns after the fields, for example to dump a
+//! dataset into a file which a data warehouse can load:
+//!
+//! ```text
+//! id,name,score,kind
+//! 1,Alice,0.5,ADMIN
+//! 2,"Smith, Bob",NaN,USER
+//! ```
+//!
+//! The values are rendered as follows:
+//!
+//!  * Numbers are decimal, and floating point values which are not finite are `NaN`, `Infinity`
+//!    and `-Infinity`, as in the JSON mapping.
+//!  * `bool` values are `true` and `false`, and `bytes` values are standard base64.
+//!  * Enum values are their names, or their numbers if the enum doesn't declare them.
+//!  * A field with presence which is not set is an empty cell. A set field holding an empty string
+//!    is quoted, as `""`, so that the two are told apart.
+//!
+//! Cells holding the delimiter, a quote or a line break are quoted, as in [RFC 4180][1], with
+//! quotes doubled. TSV files use the same quoting. Rows end with `\n`, and are split at either
+//! `\n` or `\r\n` when decoding.
+//!
+//! [1]: https://www.rfc-editor.org/rfc/rfc4180
+
+use core::fmt::{self, Write};
+use core::str::FromStr;
+
+use bytes::Bytes;
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec::Vec;
+
+use crate::base64;
+use crate::dynamic::{DynamicMessage, Value};
+use crate::reflect::{FieldDescriptor, Kind, MessageDescriptor};
+
+/// The columns of a table of messages, and the format of its rows.
+#[derive(Clone, Debug)]
+pub struct Table {
+    message: MessageDescriptor,
+    columns: Vec<FieldDescriptor>,
+    delimiter: char,
+}
+
+impl Table {
+    /// Returns a CSV table with a column for each field of the message, in declaration order.
+    ///
+    /// Returns an error if a field of the message is a message, or a repeated or map field.
+    pub fn new(message: &MessageDescriptor) -> Result<Table, TabularError> {
+        let columns = message.fields().collect::<Vec<_>>();
+        for field in &columns {
+            if field.is_list() || field.is_map() || matches!(field.kind(), Kind::Message(_)) {
+                return Err(TabularError::new(format!(
+                    "field {}.{} is not a scalar",
+                    message.full_name(),
+                    field.name()
+                )));
+            }
+        }
+        Ok(Table {
+            message: message.clone(),
+            columns,
+            delimiter: ',',
+        })
+    }
+
+    /// Sets the delimiter of the cells, which is `,` by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the delimiter is a quote or a line break.
+    pub fn delimiter(mut self, delimiter: char) -> Table {
+        assert!(
+            !matches!(delimiter, '"' | '\r' | '\n'),
+            "invalid delimiter {:?}",
+            delimiter
+        );
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets the delimiter of the cells to a tab, for TSV files.
+    pub fn tsv(self) -> Table {
+        self.delimiter('\t')
+    }
+
+    /// Returns the fields of the columns, in order.
+    pub fn columns(&self) -> &[FieldDescriptor] {
+        &self.columns
+    }
+
+    /// Appends the header row, which holds the names of the fields.
+    pub fn write_header(&self, out: &mut String) {
+        for (i, field) in self.columns.iter().enumerate() {
+            if i > 0 {
+                out.push(self.delimiter);
+            }
+            self.write_cell(field.name(), false, out);
+        }
+        out.push('\n');
+    }
+
+    /// Appends the row of a message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the message is not of the type of the table.
+    pub fn write_row(&self, message: &DynamicMessage, out: &mut String) {
+        assert_eq!(
+            message.descriptor().full_name(),
+            self.message.full_name(),
+            "message type mismatch"
+        );
+        for (i, field) in self.columns.iter().enumerate() {
+            if i > 0 {
+                out.push(self.delimiter);
+            }
+            if field.supports_presence() && !message.has_field(field) {
+                continue;
+            }
+            match &*message.get_field(field) {
+                Value::String(value) => self.write_cell(value, field.supports_presence(), out),
+                Value::Bytes(value) => write!(out, "{}", base64::Display(value)).unwrap(),
+                Value::EnumNumber(number) => match field.kind().as_enum() {
+                    Some(enum_type) => match enum_type.get_value(*number) {
+                        Some(name) => self.write_cell(name, false, out),
+                        None => write!(out, "{}", number).unwrap(),
+                    },
+                    None => write!(out, "{}", number).unwrap(),
+                },
+                value => write!(out, "{}", Scalar(value)).unwrap(),
+            }
+        }
+        out.push('\n');
+    }
+
+    /// Returns the header and the rows of the messages.
+    pub fn encode<'a, I>(&self, messages: I) -> String
+    where
+        I: IntoIterator<Item = &'a DynamicMessage>,
+    {
+        let mut out = String::new();
+        self.write_header(&mut out);
+        for message in messages {
+            self.write_row(message, &mut out);
+        }
+        out
+    }
+
+    /// Decodes the messages of the rows of a file, which starts with a header row.
+    ///
+    /// The columns are matched to the fields by the names in the header, which may be the names of
+    /// the fields or their JSON names, in any order. Fields without a column are not set. Returns
+    /// an error if a column names no field, or if a cell is not a valid value of its field.
+    pub fn decode(&self, text: &str) -> Result<Vec<DynamicMessage>, TabularError> {
+        let mut rows = Rows {
+            text,
+            delimiter: self.delimiter,
+            line: 1,
+        };
+        let header = match rows.next_row()? {
+            Some(header) => header,
+            None => return Ok(Vec::new()),
+        };
+        let columns = header
+            .into_iter()
+            .map(|cell| {
+                self.columns
+                    .iter()
+                    .find(|field| field.name() == cell.value || field.json_name() == cell.value)
+                    .ok_or_else(|| {
+                        TabularError::new(format!("line 1: unknown column {:?}", cell.value))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut messages = Vec::new();
+        loop {
+            let line = rows.line;
+            let row = match rows.next_row()? {
+                Some(row) => row,
+                None => return Ok(messages),
+            };
+            if row.len() != columns.len() {
+                return Err(TabularError::new(format!(
+                    "line {}: expected {} cells, found {}",
+                    line,
+                    columns.len(),
+                    row.len()
+                )));
+            }
+            let mut message = DynamicMessage::new(self.message.clone());
+            for (field, cell) in columns.iter().zip(row) {
+                if cell.value.is_empty() && !cell.quoted {
+                    continue;
+                }
+                let value = parse_value(field, &cell.value).ok_or_else(|| {
+                    TabularError::new(format!(
+                        "line {}: invalid value {:?} for field {}",
+                        line,
+                        cell.value,
+                        field.name()
+                    ))
+                })?;
+                message.set_field(field, value);
+            }
+            messages.push(message);
+        }
+    }
+
+    /// Appends a cell, quoting it if it holds the delimiter, a quote or a line break, or if it is
+    /// empty and `quote_empty` is set.
+    fn write_cell(&self, value: &str, quote_empty: bool, out: &mut String) {
+        let quote = (value.is_empty() && quote_empty)
+            || value.contains(|c| c == self.delimiter || c == '"' || c == '\r' || c == '\n');
+        if !quote {
+            out.push_str(value);
+            return;
+        }
+        out.push('"');
+        for c in value.chars() {
+            if c == '"' {
+                out.push('"');
+            }
+            out.push(c);
+        }
+        out.push('"');
+    }
+}
+
+/// Displays a number or a `bool`.
+struct Scalar<'a>(&'a Value);
+
+impl<'a> fmt::Display for Scalar<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self.0 {
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::I32(value) => write!(f, "{}", value),
+            Value::I64(value) => write!(f, "{}", value),
+            Value::U32(value) => write!(f, "{}", value),
+            Value::U64(value) => write!(f, "{}", value),
+            Value::F32(value) if value.is_finite() => write!(f, "{}", value),
+            Value::F64(value) if value.is_finite() => write!(f, "{}", value),
+            Value::F32(value) if value.is_nan() => f.write_str("NaN"),
+            Value::F64(value) if value.is_nan() => f.write_str("NaN"),
+            Value::F32(value) => f.write_str(if value > 0.0 { "Infinity" } else { "-Infinity" }),
+            Value::F64(value) => f.write_str(if value > 0.0 { "Infinity" } else { "-Infinity" }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parses the value of a cell.
+fn parse_value(field: &FieldDescriptor, cell: &str) -> Option<Value> {
+    let value = match field.kind() {
+        Kind::Double => Value::F64(parse_float(cell)?),
+        Kind::Float => Value::F32(parse_float(cell)?),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => Value::I32(cell.parse().ok()?),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => Value::I64(cell.parse().ok()?),
+        Kind::Uint32 | Kind::Fixed32 => Value::U32(cell.parse().ok()?),
+        Kind::Uint64 | Kind::Fixed64 => Value::U64(cell.parse().ok()?),
+        Kind::Bool => match cell {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => return None,
+        },
+        Kind::String => Value::String(cell.to_string()),
+        Kind::Bytes => Value::Bytes(Bytes::from(base64::decode(cell.as_bytes()).ok()?)),
+        Kind::Enum(enum_type) => Value::EnumNumber(match enum_type.get_value_by_name(cell) {
+            Some(number) => number,
+            None => cell.parse().ok()?,
+        }),
+        Kind::Message(_) => return None,
+    };
+    Some(value)
+}
+
+fn parse_float<T>(cell: &str) -> Option<T>
+where
+    T: FromStr + Into<f64> + Copy,
+{
+    let value = match cell {
+        "NaN" => "nan",
+        "Infinity" => "inf",
+        "-Infinity" => "-inf",
+        // Rust also parses `inf`, `nan` and a leading `+`, which are not valid cells.
+        _ if cell.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.') => cell,
+        _ => return None,
+    };
+    let parsed = value.parse::<T>().ok()?;
+    // Reject values which overflow to infinity, such as `1e999`.
+    if value == cell && !parsed.into().is_finite() {
+        return None;
+    }
+    Some(parsed)
+}
+
+/// A cell of a decoded row.
+struct Cell {
+    value: String,
+    quoted: bool,
+}
+
+/// Splits the text of a file into rows of cells.
+struct Rows<'a> {
+    text: &'a str,
+    delimiter: char,
+    /// The line of the start of the text.
+    line: usize,
+}
+
+impl<'a> Rows<'a> {
+    /// Returns the cells of the next row, or `None` at the end of the text.
+    fn next_row(&mut self) -> Result<Option<Vec<Cell>>, TabularError> {
+        if self.text.is_empty() {
+            return Ok(None);
+        }
+        let mut cells = Vec::new();
+        let mut chars = self.text.char_indices().peekable();
+        let mut cell = Cell {
+            value: String::new(),
+            quoted: false,
+        };
+        let start_line = self.line;
+        let end = loop {
+            let (index, c) = match chars.next() {
+                Some(next) => next,
+                None => break self.text.len(),
+            };
+            match c {
+                '"' if cell.value.is_empty() && !cell.quoted => {
+                    cell.quoted = true;
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) if chars.peek().map(|&(_, c)| c) == Some('"') => {
+                                chars.next();
+                                cell.value.push('"');
+                            }
+                            Some((_, '"')) => break,
+                            Some((_, c)) => {
+                                if c == '\n' {
+                                    self.line += 1;
+                                }
+                                cell.value.push(c);
+                            }
+                            None => {
+                                return Err(TabularError::new(format!(
+                                    "line {}: unterminated quoted cell",
+                                    start_line
+                                )))
+                            }
+                        }
+                    }
+                    match chars.peek() {
+                        Some(&(_, c)) if c == self.delimiter || c == '\r' || c == '\n' => (),
+                        None => (),
+                        Some(_) => {
+                            return Err(TabularError::new(format!(
+                                "line {}: unexpected character after quoted cell",
+                                self.line
+                            )))
+                        }
+                    }
+                }
+                c if c == self.delimiter => {
+                    cells.push(cell);
+                    cell = Cell {
+                        value: String::new(),
+                        quoted: false,
+                    };
+                }
+                '\r' if chars.peek().map(|&(_, c)| c) == Some('\n') => {
+                    chars.next();
+                    break index + 2;
+                }
+                '\n' => break index + 1,
+                '"' => {
+                    return Err(TabularError::new(format!(
+                        "line {}: unexpected quote in unquoted cell",
+                        self.line
+                    )))
+                }
+                c => cell.value.push(c),
+            }
+        };
+        cells.push(cell);
+        self.text = &self.text[end..];
+        self.line += 1;
+        Ok(Some(cells))
+    }
+}
+
+/// An error mapping a message type to a table, or decoding the rows of a table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TabularError {
+    description: String,
+}
+
+impl TabularError {
+    fn new(description: impl Into<String>) -> TabularError {
+        TabularError {
+            description: description.into(),
+        }
+    }
+}
+
+impl fmt::Display for TabularError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.description)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TabularError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, test_pool, typed_field};
+    use crate::{
+        DescriptorPool, DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto,
+        FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    };
+
+    fn pool() -> DescriptorPool {
+        let optional = |field: FieldDescriptorProto| FieldDescriptorProto {
+            proto3_optional: Some(true),
+            ..field
+        };
+        let row = DescriptorProto {
+            name: Some("Row".to_string()),
+            field: vec![
+                field("id", 1, Label::Optional, Type::Int64),
+                field("display_name", 2, Label::Optional, Type::String),
+                field("score", 3, Label::Optional, Type::Double),
+                field("ratio", 4, Label::Optional, Type::Float),
+                typed_field("kind", 5, Label::Optional, Type::Enum, ".test.Kind"),
+                field("active", 6, Label::Optional, Type::Bool),
+                field("data", 7, Label::Optional, Type::Bytes),
+                optional(field("note", 8, Label::Optional, Type::String)),
+                optional(field("count", 9, Label::Optional, Type::Uint32)),
+            ],
+            ..Default::default()
+        };
+        let kind = EnumDescriptorProto {
+            name: Some("Kind".to_string()),
+            value: ["USER", "ADMIN"]
+                .iter()
+                .zip(0..)
+                .map(|(name, number)| EnumValueDescriptorProto {
+                    name: Some(name.to_string()),
+                    number: Some(number),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("test.proto".to_string()),
+                package: Some("test".to_string()),
+                message_type: vec![row],
+                enum_type: vec![kind],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let pool = pool();
+        let desc = pool.get_message_by_name("test.Row").unwrap();
+        let table = Table::new(&desc).unwrap();
+
+        let mut first = DynamicMessage::new(desc.clone());
+        first.set_field_by_name("id", Value::I64(-1));
+        first.set_field_by_name(
+            "display_name",
+            Value::String("Smith, \"Bob\"\nJr".to_string()),
+        );
+        first.set_field_by_name("score", Value::F64(f64::NEG_INFINITY));
+        first.set_field_by_name("ratio", Value::F32(0.1));
+        first.set_field_by_name("kind", Value::EnumNumber(1));
+        first.set_field_by_name("active", Value::Bool(true));
+        first.set_field_by_name("data", Value::Bytes(Bytes::from_static(b"\x00\x01")));
+        first.set_field_by_name("note", Value::String(String::new()));
+        let mut second = DynamicMessage::new(desc.clone());
+        second.set_field_by_name("kind", Value::EnumNumber(7));
+        second.set_field_by_name("score", Value::F64(f64::NAN));
+        second.set_field_by_name("count", Value::U32(0));
+
+        let csv = table.encode(&[first.clone(), second.clone()]);
+        assert_eq!(
+            csv,
+            "id,display_name,score,ratio,kind,active,data,note,count\n\
+             -1,\"Smith, \"\"Bob\"\"\nJr\",-Infinity,0.1,ADMIN,true,AAE=,\"\",\n\
+             0,,NaN,0,7,false,,,0\n"
+        );
+        let decoded = table.decode(&csv).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0], first);
+        assert!(decoded[1]
+            .get_field_by_name("score")
+            .unwrap()
+            .as_f64()
+            .unwrap()
+            .is_nan());
+        assert!(!decoded[1].has_field(&desc.get_field_by_name("note").unwrap()));
+        assert_eq!(
+            decoded[1].get_field_by_name("count").unwrap().as_u32(),
+            Some(0)
+        );
+
+        let tsv = table.clone().tsv().encode(&[second]);
+        assert_eq!(
+            tsv,
+            "id\tdisplay_name\tscore\tratio\tkind\tactive\tdata\tnote\tcount\n\
+             0\t\tNaN\t0\t7\tfalse\t\t\t0\n"
+        );
+
+        let decoded = table
+            .decode("kind,displayName\r\nUSER,a\r\n1,\"b,c\"")
+            .unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(
+            decoded[1]
+                .get_field_by_name("display_name")
+                .unwrap()
+                .as_str(),
+            Some("b,c")
+        );
+        assert_eq!(table.decode("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn errors() {
+        let pool = pool();
+        let table = Table::new(&pool.get_message_by_name("test.Row").unwrap()).unwrap();
+        for (csv, error) in &[
+            ("id,unknown\n", "line 1: unknown column \"unknown\""),
+            ("id,kind\n1\n", "line 2: expected 2 cells, found 1"),
+            ("id\n1\nx\n", "line 3: invalid value \"x\" for field id"),
+            (
+                "kind\nOTHER\n",
+                "line 2: invalid value \"OTHER\" for field kind",
+            ),
+            (
+                "score\ninf\n",
+                "line 2: invalid value \"inf\" for field score",
+            ),
+            ("note\n\"a\nb\"\n\"c\n", "line 4: unterminated quoted cell"),
+            (
+                "note\n\"a\"b\n",
+                "line 2: unexpected character after quoted cell",
+            ),
+            ("note\na\"b\n", "line 2: unexpected quote in unquoted cell"),
+        ] {
+            assert_eq!(
+                table.decode(csv).unwrap_err().to_string(),
+                *error,
+                "{}",
+                csv
+            );
+        }
+
+        let test_pool = test_pool();
+        let error = Table::new(&test_pool.get_message_by_name("test.Person").unwrap())
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with("is not a scalar"), "{}", error);
+    }
+}
@@ -0,0 +1,709 @@
+//! gRPC transcoding of requests and responses between HTTP and messages.
+//!
+//! A gateway serving methods annotated with `google.api.http` options maps each HTTP request to
+//! the request message of the method by the [transcoding rules][1] of the matching binding, and
+//! the response message back to the body of the HTTP response. A client of the REST endpoints
+//! does the reverse. The functions of this module implement these rules on messages in the form
+//! of their [JSON mapping][2], as a [`Struct`], given the descriptors of the messages:
+//!
+//! ```rust,ignore
+//! let binding = transcoding::match_binding(&rule, "PATCH", uri.path()).ok_or(NotFound)?;
+//! let request = transcoding::combine_request(binding, &descriptor, uri.path(), query, body)?;
+//! ```
+//!
+//!  * [`combine_request`] builds the request message from the URL path, the query string and the
+//!    JSON body of an HTTP request, and [`split_request`] splits a request message into them.
+//!  * [`split_response`] returns the JSON body of the HTTP response holding a response message,
+//!    and [`combine_response`] the response message held by a body.
+//!
+//! The values of path variables and query parameters are strings, which are converted to the JSON
+//! values of the fields they are bound to, so that the message parses: `?page_size=10` sets the
+//! `int32` field `page_size` to the number `10`. Only fields whose values are JSON scalars can be
+//! bound to them, which excludes maps, and messages other than the well-known types rendered as
+//! JSON scalars, such as `google.protobuf.Timestamp`. The fields of nested messages are bound by
+//! their field path, such as `book.name`.
+//!
+//! [1]: https://cloud.google.com/endpoints/docs/grpc/transcoding
+//! [2]: https://protobuf.dev/programming-guides/proto3/#json
+
+use core::fmt;
+
+use prost::alloc::format;
+use prost::alloc::string::{String, ToString};
+use prost::alloc::vec;
+use prost::alloc::vec::Vec;
+
+use crate::api::{HttpRule, PathTemplate};
+use crate::http_rule::{percent_decode, percent_encode};
+use crate::reflect::{FieldDescriptor, Kind, MessageDescriptor};
+use crate::value;
+use crate::{ListValue, Struct, Value};
+
+/// An HTTP request, as mapped from a request message by [`split_request`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpRequest {
+    /// The HTTP method, such as `GET`.
+    pub method: String,
+    /// The URL path, percent-encoded.
+    pub path: String,
+    /// The query string, without the leading `?`, which is empty if there are no query parameters.
+    pub query: String,
+    /// The JSON body, if the binding has one.
+    pub body: Option<Value>,
+}
+
+/// Returns the binding of a rule, or of its additional bindings, which maps the given HTTP method
+/// and URL path, if any.
+///
+/// Bindings whose path template is invalid never match.
+pub fn match_binding<'a>(rule: &'a HttpRule, method: &str, path: &str) -> Option<&'a HttpRule> {
+    rule.bindings()
+        .find(|binding| match binding.method_and_path() {
+            Some((binding_method, template)) => {
+                binding_method.eq_ignore_ascii_case(method)
+                    && PathTemplate::parse(template)
+                        .map_or(false, |template| template.match_path(path).is_some())
+            }
+            None => false,
+        })
+}
+
+/// Builds a request message from the URL path, the query string and the JSON body of an HTTP
+/// request, by the rules of a binding.
+///
+/// The query string, without the leading `?`, is ignored if the body of the binding is `*`. Query
+/// parameters which name no field, such as `alt` or `$fields`, are ignored, and the parameters of a
+/// repeated field are appended to it. The fields bound to the path take precedence over the body,
+/// which takes precedence over the query parameters.
+pub fn combine_request(
+    binding: &HttpRule,
+    request: &MessageDescriptor,
+    path: &str,
+    query: &str,
+    body: Option<Value>,
+) -> Result<Struct, TranscodingError> {
+    let template = path_template(binding)?;
+    let variables = template.match_path(path).ok_or_else(|| {
+        TranscodingError::new(format!("path {} does not match {}", path, template))
+    })?;
+
+    let mut message = Struct::default();
+    if binding.body != "*" {
+        for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+            let (name, value) = match parameter.find('=') {
+                Some(index) => (&parameter[..index], &parameter[index + 1..]),
+                None => (parameter, ""),
+            };
+            let decode = |s: &str| {
+                percent_decode(&s.replace('+', " "), false).ok_or_else(|| {
+                    TranscodingError::new(format!("invalid query parameter {:?}", parameter))
+                })
+            };
+            let name = decode(name)?;
+            if let Some(fields) = find_fields(request, &name) {
+                let value = decode(value)?;
+                if !is_bound(binding, &template, &fields) {
+                    set_parameter(&mut message, &fields, &name, &value)?;
+                }
+            }
+        }
+    }
+
+    match (binding.body.as_str(), body) {
+        (_, None) | ("", _) => (),
+        ("*", Some(body)) => match body.kind {
+            Some(value::Kind::StructValue(body)) => message.fields.extend(body.fields),
+            Some(value::Kind::NullValue(_)) => (),
+            _ => return Err(TranscodingError::new("request body is not an object")),
+        },
+        (field_name, Some(body)) => {
+            let field = top_level_field(request, field_name)?;
+            if !body.is_null() {
+                message.insert(field.json_name(), body);
+            }
+        }
+    }
+
+    for (field_path, value) in variables {
+        let fields = find_fields(request, field_path)
+            .ok_or_else(|| TranscodingError::new(format!("invalid field path {}", field_path)))?;
+        set_parameter(&mut message, &fields, field_path, &value)?;
+    }
+    Ok(message)
+}
+
+/// Splits a request message into the URL path, the query string and the JSON body of an HTTP
+/// request, by the rules of a binding.
+///
+/// The fields bound to the path must be set, with values matching the path template, and the
+/// fields bound to query parameters must not be maps, or messages not rendered as JSON scalars.
+pub fn split_request(
+    binding: &HttpRule,
+    request: &MessageDescriptor,
+    message: &Struct,
+) -> Result<HttpRequest, TranscodingError> {
+    let template = path_template(binding)?;
+    let mut rest = message.clone();
+
+    let mut values = Vec::new();
+    for field_path in template.variables() {
+        let fields = find_fields(request, field_path)
+            .ok_or_else(|| TranscodingError::new(format!("invalid field path {}", field_path)))?;
+        let value = take(&mut rest, &fields).ok_or_else(|| {
+            TranscodingError::new(format!("field {} bound to the path is not set", field_path))
+        })?;
+        let value = parameter_string(&value).ok_or_else(|| {
+            TranscodingError::new(format!(
+                "field {} bound to the path is not a scalar",
+                field_path
+            ))
+        })?;
+        values.push((field_path, value));
+    }
+    let path = template
+        .expand(|field_path| {
+            values
+                .iter()
+                .find(|(path, _)| *path == field_path)
+                .map(|(_, value)| value.clone())
+        })
+        .ok_or_else(|| {
+            TranscodingError::new(format!(
+                "field values {:?} do not match {}",
+                values, template
+            ))
+        })?;
+
+    let body = match binding.body.as_str() {
+        "" => None,
+        "*" => Some(Value::from(core::mem::take(&mut rest))),
+        field_name => {
+            let field = top_level_field(request, field_name)?;
+            Some(rest.remove(field.json_name()).unwrap_or_else(Value::null))
+        }
+    };
+
+    let mut query = String::new();
+    append_query(&mut query, request, "", &rest)?;
+
+    let (method, _) = binding.method_and_path().unwrap_or_default();
+    Ok(HttpRequest {
+        method: method.to_string(),
+        path,
+        query,
+        body,
+    })
+}
+
+/// Returns the JSON body of the HTTP response holding a response message, which is its
+/// `response_body` field, if the binding has one, or else the whole message.
+pub fn split_response(
+    binding: &HttpRule,
+    response: &MessageDescriptor,
+    mut message: Struct,
+) -> Result<Value, TranscodingError> {
+    if binding.response_body.is_empty() {
+        return Ok(message.into());
+    }
+    let field = top_level_field(response, &binding.response_body)?;
+    Ok(message
+        .remove(field.json_name())
+        .unwrap_or_else(Value::null))
+}
+
+/// Returns the response message held by the JSON body of an HTTP response, which is its
+/// `response_body` field, if the binding has one, or else the whole message.
+pub fn combine_response(
+    binding: &HttpRule,
+    response: &MessageDescriptor,
+    body: Value,
+) -> Result<Struct, TranscodingError> {
+    let mut message = Struct::default();
+    if binding.response_body.is_empty() {
+        return match body.kind {
+            Some(value::Kind::StructValue(body)) => Ok(body),
+            _ => Err(TranscodingError::new("response body is not an object")),
+        };
+    }
+    let field = top_level_field(response, &binding.response_body)?;
+    if !body.is_null() {
+        message.insert(field.json_name(), body);
+    }
+    Ok(message)
+}
+
+fn path_template(binding: &HttpRule) -> Result<PathTemplate, TranscodingError> {
+    let (_, template) = binding
+        .method_and_path()
+        .ok_or_else(|| TranscodingError::new("binding has no pattern"))?;
+    PathTemplate::parse(template).map_err(|err| TranscodingError::new(err.to_string()))
+}
+
+fn top_level_field(
+    message: &MessageDescriptor,
+    field_name: &str,
+) -> Result<FieldDescriptor, TranscodingError> {
+    message.get_field_by_name(field_name).ok_or_else(|| {
+        TranscodingError::new(format!(
+            "no field {} in message {}",
+            field_name,
+            message.full_name()
+        ))
+    })
+}
+
+/// Finds the fields along a field path, whose names may be either the names of the fields or
+/// their JSON names.
+fn find_fields(message: &MessageDescriptor, field_path: &str) -> Option<Vec<FieldDescriptor>> {
+    let mut message = message.clone();
+    let mut fields = Vec::new();
+    for name in field_path.split('.') {
+        if let Some(field) = fields.last() {
+            message = nested_message(field)?;
+        }
+        let field = message
+            .get_field_by_name(name)
+            .or_else(|| message.get_field_by_json_name(name))?;
+        fields.push(field);
+    }
+    Some(fields)
+}
+
+/// Returns the message type of a singular message field.
+fn nested_message(field: &FieldDescriptor) -> Option<MessageDescriptor> {
+    match field.kind() {
+        Kind::Message(message) if !field.is_list() && !field.is_map() => Some(message),
+        _ => None,
+    }
+}
+
+/// Returns `true` if the field at the end of a path is bound to the path or the body of the
+/// binding, or is nested in such a field.
+fn is_bound(binding: &HttpRule, template: &PathTemplate, fields: &[FieldDescriptor]) -> bool {
+    let is_prefix = |field_path: &str| {
+        let names = field_path.split('.').collect::<Vec<_>>();
+        names.len() <= fields.len()
+            && names
+                .iter()
+                .zip(fields)
+                .all(|(name, field)| *name == field.name())
+    };
+    is_prefix(&binding.body) || template.variables().any(is_prefix)
+}
+
+/// Sets the field at the end of a path from the value of a path variable or query parameter,
+/// creating the messages along the path, and appending to a repeated field.
+fn set_parameter(
+    message: &mut Struct,
+    fields: &[FieldDescriptor],
+    field_path: &str,
+    value: &str,
+) -> Result<(), TranscodingError> {
+    let (field, parents) = fields.split_last().unwrap();
+    let mut message = message;
+    for parent in parents {
+        let nested = message
+            .fields
+            .entry(parent.json_name().to_string())
+            .or_insert_with(|| Struct::default().into());
+        message = match &mut nested.kind {
+            Some(value::Kind::StructValue(nested)) => nested,
+            _ => {
+                return Err(TranscodingError::new(format!(
+                    "field {} is not a message",
+                    parent.name()
+                )))
+            }
+        };
+    }
+
+    let invalid = |description: &str| {
+        TranscodingError::new(format!(
+            "invalid value {:?} for field {}: {}",
+            value, field_path, description
+        ))
+    };
+    if field.is_map() {
+        return Err(invalid("map fields can't be bound to parameters"));
+    }
+    let value = scalar_value(&field.kind(), value).map_err(invalid)?;
+    if field.is_list() {
+        let list = message
+            .fields
+            .entry(field.json_name().to_string())
+            .or_insert_with(|| ListValue::default().into());
+        match &mut list.kind {
+            Some(value::Kind::ListValue(list)) => list.push(value),
+            _ => *list = vec![value].into(),
+        }
+    } else {
+        message.insert(field.json_name(), value);
+    }
+    Ok(())
+}
+
+/// Converts the string value of a parameter to the JSON value of a field.
+fn scalar_value(kind: &Kind, value: &str) -> Result<Value, &'static str> {
+    Ok(match kind {
+        Kind::Double | Kind::Float => match value {
+            "NaN" | "Infinity" | "-Infinity" => value.into(),
+            _ => value.parse::<f64>().map_err(|_| "not a number")?.into(),
+        },
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+            value.parse::<i32>().map_err(|_| "not an int32")?.into()
+        }
+        Kind::Uint32 | Kind::Fixed32 => value.parse::<u32>().map_err(|_| "not a uint32")?.into(),
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => value
+            .parse::<i64>()
+            .map_err(|_| "not an int64")?
+            .to_string()
+            .into(),
+        Kind::Uint64 | Kind::Fixed64 => value
+            .parse::<u64>()
+            .map_err(|_| "not a uint64")?
+            .to_string()
+            .into(),
+        Kind::Bool => match value {
+            "true" => true.into(),
+            "false" => false.into(),
+            _ => return Err("not a bool"),
+        },
+        Kind::String | Kind::Bytes => value.into(),
+        Kind::Enum(enum_type) => match enum_type.get_value_by_name(value) {
+            Some(_) => value.into(),
+            None => value
+                .parse::<i32>()
+                .map_err(|_| "unknown enum value")?
+                .into(),
+        },
+        Kind::Message(message) => match message.full_name() {
+            "google.protobuf.Timestamp"
+            | "google.protobuf.Duration"
+            | "google.protobuf.FieldMask"
+            | "google.protobuf.Value" => value.into(),
+            "google.protobuf.DoubleValue"
+            | "google.protobuf.FloatValue"
+            | "google.protobuf.Int64Value"
+            | "google.protobuf.UInt64Value"
+            | "google.protobuf.Int32Value"
+            | "google.protobuf.UInt32Value"
+            | "google.protobuf.BoolValue"
+            | "google.protobuf.StringValue"
+            | "google.protobuf.BytesValue" => match message.get_field_by_name("value") {
+                Some(field) => scalar_value(&field.kind(), value)?,
+                None => return Err("invalid wrapper type"),
+            },
+            _ => return Err("messages can't be bound to parameters"),
+        },
+    })
+}
+
+/// Removes the value at the end of a field path from a message, if it is set.
+fn take(message: &mut Struct, fields: &[FieldDescriptor]) -> Option<Value> {
+    let (field, parents) = fields.split_last().unwrap();
+    let mut message = message;
+    for parent in parents {
+        message = match &mut message.fields.get_mut(parent.json_name())?.kind {
+            Some(value::Kind::StructValue(nested)) => nested,
+            _ => return None,
+        };
+    }
+    message
+        .remove(field.json_name())
+        .filter(|value| !value.is_null())
+}
+
+/// Returns the string value of a path variable or query parameter holding a JSON scalar.
+fn parameter_string(value: &Value) -> Option<String> {
+    match value.kind.as_ref()? {
+        value::Kind::StringValue(value) => Some(value.clone()),
+        value::Kind::BoolValue(value) => Some(value.to_string()),
+        value::Kind::NumberValue(value) if value.is_nan() => Some("NaN".to_string()),
+        value::Kind::NumberValue(value) if value.is_infinite() => Some(
+            if *value > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            }
+            .to_string(),
+        ),
+        value::Kind::NumberValue(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Appends the fields of a message as query parameters, named by their field paths.
+fn append_query(
+    query: &mut String,
+    message: &MessageDescriptor,
+    prefix: &str,
+    fields: &Struct,
+) -> Result<(), TranscodingError> {
+    for (name, value) in fields {
+        if value.is_null() {
+            continue;
+        }
+        let field = message
+            .get_field_by_json_name(name)
+            .or_else(|| message.get_field_by_name(name))
+            .ok_or_else(|| {
+                TranscodingError::new(format!(
+                    "no field {} in message {}",
+                    name,
+                    message.full_name()
+                ))
+            })?;
+        let field_path = format!("{}{}", prefix, field.name());
+        let not_scalar =
+            || TranscodingError::new(format!("field {} can't be a query parameter", field_path));
+
+        if let (Some(nested), Some(fields)) = (nested_message(&field), value.as_struct()) {
+            if !nested.full_name().starts_with("google.protobuf.") {
+                append_query(query, &nested, &format!("{}.", field_path), fields)?;
+                continue;
+            }
+        }
+        if field.is_map() {
+            return Err(not_scalar());
+        }
+        let values = match (field.is_list(), value.as_list()) {
+            (true, Some(list)) => list.iter().collect::<Vec<_>>(),
+            _ => vec![value],
+        };
+        for value in values {
+            let value = parameter_string(value).ok_or_else(not_scalar)?;
+            if !query.is_empty() {
+                query.push('&');
+            }
+            percent_encode(&field_path, query);
+            query.push('=');
+            percent_encode(&value, query);
+        }
+    }
+    Ok(())
+}
+
+/// An error mapping between an HTTP request or response and a message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscodingError {
+    description: String,
+}
+
+impl TranscodingError {
+    fn new(description: impl Into<String>) -> TranscodingError {
+        TranscodingError {
+            description: description.into(),
+        }
+    }
+}
+
+impl fmt::Display for TranscodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "transcoding failed: {}", self.description)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TranscodingError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::api::http_rule::Pattern;
+    use crate::field_descriptor_proto::{Label, Type};
+    use crate::reflect::tests::{field, typed_field};
+    use crate::{
+        prost_struct, DescriptorPool, DescriptorProto, EnumDescriptorProto,
+        EnumValueDescriptorProto, FileDescriptorProto, FileDescriptorSet,
+    };
+
+    fn pool() -> DescriptorPool {
+        let book = DescriptorProto {
+            name: Some("Book".to_string()),
+            field: vec![
+                field("name", 1, Label::Optional, Type::String),
+                field("page_count", 2, Label::Optional, Type::Int64),
+            ],
+            ..Default::default()
+        };
+        let request = DescriptorProto {
+            name: Some("UpdateBookRequest".to_string()),
+            field: vec![
+                typed_field("book", 1, Label::Optional, Type::Message, ".library.Book"),
+                field("validate_only", 2, Label::Optional, Type::Bool),
+                field("revisions", 3, Label::Repeated, Type::Uint32),
+                typed_field("view", 4, Label::Optional, Type::Enum, ".library.View"),
+                typed_field("origin", 5, Label::Optional, Type::Message, ".library.Book"),
+            ],
+            ..Default::default()
+        };
+        let view = EnumDescriptorProto {
+            name: Some("View".to_string()),
+            value: ["BASIC", "FULL"]
+                .iter()
+                .zip(0..)
+                .map(|(name, number)| EnumValueDescriptorProto {
+                    name: Some(name.to_string()),
+                    number: Some(number),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        DescriptorPool::from_file_descriptor_set(&FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                name: Some("library.proto".to_string()),
+                package: Some("library".to_string()),
+                message_type: vec![book, request],
+                enum_type: vec![view],
+                syntax: Some("proto3".to_string()),
+                ..Default::default()
+            }],
+        })
+        .unwrap()
+    }
+
+    fn update_rule() -> HttpRule {
+        HttpRule {
+            pattern: Some(Pattern::Patch(
+                "/v1/{book.name=shelves/*/books/*}".to_string(),
+            )),
+            body: "book".to_string(),
+            additional_bindings: vec![HttpRule {
+                pattern: Some(Pattern::Post(
+                    "/v1/{book.name=shelves/*/books/*}:update".to_string(),
+                )),
+                body: "*".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn request() {
+        let pool = pool();
+        let request = pool
+            .get_message_by_name("library.UpdateBookRequest")
+            .unwrap();
+        let rule = update_rule();
+
+        let binding = match_binding(&rule, "patch", "/v1/shelves/1/books/2").unwrap();
+        assert_eq!(binding, &rule);
+        let message = combine_request(
+            binding,
+            &request,
+            "/v1/shelves/1/books/2",
+            "validateOnly=true&revisions=1&revisions=2&view=FULL&origin.page_count=7&book.name=x&alt=json",
+            Some(prost_struct! { "pageCount": "300" }.into()),
+        )
+        .unwrap();
+        assert_eq!(
+            message,
+            prost_struct! {
+                "book": prost_struct! { "name": "shelves/1/books/2", "pageCount": "300" },
+                "validateOnly": true,
+                "revisions": vec![1, 2],
+                "view": "FULL",
+                "origin": prost_struct! { "pageCount": "7" },
+            }
+        );
+
+        let http = split_request(binding, &request, &message).unwrap();
+        // Query parameters follow the order of the message's fields.
+        let query = if cfg!(feature = "preserve_order") {
+            "validate_only=true&revisions=1&revisions=2&view=FULL&origin.page_count=7"
+        } else {
+            "origin.page_count=7&revisions=1&revisions=2&validate_only=true&view=FULL"
+        };
+        assert_eq!(
+            http,
+            HttpRequest {
+                method: "PATCH".to_string(),
+                path: "/v1/shelves/1/books/2".to_string(),
+                query: query.to_string(),
+                body: Some(prost_struct! { "pageCount": "300" }.into()),
+            }
+        );
+        assert_eq!(
+            combine_request(binding, &request, &http.path, &http.query, http.body).unwrap(),
+            message
+        );
+
+        let binding = match_binding(&rule, "POST", "/v1/shelves/1/books/2:update").unwrap();
+        assert_eq!(binding, &rule.additional_bindings[0]);
+        let http = split_request(binding, &request, &message).unwrap();
+        assert_eq!(http.path, "/v1/shelves/1/books/2:update");
+        assert_eq!(http.query, "");
+        assert_eq!(
+            combine_request(binding, &request, &http.path, "view=BASIC", http.body).unwrap(),
+            message
+        );
+
+        assert_eq!(match_binding(&rule, "GET", "/v1/shelves/1/books/2"), None);
+        assert_eq!(match_binding(&rule, "PATCH", "/v1/shelves/1"), None);
+    }
+
+    #[test]
+    fn invalid_request() {
+        let pool = pool();
+        let request = pool
+            .get_message_by_name("library.UpdateBookRequest")
+            .unwrap();
+        let rule = update_rule();
+        let path = "/v1/shelves/1/books/2";
+
+        for query in &[
+            "revisions=-1",
+            "validate_only=yes",
+            "view=UNKNOWN",
+            "origin=x",
+            "revisions=%",
+        ] {
+            assert!(
+                combine_request(&rule, &request, path, query, None).is_err(),
+                "{}",
+                query
+            );
+        }
+        assert!(combine_request(&rule, &request, "/v1/books/2", "", None).is_err());
+
+        assert_eq!(
+            split_request(&rule, &request, &Struct::default())
+                .unwrap_err()
+                .to_string(),
+            "transcoding failed: field book.name bound to the path is not set"
+        );
+        let message = prost_struct! { "book": prost_struct! { "name": "shelves/1" } };
+        assert!(split_request(&rule, &request, &message).is_err());
+    }
+
+    #[test]
+    fn response() {
+        let pool = pool();
+        let response = pool
+            .get_message_by_name("library.UpdateBookRequest")
+            .unwrap();
+        let message = prost_struct! {
+            "book": prost_struct! { "name": "a" },
+            "validateOnly": true,
+        };
+
+        let rule = HttpRule::default();
+        let body = split_response(&rule, &response, message.clone()).unwrap();
+        assert_eq!(body, Value::from(message.clone()));
+        assert_eq!(combine_response(&rule, &response, body).unwrap(), message);
+
+        let rule = HttpRule {
+            response_body: "book".to_string(),
+            ..Default::default()
+        };
+        let body = split_response(&rule, &response, message).unwrap();
+        assert_eq!(body, prost_struct! { "name": "a" }.into());
+        assert_eq!(
+            combine_response(&rule, &response, body).unwrap(),
+            prost_struct! { "book": prost_struct! { "name": "a" } }
+        );
+    }
+}
@@ -0,0 +1,106 @@
+//! Validity checks of `Timestamp` and `Duration` values when encoding.
+//!
+//! [`Message::encode`](prost::Message::encode) writes `Timestamp` and `Duration` values as they
+//! are, whether they are normalized and within the documented ranges or not. Like the `CheckValid`
+//! functions of the C++ implementation of Protobuf, the check can be enabled at runtime, so that
+//! invalid values are not sent to peers which would reject them:
+//!
+//! ```rust,ignore
+//! prost_types::validity::set_check_on_encode(true);
+//!
+//! // Fails, naming the field, if a timestamp or duration of the event is not valid.
+//! event.encode_checked(&mut buf)?;
+//! ```
+//!
+//! With the check enabled, [`Message::check_encode`](prost::Message::check_encode), and so
+//! [`Message::encode_checked`](prost::Message::encode_checked), reject the values for which
+//! [`Timestamp::is_valid`] or [`Duration::is_valid`] returns `false`, including values held by
+//! the fields of other messages. Values can be normalized beforehand with
+//! [`Timestamp::checked_normalize`] and [`Duration::checked_normalize`]. `Message::encode` never
+//! checks the values.
+//!
+//! The serde helpers in `prost_types::serde::timestamp` and `prost_types::serde::duration`
+//! normalize values when serializing instead, and their `strict` modules reject invalid values.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use prost::InvalidValueError;
+
+use crate::{Duration, Timestamp};
+
+static CHECK_ON_ENCODE: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether `Message::check_encode` rejects invalid `Timestamp` and `Duration` values, for
+/// the whole process.
+///
+/// The check is disabled until it is enabled.
+pub fn set_check_on_encode(enabled: bool) {
+    CHECK_ON_ENCODE.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns `true` if `Message::check_encode` rejects invalid `Timestamp` and `Duration` values.
+pub fn check_on_encode() -> bool {
+    CHECK_ON_ENCODE.load(Ordering::Relaxed)
+}
+
+/// Checks a `Timestamp` before it is encoded, called by its `Message::check_encode`.
+pub(crate) fn check_timestamp(timestamp: &Timestamp) -> Result<(), InvalidValueError> {
+    if check_on_encode() && !timestamp.is_valid() {
+        return Err(InvalidValueError::new(
+            "invalid timestamp: out of range or not normalized",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a `Duration` before it is encoded, called by its `Message::check_encode`.
+pub(crate) fn check_duration(duration: &Duration) -> Result<(), InvalidValueError> {
+    if check_on_encode() && !duration.is_valid() {
+        return Err(InvalidValueError::new(
+            "invalid duration: out of range or not normalized",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use prost::Message;
+
+    use super::*;
+
+    #[test]
+    fn check_on_encode() {
+        let invalid = Timestamp {
+            seconds: 0,
+            nanos: -1,
+        };
+        let duration = Duration {
+            seconds: 1,
+            nanos: -1,
+        };
+
+        // Other tests encode invalid values, so the check is only enabled within this test.
+        set_check_on_encode(true);
+        let timestamp_error = invalid.check_encode();
+        let duration_error = duration.check_encode();
+        let valid = Timestamp {
+            seconds: 1,
+            nanos: 0,
+        }
+        .check_encode();
+        set_check_on_encode(false);
+
+        assert_eq!(
+            timestamp_error.unwrap_err().description(),
+            "invalid timestamp: out of range or not normalized"
+        );
+        assert_eq!(
+            duration_error.unwrap_err().description(),
+            "invalid duration: out of range or not normalized"
+        );
+        assert_eq!(valid, Ok(()));
+        assert_eq!(invalid.check_encode(), Ok(()));
+        assert!(!invalid.encode_to_vec().is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+//! Checks the well-known types against the golden fixtures in `fixtures/well_known_types`.
+//!
+//! Each fixture is a message in the text format, with the binary encoding produced from it by
+//! `protoc` (see `generate.sh`), and optionally the canonical JSON encoding. Every fixture is
+//! checked to decode from and re-encode to exactly the golden binary encoding, and to map to and
+//! from the golden JSON encoding when this crate provides a JSON mapping for the type.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+use prost::Message;
+use prost_types::{
+    Any, Api, Duration, Enum, FieldMask, ListValue, SourceContext, Struct, Timestamp, Type, Value,
+};
+
+/// The well-known types, each of which must have at least one fixture.
+const WELL_KNOWN_TYPES: &[&str] = &[
+    "Any",
+    "Api",
+    "BoolValue",
+    "BytesValue",
+    "DoubleValue",
+    "Duration",
+    "Empty",
+    "Enum",
+    "FieldMask",
+    "FloatValue",
+    "Int32Value",
+    "Int64Value",
+    "ListValue",
+    "SourceContext",
+    "StringValue",
+    "Struct",
+    "Timestamp",
+    "Type",
+    "UInt32Value",
+    "UInt64Value",
+    "Value",
+];
+
+struct Fixture {
+    name: String,
+    message: String,
+    binary: Vec<u8>,
+    json: Option<String>,
+}
+
+fn fixtures() -> Vec<Fixture> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../fixtures/well_known_types");
+    let mut fixtures = Vec::new();
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path
+            .extension()
+            .map_or(true, |extension| extension != "textproto")
+        {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+        let text = fs::read_to_string(&path).unwrap();
+        let message = text
+            .lines()
+            .find_map(|line| line.strip_prefix("# proto-message: google.protobuf."))
+            .unwrap_or_else(|| panic!("{}: missing proto-message header", name))
+            .to_string();
+        let binary = fs::read(path.with_extension("binpb"))
+            .unwrap_or_else(|error| panic!("{}: {}; run generate.sh", name, error));
+        let json = fs::read_to_string(path.with_extension("json")).ok();
+        fixtures.push(Fixture {
+            name,
+            message,
+            binary,
+            json,
+        });
+    }
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    fixtures
+}
+
+#[test]
+fn golden() {
+    let fixtures = fixtures();
+    let covered = fixtures
+        .iter()
+        .map(|fixture| fixture.message.as_str())
+        .collect::<BTreeSet<_>>();
+    for message in WELL_KNOWN_TYPES {
+        assert!(covered.contains(message), "no fixture for {}", message);
+    }
+
+    for fixture in &fixtures {
+        check(fixture);
+    }
+}
+
+fn check(fixture: &Fixture) {
+    let json = fixture.json.as_deref();
+    match &*fixture.message {
+        "Any" => {
+            check_binary::<Any>(fixture);
+        }
+        "Api" => {
+            check_binary::<Api>(fixture);
+        }
+        "Enum" => {
+            check_binary::<Enum>(fixture);
+        }
+        "SourceContext" => {
+            check_binary::<SourceContext>(fixture);
+        }
+        "Type" => {
+            check_binary::<Type>(fixture);
+        }
+        "BoolValue" => json::plain(check_binary::<bool>(fixture), json),
+        "FieldMask" => json::plain(check_binary::<FieldMask>(fixture), json),
+        "Int32Value" => json::plain(check_binary::<i32>(fixture), json),
+        "StringValue" => json::plain(check_binary::<String>(fixture), json),
+        "UInt32Value" => json::plain(check_binary::<u32>(fixture), json),
+        "BytesValue" => json::bytes(check_binary::<Vec<u8>>(fixture), json),
+        "DoubleValue" => json::float(check_binary::<f64>(fixture), json),
+        "FloatValue" => json::float(check_binary::<f32>(fixture), json),
+        "Empty" => {
+            check_binary::<()>(fixture);
+            json::empty(json);
+        }
+        "Int64Value" => json::int64(check_binary::<i64>(fixture), json),
+        "UInt64Value" => json::int64(check_binary::<u64>(fixture), json),
+        "Duration" => json::duration(check_binary::<Duration>(fixture), json),
+        "Timestamp" => json::timestamp(check_binary::<Timestamp>(fixture), json),
+        "ListValue" => json::value(Value::from(check_binary::<ListValue>(fixture)), json),
+        "Struct" => json::value(Value::from(check_binary::<Struct>(fixture)), json),
+        "Value" => json::value(check_binary::<Value>(fixture), json),
+        message => panic!("{}: unexpected message type {}", fixture.name, message),
+    }
+}
+
+/// Checks that the golden binary encoding decodes and re-encodes to the same bytes.
+fn check_binary<M>(fixture: &Fixture) -> M
+where
+    M: Message + Default,
+{
+    let message = M::decode(&*fixture.binary)
+        .unwrap_or_else(|error| panic!("{}: failed to decode: {}", fixture.name, error));
+    assert_eq!(
+        message.encode_to_vec(),
+        fixture.binary,
+        "{}: re-encoding differs from the golden encoding",
+        fixture.name
+    );
+    message
+}
+
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+mod json {
+    use std::convert::TryFrom;
+    use std::fmt::Debug;
+
+    use serde_crate::{Deserialize, Serialize};
+    use serde_json::value::Serializer;
+    use serde_json::Value as Json;
+
+    use super::*;
+
+    fn golden(json: Option<&str>) -> Option<Json> {
+        json.map(|json| serde_json::from_str(json).unwrap())
+    }
+
+    pub fn plain<T>(value: T, json: Option<&str>)
+    where
+        T: Debug + PartialEq + Serialize + for<'de> Deserialize<'de>,
+    {
+        if let Some(golden) = golden(json) {
+            assert_eq!(serde_json::to_value(&value).unwrap(), golden);
+            assert_eq!(serde_json::from_value::<T>(golden).unwrap(), value);
+        }
+    }
+
+    macro_rules! with_module {
+        ($($name:ident: $ty:ty => $module:path),*) => {$(
+            pub fn $name(value: $ty, json: Option<&str>) {
+                use $module as module;
+                if let Some(golden) = golden(json) {
+                    assert_eq!(module::serialize(&value, Serializer).unwrap(), golden);
+                    let decoded: $ty = module::deserialize(golden).unwrap();
+                    assert_eq!(decoded, value);
+                }
+            }
+        )*};
+    }
+
+    with_module!(
+        bytes: Vec<u8> => prost_types::serde::vec_u8,
+        duration: Duration => prost_types::serde::duration,
+        timestamp: Timestamp => prost_types::serde::timestamp
+    );
+
+    pub fn empty(json: Option<&str>) {
+        use prost_types::serde::empty;
+        if let Some(golden) = golden(json) {
+            assert_eq!(empty::serialize(&(), Serializer).unwrap(), golden);
+            empty::deserialize(golden).unwrap();
+        }
+    }
+
+    pub fn int64<T>(value: T, json: Option<&str>)
+    where
+        T: prost_types::serde::int64::Int64 + Debug + PartialEq,
+    {
+        if let Some(golden) = golden(json) {
+            let serialized = prost_types::serde::int64::serialize(&value, Serializer).unwrap();
+            assert_eq!(serialized, golden);
+            let decoded: T = prost_types::serde::int64::deserialize(golden).unwrap();
+            assert_eq!(decoded, value);
+        }
+    }
+
+    pub fn float<T>(value: T, json: Option<&str>)
+    where
+        T: prost_types::serde::float::Float + Debug + PartialEq,
+    {
+        if let Some(golden) = golden(json) {
+            let serialized = prost_types::serde::float::serialize(&value, Serializer).unwrap();
+            assert_eq!(serialized.as_f64(), golden.as_f64());
+            let decoded: T = prost_types::serde::float::deserialize(golden).unwrap();
+            assert_eq!(decoded, value);
+        }
+    }
+
+    /// Numbers are compared as `f64`, since the JSON encoding of numbers is not canonical.
+    pub fn value(value: Value, json: Option<&str>) {
+        if let Some(golden) = golden(json) {
+            assert_eq!(Value::from(golden.clone()), value);
+            assert_eq!(serde_json::from_value::<Value>(golden).unwrap(), value);
+            let serialized = serde_json::to_value(&value).unwrap();
+            assert_eq!(Value::from(serialized), value);
+            let json = Json::try_from(value.clone()).unwrap();
+            assert_eq!(Value::from(json), value);
+        }
+    }
+}
+
+/// Without the JSON mapping, only the binary encoding is checked.
+#[cfg(not(all(feature = "serde", feature = "serde_json")))]
+mod json {
+    pub fn plain<T>(_: T, _: Option<&str>) {}
+    pub fn bytes<T>(_: T, _: Option<&str>) {}
+    pub fn duration<T>(_: T, _: Option<&str>) {}
+    pub fn empty(_: Option<&str>) {}
+    pub fn float<T>(_: T, _: Option<&str>) {}
+    pub fn int64<T>(_: T, _: Option<&str>) {}
+    pub fn timestamp<T>(_: T, _: Option<&str>) {}
+    pub fn value<T>(_: T, _: Option<&str>) {}
+}
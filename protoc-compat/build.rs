@@ -0,0 +1,21 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR environment variable not set"));
+    let file_descriptor_set = out_dir.join("compat.bin");
+
+    // Generate BTreeMap fields, so that maps are encoded in a deterministic order.
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .file_descriptor_set_path(&file_descriptor_set)
+        .compile_protos(&["src/compat.proto", "src/compat2.proto"], &["src"])
+        .unwrap();
+
+    // The tests run `protoc` on the descriptors of the messages.
+    println!("cargo:rustc-env=PROTOC={}", prost_build::protoc().display());
+    println!(
+        "cargo:rustc-env=FILE_DESCRIPTOR_SET={}",
+        file_descriptor_set.display()
+    );
+}
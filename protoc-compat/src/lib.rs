@@ -0,0 +1,77 @@
+//! Wire compatibility tests of `prost` against `protoc`.
+//!
+//! The tests generate random instances of the messages of `compat.proto` and `compat2.proto`,
+//! encode them with `prost`, and round-trip the encoded messages through the text format with
+//! `protoc --decode` and `protoc --encode`, which parse and serialize them with the C++
+//! implementation of Protobuf. The messages re-encoded by `protoc` must decode to the original
+//! messages, and must be byte-for-byte identical to the output of `prost` for messages without
+//! map entries, which have no canonical encoding.
+//!
+//! JSON interoperability is checked by the `conformance` crate, against the Protobuf conformance
+//! test runner.
+
+#![allow(clippy::large_enum_variant)]
+
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+pub mod compat {
+    include!(concat!(env!("OUT_DIR"), "/compat.rs"));
+}
+
+pub mod compat2 {
+    include!(concat!(env!("OUT_DIR"), "/compat2.rs"));
+}
+
+/// Decodes an encoded message into the text format with `protoc`, and encodes it back, returning
+/// the message as encoded by `protoc`.
+///
+/// `message` is the fully-qualified name of the message type, without a leading `.`, such as
+/// `compat.Scalars`.
+///
+/// # Panics
+///
+/// Panics if `protoc` fails, which happens if the message is invalid.
+pub fn protoc_round_trip(message: &str, encoded: &[u8]) -> Vec<u8> {
+    let text = protoc("--decode", message, encoded);
+    protoc("--encode", message, &text)
+}
+
+/// Runs `protoc` in decode or encode mode on the input, returning its output.
+fn protoc(mode: &str, message: &str, input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(env!("PROTOC"))
+        .arg(format!(
+            "--descriptor_set_in={}",
+            env!("FILE_DESCRIPTOR_SET")
+        ))
+        .arg(format!("{}={}", mode, message))
+        .arg("compat.proto")
+        .arg("compat2.proto")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run protoc");
+
+    // `protoc` reads all of its input before writing its output, so the input can be written
+    // before reading the output without deadlocking.
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input)
+        .expect("failed to write to protoc");
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = child.wait_with_output().unwrap();
+    assert!(
+        status.success(),
+        "protoc {} {} failed: {}",
+        mode,
+        message,
+        String::from_utf8_lossy(&stderr)
+    );
+    stdout
+}
@@ -0,0 +1,249 @@
+use std::fmt::Debug;
+
+use proptest::collection::{btree_map, vec};
+use proptest::option;
+use proptest::prelude::*;
+use prost::Message;
+
+use protoc_compat::compat::{composite, Color, Composite, Optionals, Repeated, Scalars};
+use protoc_compat::compat2::{legacy, Kind, Legacy};
+use protoc_compat::protoc_round_trip;
+
+/// Checks that a message round-trips through `protoc`, and that `protoc` encodes it to the same
+/// bytes as `prost` if the encoding is canonical.
+fn check<M>(name: &str, message: &M, canonical: bool) -> Result<(), TestCaseError>
+where
+    M: Message + Default + Debug,
+{
+    let encoded = message.encode_to_vec();
+    let round_tripped = protoc_round_trip(name, &encoded);
+    let decoded = M::decode(&*round_tripped)
+        .map_err(|error| TestCaseError::fail(format!("{}: {:?}", error, round_tripped)))?;
+    // Messages are compared by encoding, since NaN payloads and `-0.0` defeat `PartialEq`.
+    prop_assert_eq!(decoded.encode_to_vec(), encoded.clone(), "{:?}", message);
+    if canonical {
+        prop_assert_eq!(round_tripped, encoded, "{:?}", message);
+    }
+    Ok(())
+}
+
+/// Any number, but NaN, whose payload is not preserved by the text format.
+fn double() -> impl Strategy<Value = f64> {
+    any::<f64>().prop_filter("NaN", |value| !value.is_nan())
+}
+
+fn float() -> impl Strategy<Value = f32> {
+    any::<f32>().prop_filter("NaN", |value| !value.is_nan())
+}
+
+/// Any number, but NaN and `-0.0`, which `protoc` encodes for proto3 fields without presence,
+/// while `prost` skips it as it equals the default value.
+fn implicit_double() -> impl Strategy<Value = f64> {
+    double().prop_filter("-0.0", |value| *value != 0.0 || value.is_sign_positive())
+}
+
+fn implicit_float() -> impl Strategy<Value = f32> {
+    float().prop_filter("-0.0", |value| *value != 0.0 || value.is_sign_positive())
+}
+
+/// Any enumeration value, including unknown values of the open proto3 enumerations.
+fn color() -> impl Strategy<Value = i32> {
+    prop_oneof![
+        Just(Color::Unspecified as i32),
+        Just(Color::Red as i32),
+        Just(Color::Green as i32),
+        any::<i32>(),
+    ]
+}
+
+fn bytes() -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..16)
+}
+
+fn string() -> impl Strategy<Value = String> {
+    ".{0,16}"
+}
+
+prop_compose! {
+    fn scalars()(
+        numbers in (
+            implicit_double(),
+            implicit_float(),
+            any::<i32>(),
+            any::<i64>(),
+            any::<u32>(),
+            any::<u64>(),
+            any::<i32>(),
+            any::<i64>(),
+        ),
+        fixed in (any::<u32>(), any::<u64>(), any::<i32>(), any::<i64>()),
+        others in (any::<bool>(), string(), bytes(), color()),
+    ) -> Scalars {
+        Scalars {
+            double: numbers.0,
+            float: numbers.1,
+            int32: numbers.2,
+            int64: numbers.3,
+            uint32: numbers.4,
+            uint64: numbers.5,
+            sint32: numbers.6,
+            sint64: numbers.7,
+            fixed32: fixed.0,
+            fixed64: fixed.1,
+            sfixed32: fixed.2,
+            sfixed64: fixed.3,
+            bool: others.0,
+            string: others.1,
+            bytes: others.2,
+            color: others.3,
+        }
+    }
+}
+
+prop_compose! {
+    fn optionals()(
+        double in option::of(double()),
+        int32 in option::of(any::<i32>()),
+        sint64 in option::of(any::<i64>()),
+        fixed32 in option::of(any::<u32>()),
+        bool in option::of(any::<bool>()),
+        string in option::of(string()),
+        bytes in option::of(bytes()),
+        color in option::of(color()),
+    ) -> Optionals {
+        Optionals { double, int32, sint64, fixed32, bool, string, bytes, color }
+    }
+}
+
+prop_compose! {
+    fn repeated()(
+        double in vec(double(), 0..4),
+        int32 in vec(any::<i32>(), 0..4),
+        sint64 in vec(any::<i64>(), 0..4),
+        fixed32 in vec(any::<u32>(), 0..4),
+        bool in vec(any::<bool>(), 0..4),
+        string in vec(string(), 0..4),
+        bytes in vec(bytes(), 0..4),
+        color in vec(color(), 0..4),
+        unpacked_int64 in vec(any::<i64>(), 0..4),
+        unpacked_float in vec(float(), 0..4),
+    ) -> Repeated {
+        Repeated {
+            double,
+            int32,
+            sint64,
+            fixed32,
+            bool,
+            string,
+            bytes,
+            color,
+            unpacked_int64,
+            unpacked_float,
+        }
+    }
+}
+
+fn choice() -> impl Strategy<Value = composite::Choice> {
+    prop_oneof![
+        string().prop_map(composite::Choice::Name),
+        any::<u64>().prop_map(composite::Choice::Number),
+        scalars().prop_map(composite::Choice::Detail),
+    ]
+}
+
+prop_compose! {
+    fn composite()(
+        scalars in option::of(scalars()),
+        optionals in vec(optionals(), 0..3),
+        repeated in option::of(repeated()),
+        counts in btree_map(string(), any::<i32>(), 0..4),
+        by_id in btree_map(any::<i32>(), scalars(), 0..3),
+        choice in option::of(choice()),
+    ) -> Composite {
+        Composite { scalars, optionals, repeated, counts, by_id, choice }
+    }
+}
+
+fn kind() -> impl Strategy<Value = i32> {
+    prop_oneof![
+        Just(Kind::Unknown as i32),
+        Just(Kind::First as i32),
+        Just(Kind::Second as i32),
+    ]
+}
+
+prop_compose! {
+    fn legacy()(
+        id in any::<i32>(),
+        name in option::of(string()),
+        offset in option::of(any::<i32>()),
+        kind in option::of(kind()),
+        values in vec(any::<i64>(), 0..4),
+        packed in vec(any::<u32>(), 0..4),
+        extra in option::of((option::of(bytes()), vec(string(), 0..3))),
+        entry in vec(option::of(any::<u64>()), 0..3),
+    ) -> Legacy {
+        Legacy {
+            id,
+            name,
+            offset,
+            kind,
+            values,
+            packed,
+            extra: extra.map(|(payload, labels)| legacy::Extra { payload, labels }),
+            entry: entry.into_iter().map(|key| legacy::Entry { key }).collect(),
+        }
+    }
+}
+
+proptest! {
+    // Every case runs `protoc` twice.
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn scalars_round_trip(message in scalars()) {
+        check("compat.Scalars", &message, true)?;
+    }
+
+    #[test]
+    fn optionals_round_trip(message in optionals()) {
+        check("compat.Optionals", &message, true)?;
+    }
+
+    #[test]
+    fn repeated_round_trip(message in repeated()) {
+        check("compat.Repeated", &message, true)?;
+    }
+
+    #[test]
+    fn composite_round_trip(message in composite()) {
+        // `protoc` encodes the keys and values of map entries even if they are default values.
+        let canonical = message.counts.is_empty() && message.by_id.is_empty();
+        check("compat.Composite", &message, canonical)?;
+    }
+
+    #[test]
+    fn legacy_round_trip(message in legacy()) {
+        check("compat2.Legacy", &message, true)?;
+    }
+}
+
+#[test]
+fn empty_messages() {
+    for (name, encoded) in &[
+        ("compat.Scalars", Scalars::default().encode_to_vec()),
+        ("compat.Composite", Composite::default().encode_to_vec()),
+    ] {
+        assert!(encoded.is_empty());
+        assert_eq!(protoc_round_trip(name, encoded), *encoded);
+    }
+
+    // Unset proto2 fields are not encoded, but required fields are always set.
+    let legacy = Legacy::default();
+    assert_eq!(
+        protoc_round_trip("compat2.Legacy", &legacy.encode_to_vec()),
+        [0x08, 0x00]
+    );
+    assert_eq!(legacy.name(), "legacy");
+    assert_eq!(legacy.kind(), Kind::First);
+}
@@ -0,0 +1,384 @@
+//! Cross-checking of [`Message::encoded_len`] against the bytes actually encoded.
+//!
+//! The length delimiter of a nested message is written from its `encoded_len` before the message
+//! is encoded, so a message whose `encoded_len` disagrees with its `encode_raw`, typically a
+//! hand-written `Message` implementation or a custom field codec, silently corrupts the output of
+//! every message containing it.
+//!
+//! With the `audit-encoded-len` feature enabled, every message encoded by `prost`, at the top
+//! level or nested in another message or group, is checked after being encoded. A mismatch is
+//! reported as a [`Mismatch`] holding the path of messages and field tags leading to the faulty
+//! message, which panics unless a [`Reporter`] is installed with [`set_reporter`]. A faulty
+//! message also causes a mismatch in each message containing it, which is not reported.
+//!
+//! The number of bytes encoded is measured with `BufMut::remaining_mut`, which decreases by the
+//! number of bytes written for all buffers provided by `bytes`. The checks add a thread-local
+//! lookup and an extra `encoded_len` call for each group, so the feature is intended for tests
+//! and debugging, not for production builds.
+
+use core::cell::RefCell;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use std::boxed::Box;
+use std::vec::Vec;
+
+use bytes::BufMut;
+
+use crate::Message;
+
+/// A message on the path to a [`Mismatch`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Segment {
+    /// The tag of the field holding the message, or `None` for the message being encoded at the
+    /// top level.
+    pub tag: Option<u32>,
+    /// The Rust type name of the message.
+    pub message: &'static str,
+}
+
+/// A message whose encoded length differs from its `encoded_len`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Mismatch<'a> {
+    /// The messages leading to the faulty message, starting with the message being encoded at the
+    /// top level, and ending with the faulty message.
+    pub path: &'a [Segment],
+    /// The length returned by `encoded_len`.
+    pub expected: usize,
+    /// The number of bytes written by `encode_raw`.
+    pub actual: usize,
+}
+
+impl<'a> fmt::Display for Mismatch<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`encoded_len` returned {} but {} bytes were encoded, at ",
+            self.expected, self.actual
+        )?;
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" > ")?;
+            }
+            if let Some(tag) = segment.tag {
+                write!(f, "{}: ", tag)?;
+            }
+            f.write_str(segment.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Receives the mismatches found while encoding.
+pub trait Reporter: Sync {
+    /// Called for each mismatch, on the thread encoding the message, once the faulty message has
+    /// been encoded.
+    fn mismatch(&self, mismatch: &Mismatch<'_>);
+}
+
+static REPORTER: AtomicPtr<&'static dyn Reporter> = AtomicPtr::new(ptr::null_mut());
+
+/// Installs the reporter which is notified of mismatches, instead of panicking.
+///
+/// The reporter can only be installed once per process. Fails if a reporter is already installed.
+pub fn set_reporter(reporter: &'static dyn Reporter) -> Result<(), SetReporterError> {
+    let reporter = Box::into_raw(Box::new(reporter));
+    match REPORTER.compare_exchange(
+        ptr::null_mut(),
+        reporter,
+        Ordering::AcqRel,
+        Ordering::Acquire,
+    ) {
+        Ok(_) => Ok(()),
+        Err(_) => {
+            // Safety: the pointer was created above from a `Box`, and was not published.
+            drop(unsafe { Box::from_raw(reporter) });
+            Err(SetReporterError(()))
+        }
+    }
+}
+
+/// The error returned by [`set_reporter`] when a reporter is already installed.
+#[derive(Debug)]
+pub struct SetReporterError(());
+
+impl fmt::Display for SetReporterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an encoded_len audit reporter is already installed")
+    }
+}
+
+impl std::error::Error for SetReporterError {}
+
+fn report(mismatch: &Mismatch<'_>) {
+    let reporter = REPORTER.load(Ordering::Acquire);
+    if reporter.is_null() {
+        panic!("{}", mismatch);
+    }
+    // Safety: a non-null pointer was published by `set_reporter`, and is never freed.
+    unsafe { (*reporter).mismatch(mismatch) }
+}
+
+struct Frame {
+    segment: Segment,
+    /// Whether a message nested in this one had a mismatch, which makes this one mismatch too.
+    nested_mismatch: bool,
+}
+
+std::thread_local! {
+    static PATH: RefCell<Vec<Frame>> = RefCell::new(Vec::new());
+}
+
+/// Pops the frame pushed for a message, even if encoding it panics.
+struct PopFrame;
+
+impl Drop for PopFrame {
+    fn drop(&mut self) {
+        PATH.with(|path| path.borrow_mut().pop());
+    }
+}
+
+/// Encodes the message with `encode_raw`, checking that `expected_len` bytes were written.
+pub(crate) fn encode<M, B>(tag: Option<u32>, msg: &M, expected_len: usize, buf: &mut B)
+where
+    M: Message,
+    B: BufMut,
+{
+    let segment = Segment {
+        tag,
+        message: core::any::type_name::<M>(),
+    };
+    PATH.with(|path| {
+        path.borrow_mut().push(Frame {
+            segment,
+            nested_mismatch: false,
+        })
+    });
+    let pop = PopFrame;
+
+    let remaining = buf.remaining_mut();
+    msg.encode_raw(buf);
+    let actual_len = remaining - buf.remaining_mut();
+
+    let mismatch = actual_len != expected_len;
+    let (nested_mismatch, path) = PATH.with(|path| {
+        let path = path.borrow();
+        let frames = path.as_slice();
+        let nested_mismatch = frames.last().map_or(false, |frame| frame.nested_mismatch);
+        let segments = if mismatch && !nested_mismatch {
+            frames.iter().map(|frame| frame.segment).collect()
+        } else {
+            Vec::new()
+        };
+        (nested_mismatch, segments)
+    });
+    drop(pop);
+
+    if mismatch || nested_mismatch {
+        PATH.with(|path| {
+            if let Some(parent) = path.borrow_mut().last_mut() {
+                parent.nested_mismatch = true;
+            }
+        });
+    }
+    if mismatch && !nested_mismatch {
+        report(&Mismatch {
+            path: &path,
+            expected: expected_len,
+            actual: actual_len,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::format;
+    use std::string::{String, ToString};
+
+    use bytes::Buf;
+
+    use crate::encoding::{self, DecodeContext, WireType};
+    use crate::DecodeError;
+
+    std::thread_local! {
+        static MISMATCHES: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    }
+
+    struct Recorder;
+
+    impl Reporter for Recorder {
+        fn mismatch(&self, mismatch: &Mismatch<'_>) {
+            MISMATCHES.with(|mismatches| mismatches.borrow_mut().push(mismatch.to_string()));
+        }
+    }
+
+    /// A message holding a string, whose `encoded_len` is off by `error`.
+    #[derive(Debug, Default)]
+    struct Leaf {
+        value: String,
+        error: usize,
+    }
+
+    /// A message holding a `Leaf` as a message and as a group.
+    #[derive(Debug, Default)]
+    struct Branch {
+        message: Option<Leaf>,
+        group: Option<Leaf>,
+    }
+
+    impl Message for Leaf {
+        fn encode_raw<B>(&self, buf: &mut B)
+        where
+            B: BufMut,
+        {
+            encoding::string::encode(1, &self.value, buf);
+        }
+
+        fn merge_field<B>(
+            &mut self,
+            tag: u32,
+            wire_type: WireType,
+            buf: &mut B,
+            ctx: DecodeContext,
+        ) -> Result<(), DecodeError>
+        where
+            B: Buf,
+        {
+            encoding::skip_field(wire_type, tag, buf, ctx)
+        }
+
+        fn encoded_len(&self) -> usize {
+            encoding::string::encoded_len(1, &self.value) + self.error
+        }
+
+        fn clear(&mut self) {
+            self.value.clear();
+        }
+    }
+
+    impl Message for Branch {
+        fn encode_raw<B>(&self, buf: &mut B)
+        where
+            B: BufMut,
+        {
+            if let Some(message) = &self.message {
+                encoding::message::encode(2, message, buf);
+            }
+            if let Some(group) = &self.group {
+                encoding::group::encode(3, group, buf);
+            }
+        }
+
+        fn merge_field<B>(
+            &mut self,
+            tag: u32,
+            wire_type: WireType,
+            buf: &mut B,
+            ctx: DecodeContext,
+        ) -> Result<(), DecodeError>
+        where
+            B: Buf,
+        {
+            encoding::skip_field(wire_type, tag, buf, ctx)
+        }
+
+        fn encoded_len(&self) -> usize {
+            self.message
+                .as_ref()
+                .map_or(0, |message| encoding::message::encoded_len(2, message))
+                + self
+                    .group
+                    .as_ref()
+                    .map_or(0, |group| encoding::group::encoded_len(3, group))
+        }
+
+        fn clear(&mut self) {
+            self.message = None;
+            self.group = None;
+        }
+    }
+
+    fn leaf(error: usize) -> Leaf {
+        Leaf {
+            value: "leaf".to_string(),
+            error,
+        }
+    }
+
+    fn record<F>(f: F) -> Vec<String>
+    where
+        F: FnOnce(),
+    {
+        let _ = set_reporter(&Recorder);
+        MISMATCHES.with(|mismatches| mismatches.borrow_mut().clear());
+        f();
+        MISMATCHES.with(|mismatches| mismatches.borrow_mut().split_off(0))
+    }
+
+    #[test]
+    fn consistent() {
+        let branch = Branch {
+            message: Some(leaf(0)),
+            group: Some(leaf(0)),
+        };
+        let mismatches = record(|| {
+            branch.encode_to_vec();
+            branch.encode_length_delimited_to_vec();
+        });
+        assert!(mismatches.is_empty(), "{:?}", mismatches);
+    }
+
+    #[test]
+    fn mismatches() {
+        let leaf_name = core::any::type_name::<Leaf>();
+        let branch_name = core::any::type_name::<Branch>();
+
+        let mismatches = record(|| {
+            leaf(2).encode_to_vec();
+        });
+        assert_eq!(
+            mismatches,
+            [format!(
+                "`encoded_len` returned 8 but 6 bytes were encoded, at {}",
+                leaf_name
+            )]
+        );
+
+        // Only the faulty message is reported, not the messages containing it.
+        let branch = Branch {
+            message: Some(leaf(0)),
+            group: Some(leaf(1)),
+        };
+        let mismatches = record(|| {
+            let mut buf = Vec::new();
+            branch.encode(&mut buf).unwrap();
+        });
+        assert_eq!(
+            mismatches,
+            [format!(
+                "`encoded_len` returned 7 but 6 bytes were encoded, at {} > 3: {}",
+                branch_name, leaf_name
+            )]
+        );
+
+        let branch = Branch {
+            message: Some(leaf(3)),
+            group: None,
+        };
+        let mismatches = record(|| {
+            let mut buf = Vec::new();
+            branch.encode_length_delimited(&mut buf).unwrap();
+        });
+        assert_eq!(
+            mismatches,
+            [format!(
+                "`encoded_len` returned 9 but 6 bytes were encoded, at {} > 2: {}",
+                branch_name, leaf_name
+            )]
+        );
+    }
+}
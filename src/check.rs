@@ -0,0 +1,44 @@
+//! Checking the values of custom field types before encoding.
+
+use core::num::{NonZeroU32, NonZeroU64};
+
+use crate::{InvalidValueError, StringAdapter};
+
+/// A custom field type which can reject its value when a message is encoded with
+/// [`Message::encode_checked`](crate::Message::encode_checked).
+///
+/// The implementations derived by `prost-derive` call [`check_encode`](CheckEncode::check_encode)
+/// for the value of every field mapped to a custom scalar type: `string` fields held in a
+/// [`StringAdapter`], which forwards to [`StringAdapter::check_encode`], and `uint32` and `uint64`
+/// fields held in a `char` or a non-zero integer, whose values are valid by construction.
+pub trait CheckEncode {
+    /// Checks that the value can be encoded, returning an error describing the reason otherwise.
+    fn check_encode(&self) -> Result<(), InvalidValueError>;
+}
+
+impl<T> CheckEncode for T
+where
+    T: StringAdapter,
+{
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        StringAdapter::check_encode(self)
+    }
+}
+
+impl CheckEncode for char {
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        Ok(())
+    }
+}
+
+impl CheckEncode for NonZeroU32 {
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        Ok(())
+    }
+}
+
+impl CheckEncode for NonZeroU64 {
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        Ok(())
+    }
+}
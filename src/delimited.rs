@@ -0,0 +1,492 @@
+//! Length-delimited encoding without computing the encoded length up front.
+//!
+//! [`Message::encode_length_delimited`] calls [`Message::encoded_len`] to write the length
+//! delimiter before the message. For very large messages, computing the length can cost as much
+//! as encoding them. The functions here instead encode the message first, and write the length
+//! once it is known:
+//!
+//!  * [`LengthPlaceholder`] reserves a fixed-width length delimiter in an in-memory buffer, and
+//!    patches it after the message has been appended.
+//!  * [`DelimitedWriter::write_message_seekable`] does the same with a seekable `std::io` sink,
+//!    streaming the message to the sink in chunks.
+//!  * [`DelimitedWriter::write_message`] supports sinks which can't seek, by encoding the message
+//!    into a list of chunks, counting their length, and writing the delimiter followed by the
+//!    chunks.
+//!
+//! Patched delimiters are always [`PLACEHOLDER_LEN`] bytes long, padding the varint with
+//! continuation bytes. Protobuf decoders, including [`Message::decode_length_delimited`] and
+//! [`decode_length_delimiter`][crate::decode_length_delimiter], accept padded varints, but the
+//! output is not byte-for-byte identical to `encode_length_delimited`.
+//!
+//! Nested messages are still prefixed with their encoded length, so this only avoids the
+//! top-level length computation.
+
+use bytes::BufMut;
+
+use crate::Message;
+
+/// The length of a length delimiter written by [`LengthPlaceholder`] and
+/// [`DelimitedWriter::write_message_seekable`].
+///
+/// A five byte varint holds lengths up to 2<sup>35</sup> - 1, well beyond the 2 GiB limit on
+/// Protobuf messages.
+pub const PLACEHOLDER_LEN: usize = 5;
+
+/// The largest length which fits in a placeholder.
+const MAX_PLACEHOLDER_LENGTH: u64 = (1 << (7 * PLACEHOLDER_LEN)) - 1;
+
+/// A length delimiter reserved in a buffer, to be written once the data following it is known.
+///
+/// # Example
+///
+/// ```rust
+/// # use prost::delimited::LengthPlaceholder;
+/// # use prost::Message;
+/// # #[derive(Clone, PartialEq, Message)]
+/// # struct Event {
+/// #     #[prost(string, tag = "1")]
+/// #     name: String,
+/// # }
+/// # let event = Event { name: "started".to_string() };
+/// let mut buf = Vec::new();
+/// let placeholder = LengthPlaceholder::reserve(&mut buf);
+/// event.encode_raw(&mut buf);
+/// placeholder.patch(&mut buf);
+///
+/// assert_eq!(Event::decode_length_delimited(&buf[..]).unwrap(), event);
+/// ```
+#[derive(Debug)]
+#[must_use = "the placeholder must be patched once the data has been written"]
+pub struct LengthPlaceholder {
+    offset: usize,
+}
+
+impl LengthPlaceholder {
+    /// Appends a placeholder for a length delimiter to the buffer.
+    pub fn reserve<B>(buf: &mut B) -> LengthPlaceholder
+    where
+        B: BufMut + AsRef<[u8]>,
+    {
+        let offset = buf.as_ref().len();
+        buf.put_slice(&[0; PLACEHOLDER_LEN]);
+        LengthPlaceholder { offset }
+    }
+
+    /// Writes the length of the data appended to the buffer since the placeholder was reserved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is not the one the placeholder was reserved in, or if more than
+    /// 2<sup>35</sup> - 1 bytes have been appended.
+    pub fn patch<B>(self, buf: &mut B)
+    where
+        B: AsMut<[u8]>,
+    {
+        let buf = buf.as_mut();
+        let start = self.offset + PLACEHOLDER_LEN;
+        assert!(
+            buf.len() >= start,
+            "buffer is shorter than the reserved placeholder"
+        );
+        let length = (buf.len() - start) as u64;
+        assert!(
+            length <= MAX_PLACEHOLDER_LENGTH,
+            "length {} does not fit in a placeholder",
+            length
+        );
+        buf[self.offset..start].copy_from_slice(&padded_varint(length));
+    }
+}
+
+/// Encodes the message with a length delimiter, appending it to the buffer, without calling
+/// [`Message::encoded_len`] on the message.
+///
+/// See [`LengthPlaceholder`].
+pub fn encode_length_delimited_patched<M, B>(message: &M, buf: &mut B)
+where
+    M: Message,
+    B: BufMut + AsRef<[u8]> + AsMut<[u8]>,
+{
+    let placeholder = LengthPlaceholder::reserve(buf);
+    message.encode_raw(buf);
+    placeholder.patch(buf);
+}
+
+/// Encodes the length as a varint of exactly [`PLACEHOLDER_LEN`] bytes.
+fn padded_varint(length: u64) -> [u8; PLACEHOLDER_LEN] {
+    let mut bytes = [0; PLACEHOLDER_LEN];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (length >> (7 * i)) as u8 & 0x7F;
+        if i + 1 < PLACEHOLDER_LEN {
+            *byte |= 0x80;
+        }
+    }
+    bytes
+}
+
+#[cfg(feature = "std")]
+pub(crate) use self::writer::write_chunked;
+#[cfg(feature = "std")]
+pub use self::writer::DelimitedWriter;
+
+#[cfg(feature = "std")]
+mod writer {
+    use super::*;
+
+    use std::io::{self, Seek, SeekFrom, Write};
+    use std::mem;
+    use std::vec::Vec;
+
+    use bytes::buf::UninitSlice;
+
+    use crate::encoding::{encode_varint, encoded_len_varint};
+
+    /// The default size of the chunks messages are encoded into.
+    const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+    /// A `BufMut` which fills fixed-size chunks, handing each full chunk to `flush`.
+    struct ChunkBuf<F> {
+        chunk: Vec<u8>,
+        len: usize,
+        flush: F,
+    }
+
+    impl<F> ChunkBuf<F>
+    where
+        F: FnMut(&mut Vec<u8>),
+    {
+        fn new(chunk_size: usize, flush: F) -> ChunkBuf<F> {
+            ChunkBuf {
+                chunk: Vec::with_capacity(chunk_size),
+                len: 0,
+                flush,
+            }
+        }
+
+        /// Flushes the last chunk, returning the total length of the data written.
+        fn finish(mut self) -> usize {
+            if !self.chunk.is_empty() {
+                (self.flush)(&mut self.chunk);
+            }
+            self.len
+        }
+    }
+
+    unsafe impl<F> BufMut for ChunkBuf<F>
+    where
+        F: FnMut(&mut Vec<u8>),
+    {
+        fn remaining_mut(&self) -> usize {
+            usize::MAX - self.len
+        }
+
+        unsafe fn advance_mut(&mut self, cnt: usize) {
+            self.chunk.advance_mut(cnt);
+            self.len += cnt;
+        }
+
+        fn chunk_mut(&mut self) -> &mut UninitSlice {
+            if self.chunk.len() == self.chunk.capacity() {
+                (self.flush)(&mut self.chunk);
+            }
+            self.chunk.chunk_mut()
+        }
+    }
+
+    /// Streams the message to `writer` one chunk at a time, returning its encoded length.
+    pub(crate) fn write_chunked<M, W>(
+        message: &M,
+        writer: &mut W,
+        chunk_size: usize,
+    ) -> io::Result<usize>
+    where
+        M: Message,
+        W: Write,
+    {
+        let mut error = None;
+        let mut buf = ChunkBuf::new(chunk_size, |chunk: &mut Vec<u8>| {
+            // `BufMut` can't fail, so the first error is kept and the rest of the message is
+            // discarded.
+            if error.is_none() {
+                if let Err(e) = writer.write_all(chunk) {
+                    error = Some(e);
+                }
+            }
+            chunk.clear();
+        });
+        message.encode_raw(&mut buf);
+        let len = buf.finish();
+        match error {
+            Some(error) => Err(error),
+            None => Ok(len),
+        }
+    }
+
+    /// Writes length-delimited messages to a `std::io` sink, without calling
+    /// [`Message::encoded_len`] on the messages.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use std::io::Cursor;
+    /// # use prost::delimited::DelimitedWriter;
+    /// # use prost::Message;
+    /// # #[derive(Clone, PartialEq, Message)]
+    /// # struct Event {
+    /// #     #[prost(string, tag = "1")]
+    /// #     name: String,
+    /// # }
+    /// # let event = Event { name: "started".to_string() };
+    /// let mut writer = DelimitedWriter::new(Cursor::new(Vec::new()));
+    /// writer.write_message_seekable(&event).unwrap();
+    /// writer.write_message(&event).unwrap();
+    ///
+    /// let mut buf = &writer.into_inner().into_inner()[..];
+    /// for _ in 0..2 {
+    ///     assert_eq!(Event::decode_length_delimited(&mut buf).unwrap(), event);
+    /// }
+    /// ```
+    #[derive(Debug)]
+    pub struct DelimitedWriter<W> {
+        inner: W,
+        chunk_size: usize,
+    }
+
+    impl<W> DelimitedWriter<W> {
+        /// Creates a writer which writes messages to `inner`.
+        pub fn new(inner: W) -> DelimitedWriter<W> {
+            DelimitedWriter {
+                inner,
+                chunk_size: DEFAULT_CHUNK_SIZE,
+            }
+        }
+
+        /// Sets the size of the chunks messages are encoded into before being written, which
+        /// defaults to 64 KiB.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `chunk_size` is zero.
+        pub fn with_chunk_size(mut self, chunk_size: usize) -> DelimitedWriter<W> {
+            assert!(chunk_size > 0, "chunk size must be positive");
+            self.chunk_size = chunk_size;
+            self
+        }
+
+        /// Returns a reference to the underlying sink.
+        pub fn get_ref(&self) -> &W {
+            &self.inner
+        }
+
+        /// Returns the underlying sink.
+        pub fn into_inner(self) -> W {
+            self.inner
+        }
+    }
+
+    impl<W> DelimitedWriter<W>
+    where
+        W: Write,
+    {
+        /// Writes the message with a length delimiter, returning the number of bytes written.
+        ///
+        /// The message is encoded into a list of chunks, which are written after the length
+        /// delimiter once the whole message has been encoded. The delimiter is a minimal varint,
+        /// so the output is identical to [`Message::encode_length_delimited`].
+        pub fn write_message<M>(&mut self, message: &M) -> io::Result<usize>
+        where
+            M: Message,
+        {
+            let chunk_size = self.chunk_size;
+            let mut chunks = Vec::new();
+            let mut buf = ChunkBuf::new(chunk_size, |chunk: &mut Vec<u8>| {
+                chunks.push(mem::replace(chunk, Vec::with_capacity(chunk_size)));
+            });
+            message.encode_raw(&mut buf);
+            let len = buf.finish();
+
+            let mut delimiter = Vec::with_capacity(encoded_len_varint(len as u64));
+            encode_varint(len as u64, &mut delimiter);
+            self.inner.write_all(&delimiter)?;
+            for chunk in &chunks {
+                self.inner.write_all(chunk)?;
+            }
+            Ok(delimiter.len() + len)
+        }
+    }
+
+    impl<W> DelimitedWriter<W>
+    where
+        W: Write + Seek,
+    {
+        /// Writes the message with a length delimiter, returning the number of bytes written.
+        ///
+        /// A placeholder for the delimiter is written first, and the message is streamed to the
+        /// sink one chunk at a time. The sink is then rewound to write the delimiter, which is
+        /// [`PLACEHOLDER_LEN`] bytes long, and left positioned after the message.
+        pub fn write_message_seekable<M>(&mut self, message: &M) -> io::Result<usize>
+        where
+            M: Message,
+        {
+            let start = self.inner.stream_position()?;
+            self.inner.write_all(&[0; PLACEHOLDER_LEN])?;
+
+            let len = write_chunked(message, &mut self.inner, self.chunk_size)?;
+            if len as u64 > MAX_PLACEHOLDER_LENGTH {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "message is too long for a length placeholder",
+                ));
+            }
+            let end = self.inner.stream_position()?;
+            self.inner.seek(SeekFrom::Start(start))?;
+            self.inner.write_all(&padded_varint(len as u64))?;
+            self.inner.seek(SeekFrom::Start(end))?;
+            Ok(PLACEHOLDER_LEN + len)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::String;
+    use alloc::vec;
+    use bytes::{Buf, BytesMut};
+
+    use crate::encoding::{self, DecodeContext, WireType};
+    use crate::DecodeError;
+
+    /// A message holding a single string field, with a deliberately broken `encoded_len` to show
+    /// it isn't called.
+    #[derive(Debug, Default, PartialEq)]
+    struct Text {
+        value: String,
+    }
+
+    impl Message for Text {
+        fn encode_raw<B>(&self, buf: &mut B)
+        where
+            B: BufMut,
+        {
+            encoding::string::encode(1, &self.value, buf);
+        }
+
+        fn merge_field<B>(
+            &mut self,
+            tag: u32,
+            wire_type: WireType,
+            buf: &mut B,
+            ctx: DecodeContext,
+        ) -> Result<(), DecodeError>
+        where
+            B: Buf,
+        {
+            if tag == 1 {
+                encoding::string::merge(wire_type, &mut self.value, buf, ctx)
+            } else {
+                encoding::skip_field(wire_type, tag, buf, ctx)
+            }
+        }
+
+        fn encoded_len(&self) -> usize {
+            panic!("encoded_len called")
+        }
+
+        fn clear(&mut self) {
+            self.value.clear();
+        }
+    }
+
+    fn text(len: usize) -> Text {
+        Text {
+            value: (0..len).map(|i| (b'a' + (i % 26) as u8) as char).collect(),
+        }
+    }
+
+    #[test]
+    fn padded_varints() {
+        assert_eq!(padded_varint(0), [0x80, 0x80, 0x80, 0x80, 0x00]);
+        assert_eq!(padded_varint(300), [0xAC, 0x82, 0x80, 0x80, 0x00]);
+        assert_eq!(
+            padded_varint(MAX_PLACEHOLDER_LENGTH),
+            [0xFF, 0xFF, 0xFF, 0xFF, 0x7F]
+        );
+        for &length in &[0, 1, 127, 128, 300, 1 << 21, MAX_PLACEHOLDER_LENGTH] {
+            let decoded = crate::decode_length_delimiter(&padded_varint(length)[..]).unwrap();
+            assert_eq!(decoded as u64, length);
+        }
+    }
+
+    #[test]
+    fn placeholder() {
+        for &len in &[0, 1, 200, 100_000] {
+            let message = text(len);
+
+            let mut buf = vec![1, 2, 3];
+            encode_length_delimited_patched(&message, &mut buf);
+            assert_eq!(&buf[..3], [1, 2, 3]);
+            assert_eq!(Text::decode_length_delimited(&buf[3..]).unwrap(), message);
+
+            let mut buf = BytesMut::new();
+            encode_length_delimited_patched(&message, &mut buf);
+            assert_eq!(Text::decode_length_delimited(buf).unwrap(), message);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer is shorter than the reserved placeholder")]
+    fn placeholder_wrong_buffer() {
+        let placeholder = LengthPlaceholder::reserve(&mut vec![0; 10]);
+        placeholder.patch(&mut vec![0; 12]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn writer() {
+        use std::io::Cursor;
+
+        let messages = [text(0), text(10), text(1000), text(5000)];
+        let mut seekable = DelimitedWriter::new(Cursor::new(Vec::new())).with_chunk_size(7);
+        let mut plain = DelimitedWriter::new(Vec::new()).with_chunk_size(7);
+        for message in &messages {
+            let len = message.value.len();
+            let encoded_len = 1 + crate::length_delimiter_len(len) + len;
+            assert_eq!(
+                seekable.write_message_seekable(message).unwrap(),
+                PLACEHOLDER_LEN + encoded_len
+            );
+            assert_eq!(
+                plain.write_message(message).unwrap(),
+                crate::length_delimiter_len(encoded_len) + encoded_len
+            );
+        }
+
+        let seekable = seekable.into_inner().into_inner();
+        let plain = plain.into_inner();
+        let mut seekable_buf = &seekable[..];
+        let mut plain_buf = &plain[..];
+        for message in &messages {
+            assert_eq!(
+                &Text::decode_length_delimited(&mut seekable_buf).unwrap(),
+                message
+            );
+            assert_eq!(
+                &Text::decode_length_delimited(&mut plain_buf).unwrap(),
+                message
+            );
+        }
+        assert!(seekable_buf.is_empty());
+        assert!(plain_buf.is_empty());
+
+        // Without seeking, the delimiter is minimal, as written by `encode_length_delimited`.
+        let message = text(1000);
+        let mut expected = Vec::new();
+        crate::encode_length_delimiter(1003, &mut expected).unwrap();
+        message.encode_raw(&mut expected);
+        let mut writer = DelimitedWriter::new(Vec::new());
+        assert_eq!(writer.write_message(&message).unwrap(), expected.len());
+        assert_eq!(writer.into_inner(), expected);
+    }
+}
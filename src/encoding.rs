@@ -195,9 +195,9 @@ pub struct DecodeContext {
     /// How many times we can recurse in the current decode stack before we hit
     /// the recursion limit.
     ///
-    /// The recursion limit is defined by `RECURSION_LIMIT` and cannot be
-    /// customized. The recursion limit can be ignored by building the Prost
-    /// crate with the `no-recursion-limit` feature.
+    /// The recursion limit defaults to `RECURSION_LIMIT`, and can be raised with
+    /// `Message::decode_with_recursion_limit`. The recursion limit can be ignored
+    /// by building the Prost crate with the `no-recursion-limit` feature.
     #[cfg(not(feature = "no-recursion-limit"))]
     recurse_count: u32,
 }
@@ -213,6 +213,21 @@ impl Default for DecodeContext {
 }
 
 impl DecodeContext {
+    /// Creates a context which allows `limit` levels of nested messages and groups.
+    #[cfg(not(feature = "no-recursion-limit"))]
+    #[inline]
+    pub(crate) fn with_recursion_limit(limit: u32) -> DecodeContext {
+        DecodeContext {
+            recurse_count: limit,
+        }
+    }
+
+    #[cfg(feature = "no-recursion-limit")]
+    #[inline]
+    pub(crate) fn with_recursion_limit(_limit: u32) -> DecodeContext {
+        DecodeContext {}
+    }
+
     /// Call this function before recursively decoding.
     ///
     /// There is no `exit` function since this function creates a new `DecodeContext`
@@ -264,7 +279,7 @@ pub fn encoded_len_varint(value: u64) -> usize {
     ((((value | 1).leading_zeros() ^ 63) * 9 + 73) / 64) as usize
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum WireType {
     Varint = 0,
@@ -343,10 +358,7 @@ pub fn key_len(tag: u32) -> usize {
 #[inline]
 pub fn check_wire_type(expected: WireType, actual: WireType) -> Result<(), DecodeError> {
     if expected != actual {
-        return Err(DecodeError::new(format!(
-            "invalid wire type: {:?} (expected {:?})",
-            actual, expected
-        )));
+        return Err(DecodeError::wire_type(expected, actual));
     }
     Ok(())
 }
@@ -395,29 +407,201 @@ where
         WireType::ThirtyTwoBit => 4,
         WireType::SixtyFourBit => 8,
         WireType::LengthDelimited => decode_varint(buf)?,
-        WireType::StartGroup => loop {
-            let (inner_tag, inner_wire_type) = decode_key(buf)?;
-            match inner_wire_type {
-                WireType::EndGroup => {
-                    if inner_tag != tag {
-                        return Err(DecodeError::new("unexpected end group tag"));
-                    }
-                    break 0;
+        WireType::StartGroup => return skip_group(tag, buf, ctx),
+        WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
+    };
+
+    if len > buf.remaining() as u64 {
+        return Err(DecodeError::new("buffer underflow"));
+    }
+
+    buf.advance(len as usize);
+    Ok(())
+}
+
+/// Skips the remainder of a group, including any nested groups.
+///
+/// Nested groups are tracked on the heap rather than by recursion, so that a long run of start
+/// group tags fails with the recursion limit error rather than overflowing the stack.
+fn skip_group<B>(tag: u32, buf: &mut B, ctx: DecodeContext) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    let mut groups = Vec::new();
+    groups.push((tag, ctx));
+    while let Some((tag, ctx)) = groups.last().cloned() {
+        let (inner_tag, inner_wire_type) = decode_key(buf)?;
+        match inner_wire_type {
+            WireType::EndGroup => {
+                if inner_tag != tag {
+                    return Err(DecodeError::new("unexpected end group tag"));
                 }
-                _ => skip_field(inner_wire_type, inner_tag, buf, ctx.enter_recursion())?,
+                groups.pop();
+            }
+            WireType::StartGroup => {
+                let ctx = ctx.enter_recursion();
+                ctx.limit_reached()?;
+                groups.push((inner_tag, ctx));
             }
-        },
+            _ => skip_field(inner_wire_type, inner_tag, buf, ctx.enter_recursion())?,
+        }
+    }
+    Ok(())
+}
+
+/// Skips a field, like [`skip_field`], returning the raw bytes of its value.
+///
+/// This allows a field to be extracted by tag without decoding it, for example to forward an
+/// embedded message as is, or to decode it later. The captured bytes are:
+///
+///  * For a length-delimited field, the contents following the length delimiter, so that an
+///    embedded message can be decoded directly from them. These are not copied when `buf` is a
+///    `Bytes`.
+///  * For a group, the encoded fields within the group, excluding the end group tag, so that the
+///    group can be decoded as a message.
+///  * For other fields, the encoded value.
+///
+/// Varints, including the keys of the fields within a group, are captured in their canonical
+/// encoding, which is the one produced by encoders.
+pub fn skip_and_capture<B>(
+    wire_type: WireType,
+    tag: u32,
+    buf: &mut B,
+    ctx: DecodeContext,
+) -> Result<Bytes, DecodeError>
+where
+    B: Buf,
+{
+    ctx.limit_reached()?;
+    let mut captured = Vec::new();
+    match wire_type {
+        WireType::LengthDelimited => {
+            let len = decode_varint(buf)?;
+            if len > buf.remaining() as u64 {
+                return Err(DecodeError::new("buffer underflow"));
+            }
+            return Ok(buf.copy_to_bytes(len as usize));
+        }
+        WireType::StartGroup => capture_group(tag, buf, ctx, &mut captured)?,
         WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
+        _ => capture_value(wire_type, buf, &mut captured)?,
+    }
+    Ok(captured.into())
+}
+
+/// Copies the value of a field other than a group to `captured`, including the length delimiter
+/// of a length-delimited value.
+fn capture_value<B>(
+    wire_type: WireType,
+    buf: &mut B,
+    captured: &mut Vec<u8>,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    let len = match wire_type {
+        WireType::Varint => {
+            encode_varint(decode_varint(buf)?, captured);
+            return Ok(());
+        }
+        WireType::ThirtyTwoBit => 4,
+        WireType::SixtyFourBit => 8,
+        WireType::LengthDelimited => {
+            let len = decode_varint(buf)?;
+            encode_varint(len, captured);
+            len
+        }
+        WireType::StartGroup | WireType::EndGroup => unreachable!(),
     };
 
     if len > buf.remaining() as u64 {
         return Err(DecodeError::new("buffer underflow"));
     }
+    captured.put(buf.take(len as usize));
+    Ok(())
+}
 
-    buf.advance(len as usize);
+/// Copies the remainder of a group to `captured`, like [`skip_group`] skips it, excluding the end
+/// group tag of the outermost group.
+fn capture_group<B>(
+    tag: u32,
+    buf: &mut B,
+    ctx: DecodeContext,
+    captured: &mut Vec<u8>,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    let mut groups = Vec::new();
+    groups.push((tag, ctx));
+    while let Some((tag, ctx)) = groups.last().cloned() {
+        let (inner_tag, inner_wire_type) = decode_key(buf)?;
+        match inner_wire_type {
+            WireType::EndGroup => {
+                if inner_tag != tag {
+                    return Err(DecodeError::new("unexpected end group tag"));
+                }
+                groups.pop();
+                if !groups.is_empty() {
+                    encode_key(inner_tag, inner_wire_type, captured);
+                }
+            }
+            WireType::StartGroup => {
+                let ctx = ctx.enter_recursion();
+                ctx.limit_reached()?;
+                groups.push((inner_tag, ctx));
+                encode_key(inner_tag, inner_wire_type, captured);
+            }
+            _ => {
+                ctx.enter_recursion().limit_reached()?;
+                encode_key(inner_tag, inner_wire_type, captured);
+                capture_value(inner_wire_type, buf, captured)?;
+            }
+        }
+    }
     Ok(())
 }
 
+/// Runs `decode`, which decodes a nested message or group.
+///
+/// With the `stacker` feature, `decode` runs on a newly allocated segment of stack when the
+/// remaining stack space is low, so that deeply nested messages which are within the recursion
+/// limit can't overflow the stack.
+#[inline]
+fn with_stack<R>(decode: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "stacker")]
+    {
+        stacker::maybe_grow(64 * 1024, 1024 * 1024, decode)
+    }
+    #[cfg(not(feature = "stacker"))]
+    {
+        decode()
+    }
+}
+
+/// Encodes the message without a length delimiter.
+///
+/// With the `audit-encoded-len` feature, the number of bytes written is checked against
+/// `encoded_len`, which is only called in that case. `tag` is the tag of the field holding the
+/// message, if any.
+#[inline]
+pub(crate) fn encode_raw<M, B, F>(tag: Option<u32>, msg: &M, encoded_len: F, buf: &mut B)
+where
+    M: Message,
+    B: BufMut,
+    F: FnOnce() -> usize,
+{
+    #[cfg(feature = "audit-encoded-len")]
+    {
+        crate::audit::encode(tag, msg, encoded_len(), buf)
+    }
+    #[cfg(not(feature = "audit-encoded-len"))]
+    {
+        let _ = (tag, encoded_len);
+        msg.encode_raw(buf)
+    }
+}
+
 /// Helper macro which emits an `encode_repeated` function for the type.
 macro_rules! encode_repeated {
     ($ty:ty) => {
@@ -596,6 +780,203 @@ to_uint64(value) {
 from_uint64(value) {
     ((value >> 1) as i64) ^ (-((value & 1) as i64))
 });
+// A `uint32` field mapped to `char`. Values which are not Unicode scalar values fail to decode.
+varint!(char, char,
+to_uint64(value) {
+    *value as u64
+},
+from_uint64(value) {
+    u32::try_from(value)
+        .ok()
+        .and_then(core::char::from_u32)
+        .ok_or_else(|| DecodeError::new("invalid char value"))?
+});
+
+/// Macro which emits a module containing a set of encoding functions for a `uint32` or `uint64`
+/// field mapped to a non-zero integer type.
+///
+/// Non-zero types have no default value, so singular fields are merged into an `Option`. Decoding
+/// a zero value fails.
+macro_rules! nonzero_varint {
+    ($ty:ty, $inner:ty, $proto_ty:ident) => {
+        pub mod $proto_ty {
+            use crate::encoding::*;
+
+            pub fn encode<B>(tag: u32, value: &$ty, buf: &mut B)
+            where
+                B: BufMut,
+            {
+                encode_key(tag, WireType::Varint, buf);
+                encode_varint(value.get() as u64, buf);
+            }
+
+            pub fn merge<B>(
+                wire_type: WireType,
+                value: &mut Option<$ty>,
+                buf: &mut B,
+                _ctx: DecodeContext,
+            ) -> Result<(), DecodeError>
+            where
+                B: Buf,
+            {
+                check_wire_type(WireType::Varint, wire_type)?;
+                *value = Some(decode(buf)?);
+                Ok(())
+            }
+
+            fn decode<B>(buf: &mut B) -> Result<$ty, DecodeError>
+            where
+                B: Buf,
+            {
+                let value = decode_varint(buf)? as $inner;
+                <$ty>::new(value).ok_or_else(|| DecodeError::new("invalid zero value"))
+            }
+
+            encode_repeated!($ty);
+
+            pub fn encode_packed<B>(tag: u32, values: &[$ty], buf: &mut B)
+            where
+                B: BufMut,
+            {
+                if values.is_empty() {
+                    return;
+                }
+
+                encode_key(tag, WireType::LengthDelimited, buf);
+                let len: usize = values
+                    .iter()
+                    .map(|value| encoded_len_varint(value.get() as u64))
+                    .sum();
+                encode_varint(len as u64, buf);
+
+                for value in values {
+                    encode_varint(value.get() as u64, buf);
+                }
+            }
+
+            pub fn merge_repeated<B>(
+                wire_type: WireType,
+                values: &mut Vec<$ty>,
+                buf: &mut B,
+                ctx: DecodeContext,
+            ) -> Result<(), DecodeError>
+            where
+                B: Buf,
+            {
+                if wire_type == WireType::LengthDelimited {
+                    // Packed.
+                    merge_loop(values, buf, ctx, |values, buf, _ctx| {
+                        values.push(decode(buf)?);
+                        Ok(())
+                    })
+                } else {
+                    // Unpacked.
+                    check_wire_type(WireType::Varint, wire_type)?;
+                    values.push(decode(buf)?);
+                    Ok(())
+                }
+            }
+
+            #[inline]
+            pub fn encoded_len(tag: u32, value: &$ty) -> usize {
+                key_len(tag) + encoded_len_varint(value.get() as u64)
+            }
+
+            #[inline]
+            pub fn encoded_len_repeated(tag: u32, values: &[$ty]) -> usize {
+                key_len(tag) * values.len()
+                    + values
+                        .iter()
+                        .map(|value| encoded_len_varint(value.get() as u64))
+                        .sum::<usize>()
+            }
+
+            #[inline]
+            pub fn encoded_len_packed(tag: u32, values: &[$ty]) -> usize {
+                if values.is_empty() {
+                    0
+                } else {
+                    let len = values
+                        .iter()
+                        .map(|value| encoded_len_varint(value.get() as u64))
+                        .sum::<usize>();
+                    key_len(tag) + encoded_len_varint(len as u64) + len
+                }
+            }
+
+            #[cfg(test)]
+            mod test {
+                use proptest::prelude::*;
+
+                use super::*;
+
+                proptest! {
+                    #[test]
+                    fn check(value in 1..=<$inner>::MAX, tag in MIN_TAG..=MAX_TAG) {
+                        let value = <$ty>::new(value).unwrap();
+                        let mut buf = Vec::new();
+                        encode(tag, &value, &mut buf);
+                        prop_assert_eq!(buf.len(), encoded_len(tag, &value));
+
+                        let mut buf = buf.as_slice();
+                        let (decoded_tag, wire_type) = decode_key(&mut buf)?;
+                        prop_assert_eq!(decoded_tag, tag);
+                        let mut decoded = None;
+                        merge(wire_type, &mut decoded, &mut buf, DecodeContext::default())?;
+                        prop_assert_eq!(decoded, Some(value));
+                        prop_assert!(buf.is_empty());
+                    }
+
+                    #[test]
+                    fn check_packed(values in prop::collection::vec(1..=<$inner>::MAX, 0..10),
+                                    tag in MIN_TAG..=MAX_TAG) {
+                        let values = values
+                            .into_iter()
+                            .map(|value| <$ty>::new(value).unwrap())
+                            .collect::<Vec<_>>();
+                        let mut buf = Vec::new();
+                        encode_packed(tag, &values, &mut buf);
+                        prop_assert_eq!(buf.len(), encoded_len_packed(tag, &values));
+
+                        let mut decoded = Vec::new();
+                        let mut buf = buf.as_slice();
+                        if !values.is_empty() {
+                            let (_, wire_type) = decode_key(&mut buf)?;
+                            merge_repeated(wire_type, &mut decoded, &mut buf,
+                                           DecodeContext::default())?;
+                        }
+                        prop_assert_eq!(decoded, values);
+                    }
+                }
+
+                #[test]
+                fn zero() {
+                    let mut value = None;
+                    merge(
+                        WireType::Varint,
+                        &mut value,
+                        &mut &[0u8][..],
+                        DecodeContext::default(),
+                    )
+                    .expect_err("decoding zero succeeded");
+                    assert_eq!(value, None);
+
+                    let mut values = Vec::new();
+                    merge_repeated(
+                        WireType::LengthDelimited,
+                        &mut values,
+                        &mut &[2u8, 1, 0][..],
+                        DecodeContext::default(),
+                    )
+                    .expect_err("decoding packed zero succeeded");
+                }
+            }
+        }
+    };
+}
+
+nonzero_varint!(core::num::NonZeroU32, u32, nonzero_uint32);
+nonzero_varint!(core::num::NonZeroU64, u64, nonzero_uint64);
 
 /// Macro which emits a module containing a set of encoding functions for a
 /// fixed width numeric type.
@@ -873,6 +1254,78 @@ pub mod string {
     }
 }
 
+/// Encoding of `string` fields held in a [`StringAdapter`] other than `String`.
+pub mod string_adapter {
+    use super::*;
+
+    use crate::StringAdapter;
+
+    pub fn encode<A, B>(tag: u32, value: &A, buf: &mut B)
+    where
+        A: StringAdapter,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value.as_bytes());
+    }
+
+    pub fn merge<A, B>(
+        wire_type: WireType,
+        value: &mut A,
+        buf: &mut B,
+        _ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        A: StringAdapter,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        if len > buf.remaining() as u64 {
+            return Err(DecodeError::new("buffer underflow"));
+        }
+        value.replace_with(buf.take(len as usize))
+    }
+
+    length_delimited!(impl StringAdapter);
+
+    #[cfg(test)]
+    mod test {
+        use alloc::borrow::Cow;
+
+        use proptest::prelude::*;
+
+        use super::super::test::{check_collection_type, check_type};
+        use super::*;
+        use crate::ByteStr;
+
+        proptest! {
+            #[test]
+            fn check_cow(value: String, tag in MIN_TAG..=MAX_TAG) {
+                let value = Cow::<'static, str>::Owned(value);
+                super::test::check_type::<Cow<'static, str>, Cow<'static, str>>(
+                    value, tag, WireType::LengthDelimited, encode, merge, encoded_len)?;
+            }
+
+            #[test]
+            fn check_byte_str(value: String, tag in MIN_TAG..=MAX_TAG) {
+                let value = ByteStr::from(value);
+                super::test::check_type::<ByteStr, ByteStr>(value, tag, WireType::LengthDelimited,
+                                                            encode, merge, encoded_len)?;
+            }
+
+            #[test]
+            fn check_repeated_byte_str(value: Vec<String>, tag in MIN_TAG..=MAX_TAG) {
+                let value = value.into_iter().map(ByteStr::from).collect();
+                super::test::check_collection_type(value, tag, WireType::LengthDelimited,
+                                                   encode_repeated, merge_repeated,
+                                                   encoded_len_repeated)?;
+            }
+        }
+    }
+}
+
 pub trait BytesAdapter: sealed::BytesAdapter {}
 
 mod sealed {
@@ -1061,8 +1514,9 @@ pub mod message {
         B: BufMut,
     {
         encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(msg.encoded_len() as u64, buf);
-        msg.encode_raw(buf);
+        let len = msg.encoded_len();
+        encode_varint(len as u64, buf);
+        encode_raw(Some(tag), msg, || len, buf);
     }
 
     pub fn merge<M, B>(
@@ -1077,15 +1531,17 @@ pub mod message {
     {
         check_wire_type(WireType::LengthDelimited, wire_type)?;
         ctx.limit_reached()?;
-        merge_loop(
-            msg,
-            buf,
-            ctx.enter_recursion(),
-            |msg: &mut M, buf: &mut B, ctx| {
-                let (tag, wire_type) = decode_key(buf)?;
-                msg.merge_field(tag, wire_type, buf, ctx)
-            },
-        )
+        with_stack(|| {
+            merge_loop(
+                msg,
+                buf,
+                ctx.enter_recursion(),
+                |msg: &mut M, buf: &mut B, ctx| {
+                    let (tag, wire_type) = decode_key(buf)?;
+                    msg.merge_field(tag, wire_type, buf, ctx)
+                },
+            )
+        })
     }
 
     pub fn encode_repeated<M, B>(tag: u32, messages: &[M], buf: &mut B)
@@ -1147,7 +1603,7 @@ pub mod group {
         B: BufMut,
     {
         encode_key(tag, WireType::StartGroup, buf);
-        msg.encode_raw(buf);
+        encode_raw(Some(tag), msg, || msg.encoded_len(), buf);
         encode_key(tag, WireType::EndGroup, buf);
     }
 
@@ -1165,7 +1621,7 @@ pub mod group {
         check_wire_type(WireType::StartGroup, wire_type)?;
 
         ctx.limit_reached()?;
-        loop {
+        with_stack(|| loop {
             let (field_tag, field_wire_type) = decode_key(buf)?;
             if field_wire_type == WireType::EndGroup {
                 if field_tag != tag {
@@ -1175,7 +1631,7 @@ pub mod group {
             }
 
             M::merge_field(msg, field_tag, field_wire_type, buf, ctx.enter_recursion())?;
-        }
+        })
     }
 
     pub fn encode_repeated<M, B>(tag: u32, messages: &[M], buf: &mut B)
@@ -1420,6 +1876,205 @@ pub mod btree_map {
     map!(BTreeMap);
 }
 
+/// Generic protobuf map functions for any map type implementing [`ProstMap`].
+///
+/// Entries are encoded in the iteration order of the map, and inserted in the order they are
+/// decoded.
+pub mod prost_map {
+    use crate::encoding::*;
+    use crate::ProstMap;
+
+    /// Generic protobuf map encode function.
+    pub fn encode<M, K, V, B, KE, KL, VE, VL>(
+        key_encode: KE,
+        key_encoded_len: KL,
+        val_encode: VE,
+        val_encoded_len: VL,
+        tag: u32,
+        values: &M,
+        buf: &mut B,
+    ) where
+        M: ProstMap<K, V>,
+        for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Default + PartialEq,
+        V: Default + PartialEq,
+        B: BufMut,
+        KE: Fn(u32, &K, &mut B),
+        KL: Fn(u32, &K) -> usize,
+        VE: Fn(u32, &V, &mut B),
+        VL: Fn(u32, &V) -> usize,
+    {
+        encode_with_default(
+            key_encode,
+            key_encoded_len,
+            val_encode,
+            val_encoded_len,
+            &V::default(),
+            tag,
+            values,
+            buf,
+        )
+    }
+
+    /// Generic protobuf map merge function.
+    pub fn merge<M, K, V, B, KM, VM>(
+        key_merge: KM,
+        val_merge: VM,
+        values: &mut M,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        M: ProstMap<K, V>,
+        K: Default,
+        V: Default,
+        B: Buf,
+        KM: Fn(WireType, &mut K, &mut B, DecodeContext) -> Result<(), DecodeError>,
+        VM: Fn(WireType, &mut V, &mut B, DecodeContext) -> Result<(), DecodeError>,
+    {
+        merge_with_default(key_merge, val_merge, V::default(), values, buf, ctx)
+    }
+
+    /// Generic protobuf map encode function.
+    pub fn encoded_len<M, K, V, KL, VL>(
+        key_encoded_len: KL,
+        val_encoded_len: VL,
+        tag: u32,
+        values: &M,
+    ) -> usize
+    where
+        M: ProstMap<K, V>,
+        for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Default + PartialEq,
+        V: Default + PartialEq,
+        KL: Fn(u32, &K) -> usize,
+        VL: Fn(u32, &V) -> usize,
+    {
+        encoded_len_with_default(key_encoded_len, val_encoded_len, &V::default(), tag, values)
+    }
+
+    /// Generic protobuf map encode function with an overriden value default.
+    ///
+    /// This is necessary because enumeration values can have a default value other
+    /// than 0 in proto2.
+    pub fn encode_with_default<M, K, V, B, KE, KL, VE, VL>(
+        key_encode: KE,
+        key_encoded_len: KL,
+        val_encode: VE,
+        val_encoded_len: VL,
+        val_default: &V,
+        tag: u32,
+        values: &M,
+        buf: &mut B,
+    ) where
+        M: ProstMap<K, V>,
+        for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Default + PartialEq,
+        V: PartialEq,
+        B: BufMut,
+        KE: Fn(u32, &K, &mut B),
+        KL: Fn(u32, &K) -> usize,
+        VE: Fn(u32, &V, &mut B),
+        VL: Fn(u32, &V) -> usize,
+    {
+        let key_default = K::default();
+        for (key, val) in values {
+            let skip_key = key == &key_default;
+            let skip_val = val == val_default;
+
+            let len = (if skip_key { 0 } else { key_encoded_len(1, key) })
+                + (if skip_val { 0 } else { val_encoded_len(2, val) });
+
+            encode_key(tag, WireType::LengthDelimited, buf);
+            encode_varint(len as u64, buf);
+            if !skip_key {
+                key_encode(1, key, buf);
+            }
+            if !skip_val {
+                val_encode(2, val, buf);
+            }
+        }
+    }
+
+    /// Generic protobuf map merge function with an overriden value default.
+    ///
+    /// This is necessary because enumeration values can have a default value other
+    /// than 0 in proto2.
+    pub fn merge_with_default<M, K, V, B, KM, VM>(
+        key_merge: KM,
+        val_merge: VM,
+        val_default: V,
+        values: &mut M,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        M: ProstMap<K, V>,
+        K: Default,
+        B: Buf,
+        KM: Fn(WireType, &mut K, &mut B, DecodeContext) -> Result<(), DecodeError>,
+        VM: Fn(WireType, &mut V, &mut B, DecodeContext) -> Result<(), DecodeError>,
+    {
+        let mut key = Default::default();
+        let mut val = val_default;
+        ctx.limit_reached()?;
+        merge_loop(
+            &mut (&mut key, &mut val),
+            buf,
+            ctx.enter_recursion(),
+            |&mut (ref mut key, ref mut val), buf, ctx| {
+                let (tag, wire_type) = decode_key(buf)?;
+                match tag {
+                    1 => key_merge(wire_type, key, buf, ctx),
+                    2 => val_merge(wire_type, val, buf, ctx),
+                    _ => skip_field(wire_type, tag, buf, ctx),
+                }
+            },
+        )?;
+        values.insert(key, val);
+
+        Ok(())
+    }
+
+    /// Generic protobuf map encode function with an overriden value default.
+    ///
+    /// This is necessary because enumeration values can have a default value other
+    /// than 0 in proto2.
+    pub fn encoded_len_with_default<M, K, V, KL, VL>(
+        key_encoded_len: KL,
+        val_encoded_len: VL,
+        val_default: &V,
+        tag: u32,
+        values: &M,
+    ) -> usize
+    where
+        M: ProstMap<K, V>,
+        for<'a> &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+        K: Default + PartialEq,
+        V: PartialEq,
+        KL: Fn(u32, &K) -> usize,
+        VL: Fn(u32, &V) -> usize,
+    {
+        let key_default = K::default();
+        key_len(tag) * values.len()
+            + values
+                .into_iter()
+                .map(|(key, val)| {
+                    let len = (if key == &key_default {
+                        0
+                    } else {
+                        key_encoded_len(1, key)
+                    }) + (if val == val_default {
+                        0
+                    } else {
+                        val_encoded_len(2, val)
+                    });
+                    encoded_len_varint(len as u64) + len
+                })
+                .sum::<usize>()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use alloc::string::ToString;
@@ -1680,6 +2335,63 @@ mod test {
         );
     }
 
+    #[test]
+    fn skip_and_capture_fields() {
+        let mut group = Vec::new();
+        int32::encode(5, &-1, &mut group);
+        encode_key(6, WireType::StartGroup, &mut group);
+        string::encode(7, &"nested".to_string(), &mut group);
+        encode_key(6, WireType::EndGroup, &mut group);
+
+        let mut buf = Vec::new();
+        uint64::encode(1, &150, &mut buf);
+        fixed32::encode(2, &7, &mut buf);
+        bytes::encode(3, &vec![1, 2, 3], &mut buf);
+        encode_key(4, WireType::StartGroup, &mut buf);
+        buf.extend_from_slice(&group);
+        encode_key(4, WireType::EndGroup, &mut buf);
+        sfixed64::encode(8, &-2, &mut buf);
+        let buf = Bytes::from(buf);
+
+        let mut remaining = buf.clone();
+        let mut captured = Vec::new();
+        while remaining.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut remaining).unwrap();
+            let value =
+                skip_and_capture(wire_type, tag, &mut remaining, DecodeContext::default()).unwrap();
+            captured.push((tag, value));
+        }
+
+        assert_eq!(captured[0], (1, Bytes::from_static(&[0x96, 0x01])));
+        assert_eq!(captured[1], (2, Bytes::from_static(&[7, 0, 0, 0])));
+        assert_eq!(captured[2], (3, Bytes::from_static(&[1, 2, 3])));
+        // Length-delimited values are sliced out of the buffer rather than copied.
+        assert!(buf.as_ptr_range().contains(&captured[2].1.as_ptr()));
+        assert_eq!(captured[3], (4, Bytes::from(group)));
+        assert_eq!(
+            captured[4],
+            (8, Bytes::copy_from_slice(&(-2i64).to_le_bytes()))
+        );
+        assert_eq!(captured.len(), 5);
+    }
+
+    #[test]
+    fn skip_and_capture_invalid() {
+        for (wire_type, mut buf) in [
+            (WireType::LengthDelimited, &[0x05, 0x01][..]),
+            (WireType::SixtyFourBit, &[0x01; 7][..]),
+            (WireType::StartGroup, &[0x14][..]),
+            (WireType::StartGroup, &[0x08, 0x01][..]),
+            (WireType::EndGroup, &[][..]),
+        ]
+        .iter()
+        .cloned()
+        {
+            skip_and_capture(wire_type, 1, &mut buf, DecodeContext::default())
+                .expect_err("invalid field captured");
+        }
+    }
+
     #[test]
     fn varint_overflow() {
         let mut u64_max_plus_one: &[u8] =
@@ -1690,6 +2402,84 @@ mod test {
             .expect_err("slow decoding u64::MAX + 1 succeeded");
     }
 
+    #[test]
+    fn float_bits_preserved() {
+        // Negative zero, subnormals, extremes and NaNs with non-canonical sign and payload bits
+        // must survive a round-trip unchanged, including through packed encoding.
+        let f32_bits = [
+            0x0000_0000,
+            0x8000_0000,
+            0x0000_0001,
+            0x8000_0001,
+            0x007F_FFFF,
+            0x0080_0000,
+            0x7F7F_FFFF,
+            0x7F80_0000,
+            0xFF80_0000,
+            0x7FC0_0000,
+            0xFFC0_0001,
+            0x7F80_0001,
+        ];
+        let values = f32_bits
+            .iter()
+            .map(|&b| f32::from_bits(b))
+            .collect::<Vec<_>>();
+        for &value in &values {
+            let mut buf = Vec::new();
+            float::encode(1, &value, &mut buf);
+            let mut buf = &buf[1..];
+            let mut decoded = 0.0f32;
+            float::merge(
+                WireType::ThirtyTwoBit,
+                &mut decoded,
+                &mut buf,
+                DecodeContext::default(),
+            )
+            .unwrap();
+            assert_eq!(decoded.to_bits(), value.to_bits());
+        }
+        let mut buf = Vec::new();
+        float::encode_packed(1, &values, &mut buf);
+        let mut buf = &buf[1..];
+        let mut decoded = Vec::new();
+        float::merge_repeated(
+            WireType::LengthDelimited,
+            &mut decoded,
+            &mut buf,
+            DecodeContext::default(),
+        )
+        .unwrap();
+        let decoded_bits = decoded.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(decoded_bits, f32_bits);
+
+        let f64_bits = [
+            0x0000_0000_0000_0000,
+            0x8000_0000_0000_0000,
+            0x0000_0000_0000_0001,
+            0x800F_FFFF_FFFF_FFFF,
+            0x7FEF_FFFF_FFFF_FFFF,
+            0xFFF0_0000_0000_0000,
+            0x7FF8_0000_0000_0000,
+            0xFFF8_0000_DEAD_BEEF,
+            0x7FF0_0000_0000_0001,
+        ];
+        for &bits in &f64_bits {
+            let value = f64::from_bits(bits);
+            let mut buf = Vec::new();
+            double::encode(1, &value, &mut buf);
+            let mut buf = &buf[1..];
+            let mut decoded = 0.0f64;
+            double::merge(
+                WireType::SixtyFourBit,
+                &mut decoded,
+                &mut buf,
+                DecodeContext::default(),
+            )
+            .unwrap();
+            assert_eq!(decoded.to_bits(), bits);
+        }
+    }
+
     /// This big bowl o' macro soup generates an encoding property test for each combination of map
     /// type, scalar map key, and value type.
     /// TODO: these tests take a long time to compile, can this be improved?
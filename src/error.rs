@@ -2,15 +2,24 @@
 
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 
 use core::fmt;
 
+use crate::encoding::WireType;
+
 /// A Protobuf message decoding error.
 ///
 /// `DecodeError` indicates that the input buffer does not contain a valid
 /// Protobuf message. The error details should be considered 'best effort': in
 /// general it is not possible to exactly pinpoint why data is malformed.
+///
+/// Besides its description, the error records where decoding failed: the
+/// offset in the input, the path of fields leading to the failing field, and
+/// the expected and actual wire types of a field encoded with the wrong wire
+/// type.
 #[derive(Clone, PartialEq, Eq)]
 pub struct DecodeError {
     inner: Box<Inner>,
@@ -20,10 +29,24 @@ pub struct DecodeError {
 struct Inner {
     /// A 'best effort' root cause description.
     description: Cow<'static, str>,
-    /// A stack of (message, field) name pairs, which identify the specific
-    /// message type and field where decoding failed. The stack contains an
-    /// entry per level of nesting.
-    stack: Vec<(&'static str, &'static str)>,
+    /// A stack of locations, which identify the specific message type and
+    /// field where decoding failed. The stack contains an entry per level of
+    /// nesting, starting with the innermost.
+    stack: Vec<Location>,
+    /// The number of bytes consumed from the input when the error was
+    /// detected.
+    offset: Option<usize>,
+    /// The expected and actual wire types of a field encoded with the wrong
+    /// wire type.
+    wire_types: Option<(WireType, WireType)>,
+}
+
+/// A (message, field) name pair, with the number of the field if it is known.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    message: &'static str,
+    field: &'static str,
+    number: Option<u32>,
 }
 
 impl DecodeError {
@@ -37,16 +60,113 @@ impl DecodeError {
             inner: Box::new(Inner {
                 description: description.into(),
                 stack: Vec::new(),
+                offset: None,
+                wire_types: None,
             }),
         }
     }
 
+    /// Creates a new `DecodeError` for a field encoded with the `actual` wire
+    /// type, rather than the `expected` one.
+    #[cold]
+    pub(crate) fn wire_type(expected: WireType, actual: WireType) -> DecodeError {
+        let mut error = DecodeError::new(format!(
+            "invalid wire type: {:?} (expected {:?})",
+            actual, expected
+        ));
+        error.inner.wire_types = Some((expected, actual));
+        error
+    }
+
     /// Pushes a (message, field) name location pair on to the location stack.
     ///
     /// Meant to be used only by `Message` implementations.
     #[doc(hidden)]
     pub fn push(&mut self, message: &'static str, field: &'static str) {
-        self.inner.stack.push((message, field));
+        self.inner.stack.push(Location {
+            message,
+            field,
+            number: None,
+        });
+    }
+
+    /// Pushes a (message, field) name location pair on to the location stack,
+    /// along with the number of the field.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn push_field(&mut self, message: &'static str, field: &'static str, number: u32) {
+        self.inner.stack.push(Location {
+            message,
+            field,
+            number: Some(number),
+        });
+    }
+
+    /// Records the number of bytes consumed from the input, unless an offset
+    /// was already recorded by a nested decoding.
+    pub(crate) fn set_offset(&mut self, offset: usize) {
+        if self.inner.offset.is_none() {
+            self.inner.offset = Some(offset);
+        }
+    }
+
+    /// Returns the 'best effort' root cause description, without the location
+    /// of the error.
+    pub fn description(&self) -> &str {
+        &self.inner.description
+    }
+
+    /// Returns the number of bytes which had been consumed from the input when
+    /// the error was detected, such as the offset following the key of a field
+    /// encoded with the wrong wire type.
+    ///
+    /// The offset is relative to the start of the buffer passed to
+    /// [`Message::decode`](crate::Message::decode) or the other decoding
+    /// methods of `Message`, and is `None` for errors which were not returned
+    /// by them, such as those of [`Message::check_required`](crate::Message::check_required).
+    pub fn offset(&self) -> Option<usize> {
+        self.inner.offset
+    }
+
+    /// Returns the path of the field where decoding failed, relative to the
+    /// decoded message, such as `items.name`.
+    pub fn field_path(&self) -> String {
+        let mut path = String::new();
+        for location in self.inner.stack.iter().rev() {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(location.field);
+        }
+        path
+    }
+
+    /// Returns the numbers of the fields leading to the field where decoding
+    /// failed, starting with the outermost, such as `[3, 1]` for the first
+    /// field of a message held by the third field of the decoded message.
+    ///
+    /// Locations pushed without a field number by `Message` implementations
+    /// which don't record them are skipped.
+    pub fn field_numbers(&self) -> Vec<u32> {
+        self.inner
+            .stack
+            .iter()
+            .rev()
+            .filter_map(|location| location.number)
+            .collect()
+    }
+
+    /// Returns the wire type a field was expected to be encoded with, if
+    /// decoding failed because it was encoded with another one.
+    pub fn expected_wire_type(&self) -> Option<WireType> {
+        self.inner.wire_types.map(|(expected, _)| expected)
+    }
+
+    /// Returns the wire type a field was encoded with, if decoding failed
+    /// because another one was expected.
+    pub fn actual_wire_type(&self) -> Option<WireType> {
+        self.inner.wire_types.map(|(_, actual)| actual)
     }
 }
 
@@ -55,6 +175,8 @@ impl fmt::Debug for DecodeError {
         f.debug_struct("DecodeError")
             .field("description", &self.inner.description)
             .field("stack", &self.inner.stack)
+            .field("offset", &self.inner.offset)
+            .field("wire_types", &self.inner.wire_types)
             .finish()
     }
 }
@@ -62,8 +184,8 @@ impl fmt::Debug for DecodeError {
 impl fmt::Display for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str("failed to decode Protobuf message: ")?;
-        for &(message, field) in &self.inner.stack {
-            write!(f, "{}.{}: ", message, field)?;
+        for location in &self.inner.stack {
+            write!(f, "{}.{}: ", location.message, location.field)?;
         }
         f.write_str(&self.inner.description)
     }
@@ -129,3 +251,153 @@ impl From<EncodeError> for std::io::Error {
         std::io::Error::new(std::io::ErrorKind::InvalidInput, error)
     }
 }
+
+/// A value which a custom field type can't encode.
+///
+/// `InvalidValueError` is returned by [`Message::check_encode`](crate::Message::check_encode)
+/// when a custom field type, such as a [`StringAdapter`](crate::StringAdapter) validating its
+/// contents, rejects the value of a field. Besides the description of the reason, the error
+/// records the path of the field holding the value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct InvalidValueError {
+    inner: Box<InvalidValue>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct InvalidValue {
+    /// A description of the reason the value was rejected.
+    description: Cow<'static, str>,
+    /// A stack of (message, field) name pairs, which identify the field
+    /// holding the value. The stack contains an entry per level of nesting,
+    /// starting with the innermost.
+    stack: Vec<(&'static str, &'static str)>,
+}
+
+impl InvalidValueError {
+    /// Creates a new `InvalidValueError` with a description of the reason
+    /// the value was rejected.
+    ///
+    /// Meant to be returned by the [`CheckEncode`](crate::CheckEncode)
+    /// implementations of custom field types.
+    #[cold]
+    pub fn new(description: impl Into<Cow<'static, str>>) -> InvalidValueError {
+        InvalidValueError {
+            inner: Box::new(InvalidValue {
+                description: description.into(),
+                stack: Vec::new(),
+            }),
+        }
+    }
+
+    /// Pushes a (message, field) name location pair on to the location stack.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn push(&mut self, message: &'static str, field: &'static str) {
+        self.inner.stack.push((message, field));
+    }
+
+    /// Returns the description of the reason the value was rejected.
+    pub fn description(&self) -> &str {
+        &self.inner.description
+    }
+
+    /// Returns the path of the field holding the rejected value, relative to
+    /// the checked message, such as `items.name`.
+    pub fn field_path(&self) -> String {
+        let mut path = String::new();
+        for &(_, field) in self.inner.stack.iter().rev() {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(field);
+        }
+        path
+    }
+}
+
+impl fmt::Debug for InvalidValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InvalidValueError")
+            .field("description", &self.inner.description)
+            .field("stack", &self.inner.stack)
+            .finish()
+    }
+}
+
+impl fmt::Display for InvalidValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("failed to encode Protobuf message: ")?;
+        for &(message, field) in &self.inner.stack {
+            write!(f, "{}.{}: ", message, field)?;
+        }
+        f.write_str(&self.inner.description)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for InvalidValueError {}
+
+#[cfg(feature = "std")]
+impl From<InvalidValueError> for std::io::Error {
+    fn from(error: InvalidValueError) -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, error)
+    }
+}
+
+/// An error encoding a message with
+/// [`Message::encode_checked`](crate::Message::encode_checked).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CheckedEncodeError {
+    /// The provided buffer had insufficient capacity.
+    Capacity(EncodeError),
+    /// A custom field type rejected its value.
+    InvalidValue(InvalidValueError),
+}
+
+impl From<EncodeError> for CheckedEncodeError {
+    fn from(error: EncodeError) -> CheckedEncodeError {
+        CheckedEncodeError::Capacity(error)
+    }
+}
+
+impl From<InvalidValueError> for CheckedEncodeError {
+    fn from(error: InvalidValueError) -> CheckedEncodeError {
+        CheckedEncodeError::InvalidValue(error)
+    }
+}
+
+impl fmt::Display for CheckedEncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckedEncodeError::Capacity(error) => error.fmt(f),
+            CheckedEncodeError::InvalidValue(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheckedEncodeError {}
+
+#[cfg(feature = "std")]
+impl From<CheckedEncodeError> for std::io::Error {
+    fn from(error: CheckedEncodeError) -> std::io::Error {
+        match error {
+            CheckedEncodeError::Capacity(error) => error.into(),
+            CheckedEncodeError::InvalidValue(error) => error.into(),
+        }
+    }
+}
+
+/// An error converting a number to an enumeration which has no variant with the number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnknownEnumValue(pub i32);
+
+impl fmt::Display for UnknownEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown enumeration value {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownEnumValue {}
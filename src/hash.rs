@@ -0,0 +1,407 @@
+//! Canonical hashing and equality of messages.
+//!
+//! Generated messages derive `PartialEq`, but not `Hash` or `Eq`: `HashMap` fields do not
+//! implement `Hash`, and `f32` and `f64` fields implement neither. Messages deriving
+//! [`CanonicalHash`], which `prost-build` adds with `Config::derive(".", "::prost::CanonicalHash")`,
+//! can instead be compared and hashed canonically, and wrapped in a [`Canonical`] to be used as
+//! the keys of a `HashMap` or `HashSet`, for example to deduplicate messages or to cache results
+//! by request.
+//!
+//! Canonical hashing and equality differ from the derived `PartialEq` in two ways:
+//!
+//!  * The entries of `map` fields are hashed in the order of their keys, so that equal maps hash
+//!    equally whatever the type and iteration order of the map.
+//!  * Floating point values are compared by their bits, so that a `NaN` is equal to itself, as
+//!    `Eq` requires, and `0.0` is not equal to `-0.0`.
+//!
+//! The values are fed to the hasher without any `usize`, so that a hasher with fixed keys gives
+//! the same hash on every platform, and in every run of the program.
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+use bytes::Bytes;
+
+use crate::string::ByteStr;
+use crate::{OpenEnum, PresenceBits};
+
+/// Hashing and equality of a message by its contents.
+///
+/// Derive it with `#[derive(CanonicalHash)]` on messages and oneofs whose fields implement it,
+/// which hashes and compares the fields in declaration order, except the `#[prost(skip)]` fields,
+/// which are not part of the message.
+pub trait CanonicalHash {
+    /// Feeds the contents of the value into the hasher.
+    fn canonical_hash<H: Hasher>(&self, state: &mut H);
+
+    /// Returns `true` if the values have the same contents.
+    ///
+    /// Values which are equal must have the same canonical hash.
+    fn canonical_eq(&self, other: &Self) -> bool;
+}
+
+/// A wrapper implementing `Hash` and `Eq` through the [`CanonicalHash`] of the value.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Canonical<T>(pub T);
+
+impl<T> Canonical<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Canonical<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: CanonicalHash> Hash for Canonical<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.canonical_hash(state);
+    }
+}
+
+impl<T: CanonicalHash> PartialEq for Canonical<T> {
+    fn eq(&self, other: &Canonical<T>) -> bool {
+        self.0.canonical_eq(&other.0)
+    }
+}
+
+impl<T: CanonicalHash> Eq for Canonical<T> {}
+
+/// Implements `CanonicalHash` for types whose `Hash` and `Eq` are already canonical.
+macro_rules! hash_eq {
+    ($($ty:ty),*) => {
+        $(
+            impl CanonicalHash for $ty {
+                fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+                    Hash::hash(self, state);
+                }
+
+                fn canonical_eq(&self, other: &$ty) -> bool {
+                    self == other
+                }
+            }
+        )*
+    };
+}
+
+hash_eq!(bool, u8, i32, i64, u32, u64);
+
+/// Feeds a string or a byte string into the hasher, prefixed with its length as a `u64`, rather
+/// than through `Hash`, which writes a `usize` length or a terminator.
+fn hash_bytes<H: Hasher>(bytes: &[u8], state: &mut H) {
+    state.write_u64(bytes.len() as u64);
+    state.write(bytes);
+}
+
+/// Implements `CanonicalHash` for string types, which dereference to `str`.
+macro_rules! hash_str {
+    ($($ty:ty),*) => {
+        $(
+            impl CanonicalHash for $ty {
+                fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+                    hash_bytes(self.as_bytes(), state);
+                }
+
+                fn canonical_eq(&self, other: &$ty) -> bool {
+                    self == other
+                }
+            }
+        )*
+    };
+}
+
+hash_str!(str, String, Cow<'static, str>, ByteStr);
+
+#[cfg(feature = "smol_str")]
+hash_str!(smol_str::SmolStr);
+
+impl CanonicalHash for Bytes {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        hash_bytes(self, state);
+    }
+
+    fn canonical_eq(&self, other: &Bytes) -> bool {
+        self == other
+    }
+}
+
+impl<const N: usize> CanonicalHash for PresenceBits<N> {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        for word in self.words() {
+            state.write_u64(*word);
+        }
+    }
+
+    fn canonical_eq(&self, other: &PresenceBits<N>) -> bool {
+        self == other
+    }
+}
+
+impl<E: CanonicalHash> CanonicalHash for OpenEnum<E> {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            OpenEnum::Known(value) => {
+                state.write_u8(0);
+                value.canonical_hash(state);
+            }
+            OpenEnum::Unknown(value) => {
+                state.write_u8(1);
+                state.write_i32(*value);
+            }
+        }
+    }
+
+    fn canonical_eq(&self, other: &OpenEnum<E>) -> bool {
+        match (self, other) {
+            (OpenEnum::Known(a), OpenEnum::Known(b)) => a.canonical_eq(b),
+            (OpenEnum::Unknown(a), OpenEnum::Unknown(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl CanonicalHash for f32 {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u32(self.to_bits());
+    }
+
+    fn canonical_eq(&self, other: &f32) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl CanonicalHash for f64 {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.to_bits());
+    }
+
+    fn canonical_eq(&self, other: &f64) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl<T: CanonicalHash + ?Sized> CanonicalHash for Box<T> {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).canonical_hash(state);
+    }
+
+    fn canonical_eq(&self, other: &Box<T>) -> bool {
+        (**self).canonical_eq(other)
+    }
+}
+
+impl<T: CanonicalHash> CanonicalHash for Option<T> {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => state.write_u8(0),
+            Some(value) => {
+                state.write_u8(1);
+                value.canonical_hash(state);
+            }
+        }
+    }
+
+    fn canonical_eq(&self, other: &Option<T>) -> bool {
+        match (self, other) {
+            (None, None) => true,
+            (Some(a), Some(b)) => a.canonical_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl<T: CanonicalHash> CanonicalHash for Vec<T> {
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.len() as u64);
+        for value in self {
+            value.canonical_hash(state);
+        }
+    }
+
+    fn canonical_eq(&self, other: &Vec<T>) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.canonical_eq(b))
+    }
+}
+
+/// Hashes the entries of a map in the order of their keys.
+#[cfg(any(feature = "std", feature = "indexmap"))]
+fn hash_entries<'a, K, V, I, H>(entries: I, state: &mut H)
+where
+    K: CanonicalHash + Ord + 'a,
+    V: CanonicalHash + 'a,
+    I: IntoIterator<Item = (&'a K, &'a V)>,
+    H: Hasher,
+{
+    let mut entries = entries.into_iter().collect::<Vec<_>>();
+    entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    state.write_u64(entries.len() as u64);
+    for (key, value) in entries {
+        key.canonical_hash(state);
+        value.canonical_hash(state);
+    }
+}
+
+impl<K, V> CanonicalHash for BTreeMap<K, V>
+where
+    K: CanonicalHash + Ord,
+    V: CanonicalHash,
+{
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.len() as u64);
+        for (key, value) in self {
+            key.canonical_hash(state);
+            value.canonical_hash(state);
+        }
+    }
+
+    fn canonical_eq(&self, other: &BTreeMap<K, V>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other)
+                .all(|(a, b)| a.0 == b.0 && a.1.canonical_eq(b.1))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> CanonicalHash for std::collections::HashMap<K, V, S>
+where
+    K: CanonicalHash + Eq + Hash + Ord,
+    V: CanonicalHash,
+    S: core::hash::BuildHasher,
+{
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        hash_entries(self, state);
+    }
+
+    fn canonical_eq(&self, other: &std::collections::HashMap<K, V, S>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, a)| other.get(key).is_some_and(|b| a.canonical_eq(b)))
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> CanonicalHash for indexmap::IndexMap<K, V, S>
+where
+    K: CanonicalHash + Eq + Hash + Ord,
+    V: CanonicalHash,
+    S: core::hash::BuildHasher,
+{
+    fn canonical_hash<H: Hasher>(&self, state: &mut H) {
+        hash_entries(self, state);
+    }
+
+    fn canonical_eq(&self, other: &indexmap::IndexMap<K, V, S>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, a)| other.get(key).is_some_and(|b| a.canonical_eq(b)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::{HashMap, HashSet};
+
+    fn hash<T: CanonicalHash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.canonical_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// A hasher which records the bytes it is fed.
+    #[derive(Default)]
+    struct Recorder(Vec<u8>);
+
+    impl Hasher for Recorder {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.extend_from_slice(bytes);
+        }
+    }
+
+    fn record<T: CanonicalHash + ?Sized>(value: &T) -> Vec<u8> {
+        let mut recorder = Recorder::default();
+        value.canonical_hash(&mut recorder);
+        recorder.0
+    }
+
+    #[test]
+    fn byte_sequences() {
+        let mut expected = 2u64.to_ne_bytes().to_vec();
+        expected.extend_from_slice(b"ab");
+        assert_eq!(record("ab"), expected);
+        assert_eq!(record(&String::from("ab")), expected);
+        assert_eq!(record(&Cow::Borrowed("ab")), expected);
+        assert_eq!(record(&ByteStr::from_static("ab")), expected);
+        assert_eq!(record(&Bytes::from_static(b"ab")), expected);
+
+        assert_eq!(record(""), 0u64.to_ne_bytes());
+        assert_eq!(record(&7u32), 7u32.to_ne_bytes());
+
+        let mut bits = PresenceBits::<2>::new();
+        bits.set(64);
+        let mut expected = 0u64.to_ne_bytes().to_vec();
+        expected.extend_from_slice(&1u64.to_ne_bytes());
+        assert_eq!(record(&bits), expected);
+    }
+
+    #[test]
+    fn map_order() {
+        let a = (0..100)
+            .map(|i| (i, format!("{}", i)))
+            .collect::<HashMap<i32, String>>();
+        let mut b = HashMap::with_capacity(1000);
+        b.extend((0..100).rev().map(|i| (i, format!("{}", i))));
+        let c = a.clone().into_iter().collect::<BTreeMap<_, _>>();
+
+        assert!(a.canonical_eq(&b));
+        assert_eq!(hash(&a), hash(&b));
+        assert_eq!(hash(&a), hash(&c));
+
+        b.insert(100, "100".to_string());
+        assert!(!a.canonical_eq(&b));
+        b.remove(&100);
+        b.insert(99, "-99".to_string());
+        assert!(!a.canonical_eq(&b));
+    }
+
+    #[test]
+    fn floats() {
+        assert!(f64::NAN.canonical_eq(&f64::NAN));
+        assert_eq!(hash(&f32::NAN), hash(&f32::NAN));
+        assert!(!0.0f64.canonical_eq(&-0.0));
+        assert!(!0.0f32.canonical_eq(&-0.0));
+        assert!(1.5f64.canonical_eq(&1.5));
+    }
+
+    #[test]
+    fn canonical() {
+        let mut set = HashSet::new();
+        assert!(set.insert(Canonical(vec![Some(1.0f64), None])));
+        assert!(!set.insert(Canonical(vec![Some(1.0f64), None])));
+        assert!(set.insert(Canonical(vec![None, Some(1.0f64)])));
+        assert!(set.insert(Canonical(vec![Some(f64::NAN)])));
+        assert!(!set.insert(Canonical(vec![Some(f64::NAN)])));
+        assert_eq!(set.len(), 3);
+    }
+}
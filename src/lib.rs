@@ -9,15 +9,47 @@ pub extern crate alloc;
 #[doc(hidden)]
 pub use bytes;
 
+// Re-export the smol_str crate for use within derived code.
+#[cfg(feature = "smol_str")]
+#[doc(hidden)]
+pub use smol_str;
+
+mod check;
 mod error;
 mod message;
+mod name;
+mod open_enum;
 mod types;
 
+#[cfg(feature = "audit-encoded-len")]
+pub mod audit;
+pub mod delimited;
+pub mod hash;
+pub mod map;
+pub mod pool;
+pub mod presence;
+pub mod recursive;
+pub mod string;
+pub mod validate;
+pub mod wire;
+
 #[doc(hidden)]
 pub mod encoding;
 
-pub use crate::error::{DecodeError, EncodeError};
+pub use crate::check::CheckEncode;
+pub use crate::error::{
+    CheckedEncodeError, DecodeError, EncodeError, InvalidValueError, UnknownEnumValue,
+};
+pub use crate::hash::{Canonical, CanonicalHash};
+pub use crate::map::ProstMap;
 pub use crate::message::Message;
+pub use crate::name::Name;
+pub use crate::open_enum::OpenEnum;
+pub use crate::pool::MessagePool;
+pub use crate::presence::PresenceBits;
+pub use crate::string::{ByteStr, StringAdapter};
+pub use crate::validate::{Validate, ValidationError};
+pub use crate::wire::validate_wire_format;
 
 use bytes::{Buf, BufMut};
 
@@ -80,7 +112,26 @@ where
     Ok(length as usize)
 }
 
-// Re-export #[derive(Message, Enumeration, Oneof)].
+/// Includes the code generated by `prost-build` for a Protobuf package in the `OUT_DIR`.
+///
+/// The package is given by its name, such as `"foo.bar"`, which is the name of the generated file
+/// without the `.rs` extension.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// pub mod items {
+///     prost::include_proto!("snazzy.items");
+/// }
+/// ```
+#[macro_export]
+macro_rules! include_proto {
+    ($package:literal) => {
+        include!(concat!(env!("OUT_DIR"), "/", $package, ".rs"));
+    };
+}
+
+// Re-export #[derive(Message, Enumeration, Oneof, CanonicalHash)].
 // Based on serde's equivalent re-export [1], but enabled by default.
 //
 // [1]: https://github.com/serde-rs/serde/blob/v1.0.89/serde/src/lib.rs#L245-L256
@@ -0,0 +1,274 @@
+//! Alternative Rust types for Protobuf `map` fields.
+//!
+//! Generated messages hold `map` fields in a `HashMap` by default, or in a `BTreeMap` for fields
+//! matched by `prost-build`'s `Config::btree_map`. Fields matched by `Config::map_type` can
+//! instead use any type implementing [`ProstMap`]:
+//!
+//!  * `IndexMap`, which preserves the order in which entries were inserted or decoded, with the
+//!    `indexmap` feature.
+//!  * Any other map type, such as a sorted `Vec` of entries, implementing [`ProstMap`].
+
+use core::borrow::Borrow;
+#[cfg(any(feature = "std", feature = "indexmap"))]
+use core::hash::BuildHasher;
+use core::hash::Hash;
+
+use alloc::collections::BTreeMap;
+
+/// A map type which can be used for Protobuf `map` fields, in place of `HashMap` or `BTreeMap`.
+///
+/// The entries of the map are read through its `&'a M: IntoIterator<Item = (&'a K, &'a V)>`
+/// implementation when encoding, in the iteration order of the map.
+pub trait ProstMap<K, V>: Default {
+    /// Returns the number of entries in the map.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the map has no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value corresponding to the key.
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord;
+
+    /// Inserts a key value pair into the map, returning the previous value of the key.
+    ///
+    /// Decoding inserts entries in the order they appear in the encoded message, and a key which
+    /// is already present must keep its position, as a repeated key replaces the previous value.
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    /// Removes all entries from the map.
+    fn clear(&mut self);
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> ProstMap<K, V> for std::collections::HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.get(key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+impl<K, V> ProstMap<K, V> for BTreeMap<K, V>
+where
+    K: Ord,
+{
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.get(key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> ProstMap<K, V> for indexmap::IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Default,
+{
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: ?Sized + Hash + Ord,
+    {
+        self.get(key)
+    }
+
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    fn clear(&mut self) {
+        self.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use crate::encoding::{btree_map, int32, prost_map, string, DecodeContext, WireType};
+
+    /// A map preserving insertion order, backed by a `Vec`.
+    #[derive(Debug, PartialEq)]
+    struct OrderedMap<K, V>(Vec<(K, V)>);
+
+    impl<K, V> Default for OrderedMap<K, V> {
+        fn default() -> OrderedMap<K, V> {
+            OrderedMap(Vec::new())
+        }
+    }
+
+    impl<K, V> ProstMap<K, V> for OrderedMap<K, V>
+    where
+        K: PartialEq,
+    {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: ?Sized + Hash + Ord,
+        {
+            self.0
+                .iter()
+                .find(|(k, _)| k.borrow() == key)
+                .map(|(_, v)| v)
+        }
+
+        fn insert(&mut self, key: K, value: V) -> Option<V> {
+            match self.0.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => Some(core::mem::replace(v, value)),
+                None => {
+                    self.0.push((key, value));
+                    None
+                }
+            }
+        }
+
+        fn clear(&mut self) {
+            self.0.clear()
+        }
+    }
+
+    impl<'a, K, V> IntoIterator for &'a OrderedMap<K, V> {
+        type Item = (&'a K, &'a V);
+        type IntoIter = core::iter::Map<core::slice::Iter<'a, (K, V)>, fn(&(K, V)) -> (&K, &V)>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.0.iter().map(|(k, v)| (k, v))
+        }
+    }
+
+    fn decode(mut buf: &[u8]) -> OrderedMap<String, i32> {
+        let mut map = OrderedMap::default();
+        while !buf.is_empty() {
+            let (tag, wire_type) = crate::encoding::decode_key(&mut buf).unwrap();
+            assert_eq!((tag, wire_type), (1, WireType::LengthDelimited));
+            prost_map::merge(
+                string::merge,
+                int32::merge,
+                &mut map,
+                &mut buf,
+                DecodeContext::default(),
+            )
+            .unwrap();
+        }
+        map
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut map = OrderedMap::default();
+        for (key, value) in [("zulu", 1), ("", 2), ("alpha", 0), ("mike", 3)].iter() {
+            map.insert(String::from(*key), *value);
+        }
+
+        let mut buf = Vec::new();
+        prost_map::encode(
+            string::encode,
+            string::encoded_len,
+            int32::encode,
+            int32::encoded_len,
+            1,
+            &map,
+            &mut buf,
+        );
+        assert_eq!(
+            buf.len(),
+            prost_map::encoded_len(string::encoded_len, int32::encoded_len, 1, &map)
+        );
+
+        // The entries are the same as those of a sorted map, in a different order.
+        let sorted = map.0.iter().cloned().collect::<BTreeMap<_, _>>();
+        assert_eq!(
+            buf.len(),
+            btree_map::encoded_len(string::encoded_len, int32::encoded_len, 1, &sorted)
+        );
+
+        let decoded = decode(&buf);
+        assert_eq!(decoded, map);
+        assert_eq!(decoded.get("alpha"), Some(&0));
+
+        // A repeated key replaces the value, keeping the position of the first entry.
+        let mut repeated = OrderedMap::default();
+        repeated.insert(String::from("zulu"), 5);
+        prost_map::encode(
+            string::encode,
+            string::encoded_len,
+            int32::encode,
+            int32::encoded_len,
+            1,
+            &repeated,
+            &mut buf,
+        );
+        let decoded = decode(&buf);
+        assert_eq!(decoded.0[0], (String::from("zulu"), 5));
+        assert_eq!(decoded.len(), 4);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn index_map() {
+        let mut map = indexmap::IndexMap::<String, i32>::new();
+        map.insert(String::from("zulu"), 1);
+        map.insert(String::from("alpha"), 2);
+
+        let mut buf = Vec::new();
+        prost_map::encode(
+            string::encode,
+            string::encoded_len,
+            int32::encode,
+            int32::encoded_len,
+            1,
+            &map,
+            &mut buf,
+        );
+        assert_eq!(decode(&buf).0, map.into_iter().collect::<Vec<_>>());
+    }
+}
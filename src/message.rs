@@ -1,4 +1,5 @@
 use alloc::boxed::Box;
+use alloc::format;
 use alloc::vec::Vec;
 
 use core::fmt::Debug;
@@ -7,10 +8,12 @@ use core::usize;
 use bytes::{Buf, BufMut};
 
 use crate::encoding::{
-    decode_key, encode_varint, encoded_len_varint, message, DecodeContext, WireType,
+    decode_key, encode_raw, encode_varint, encoded_len_varint, message, DecodeContext, WireType,
 };
+use crate::CheckedEncodeError;
 use crate::DecodeError;
 use crate::EncodeError;
+use crate::InvalidValueError;
 
 /// A Protocol Buffers message.
 pub trait Message: Debug + Send + Sync {
@@ -57,18 +60,35 @@ pub trait Message: Debug + Send + Sync {
             return Err(EncodeError::new(required, remaining));
         }
 
-        self.encode_raw(buf);
+        encode_raw(None, self, || required, buf);
         Ok(())
     }
 
+    /// Encodes the message to a buffer, failing if a field holds a value which its type can't
+    /// encode.
+    ///
+    /// The built-in field types encode every value, but custom field types, such as a
+    /// [`StringAdapter`](crate::StringAdapter) validating its contents, can reject values with
+    /// [`Message::check_encode`], which [`Message::encode`] doesn't call. Nothing is written to the
+    /// buffer if a value is rejected, or if the buffer does not have sufficient capacity.
+    fn encode_checked<B>(&self, buf: &mut B) -> Result<(), CheckedEncodeError>
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        self.check_encode()?;
+        Ok(self.encode(buf)?)
+    }
+
     /// Encodes the message to a newly allocated buffer.
     fn encode_to_vec(&self) -> Vec<u8>
     where
         Self: Sized,
     {
-        let mut buf = Vec::with_capacity(self.encoded_len());
+        let len = self.encoded_len();
+        let mut buf = Vec::with_capacity(len);
 
-        self.encode_raw(&mut buf);
+        encode_raw(None, self, || len, &mut buf);
         buf
     }
 
@@ -87,7 +107,7 @@ pub trait Message: Debug + Send + Sync {
             return Err(EncodeError::new(required, remaining));
         }
         encode_varint(len as u64, buf);
-        self.encode_raw(buf);
+        encode_raw(None, self, || len, buf);
         Ok(())
     }
 
@@ -100,10 +120,46 @@ pub trait Message: Debug + Send + Sync {
         let mut buf = Vec::with_capacity(len + encoded_len_varint(len as u64));
 
         encode_varint(len as u64, &mut buf);
-        self.encode_raw(&mut buf);
+        encode_raw(None, self, || len, &mut buf);
         buf
     }
 
+    /// Encodes the message to the start of a slice, returning the number of bytes written.
+    ///
+    /// This suits fixed-size frames and buffers shared over FFI, which don't implement `BufMut`
+    /// directly. The bytes of the slice past the message are left untouched. An error will be
+    /// returned if the slice is too short, in which case nothing is written.
+    fn encode_to_slice(&self, slice: &mut [u8]) -> Result<usize, EncodeError>
+    where
+        Self: Sized,
+    {
+        let len = self.encoded_len();
+        if len > slice.len() {
+            return Err(EncodeError::new(len, slice.len()));
+        }
+
+        encode_raw(None, self, || len, &mut &mut slice[..len]);
+        Ok(len)
+    }
+
+    /// Encodes the message to a `std::io` sink, returning the number of bytes written.
+    ///
+    /// The message is encoded into chunks of up to 8 KiB, each written to the sink with
+    /// [`Write::write_all`][std::io::Write::write_all] as soon as it is full, rather than into a
+    /// buffer holding the whole message. [`Message::encoded_len`] is not called on the message.
+    /// Wrap unbuffered sinks in a [`BufWriter`][std::io::BufWriter] if writes are costly.
+    ///
+    /// If writing fails, the rest of the message is discarded and the first error is returned.
+    /// Part of the message may already have been written.
+    #[cfg(feature = "std")]
+    fn encode_to_writer<W>(&self, mut writer: W) -> std::io::Result<usize>
+    where
+        W: std::io::Write,
+        Self: Sized,
+    {
+        crate::delimited::write_chunked(self, &mut writer, 8 * 1024)
+    }
+
     /// Decodes an instance of the message from a buffer.
     ///
     /// The entire buffer will be consumed.
@@ -127,6 +183,42 @@ pub trait Message: Debug + Send + Sync {
         Ok(message)
     }
 
+    /// Decodes a length-delimited instance of the message from the buffer, failing if any bytes
+    /// remain after it.
+    ///
+    /// [`Message::decode_length_delimited`] ignores the bytes following the message, which can
+    /// mask framing errors, such as a buffer holding several messages or a corrupted length
+    /// delimiter.
+    fn decode_exact<B>(mut buf: B) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let message = Self::decode_length_delimited(&mut buf)?;
+        if buf.has_remaining() {
+            return Err(DecodeError::new(format!(
+                "{} bytes remaining after length-delimited message",
+                buf.remaining()
+            )));
+        }
+        Ok(message)
+    }
+
+    /// Decodes a length-delimited instance of the message from the start of the buffer, returning
+    /// it along with the number of bytes it took, including the length delimiter.
+    ///
+    /// The bytes following the message are not read, so that the next message of a stream of
+    /// length-delimited messages starts at the returned offset.
+    fn decode_prefix<B>(mut buf: B) -> Result<(Self, usize), DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let remaining = buf.remaining();
+        let message = Self::decode_length_delimited(&mut buf)?;
+        Ok((message, remaining - buf.remaining()))
+    }
+
     /// Decodes an instance of the message from a buffer, and merges it into `self`.
     ///
     /// The entire buffer will be consumed.
@@ -135,12 +227,76 @@ pub trait Message: Debug + Send + Sync {
         B: Buf,
         Self: Sized,
     {
-        let ctx = DecodeContext::default();
-        while buf.has_remaining() {
-            let (tag, wire_type) = decode_key(&mut buf)?;
-            self.merge_field(tag, wire_type, &mut buf, ctx.clone())?;
-        }
-        Ok(())
+        merge_fields(self, &mut buf, DecodeContext::default())
+    }
+
+    /// Decodes an instance of the message from a buffer, replacing the contents of `self`.
+    ///
+    /// Unlike [`Message::merge`], fields missing from the buffer are reset to their default value
+    /// rather than kept, so the result equals that of [`Message::decode`]. The message is cleared
+    /// with [`Message::clear`] before decoding, so the buffers of its string, bytes, repeated and
+    /// map fields are reused: decoding messages of similar sizes into the same message, as in a
+    /// loop receiving a stream of messages, stops allocating for those fields once their capacity
+    /// is large enough. Message fields which are not `required`, oneofs, and the elements of
+    /// repeated message fields are still allocated as they are decoded.
+    ///
+    /// If decoding fails, the message is left partially decoded. The entire buffer will be
+    /// consumed.
+    fn merge_replace<B>(&mut self, buf: B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+        Self: Sized,
+    {
+        self.clear();
+        self.merge(buf)
+    }
+
+    /// Decodes an instance of the message from a buffer, allowing messages and groups to be nested
+    /// up to `limit` levels deep, rather than the default of 100.
+    ///
+    /// Raising the limit far beyond the default risks overflowing the stack while decoding, unless
+    /// the `stacker` feature is enabled. The limit is ignored when the `no-recursion-limit`
+    /// feature is enabled.
+    ///
+    /// The entire buffer will be consumed.
+    fn decode_with_recursion_limit<B>(mut buf: B, limit: u32) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let mut message = Self::default();
+        merge_fields(
+            &mut message,
+            &mut buf,
+            DecodeContext::with_recursion_limit(limit),
+        )?;
+        Ok(message)
+    }
+
+    /// Decodes an instance of the message from a buffer, and merges it into `self`, allowing
+    /// messages and groups to be nested up to `limit` levels deep.
+    ///
+    /// See [`Message::decode_with_recursion_limit`].
+    fn merge_with_recursion_limit<B>(&mut self, mut buf: B, limit: u32) -> Result<(), DecodeError>
+    where
+        B: Buf,
+        Self: Sized,
+    {
+        merge_fields(self, &mut buf, DecodeContext::with_recursion_limit(limit))
+    }
+
+    /// Decodes an instance of the message from a buffer, failing if a required field of the
+    /// message, or of a message nested in it, is missing.
+    ///
+    /// See [`Message::check_required`]. The entire buffer will be consumed.
+    fn decode_required<B>(buf: B) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let message = Self::decode(buf)?;
+        message.check_required()?;
+        Ok(message)
     }
 
     /// Decodes a length-delimited instance of the message from buffer, and
@@ -150,16 +306,82 @@ pub trait Message: Debug + Send + Sync {
         B: Buf,
         Self: Sized,
     {
+        let remaining = buf.remaining();
         message::merge(
             WireType::LengthDelimited,
             self,
             &mut buf,
             DecodeContext::default(),
         )
+        .map_err(|mut error| {
+            error.set_offset(remaining - buf.remaining());
+            error
+        })
     }
 
     /// Clears the message, resetting all fields to their default.
+    ///
+    /// The implementations derived by `prost-derive` clear string, bytes, repeated and map
+    /// fields, and `required` message fields, in place, without releasing their memory, except for
+    /// `BTreeMap` fields, which have no spare capacity. A message which is cleared and merged again,
+    /// such as a message reused by each iteration of a decoding loop, reuses those buffers instead
+    /// of allocating new ones. Optional message fields tracked with presence bits, as generated
+    /// for messages matched by `prost-build`'s `Config::presence_bits`, are also cleared in place,
+    /// keeping the allocations of the messages they hold. Other optional message fields, oneofs
+    /// and the elements of repeated fields are dropped, so clearing them takes time proportional
+    /// to the number of values they hold.
     fn clear(&mut self);
+
+    /// Returns `true` if each field of the message holds its default value, as after `clear`.
+    ///
+    /// The implementations derived by `prost-derive` check each field, including the presence
+    /// bits of the fields which have one, and ignore `#[prost(skip)]` fields. Floating point fields
+    /// are compared by bit pattern, so that a field holding `-0.0` is not default, as it is
+    /// encoded. The default implementation returns `true` if the message encodes to no bytes,
+    /// which is only equivalent for messages without `required` fields.
+    fn is_default(&self) -> bool {
+        self.encoded_len() == 0
+    }
+
+    /// Checks that the required fields of the message, and of the messages nested in it, are set,
+    /// returning an error naming the first missing field otherwise.
+    ///
+    /// Plain `decode` sets missing `required` fields to their default value. The presence of
+    /// required fields is only tracked by messages generated with `prost-build`'s
+    /// `Config::enforce_required`, for which the implementations derived by `prost-derive` check
+    /// the presence bit of each required field. The default implementation returns `Ok(())`.
+    fn check_required(&self) -> Result<(), DecodeError> {
+        Ok(())
+    }
+
+    /// Checks that the fields of the message, and of the messages nested in it, hold values which
+    /// their types can encode, returning an error naming the first rejected value otherwise.
+    ///
+    /// The implementations derived by `prost-derive` check the values of fields mapped to custom
+    /// scalar types with [`CheckEncode`](crate::CheckEncode), and the messages they embed. The
+    /// default implementation returns `Ok(())`.
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        Ok(())
+    }
+}
+
+fn merge_fields<M, B>(message: &mut M, buf: &mut B, ctx: DecodeContext) -> Result<(), DecodeError>
+where
+    M: Message,
+    B: Buf,
+{
+    let remaining = buf.remaining();
+    let mut merge = || {
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(buf)?;
+            message.merge_field(tag, wire_type, buf, ctx.clone())?;
+        }
+        Ok(())
+    };
+    merge().map_err(|mut error: DecodeError| {
+        error.set_offset(remaining - buf.remaining());
+        error
+    })
 }
 
 impl<M> Message for Box<M>
@@ -190,11 +412,103 @@ where
     fn clear(&mut self) {
         (**self).clear()
     }
+    fn is_default(&self) -> bool {
+        (**self).is_default()
+    }
+    fn check_required(&self) -> Result<(), DecodeError> {
+        (**self).check_required()
+    }
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        (**self).check_encode()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use alloc::string::{String, ToString};
+
     const _MESSAGE_IS_OBJECT_SAFE: Option<&dyn Message> = None;
+
+    #[test]
+    fn trailing_data() {
+        let mut buf = String::from("hello").encode_length_delimited_to_vec();
+        let len = buf.len();
+        assert_eq!(String::decode_exact(&buf[..]).unwrap(), "hello");
+        assert_eq!(
+            String::decode_prefix(&buf[..]).unwrap(),
+            ("hello".into(), len)
+        );
+
+        // A second message following the first one.
+        buf.extend(String::from("world").encode_length_delimited_to_vec());
+        assert_eq!(String::decode_length_delimited(&buf[..]).unwrap(), "hello");
+        assert_eq!(
+            String::decode_exact(&buf[..]).unwrap_err().to_string(),
+            "failed to decode Protobuf message: 8 bytes remaining after length-delimited message"
+        );
+        let (first, consumed) = String::decode_prefix(&buf[..]).unwrap();
+        assert_eq!((first.as_str(), consumed), ("hello", len));
+        let (second, _) = String::decode_prefix(&buf[consumed..]).unwrap();
+        assert_eq!(second, "world");
+
+        // A truncated message.
+        assert!(String::decode_prefix(&buf[..len - 1]).is_err());
+        assert!(String::decode_exact(&buf[..len - 1]).is_err());
+    }
+
+    #[test]
+    fn error_context() {
+        // A varint in place of the string, following a valid one.
+        let buf = b"\x0a\x02hi\x0a\x01!\x08\x01";
+        let error = String::decode(&buf[..]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "failed to decode Protobuf message: invalid wire type: Varint (expected LengthDelimited)"
+        );
+        assert_eq!(error.offset(), Some(8));
+        assert_eq!(error.expected_wire_type(), Some(WireType::LengthDelimited));
+        assert_eq!(error.actual_wire_type(), Some(WireType::Varint));
+        assert_eq!(error.field_path(), "");
+        assert!(error.field_numbers().is_empty());
+
+        let mut delimited = alloc::vec![buf.len() as u8];
+        delimited.extend_from_slice(buf);
+        let error = String::decode_length_delimited(&delimited[..]).unwrap_err();
+        assert_eq!(error.offset(), Some(9));
+
+        let error = String::decode(&b"\x0a\x05hi"[..]).unwrap_err();
+        assert_eq!(error.description(), "buffer underflow");
+        assert_eq!(error.offset(), Some(2));
+        assert_eq!(error.expected_wire_type(), None);
+    }
+
+    #[test]
+    fn encode_to_slice() {
+        let message = "hello".repeat(3000);
+        let encoded = message.encode_to_vec();
+
+        let mut slice = [0xFF; 16];
+        assert_eq!(String::from("hi").encode_to_slice(&mut slice).unwrap(), 4);
+        assert_eq!(slice[..5], [0x0A, 0x02, b'h', b'i', 0xFF]);
+
+        let mut slice = alloc::vec![0; encoded.len() - 1];
+        let error = message.encode_to_slice(&mut slice).unwrap_err();
+        assert_eq!(error.required_capacity(), encoded.len());
+        assert!(slice.iter().all(|&b| b == 0));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn encode_to_writer() {
+        let message = "hello".repeat(3000);
+        let mut writer = std::vec::Vec::new();
+        assert_eq!(message.encode_to_writer(&mut writer).unwrap(), 15003);
+        assert_eq!(writer, message.encode_to_vec());
+
+        let mut slice = [0; 10_000];
+        let error = message.encode_to_writer(&mut slice[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::WriteZero);
+    }
 }
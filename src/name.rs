@@ -0,0 +1,33 @@
+//! Support for associating type names with the [`Message`] trait.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::Message;
+
+/// Associates a Protobuf message type with its fully-qualified name.
+///
+/// Implementations are generated by `prost-build` when type names are enabled, and are used to
+/// build the type URLs of messages packed into `google.protobuf.Any`.
+pub trait Name: Message {
+    /// The simple name of the message, including the names of any enclosing messages separated by
+    /// `.`, but excluding the package.
+    const NAME: &'static str;
+
+    /// The Protobuf package containing the message, which may be empty.
+    const PACKAGE: &'static str;
+
+    /// Returns the fully-qualified name of the message, e.g. `google.protobuf.Duration`.
+    fn full_name() -> String {
+        if Self::PACKAGE.is_empty() {
+            Self::NAME.into()
+        } else {
+            format!("{}.{}", Self::PACKAGE, Self::NAME)
+        }
+    }
+
+    /// Returns the type URL of the message, e.g. `type.googleapis.com/google.protobuf.Duration`.
+    fn type_url() -> String {
+        format!("type.googleapis.com/{}", Self::full_name())
+    }
+}
@@ -0,0 +1,127 @@
+//! Enumeration values which keep unknown numbers.
+//!
+//! Enumeration fields of generated messages are held as `i32`, so that numbers added to the
+//! enumeration after the code was generated survive a round-trip. Their generated getters convert
+//! unknown numbers to the default variant, while an [`OpenEnum`] keeps them, so that code handling
+//! the known variants can also forward the unknown ones.
+
+use core::convert::TryFrom;
+
+/// The value of an enumeration field, which is either a variant of the enumeration `E`, or a
+/// number unknown to it.
+///
+/// Aliases share the number of a variant, so they convert to that variant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OpenEnum<E> {
+    /// A variant of the enumeration.
+    Known(E),
+    /// A number which is not a variant of the enumeration.
+    Unknown(i32),
+}
+
+impl<E> OpenEnum<E> {
+    /// Returns the variant, or `None` if the number is unknown.
+    pub fn known(self) -> Option<E> {
+        match self {
+            OpenEnum::Known(value) => Some(value),
+            OpenEnum::Unknown(_) => None,
+        }
+    }
+
+    /// Returns `true` if the value is a variant of the enumeration.
+    pub fn is_known(&self) -> bool {
+        matches!(self, OpenEnum::Known(_))
+    }
+
+    /// Returns the variant, or the default variant if the number is unknown, as the generated
+    /// getters of enumeration fields do.
+    pub fn known_or_default(self) -> E
+    where
+        E: Default,
+    {
+        self.known().unwrap_or_default()
+    }
+
+    /// Returns the number of the value.
+    pub fn number(self) -> i32
+    where
+        E: Into<i32>,
+    {
+        match self {
+            OpenEnum::Known(value) => value.into(),
+            OpenEnum::Unknown(number) => number,
+        }
+    }
+}
+
+impl<E> Default for OpenEnum<E>
+where
+    E: Default,
+{
+    fn default() -> OpenEnum<E> {
+        OpenEnum::Known(E::default())
+    }
+}
+
+impl<E> From<i32> for OpenEnum<E>
+where
+    E: TryFrom<i32>,
+{
+    fn from(number: i32) -> OpenEnum<E> {
+        match E::try_from(number) {
+            Ok(value) => OpenEnum::Known(value),
+            Err(_) => OpenEnum::Unknown(number),
+        }
+    }
+}
+
+impl<E> From<OpenEnum<E>> for i32
+where
+    E: Into<i32>,
+{
+    fn from(value: OpenEnum<E>) -> i32 {
+        value.number()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::UnknownEnumValue;
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    struct Even(i32);
+
+    impl TryFrom<i32> for Even {
+        type Error = UnknownEnumValue;
+
+        fn try_from(number: i32) -> Result<Even, UnknownEnumValue> {
+            if number % 2 == 0 {
+                Ok(Even(number))
+            } else {
+                Err(UnknownEnumValue(number))
+            }
+        }
+    }
+
+    impl From<Even> for i32 {
+        fn from(value: Even) -> i32 {
+            value.0
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let known = OpenEnum::<Even>::from(4);
+        assert_eq!(known, OpenEnum::Known(Even(4)));
+        assert!(known.is_known());
+        assert_eq!(i32::from(known), 4);
+
+        let unknown = OpenEnum::<Even>::from(3);
+        assert_eq!(unknown, OpenEnum::Unknown(3));
+        assert_eq!(unknown.known(), None);
+        assert_eq!(unknown.known_or_default(), Even(0));
+        assert_eq!(i32::from(unknown), 3);
+    }
+}
@@ -0,0 +1,250 @@
+//! A pool of messages, reused to decode without allocating.
+
+use alloc::vec::Vec;
+
+use bytes::Buf;
+
+use crate::{DecodeError, Message};
+
+/// A pool of cleared messages, which are reused to decode messages rather than allocating new
+/// ones.
+///
+/// Messages taken from the pool with [`MessagePool::decode`] or [`MessagePool::take`] are
+/// returned to it with [`MessagePool::put`], which clears them with [`Message::clear`], keeping
+/// the capacity of their string, bytes, repeated and map fields. Once the pool holds as many
+/// messages as are in use at once, and their fields have grown to the sizes of the decoded
+/// messages, decoding stops allocating for those fields. See [`Message::merge_replace`].
+///
+/// The pool holds at most [`MessagePool::limit`] idle messages, and drops those returned beyond
+/// it, so that a burst of messages doesn't keep their memory alive.
+///
+/// ```
+/// # use prost::{Message, MessagePool};
+/// #[derive(Clone, PartialEq, Message)]
+/// struct Event {
+///     #[prost(string, tag = "1")]
+///     name: String,
+/// }
+///
+/// let mut pool = MessagePool::<Event>::new();
+/// for encoded in [&b"\x0a\x01a"[..], &b"\x0a\x01b"[..]].iter() {
+///     let event = pool.decode(*encoded).unwrap();
+///     // ... handle the event ...
+///     pool.put(event);
+/// }
+/// assert_eq!(pool.len(), 1);
+/// ```
+#[derive(Debug)]
+pub struct MessagePool<M> {
+    messages: Vec<M>,
+    limit: usize,
+}
+
+impl<M> MessagePool<M>
+where
+    M: Message + Default,
+{
+    /// The default maximum number of idle messages held by a pool.
+    pub const DEFAULT_LIMIT: usize = 64;
+
+    /// Creates an empty pool, holding at most [`MessagePool::DEFAULT_LIMIT`] idle messages.
+    pub fn new() -> MessagePool<M> {
+        MessagePool::with_limit(MessagePool::<M>::DEFAULT_LIMIT)
+    }
+
+    /// Creates an empty pool, holding at most `limit` idle messages.
+    pub fn with_limit(limit: usize) -> MessagePool<M> {
+        MessagePool {
+            messages: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Returns the maximum number of idle messages held by the pool.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the number of idle messages held by the pool.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns `true` if the pool holds no idle messages.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Takes a cleared message from the pool, or returns a new default message if the pool is
+    /// empty.
+    pub fn take(&mut self) -> M {
+        self.messages.pop().unwrap_or_default()
+    }
+
+    /// Returns a message to the pool, clearing it, unless the pool is full.
+    pub fn put(&mut self, mut message: M) {
+        if self.messages.len() < self.limit {
+            message.clear();
+            self.messages.push(message);
+        }
+    }
+
+    /// Decodes a message from a buffer into a message taken from the pool.
+    ///
+    /// If decoding fails, the message is returned to the pool. The entire buffer will be consumed.
+    pub fn decode<B>(&mut self, buf: B) -> Result<M, DecodeError>
+    where
+        B: Buf,
+    {
+        let mut message = self.take();
+        match message.merge(buf) {
+            Ok(()) => Ok(message),
+            Err(error) => {
+                self.put(message);
+                Err(error)
+            }
+        }
+    }
+
+    /// Decodes a length-delimited message from a buffer into a message taken from the pool.
+    ///
+    /// If decoding fails, the message is returned to the pool.
+    pub fn decode_length_delimited<B>(&mut self, buf: B) -> Result<M, DecodeError>
+    where
+        B: Buf,
+    {
+        let mut message = self.take();
+        match message.merge_length_delimited(buf) {
+            Ok(()) => Ok(message),
+            Err(error) => {
+                self.put(message);
+                Err(error)
+            }
+        }
+    }
+}
+
+impl<M> Default for MessagePool<M>
+where
+    M: Message + Default,
+{
+    fn default() -> MessagePool<M> {
+        MessagePool::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::String;
+    use alloc::vec;
+    use bytes::BufMut;
+
+    use crate::encoding::{self, DecodeContext, WireType};
+
+    /// A message holding a string and a repeated field.
+    #[derive(Debug, Default, PartialEq)]
+    struct Event {
+        name: String,
+        ids: Vec<u32>,
+    }
+
+    impl Message for Event {
+        fn encode_raw<B>(&self, buf: &mut B)
+        where
+            B: BufMut,
+        {
+            if !self.name.is_empty() {
+                encoding::string::encode(1, &self.name, buf);
+            }
+            encoding::uint32::encode_packed(2, &self.ids, buf);
+        }
+
+        fn merge_field<B>(
+            &mut self,
+            tag: u32,
+            wire_type: WireType,
+            buf: &mut B,
+            ctx: DecodeContext,
+        ) -> Result<(), DecodeError>
+        where
+            B: Buf,
+        {
+            match tag {
+                1 => encoding::string::merge(wire_type, &mut self.name, buf, ctx),
+                2 => encoding::uint32::merge_repeated(wire_type, &mut self.ids, buf, ctx),
+                _ => encoding::skip_field(wire_type, tag, buf, ctx),
+            }
+        }
+
+        fn encoded_len(&self) -> usize {
+            let name = if self.name.is_empty() {
+                0
+            } else {
+                encoding::string::encoded_len(1, &self.name)
+            };
+            name + encoding::uint32::encoded_len_packed(2, &self.ids)
+        }
+
+        fn clear(&mut self) {
+            self.name.clear();
+            self.ids.clear();
+        }
+    }
+
+    #[test]
+    fn reuse() {
+        let mut event = Event {
+            name: "event".repeat(8),
+            ids: (0..16).collect(),
+        };
+        let encoded = event.encode_to_vec();
+
+        let mut pool = MessagePool::new();
+        let decoded: Event = pool.decode(&encoded[..]).unwrap();
+        assert_eq!(decoded, event);
+        let name = decoded.name.as_ptr();
+        let ids = decoded.ids.as_ptr();
+        pool.put(decoded);
+        assert_eq!(pool.len(), 1);
+
+        // The decoded message reuses the buffers of the returned one.
+        event.name.truncate(4);
+        event.ids.truncate(4);
+        let decoded = pool.decode(&*event.encode_to_vec()).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(decoded.name.as_ptr(), name);
+        assert_eq!(decoded.ids.as_ptr(), ids);
+        assert!(pool.is_empty());
+
+        // A failed decode returns the message to the pool.
+        assert!(pool.decode(&b"\x0a\x05a"[..]).is_err());
+        assert_eq!(pool.len(), 1);
+        assert!(pool.take().is_default());
+    }
+
+    #[test]
+    fn limit() {
+        let mut pool = MessagePool::<Event>::with_limit(1);
+        pool.put(Event::default());
+        pool.put(Event::default());
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn merge_replace() {
+        let mut event = Event {
+            name: "a".into(),
+            ids: vec![1, 2],
+        };
+        event.merge_replace(&b"\x12\x01\x03"[..]).unwrap();
+        assert_eq!(
+            event,
+            Event {
+                name: String::new(),
+                ids: vec![3],
+            }
+        );
+    }
+}
@@ -0,0 +1,136 @@
+//! Compact presence tracking for the optional fields of wide messages.
+//!
+//! Generated messages hold optional scalar fields in an `Option` by default, which doubles the
+//! size of most numeric fields once padding is accounted for. Messages matched by `prost-build`'s
+//! `Config::presence_bits` instead hold the values of their optional scalar and message fields
+//! directly, and record which of them are set in a single [`PresenceBits`] field, with one bit
+//! per field.
+
+use core::fmt;
+
+/// A fixed-size set of presence bits, holding `64 * N` bits.
+///
+/// Each optional field of a message using presence bits is assigned a bit by index. The bit is
+/// set when the field is decoded or set through its generated `set_` method, and cleared by its
+/// `clear_` method.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PresenceBits<const N: usize> {
+    words: [u64; N],
+}
+
+impl<const N: usize> PresenceBits<N> {
+    /// Creates a set with no bits set.
+    pub const fn new() -> PresenceBits<N> {
+        PresenceBits { words: [0; N] }
+    }
+
+    /// Returns `true` if the bit is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is not less than `64 * N`.
+    #[inline]
+    pub fn get(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Sets the bit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is not less than `64 * N`.
+    #[inline]
+    pub fn set(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    /// Unsets the bit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is not less than `64 * N`.
+    #[inline]
+    pub fn unset(&mut self, index: usize) {
+        self.words[index / 64] &= !(1 << (index % 64));
+    }
+
+    /// Unsets all bits.
+    pub fn clear(&mut self) {
+        self.words = [0; N];
+    }
+
+    /// Returns the number of bits which are set.
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Returns `true` if no bit is set.
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// Returns the words holding the bits, the first bit being the lowest bit of the first word.
+    pub(crate) fn words(&self) -> &[u64; N] {
+        &self.words
+    }
+
+    /// Returns an iterator over the indices of the bits which are set, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..N * 64).filter(move |&index| self.get(index))
+    }
+}
+
+impl<const N: usize> Default for PresenceBits<N> {
+    fn default() -> PresenceBits<N> {
+        PresenceBits::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for PresenceBits<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::format;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn bits() {
+        let mut bits = PresenceBits::<2>::new();
+        assert!(bits.is_empty());
+        assert_eq!(bits, PresenceBits::default());
+
+        for &index in &[0, 63, 64, 127] {
+            assert!(!bits.get(index));
+            bits.set(index);
+            assert!(bits.get(index));
+        }
+        // Setting a bit twice has no effect.
+        bits.set(64);
+        assert_eq!(bits.len(), 4);
+        assert_eq!(bits.iter().collect::<Vec<_>>(), [0, 63, 64, 127]);
+        assert_eq!(format!("{:?}", bits), "{0, 63, 64, 127}");
+
+        bits.unset(63);
+        bits.unset(62);
+        assert!(!bits.get(63));
+        assert_eq!(bits.len(), 3);
+
+        bits.clear();
+        assert!(bits.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_range() {
+        PresenceBits::<1>::new().set(64);
+    }
+}
@@ -0,0 +1,276 @@
+//! Stack-safe `Clone`, `PartialEq` and `Drop` for recursive messages.
+//!
+//! The derived implementations of these traits recurse into nested messages, so a sufficiently
+//! deep message tree, which may come from untrusted input, overflows the stack. Messages which
+//! implement [`Recursive`] can instead use [`clone`], [`eq`] and [`drop`], which walk the tree
+//! with an explicit work-list on the heap.
+//!
+//! Implementations are generated by `prost-build` for recursive messages when
+//! `Config::iterative_recursion` is enabled.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::Any;
+
+/// A message which is part of a recursive message graph.
+///
+/// The recursive children of a message are the messages it directly contains which also implement
+/// `Recursive`. All other fields are cloned and compared as usual.
+pub trait Recursive: Any {
+    /// Appends the recursive children of the message to `children`.
+    fn children<'a>(&'a self, children: &mut Vec<&'a dyn Recursive>);
+
+    /// Moves the recursive children out of the message, appending them to `children`.
+    fn take_children(&mut self, children: &mut Vec<Box<dyn Recursive>>);
+
+    /// Clones the message, using clones of its recursive children popped from `children`.
+    ///
+    /// The clones of the children are popped in the order the children are appended by
+    /// [`Recursive::children`].
+    fn clone_with_children(&self, children: &mut Vec<Box<dyn Recursive>>) -> Box<dyn Recursive>;
+
+    /// Compares the message with `other`, ignoring the contents of recursive children, which are
+    /// instead appended to `pairs` to be compared later.
+    fn eq_shallow<'a>(
+        &'a self,
+        other: &'a dyn Recursive,
+        pairs: &mut Vec<(&'a dyn Recursive, &'a dyn Recursive)>,
+    ) -> bool;
+
+    /// Returns the message as `Any`, for downcasting.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns the boxed message as `Any`, for downcasting.
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+/// Clones a recursive message without recursion.
+pub fn clone<T>(message: &T) -> T
+where
+    T: Recursive,
+{
+    // Flatten the tree in pre-order, then clone it back to front, so that the clones of a
+    // message's children are on top of the stack, in order, when the message is cloned.
+    let mut messages: Vec<&dyn Recursive> = Vec::new();
+    let mut stack: Vec<&dyn Recursive> = Vec::new();
+    let mut children = Vec::new();
+    stack.push(message);
+    while let Some(message) = stack.pop() {
+        messages.push(message);
+        message.children(&mut children);
+        stack.extend(children.drain(..).rev());
+    }
+
+    let mut clones = Vec::new();
+    while let Some(message) = messages.pop() {
+        let clone = message.clone_with_children(&mut clones);
+        clones.push(clone);
+    }
+    debug_assert_eq!(clones.len(), 1);
+    *pop(&mut clones)
+}
+
+/// Compares two recursive messages without recursion.
+pub fn eq<T>(a: &T, b: &T) -> bool
+where
+    T: Recursive,
+{
+    let mut pairs: Vec<(&dyn Recursive, &dyn Recursive)> = Vec::new();
+    pairs.push((a, b));
+    while let Some((a, b)) = pairs.pop() {
+        if !a.eq_shallow(b, &mut pairs) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Drops the recursive children of a message without recursion.
+///
+/// This is intended to be called from the message's `Drop` implementation. Each child is emptied
+/// of its own children before it is dropped, so the nested `drop` calls never go deeper than one
+/// level.
+pub fn drop<T>(message: &mut T)
+where
+    T: Recursive,
+{
+    let mut children = Vec::new();
+    message.take_children(&mut children);
+    while let Some(mut child) = children.pop() {
+        child.take_children(&mut children);
+    }
+}
+
+/// Pops the clone of a recursive child in [`Recursive::clone_with_children`].
+///
+/// # Panics
+///
+/// Panics if `children` is empty or the child is not a `T`, which indicates that
+/// [`Recursive::children`] and [`Recursive::clone_with_children`] disagree.
+pub fn pop<T>(children: &mut Vec<Box<dyn Recursive>>) -> Box<T>
+where
+    T: Recursive,
+{
+    children
+        .pop()
+        .and_then(|child| child.into_any().downcast().ok())
+        .expect("mismatched recursive message children")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[derive(Debug, Default)]
+    struct Node {
+        value: u32,
+        next: Option<Box<Node>>,
+        children: Vec<Node>,
+    }
+
+    impl Recursive for Node {
+        fn children<'a>(&'a self, children: &mut Vec<&'a dyn Recursive>) {
+            if let Some(child) = &self.next {
+                children.push(&**child);
+            }
+            for child in &self.children {
+                children.push(child);
+            }
+        }
+
+        fn take_children(&mut self, children: &mut Vec<Box<dyn Recursive>>) {
+            if let Some(child) = self.next.take() {
+                children.push(child);
+            }
+            for child in self.children.drain(..) {
+                children.push(Box::new(child));
+            }
+        }
+
+        fn clone_with_children(
+            &self,
+            children: &mut Vec<Box<dyn Recursive>>,
+        ) -> Box<dyn Recursive> {
+            Box::new(Node {
+                value: self.value,
+                next: self.next.as_ref().map(|_| pop(children)),
+                children: self.children.iter().map(|_| *pop(children)).collect(),
+            })
+        }
+
+        fn eq_shallow<'a>(
+            &'a self,
+            other: &'a dyn Recursive,
+            pairs: &mut Vec<(&'a dyn Recursive, &'a dyn Recursive)>,
+        ) -> bool {
+            let other = match other.as_any().downcast_ref::<Node>() {
+                Some(other) => other,
+                None => return false,
+            };
+            if self.value != other.value {
+                return false;
+            }
+            match (&self.next, &other.next) {
+                (Some(a), Some(b)) => pairs.push((&**a, &**b)),
+                (None, None) => (),
+                _ => return false,
+            }
+            if self.children.len() != other.children.len() {
+                return false;
+            }
+            for (a, b) in self.children.iter().zip(&other.children) {
+                pairs.push((a, b));
+            }
+            true
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn into_any(self: Box<Self>) -> Box<dyn Any> {
+            self
+        }
+    }
+
+    impl Clone for Node {
+        fn clone(&self) -> Node {
+            clone(self)
+        }
+    }
+
+    impl PartialEq for Node {
+        fn eq(&self, other: &Node) -> bool {
+            eq(self, other)
+        }
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            drop(self)
+        }
+    }
+
+    fn leaf(value: u32) -> Node {
+        // Struct update syntax can't move out of a type implementing `Drop`.
+        Node {
+            value,
+            next: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// Returns a list of `len + 1` nodes linked through `next`, with the values counting down to 0.
+    fn list(len: u32) -> Node {
+        let mut head = leaf(0);
+        for value in 1..=len {
+            head = Node {
+                value,
+                next: Some(Box::new(head)),
+                children: Vec::new(),
+            };
+        }
+        head
+    }
+
+    #[test]
+    fn clone_and_eq() {
+        let mut tree = Node {
+            value: 1,
+            next: Some(Box::new(leaf(2))),
+            children: vec![leaf(3), list(3), leaf(4)],
+        };
+        tree.children[1].children.push(leaf(5));
+
+        let clone = tree.clone();
+        assert_eq!(clone, tree);
+        assert_eq!(clone.children[1].next.as_ref().unwrap().value, 2);
+        assert_eq!(clone.children[1].children[0].value, 5);
+
+        let mut other = tree.clone();
+        other.children[1].next.as_mut().unwrap().value = 7;
+        assert_ne!(other, tree);
+
+        let mut other = tree.clone();
+        other.children.pop();
+        assert_ne!(other, tree);
+
+        let mut other = tree.clone();
+        other.next = None;
+        assert_ne!(other, tree);
+    }
+
+    #[test]
+    fn deep() {
+        let a = list(1_000_000);
+        let b = a.clone();
+        assert_eq!(a, b);
+        core::mem::drop(a);
+
+        let mut wide = leaf(0);
+        wide.children = (0..1_000).map(|_| list(1_000)).collect();
+        assert_eq!(wide.clone(), wide);
+    }
+}
@@ -0,0 +1,311 @@
+//! Alternative Rust types for Protobuf `string` fields.
+//!
+//! Generated messages hold `string` fields in a [`String`] by default. Fields matched by
+//! `prost-build`'s `Config::string_type` can instead use any type implementing [`StringAdapter`]:
+//!
+//!  * `Cow<'static, str>`, which holds static strings without allocating.
+//!  * [`ByteStr`], which shares the buffer it was decoded from when decoding from [`Bytes`].
+//!  * `SmolStr`, which stores short strings inline, with the `smol_str` feature.
+//!  * Any other string type, such as an interned string, implementing [`StringAdapter`].
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::fmt;
+use core::ops::Deref;
+use core::str;
+
+use bytes::{Buf, Bytes};
+
+use crate::{DecodeError, InvalidValueError};
+
+/// A string type which can be used for Protobuf `string` fields, in place of [`String`].
+///
+/// The contents of the string are read through `Deref<Target = str>` when encoding.
+pub trait StringAdapter: Default + Deref<Target = str> + Sized + 'static {
+    /// Creates a string holding a copy of the value.
+    fn copy_from_str(value: &str) -> Self;
+
+    /// Checks that the string can be encoded, for types which restrict their contents.
+    ///
+    /// Called for each string of a message, through [`CheckEncode`](crate::CheckEncode), by
+    /// [`Message::encode_checked`](crate::Message::encode_checked) and
+    /// [`Message::check_encode`](crate::Message::check_encode), which fail with the returned error.
+    /// [`Message::encode`](crate::Message::encode) doesn't check the strings. The default
+    /// implementation accepts every string.
+    fn check_encode(&self) -> Result<(), InvalidValueError> {
+        Ok(())
+    }
+
+    /// Replaces the contents of the string with the bytes remaining in the buffer.
+    ///
+    /// Fails if the bytes are not UTF-8 encoded, in which case the string may be left with any
+    /// valid contents. The default implementation validates the bytes, copying them into a
+    /// temporary buffer only if they are not contiguous, and calls
+    /// [`copy_from_str`](StringAdapter::copy_from_str).
+    fn replace_with<B>(&mut self, mut buf: B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let len = buf.remaining();
+        if buf.chunk().len() == len {
+            *self = Self::copy_from_str(from_utf8(buf.chunk())?);
+            buf.advance(len);
+        } else {
+            let mut bytes = Vec::with_capacity(buf.remaining());
+            while buf.has_remaining() {
+                let chunk = buf.chunk();
+                bytes.extend_from_slice(chunk);
+                let len = chunk.len();
+                buf.advance(len);
+            }
+            *self = Self::copy_from_str(from_utf8(&bytes)?);
+        }
+        Ok(())
+    }
+}
+
+fn from_utf8(bytes: &[u8]) -> Result<&str, DecodeError> {
+    str::from_utf8(bytes)
+        .map_err(|_| DecodeError::new("invalid string value: data is not UTF-8 encoded"))
+}
+
+impl StringAdapter for String {
+    fn copy_from_str(value: &str) -> String {
+        value.into()
+    }
+}
+
+impl StringAdapter for Cow<'static, str> {
+    fn copy_from_str(value: &str) -> Cow<'static, str> {
+        Cow::Owned(value.into())
+    }
+
+    fn replace_with<B>(&mut self, buf: B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        // Reuse the allocation of an owned string.
+        let mut bytes = match core::mem::take(self) {
+            Cow::Owned(value) => value.into_bytes(),
+            Cow::Borrowed(_) => Vec::new(),
+        };
+        bytes.clear();
+        bytes.reserve(buf.remaining());
+        bytes::BufMut::put(&mut bytes, buf);
+        match String::from_utf8(bytes) {
+            Ok(value) => {
+                *self = Cow::Owned(value);
+                Ok(())
+            }
+            Err(_) => Err(DecodeError::new(
+                "invalid string value: data is not UTF-8 encoded",
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "smol_str")]
+impl StringAdapter for smol_str::SmolStr {
+    fn copy_from_str(value: &str) -> smol_str::SmolStr {
+        smol_str::SmolStr::new(value)
+    }
+}
+
+/// An immutable UTF-8 string backed by [`Bytes`].
+///
+/// Decoding a `ByteStr` field from a `Bytes` buffer shares the buffer instead of copying the
+/// string, like decoding a `Bytes` field.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteStr {
+    // Invariant: the bytes are UTF-8 encoded.
+    bytes: Bytes,
+}
+
+impl ByteStr {
+    /// Creates an empty string.
+    pub const fn new() -> ByteStr {
+        ByteStr {
+            bytes: Bytes::new(),
+        }
+    }
+
+    /// Creates a string referencing a static string, without copying it.
+    pub const fn from_static(value: &'static str) -> ByteStr {
+        ByteStr {
+            bytes: Bytes::from_static(value.as_bytes()),
+        }
+    }
+
+    /// Converts UTF-8 encoded bytes to a string, without copying them.
+    ///
+    /// Fails if the bytes are not UTF-8 encoded, returning them.
+    pub fn from_utf8(bytes: Bytes) -> Result<ByteStr, Bytes> {
+        match str::from_utf8(&bytes) {
+            Ok(_) => Ok(ByteStr { bytes }),
+            Err(_) => Err(bytes),
+        }
+    }
+
+    /// Returns the string as a `str`.
+    pub fn as_str(&self) -> &str {
+        // Safety: the bytes are UTF-8 encoded.
+        unsafe { str::from_utf8_unchecked(&self.bytes) }
+    }
+
+    /// Returns the UTF-8 encoded bytes of the string.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Converts the string into its UTF-8 encoded bytes.
+    pub fn into_bytes(self) -> Bytes {
+        self.bytes
+    }
+}
+
+impl StringAdapter for ByteStr {
+    fn copy_from_str(value: &str) -> ByteStr {
+        ByteStr {
+            bytes: Bytes::copy_from_slice(value.as_bytes()),
+        }
+    }
+
+    fn replace_with<B>(&mut self, mut buf: B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let bytes = buf.copy_to_bytes(buf.remaining());
+        match ByteStr::from_utf8(bytes) {
+            Ok(value) => {
+                *self = value;
+                Ok(())
+            }
+            Err(_) => {
+                *self = ByteStr::new();
+                Err(DecodeError::new(
+                    "invalid string value: data is not UTF-8 encoded",
+                ))
+            }
+        }
+    }
+}
+
+impl Deref for ByteStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ByteStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for ByteStr {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Borrow<str> for ByteStr {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<'a> From<&'a str> for ByteStr {
+    fn from(value: &'a str) -> ByteStr {
+        ByteStr::copy_from_str(value)
+    }
+}
+
+impl From<String> for ByteStr {
+    fn from(value: String) -> ByteStr {
+        ByteStr {
+            bytes: Bytes::from(value),
+        }
+    }
+}
+
+impl From<ByteStr> for Bytes {
+    fn from(value: ByteStr) -> Bytes {
+        value.bytes
+    }
+}
+
+impl PartialEq<str> for ByteStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for ByteStr {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for ByteStr {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bytes::Buf;
+
+    #[test]
+    fn replace_with() {
+        let data = Bytes::from_static("héllo wörld".as_bytes());
+
+        let mut value = ByteStr::from_static("previous");
+        value.replace_with(data.clone()).unwrap();
+        assert_eq!(value, "héllo wörld");
+        // The string shares the buffer it was decoded from.
+        assert_eq!(value.as_bytes().as_ptr(), data.as_ptr());
+
+        let mut value: Cow<'static, str> = Cow::Borrowed("previous");
+        value.replace_with(data.clone()).unwrap();
+        assert_eq!(value, "héllo wörld");
+
+        // The buffer is consumed.
+        let mut value = String::from("previous");
+        let mut contiguous = &b"h\xc3\xa9llo"[..];
+        value.replace_with(&mut contiguous).unwrap();
+        assert_eq!(value, "héllo");
+        assert!(contiguous.is_empty());
+
+        // A buffer made of several chunks.
+        let mut value = String::from("previous");
+        let chunked = Buf::chain(&b"h\xc3"[..], &b"\xa9llo"[..]);
+        value.replace_with(chunked).unwrap();
+        assert_eq!(value, "héllo");
+
+        let invalid = &b"h\xc3llo"[..];
+        assert!(ByteStr::new().replace_with(invalid).is_err());
+        assert!(Cow::<'static, str>::default()
+            .replace_with(invalid)
+            .is_err());
+        assert!(String::new().replace_with(invalid).is_err());
+    }
+}
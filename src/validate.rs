@@ -0,0 +1,159 @@
+//! Validation of messages against the rules of their fields.
+//!
+//! Messages matched by `prost-build`'s `Config::validate` implement [`Validate`], checking the
+//! validation rules declared with the custom field options of [protoc-gen-validate][1] or
+//! [protovalidate][2], so that servers can reject invalid requests without generating code with a
+//! separate plugin.
+//!
+//! [1]: https://github.com/bufbuild/protoc-gen-validate
+//! [2]: https://github.com/bufbuild/protovalidate
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A message which can be checked against the validation rules of its fields.
+pub trait Validate {
+    /// Checks the message against the validation rules of its fields, and those of the messages
+    /// it embeds, returning the first violation found.
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
+impl<T> Validate for Box<T>
+where
+    T: Validate + ?Sized,
+{
+    fn validate(&self) -> Result<(), ValidationError> {
+        (**self).validate()
+    }
+}
+
+/// A violation of the validation rules of a message.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    inner: Box<Inner>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct Inner {
+    /// A description of the rule which was violated.
+    description: Cow<'static, str>,
+    /// A stack of (message, field) name pairs, which identify the field which violates the rule.
+    /// The stack contains an entry per level of nesting, starting with the innermost.
+    stack: Vec<(&'static str, &'static str)>,
+}
+
+impl ValidationError {
+    /// Creates a new `ValidationError` with a description of the rule which was violated.
+    ///
+    /// Meant to be used only by `Validate` implementations.
+    #[doc(hidden)]
+    #[cold]
+    pub fn new(description: impl Into<Cow<'static, str>>) -> ValidationError {
+        ValidationError {
+            inner: Box::new(Inner {
+                description: description.into(),
+                stack: Vec::new(),
+            }),
+        }
+    }
+
+    /// Pushes a (message, field) name location pair on to the location stack.
+    ///
+    /// Meant to be used only by `Validate` implementations.
+    #[doc(hidden)]
+    pub fn push(&mut self, message: &'static str, field: &'static str) {
+        self.inner.stack.push((message, field));
+    }
+
+    /// Returns the description of the rule which was violated.
+    pub fn description(&self) -> &str {
+        &self.inner.description
+    }
+
+    /// Returns the path of the field which violates the rule, relative to the validated message,
+    /// such as `items.name`.
+    pub fn field_path(&self) -> String {
+        let mut path = String::new();
+        for &(_, field) in self.inner.stack.iter().rev() {
+            if !path.is_empty() {
+                path.push('.');
+            }
+            path.push_str(field);
+        }
+        path
+    }
+}
+
+impl fmt::Debug for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValidationError")
+            .field("description", &self.inner.description)
+            .field("stack", &self.inner.stack)
+            .finish()
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid Protobuf message: ")?;
+        for &(message, field) in &self.inner.stack {
+            write!(f, "{}.{}: ", message, field)?;
+        }
+        f.write_str(&self.inner.description)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+
+    struct Inner(i32);
+
+    impl Validate for Inner {
+        fn validate(&self) -> Result<(), ValidationError> {
+            if self.0 < 0 {
+                let mut error = ValidationError::new("value must be greater than or equal to 0");
+                error.push("Inner", "value");
+                return Err(error);
+            }
+            Ok(())
+        }
+    }
+
+    struct Outer(Box<Inner>);
+
+    impl Validate for Outer {
+        fn validate(&self) -> Result<(), ValidationError> {
+            if let Err(mut error) = self.0.validate() {
+                error.push("Outer", "inner");
+                return Err(error);
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn nested() {
+        assert_eq!(Outer(Box::new(Inner(1))).validate(), Ok(()));
+
+        let error = Outer(Box::new(Inner(-1))).validate().unwrap_err();
+        assert_eq!(
+            error.description(),
+            "value must be greater than or equal to 0"
+        );
+        assert_eq!(error.field_path(), "inner.value");
+        assert_eq!(
+            error.to_string(),
+            "invalid Protobuf message: Inner.value: Outer.inner: value must be greater than or \
+             equal to 0"
+        );
+    }
+}
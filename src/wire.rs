@@ -0,0 +1,552 @@
+//! Low-level access to the Protobuf wire format, without generated message types.
+//!
+//! [`WireReader`] iterates over the fields of an encoded message as tags and raw [`WireValue`]s,
+//! borrowing from the encoded buffer. It allows custom scanning, such as extracting a single
+//! field from a large number of records, without decoding the rest of each record.
+//! [`WireWriter`] is its counterpart, writing fields to a buffer one at a time.
+//!
+//! [`validate_wire_format`] walks an encoded message, checking that its keys, varints, lengths
+//! and groups are well-formed, without allocating message structs. This is a cheap pre-filter
+//! for untrusted input, rejecting malformed payloads before they are queued for an expensive
+//! decode.
+//!
+//! Without a schema, length-delimited fields are opaque, since they may hold strings, bytes,
+//! packed values or embedded messages. [`validate_wire_format_with`] takes a [`WireSchema`]
+//! describing which fields are strings and embedded messages, so that strings are checked to be
+//! UTF-8, and embedded messages are checked recursively.
+
+use core::str;
+
+use bytes::BufMut;
+
+use crate::encoding::{
+    check_wire_type, decode_key, decode_varint, encode_key, encode_varint, WireType,
+};
+use crate::DecodeError;
+
+/// The default maximum nesting depth of the groups skipped by a [`WireReader`].
+const DEFAULT_MAX_DEPTH: u32 = 100;
+
+/// The raw value of a field, as read by [`WireReader`] and written by [`WireWriter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireValue<'a> {
+    /// A varint, holding an `int32`, `int64`, `uint32`, `uint64`, `sint32`, `sint64`, `bool` or
+    /// enum value. Signed and zig-zag encoded values are not decoded.
+    Varint(u64),
+    /// A 64-bit value, holding the bits of a `fixed64`, `sfixed64` or `double` value.
+    SixtyFourBit(u64),
+    /// A length-delimited value, holding a string, bytes, an embedded message or packed repeated
+    /// values, excluding the length delimiter.
+    LengthDelimited(&'a [u8]),
+    /// A group, holding the encoded fields between its start and end group tags.
+    Group(&'a [u8]),
+    /// A 32-bit value, holding the bits of a `fixed32`, `sfixed32` or `float` value.
+    ThirtyTwoBit(u32),
+}
+
+/// A cursor over the fields of an encoded message.
+///
+/// Fields are read in the order they are encoded, including repeated occurrences of the same
+/// tag, and the values of length-delimited fields and groups borrow from the buffer. Groups are
+/// read as a whole, and their end group tags are checked, so that the fields of a group can in
+/// turn be read with another `WireReader`.
+///
+/// `WireReader` is also an iterator over the fields, which ends after the first error.
+///
+/// # Example
+///
+/// Extracting field 2 from a stream of length-delimited records:
+///
+/// ```
+/// use prost::wire::{WireReader, WireValue};
+///
+/// let records = b"\x06\x08\x01\x12\x02hi\x04\x12\x02yo\x02\x08\x03";
+/// let mut buf = &records[..];
+/// let mut names = Vec::new();
+/// while !buf.is_empty() {
+///     let len = prost::decode_length_delimiter(&mut buf).unwrap();
+///     let (record, rest) = buf.split_at(len);
+///     let mut reader = WireReader::new(record);
+///     if let Some(WireValue::LengthDelimited(name)) = reader.find_field(2).unwrap() {
+///         names.push(name);
+///     }
+///     buf = rest;
+/// }
+/// assert_eq!(names, [b"hi", b"yo"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct WireReader<'a> {
+    buf: &'a [u8],
+    len: usize,
+    max_depth: u32,
+}
+
+impl<'a> WireReader<'a> {
+    /// Creates a reader over the fields encoded in `buf`.
+    pub fn new(buf: &'a [u8]) -> WireReader<'a> {
+        WireReader {
+            buf,
+            len: buf.len(),
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    /// Sets the maximum nesting depth of groups, which defaults to 100.
+    pub fn with_max_depth(mut self, max_depth: u32) -> WireReader<'a> {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Returns the bytes which have not been read yet.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Returns the offset of the next field from the start of the buffer.
+    pub fn position(&self) -> usize {
+        self.len - self.buf.len()
+    }
+
+    /// Reads the next field, returning its tag and value, or `None` at the end of the buffer.
+    ///
+    /// If an error is returned, the position of the reader is unspecified.
+    pub fn next_field(&mut self) -> Result<Option<(u32, WireValue<'a>)>, DecodeError> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+        let (tag, wire_type) = decode_key(&mut self.buf)?;
+        let value = match wire_type {
+            WireType::Varint => WireValue::Varint(decode_varint(&mut self.buf)?),
+            WireType::SixtyFourBit => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(take(&mut self.buf, 8)?);
+                WireValue::SixtyFourBit(u64::from_le_bytes(bytes))
+            }
+            WireType::LengthDelimited => {
+                let len = decode_varint(&mut self.buf)?;
+                WireValue::LengthDelimited(take(&mut self.buf, len)?)
+            }
+            WireType::StartGroup => {
+                let fields = self.buf;
+                let depth = enter(self.max_depth)?;
+                let len = validate_fields::<()>(&mut self.buf, None, Some(tag), depth)?;
+                WireValue::Group(&fields[..len])
+            }
+            WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
+            WireType::ThirtyTwoBit => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(take(&mut self.buf, 4)?);
+                WireValue::ThirtyTwoBit(u32::from_le_bytes(bytes))
+            }
+        };
+        Ok(Some((tag, value)))
+    }
+
+    /// Reads fields until one with the given tag is found, returning its value.
+    ///
+    /// The reader is left after the field, so that calling this again returns the next
+    /// occurrence of a repeated field.
+    pub fn find_field(&mut self, tag: u32) -> Result<Option<WireValue<'a>>, DecodeError> {
+        while let Some((field_tag, value)) = self.next_field()? {
+            if field_tag == tag {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for WireReader<'a> {
+    type Item = Result<(u32, WireValue<'a>), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let field = self.next_field();
+        if field.is_err() {
+            self.buf = &[];
+        }
+        field.transpose()
+    }
+}
+
+/// Writes fields to a buffer as tags and raw [`WireValue`]s.
+///
+/// This method of writing panics if the buffer has insufficient capacity, like
+/// [`Message::encode_raw`][crate::Message::encode_raw].
+///
+/// ```
+/// use prost::wire::{WireReader, WireValue, WireWriter};
+///
+/// let mut writer = WireWriter::new(Vec::new());
+/// writer.write_field(1, WireValue::Varint(150));
+/// writer.write_field(2, WireValue::LengthDelimited(b"hi"));
+/// let buf = writer.into_inner();
+/// assert_eq!(buf, b"\x08\x96\x01\x12\x02hi");
+/// assert_eq!(WireReader::new(&buf).count(), 2);
+/// ```
+#[derive(Debug)]
+pub struct WireWriter<B> {
+    buf: B,
+}
+
+impl<B> WireWriter<B>
+where
+    B: BufMut,
+{
+    /// Creates a writer which appends fields to `buf`.
+    pub fn new(buf: B) -> WireWriter<B> {
+        WireWriter { buf }
+    }
+
+    /// Writes a field with the given tag and value.
+    ///
+    /// The length delimiter of a length-delimited value, and the start and end group tags of a
+    /// group, are written around the value. The fields of a group are written as is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` is not between 1 and 2<sup>29</sup> - 1.
+    pub fn write_field(&mut self, tag: u32, value: WireValue<'_>) {
+        assert!(
+            (crate::encoding::MIN_TAG..=crate::encoding::MAX_TAG).contains(&tag),
+            "invalid tag: {}",
+            tag
+        );
+        let buf = &mut self.buf;
+        match value {
+            WireValue::Varint(value) => {
+                encode_key(tag, WireType::Varint, buf);
+                encode_varint(value, buf);
+            }
+            WireValue::SixtyFourBit(value) => {
+                encode_key(tag, WireType::SixtyFourBit, buf);
+                buf.put_u64_le(value);
+            }
+            WireValue::LengthDelimited(value) => {
+                encode_key(tag, WireType::LengthDelimited, buf);
+                encode_varint(value.len() as u64, buf);
+                buf.put_slice(value);
+            }
+            WireValue::Group(fields) => {
+                encode_key(tag, WireType::StartGroup, buf);
+                buf.put_slice(fields);
+                encode_key(tag, WireType::EndGroup, buf);
+            }
+            WireValue::ThirtyTwoBit(value) => {
+                encode_key(tag, WireType::ThirtyTwoBit, buf);
+                buf.put_u32_le(value);
+            }
+        }
+    }
+
+    /// Returns a reference to the underlying buffer.
+    pub fn get_ref(&self) -> &B {
+        &self.buf
+    }
+
+    /// Returns a mutable reference to the underlying buffer.
+    pub fn get_mut(&mut self) -> &mut B {
+        &mut self.buf
+    }
+
+    /// Returns the underlying buffer.
+    pub fn into_inner(self) -> B {
+        self.buf
+    }
+}
+
+/// The fields of a message, as far as [`validate_wire_format_with`] needs to know them.
+///
+/// Implemented by `()` for messages whose fields are all unknown, and by
+/// `prost_types::reflect::MessageDescriptor` for messages described by a descriptor.
+pub trait WireSchema: Sized {
+    /// Returns the kind of the field with the given tag.
+    fn field(&self, tag: u32) -> FieldKind<Self>;
+}
+
+/// The kind of a field of a [`WireSchema`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldKind<S> {
+    /// A field whose contents aren't checked beyond the wire format, which includes unknown
+    /// fields and fields of scalar types other than `string`.
+    Other,
+    /// A `string` field, which must be length-delimited and valid UTF-8.
+    String,
+    /// An embedded message or group field, whose contents are checked against the schema.
+    Message(S),
+}
+
+impl WireSchema for () {
+    fn field(&self, _tag: u32) -> FieldKind<()> {
+        FieldKind::Other
+    }
+}
+
+/// Checks that `buf` holds a well-formed encoded message, without decoding it.
+///
+/// The keys, varints and length delimiters of the fields are checked, as are the start and end
+/// tags of groups, which may be nested up to `max_depth` levels deep. Length-delimited fields are
+/// not inspected; see [`validate_wire_format_with`] to check strings and embedded messages.
+///
+/// A message which passes the check may still fail to decode as a particular message type, for
+/// example if a field has a different wire type than the type declares.
+///
+/// ```
+/// assert!(prost::validate_wire_format(b"\x08\x96\x01\x1a\x02hi", 100).is_ok());
+/// assert!(prost::validate_wire_format(b"\x1a\x05hi", 100).is_err());
+/// ```
+pub fn validate_wire_format(buf: &[u8], max_depth: u32) -> Result<(), DecodeError> {
+    validate_wire_format_with(buf, &(), max_depth)
+}
+
+/// Checks that `buf` holds a well-formed encoded message with the given schema, without
+/// decoding it.
+///
+/// In addition to the checks of [`validate_wire_format`], `string` fields must be
+/// length-delimited and valid UTF-8, and embedded messages must be length-delimited or groups,
+/// and are checked in turn. Embedded messages and groups may be nested up to `max_depth` levels
+/// deep. The check recurses once per level, so `max_depth` should be kept close to the recursion
+/// limit of [`Message::decode`][crate::Message::decode].
+pub fn validate_wire_format_with<S>(
+    mut buf: &[u8],
+    schema: &S,
+    max_depth: u32,
+) -> Result<(), DecodeError>
+where
+    S: WireSchema,
+{
+    validate_fields(&mut buf, Some(schema), None, max_depth).map(|_| ())
+}
+
+/// Checks the fields of a message or group, leaving `buf` after the end group tag of `group`.
+/// Returns the length of the fields, excluding the end group tag.
+///
+/// A `schema` of `None` is used for the contents of unknown groups.
+fn validate_fields<S>(
+    buf: &mut &[u8],
+    schema: Option<&S>,
+    group: Option<u32>,
+    depth: u32,
+) -> Result<usize, DecodeError>
+where
+    S: WireSchema,
+{
+    let start = buf.len();
+    while !buf.is_empty() {
+        let len = start - buf.len();
+        let (tag, wire_type) = decode_key(buf)?;
+        let kind = schema.map_or(FieldKind::Other, |schema| schema.field(tag));
+        match wire_type {
+            WireType::Varint => {
+                check_kind(&kind, wire_type)?;
+                decode_varint(buf)?;
+            }
+            WireType::ThirtyTwoBit | WireType::SixtyFourBit => {
+                check_kind(&kind, wire_type)?;
+                let len = if wire_type == WireType::ThirtyTwoBit {
+                    4
+                } else {
+                    8
+                };
+                take(buf, len as u64)?;
+            }
+            WireType::LengthDelimited => {
+                let len = decode_varint(buf)?;
+                let mut value = take(buf, len)?;
+                match kind {
+                    FieldKind::Other => {}
+                    FieldKind::String => {
+                        if str::from_utf8(value).is_err() {
+                            return Err(DecodeError::new(
+                                "invalid string value: data is not UTF-8 encoded",
+                            ));
+                        }
+                    }
+                    FieldKind::Message(schema) => {
+                        let depth = enter(depth)?;
+                        validate_fields(&mut value, Some(&schema), None, depth)?;
+                    }
+                }
+            }
+            WireType::StartGroup => {
+                let depth = enter(depth)?;
+                match kind {
+                    FieldKind::Other => {
+                        validate_fields::<S>(buf, None, Some(tag), depth)?;
+                    }
+                    FieldKind::String => check_wire_type(WireType::LengthDelimited, wire_type)?,
+                    FieldKind::Message(schema) => {
+                        validate_fields(buf, Some(&schema), Some(tag), depth)?;
+                    }
+                }
+            }
+            WireType::EndGroup => {
+                if group == Some(tag) {
+                    return Ok(len);
+                }
+                return Err(DecodeError::new("unexpected end group tag"));
+            }
+        }
+    }
+
+    match group {
+        Some(_) => Err(DecodeError::new("buffer underflow")),
+        None => Ok(start),
+    }
+}
+
+/// Checks that a field of the given kind may have a non-length-delimited, non-group wire type.
+fn check_kind<S>(kind: &FieldKind<S>, wire_type: WireType) -> Result<(), DecodeError> {
+    match kind {
+        FieldKind::Other => Ok(()),
+        FieldKind::String | FieldKind::Message(_) => {
+            check_wire_type(WireType::LengthDelimited, wire_type)
+        }
+    }
+}
+
+/// Returns the depth remaining within an embedded message or group.
+fn enter(depth: u32) -> Result<u32, DecodeError> {
+    depth
+        .checked_sub(1)
+        .ok_or_else(|| DecodeError::new("recursion limit reached"))
+}
+
+/// Splits `len` bytes off the front of `buf`.
+fn take<'a>(buf: &mut &'a [u8], len: u64) -> Result<&'a [u8], DecodeError> {
+    if len > buf.len() as u64 {
+        return Err(DecodeError::new("buffer underflow"));
+    }
+    let (value, rest) = buf.split_at(len as usize);
+    *buf = rest;
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    /// A message with a string field 1 and a field 2 holding the same message type.
+    struct Nested;
+
+    impl WireSchema for Nested {
+        fn field(&self, tag: u32) -> FieldKind<Nested> {
+            match tag {
+                1 => FieldKind::String,
+                2 => FieldKind::Message(Nested),
+                _ => FieldKind::Other,
+            }
+        }
+    }
+
+    fn error(buf: &[u8], max_depth: u32) -> alloc::string::String {
+        validate_wire_format_with(buf, &Nested, max_depth)
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn schemaless() {
+        assert!(validate_wire_format(b"", 0).is_ok());
+        // A varint, a fixed32, a fixed64 and a length-delimited field.
+        let valid =
+            b"\x08\x96\x01\x15\x00\x00\x80\x3f\x19\x00\x00\x00\x00\x00\x00\xf0\x3f\x22\x01\xff";
+        assert!(validate_wire_format(valid, 0).is_ok());
+        for end in 1..valid.len() {
+            if end != 3 && end != 8 && end != 17 {
+                assert!(validate_wire_format(&valid[..end], 0).is_err(), "{}", end);
+            }
+        }
+
+        // Tag 0 and wire type 7.
+        assert!(validate_wire_format(b"\x00\x00", 0).is_err());
+        assert!(validate_wire_format(b"\x0f\x00", 0).is_err());
+
+        // A group holding a group.
+        assert!(validate_wire_format(b"\x0b\x13\x14\x0c", 2).is_ok());
+        assert!(validate_wire_format(b"\x0b\x13\x14\x0c", 1).is_err());
+        assert!(validate_wire_format(b"\x0b\x13\x0c\x14", 2).is_err());
+        assert!(validate_wire_format(b"\x0b\x13\x14", 2).is_err());
+        assert!(validate_wire_format(b"\x0c", 2).is_err());
+    }
+
+    #[test]
+    fn reader() {
+        let buf = b"\x08\x96\x01\x11\x01\x00\x00\x00\x00\x00\x00\x80\x1a\x02hi\x23\x08\x01\x2b\x2c\x24\x2d\x00\x00\x80\x3f";
+        let mut reader = WireReader::new(buf);
+        assert_eq!(
+            reader.next_field().unwrap(),
+            Some((1, WireValue::Varint(150)))
+        );
+        assert_eq!(reader.position(), 3);
+        let fields = reader.clone().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            fields,
+            [
+                (2, WireValue::SixtyFourBit(1 << 63 | 1)),
+                (3, WireValue::LengthDelimited(b"hi")),
+                (4, WireValue::Group(b"\x08\x01\x2b\x2c")),
+                (5, WireValue::ThirtyTwoBit(1f32.to_bits())),
+            ]
+        );
+        assert_eq!(reader.find_field(4).unwrap(), Some(fields[2].1));
+        assert_eq!(reader.find_field(4).unwrap(), None);
+        assert!(reader.remaining().is_empty());
+
+        // The fields are written back as they were read.
+        let mut writer = WireWriter::new(Vec::new());
+        for field in WireReader::new(buf) {
+            let (tag, value) = field.unwrap();
+            writer.write_field(tag, value);
+        }
+        assert_eq!(writer.into_inner(), &buf[..]);
+
+        // Errors end the iteration.
+        let mut reader = WireReader::new(b"\x23\x08\x01\x2c\x08\x01");
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        assert!(WireReader::new(b"\x24").next_field().is_err());
+        assert!(WireReader::new(b"\x23\x2b\x2c\x24")
+            .with_max_depth(1)
+            .next_field()
+            .is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid tag: 0")]
+    fn writer_invalid_tag() {
+        WireWriter::new(Vec::new()).write_field(0, WireValue::Varint(0));
+    }
+
+    #[test]
+    fn schema() {
+        assert!(validate_wire_format_with(b"\x0a\x02hi\x12\x04\x0a\x02hi", &Nested, 1).is_ok());
+        assert_eq!(
+            error(b"\x12\x04\x0a\x02\xff\xff", 1),
+            "failed to decode Protobuf message: invalid string value: data is not UTF-8 encoded"
+        );
+        // Invalid UTF-8 is allowed in unknown fields.
+        assert!(validate_wire_format_with(b"\x1a\x02\xff\xff", &Nested, 1).is_ok());
+        assert_eq!(
+            error(b"\x08\x01", 1),
+            "failed to decode Protobuf message: invalid wire type: Varint (expected LengthDelimited)"
+        );
+        // A group in place of an embedded message.
+        assert!(validate_wire_format_with(b"\x13\x0a\x00\x14", &Nested, 1).is_ok());
+        assert!(validate_wire_format_with(b"\x13\x0a\x01\xff\x14", &Nested, 1).is_err());
+
+        let mut deep = Vec::new();
+        for _ in 0..10 {
+            let mut outer = alloc::vec![0x12, deep.len() as u8];
+            outer.extend(deep);
+            deep = outer;
+        }
+        assert!(validate_wire_format_with(&deep, &Nested, 10).is_ok());
+        assert_eq!(
+            error(&deep, 9),
+            "failed to decode Protobuf message: recursion limit reached"
+        );
+    }
+}
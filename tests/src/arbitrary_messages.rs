@@ -0,0 +1,50 @@
+use alloc::boxed::Box;
+
+use arbitrary::{Arbitrary, Unstructured};
+use proptest::prelude::*;
+use prost_types::arbitrary::{assert_json_roundtrip, assert_roundtrip, MAX_DEPTH};
+
+mod arbitrary_messages {
+    include!(concat!(env!("OUT_DIR"), "/arbitrary_messages.rs"));
+}
+
+use self::arbitrary_messages::tree::Leaf;
+use self::arbitrary_messages::{Collections, Color, Scalars, Tree, WellKnown};
+
+prost_types::proptest_roundtrip!(scalars_roundtrip, Scalars, json);
+prost_types::proptest_roundtrip!(collections_roundtrip, Collections, json);
+prost_types::proptest_roundtrip!(tree_roundtrip, Tree, json);
+prost_types::proptest_roundtrip!(well_known_roundtrip, WellKnown, json);
+
+proptest! {
+    #[test]
+    fn enum_values_are_known(scalars in any::<Scalars>(), color in any::<Color>()) {
+        prop_assert!(Color::from_i32(scalars.color).is_some());
+        prop_assert!(Color::from_i32(color as i32).is_some());
+    }
+}
+
+fn depth(tree: &Tree) -> usize {
+    let children = tree
+        .left
+        .iter()
+        .chain(&tree.right)
+        .map(Box::as_ref)
+        .chain(&tree.children)
+        .chain(tree.named.values())
+        .chain(match &tree.leaf {
+            Some(Leaf::Subtree(subtree)) => Some(subtree.as_ref()),
+            _ => None,
+        });
+    1 + children.map(depth).max().unwrap_or(0)
+}
+
+#[test]
+fn recursion_is_bounded() {
+    // Input which sets every optional field, so that the left-most branch is as deep as possible.
+    let data = [0xFF; 64 * 1024];
+    let tree = Tree::arbitrary(&mut Unstructured::new(&data)).unwrap();
+    assert_eq!(depth(&tree), MAX_DEPTH + 1);
+    assert_roundtrip(&tree);
+    assert_json_roundtrip(&tree);
+}
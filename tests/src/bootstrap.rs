@@ -23,6 +23,16 @@ fn bootstrap() {
     prost_build::Config::new()
         .compile_well_known_types()
         .btree_map(&["."])
+        .map_type(&[".google.protobuf.Struct.fields"], "crate::StructMap")
+        .derive(".", "::prost::CanonicalHash")
+        .check_encode(
+            ".google.protobuf.Timestamp",
+            "crate::validity::check_timestamp",
+        )
+        .check_encode(
+            ".google.protobuf.Duration",
+            "crate::validity::check_duration",
+        )
         .out_dir(tempdir.path())
         .compile_protos(
             &[
@@ -90,3 +100,121 @@ fn bootstrap() {
     assert_eq!(protobuf, bootstrapped_protobuf);
     assert_eq!(compiler, bootstrapped_compiler);
 }
+
+/// Test which bootstraps longrunning.rs, rpc.rs, api.rs and reflection.rs from the .proto
+/// definitions bundled with prost-types. Ensures that the checked-in compiled versions are up-to-date.
+#[test]
+fn bootstrap_longrunning() {
+    let prost_types = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("no parent")
+        .join("prost-types");
+    let protos = prost_types.join("protos");
+
+    let tempdir = tempfile::Builder::new()
+        .prefix("prost-types-bootstrap-longrunning")
+        .tempdir()
+        .unwrap();
+
+    prost_build::Config::new()
+        .compile_well_known_types()
+        .btree_map(&["."])
+        .extern_path(".google.protobuf", "crate")
+        .out_dir(tempdir.path())
+        .compile_protos(
+            &[
+                protos
+                    .join("google")
+                    .join("longrunning")
+                    .join("operations.proto"),
+                protos.join("google").join("api").join("annotations.proto"),
+                protos
+                    .join("grpc")
+                    .join("reflection")
+                    .join("v1")
+                    .join("reflection.proto"),
+            ],
+            &[protos],
+        )
+        .unwrap();
+
+    for (generated, checked_in) in &[
+        ("google.longrunning.rs", "longrunning.rs"),
+        ("google.rpc.rs", "rpc.rs"),
+        ("google.api.rs", "api.rs"),
+        ("grpc.reflection.v1.rs", "reflection.rs"),
+    ] {
+        let mut bootstrapped = String::new();
+        fs::File::open(tempdir.path().join(generated))
+            .unwrap()
+            .read_to_string(&mut bootstrapped)
+            .unwrap();
+
+        let path = prost_types.join("src").join(checked_in);
+        let mut current = String::new();
+        fs::File::open(&path)
+            .unwrap()
+            .read_to_string(&mut current)
+            .unwrap();
+
+        if current != bootstrapped {
+            fs::File::create(&path)
+                .unwrap()
+                .write_all(bootstrapped.as_bytes())
+                .unwrap();
+        }
+
+        assert_eq!(current, bootstrapped);
+    }
+}
+
+/// Test which bootstraps well_known_types.bin, the file descriptor set preloaded by
+/// `DescriptorPool::new`, from the .proto definitions in the Protobuf repo. Ensures that the
+/// checked-in version is up-to-date.
+#[test]
+fn bootstrap_well_known_types() {
+    let tempdir = tempfile::Builder::new()
+        .prefix("prost-types-bootstrap-well-known-types")
+        .tempdir()
+        .unwrap();
+    let bootstrapped_path = tempdir.path().join("well_known_types.bin");
+
+    let status = std::process::Command::new(prost_build::protoc())
+        .arg("--include_imports")
+        .arg("-I")
+        .arg(prost_build::protoc_include())
+        .arg("--descriptor_set_out")
+        .arg(&bootstrapped_path)
+        .args(&[
+            "google/protobuf/any.proto",
+            "google/protobuf/api.proto",
+            "google/protobuf/descriptor.proto",
+            "google/protobuf/duration.proto",
+            "google/protobuf/empty.proto",
+            "google/protobuf/field_mask.proto",
+            "google/protobuf/source_context.proto",
+            "google/protobuf/struct.proto",
+            "google/protobuf/timestamp.proto",
+            "google/protobuf/type.proto",
+            "google/protobuf/wrappers.proto",
+            "google/protobuf/compiler/plugin.proto",
+        ])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let bootstrapped = fs::read(&bootstrapped_path).unwrap();
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("no parent")
+        .join("prost-types")
+        .join("src")
+        .join("well_known_types.bin");
+    let current = fs::read(&path).unwrap();
+
+    if current != bootstrapped {
+        fs::write(&path, &bootstrapped).unwrap();
+    }
+
+    assert!(current == bootstrapped);
+}
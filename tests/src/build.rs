@@ -12,6 +12,8 @@ use std::env;
 use std::fs;
 use std::path::PathBuf;
 
+use prost_build::StringType;
+
 fn main() {
     env_logger::init();
 
@@ -95,6 +97,101 @@ fn main() {
         .compile_protos(&[src.join("proto3_presence.proto")], includes)
         .unwrap();
 
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .embed_file_descriptor_set()
+        .enable_type_names()
+        .message_pipeline(&[".embedded_descriptors.Event"])
+        .compile_protos(&[src.join("embedded_descriptors.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .builders(&[".builders"])
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile_protos(&[src.join("builders.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .boxed(&[
+            ".boxed.Container.configured",
+            ".boxed.Container.configured_choice",
+        ])
+        .box_large_messages(128)
+        .compile_protos(&[src.join("boxed.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .string_type(&[".string_types.Event"], StringType::Bytes)
+        .string_type(&[".string_types.Event.name"], StringType::Cow)
+        .string_type(&[".string_types.Event.tags"], StringType::SmolStr)
+        .string_type(
+            &[".string_types.Event.host"],
+            StringType::Custom("crate::string_types::Symbol".to_string()),
+        )
+        .compile_protos(&[src.join("string_types.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&[".map_types.Settings.sorted"])
+        .map_type(&[".map_types"], "crate::map_types::OrderedMap")
+        .compile_protos(&[src.join("map_types.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .presence_bits(&[".presence_bits"])
+        .builders(&[".presence_bits"])
+        .accessors(&[".presence_bits.Envelope"])
+        .compile_protos(&[src.join("presence_bits.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .enforce_required(&[".required_fields"])
+        .presence_bits(&[".required_fields.Record"])
+        .builders(&[".required_fields.Record"])
+        .compile_protos(&[src.join("required_fields.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .validate(&[".validation"])
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile_protos(&[src.join("validation.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .accessors(&[".accessors"])
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile_protos(&[src.join("accessors.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .iterative_recursion(&["."])
+        .compile_protos(&[src.join("iterative_recursion.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .enable_serde_json()
+        .timestamp_map_keys(&[".json_mapping.Buckets.by_start"])
+        .duration_map_keys(&[".json_mapping.Buckets.by_latency"])
+        .int64_numbers(&[".json_mapping.Counters"])
+        .enum_numbers(&[".json_mapping.Palette"])
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile_protos(&[src.join("json_mapping.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .enable_serde_json()
+        .proptest_arbitrary(&["."])
+        .protoc_arg("--experimental_allow_proto3_optional")
+        .compile_protos(&[src.join("arbitrary_messages.proto")], includes)
+        .unwrap();
+
     {
         let mut config = prost_build::Config::new();
         config.disable_comments(&["."]);
@@ -104,6 +201,15 @@ fn main() {
             .unwrap();
     }
 
+    {
+        let mut config = prost_build::Config::new();
+        config.text_code_blocks(&["."]);
+
+        config
+            .compile_protos(&[src.join("doctest_code_blocks.proto")], includes)
+            .unwrap();
+    }
+
     config
         .bytes(&["."])
         .compile_protos(&[src.join("well_known_types.proto")], includes)
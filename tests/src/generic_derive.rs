@@ -1,3 +1,10 @@
+use core::marker::PhantomData;
+
+use prost::alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use prost::Message;
+
+use crate::check_message;
+
 pub trait CustomType: prost::Message + Default {}
 
 impl CustomType for u64 {}
@@ -26,3 +33,84 @@ fn generic_enum() {
         GenericEnum::Number(_) => panic!("Not supposed to reach"),
     }
 }
+
+/// A generic message without bounds, which are added by the derive.
+#[derive(Clone, PartialEq, prost::Message)]
+struct Page<T> {
+    #[prost(message, repeated, tag = "1")]
+    items: Vec<T>,
+    #[prost(string, tag = "2")]
+    next_page_token: String,
+    #[prost(oneof = "Cursor", tags = "3, 4")]
+    cursor: Option<Cursor<T>>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum Cursor<T> {
+    #[prost(message, tag = "3")]
+    Last(T),
+    #[prost(uint64, tag = "4")]
+    Offset(u64),
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Item {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[test]
+fn generic_message() {
+    let item = |name: &str| Item {
+        name: name.to_owned(),
+    };
+    let page = Page {
+        items: vec![item("a"), item("b")],
+        next_page_token: "c".to_owned(),
+        cursor: Some(Cursor::Last(item("b"))),
+    };
+    check_message(&page);
+    check_message(&Page::<Item>::default());
+
+    // Only the variant whose type is not a type parameter can be constructed with `From`.
+    assert_eq!(Cursor::<Item>::from(2), Cursor::Offset(2));
+    let cursor = page.cursor.unwrap();
+    assert_eq!(cursor.as_last(), Some(&item("b")));
+    assert_eq!(cursor.into_offset(), None);
+}
+
+/// A generic message whose type parameters are only used by skipped fields, so they keep the
+/// bounds they are declared with.
+#[derive(Clone, PartialEq, prost::Message)]
+struct Memoized<C: Default + Send + Sync, K> {
+    #[prost(string, tag = "1")]
+    query: String,
+    #[prost(skip)]
+    cache: C,
+    #[prost(skip)]
+    kind: PhantomData<fn() -> K>,
+}
+
+/// Neither a message nor `Debug`.
+#[derive(Clone, Default, PartialEq)]
+struct Hits(u32);
+
+#[derive(Clone, PartialEq)]
+struct Users;
+
+#[test]
+fn skipped_generic_fields() {
+    let memoized = Memoized::<Hits, Users> {
+        query: "users".to_owned(),
+        cache: Hits(3),
+        kind: PhantomData,
+    };
+    let decoded = Memoized::<Hits, Users>::decode(&*memoized.encode_to_vec()).unwrap();
+    assert_eq!(decoded.query, "users");
+    assert!(decoded.cache == Hits(0));
+
+    check_message(&Memoized {
+        cache: Hits(0),
+        ..memoized
+    });
+}
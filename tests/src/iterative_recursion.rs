@@ -0,0 +1,102 @@
+use alloc::boxed::Box;
+use alloc::string::ToString;
+use alloc::vec;
+
+use prost::Message;
+
+mod iterative_recursion {
+    include!(concat!(env!("OUT_DIR"), "/iterative_recursion.rs"));
+}
+
+use self::iterative_recursion::{expr, Binary, Call, Expr, Leaf};
+
+const DEPTH: i64 = 100_000;
+
+fn leaf(id: i64) -> Expr {
+    let mut message = Expr::default();
+    message.id = id;
+    message.kind = Some(expr::Kind::Leaf(Leaf {
+        name: id.to_string(),
+    }));
+    message
+}
+
+/// Builds a tree which uses every kind of recursive field, with a long chain of each kind.
+fn tree(depth: i64) -> Expr {
+    let mut root = leaf(0);
+    for id in 1..depth {
+        let mut next = Expr::default();
+        next.id = id;
+        match id % 5 {
+            0 => next.next = Some(Box::new(root)),
+            1 => next.args = vec![leaf(-id), root],
+            2 => {
+                next.named.insert("child".to_string(), root);
+            }
+            3 => {
+                next.kind = Some(expr::Kind::Binary(Box::new(Binary {
+                    left: Some(Box::new(root)),
+                    right: Some(Box::new(leaf(-id))),
+                })))
+            }
+            _ => {
+                next.kind = Some(expr::Kind::Call(Call {
+                    name: "f".to_string(),
+                    args: vec![root],
+                }))
+            }
+        }
+        root = next;
+    }
+    root
+}
+
+#[test]
+fn test_generated_impls() {
+    let a = tree(10);
+    let b = a.clone();
+    assert_eq!(a, b);
+    assert_eq!(a.encode_to_vec(), b.encode_to_vec());
+
+    let mut c = a.clone();
+    c.id = -1;
+    assert_ne!(a, c);
+
+    let mut c = a.clone();
+    match c.kind {
+        Some(expr::Kind::Call(ref mut call)) => call.args[0].id = -1,
+        _ => panic!("unexpected kind"),
+    }
+    assert_ne!(a, c);
+
+    let mut c = a.clone();
+    c.kind = Some(expr::Kind::Literal(1));
+    assert_ne!(a, c);
+    assert_ne!(c, a);
+    assert_eq!(c.clone(), c);
+}
+
+#[test]
+fn test_deep_clone_eq_drop() {
+    let a = tree(DEPTH);
+    let b = a.clone();
+    assert!(a == b);
+
+    let c = tree(DEPTH - 1);
+    assert!(a != c);
+
+    drop(a);
+    drop(b);
+    drop(c);
+}
+
+#[test]
+fn test_deep_map() {
+    let mut root = leaf(0);
+    for id in 1..DEPTH {
+        let mut next = Expr::default();
+        next.named.insert(id.to_string(), root);
+        root = next;
+    }
+    assert!(root.clone() == root);
+}
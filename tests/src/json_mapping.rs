@@ -0,0 +1,509 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use core::fmt::Debug;
+
+#[cfg(feature = "std")]
+use prost_types::serde::mode::{self, Mode};
+use prost_types::{Duration, FieldMask, ListValue, Struct, Timestamp, Value};
+
+mod json_mapping {
+    include!(concat!(env!("OUT_DIR"), "/json_mapping.rs"));
+}
+
+use self::json_mapping::{
+    collections, counters, empties, palette, Aliased, Buckets, Collections, Color, Counters,
+    Empties, Legend, Optionals, Palette, Recursive, Scalars, WellKnown,
+};
+use prost::Message;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+fn check<T>(message: &T, json: &str)
+where
+    T: Debug + PartialEq + Serialize + DeserializeOwned,
+{
+    assert_eq!(serde_json::to_string(message).unwrap(), json);
+    assert_eq!(&serde_json::from_str::<T>(json).unwrap(), message);
+}
+
+#[test]
+fn test_scalars() {
+    check(&Scalars::default(), "{}");
+
+    let scalars = Scalars {
+        int32_value: -1,
+        int64_value: i64::MIN,
+        uint64_value: u64::MAX,
+        sint64_value: -2,
+        fixed64_value: 3,
+        sfixed64_value: -4,
+        float_value: 1.5,
+        double_value: f64::NEG_INFINITY,
+        bool_value: true,
+        string_value: "text".to_string(),
+        bytes_value: b"\x00\xff".to_vec(),
+        color: Color::Green as i32,
+        aliased: Aliased::One as i32,
+        renamed: "renamed".to_string(),
+    };
+    check(
+        &scalars,
+        r#"{"int32Value":-1,"int64Value":"-9223372036854775808","uint64Value":"18446744073709551615","sint64Value":"-2","fixed64Value":"3","sfixed64Value":"-4","floatValue":1.5,"doubleValue":"-Infinity","boolValue":true,"stringValue":"text","bytesValue":"AP8=","color":"COLOR_GREEN","aliased":"ALIASED_ONE","customName":"renamed"}"#,
+    );
+}
+
+#[test]
+fn test_lenient_input() {
+    let json = r#"{
+        "int32_value": 7,
+        "int64Value": 8,
+        "uint64_value": "1e2",
+        "color": 5,
+        "aliased": "ALIASED_UNO",
+        "renamed": "original name"
+    }"#;
+    let scalars: Scalars = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        scalars,
+        Scalars {
+            int32_value: 7,
+            int64_value: 8,
+            uint64_value: 100,
+            color: 5,
+            aliased: Aliased::One as i32,
+            renamed: "original name".to_string(),
+            ..Scalars::default()
+        }
+    );
+    assert_eq!(
+        serde_json::to_string(&scalars).unwrap(),
+        r#"{"int32Value":7,"int64Value":"8","uint64Value":"100","color":5,"aliased":"ALIASED_ONE","customName":"original name"}"#
+    );
+
+    let scalars: Scalars = serde_json::from_str(r#"{"color":"COLOR_BLUE"}"#).unwrap();
+    assert_eq!(scalars.color, Color::Unspecified as i32);
+
+    assert!(serde_json::from_str::<Scalars>(r#"{"int64Value":"1.5"}"#).is_err());
+}
+
+#[test]
+fn test_proto3_optional() {
+    check(&Optionals::default(), "{}");
+
+    // Zero values are emitted when present, unlike those of singular fields without presence.
+    let zeros = Optionals {
+        int32_value: Some(0),
+        int64_value: Some(0),
+        uint32_value: Some(0),
+        uint64_value: Some(0),
+        sint32_value: Some(0),
+        sint64_value: Some(0),
+        fixed32_value: Some(0),
+        fixed64_value: Some(0),
+        sfixed32_value: Some(0),
+        sfixed64_value: Some(0),
+        float_value: Some(0.0),
+        double_value: Some(0.0),
+        bool_value: Some(false),
+        string_value: Some(String::new()),
+        bytes_value: Some(vec![]),
+        color: Some(Color::Unspecified as i32),
+    };
+    check(
+        &zeros,
+        r#"{"int32Value":0,"int64Value":"0","uint32Value":0,"uint64Value":"0","sint32Value":0,"sint64Value":"0","fixed32Value":0,"fixed64Value":"0","sfixed32Value":0,"sfixed64Value":"0","floatValue":0.0,"doubleValue":0.0,"boolValue":false,"stringValue":"","bytesValue":"","color":"COLOR_UNSPECIFIED"}"#,
+    );
+    // Presence survives the binary encoding as well.
+    assert_eq!(Optionals::decode(&*zeros.encode_to_vec()).unwrap(), zeros);
+    assert_eq!(Optionals::default().encoded_len(), 0);
+
+    // `null` is read as an absent field.
+    let json = r#"{"int32Value":null,"uint32Value":null,"sint32Value":null,"fixed32Value":null,"sfixed32Value":null,"int64Value":null,"floatValue":null,"boolValue":null,"stringValue":null,"bytesValue":null,"color":null}"#;
+    assert_eq!(
+        serde_json::from_str::<Optionals>(json).unwrap(),
+        Optionals::default()
+    );
+
+    // 32-bit integers are read from strings and exponent notation, like 64-bit integers.
+    let json = r#"{"int32Value":"-7","uint32Value":"4294967295","sint32Value":1e2,"fixed32Value":"3","sfixed32Value":-2.0}"#;
+    assert_eq!(
+        serde_json::from_str::<Optionals>(json).unwrap(),
+        Optionals {
+            int32_value: Some(-7),
+            uint32_value: Some(u32::MAX),
+            sint32_value: Some(100),
+            fixed32_value: Some(3),
+            sfixed32_value: Some(-2),
+            ..Optionals::default()
+        }
+    );
+    for json in &[
+        r#"{"int32Value":"1.5"}"#,
+        r#"{"int32Value":2147483648}"#,
+        r#"{"uint32Value":-1}"#,
+        r#"{"fixed32Value":"x"}"#,
+    ] {
+        assert!(serde_json::from_str::<Optionals>(json).is_err(), "{}", json);
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_legacy_mode() {
+    let collections = Collections {
+        int64_values: vec![1, -1],
+        colors: vec![Color::Red as i32],
+        optional_int64: Some(2),
+        choice: Some(collections::Choice::ChoiceColor(Color::Green as i32)),
+        ..Collections::default()
+    };
+    let json = mode::with(Mode::Legacy, || {
+        serde_json::to_string(&collections).unwrap()
+    });
+    assert_eq!(
+        json,
+        r#"{"int64Values":[1,-1],"colors":[1],"optionalInt64":2,"choiceColor":2}"#
+    );
+    assert_eq!(
+        serde_json::from_str::<Collections>(&json).unwrap(),
+        collections
+    );
+    assert_eq!(
+        serde_json::to_string(&collections).unwrap(),
+        r#"{"int64Values":["1","-1"],"colors":["COLOR_RED"],"optionalInt64":"2","choiceColor":"COLOR_GREEN"}"#
+    );
+}
+
+#[test]
+fn test_collections() {
+    check(&Collections::default(), "{}");
+
+    let mut labels = BTreeMap::new();
+    labels.insert("key".to_string(), "value".to_string());
+    let mut by_id = BTreeMap::new();
+    by_id.insert(
+        -1,
+        Scalars {
+            int64_value: 5,
+            ..Scalars::default()
+        },
+    );
+    let collections = Collections {
+        int64_values: vec![1, -1],
+        double_values: vec![0.5, f64::INFINITY],
+        bytes_values: vec![b"a".to_vec(), vec![]],
+        colors: vec![Color::Red as i32, 7],
+        messages: vec![Scalars::default()],
+        labels,
+        by_id,
+        optional_int64: Some(0),
+        optional_color: Some(Color::Unspecified as i32),
+        choice: Some(collections::Choice::Number(9)),
+        other: Some(collections::Other::When(Timestamp::default())),
+    };
+    check(
+        &collections,
+        r#"{"int64Values":["1","-1"],"doubleValues":[0.5,"Infinity"],"bytesValues":["YQ==",""],"colors":["COLOR_RED",7],"messages":[{}],"labels":{"key":"value"},"byId":{"-1":{"int64Value":"5"}},"optionalInt64":"0","optionalColor":"COLOR_UNSPECIFIED","number":"9","when":"1970-01-01T00:00:00Z"}"#,
+    );
+
+    let collections = Collections {
+        choice: Some(collections::Choice::ChoiceColor(Color::Red as i32)),
+        ..Collections::default()
+    };
+    check(&collections, r#"{"choiceColor":"COLOR_RED"}"#);
+}
+
+#[test]
+fn test_oneofs() {
+    let json = r#"{"choice_color":"COLOR_GREEN","flag":true,"unknown":1}"#;
+    let collections: Collections = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        collections.choice,
+        Some(collections::Choice::ChoiceColor(Color::Green as i32))
+    );
+    assert_eq!(collections.other, Some(collections::Other::Flag(true)));
+
+    let message = Collections {
+        choice: Some(collections::Choice::Message(Scalars::default())),
+        ..Collections::default()
+    };
+    check(&message, r#"{"message":{}}"#);
+
+    for json in &[
+        r#"{"text":"a","number":"1"}"#,
+        r#"{"flag":true,"when":"1970-01-01T00:00:00Z"}"#,
+        r#"{"text":"a","text":"b"}"#,
+        r#"{"number":"a"}"#,
+        r#"{"when":1}"#,
+    ] {
+        assert!(
+            serde_json::from_str::<Collections>(json).is_err(),
+            "{}",
+            json
+        );
+    }
+    let error = serde_json::from_str::<Collections>(r#"{"choiceColor":1,"text":""}"#)
+        .unwrap_err()
+        .to_string();
+    assert!(
+        error.starts_with(
+            "multiple fields of oneof json_mapping.Collections.choice are set: `choiceColor` and `text`"
+        ),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_query_string() {
+    let collections: Collections = prost_types::serde::query::from_str(
+        "int64_values=1&int64_values=-1&colors=COLOR_RED&labels.key=a+b\
+         &byId.-1.int64Value=5&optionalColor=2&number=9",
+    )
+    .unwrap();
+
+    let mut labels = BTreeMap::new();
+    labels.insert("key".to_string(), "a b".to_string());
+    let mut by_id = BTreeMap::new();
+    by_id.insert(
+        -1,
+        Scalars {
+            int64_value: 5,
+            ..Scalars::default()
+        },
+    );
+    assert_eq!(
+        collections,
+        Collections {
+            int64_values: vec![1, -1],
+            colors: vec![Color::Red as i32],
+            labels,
+            by_id,
+            optional_color: Some(Color::Green as i32),
+            choice: Some(collections::Choice::Number(9)),
+            ..Collections::default()
+        }
+    );
+
+    for query in &["int64Values=a", "text=a&number=1", "byId.x.int64Value=1"] {
+        assert!(
+            prost_types::serde::query::from_str::<Collections>(query).is_err(),
+            "{}",
+            query
+        );
+    }
+}
+
+#[test]
+fn test_well_known_types() {
+    check(&WellKnown::default(), "{}");
+
+    let mut r#struct = Struct::default();
+    r#struct
+        .fields
+        .insert("key".to_string(), Value::from("value"));
+    let well_known = WellKnown {
+        timestamp: Some(Timestamp {
+            seconds: 1,
+            nanos: 500_000_000,
+        }),
+        duration: Some(Duration {
+            seconds: -1,
+            nanos: 0,
+        }),
+        empty: Some(()),
+        field_mask: Some(FieldMask {
+            paths: vec!["foo_bar".to_string(), "baz".to_string()],
+        }),
+        r#struct: Some(r#struct),
+        value: Some(Value::from(1.5)),
+        list: Some(ListValue {
+            values: vec![Value::from(true)],
+        }),
+        int64: Some(-1),
+        uint64: Some(0),
+        double: Some(f64::NAN),
+        bytes: Some(b"bytes".to_vec()),
+        string: Some(String::new()),
+        bool: Some(false),
+        timestamps: vec![Timestamp::default()],
+        durations: vec![Duration {
+            seconds: 0,
+            nanos: 1,
+        }],
+        null: 0,
+    };
+    let json = serde_json::to_string(&well_known).unwrap();
+    assert_eq!(
+        json,
+        r#"{"timestamp":"1970-01-01T00:00:01.500Z","duration":"-1s","empty":{},"fieldMask":"fooBar,baz","struct":{"key":"value"},"value":1.5,"list":[true],"int64":"-1","uint64":"0","double":"NaN","bytes":"Ynl0ZXM=","string":"","bool":false,"timestamps":["1970-01-01T00:00:00Z"],"durations":["0.000000001s"]}"#
+    );
+    let mut decoded: WellKnown = serde_json::from_str(&json).unwrap();
+    assert!(decoded.double.take().unwrap().is_nan());
+    assert_eq!(
+        decoded,
+        WellKnown {
+            double: None,
+            ..well_known
+        }
+    );
+}
+
+#[test]
+fn test_empty() {
+    check(&Empties::default(), "{}");
+
+    let mut set = BTreeMap::new();
+    set.insert(1, ());
+    let empties = Empties {
+        list: vec![(), ()],
+        set,
+        choice: Some(empties::Choice::None(())),
+    };
+    check(&empties, r#"{"list":[{},{}],"set":{"1":{}},"none":{}}"#);
+
+    let empties: Empties = serde_json::from_str(r#"{"list":[{"unknown":1}],"none":{}}"#).unwrap();
+    assert_eq!(empties.list, vec![()]);
+    assert_eq!(empties.choice, Some(empties::Choice::None(())));
+
+    for json in &[
+        r#"{"list":[null]}"#,
+        r#"{"set":{"1":null}}"#,
+        r#"{"none":[]}"#,
+        r#"{"none":{},"some":""}"#,
+    ] {
+        assert!(serde_json::from_str::<Empties>(json).is_err(), "{}", json);
+    }
+}
+
+#[test]
+fn test_recursive() {
+    let recursive = Recursive {
+        child: Some(Box::new(Recursive {
+            child: None,
+            name: "child".to_string(),
+        })),
+        name: String::new(),
+    };
+    check(&recursive, r#"{"child":{"name":"child"}}"#);
+}
+
+#[test]
+fn test_time_map_keys() {
+    let buckets: Buckets = serde_json::from_str(
+        r#"{"byStart":{"2021-03-01T11:00:00+01:00":3},"byLatency":{"0.5s":7,"2s":1}}"#,
+    )
+    .unwrap();
+    let mut by_start = BTreeMap::new();
+    by_start.insert("2021-03-01T10:00:00Z".to_string(), 3);
+    let mut by_latency = BTreeMap::new();
+    by_latency.insert("0.500s".to_string(), 7);
+    by_latency.insert("2s".to_string(), 1);
+    check(
+        &buckets,
+        r#"{"byStart":{"2021-03-01T10:00:00Z":3},"byLatency":{"0.500s":7,"2s":1}}"#,
+    );
+    assert_eq!(
+        buckets,
+        Buckets {
+            by_start,
+            by_latency,
+        }
+    );
+
+    assert!(serde_json::from_str::<Buckets>(r#"{"byStart":{"today":1}}"#).is_err());
+    assert!(serde_json::from_str::<Buckets>(r#"{"byLatency":{"1s":1,"1.0s":2}}"#).is_err());
+}
+
+#[test]
+fn test_int64_numbers() {
+    let counters = Counters {
+        total: i64::MIN,
+        limit: Some(u64::MAX),
+        deltas: vec![-1, 1 << 53 | 1],
+        peak: Some(7),
+        last: Some(counters::Last::At(8)),
+    };
+    check(
+        &counters,
+        r#"{"total":-9223372036854775808,"limit":18446744073709551615,"deltas":[-1,9007199254740993],"peak":7,"at":8}"#,
+    );
+
+    let decoded: Counters = serde_json::from_str(
+        r#"{"total":"-9223372036854775808","limit":"18446744073709551615","deltas":["-1",9007199254740993],"peak":"7","at":"8"}"#,
+    )
+    .unwrap();
+    assert_eq!(decoded, counters);
+}
+
+#[test]
+fn test_enum_map_values() {
+    check(&Legend::default(), "{}");
+
+    let mut legend = Legend::default();
+    legend
+        .by_label
+        .insert("grass".to_string(), Color::Green as i32);
+    legend.by_label.insert("unknown".to_string(), 7);
+    legend.by_id.insert(-1, Aliased::One as i32);
+    check(
+        &legend,
+        r#"{"byLabel":{"grass":"COLOR_GREEN","unknown":7},"byId":{"-1":"ALIASED_ONE"}}"#,
+    );
+
+    let decoded: Legend = serde_json::from_str(
+        r#"{"byLabel":{"grass":2,"unknown":"7"},"byId":{"-1":"ALIASED_UNO"}}"#,
+    )
+    .unwrap();
+    assert_eq!(decoded, legend);
+    assert!(serde_json::from_str::<Legend>(r#"{"byId":{"1":true}}"#).is_err());
+
+    #[cfg(feature = "std")]
+    {
+        let json = mode::with(Mode::Legacy, || serde_json::to_string(&legend).unwrap());
+        assert_eq!(
+            json,
+            r#"{"byLabel":{"grass":2,"unknown":7},"byId":{"-1":1}}"#
+        );
+    }
+}
+
+#[test]
+fn test_enum_numbers() {
+    let palette = Palette {
+        primary: Color::Green as i32,
+        accent: Some(Color::Unspecified as i32),
+        swatches: vec![Color::Red as i32, 7],
+        pick: Some(palette::Pick::Chosen(Color::Red as i32)),
+        by_name: vec![("sky".to_string(), Color::Green as i32)]
+            .into_iter()
+            .collect(),
+    };
+    check(
+        &palette,
+        r#"{"primary":2,"accent":0,"swatches":[1,7],"byName":{"sky":2},"chosen":1}"#,
+    );
+
+    let decoded: Palette = serde_json::from_str(
+        r#"{"primary":"COLOR_GREEN","accent":"COLOR_UNSPECIFIED","swatches":["COLOR_RED","7"],"chosen":"COLOR_RED","byName":{"sky":"COLOR_GREEN"}}"#,
+    )
+    .unwrap();
+    assert_eq!(decoded, palette);
+
+    // Fields which are not configured still render names, unless asked at runtime.
+    let optionals = Optionals {
+        color: Some(Color::Red as i32),
+        ..Optionals::default()
+    };
+    check(&optionals, r#"{"color":"COLOR_RED"}"#);
+    #[cfg(feature = "std")]
+    {
+        let json = prost_types::json::Options::new()
+            .enums_as_ints(true)
+            .to_string(&optionals)
+            .unwrap();
+        assert_eq!(json, r#"{"color":1}"#);
+    }
+}
@@ -18,9 +18,16 @@ cfg_if! {
         extern crate prost;
         extern crate prost_types;
         extern crate protobuf;
+        extern crate serde;
+        #[cfg(test)]
+        extern crate arbitrary;
+        #[cfg(test)]
+        extern crate proptest;
         #[cfg(test)]
         extern crate prost_build;
         #[cfg(test)]
+        extern crate serde_json;
+        #[cfg(test)]
         extern crate tempfile;
     }
 }
@@ -30,6 +37,8 @@ pub mod no_root_packages;
 pub mod packages;
 pub mod unittest;
 
+#[cfg(all(test, feature = "std"))]
+mod arbitrary_messages;
 #[cfg(test)]
 mod bootstrap;
 #[cfg(test)]
@@ -39,10 +48,16 @@ mod deprecated_field;
 #[cfg(test)]
 mod generic_derive;
 #[cfg(test)]
+mod iterative_recursion;
+#[cfg(test)]
+mod json_mapping;
+#[cfg(test)]
 mod message_encoding;
 #[cfg(test)]
 mod no_unused_results;
 #[cfg(test)]
+mod tuple_derive;
+#[cfg(test)]
 mod well_known_types;
 
 pub mod foo {
@@ -52,13 +67,128 @@ pub mod foo {
 }
 
 pub mod nesting {
-    include!(concat!(env!("OUT_DIR"), "/nesting.rs"));
+    ::prost::include_proto!("nesting");
 }
 
 pub mod recursive_oneof {
     include!(concat!(env!("OUT_DIR"), "/recursive_oneof.rs"));
 }
 
+pub mod builders {
+    include!(concat!(env!("OUT_DIR"), "/builders.rs"));
+}
+
+pub mod accessors {
+    include!(concat!(env!("OUT_DIR"), "/accessors.rs"));
+}
+
+pub mod boxed {
+    include!(concat!(env!("OUT_DIR"), "/boxed.rs"));
+}
+
+pub mod string_types {
+    use alloc::boxed::Box;
+    use core::ops::Deref;
+
+    include!(concat!(env!("OUT_DIR"), "/string_types.rs"));
+
+    /// A custom string type, standing in for an interned string.
+    #[derive(Clone, Debug, Default, PartialEq)]
+    pub struct Symbol(Box<str>);
+
+    impl Deref for Symbol {
+        type Target = str;
+
+        fn deref(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl prost::StringAdapter for Symbol {
+        fn copy_from_str(value: &str) -> Symbol {
+            Symbol(value.into())
+        }
+
+        fn check_encode(&self) -> Result<(), prost::InvalidValueError> {
+            if self.0.is_empty() {
+                return Err(prost::InvalidValueError::new("empty symbol"));
+            }
+            Ok(())
+        }
+    }
+}
+
+pub mod map_types {
+    use alloc::vec::Vec;
+    use core::borrow::Borrow;
+    use core::hash::Hash;
+
+    include!(concat!(env!("OUT_DIR"), "/map_types.rs"));
+
+    /// A map preserving insertion order, backed by a `Vec`.
+    #[derive(Clone, Debug, PartialEq)]
+    pub struct OrderedMap<K, V>(pub Vec<(K, V)>);
+
+    impl<K, V> Default for OrderedMap<K, V> {
+        fn default() -> OrderedMap<K, V> {
+            OrderedMap(Vec::new())
+        }
+    }
+
+    impl<K, V> prost::ProstMap<K, V> for OrderedMap<K, V>
+    where
+        K: PartialEq,
+    {
+        fn len(&self) -> usize {
+            self.0.len()
+        }
+
+        fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: ?Sized + Hash + Ord,
+        {
+            self.0
+                .iter()
+                .find(|entry| entry.0.borrow() == key)
+                .map(|entry| &entry.1)
+        }
+
+        fn insert(&mut self, key: K, value: V) -> Option<V> {
+            if let Some(entry) = self.0.iter_mut().find(|entry| entry.0 == key) {
+                return Some(core::mem::replace(&mut entry.1, value));
+            }
+            self.0.push((key, value));
+            None
+        }
+
+        fn clear(&mut self) {
+            self.0.clear()
+        }
+    }
+
+    impl<'a, K, V> IntoIterator for &'a OrderedMap<K, V> {
+        type Item = (&'a K, &'a V);
+        type IntoIter = core::iter::Map<core::slice::Iter<'a, (K, V)>, fn(&(K, V)) -> (&K, &V)>;
+
+        fn into_iter(self) -> Self::IntoIter {
+            self.0.iter().map(|entry| (&entry.0, &entry.1))
+        }
+    }
+}
+
+pub mod presence_bits {
+    include!(concat!(env!("OUT_DIR"), "/presence_bits.rs"));
+}
+
+pub mod required_fields {
+    include!(concat!(env!("OUT_DIR"), "/required_fields.rs"));
+}
+
+pub mod validation {
+    include!(concat!(env!("OUT_DIR"), "/validation.rs"));
+}
+
 /// This tests the custom attributes support by abusing docs.
 ///
 /// Docs really are full-blown attributes. So we use them to ensure we can place them on everything
@@ -102,10 +232,20 @@ pub mod invalid {
     }
 }
 
+pub mod doctest {
+    pub mod code_blocks {
+        include!(concat!(env!("OUT_DIR"), "/doctest.code_blocks.rs"));
+    }
+}
+
 pub mod default_string_escape {
     include!(concat!(env!("OUT_DIR"), "/default_string_escape.rs"));
 }
 
+pub mod embedded_descriptors {
+    include!(concat!(env!("OUT_DIR"), "/embedded_descriptors.rs"));
+}
+
 use alloc::vec::Vec;
 
 use anyhow::anyhow;
@@ -472,6 +612,27 @@ mod tests {
         assert!(build_and_roundtrip(101).is_err());
     }
 
+    #[test]
+    fn test_deep_nesting_recursion_limit() {
+        use crate::nesting::C;
+
+        let mut c = C::default();
+        for _ in 0..1000 {
+            let mut next = C::default();
+            next.r.push(c);
+            c = next;
+        }
+
+        let buf = c.encode_to_vec();
+        assert!(C::decode(&*buf).is_err());
+        assert_eq!(C::decode_with_recursion_limit(&*buf, 1000).unwrap(), c);
+        assert!(C::decode_with_recursion_limit(&*buf, 999).is_err());
+
+        let mut merged = C::default();
+        merged.merge_with_recursion_limit(&*buf, 1000).unwrap();
+        assert_eq!(merged, c);
+    }
+
     #[test]
     fn test_deep_nesting_map() {
         fn build_and_roundtrip(depth: usize) -> Result<(), prost::DecodeError> {
@@ -515,6 +676,22 @@ mod tests {
         <() as Message>::decode(&buf[..]).err().unwrap();
     }
 
+    #[test]
+    fn test_skip_deep_groups() {
+        // Unknown groups are skipped without recursion, so deeply nested groups can be skipped
+        // when the recursion limit allows them.
+        let mut buf = vec![b'C'; 1 << 20];
+        buf.extend(vec![b'D'; 1 << 20]);
+        <() as Message>::decode(&buf[..]).err().unwrap();
+        <() as Message>::decode_with_recursion_limit(&buf[..], u32::MAX).unwrap();
+
+        // Mismatched end group tags are still detected.
+        buf[(1 << 20) + 1] = b'L';
+        <() as Message>::decode_with_recursion_limit(&buf[..], u32::MAX)
+            .err()
+            .unwrap();
+    }
+
     #[test]
     fn test_default_enum() {
         let msg = default_enum_value::Test::default();
@@ -529,6 +706,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_enum_aliases() {
+        use core::convert::TryFrom;
+        use default_enum_value::Priority;
+        use prost::{OpenEnum, UnknownEnumValue};
+
+        assert_eq!(Priority::Urgent, Priority::High);
+        assert_eq!(Priority::try_from(2), Ok(Priority::High));
+        assert_eq!(Priority::try_from(3), Err(UnknownEnumValue(3)));
+
+        assert_eq!(OpenEnum::from(2), OpenEnum::Known(Priority::High));
+        let unknown = OpenEnum::<Priority>::from(3);
+        assert_eq!(unknown, OpenEnum::Unknown(3));
+        assert_eq!(i32::from(unknown), 3);
+    }
+
+    #[test]
+    fn test_enum_names() {
+        use default_enum_value::Priority;
+
+        assert_eq!(Priority::VALUES, &[Priority::Low, Priority::High]);
+        assert_eq!(
+            Priority::NAMES,
+            &[
+                ("PRIORITY_LOW", 1),
+                ("PRIORITY_HIGH", 2),
+                ("PRIORITY_URGENT", 2)
+            ]
+        );
+        assert_eq!(Priority::High.as_str_name(), "PRIORITY_HIGH");
+        assert_eq!(
+            Priority::from_str_name("PRIORITY_URGENT"),
+            Some(Priority::High)
+        );
+        assert_eq!(Priority::from_str_name("High"), None);
+    }
+
     #[test]
     fn test_default_string_escape() {
         let msg = default_string_escape::Person::default();
@@ -628,4 +842,746 @@ mod tests {
             include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
         prost_types::FileDescriptorSet::decode(&file_descriptor_set_bytes[..]).unwrap();
     }
+
+    #[test]
+    fn test_embedded_file_descriptor_set() {
+        use embedded_descriptors::{event, Event};
+
+        let file_descriptor_set =
+            prost_types::FileDescriptorSet::decode(embedded_descriptors::file_descriptor_set())
+                .unwrap();
+        assert!(file_descriptor_set
+            .file
+            .iter()
+            .all(|file| file.source_code_info.is_none()));
+
+        let pool =
+            prost_types::DescriptorPool::from_file_descriptor_set(&file_descriptor_set).unwrap();
+        assert_eq!(Event::DESCRIPTOR_FULL_NAME, "embedded_descriptors.Event");
+        assert_eq!(
+            event::Detail::DESCRIPTOR_FULL_NAME,
+            "embedded_descriptors.Event.Detail"
+        );
+        assert!(pool
+            .get_message_by_name(Event::DESCRIPTOR_FULL_NAME)
+            .is_some());
+        assert!(pool
+            .get_message_by_name(event::Detail::DESCRIPTOR_FULL_NAME)
+            .is_some());
+        assert!(pool
+            .get_message_by_name("google.protobuf.Timestamp")
+            .is_some());
+    }
+
+    #[test]
+    fn test_type_names() {
+        use embedded_descriptors::{event, Event};
+        use prost::Name;
+        use prost_types::{Any, DescriptorPool, TypeRegistry};
+
+        assert_eq!(Event::NAME, "Event");
+        assert_eq!(Event::PACKAGE, "embedded_descriptors");
+        assert_eq!(event::Detail::NAME, "Event.Detail");
+        assert_eq!(
+            event::Detail::type_url(),
+            "type.googleapis.com/embedded_descriptors.Event.Detail"
+        );
+
+        let msg = Event {
+            name: "launch".to_string(),
+            ..Default::default()
+        };
+        let any = Any::pack(&msg);
+        assert!(any.is::<Event>());
+        assert!(!any.is::<event::Detail>());
+        assert_eq!(any.unpack::<Event>().unwrap(), msg);
+
+        let file_descriptor_set =
+            prost_types::FileDescriptorSet::decode(embedded_descriptors::file_descriptor_set())
+                .unwrap();
+        let mut registry = TypeRegistry::new();
+        registry.descriptor_pool(
+            DescriptorPool::from_file_descriptor_set(&file_descriptor_set).unwrap(),
+        );
+        let unpacked = registry.unpack_dynamic(&any).unwrap();
+        assert_eq!(unpacked.encode_to_vec(), msg.encode_to_vec());
+        registry.register::<Event>();
+        assert!(registry
+            .unpack(&any)
+            .unwrap()
+            .downcast_ref::<Event>()
+            .is_some());
+    }
+
+    #[test]
+    fn test_message_pipeline() {
+        use embedded_descriptors::Event;
+        use prost_types::pipeline::{Pipeline, Pipelined};
+
+        assert_eq!(Event::FULL_NAME, "embedded_descriptors.Event");
+        let pipeline = Pipeline::<Event, ()>::new();
+        assert_eq!(alloc::format!("{:?}", pipeline), "Pipeline { hooks: [] }");
+    }
+
+    #[test]
+    fn test_builders() {
+        use crate::builders::{order, Item, Order};
+
+        let order = Order::builder()
+            .id(42u32)
+            .customer("ACME")
+            .status(order::Status::Open)
+            .items(vec![Item::builder().sku("123").build()])
+            .quantities(vec![("123".to_string(), 2)])
+            .priority(1u8)
+            .replaces(Order::builder().id(41u32).build())
+            .email("orders@acme.example")
+            .build_(true)
+            .build();
+
+        assert_eq!(order.id, 42);
+        assert_eq!(order.customer, "ACME");
+        assert_eq!(order.status(), order::Status::Open);
+        assert_eq!(order.items[0].sku, "123");
+        assert_eq!(order.quantities["123"], 2);
+        assert_eq!(order.priority, Some(1));
+        assert_eq!(order.replaces.as_ref().map(|order| order.id), Some(41));
+        assert_eq!(
+            order.contact,
+            Some(order::Contact::Email("orders@acme.example".to_string()))
+        );
+        assert!(order.build);
+
+        let referred = Order::builder().referrer(order.clone()).build();
+        assert_eq!(
+            referred.contact,
+            Some(order::Contact::Referrer(Box::new(order)))
+        );
+        assert_eq!(Order::builder().build(), Order::default());
+    }
+
+    #[test]
+    fn test_boxed() {
+        use crate::boxed::{container, Container, Large, Small};
+
+        let container = Container {
+            large: Some(Box::new(Large::default())),
+            small: Some(Small::default()),
+            configured: Some(Box::new(Small::default())),
+            choice: Some(container::Choice::LargeChoice(Box::new(Large::default()))),
+        };
+        assert_eq!(
+            Container::decode(&*container.encode_to_vec()).unwrap(),
+            container
+        );
+
+        let _ = container::Choice::SmallChoice(Small::default());
+        let _ = container::Choice::ConfiguredChoice(Box::new(Small::default()));
+        assert!(core::mem::size_of::<container::Choice>() < core::mem::size_of::<Large>());
+    }
+
+    #[test]
+    fn test_oneof_accessors() {
+        use crate::boxed::{container::Choice, Container, Large, Small};
+
+        let mut container = Container {
+            choice: Some(Choice::from(Small { value: 1 })),
+            ..Container::default()
+        };
+        let choice = container.choice.as_mut().unwrap();
+        assert_eq!(choice.as_small_choice(), Some(&Small { value: 1 }));
+        assert_eq!(choice.as_large_choice(), None);
+        choice.as_small_choice_mut().unwrap().value = 2;
+        assert_eq!(
+            container.choice.clone().unwrap().into_small_choice(),
+            Some(Small { value: 2 })
+        );
+
+        // Boxed variants are constructed from their box.
+        container.choice = Some(Box::new(Small { value: 3 }).into());
+        let choice = container.choice.unwrap();
+        assert_eq!(choice.as_configured_choice().unwrap().value, 3);
+        assert_eq!(choice.into_large_choice(), None);
+        let _ = Choice::from(Box::new(Large::default()));
+    }
+
+    #[test]
+    fn test_map_types() {
+        use prost::ProstMap;
+
+        use crate::map_types::{Level, Settings};
+
+        let mut settings = Settings::default();
+        for key in ["zulu", "alpha", "mike"].iter() {
+            settings.labels.insert(key.to_string(), key.to_uppercase());
+        }
+        settings.insert_levels("net".to_string(), Level::Debug);
+        settings.insert_levels("db".to_string(), Level::Info);
+        settings.children.insert(2, Settings::default());
+        settings.children.insert(1, Settings::default());
+        settings.sorted.insert("b".to_string(), "2".to_string());
+        settings.sorted.insert("a".to_string(), "1".to_string());
+
+        let decoded = Settings::decode(&*settings.encode_to_vec()).unwrap();
+        assert_eq!(decoded, settings);
+        // Entries are encoded and decoded in insertion order.
+        let keys = decoded
+            .labels
+            .0
+            .iter()
+            .map(|entry| &*entry.0)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["zulu", "alpha", "mike"]);
+        assert_eq!(decoded.get_levels("db"), Some(Level::Info));
+        assert_eq!(decoded.children.0[0].0, 2);
+        assert_eq!(
+            alloc::format!("{:?}", decoded.levels),
+            r#"OrderedMap([("net", 1), ("db", 2)])"#
+        );
+        assert!(alloc::format!("{:?}", decoded).contains(r#"levels: {"net": Debug, "db": Info}"#));
+
+        let mut cleared = decoded;
+        cleared.clear();
+        assert_eq!(cleared, Settings::default());
+        assert!(cleared.labels.is_empty());
+        assert_eq!(cleared.sorted, Default::default());
+    }
+
+    #[test]
+    fn test_presence_bits() {
+        use crate::presence_bits::{Empty, Level, Sample};
+
+        let sample = Sample::default();
+        assert!(!sample.has_count());
+        assert_eq!(sample.name(), "unnamed");
+        assert_eq!(sample.level(), Level::Low);
+        assert_eq!(sample.payload(), b"");
+        assert!(sample._presence.is_empty());
+        assert_eq!(
+            alloc::format!("{:?}", sample)
+                .split(", extra_12")
+                .next()
+                .unwrap(),
+            "Sample { count: None, name: None, payload: None, level: None, ratio: None, \
+             enabled: None, id: 0, values: [], parent: None"
+        );
+
+        let mut sample = Sample::builder()
+            .id(1u64)
+            .count(0i32)
+            .name("first")
+            .level(Level::High)
+            .build();
+        sample.set_enabled(false);
+        sample.set_last(7);
+        sample.set_extra_70(-3);
+        assert!(sample.has_count() && sample.has_enabled() && sample.has_last());
+        assert!(!sample.has_ratio() && !sample.has_extra_12());
+        assert_eq!(sample._presence.len(), 6);
+        assert!(alloc::format!("{:?}", sample).contains("count: Some(0), name: Some(\"first\")"));
+
+        // Fields which are set are encoded, even if they hold the default value.
+        let decoded = Sample::decode(&*sample.encode_to_vec()).unwrap();
+        assert_eq!(decoded, sample);
+        assert_eq!(decoded.count(), 0);
+        assert!(decoded.has_count() && !decoded.has_payload());
+        assert_eq!(decoded.level(), Level::High);
+        assert_eq!(decoded.extra_70(), -3);
+        assert_eq!(decoded.last(), 7);
+
+        // A value assigned without its presence bit is not encoded.
+        let mut unset = Sample::default();
+        unset.ratio = 0.5;
+        assert_eq!(
+            Sample::decode(&*unset.encode_to_vec()).unwrap(),
+            Sample::default()
+        );
+
+        let mut cleared = decoded.clone();
+        cleared.clear_name();
+        assert!(!cleared.has_name());
+        assert_eq!(cleared.name, "unnamed");
+        assert_eq!(
+            cleared.encoded_len(),
+            decoded.encoded_len() - decoded.name.len() - 2
+        );
+        cleared.clear();
+        assert_eq!(cleared, Sample::default());
+
+        // The presence bits take two words, rather than an `Option` per field.
+        assert_eq!(core::mem::size_of_val(&sample._presence), 16);
+        let _ = Empty { values: Vec::new() };
+    }
+
+    #[test]
+    fn test_presence_bits_messages() {
+        use crate::presence_bits::{Empty, Envelope, Sample};
+
+        let envelope = Envelope::default();
+        assert!(!envelope.has_header() && !envelope.has_body());
+        assert_eq!(envelope.header(), None);
+        assert_eq!(envelope.encoded_len(), 0);
+        assert_eq!(
+            alloc::format!("{:?}", envelope),
+            "Envelope { header: None, body: None }"
+        );
+
+        let mut envelope = Envelope::builder().header(Empty::default()).build();
+        assert!(envelope.has_header() && !envelope.has_body());
+        assert_eq!(envelope.header(), Some(&Empty::default()));
+        // An empty message which is set is encoded.
+        assert_eq!(envelope.encode_to_vec(), [0x0a, 0x00]);
+        assert_eq!(Envelope::decode(&[0x0a, 0x00][..]).unwrap(), envelope);
+
+        envelope.body_mut().set_name("body".to_string());
+        envelope.body_mut().values = vec![1, 2, 3];
+        let decoded = Envelope::decode(&*envelope.encode_to_vec()).unwrap();
+        assert_eq!(decoded, envelope);
+        assert_eq!(decoded.body().map(Sample::name), Some("body"));
+
+        envelope.clear_header();
+        assert!(!envelope.has_header());
+        assert_eq!(envelope.header(), None);
+
+        // Clearing keeps the allocations of the messages held by optional fields, which merging
+        // reuses.
+        let encoded = decoded.encode_to_vec();
+        let mut reused = decoded;
+        let values = reused.body.values.as_ptr();
+        reused.clear();
+        assert_eq!(reused, Envelope::default());
+        assert!(reused.body.values.capacity() >= 3);
+        reused.merge(&*encoded).unwrap();
+        assert_eq!(reused.body.values.as_ptr(), values);
+        assert_eq!(reused.body.values, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_required_fields() {
+        use prost::encoding::WireType;
+
+        use crate::required_fields::{record, Detail, Kind, Record};
+
+        fn detail(code: i32) -> Detail {
+            let mut detail = Detail::default();
+            detail.set_code(code);
+            detail
+        }
+        fn audit() -> record::Audit {
+            let mut audit = record::Audit::default();
+            audit.set_author("admin".to_string());
+            audit
+        }
+        fn error(record: &Record) -> alloc::string::String {
+            record.check_required().unwrap_err().to_string()
+        }
+
+        let empty = Record::default();
+        assert!(!empty.has_id() && !empty.has_detail());
+        assert_eq!(empty.name(), "anonymous");
+        assert_eq!(
+            error(&empty),
+            "failed to decode Protobuf message: Record.id: missing required field"
+        );
+        // Plain decoding fills in missing required fields with their default value.
+        assert_eq!(Record::decode(&[][..]).unwrap(), empty);
+        assert!(Record::decode_required(&[][..]).is_err());
+
+        let builder = Record::builder()
+            .id(0u64)
+            .name("first")
+            .kind(Kind::User)
+            .detail(detail(1))
+            .audit(audit());
+        let record = builder.clone().build();
+        assert!(record.has_id() && record.has_detail() && record.has_audit());
+        assert!(!record.has_count());
+        record.check_required().unwrap();
+
+        let decoded = Record::decode_required(&*record.encode_to_vec()).unwrap();
+        assert_eq!(decoded, record);
+        assert_eq!(decoded.id(), 0);
+        assert_eq!(decoded.kind(), Kind::User);
+        assert_eq!(decoded.audit.author(), "admin");
+
+        // Required fields are encoded even if unset, and set when decoded.
+        let mut unset = record.clone();
+        unset.clear_id();
+        assert!(!unset.has_id());
+        assert_eq!(unset.encoded_len(), record.encoded_len());
+        assert!(Record::decode_required(&*unset.encode_to_vec())
+            .unwrap()
+            .has_id());
+
+        // Missing fields of nested messages are reported with the path leading to them.
+        let nested = builder.extra(Detail::default()).build();
+        assert_eq!(
+            error(&nested),
+            "failed to decode Protobuf message: Detail.code: Record.extra: missing required field"
+        );
+        // An empty `extra` message, missing its `code`.
+        let mut buf = record.encode_to_vec();
+        buf.extend_from_slice(&[0x2a, 0x00]);
+        let decoded = Record::decode(&*buf).unwrap();
+        assert!(decoded.has_extra());
+        assert_eq!(decoded.extra, Detail::default());
+        assert!(Record::decode_required(&*buf).is_err());
+        let decode_error = Record::decode_required(&*buf).unwrap_err();
+        assert_eq!(decode_error.field_path(), "extra.code");
+        assert_eq!(decode_error.field_numbers(), vec![5, 1]);
+        assert_eq!(decode_error.offset(), None);
+
+        // A length-delimited `code` in the `extra` message.
+        let len = buf.len();
+        buf.truncate(len - 2);
+        buf.extend_from_slice(&[0x2a, 0x02, 0x0a, 0x00]);
+        let decode_error = Record::decode(&*buf).unwrap_err();
+        assert_eq!(
+            decode_error.to_string(),
+            "failed to decode Protobuf message: Detail.code: Record.extra: \
+             invalid wire type: LengthDelimited (expected Varint)"
+        );
+        assert_eq!(decode_error.field_path(), "extra.code");
+        assert_eq!(decode_error.field_numbers(), vec![5, 1]);
+        assert_eq!(decode_error.offset(), Some(len + 1));
+        assert_eq!(decode_error.expected_wire_type(), Some(WireType::Varint));
+        assert_eq!(
+            decode_error.actual_wire_type(),
+            Some(WireType::LengthDelimited)
+        );
+
+        let mut nested = record.clone();
+        nested.history = vec![detail(2), Detail::default()];
+        assert!(error(&nested).contains("Record.history"));
+
+        let mut nested = record.clone();
+        nested
+            .by_name
+            .insert("missing".to_string(), Detail::default());
+        assert!(error(&nested).contains("Record.by_name"));
+
+        let mut nested = record.clone();
+        nested.source = Some(record::Source::Origin(Detail::default()));
+        assert!(error(&nested).contains("Record.source"));
+        nested.source = Some(record::Source::Host("localhost".to_string()));
+        nested.check_required().unwrap();
+
+        let mut nested = record.clone();
+        nested.audit = record::Audit::default();
+        assert!(error(&nested).contains("Audit.author: Record.audit"));
+
+        let mut cleared = record;
+        cleared.clear();
+        assert_eq!(cleared, Record::default());
+        assert!(!cleared.has_detail());
+    }
+
+    #[test]
+    fn test_validation() {
+        use prost::Validate;
+
+        use crate::validation::{user, Address, Status, User};
+
+        fn address(city: &str) -> Address {
+            Address {
+                city: city.to_string(),
+                country: "FR".to_string(),
+            }
+        }
+        fn error(user: &User) -> (alloc::string::String, alloc::string::String) {
+            let error = user.validate().unwrap_err();
+            (error.field_path(), error.description().to_string())
+        }
+        fn check<F: FnOnce(&mut User)>(valid: &User, f: F, path: &str, description: &str) {
+            let mut user = valid.clone();
+            f(&mut user);
+            assert_eq!(
+                error(&user),
+                (path.to_string(), description.to_string()),
+                "{:?}",
+                user
+            );
+        }
+
+        let valid = User {
+            id: 1,
+            email: "user@example.com".to_string(),
+            age: 0,
+            score: 0.5,
+            status: Status::Active as i32,
+            token: b"tk-secret".to_vec(),
+            address: Some(address("Paris")),
+            billing: Some(address("")),
+            tags: vec!["a".to_string(), "b".to_string()],
+            previous: vec![address("Lyon")],
+            contacts: Default::default(),
+            nickname: None,
+            priority: None,
+            active: true,
+            name: "User".to_string(),
+            ranks: vec![1, 2],
+            contact: Some(user::Contact::Mail(address("Nice"))),
+        };
+        assert_eq!(valid.validate(), Ok(()));
+
+        check(&valid, |u| u.id = 0, "id", "value must be greater than 0");
+        check(
+            &valid,
+            |u| u.email = "user.example.com".to_string(),
+            "email",
+            "value must contain substring \"@\"",
+        );
+        // `ignore_empty` only skips the default value.
+        check(
+            &valid,
+            |u| u.age = 17,
+            "age",
+            "value must be greater than or equal to 18",
+        );
+        check(
+            &valid,
+            |u| u.score = f64::NAN,
+            "score",
+            "value must be finite",
+        );
+        check(
+            &valid,
+            |u| u.score = 1.5,
+            "score",
+            "value must be less than or equal to 1",
+        );
+        check(
+            &valid,
+            |u| u.status = 7,
+            "status",
+            "value must be one of the defined enum values",
+        );
+        check(
+            &valid,
+            |u| u.status = 0,
+            "status",
+            "value must not be in list [0]",
+        );
+        check(
+            &valid,
+            |u| u.token = b"tk".to_vec(),
+            "token",
+            "value length must be at least 4 bytes",
+        );
+        check(&valid, |u| u.address = None, "address", "value is required");
+        check(
+            &valid,
+            |u| u.address = Some(address("")),
+            "address.city",
+            "value length must be at least 1 characters",
+        );
+        check(
+            &valid,
+            |u| u.address.as_mut().unwrap().country = "DE".to_string(),
+            "address.country",
+            "value must be in list [\"FR\", \"US\"]",
+        );
+        check(
+            &valid,
+            |u| u.tags.push("a".to_string()),
+            "tags",
+            "repeated value must contain unique items",
+        );
+        check(
+            &valid,
+            |u| u.tags = vec!["".to_string()],
+            "tags",
+            "value length must be at least 1 characters",
+        );
+        check(
+            &valid,
+            |u| u.previous.push(address("")),
+            "previous.city",
+            "value length must be at least 1 characters",
+        );
+        check(
+            &valid,
+            |u| {
+                for city in &["A", "B", "C"] {
+                    u.contacts.insert(city.to_string(), address(city));
+                }
+            },
+            "contacts",
+            "value must contain no more than 2 pair(s)",
+        );
+        check(
+            &valid,
+            |u| {
+                u.contacts.insert("x".to_string(), address(""));
+            },
+            "contacts.city",
+            "value length must be at least 1 characters",
+        );
+        check(
+            &valid,
+            |u| u.nickname = Some("nick".to_string()),
+            "nickname",
+            "value must have prefix \"@\"",
+        );
+        check(
+            &valid,
+            |u| u.priority = Some(10),
+            "priority",
+            "value must be less than or equal to 9",
+        );
+        check(
+            &valid,
+            |u| u.active = false,
+            "active",
+            "value must equal true",
+        );
+        check(&valid, |u| u.name.clear(), "name", "value is required");
+        check(
+            &valid,
+            |u| u.ranks.push(0),
+            "ranks",
+            "value must be greater than 0",
+        );
+        check(&valid, |u| u.contact = None, "contact", "value is required");
+        check(
+            &valid,
+            |u| u.contact = Some(user::Contact::Phone("0123".to_string())),
+            "phone",
+            "value must have prefix \"+\"",
+        );
+        check(
+            &valid,
+            |u| u.contact = Some(user::Contact::Mail(address(""))),
+            "mail.city",
+            "value length must be at least 1 characters",
+        );
+
+        let mut user = valid.clone();
+        user.nickname = Some("@nick".to_string());
+        user.priority = Some(9);
+        user.contact = Some(user::Contact::Phone("+33".to_string()));
+        assert_eq!(user.validate(), Ok(()));
+        assert_eq!(error(&User { id: 0, ..user }).0, "id",);
+    }
+
+    #[test]
+    fn test_string_types() {
+        use alloc::borrow::Cow;
+
+        use bytes::Bytes;
+        use prost::{ByteStr, StringAdapter};
+
+        use crate::string_types::{event, Event, Symbol};
+
+        let event = Event::default();
+        assert_eq!(event.name(), "unknown");
+        assert_eq!(event.source, "");
+        assert_eq!(event.host(), "");
+
+        let event = Event {
+            name: Some(Cow::Borrowed("start")),
+            source: ByteStr::from_static("scheduler"),
+            tags: vec!["a".into(), "b".into()],
+            host: Some(Symbol::copy_from_str("localhost")),
+            labels: [("zone".to_string(), "east".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            detail: Some(event::Detail::Message(ByteStr::from_static("started"))),
+        };
+        let encoded = Bytes::from(event.encode_to_vec());
+        let decoded = Event::decode(encoded.clone()).unwrap();
+        assert_eq!(decoded, event);
+        assert_eq!(decoded.name(), "start");
+        assert_eq!(decoded.host(), "localhost");
+
+        // Strings held in a `ByteStr` share the buffer they were decoded from.
+        let source = decoded.source.as_bytes().as_ptr() as usize;
+        let buffer = encoded.as_ptr() as usize;
+        assert!(source >= buffer && source < buffer + encoded.len());
+
+        // Invalid UTF-8 is rejected by every string type.
+        let mut invalid = encoded.to_vec();
+        let at = invalid.windows(9).position(|w| w == b"scheduler").unwrap();
+        invalid[at] = 0xff;
+        assert!(Event::decode(&*invalid).is_err());
+
+        // `encode_checked` fails like `encode` if the buffer has insufficient capacity.
+        let mut buf = Vec::new();
+        event.encode_checked(&mut buf).unwrap();
+        assert_eq!(buf, encoded);
+        let mut buf = [0u8; 4];
+        match event.encode_checked(&mut &mut buf[..]).unwrap_err() {
+            prost::CheckedEncodeError::Capacity(error) => {
+                assert_eq!(error.required_capacity(), encoded.len());
+                assert_eq!(error.remaining(), 4);
+            }
+            error => panic!("unexpected error: {}", error),
+        }
+
+        // Custom string types can reject their values when encoding with `encode_checked`.
+        let empty = Event {
+            host: Some(Symbol::copy_from_str("")),
+            ..event
+        };
+        let mut buf = Vec::new();
+        let error = match empty.encode_checked(&mut buf).unwrap_err() {
+            prost::CheckedEncodeError::InvalidValue(error) => error,
+            error => panic!("unexpected error: {}", error),
+        };
+        assert_eq!(error.description(), "empty symbol");
+        assert_eq!(error.field_path(), "host");
+        assert_eq!(
+            error.to_string(),
+            "failed to encode Protobuf message: Event.host: empty symbol"
+        );
+        assert_eq!(empty.check_encode(), Err(error));
+        assert!(buf.is_empty());
+        assert!(!empty.encode_to_vec().is_empty());
+    }
+
+    #[test]
+    fn test_accessors() {
+        use crate::accessors::{node, Node};
+
+        let mut node = Node::default();
+        assert_eq!(node.name(), "");
+        assert_eq!(node.weight(), 0);
+        assert!(!node.has_limit());
+        assert_eq!(node.limit(), 0);
+        assert_eq!(node.parent(), None);
+        assert_eq!(node.data(), b"");
+        assert_eq!(node.link(), None);
+
+        node.set_name("root");
+        node.set_weight(3u8);
+        node.set_limit(10);
+        node.parent_mut().set_name("parent");
+        node.children.push(Node::default());
+        node.set_data(b"abc".to_vec());
+        assert_eq!(node.name(), "root");
+        assert_eq!(node.weight(), 3);
+        assert!(node.has_limit());
+        assert_eq!(node.limit(), 10);
+        assert!(node.has_parent());
+        assert_eq!(node.parent().map(Node::name), Some("parent"));
+        assert!(node.has_data());
+        assert_eq!(node.data(), b"abc");
+
+        node.set_link(Node::default());
+        assert!(!node.has_data());
+        assert_eq!(node.data(), b"");
+        assert_eq!(node.link(), Some(&Node::default()));
+        node.clear_data();
+        assert!(node.has_link());
+        node.clear_link();
+        assert_eq!(node.value, None::<node::Value>);
+
+        node.clear_name();
+        node.clear_weight();
+        node.clear_limit();
+        node.clear_parent();
+        node.clear_children();
+        assert_eq!(node, Node::default());
+    }
 }
@@ -1,5 +1,5 @@
 use bytes::Bytes;
-use prost::alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
+use prost::alloc::{borrow::ToOwned, format, string::String, vec, vec::Vec};
 use prost::{Enumeration, Message, Oneof};
 
 use crate::check_message;
@@ -275,6 +275,46 @@ pub struct TagsQualified {
     pub eleven: ::alloc::collections::BTreeMap<String, String>,
 }
 
+/// A prost message with a field which is not part of the message.
+#[derive(Clone, PartialEq, Message)]
+pub struct SkippedFields {
+    #[prost(string)]
+    pub text: String,
+    #[prost(skip)]
+    pub word_count: Option<usize>,
+    #[prost(int32)]
+    pub two: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct WithoutSkippedFields {
+    #[prost(string, tag = "1")]
+    pub text: String,
+    #[prost(int32, tag = "2")]
+    pub two: i32,
+}
+
+#[test]
+fn check_skipped_fields() {
+    let mut skipped_fields = SkippedFields {
+        text: "one two".to_owned(),
+        word_count: Some(2),
+        two: 2,
+    };
+    let without_skipped_fields = WithoutSkippedFields {
+        text: "one two".to_owned(),
+        two: 2,
+    };
+    check_serialize_equivalent(&skipped_fields, &without_skipped_fields);
+
+    let decoded = SkippedFields::decode(skipped_fields.encode_to_vec().as_slice()).unwrap();
+    assert_eq!(decoded.word_count, None);
+    assert!(!format!("{:?}", decoded).contains("word_count"));
+
+    skipped_fields.clear();
+    assert_eq!(skipped_fields, SkippedFields::default());
+}
+
 /// A prost message with default value.
 #[derive(Clone, PartialEq, Message)]
 pub struct DefaultValues {
@@ -313,10 +353,29 @@ fn check_default_values() {
     assert_eq!(&default.bytes_buf.as_ref(), b"foo\0bar");
     assert_eq!(default.enumeration, BasicEnumeration::ONE as i32);
     assert_eq!(default.optional_enumeration, None);
-    assert_eq!(&default.repeated_enumeration, &[]);
+    assert!(default.repeated_enumeration.is_empty());
     assert_eq!(0, default.encoded_len());
 }
 
+#[test]
+fn check_decoded_default_values() {
+    // Fields missing from the encoded message keep their default values.
+    let decoded = DefaultValues::decode(&[][..]).unwrap();
+    assert_eq!(decoded, DefaultValues::default());
+
+    // Fields set to zero values differ from their default values, so they are encoded.
+    let zero = DefaultValues {
+        int32: 0,
+        string: String::new(),
+        bytes_vec: Vec::new(),
+        bytes_buf: Bytes::new(),
+        enumeration: BasicEnumeration::ZERO as i32,
+        ..DefaultValues::default()
+    };
+    assert_ne!(0, zero.encoded_len());
+    check_message(&zero);
+}
+
 /// A protobuf enum.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Copy, Debug, PartialEq, Enumeration)]
@@ -366,6 +425,65 @@ pub struct Basic {
     pub bytes_map: ::std::collections::HashMap<String, Vec<u8>>,
 }
 
+#[test]
+#[cfg(feature = "std")]
+fn check_clear_keeps_capacity() {
+    let mut message = Compound::default();
+    message.required_message.string = "a".repeat(64);
+    message.required_message.bools = vec![true; 64];
+    message
+        .required_message
+        .string_map
+        .insert("key".to_owned(), "value".to_owned());
+    message.repeated_message = vec![Basic::default(); 8];
+    let encoded = message.encode_to_vec();
+
+    message.clear();
+    assert_eq!(message, Compound::default());
+    assert!(message.required_message.string.capacity() >= 64);
+    assert!(message.required_message.bools.capacity() >= 64);
+    assert!(message.required_message.string_map.capacity() >= 1);
+    assert!(message.repeated_message.capacity() >= 8);
+
+    // Merging into the cleared message reuses the buffers of its fields.
+    let string = message.required_message.string.as_ptr();
+    let bools = message.required_message.bools.as_ptr();
+    let repeated_message = message.repeated_message.as_ptr();
+    message.merge(&encoded[..]).unwrap();
+    assert_eq!(message.required_message.string.as_ptr(), string);
+    assert_eq!(message.required_message.bools.as_ptr(), bools);
+    assert_eq!(message.repeated_message.as_ptr(), repeated_message);
+}
+
+#[test]
+fn check_is_default() {
+    let mut message = Compound::default();
+    assert!(message.is_default());
+    // The required message field is encoded, even though it holds its default value.
+    assert_ne!(0, message.encoded_len());
+
+    message.required_message.oneof = Some(BasicOneof::Int(0));
+    assert!(!message.is_default());
+    message.clear();
+    assert!(message.is_default());
+
+    message
+        .required_message
+        .enumeration_btree_map
+        .insert(1, BasicEnumeration::ONE as i32);
+    assert!(!message.is_default());
+    message.clear();
+    assert!(message.is_default());
+
+    // Fields are compared to their declared default values.
+    let mut default_values = DefaultValues::default();
+    assert!(default_values.is_default());
+    default_values.int32 = 0;
+    assert!(!default_values.is_default());
+    default_values.clear();
+    assert!(default_values.is_default());
+}
+
 #[derive(Clone, PartialEq, Message)]
 pub struct Compound {
     #[prost(message, optional, tag = "1")]
@@ -392,3 +510,29 @@ pub enum BasicOneof {
     #[prost(string, tag = "9")]
     String(String),
 }
+
+/// A message with fields mapped to custom scalar types, which are checked by `check_encode`.
+#[derive(Clone, PartialEq, Message)]
+pub struct CustomScalars {
+    #[prost(uint32 = "char", tag = "1")]
+    pub glyph: char,
+    #[prost(uint32 = "nonzero", optional, tag = "2")]
+    pub id: Option<core::num::NonZeroU32>,
+    #[prost(uint64 = "nonzero", repeated, tag = "3")]
+    pub ids: Vec<core::num::NonZeroU64>,
+}
+
+#[test]
+fn check_custom_scalars() {
+    let message = CustomScalars {
+        glyph: 'é',
+        id: core::num::NonZeroU32::new(7),
+        ids: vec![core::num::NonZeroU64::new(u64::MAX).unwrap()],
+    };
+    assert_eq!(message.check_encode(), Ok(()));
+
+    let mut buf = Vec::new();
+    message.encode_checked(&mut buf).unwrap();
+    assert_eq!(buf, message.encode_to_vec());
+    assert_eq!(CustomScalars::decode(&*buf).unwrap(), message);
+}
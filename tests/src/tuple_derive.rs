@@ -0,0 +1,71 @@
+use prost::alloc::{borrow::ToOwned, format, string::String};
+use prost::Message;
+
+use crate::check_message;
+
+#[derive(Clone, PartialEq, Message)]
+struct Named {
+    #[prost(uint64, tag = "1")]
+    id: u64,
+    #[prost(string, tag = "2")]
+    name: String,
+}
+
+/// A tuple struct with the fields of `Named`.
+#[derive(Clone, PartialEq, Message)]
+struct Tuple(
+    #[prost(uint64, tag = "1")] u64,
+    #[prost(string, tag = "2")] String,
+);
+
+#[derive(Clone, PartialEq, Message)]
+struct UserId(#[prost(uint64, tag = "1")] u64);
+
+/// A newtype encoded as the `UserId` it wraps.
+#[derive(Clone, PartialEq, Message)]
+#[prost(transparent)]
+struct AccountId(UserId);
+
+#[derive(Clone, PartialEq, Message)]
+#[prost(transparent)]
+struct Wrapper {
+    named: Named,
+}
+
+#[test]
+fn tuple_struct() {
+    let tuple = Tuple(7, "seven".to_owned());
+    check_message(&tuple);
+    let named = Named {
+        id: 7,
+        name: "seven".to_owned(),
+    };
+    assert_eq!(tuple.encode_to_vec(), named.encode_to_vec());
+    assert_eq!(format!("{:?}", tuple), "Tuple(7, \"seven\")");
+
+    let mut user = UserId(42);
+    check_message(&user);
+    user.clear();
+    assert_eq!(user, UserId::default());
+}
+
+#[test]
+fn transparent_newtype() {
+    let account = AccountId(UserId(42));
+    check_message(&account);
+    assert_eq!(account.encode_to_vec(), UserId(42).encode_to_vec());
+    assert_eq!(
+        AccountId::decode(&*UserId(42).encode_to_vec()).unwrap(),
+        account
+    );
+    assert_eq!(format!("{:?}", account), "AccountId(UserId(42))");
+
+    let wrapper = Wrapper {
+        named: Named {
+            id: 1,
+            name: "one".to_owned(),
+        },
+    };
+    check_message(&wrapper);
+    assert_eq!(wrapper.encode_to_vec(), wrapper.named.encode_to_vec());
+}